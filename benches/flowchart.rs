@@ -0,0 +1,43 @@
+//! Rendering time of large flowcharts
+//!
+//! Run with `cargo bench`. The budget is for a 500-node flowchart to render
+//! in well under a second, to ASCII as to SVG.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use m2svg::{parse_mermaid, render_mermaid_ascii, render_parsed_svg};
+
+/// A flowchart of `nodes` nodes: a tree where each node hangs off one of the
+/// few nodes before it, with a cross edge on every fifth node
+fn flowchart(nodes: usize) -> String {
+    let mut text = String::from("graph TD\n");
+    for i in 1..nodes {
+        let parent = (i - 1).saturating_sub(i * 7 % 8);
+        text.push_str(&format!("  N{parent}[Node {parent}] --> N{i}[Node {i}]\n"));
+        if i % 5 == 0 && i >= 3 {
+            text.push_str(&format!("  N{} -.-> N{i}\n", i - 3));
+        }
+    }
+    text
+}
+
+fn bench_flowcharts(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flowchart");
+    group.sample_size(10);
+    for nodes in [100, 500, 1000] {
+        let text = flowchart(nodes);
+        group.bench_with_input(BenchmarkId::new("parse", nodes), &text, |b, text| {
+            b.iter(|| parse_mermaid(text).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("ascii", nodes), &text, |b, text| {
+            b.iter(|| render_mermaid_ascii(text, None).unwrap())
+        });
+        let parsed = parse_mermaid(&text).unwrap();
+        group.bench_with_input(BenchmarkId::new("svg", nodes), &parsed, |b, parsed| {
+            b.iter(|| render_parsed_svg(parsed, None))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_flowcharts);
+criterion_main!(benches);
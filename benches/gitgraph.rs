@@ -0,0 +1,41 @@
+//! Rendering time of long git graphs
+//!
+//! Run with `cargo bench --bench gitgraph`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use m2svg::{parse_mermaid, render_mermaid_ascii, render_parsed_svg};
+
+/// A git graph of `commits` commits over a handful of feature branches, each
+/// merged back into main after a few commits
+fn gitgraph(commits: usize) -> String {
+    let mut text = String::from("gitGraph\n");
+    for i in 0..commits {
+        if i % 10 == 3 {
+            text.push_str(&format!("  branch feature{i}\n"));
+        }
+        text.push_str(&format!("  commit id: \"c{i}\"\n"));
+        if i % 10 == 7 {
+            text.push_str(&format!("  checkout main\n  merge feature{}\n", i - 4));
+        }
+    }
+    text
+}
+
+fn bench_gitgraphs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gitgraph");
+    group.sample_size(10);
+    for commits in [100, 1000] {
+        let text = gitgraph(commits);
+        group.bench_with_input(BenchmarkId::new("ascii", commits), &text, |b, text| {
+            b.iter(|| render_mermaid_ascii(text, None).unwrap())
+        });
+        let parsed = parse_mermaid(&text).unwrap();
+        group.bench_with_input(BenchmarkId::new("svg", commits), &parsed, |b, parsed| {
+            b.iter(|| render_parsed_svg(parsed, None))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_gitgraphs);
+criterion_main!(benches);
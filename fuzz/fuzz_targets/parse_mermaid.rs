@@ -0,0 +1,16 @@
+//! `parse_mermaid` must return, with a diagram or an error, on any input.
+//!
+//! Run with `cargo +nightly fuzz run parse_mermaid` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use m2svg::{parse_mermaid_with_mode, ParseMode};
+
+fuzz_target!(|text: &str| {
+    let lenient = parse_mermaid_with_mode(text, ParseMode::Lenient);
+    // A diagram that parses strictly has nothing to warn about
+    if parse_mermaid_with_mode(text, ParseMode::Strict).is_ok() {
+        assert!(lenient.is_ok_and(|(_, warnings)| warnings.is_empty()));
+    }
+});
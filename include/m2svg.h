@@ -0,0 +1,62 @@
+#ifndef M2SVG_H
+#define M2SVG_H
+
+/* Generated with cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Render a Mermaid diagram to ASCII/Unicode text.
+ *
+ * Returns NULL on error, with the message in `*error_out`.
+ *
+ * # Safety
+ * `input` must be a valid NUL-terminated string; `opts_json` and
+ * `error_out` must each be NULL or valid.
+ */
+char *m2svg_render_ascii(const char *input, const char *opts_json, char **error_out);
+
+/**
+ * Render a Mermaid diagram to SVG.
+ *
+ * Returns NULL on error, with the message in `*error_out`.
+ *
+ * # Safety
+ * `input` must be a valid NUL-terminated string; `opts_json` and
+ * `error_out` must each be NULL or valid.
+ */
+char *m2svg_render_svg(const char *input, const char *opts_json, char **error_out);
+
+/**
+ * Lay out a Mermaid flowchart and return its node positions and edge routes
+ * as JSON. Takes the same options as `m2svg_render_svg`.
+ *
+ * Returns NULL on error, with the message in `*error_out`.
+ *
+ * # Safety
+ * `input` must be a valid NUL-terminated string; `opts_json` and
+ * `error_out` must each be NULL or valid.
+ */
+char *m2svg_layout_json(const char *input, const char *opts_json, char **error_out);
+
+/**
+ * Release a string returned by this library. NULL is ignored.
+ *
+ * # Safety
+ * `s` must be NULL or a string from an `m2svg_*` function, not yet freed.
+ */
+void m2svg_free_string(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* M2SVG_H */
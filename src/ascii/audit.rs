@@ -0,0 +1,120 @@
+//! Junction audit for Unicode output
+//!
+//! Scans a rendered diagram for box-drawing characters that cannot meet the
+//! way they do: a line running into a corner or tee that has no arm facing it
+//! (`─┌`), or a line stopping one cell short of another line (`─ │`). Golden
+//! files only catch changes; this catches output that was wrong to begin with.
+
+use std::fmt;
+
+use super::canvas::text_cells;
+
+const N: u8 = 1;
+const E: u8 = 2;
+const S: u8 = 4;
+const W: u8 = 8;
+
+/// Directions a box-drawing character has arms in, or `None` for any other
+/// character. Double lines (`║`, `╟`) are ER cardinality marks, not lines.
+fn arms(c: char) -> Option<u8> {
+    Some(match c {
+        '─' | '╌' => E | W,
+        '│' | '┊' => N | S,
+        '┌' | '╭' => E | S,
+        '┐' | '╮' => S | W,
+        '└' | '╰' => N | E,
+        '┘' | '╯' => N | W,
+        '├' => N | E | S,
+        '┤' => N | S | W,
+        '┬' => E | S | W,
+        '┴' => N | E | W,
+        '┼' => N | E | S | W,
+        _ => return None,
+    })
+}
+
+/// A suspicious spot in the output, at column `x` of line `y` (both from 0)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JunctionIssue {
+    pub x: usize,
+    pub y: usize,
+    pub message: String,
+}
+
+impl fmt::Display for JunctionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.y + 1, self.x + 1, self.message)
+    }
+}
+
+/// Find impossible adjacencies between box-drawing characters in `text`.
+///
+/// # Example
+/// ```rust
+/// use m2svg::ascii::audit::audit_junctions;
+///
+/// assert!(audit_junctions("┌──┐\n└──┘").is_empty());
+/// let issues = audit_junctions("──┌");
+/// assert_eq!(issues[0].to_string(), "1:2: '─' runs into '┌' at 1:3, which has no arm facing it");
+/// ```
+pub fn audit_junctions(text: &str) -> Vec<JunctionIssue> {
+    // One cell per column, so wide labels don't shift the lines after them
+    let grid: Vec<Vec<char>> = text.lines().map(text_cells).collect();
+    let at = |x: isize, y: isize| -> Option<char> {
+        let row = grid.get(usize::try_from(y).ok()?)?;
+        row.get(usize::try_from(x).ok()?).copied()
+    };
+    // (arm, opposite arm, dx, dy) per direction
+    let steps = [(E, W, 1, 0), (S, N, 0, 1), (W, E, -1, 0), (N, S, 0, -1)];
+
+    let mut issues = Vec::new();
+    for (y, row) in grid.iter().enumerate() {
+        for (x, &c) in row.iter().enumerate() {
+            let Some(a) = arms(c) else { continue };
+            let (x, y) = (x as isize, y as isize);
+            for (arm, opposite, dx, dy) in steps {
+                let Some(next) = at(x + dx, y + dy) else {
+                    continue;
+                };
+                let issue = match arms(next) {
+                    // A line ending against the side of a straight line is a
+                    // deliberate butt joint (messages on lifelines, relationships
+                    // on class boxes)
+                    Some(b) if b == E | W || b == N | S => continue,
+                    // A line running into a neighbour with no arm facing it. Each
+                    // pair is reported once, from the side whose arm dangles.
+                    Some(b) if a & arm != 0 && b & opposite == 0 => format!(
+                        "'{}' runs into '{}' at {}:{}, which has no arm facing it",
+                        c,
+                        next,
+                        y + dy + 1,
+                        x + dx + 1
+                    ),
+                    // A line stopping one cell short of another line
+                    None if next == ' ' && a & arm != 0 => {
+                        let Some(beyond) = at(x + 2 * dx, y + 2 * dy) else {
+                            continue;
+                        };
+                        if arms(beyond).is_none() {
+                            continue;
+                        }
+                        format!(
+                            "'{}' stops one cell short of '{}' at {}:{}",
+                            c,
+                            beyond,
+                            y + 2 * dy + 1,
+                            x + 2 * dx + 1
+                        )
+                    }
+                    _ => continue,
+                };
+                issues.push(JunctionIssue {
+                    x: x as usize,
+                    y: y as usize,
+                    message: issue,
+                });
+            }
+        }
+    }
+    issues
+}
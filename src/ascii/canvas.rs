@@ -1,9 +1,147 @@
 //! 2D text canvas operations
+//!
+//! Each cell is one terminal column. Text is laid out by display width: a
+//! double-width character (CJK, most emoji) takes its cell plus a
+//! [`WIDE_FILLER`] cell to its right, and a grapheme cluster of several chars
+//! (a letter with combining accents, an emoji sequence) is kept whole in one
+//! cell as a stand-in char that [`canvas_to_string`] expands again.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 use super::types::{Canvas, DrawingCoord};
 
-/// Create a blank canvas filled with spaces
+/// Placeholder in the cell right of a double-width character
+pub const WIDE_FILLER: char = '\0';
+
+/// Stand-ins for multi-char grapheme clusters are taken from Supplementary
+/// Private Use Area-A, numbered in the order the clusters were first drawn
+const CLUSTER_BASE: u32 = 0xF0000;
+
+thread_local! {
+    static CLUSTERS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    /// Most cells a canvas may have on this thread (0 = no limit)
+    static CELL_LIMIT: Cell<usize> = const { Cell::new(0) };
+    /// Most cells a canvas asked for beyond [`CELL_LIMIT`]
+    static CELLS_REFUSED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Run `f` with canvases limited to `max_cells` cells (0 = no limit), and
+/// return its result along with the most cells a canvas asked for beyond the
+/// limit, if any did.
+///
+/// Canvases are not grown past the limit: what would be drawn outside them is
+/// dropped, so the result of `f` is only meaningful when no canvas was refused.
+///
+/// # Example
+/// ```rust
+/// use m2svg::ascii::canvas::{mk_canvas, with_cell_limit};
+///
+/// let (_, refused) = with_cell_limit(100, || mk_canvas(9, 9));
+/// assert_eq!(refused, None);
+/// let (_, refused) = with_cell_limit(100, || mk_canvas(10, 10));
+/// assert_eq!(refused, Some(121));
+/// ```
+pub fn with_cell_limit<R>(max_cells: usize, f: impl FnOnce() -> R) -> (R, Option<usize>) {
+    let limit = CELL_LIMIT.replace(max_cells);
+    let refused = CELLS_REFUSED.replace(0);
+    let result = f();
+    CELL_LIMIT.set(limit);
+    let cells = CELLS_REFUSED.replace(refused);
+    (result, (cells > 0).then_some(cells))
+}
+
+/// Whether a canvas reaching (max_x, max_y) is within the cell limit, noting
+/// its size if not
+fn within_cell_limit(max_x: usize, max_y: usize) -> bool {
+    let limit = CELL_LIMIT.get();
+    let cells = max_x
+        .saturating_add(1)
+        .saturating_mul(max_y.saturating_add(1));
+    if limit == 0 || cells <= limit {
+        return true;
+    }
+    CELLS_REFUSED.set(CELLS_REFUSED.get().max(cells));
+    false
+}
+
+/// Display width of `text` in terminal columns: double-width characters count
+/// two, combining marks and other zero-width characters none.
+///
+/// # Example
+/// ```rust
+/// use m2svg::ascii::canvas::text_width;
+///
+/// assert_eq!(text_width("abc"), 3);
+/// assert_eq!(text_width("日本"), 4);
+/// assert_eq!(text_width("e\u{301}"), 1);
+/// ```
+pub fn text_width(text: &str) -> usize {
+    text.width()
+}
+
+/// Display width of the widest line of `text`
+pub fn block_width(text: &str) -> usize {
+    text.lines().map(text_width).max().unwrap_or(0)
+}
+
+/// The longest prefix of `text` at most `width` columns wide, cut between
+/// grapheme clusters
+pub fn truncate_to_width(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, cluster) in text.grapheme_indices(true) {
+        used += cluster.width();
+        if used > width {
+            return &text[..i];
+        }
+    }
+    text
+}
+
+/// The char stored in a cell for one grapheme cluster
+fn cluster_char(cluster: &str) -> char {
+    let mut chars = cluster.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if !(CLUSTER_BASE..CLUSTER_BASE + 0x10000).contains(&(c as u32)) {
+            return c;
+        }
+    }
+    CLUSTERS.with(|clusters| {
+        let mut clusters = clusters.borrow_mut();
+        let index = match clusters.iter().position(|c| c == cluster) {
+            Some(index) => index,
+            None => {
+                clusters.push(cluster.to_string());
+                clusters.len() - 1
+            }
+        };
+        char::from_u32(CLUSTER_BASE + index as u32).unwrap_or('\u{FFFD}')
+    })
+}
+
+/// The text a cell char stands for
+fn cell_text(c: char) -> Option<String> {
+    let index = (c as u32).checked_sub(CLUSTER_BASE)? as usize;
+    CLUSTERS.with(|clusters| clusters.borrow().get(index).cloned())
+}
+
+/// Columns the cell char `c` takes when printed
+fn cell_width(c: char) -> usize {
+    match cell_text(c) {
+        Some(text) => text_width(&text),
+        None => text_width(c.encode_utf8(&mut [0; 4])),
+    }
+}
+
+/// Create a blank canvas filled with spaces. Beyond the cell limit (see
+/// [`with_cell_limit`]) the canvas is a single cell.
 pub fn mk_canvas(width: usize, height: usize) -> Canvas {
+    if !within_cell_limit(width, height) {
+        return vec![vec![' ']];
+    }
     let mut canvas = Vec::with_capacity(width + 1);
     for _ in 0..=width {
         canvas.push(vec![' '; height + 1]);
@@ -28,11 +166,15 @@ pub fn copy_canvas(source: &Canvas) -> Canvas {
     mk_canvas(max_x, max_y)
 }
 
-/// Grow the canvas to fit at least (new_x, new_y)
+/// Grow the canvas to fit at least (new_x, new_y), unless that is beyond the
+/// cell limit (see [`with_cell_limit`])
 pub fn increase_size(canvas: &mut Canvas, new_x: usize, new_y: usize) {
     let (curr_x, curr_y) = get_canvas_size(canvas);
     let target_x = new_x.max(curr_x);
     let target_y = new_y.max(curr_y);
+    if !within_cell_limit(target_x, target_y) {
+        return;
+    }
 
     // Extend existing columns
     for col in canvas.iter_mut() {
@@ -52,7 +194,13 @@ pub fn set_char(canvas: &mut Canvas, x: i32, y: i32, c: char) {
     }
     let x = x as usize;
     let y = y as usize;
-    increase_size(canvas, x, y);
+    // Growing touches every column, so only do it when the cell is outside
+    if x >= canvas.len() || y >= canvas[x].len() {
+        increase_size(canvas, x, y);
+        if x >= canvas.len() || y >= canvas[x].len() {
+            return;
+        }
+    }
     canvas[x][y] = c;
 }
 
@@ -153,7 +301,17 @@ pub fn merge_junctions(c1: char, c2: char) -> char {
         ('┬', '┘') | ('┘', '┬') => '┼', // ┬ (LEFT,RIGHT,DOWN) + ┘ (LEFT,UP) → ┼
         ('┴', '┌') | ('┌', '┴') => '┼', // ┴ (LEFT,RIGHT,UP) + ┌ (RIGHT,DOWN) → ┼
         ('┴', '┐') | ('┐', '┴') => '┼', // ┴ (LEFT,RIGHT,UP) + ┐ (LEFT,DOWN) → ┼
-        _ => c2,                        // Default to the new character
+        // A line along the bar of a T-junction keeps the junction
+        ('─', '┬') | ('┬', '─') => '┬',
+        ('─', '┴') | ('┴', '─') => '┴',
+        // So does a corner with arms the junction already has
+        ('├', '┌') | ('┌', '├') | ('├', '└') | ('└', '├') => '├',
+        ('┤', '┐') | ('┐', '┤') | ('┤', '┘') | ('┘', '┤') => '┤',
+        ('┬', '┌') | ('┌', '┬') | ('┬', '┐') | ('┐', '┬') => '┬',
+        ('┴', '└') | ('└', '┴') | ('┴', '┘') | ('┘', '┴') => '┴',
+        // A crossing already has every arm
+        ('┼', _) | (_, '┼') => '┼',
+        _ => c2, // Default to the new character
     }
 }
 
@@ -164,8 +322,15 @@ pub fn merge_canvases(
     use_ascii: bool,
     overlays: &[&Canvas],
 ) -> Canvas {
-    let (mut max_x, mut max_y) = get_canvas_size(base);
+    let mut merged = base.clone();
+    merge_into(&mut merged, offset, use_ascii, overlays);
+    merged
+}
 
+/// [`merge_canvases`] in place: draw `overlays` onto `base` at `offset`,
+/// without copying the base
+pub fn merge_into(base: &mut Canvas, offset: DrawingCoord, use_ascii: bool, overlays: &[&Canvas]) {
+    let (mut max_x, mut max_y) = get_canvas_size(base);
     for overlay in overlays {
         let (o_x, o_y) = get_canvas_size(overlay);
         if offset.x >= 0 && offset.y >= 0 {
@@ -173,52 +338,134 @@ pub fn merge_canvases(
             max_y = max_y.max(o_y.saturating_add(offset.y as usize));
         }
     }
+    increase_size(base, max_x, max_y);
 
-    let mut merged = mk_canvas(max_x, max_y);
-
-    // Copy base
-    for x in 0..=max_x {
-        for y in 0..=max_y {
-            if x < base.len() && y < base[x].len() {
-                merged[x][y] = base[x][y];
+    for overlay in overlays {
+        for (x, column) in overlay.iter().enumerate() {
+            for (y, &c) in column.iter().enumerate() {
+                if c != ' ' {
+                    merge_cell(base, x as i32 + offset.x, y as i32 + offset.y, c, use_ascii);
+                }
             }
         }
     }
+}
 
-    // Apply overlays
-    for overlay in overlays {
-        let (o_x, o_y) = get_canvas_size(overlay);
-        for x in 0..=o_x {
-            for y in 0..=o_y {
-                let c = overlay[x][y];
-                if c != ' ' {
-                    let mx_i32 = x as i32 + offset.x;
-                    let my_i32 = y as i32 + offset.y;
-                    if mx_i32 >= 0 && my_i32 >= 0 {
-                        let mx = mx_i32 as usize;
-                        let my = my_i32 as usize;
-                        // Grow canvas if needed
-                        if mx >= merged.len() || my >= merged.first().map(|v| v.len()).unwrap_or(0)
-                        {
-                            increase_size(&mut merged, mx, my);
-                        }
-                        if mx < merged.len() && my < merged[mx].len() {
-                            let current = merged[mx][my];
-                            // Only merge junctions in Unicode mode
-                            if !use_ascii && is_junction_char(c) && is_junction_char(current) {
-                                merged[mx][my] = merge_junctions(current, c);
-                            } else {
-                                // In ASCII mode (or non-junction chars), just overwrite
-                                merged[mx][my] = c;
-                            }
-                        }
-                    }
+/// Draw `c` over the cell at (x, y), growing the canvas to reach it.
+/// Box-drawing characters join up with the ones they land on, except in
+/// ASCII mode where the new character simply wins.
+fn merge_cell(canvas: &mut Canvas, x: i32, y: i32, c: char, use_ascii: bool) {
+    if x < 0 || y < 0 {
+        return;
+    }
+    let (x, y) = (x as usize, y as usize);
+    if x >= canvas.len() || y >= canvas.first().map_or(0, Vec::len) {
+        increase_size(canvas, x, y);
+        if x >= canvas.len() || y >= canvas.first().map_or(0, Vec::len) {
+            return;
+        }
+    }
+    let current = canvas[x][y];
+    canvas[x][y] = if !use_ascii && is_junction_char(c) && is_junction_char(current) {
+        merge_junctions(current, c)
+    } else {
+        c
+    };
+}
+
+/// Anything characters can be drawn on
+pub trait Surface {
+    /// Set the character at (x, y); negative coordinates are ignored
+    fn put(&mut self, x: i32, y: i32, c: char);
+}
+
+impl Surface for Canvas {
+    fn put(&mut self, x: i32, y: i32, c: char) {
+        set_char(self, x, y, c);
+    }
+}
+
+/// The cells drawn on one overlay, kept apart from the canvas until they are
+/// merged onto it.
+///
+/// An edge touches a few dozen cells of a drawing that can span thousands of
+/// columns; drawing its layers on blank canvases the size of the whole
+/// drawing made large flowcharts slow to render.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Layer {
+    cells: HashMap<(i32, i32), char>,
+}
+
+impl Surface for Layer {
+    fn put(&mut self, x: i32, y: i32, c: char) {
+        if x >= 0 && y >= 0 {
+            self.cells.insert((x, y), c);
+        }
+    }
+}
+
+impl Layer {
+    /// Merge the drawn cells onto `canvas`, as [`merge_canvases`] merges an
+    /// overlay canvas with the same cells drawn on it
+    pub fn merge_onto(&self, canvas: &mut Canvas, use_ascii: bool) {
+        for (&(x, y), &c) in &self.cells {
+            if c == ' ' {
+                // Blanks still stretch the drawing, as they would an overlay
+                if x as usize >= canvas.len() || y as usize >= canvas.first().map_or(0, Vec::len) {
+                    increase_size(canvas, x as usize, y as usize);
                 }
+            } else {
+                merge_cell(canvas, x, y, c, use_ascii);
             }
         }
     }
 
-    merged
+    /// The layer drawn on a blank canvas of at least `width` x `height`
+    pub fn to_canvas(&self, width: usize, height: usize, use_ascii: bool) -> Canvas {
+        let mut canvas = mk_canvas(width, height);
+        self.merge_onto(&mut canvas, use_ascii);
+        canvas
+    }
+}
+
+/// The cells `text` takes on one row: one char per grapheme cluster, each
+/// double-width one followed by a [`WIDE_FILLER`]
+pub fn text_cells(text: &str) -> Vec<char> {
+    let mut cells = Vec::new();
+    for cluster in text.graphemes(true) {
+        let width = text_width(cluster);
+        if width > 0 {
+            cells.push(cluster_char(cluster));
+            cells.extend(std::iter::repeat_n(WIDE_FILLER, width - 1));
+        }
+    }
+    cells
+}
+
+/// Turn one row of cells back into text
+pub fn cells_to_string(cells: &[char]) -> String {
+    let cell = |x: usize| cells.get(x).copied().unwrap_or(' ');
+    let mut line = String::new();
+    let mut x = 0;
+    while x < cells.len() {
+        let c = cell(x);
+        let width = cell_width(c);
+        if c == WIDE_FILLER {
+            // The wide character it belonged to was drawn over
+            line.push(' ');
+        } else if width > 1 && (1..width).any(|i| cell(x + i) != WIDE_FILLER) {
+            // Something was drawn over the right half: keep the columns aligned
+            line.push(' ');
+        } else {
+            match cell_text(c) {
+                Some(text) => line.push_str(&text),
+                None => line.push(c),
+            }
+            x += width.saturating_sub(1);
+        }
+        x += 1;
+    }
+    line
 }
 
 /// Convert the canvas to a multi-line string
@@ -227,15 +474,16 @@ pub fn canvas_to_string(canvas: &Canvas) -> String {
     let mut lines = Vec::new();
 
     for y in 0..=max_y {
-        let mut line = String::new();
-        for x in 0..=max_x {
-            if x < canvas.len() && y < canvas[x].len() {
-                line.push(canvas[x][y]);
-            } else {
-                line.push(' ');
-            }
-        }
-        lines.push(line);
+        let row: Vec<char> = (0..=max_x)
+            .map(|x| {
+                canvas
+                    .get(x)
+                    .and_then(|col| col.get(y))
+                    .copied()
+                    .unwrap_or(' ')
+            })
+            .collect();
+        lines.push(cells_to_string(&row));
     }
 
     // Remove trailing empty lines
@@ -282,10 +530,10 @@ pub fn flip_canvas_vertically(text: &str) -> String {
     flipped.join("\n")
 }
 
-/// Draw text onto canvas starting at position
-pub fn draw_text(canvas: &mut Canvas, x: i32, y: i32, text: &str) {
-    for (i, c) in text.chars().enumerate() {
-        set_char(canvas, x + i as i32, y, c);
+/// Draw text onto canvas starting at position, one column per cell
+pub fn draw_text(canvas: &mut impl Surface, x: i32, y: i32, text: &str) {
+    for (i, c) in text_cells(text).into_iter().enumerate() {
+        canvas.put(x + i as i32, y, c);
     }
 }
 
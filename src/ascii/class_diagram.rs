@@ -1,9 +1,13 @@
 //! Class diagram ASCII rendering
 
-use super::canvas::{canvas_to_string, draw_text, mk_canvas, set_char};
+use super::canvas::{canvas_to_string, draw_text, mk_canvas, set_char, text_width};
+use super::draw::SelfLoop;
 use super::types::AsciiConfig;
-use crate::types::{ClassDiagram, ClassMember, RelationshipType, Visibility};
-use std::collections::{HashMap, HashSet};
+use crate::node_width::cut_to_width;
+use crate::types::{
+    ClassDiagram, ClassMember, ClassNode, LabelAlign, RelationshipType, Visibility,
+};
+use indexmap::{IndexMap, IndexSet};
 
 /// Render a class diagram to ASCII
 pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Result<String, String> {
@@ -12,7 +16,28 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
     }
 
     let use_ascii = config.use_ascii;
+
+    // A relationship of a class with itself takes no part in the layout; it is
+    // drawn as a loop beside the class once the class is placed
+    let loops = self_loops(diagram, use_ascii);
+    let without_loops;
+    let diagram = if loops.is_empty() {
+        diagram
+    } else {
+        without_loops = ClassDiagram {
+            relationships: diagram
+                .relationships
+                .iter()
+                .filter(|rel| rel.from != rel.to)
+                .cloned()
+                .collect(),
+            ..diagram.clone()
+        };
+        &without_loops
+    };
+
     let padding = 1;
+    let ellipsis = if use_ascii { "..." } else { "…" };
     let h_gap = 4; // horizontal gap between class boxes
     let v_gap_normal = 3; // vertical gap for single child inheritance
     let v_gap_fanout = 4; // vertical gap when parent has multiple children (for centered layout)
@@ -20,7 +45,7 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
     let is_rl = diagram.direction == "RL";
 
     // Build box dimensions for each class
-    let mut class_boxes: HashMap<String, ClassBox> = HashMap::new();
+    let mut class_boxes: IndexMap<String, ClassBox> = IndexMap::new();
 
     for cls in &diagram.classes {
         // Lollipop interface nodes are rendered as plain text labels (no box)
@@ -33,28 +58,53 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
                     annotation: None,
                     attr_lines: Vec::new(),
                     method_lines: Vec::new(),
-                    width: cls.label.len(),
+                    width: text_width(&cls.label),
                     height: 1,
                     x: 0,
                     y: 0,
                     is_lollipop: true,
+                    loop_width: 0,
                 },
             );
             continue;
         }
 
-        let has_annotation = cls.annotation.is_some();
-        let annotation_str = cls.annotation.as_ref().map(|a| format!("<<{}>>", a));
+        // Text wider than the widest box allows is cut
+        let room = config.node_width.text_room(2 + 2 * padding);
+        let cut = |text: &str, room: usize| cut_to_width(text, room, ellipsis);
+        let annotation = match room {
+            Some(room) => cls
+                .annotation
+                .as_deref()
+                .map(|a| cut(a, room.saturating_sub(4))),
+            None => cls.annotation.clone(),
+        };
+        let has_annotation = annotation.is_some();
+        let annotation_str = annotation.as_ref().map(|a| format!("<<{}>>", a));
+        let mut label = header_label(diagram, cls);
+        let mut attr_lines: Vec<String> = cls.attributes.iter().map(format_member).collect();
+        let mut method_lines: Vec<String> = cls.methods.iter().map(format_member).collect();
+        if let Some(room) = room {
+            for text in std::iter::once(&mut label)
+                .chain(&mut attr_lines)
+                .chain(&mut method_lines)
+            {
+                *text = cut(text, room);
+            }
+        }
 
         // Calculate width based on widest line
-        let annotation_width = annotation_str.as_ref().map(|s| s.len()).unwrap_or(0) + 2 * padding;
-        let header_width = cls.label.len() + 2 * padding;
+        let annotation_width =
+            annotation_str.as_ref().map(|s| text_width(s)).unwrap_or(0) + 2 * padding;
+        let header_width = text_width(&label) + 2 * padding;
 
-        let attr_lines: Vec<String> = cls.attributes.iter().map(format_member).collect();
-        let method_lines: Vec<String> = cls.methods.iter().map(format_member).collect();
-
-        let attr_width = attr_lines.iter().map(|s| s.len()).max().unwrap_or(0) + 2 * padding;
-        let method_width = method_lines.iter().map(|s| s.len()).max().unwrap_or(0) + 2 * padding;
+        let attr_width = attr_lines.iter().map(|s| text_width(s)).max().unwrap_or(0) + 2 * padding;
+        let method_width = method_lines
+            .iter()
+            .map(|s| text_width(s))
+            .max()
+            .unwrap_or(0)
+            + 2 * padding;
 
         let inner_width = header_width
             .max(attr_width)
@@ -78,8 +128,8 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
             cls.id.clone(),
             ClassBox {
                 _id: cls.id.clone(),
-                label: cls.label.clone(),
-                annotation: cls.annotation.clone(),
+                label,
+                annotation,
                 attr_lines,
                 method_lines,
                 width: box_width,
@@ -87,15 +137,36 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
                 x: 0,
                 y: 0,
                 is_lollipop: false,
+                loop_width: 0,
             },
         );
     }
 
+    // Widen boxes to the configured node widths
+    let widest = class_boxes
+        .values()
+        .filter(|cb| !cb.is_lollipop)
+        .map(|cb| cb.width)
+        .max()
+        .unwrap_or(0);
+    for cb in class_boxes.values_mut().filter(|cb| !cb.is_lollipop) {
+        cb.width = config.node_width.fit(cb.width as f64, widest as f64) as usize;
+    }
+
+    // A loop needs two rows of the box's right side, and in a horizontal
+    // layout a third below them for the edges
+    for (id, l) in &loops {
+        if let Some(cb) = class_boxes.get_mut(id) {
+            cb.height = cb.height.max(if is_horizontal { 5 } else { 4 });
+            cb.loop_width = l.width();
+        }
+    }
+
     // Assign levels using topological sort - all relationships cause level separation
     // "from" nodes are placed above "to" nodes in general
     // For inheritance/realization with marker_at_from, parent is 'from', child is 'to'
-    let mut parents: HashMap<String, HashSet<String>> = HashMap::new();
-    let mut children: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut parents: IndexMap<String, IndexSet<String>> = IndexMap::new();
+    let mut children: IndexMap<String, IndexSet<String>> = IndexMap::new();
 
     for rel in &diagram.relationships {
         // Determine parent (at top) and child (at bottom)
@@ -125,7 +196,7 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
     }
 
     // BFS from roots to assign levels
-    let mut level: HashMap<String, usize> = HashMap::new();
+    let mut level: IndexMap<String, usize> = IndexMap::new();
     let roots: Vec<_> = diagram
         .classes
         .iter()
@@ -185,7 +256,8 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
             &children,
             h_gap,
             is_rl,
-            use_ascii,
+            &loops,
+            config,
         );
     }
 
@@ -251,7 +323,7 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
         let group = &level_groups[lv];
 
         // Track which nodes have been positioned (centered over children)
-        let mut positioned: HashSet<String> = HashSet::new();
+        let mut positioned: IndexSet<String> = IndexSet::new();
 
         // For each node in this level, if it has children, center over them
         for id in group {
@@ -291,8 +363,8 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
             // can drop straight down without crossing through intermediate boxes.
             fn subtree_depth(
                 id: &str,
-                children: &HashMap<String, HashSet<String>>,
-                memo: &mut HashMap<String, usize>,
+                children: &IndexMap<String, IndexSet<String>>,
+                memo: &mut IndexMap<String, usize>,
             ) -> usize {
                 if let Some(&d) = memo.get(id) {
                     return d;
@@ -310,7 +382,7 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
                 memo.insert(id.to_string(), d);
                 d
             }
-            let mut depth_memo: HashMap<String, usize> = HashMap::new();
+            let mut depth_memo: IndexMap<String, usize> = IndexMap::new();
             let mut sorted: Vec<String> = positioned_ids.into_iter().cloned().collect();
             sorted.sort_by(|a, b| {
                 let da = subtree_depth(a, &children, &mut depth_memo);
@@ -414,6 +486,8 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
         }
     }
 
+    make_room_for_loops(&mut class_boxes);
+
     // Ensure no negative X coordinates - shift everything right if needed
     // Also account for relationship labels that extend left of their parent box
     let mut min_x = class_boxes.values().map(|cb| cb.x).min().unwrap_or(0);
@@ -421,8 +495,8 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
     // Check if any relationship label would extend past the left edge
     for rel in &diagram.relationships {
         if let Some(ref lbl) = rel.label {
-            let padded_len = lbl.len() as i32 + 2; // " label "
-                                                   // Find the box whose center the label will be drawn around
+            let padded_len = text_width(lbl) as i32 + 2; // " label "
+                                                         // Find the box whose center the label will be drawn around
             let center_x = if let Some(from_box) = class_boxes.get(&rel.from) {
                 if let Some(to_box) = class_boxes.get(&rel.to) {
                     let (top_box, _) = if from_box.y <= to_box.y {
@@ -452,7 +526,7 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
     let mut total_w: usize = 0;
     let mut total_h: usize = 0;
     for cb in class_boxes.values() {
-        total_w = total_w.max(cb.x as usize + cb.width);
+        total_w = total_w.max(cb.x as usize + cb.width + cb.loop_width);
         total_h = total_h.max(cb.y as usize + cb.height);
     }
     total_w += 4;
@@ -467,15 +541,15 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
                 // Draw as plain text label (no box)
                 draw_text(&mut canvas, cb.x, cb.y, &cb.label);
             } else {
-                draw_class_box(&mut canvas, cb, use_ascii);
+                draw_class_box(&mut canvas, cb, config);
             }
         }
     }
 
     // Group inheritance relationships by parent for fan-out rendering
     // Each entry stores (child_id, label, is_dashed)
-    let mut inheritance_by_parent: HashMap<String, Vec<(String, Option<String>, bool)>> =
-        HashMap::new();
+    let mut inheritance_by_parent: IndexMap<String, Vec<(String, Option<String>, bool)>> =
+        IndexMap::new();
     let mut non_hierarchical_rels: Vec<_> = Vec::new();
 
     for rel in &diagram.relationships {
@@ -559,13 +633,8 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
             // Draw label if present (with space padding for readability)
             if let Some(lbl) = label_opt {
                 let padded = format!(" {} ", lbl); // Add space padding on both sides
-                let label_start = parent_center_x - (padded.len() as i32 / 2);
-                for (i, ch) in padded.chars().enumerate() {
-                    let x = label_start + i as i32;
-                    if x >= 0 {
-                        set_char(&mut canvas, x, mid_y, ch);
-                    }
-                }
+                let label_start = parent_center_x - (text_width(&padded) as i32 / 2);
+                draw_text(&mut canvas, label_start, mid_y, &padded);
                 // Draw vertical lines above and below label
                 for y in (marker_y + 1)..mid_y {
                     set_char(&mut canvas, parent_center_x, y, line_v);
@@ -582,8 +651,8 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
                     };
                     for x in left_x..=right_x {
                         // Don't overwrite label chars
-                        let label_start = parent_center_x - (lbl.len() as i32 / 2);
-                        let label_end = label_start + lbl.len() as i32 - 1;
+                        let label_start = parent_center_x - (text_width(lbl) as i32 / 2);
+                        let label_end = label_start + text_width(lbl) as i32 - 1;
                         if x < label_start || x > label_end {
                             set_char(&mut canvas, x, mid_y, solid_h);
                         }
@@ -831,13 +900,8 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
             // Draw label if present (with space padding)
             if let Some(ref lbl) = rel.label {
                 let padded = format!(" {} ", lbl);
-                let label_start = top_center_x - (padded.len() as i32 / 2);
-                for (i, ch) in padded.chars().enumerate() {
-                    let x = label_start + i as i32;
-                    if x >= 0 {
-                        set_char(&mut canvas, x, mid_y, ch);
-                    }
-                }
+                let label_start = top_center_x - (text_width(&padded) as i32 / 2);
+                draw_text(&mut canvas, label_start, mid_y, &padded);
             }
 
             // Vertical line from after marker/label to target
@@ -854,13 +918,8 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
 
                 // Draw label (with space padding)
                 let padded = format!(" {} ", lbl);
-                let label_start = top_center_x - (padded.len() as i32 / 2);
-                for (i, ch) in padded.chars().enumerate() {
-                    let x = label_start + i as i32;
-                    if x >= 0 {
-                        set_char(&mut canvas, x, mid_y, ch);
-                    }
-                }
+                let label_start = top_center_x - (text_width(&padded) as i32 / 2);
+                draw_text(&mut canvas, label_start, mid_y, &padded);
 
                 // Vertical line from below label to arrow
                 for y in (mid_y + 1)..(bottom_top_y - 1) {
@@ -920,14 +979,14 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
         // Top cardinality: to the left of the vertical line, on the first line below the source box
         if let Some(ref card) = top_card {
             let card_y = top_bottom_y + 1;
-            let card_x = top_center_x - card.len() as i32;
+            let card_x = top_center_x - text_width(card) as i32;
             draw_text(&mut canvas, card_x, card_y, card);
         }
         // Bottom cardinality: right after the arrow marker
         if let Some(ref card) = bottom_card {
             let card_y = bottom_top_y - 1;
             let card_x = bottom_center_x + 1;
-            if card.len() > 1 {
+            if text_width(card) > 1 {
                 let padded = format!(" {}", card);
                 draw_text(&mut canvas, card_x, card_y, &padded);
             } else {
@@ -971,15 +1030,11 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
 
         // Draw padded label
         let padded = format!(" {} ", label);
-        let label_start = center_x - (padded.len() as i32 / 2);
-        for (i, ch) in padded.chars().enumerate() {
-            let x = label_start + i as i32;
-            if x >= 0 {
-                set_char(&mut canvas, x, mid_y, ch);
-            }
-        }
+        let label_start = center_x - (text_width(&padded) as i32 / 2);
+        draw_text(&mut canvas, label_start, mid_y, &padded);
     }
 
+    draw_self_loops(&mut canvas, &class_boxes, &loops, false, use_ascii);
     Ok(canvas_to_string(&canvas))
 }
 
@@ -989,15 +1044,17 @@ pub fn render_class_ascii(diagram: &ClassDiagram, config: &AsciiConfig) -> Resul
 #[allow(clippy::too_many_arguments)]
 fn render_horizontal_class_diagram(
     diagram: &ClassDiagram,
-    class_boxes: &mut HashMap<String, ClassBox>,
-    level: &HashMap<String, usize>,
+    class_boxes: &mut IndexMap<String, ClassBox>,
+    level: &IndexMap<String, usize>,
     level_groups: &[Vec<String>],
     max_level: usize,
-    children: &HashMap<String, HashSet<String>>,
+    children: &IndexMap<String, IndexSet<String>>,
     _h_gap: usize,
     is_rl: bool,
-    use_ascii: bool,
+    loops: &IndexMap<String, ClassLoop>,
+    config: &AsciiConfig,
 ) -> Result<String, String> {
+    let use_ascii = config.use_ascii;
     let v_gap = 1; // vertical gap between boxes in the same column
 
     // Compute X positions for each level (columns left-to-right)
@@ -1011,7 +1068,7 @@ fn render_horizontal_class_diagram(
         .relationships
         .iter()
         .filter_map(|r| r.label.as_ref())
-        .map(|l| l.len())
+        .map(|l| text_width(l))
         .max()
         .unwrap_or(0);
     // Need room for: gap(1) + marker(1) + cardinality(~2) + space(1) + label + space(1) + cardinality(~2) + marker(1) + gap(1)
@@ -1083,7 +1140,7 @@ fn render_horizontal_class_diagram(
 
     for lv in levels_to_process {
         let group = &level_groups[lv];
-        let mut positioned: HashSet<String> = HashSet::new();
+        let mut positioned: IndexSet<String> = IndexSet::new();
 
         for id in group {
             if let Some(child_set) = children.get(id) {
@@ -1129,6 +1186,8 @@ fn render_horizontal_class_diagram(
         }
     }
 
+    make_room_for_loops(class_boxes);
+
     // Ensure no negative Y coordinates
     let min_y = class_boxes.values().map(|cb| cb.y).min().unwrap_or(0);
     if min_y < 0 {
@@ -1141,7 +1200,7 @@ fn render_horizontal_class_diagram(
     let mut total_w: usize = 0;
     let mut total_h: usize = 0;
     for cb in class_boxes.values() {
-        total_w = total_w.max(cb.x as usize + cb.width);
+        total_w = total_w.max(cb.x as usize + cb.width + cb.loop_width);
         total_h = total_h.max(cb.y as usize + cb.height);
     }
     total_w += 4;
@@ -1155,7 +1214,7 @@ fn render_horizontal_class_diagram(
             if cb.is_lollipop {
                 draw_text(&mut canvas, cb.x, cb.y, &cb.label);
             } else {
-                draw_class_box(&mut canvas, cb, use_ascii);
+                draw_class_box(&mut canvas, cb, config);
             }
         }
     }
@@ -1194,12 +1253,13 @@ fn render_horizontal_class_diagram(
         };
 
         // Connection X coordinates: just outside each box edge, with 1 char gap
-        let left_conn_x = left_box.x + left_box.width as i32 + 1;
+        // Past the loop of a box with one
+        let left_conn_x = left_box.x + (left_box.width + left_box.loop_width) as i32 + 1;
         let right_conn_x = right_box.x - 2;
 
         // Y centers
-        let left_center_y = left_box.y + left_box.height as i32 / 2;
-        let right_center_y = right_box.y + right_box.height as i32 / 2;
+        let left_center_y = left_box.side_y();
+        let right_center_y = right_box.side_y();
 
         // Determine marker side (needed for vertical connector X position)
         let is_hierarchical = matches!(
@@ -1282,10 +1342,11 @@ fn render_horizontal_class_diagram(
             (to_box, from_box, false)
         };
 
-        let left_conn_x = left_box.x + left_box.width as i32 + 1;
+        // Past the loop of a box with one
+        let left_conn_x = left_box.x + (left_box.width + left_box.loop_width) as i32 + 1;
         let right_conn_x = right_box.x - 2;
-        let left_center_y = left_box.y + left_box.height as i32 / 2;
-        let right_center_y = right_box.y + right_box.height as i32 / 2;
+        let left_center_y = left_box.side_y();
+        let right_center_y = right_box.side_y();
 
         let is_hierarchical = matches!(
             rel.rel_type,
@@ -1346,6 +1407,13 @@ fn render_horizontal_class_diagram(
         for x in line_start..=line_end {
             set_char(&mut canvas, x, line_y, line_h);
         }
+        // Run the edge back under the loop to the box it leaves from
+        if left_box.loop_width > 0 && !target_is_left {
+            let border = left_box.x + left_box.width as i32;
+            for x in border..left_conn_x {
+                set_char(&mut canvas, x, left_center_y, line_h);
+            }
+        }
         set_char(&mut canvas, marker_x, line_y, marker_char);
 
         // In Unicode mode, draw corner piece where vertical connector meets horizontal line
@@ -1400,10 +1468,11 @@ fn render_horizontal_class_diagram(
             (to_box, from_box, false)
         };
 
-        let left_conn_x = left_box.x + left_box.width as i32 + 1;
+        // Past the loop of a box with one
+        let left_conn_x = left_box.x + (left_box.width + left_box.loop_width) as i32 + 1;
         let right_conn_x = right_box.x - 2;
-        let left_center_y = left_box.y + left_box.height as i32 / 2;
-        let right_center_y = right_box.y + right_box.height as i32 / 2;
+        let left_center_y = left_box.side_y();
+        let right_center_y = right_box.side_y();
 
         let target_is_left = !from_is_left;
         let line_y = if target_is_left {
@@ -1460,7 +1529,7 @@ fn render_horizontal_class_diagram(
                 let card_x = if from_is_left {
                     left_card_x
                 } else {
-                    right_card_x_fn(card.len())
+                    right_card_x_fn(text_width(card))
                 };
                 draw_text(&mut canvas, card_x, card_y, card);
             }
@@ -1468,7 +1537,7 @@ fn render_horizontal_class_diagram(
         // to_cardinality is near the target box (no vertical connector there)
         if let Some(ref card) = rel.to_cardinality {
             let card_x = if from_is_left {
-                right_card_x_fn(card.len())
+                right_card_x_fn(text_width(card))
             } else {
                 left_card_x
             };
@@ -1478,14 +1547,166 @@ fn render_horizontal_class_diagram(
         // Label centered above the horizontal line
         if let Some(ref lbl) = rel.label {
             let mid_x = (left_conn_x + right_conn_x) / 2;
-            let label_start = mid_x - lbl.len() as i32 / 2;
+            let label_start = mid_x - text_width(lbl) as i32 / 2;
             draw_text(&mut canvas, label_start, card_y, lbl);
         }
     }
 
+    draw_self_loops(&mut canvas, class_boxes, loops, true, use_ascii);
     Ok(canvas_to_string(&canvas))
 }
 
+/// The relationships of a class with itself, drawn as one loop
+struct ClassLoop {
+    /// Marker against the box where the loop leaves it, and where it comes back
+    start: String,
+    end: String,
+    line: char,
+    label: String,
+    from_cardinality: Option<String>,
+    to_cardinality: Option<String>,
+}
+
+impl ClassLoop {
+    fn self_loop(&self) -> SelfLoop<'_> {
+        SelfLoop {
+            start: &self.start,
+            end: &self.end,
+            line: self.line,
+            label: &self.label,
+        }
+    }
+
+    /// Columns the loop and its cardinalities take right of the box
+    fn width(&self) -> usize {
+        let cardinality = |card: &Option<String>| card.as_deref().map_or(0, text_width) + 1;
+        self.self_loop()
+            .width()
+            .max(cardinality(&self.from_cardinality))
+            .max(cardinality(&self.to_cardinality))
+    }
+}
+
+/// The loop of each class with relationships to itself. A class with several
+/// gets one loop, with the markers of the first and all their labels.
+fn self_loops(diagram: &ClassDiagram, use_ascii: bool) -> IndexMap<String, ClassLoop> {
+    let mut loops: IndexMap<String, ClassLoop> = IndexMap::new();
+    for rel in diagram
+        .relationships
+        .iter()
+        .filter(|rel| rel.from == rel.to)
+    {
+        let label = rel.label.clone().unwrap_or_default();
+        if let Some(existing) = loops.get_mut(&rel.from) {
+            if !label.is_empty() {
+                if !existing.label.is_empty() {
+                    existing.label.push_str(", ");
+                }
+                existing.label.push_str(&label);
+            }
+            continue;
+        }
+
+        let is_hierarchical = matches!(
+            rel.rel_type,
+            RelationshipType::Inheritance | RelationshipType::Realization
+        );
+        let marker_at_source = matches!(
+            rel.rel_type,
+            RelationshipType::Composition | RelationshipType::Aggregation
+        );
+        // Both ends point left, into the box
+        let marker = if is_hierarchical {
+            if use_ascii {
+                '<'
+            } else {
+                '◁'
+            }
+        } else if marker_at_source {
+            get_marker_shape(&rel.rel_type, false, use_ascii)
+        } else if use_ascii {
+            '<'
+        } else {
+            '◀'
+        };
+        let marker_on_from = (is_hierarchical || marker_at_source) && rel.marker_at_from;
+        let (start, end) = if marker_on_from {
+            (marker.to_string(), String::new())
+        } else {
+            (String::new(), marker.to_string())
+        };
+        let is_dashed = matches!(
+            rel.rel_type,
+            RelationshipType::Dependency | RelationshipType::Realization
+        );
+        let line = match (is_dashed, use_ascii) {
+            (true, true) => '.',
+            (true, false) => '┄',
+            (false, true) => '-',
+            (false, false) => '─',
+        };
+        loops.insert(
+            rel.from.clone(),
+            ClassLoop {
+                start,
+                end,
+                line,
+                label,
+                from_cardinality: rel.from_cardinality.clone(),
+                to_cardinality: rel.to_cardinality.clone(),
+            },
+        );
+    }
+    loops
+}
+
+/// Move the boxes right of each box with a loop out of the way of the loop
+fn make_room_for_loops(class_boxes: &mut IndexMap<String, ClassBox>) {
+    // Rightmost first, so that each shift carries the ones already made
+    let mut rights: Vec<(i32, usize)> = class_boxes
+        .values()
+        .filter(|cb| cb.loop_width > 0)
+        .map(|cb| (cb.x + cb.width as i32, cb.loop_width))
+        .collect();
+    rights.sort_by_key(|r| std::cmp::Reverse(r.0));
+    for (right, width) in rights {
+        for cb in class_boxes.values_mut().filter(|cb| cb.x >= right) {
+            cb.x += width as i32;
+        }
+    }
+}
+
+/// Draw the loops right of their boxes, with the `from` cardinality above
+/// the loop and the `to` one below it. Loops span the box, or in a
+/// horizontal layout keep above the row where edges meet it.
+fn draw_self_loops(
+    canvas: &mut super::types::Canvas,
+    class_boxes: &IndexMap<String, ClassBox>,
+    loops: &IndexMap<String, ClassLoop>,
+    is_horizontal: bool,
+    use_ascii: bool,
+) {
+    for (id, l) in loops {
+        let Some(cb) = class_boxes.get(id).filter(|cb| !cb.is_lollipop) else {
+            continue;
+        };
+        let right = cb.x + cb.width as i32 - 1;
+        let from_y = cb.y + 1;
+        let to_y = if is_horizontal {
+            from_y + 1
+        } else {
+            cb.y + cb.height as i32 - 2
+        };
+        l.self_loop().draw(canvas, right, from_y, to_y, use_ascii);
+        if let Some(card) = &l.from_cardinality {
+            draw_text(canvas, right + 1, from_y - 1, card);
+        }
+        if let Some(card) = &l.to_cardinality {
+            draw_text(canvas, right + 1, to_y + 1, card);
+        }
+    }
+}
+
 fn get_marker_shape(rel_type: &RelationshipType, _is_hierarchical: bool, use_ascii: bool) -> char {
     match rel_type {
         RelationshipType::Inheritance | RelationshipType::Realization => {
@@ -1530,6 +1751,44 @@ struct ClassBox {
     x: i32,
     y: i32,
     is_lollipop: bool,
+    /// Columns taken by the loop on the right side, if there is one
+    loop_width: usize,
+}
+
+impl ClassBox {
+    /// Row where edges meet the side of the box: the middle one, or the one
+    /// below the loop of a box with one
+    fn side_y(&self) -> i32 {
+        let middle = self.y + self.height as i32 / 2;
+        if self.loop_width > 0 {
+            middle.max(self.y + 3)
+        } else {
+            middle
+        }
+    }
+}
+
+/// The name row of a class box. A class whose stereotype has a configured
+/// style gets that style's `marker` in front, or the stereotype's initial as
+/// `(I)`, so styled stereotypes stand out without color.
+fn header_label(diagram: &ClassDiagram, cls: &ClassNode) -> String {
+    let styled = cls
+        .annotation
+        .as_ref()
+        .and_then(|a| Some((a, diagram.stereotype_styles.get(&a.to_lowercase())?)));
+    match styled {
+        Some((annotation, style)) => {
+            let marker = match style.get("marker") {
+                Some(marker) => marker.clone(),
+                None => {
+                    let initial = annotation.chars().next().unwrap_or('?');
+                    format!("({})", initial.to_uppercase())
+                }
+            };
+            format!("{} {}", marker, cls.label)
+        }
+        None => cls.label.clone(),
+    }
 }
 
 fn format_member(member: &ClassMember) -> String {
@@ -1558,8 +1817,8 @@ fn format_member(member: &ClassMember) -> String {
     }
 }
 
-fn draw_class_box(canvas: &mut super::types::Canvas, cb: &ClassBox, use_ascii: bool) {
-    let (h_line, v_line, tl, tr, bl, br, div_l, div_r) = if use_ascii {
+fn draw_class_box(canvas: &mut super::types::Canvas, cb: &ClassBox, config: &AsciiConfig) {
+    let (h_line, v_line, tl, tr, bl, br, div_l, div_r) = if config.use_ascii {
         ('-', '|', '+', '+', '+', '+', '+', '+')
     } else {
         ('─', '│', '┌', '┐', '└', '┘', '├', '┤')
@@ -1569,6 +1828,15 @@ fn draw_class_box(canvas: &mut super::types::Canvas, cb: &ClassBox, use_ascii: b
     let y = cb.y;
     let w = cb.width as i32;
 
+    // Text lines sit inside the padding, as aligned as configured, or else
+    // as `default` is
+    let padding = 1;
+    let room = (w - 2 - 2 * padding).max(0) as usize;
+    let text_x = |text: &str, default: LabelAlign| {
+        let align = config.label_align.unwrap_or(default);
+        x + 1 + padding + align.offset(room, text_width(text)) as i32
+    };
+
     // Top border
     set_char(canvas, x, y, tl);
     for i in 1..(w - 1) {
@@ -1582,27 +1850,20 @@ fn draw_class_box(canvas: &mut super::types::Canvas, cb: &ClassBox, use_ascii: b
     if let Some(ref annot) = cb.annotation {
         let annot_str = format!("<<{}>>", annot);
         set_char(canvas, x, cur_y, v_line);
-        // Center annotation within the box (inner width = w - 2)
-        let inner_w = (w - 2) as usize;
-        let annot_offset = if annot_str.len() < inner_w {
-            (inner_w - annot_str.len()) / 2
-        } else {
-            1
-        };
-        draw_text(canvas, x + 1 + annot_offset as i32, cur_y, &annot_str);
+        let annot_x = text_x(&annot_str, LabelAlign::Center);
+        draw_text(canvas, annot_x, cur_y, &annot_str);
         set_char(canvas, x + w - 1, cur_y, v_line);
         cur_y += 1;
     }
 
-    // Class name row (centered)
+    // Class name row (centered by default)
     set_char(canvas, x, cur_y, v_line);
-    let inner_w = (w - 2) as usize;
-    let name_offset = if cb.label.len() < inner_w {
-        (inner_w - cb.label.len()) / 2
-    } else {
-        1
-    };
-    draw_text(canvas, x + 1 + name_offset as i32, cur_y, &cb.label);
+    draw_text(
+        canvas,
+        text_x(&cb.label, LabelAlign::Center),
+        cur_y,
+        &cb.label,
+    );
     set_char(canvas, x + w - 1, cur_y, v_line);
     cur_y += 1;
 
@@ -1623,7 +1884,7 @@ fn draw_class_box(canvas: &mut super::types::Canvas, cb: &ClassBox, use_ascii: b
         if has_attrs {
             for line in &cb.attr_lines {
                 set_char(canvas, x, cur_y, v_line);
-                draw_text(canvas, x + 2, cur_y, line);
+                draw_text(canvas, text_x(line, LabelAlign::Left), cur_y, line);
                 set_char(canvas, x + w - 1, cur_y, v_line);
                 cur_y += 1;
             }
@@ -1646,13 +1907,20 @@ fn draw_class_box(canvas: &mut super::types::Canvas, cb: &ClassBox, use_ascii: b
 
             for line in &cb.method_lines {
                 set_char(canvas, x, cur_y, v_line);
-                draw_text(canvas, x + 2, cur_y, line);
+                draw_text(canvas, text_x(line, LabelAlign::Left), cur_y, line);
                 set_char(canvas, x + w - 1, cur_y, v_line);
                 cur_y += 1;
             }
         }
     }
 
+    // Empty rows down to the height of the box, if it was made taller
+    while cur_y < y + cb.height as i32 - 1 {
+        set_char(canvas, x, cur_y, v_line);
+        set_char(canvas, x + w - 1, cur_y, v_line);
+        cur_y += 1;
+    }
+
     // Bottom border
     set_char(canvas, x, cur_y, bl);
     for i in 1..(w - 1) {
@@ -0,0 +1,122 @@
+//! ANSI colors for Unicode text output
+//!
+//! Lines and arrow heads are colored with a palette chosen for the terminal's
+//! background; labels keep the terminal's own foreground color.
+
+/// Terminal background brightness, which decides the palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    Light,
+    Dark,
+}
+
+impl Background {
+    /// Read the reply to an OSC 11 query (`ESC ] 11 ; rgb:RRRR/GGGG/BBBB` ended
+    /// by BEL or ST).
+    ///
+    /// # Example
+    /// ```rust
+    /// use m2svg::ascii::color::Background;
+    ///
+    /// let reply = "\x1b]11;rgb:ffff/ffff/dddd\x07";
+    /// assert_eq!(Background::from_osc11_reply(reply), Some(Background::Light));
+    /// let reply = "\x1b]11;rgb:1e1e/1e1e/2e2e\x1b\\";
+    /// assert_eq!(Background::from_osc11_reply(reply), Some(Background::Dark));
+    /// ```
+    pub fn from_osc11_reply(reply: &str) -> Option<Self> {
+        let rgb = reply.split("rgb:").nth(1)?;
+        let rgb = rgb.trim_end_matches(['\x07', '\\', '\x1b']);
+        // Each channel has 1 to 4 hex digits, scaled to 0..1
+        let channels: Vec<f64> = rgb
+            .split('/')
+            .map(|c| {
+                let value = u32::from_str_radix(c, 16).ok()?;
+                let max = 16u32.checked_pow(c.len() as u32)? - 1;
+                (max > 0).then(|| value as f64 / max as f64)
+            })
+            .collect::<Option<_>>()?;
+        let &[r, g, b] = &channels[..] else {
+            return None;
+        };
+        Some(Self::from_luminance(0.299 * r + 0.587 * g + 0.114 * b))
+    }
+
+    /// Read the `COLORFGBG` variable some terminals set (`fg;bg`, as palette
+    /// indices), e.g. `15;0` for white on black.
+    pub fn from_colorfgbg(value: &str) -> Option<Self> {
+        let bg: u8 = value.rsplit(';').next()?.trim().parse().ok()?;
+        // Palette 7 (white) and the bright colors other than 8 (grey) are light
+        Some(match bg {
+            7 | 9..=15 => Self::Light,
+            _ => Self::Dark,
+        })
+    }
+
+    fn from_luminance(luminance: f64) -> Self {
+        if luminance > 0.5 {
+            Self::Light
+        } else {
+            Self::Dark
+        }
+    }
+
+    /// SGR codes for (lines, arrow heads)
+    fn palette(self) -> (&'static str, &'static str) {
+        match self {
+            // Cyan and bright yellow stand out on dark backgrounds
+            Self::Dark => ("\x1b[36m", "\x1b[93m"),
+            // Blue and magenta keep their contrast on light ones
+            Self::Light => ("\x1b[34m", "\x1b[35m"),
+        }
+    }
+}
+
+/// What a character of the output draws
+#[derive(Clone, Copy, PartialEq)]
+enum Part {
+    Line,
+    Arrow,
+    Text,
+}
+
+fn part(c: char) -> Part {
+    match c {
+        '\u{2500}'..='\u{257F}' => Part::Line,
+        '►' | '◄' | '▲' | '▼' | '▶' | '◀' | '△' | '▽' | '◇' | '◆' | '○' | '●' => {
+            Part::Arrow
+        }
+        _ => Part::Text,
+    }
+}
+
+/// Color the lines and arrow heads of Unicode text output for a terminal
+/// with the given background.
+///
+/// # Example
+/// ```rust
+/// use m2svg::ascii::color::{colorize, Background};
+///
+/// assert_eq!(colorize("A ──► B", Background::Dark), "A \x1b[36m──\x1b[93m►\x1b[0m B");
+/// ```
+pub fn colorize(text: &str, background: Background) -> String {
+    let (line, arrow) = background.palette();
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut current = Part::Text;
+    for c in text.chars() {
+        // Newlines end a colored run so each line stands on its own
+        let next = if c == '\n' { Part::Text } else { part(c) };
+        if next != current {
+            out.push_str(match next {
+                Part::Line => line,
+                Part::Arrow => arrow,
+                Part::Text => "\x1b[0m",
+            });
+            current = next;
+        }
+        out.push(c);
+    }
+    if current != Part::Text {
+        out.push_str("\x1b[0m");
+    }
+    out
+}
@@ -1,13 +1,91 @@
 //! Drawing operations for ASCII rendering
 
-use super::canvas::{copy_canvas, merge_canvases, mk_canvas, set_char};
-use super::grid::{grid_to_drawing_coord, grid_to_drawing_coord_topleft};
+use super::canvas::{
+    draw_text, get_canvas_size, get_char, merge_into, merge_junctions, mk_canvas, set_char,
+    text_width, truncate_to_width, Layer, Surface,
+};
+use super::grid::{grid_to_drawing_coord_topleft, path_to_drawing};
 use super::types::{
-    determine_direction_drawing, AsciiGraph, AsciiNode, Canvas, Direction, DrawingCoord, GridCoord,
-    DOWN, LEFT, LOWER_LEFT, LOWER_RIGHT, RIGHT, UP, UPPER_LEFT, UPPER_RIGHT,
+    determine_direction_drawing, get_opposite, AsciiEdge, AsciiGraph, AsciiNode, Canvas, Direction,
+    DrawingCoord, DOWN, LEFT, LOWER_LEFT, LOWER_RIGHT, RIGHT, UP, UPPER_LEFT, UPPER_RIGHT,
 };
+use crate::types::{EdgeMarker, LabelAlign, NodeShape};
+
+/// Characters used to draw the border of a node box
+struct BoxStyle {
+    h_line: char,
+    /// Top-left, top-right, bottom-left, bottom-right
+    corners: [char; 4],
+    /// Left side above, on and below the label row
+    left: [char; 3],
+    /// Right side above, on and below the label row
+    right: [char; 3],
+}
+
+/// Pick the border characters that best convey a node shape.
+/// Shapes without a text equivalent fall back to a plain rectangle, and
+/// without `rounded` corners, rounded shapes get square ones. `double` draws
+/// square-cornered Unicode boxes with double lines.
+fn box_style(shape: NodeShape, use_ascii: bool, rounded: bool, double: bool) -> BoxStyle {
+    let (h_line, v_line) = if use_ascii {
+        ('-', '|')
+    } else {
+        ('─', '│')
+    };
+    let (fslash, bslash) = if use_ascii {
+        ('/', '\\')
+    } else {
+        ('╱', '╲')
+    };
+    let square = ['┌', '┐', '└', '┘'];
+    let rounded = if rounded {
+        ['╭', '╮', '╰', '╯']
+    } else {
+        square
+    };
+    let slanted = [fslash, bslash, bslash, fslash];
+
+    let (corners, left, right) = match shape {
+        NodeShape::Rounded if use_ascii => (['.', '.', '\'', '\''], ['('; 3], [')'; 3]),
+        NodeShape::Rounded => (rounded, [v_line; 3], [v_line; 3]),
+        NodeShape::Stadium | NodeShape::Circle | NodeShape::DoubleCircle => {
+            let corners = if use_ascii { slanted } else { rounded };
+            (corners, ['('; 3], [')'; 3])
+        }
+        NodeShape::Diamond => (slanted, [v_line, '<', v_line], [v_line, '>', v_line]),
+        NodeShape::Hexagon => (slanted, [fslash, '<', bslash], [bslash, '>', fslash]),
+        _ if use_ascii => (['+'; 4], [v_line; 3], [v_line; 3]),
+        _ => (square, [v_line; 3], [v_line; 3]),
+    };
+    if double && corners == square {
+        return BoxStyle {
+            h_line: '═',
+            corners: ['╔', '╗', '╚', '╝'],
+            left: ['║'; 3],
+            right: ['║'; 3],
+        };
+    }
 
-/// Draw a node box with centered label text
+    BoxStyle {
+        h_line,
+        corners,
+        left,
+        right,
+    }
+}
+
+/// The junction `c` on a double border: a double line with a single arm
+fn double_junction(c: char) -> char {
+    match c {
+        '┴' => '╧',
+        '┬' => '╤',
+        '┤' => '╢',
+        '├' => '╟',
+        c => c,
+    }
+}
+
+/// Draw a node box with its label text centered, or aligned as configured
 pub fn draw_box(node: &AsciiNode, graph: &AsciiGraph) -> Canvas {
     let gc = match node.grid_coord {
         Some(c) => c,
@@ -28,36 +106,46 @@ pub fn draw_box(node: &AsciiNode, graph: &AsciiGraph) -> Canvas {
 
     let mut box_canvas = mk_canvas(w.max(0) as usize, h.max(0) as usize);
 
-    // Box-drawing characters
-    let (h_line, v_line, tl, tr, bl, br) = if use_ascii {
-        ('-', '|', '+', '+', '+', '+')
-    } else {
-        ('─', '│', '┌', '┐', '└', '┘')
-    };
+    let style = box_style(
+        node.shape,
+        use_ascii,
+        graph.config.rounded_corners,
+        node.emphasized,
+    );
+    let text_y = h / 2;
 
     // Draw horizontal lines
     for x in 1..w {
-        set_char(&mut box_canvas, x, 0, h_line);
-        set_char(&mut box_canvas, x, h, h_line);
+        set_char(&mut box_canvas, x, 0, style.h_line);
+        set_char(&mut box_canvas, x, h, style.h_line);
     }
-    // Draw vertical lines
+    // Draw the sides, which may change around the label row
     for y in 1..h {
-        set_char(&mut box_canvas, 0, y, v_line);
-        set_char(&mut box_canvas, w, y, v_line);
+        let part = (y.cmp(&text_y) as i32 + 1) as usize;
+        set_char(&mut box_canvas, 0, y, style.left[part]);
+        set_char(&mut box_canvas, w, y, style.right[part]);
     }
     // Draw corners
+    let [tl, tr, bl, br] = style.corners;
     set_char(&mut box_canvas, 0, 0, tl);
     set_char(&mut box_canvas, w, 0, tr);
     set_char(&mut box_canvas, 0, h, bl);
     set_char(&mut box_canvas, w, h, br);
 
-    // Center the label (matching TypeScript: floor(w/2) - ceil(label.len/2) + 1)
-    let label = &node.display_label;
-    let text_y = h / 2;
-    let label_half = (label.len() as i32 + 1) / 2; // ceil division
-    let text_x = w / 2 - label_half + 1;
-    for (i, c) in label.chars().enumerate() {
-        set_char(&mut box_canvas, text_x + i as i32, text_y, c);
+    // Center each label line (matching TypeScript: floor(w/2) - ceil(label.len/2) + 1),
+    // or align it inside the padding, and the lines of a wrapped label around
+    // the middle row
+    let padding = graph.config.box_border_padding as i32;
+    let lines: Vec<&str> = node.display_label.lines().collect();
+    let first_y = text_y - (lines.len().max(1) as i32 - 1) / 2;
+    for (i, line) in lines.iter().enumerate() {
+        let width = text_width(line) as i32;
+        let text_x = match graph.config.label_align {
+            Some(LabelAlign::Left) => 1 + padding,
+            Some(LabelAlign::Right) => w - padding - width,
+            Some(LabelAlign::Center) | None => w / 2 - (width + 1) / 2 + 1,
+        };
+        draw_text(&mut box_canvas, text_x, first_y + i as i32, line);
     }
 
     box_canvas
@@ -65,7 +153,7 @@ pub fn draw_box(node: &AsciiNode, graph: &AsciiGraph) -> Canvas {
 
 /// Draw a line between two drawing coordinates
 pub fn draw_line(
-    canvas: &mut Canvas,
+    canvas: &mut impl Surface,
     from: DrawingCoord,
     to: DrawingCoord,
     offset_from: i32,
@@ -84,29 +172,29 @@ pub fn draw_line(
     if dir == UP {
         for y in ((to.y - offset_to)..=(from.y - offset_from)).rev() {
             drawn_coords.push(DrawingCoord::new(from.x, y));
-            set_char(canvas, from.x, y, v_char);
+            canvas.put(from.x, y, v_char);
         }
     } else if dir == DOWN {
         for y in (from.y + offset_from)..=(to.y + offset_to) {
             drawn_coords.push(DrawingCoord::new(from.x, y));
-            set_char(canvas, from.x, y, v_char);
+            canvas.put(from.x, y, v_char);
         }
     } else if dir == LEFT {
         for x in ((to.x - offset_to)..=(from.x - offset_from)).rev() {
             drawn_coords.push(DrawingCoord::new(x, from.y));
-            set_char(canvas, x, from.y, h_char);
+            canvas.put(x, from.y, h_char);
         }
     } else if dir == RIGHT {
         for x in (from.x + offset_from)..=(to.x + offset_to) {
             drawn_coords.push(DrawingCoord::new(x, from.y));
-            set_char(canvas, x, from.y, h_char);
+            canvas.put(x, from.y, h_char);
         }
     } else if dir == UPPER_LEFT {
         let mut x = from.x;
         let mut y = from.y - offset_from;
         while x >= to.x - offset_to && y >= to.y - offset_to {
             drawn_coords.push(DrawingCoord::new(x, y));
-            set_char(canvas, x, y, bslash);
+            canvas.put(x, y, bslash);
             x -= 1;
             y -= 1;
         }
@@ -115,7 +203,7 @@ pub fn draw_line(
         let mut y = from.y - offset_from;
         while x <= to.x + offset_to && y >= to.y - offset_to {
             drawn_coords.push(DrawingCoord::new(x, y));
-            set_char(canvas, x, y, fslash);
+            canvas.put(x, y, fslash);
             x += 1;
             y -= 1;
         }
@@ -124,7 +212,7 @@ pub fn draw_line(
         let mut y = from.y + offset_from;
         while x >= to.x - offset_to && y <= to.y + offset_to {
             drawn_coords.push(DrawingCoord::new(x, y));
-            set_char(canvas, x, y, fslash);
+            canvas.put(x, y, fslash);
             x -= 1;
             y += 1;
         }
@@ -133,7 +221,7 @@ pub fn draw_line(
         let mut y = from.y + offset_from;
         while x <= to.x + offset_to && y <= to.y + offset_to {
             drawn_coords.push(DrawingCoord::new(x, y));
-            set_char(canvas, x, y, bslash);
+            canvas.put(x, y, bslash);
             x += 1;
             y += 1;
         }
@@ -144,10 +232,27 @@ pub fn draw_line(
 
 /// Draw an arrowhead at the end of a path
 pub fn draw_arrow_head(
-    canvas: &mut Canvas,
+    canvas: &mut impl Surface,
     last_line: &[DrawingCoord],
     fallback_dir: Direction,
     use_ascii: bool,
+) {
+    draw_end_marker(
+        canvas,
+        last_line,
+        fallback_dir,
+        EdgeMarker::Arrow,
+        use_ascii,
+    );
+}
+
+/// Draw an edge marker (arrow, circle or cross) at the end of a path
+fn draw_end_marker(
+    canvas: &mut impl Surface,
+    last_line: &[DrawingCoord],
+    fallback_dir: Direction,
+    marker: EdgeMarker,
+    use_ascii: bool,
 ) {
     if last_line.is_empty() {
         return;
@@ -161,8 +266,42 @@ pub fn draw_arrow_head(
         fallback_dir
     };
 
-    let c = if !use_ascii {
-        match dir {
+    if let Some(c) = marker_char(marker, dir, use_ascii) {
+        canvas.put(last_pos.x, last_pos.y, c);
+    }
+}
+
+/// Draw an edge marker at the start of a path, pointing back at the source node
+fn draw_start_marker(
+    canvas: &mut impl Surface,
+    first_line: &[DrawingCoord],
+    fallback_dir: Direction,
+    marker: EdgeMarker,
+    use_ascii: bool,
+) {
+    if first_line.is_empty() {
+        return;
+    }
+
+    let first_pos = first_line[0];
+    let dir = if first_line.len() > 1 {
+        determine_direction_drawing(first_pos, *first_line.last().unwrap())
+    } else {
+        fallback_dir
+    };
+
+    if let Some(c) = marker_char(marker, get_opposite(dir), use_ascii) {
+        canvas.put(first_pos.x, first_pos.y, c);
+    }
+}
+
+/// Character for an edge marker; `dir` is the direction an arrow points in
+fn marker_char(marker: EdgeMarker, dir: Direction, use_ascii: bool) -> Option<char> {
+    match marker {
+        EdgeMarker::None => None,
+        EdgeMarker::Circle => Some('o'),
+        EdgeMarker::Cross => Some('x'),
+        EdgeMarker::Arrow if !use_ascii => Some(match dir {
             d if d == UP => '▲',
             d if d == DOWN => '▼',
             d if d == LEFT => '◄',
@@ -172,32 +311,25 @@ pub fn draw_arrow_head(
             d if d == LOWER_RIGHT => '◢',
             d if d == LOWER_LEFT => '◣',
             _ => '●',
-        }
-    } else {
-        match dir {
+        }),
+        EdgeMarker::Arrow => Some(match dir {
             d if d == UP => '^',
             d if d == DOWN => 'v',
             d if d == LEFT => '<',
             d if d == RIGHT => '>',
             _ => '*',
-        }
-    };
-
-    set_char(canvas, last_pos.x, last_pos.y, c);
+        }),
+    }
 }
 
 /// Draw corner characters at path bends
-pub fn draw_corners(graph: &AsciiGraph, path: &[GridCoord]) -> Canvas {
-    let mut canvas = copy_canvas(&graph.canvas);
-
-    for idx in 1..path.len().saturating_sub(1) {
-        let prev = path[idx - 1];
-        let coord = path[idx];
-        let next = path[idx + 1];
+pub fn draw_corners(graph: &AsciiGraph, points: &[DrawingCoord]) -> Layer {
+    let mut layer = Layer::default();
 
-        let dc = grid_to_drawing_coord(graph, coord, None);
-        let prev_dir = determine_direction_drawing(grid_to_drawing_coord(graph, prev, None), dc);
-        let next_dir = determine_direction_drawing(dc, grid_to_drawing_coord(graph, next, None));
+    for idx in 1..points.len().saturating_sub(1) {
+        let dc = points[idx];
+        let prev_dir = determine_direction_drawing(points[idx - 1], dc);
+        let next_dir = determine_direction_drawing(dc, points[idx + 1]);
 
         let corner = if graph.config.use_ascii {
             '+'
@@ -205,10 +337,10 @@ pub fn draw_corners(graph: &AsciiGraph, path: &[GridCoord]) -> Canvas {
             determine_corner(prev_dir, next_dir)
         };
 
-        set_char(&mut canvas, dc.x, dc.y, corner);
+        layer.put(dc.x, dc.y, corner);
     }
 
-    canvas
+    layer
 }
 
 /// Determine the correct corner character for a path bend
@@ -245,121 +377,136 @@ fn determine_corner(from_dir: Direction, to_dir: Direction) -> char {
 /// Draw the path lines for an edge
 fn draw_path(
     graph: &AsciiGraph,
-    path: &[GridCoord],
-) -> (Canvas, Vec<Vec<DrawingCoord>>, Vec<Direction>) {
-    let mut canvas = copy_canvas(&graph.canvas);
+    points: &[DrawingCoord],
+) -> (Layer, Vec<Vec<DrawingCoord>>, Vec<Direction>) {
+    let mut layer = Layer::default();
     let mut lines_drawn: Vec<Vec<DrawingCoord>> = Vec::new();
     let mut line_dirs: Vec<Direction> = Vec::new();
 
-    if path.is_empty() {
-        return (canvas, lines_drawn, line_dirs);
-    }
-
-    let mut previous_coord = path[0];
-
-    for next_coord in &path[1..] {
-        let next_coord = *next_coord;
-        let prev_dc = grid_to_drawing_coord(graph, previous_coord, None);
-        let next_dc = grid_to_drawing_coord(graph, next_coord, None);
-
+    for pair in points.windows(2) {
+        let (prev_dc, next_dc) = (pair[0], pair[1]);
         if prev_dc == next_dc {
-            previous_coord = next_coord;
             continue;
         }
 
         let dir = determine_direction_drawing(prev_dc, next_dc);
-        let mut segment = draw_line(&mut canvas, prev_dc, next_dc, 1, -1, graph.config.use_ascii);
+        let mut segment = draw_line(&mut layer, prev_dc, next_dc, 1, -1, graph.config.use_ascii);
         if segment.is_empty() {
             segment.push(prev_dc);
         }
         lines_drawn.push(segment);
         line_dirs.push(dir);
-        previous_coord = next_coord;
     }
 
-    (canvas, lines_drawn, line_dirs)
+    (layer, lines_drawn, line_dirs)
 }
 
 /// Draw a complete arrow (edge) returning separate layer canvases
 /// Returns (path, corners, arrowhead, label)
-pub fn draw_arrow_layers(graph: &AsciiGraph, edge_idx: usize) -> (Canvas, Canvas, Canvas, Canvas) {
+pub fn draw_arrow_layers(graph: &AsciiGraph, edge_idx: usize) -> (Layer, Layer, Layer, Layer) {
     let edge = &graph.edges[edge_idx];
-    let empty = copy_canvas(&graph.canvas);
 
     if edge.path.is_empty() {
-        return (empty.clone(), empty.clone(), empty.clone(), empty);
+        return Default::default();
     }
 
-    let label_canvas = draw_arrow_label(graph, edge_idx);
-    let (path_canvas, lines_drawn, line_dirs) = draw_path(graph, &edge.path);
+    let points = path_to_drawing(graph, edge);
+    let mut label_layer = draw_arrow_label(graph, edge_idx, &points);
+    let (path_layer, lines_drawn, line_dirs) = draw_path(graph, &points);
+    draw_end_labels(&mut label_layer, edge, &lines_drawn);
 
     // Corners
-    let corners_canvas = draw_corners(graph, &edge.path);
+    let corners_layer = draw_corners(graph, &points);
 
-    // Arrowhead
-    let mut arrow_head_canvas = copy_canvas(&graph.canvas);
+    // Arrowheads (or circle/cross markers) at either end
+    let mut arrow_head_layer = Layer::default();
     if !lines_drawn.is_empty() {
+        let first_line = &lines_drawn[0];
+        let first_dir = line_dirs.first().copied().unwrap_or(DOWN);
+        draw_start_marker(
+            &mut arrow_head_layer,
+            first_line,
+            first_dir,
+            edge.start_marker,
+            graph.config.use_ascii,
+        );
+
         let last_line = lines_drawn.last().unwrap();
         let fallback_dir = line_dirs.last().copied().unwrap_or(DOWN);
-        draw_arrow_head(
-            &mut arrow_head_canvas,
+        draw_end_marker(
+            &mut arrow_head_layer,
             last_line,
             fallback_dir,
+            edge.end_marker,
             graph.config.use_ascii,
         );
     }
 
     // Also add box start junction to corners canvas in Unicode mode
-    let mut combined_corners = corners_canvas;
-    if !graph.config.use_ascii && !lines_drawn.is_empty() && edge.path.len() > 1 {
+    // (edges with a start marker are drawn like edge ends, without a junction)
+    let mut combined_corners = corners_layer;
+    if !graph.config.use_ascii
+        && edge.start_marker == EdgeMarker::None
+        && !lines_drawn.is_empty()
+        && edge.path.len() > 1
+    {
         let first_line = &lines_drawn[0];
         if !first_line.is_empty() {
             let from = first_line[0];
-            let dir = determine_direction_drawing(
-                grid_to_drawing_coord(graph, edge.path[0], None),
-                grid_to_drawing_coord(graph, edge.path[1], None),
-            );
+            let dir = determine_direction_drawing(points[0], points[1]);
 
-            if dir == UP {
-                set_char(&mut combined_corners, from.x, from.y + 1, '┴');
+            let junction = if dir == UP {
+                Some((from.x, from.y + 1, '┴'))
             } else if dir == DOWN {
-                set_char(&mut combined_corners, from.x, from.y - 1, '┬');
+                Some((from.x, from.y - 1, '┬'))
             } else if dir == LEFT {
-                set_char(&mut combined_corners, from.x + 1, from.y, '┤');
+                Some((from.x + 1, from.y, '┤'))
             } else if dir == RIGHT {
-                set_char(&mut combined_corners, from.x - 1, from.y, '├');
+                Some((from.x - 1, from.y, '├'))
+            } else {
+                None
+            };
+            // Only join straight borders; shape delimiters like `<` or `(` stay
+            if let Some((x, y, c)) = junction {
+                match get_char(&graph.canvas, x, y) {
+                    '─' | '│' => combined_corners.put(x, y, c),
+                    '═' | '║' => combined_corners.put(x, y, double_junction(c)),
+                    _ => {}
+                }
             }
         }
     }
 
-    (
-        path_canvas,
-        combined_corners,
-        arrow_head_canvas,
-        label_canvas,
-    )
+    (path_layer, combined_corners, arrow_head_layer, label_layer)
 }
 
 /// Legacy wrapper for draw_arrow
 pub fn draw_arrow(graph: &AsciiGraph, edge_idx: usize) -> Vec<Canvas> {
     let (path, corners, arrowhead, label) = draw_arrow_layers(graph, edge_idx);
-    vec![path, corners, arrowhead, label]
+    let (width, height) = get_canvas_size(&graph.canvas);
+    [path, corners, arrowhead, label]
+        .iter()
+        .map(|layer| layer.to_canvas(width, height, graph.config.use_ascii))
+        .collect()
 }
 
 /// Draw an edge label
-fn draw_arrow_label(graph: &AsciiGraph, edge_idx: usize) -> Canvas {
-    let mut canvas = copy_canvas(&graph.canvas);
+fn draw_arrow_label(graph: &AsciiGraph, edge_idx: usize, points: &[DrawingCoord]) -> Layer {
+    let mut layer = Layer::default();
     let edge = &graph.edges[edge_idx];
 
     if edge.text.is_empty() {
-        return canvas;
+        return layer;
     }
 
     // Use label_line if available, otherwise fall back to path midpoint
-    let (center_x, center_y) = if edge.label_line.len() >= 2 {
-        // Use the label_line segment
-        let dc1 = grid_to_drawing_coord(graph, edge.label_line[0], None);
-        let dc2 = grid_to_drawing_coord(graph, edge.label_line[1], None);
+    let label_segment = edge
+        .path
+        .windows(2)
+        .position(|pair| edge.label_line.len() >= 2 && pair == &edge.label_line[..2]);
+    let (center_x, center_y) = if let Some(i) = label_segment {
+        // Use the label_line segment, on its track
+        let (dc1, dc2) = (points[i], points[i + 1]);
         let min_x = dc1.x.min(dc2.x);
         let max_x = dc1.x.max(dc2.x);
         let min_y = dc1.y.min(dc2.y);
@@ -367,21 +514,68 @@ fn draw_arrow_label(graph: &AsciiGraph, edge_idx: usize) -> Canvas {
         (min_x + (max_x - min_x) / 2, min_y + (max_y - min_y) / 2)
     } else if edge.path.len() >= 2 {
         // Fall back to path midpoint
-        let mid_idx = edge.path.len() / 2;
-        let dc = grid_to_drawing_coord(graph, edge.path[mid_idx], None);
+        let dc = points[points.len() / 2];
         (dc.x, dc.y)
     } else {
-        return canvas;
+        return layer;
     };
 
     // Draw label centered ON the line (not above)
     let label = &edge.text;
-    let start_x = center_x - (label.len() as i32) / 2;
-    for (i, c) in label.chars().enumerate() {
-        set_char(&mut canvas, start_x + i as i32, center_y, c);
+    let start_x = center_x - (text_width(label) as i32) / 2;
+    draw_text(&mut layer, start_x, center_y, label);
+
+    layer
+}
+
+/// Draw the source- and target-side labels of an edge.
+/// On horizontal ends the text sits just above the line, against the node; on
+/// vertical ends it sits to the right of the line.
+fn draw_end_labels(canvas: &mut Layer, edge: &AsciiEdge, lines_drawn: &[Vec<DrawingCoord>]) {
+    let (Some(first_line), Some(last_line)) = (lines_drawn.first(), lines_drawn.last()) else {
+        return;
+    };
+
+    if let (Some(&start), Some(&end)) = (first_line.first(), first_line.last()) {
+        let dir = determine_direction_drawing(start, end);
+        draw_end_label(canvas, &edge.start_text, start, dir, dir == LEFT);
     }
+    if let (Some(&start), Some(&end)) = (last_line.first(), last_line.last()) {
+        let dir = determine_direction_drawing(start, end);
+        // Keep clear of the arrowhead
+        let anchor = if dir == RIGHT {
+            DrawingCoord::new(end.x - 1, end.y)
+        } else if dir == LEFT {
+            DrawingCoord::new(end.x + 1, end.y)
+        } else {
+            end
+        };
+        draw_end_label(canvas, &edge.end_text, anchor, dir, dir == RIGHT);
+    }
+}
 
-    canvas
+/// Draw one end label next to `anchor`, the line cell closest to the node.
+/// `dir` is the direction of the line there; on horizontal lines `grow_left`
+/// makes the text end at the anchor instead of starting there.
+fn draw_end_label(
+    canvas: &mut Layer,
+    text: &str,
+    anchor: DrawingCoord,
+    dir: Direction,
+    grow_left: bool,
+) {
+    if text.is_empty() {
+        return;
+    }
+    let len = text_width(text) as i32;
+    let (x, y) = if dir == UP || dir == DOWN {
+        (anchor.x + 2, anchor.y)
+    } else if grow_left {
+        (anchor.x - len + 1, anchor.y - 1)
+    } else {
+        (anchor.x, anchor.y - 1)
+    };
+    draw_text(canvas, x, y, text);
 }
 
 /// Draw a subgraph border
@@ -430,15 +624,75 @@ pub fn draw_subgraph_label(canvas: &mut Canvas, min_x: i32, min_y: i32, max_x: i
 
     let width = max_x - min_x;
     let label_y = min_y + 1; // Second row (inside the border)
-    let mut label_x = min_x + width / 2 - (label.len() as i32) / 2;
+    let mut label_x = min_x + width / 2 - (text_width(label) as i32) / 2;
     if label_x < min_x + 1 {
         label_x = min_x + 1;
     }
 
-    for (i, c) in label.chars().enumerate() {
-        if (label_x + i as i32) < max_x {
-            set_char(canvas, label_x + i as i32, label_y, c);
+    let room = (max_x - label_x).max(0) as usize;
+    draw_text(canvas, label_x, label_y, truncate_to_width(label, room));
+}
+
+/// A relationship of a class or entity with itself, drawn as a small loop
+/// out of the right side of its box and back in lower down:
+///
+/// ```text
+/// │ Animal ├──┐ knows
+/// │        │◀─┘
+/// ```
+pub struct SelfLoop<'a> {
+    /// What the loop starts with at the border, such as a marker; may be empty
+    pub start: &'a str,
+    /// What the loop ends with at the border
+    pub end: &'a str,
+    /// Horizontal line character
+    pub line: char,
+    /// Text right of the loop, on its first row
+    pub label: &'a str,
+}
+
+impl SelfLoop<'_> {
+    /// Columns from the border to the loop's corner, with at least one line
+    /// character past the longer end
+    fn reach(&self) -> i32 {
+        text_width(self.start).max(text_width(self.end)) as i32 + 2
+    }
+
+    /// Columns the loop and its label take right of the box
+    pub fn width(&self) -> usize {
+        let label = match text_width(self.label) {
+            0 => 0,
+            width => width + 1,
+        };
+        self.reach() as usize + label
+    }
+
+    /// Draw the loop right of the border at column `right`, leaving at row
+    /// `from_y` and coming back at the lower row `to_y`
+    pub fn draw(&self, canvas: &mut Canvas, right: i32, from_y: i32, to_y: i32, use_ascii: bool) {
+        let (v_line, top, bottom, junction) = if use_ascii {
+            ('|', '+', '+', None)
+        } else {
+            ('│', '┐', '┘', Some('├'))
+        };
+        let corner_x = right + self.reach();
+        for (y, end, corner) in [(from_y, self.start, top), (to_y, self.end, bottom)] {
+            if end.is_empty() {
+                if let Some(junction) = junction {
+                    let border = get_char(canvas, right, y);
+                    set_char(canvas, right, y, merge_junctions(border, junction));
+                }
+            }
+            draw_text(canvas, right + 1, y, end);
+            for x in (right + 1 + text_width(end) as i32)..corner_x {
+                set_char(canvas, x, y, self.line);
+            }
+            set_char(canvas, corner_x, y, corner);
+        }
+        for y in (from_y + 1)..to_y {
+            set_char(canvas, corner_x, y, v_line);
         }
+        draw_text(canvas, corner_x + 2, from_y, self.label);
     }
 }
 
@@ -504,38 +758,33 @@ pub fn draw_graph(graph: &mut AsciiGraph) {
             Some(dc) => dc,
             None => grid_to_drawing_coord_topleft(graph, gc),
         };
-        graph.canvas = merge_canvases(&graph.canvas, offset, use_ascii, &[&box_canvas]);
+        merge_into(&mut graph.canvas, offset, use_ascii, &[&box_canvas]);
         graph.nodes[i].drawn = true;
     }
 
     // 3-7. Collect all edge layers separately, then merge them in order
     // This ensures corners appear on top of paths, arrowheads on top of corners, etc.
-    let mut path_canvases: Vec<Canvas> = Vec::new();
-    let mut corner_canvases: Vec<Canvas> = Vec::new();
-    let mut arrowhead_canvases: Vec<Canvas> = Vec::new();
-    let mut label_canvases: Vec<Canvas> = Vec::new();
+    let mut path_layers: Vec<Layer> = Vec::new();
+    let mut corner_layers: Vec<Layer> = Vec::new();
+    let mut arrowhead_layers: Vec<Layer> = Vec::new();
+    let mut label_layers: Vec<Layer> = Vec::new();
 
     for i in 0..graph.edges.len() {
         let (path_c, corner_c, arrowhead_c, label_c) = draw_arrow_layers(graph, i);
-        path_canvases.push(path_c);
-        corner_canvases.push(corner_c);
-        arrowhead_canvases.push(arrowhead_c);
-        label_canvases.push(label_c);
+        path_layers.push(path_c);
+        corner_layers.push(corner_c);
+        arrowhead_layers.push(arrowhead_c);
+        label_layers.push(label_c);
     }
 
     // Merge layers in order
-    let zero = DrawingCoord::new(0, 0);
-    for pc in &path_canvases {
-        graph.canvas = merge_canvases(&graph.canvas, zero, use_ascii, &[pc]);
-    }
-    for cc in &corner_canvases {
-        graph.canvas = merge_canvases(&graph.canvas, zero, use_ascii, &[cc]);
-    }
-    for ac in &arrowhead_canvases {
-        graph.canvas = merge_canvases(&graph.canvas, zero, use_ascii, &[ac]);
-    }
-    for lc in &label_canvases {
-        graph.canvas = merge_canvases(&graph.canvas, zero, use_ascii, &[lc]);
+    for layer in path_layers
+        .iter()
+        .chain(&corner_layers)
+        .chain(&arrowhead_layers)
+        .chain(&label_layers)
+    {
+        layer.merge_onto(&mut graph.canvas, use_ascii);
     }
 
     // 8. Draw subgraph labels LAST (top layer)
@@ -1,8 +1,9 @@
 //! ER diagram ASCII rendering
 
-use super::canvas::{canvas_to_string, draw_text, mk_canvas, set_char};
+use super::canvas::{canvas_to_string, draw_text, mk_canvas, set_char, text_width};
+use super::draw::SelfLoop;
 use super::types::AsciiConfig;
-use crate::types::{Cardinality, ErDiagram};
+use crate::types::{Cardinality, ErDiagram, ErRelationship};
 
 /// Render an ER diagram to ASCII
 pub fn render_er_ascii(diagram: &ErDiagram, config: &AsciiConfig) -> Result<String, String> {
@@ -95,6 +96,16 @@ fn render_general_er(diagram: &ErDiagram, config: &AsciiConfig) -> Result<String
         .map(|id| entity_for(id).map(format_entity_attrs).unwrap_or_default())
         .collect();
 
+    // Relationships of an entity with itself, drawn as a loop right of its box
+    let loops: Vec<Option<ErLoop>> = ordered_ids
+        .iter()
+        .map(|id| ErLoop::new(diagram, id, use_ascii))
+        .collect();
+    let loop_widths: Vec<usize> = loops
+        .iter()
+        .map(|l| l.as_ref().map_or(0, |l| l.self_loop().width()))
+        .collect();
+
     // Find relationship between two adjacent entities (if any)
     let rel_between = |id1: &str, id2: &str| -> Option<&crate::types::ErRelationship> {
         diagram.relationships.iter().find(|r| {
@@ -136,9 +147,10 @@ fn render_general_er(diagram: &ErDiagram, config: &AsciiConfig) -> Result<String
 
             // The label (with padding) must fit over the line portion only
             let label_padded = format!(" {} ", rel.label);
-            let label_padded_len = label_padded.chars().count();
-            // Minimum 2 line chars (the base "--" or "..")
-            let line_len = label_padded_len.max(2);
+            let label_padded_len = text_width(&label_padded);
+            // Minimum 2 line chars (the base "--" or ".."), and long enough
+            // to pass under the loop of the left entity
+            let line_len = label_padded_len.max(2).max(loop_widths[i]);
 
             // Build the connector: card1 + line_chars + card2
             let line_fill: String = std::iter::repeat_n(fill_char, line_len).collect();
@@ -164,7 +176,7 @@ fn render_general_er(diagram: &ErDiagram, config: &AsciiConfig) -> Result<String
             gaps.push(Gap {
                 label: String::new(),
                 connector: String::new(),
-                width: 6,
+                width: 6 + loop_widths[i],
             });
         }
     }
@@ -174,18 +186,27 @@ fn render_general_er(diagram: &ErDiagram, config: &AsciiConfig) -> Result<String
         .iter()
         .enumerate()
         .map(|(idx, id)| {
-            let label_len = label_for(id).len();
-            let attr_max = attrs_for[idx].iter().map(|s| s.len()).max().unwrap_or(0);
+            let label_len = text_width(&label_for(id));
+            let attr_max = attrs_for[idx]
+                .iter()
+                .map(|s| text_width(s))
+                .max()
+                .unwrap_or(0);
             label_len.max(attr_max) + 4
         })
         .collect();
 
     // Compute entity box heights
-    // No attrs: 3 rows (top, name, bottom)
+    // No attrs: 3 rows (top, name, bottom), 5 with a loop
     // With attrs: 3 + num_attrs + 1 rows (top, name, divider, attrs..., bottom)
     let entity_heights: Vec<usize> = attrs_for
         .iter()
-        .map(|attrs| if attrs.is_empty() { 3 } else { 4 + attrs.len() })
+        .zip(&loops)
+        .map(|(attrs, l)| match (attrs.is_empty(), l.is_some()) {
+            (true, false) => 3,
+            (true, true) => 5,
+            (false, _) => 4 + attrs.len(),
+        })
         .collect();
 
     // Compute positions — each entity box is placed after the previous box + gap
@@ -198,7 +219,10 @@ fn render_general_er(diagram: &ErDiagram, config: &AsciiConfig) -> Result<String
         }
     }
 
-    let total_w = positions.last().unwrap_or(&0) + entity_widths.last().unwrap_or(&0) + 3;
+    let total_w = positions.last().unwrap_or(&0)
+        + entity_widths.last().unwrap_or(&0)
+        + loop_widths.last().unwrap_or(&0)
+        + 3;
     let max_height = *entity_heights.iter().max().unwrap_or(&3);
     let total_h = max_height + 1;
 
@@ -213,7 +237,8 @@ fn render_general_er(diagram: &ErDiagram, config: &AsciiConfig) -> Result<String
 
         if attrs.is_empty() {
             // Simple 3-row box: top, name, bottom
-            draw_simple_box(&mut canvas, x, 0, w, 3, &label, use_ascii);
+            let h = entity_heights[i] as i32;
+            draw_simple_box(&mut canvas, x, 0, w, h, &label, use_ascii);
         } else {
             // Box with attributes: top, name, divider, attrs..., bottom
             // Top border
@@ -261,11 +286,65 @@ fn render_general_er(diagram: &ErDiagram, config: &AsciiConfig) -> Result<String
             // Row 1 (name row): draw the connector
             draw_text(&mut canvas, gap_x, 1, &gap.connector);
         }
+
+        // Draw the loop below the connector, between the name row and the bottom border
+        if let Some(l) = &loops[i] {
+            let bottom = entity_heights[i] as i32 - 1;
+            l.self_loop()
+                .draw(&mut canvas, x + w - 1, 2, bottom - 1, use_ascii);
+        }
     }
 
     Ok(canvas_to_string(&canvas))
 }
 
+/// Relationships of an entity with itself, merged into one loop
+struct ErLoop {
+    start: &'static str,
+    end: &'static str,
+    line: char,
+    label: String,
+}
+
+impl ErLoop {
+    /// The loop of entity `id`, if it has relationships with itself. The
+    /// cardinalities are those of the first one; the labels of all of them.
+    fn new(diagram: &ErDiagram, id: &str, use_ascii: bool) -> Option<Self> {
+        let rels: Vec<&ErRelationship> = diagram
+            .relationships
+            .iter()
+            .filter(|r| r.entity1 == id && r.entity2 == id)
+            .collect();
+        let first = rels.first()?;
+        let line = match (first.identifying, use_ascii) {
+            (true, true) => '-',
+            (true, false) => '─',
+            (false, _) => '.',
+        };
+        let label = rels
+            .iter()
+            .map(|r| r.label.as_str())
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ");
+        Some(Self {
+            start: cardinality_to_str_left(first.cardinality1, use_ascii),
+            end: cardinality_to_str_left(first.cardinality2, use_ascii),
+            line,
+            label,
+        })
+    }
+
+    fn self_loop(&self) -> SelfLoop<'_> {
+        SelfLoop {
+            start: self.start,
+            end: self.end,
+            line: self.line,
+            label: &self.label,
+        }
+    }
+}
+
 /// Left-side cardinality symbol (entity is to the left of the connector)
 fn cardinality_to_str_left(card: Cardinality, use_ascii: bool) -> &'static str {
     if use_ascii {
@@ -326,11 +405,13 @@ fn draw_simple_box(
     }
     set_char(canvas, x + w - 1, y, tr);
 
-    // Middle row
-    set_char(canvas, x, y + 1, v_line);
-    let label_x = x + (w - label.len() as i32) / 2;
+    // Middle rows, the label on the first
+    for row in (y + 1)..(y + h - 1) {
+        set_char(canvas, x, row, v_line);
+        set_char(canvas, x + w - 1, row, v_line);
+    }
+    let label_x = x + (w - text_width(label) as i32) / 2;
     draw_text(canvas, label_x, y + 1, label);
-    set_char(canvas, x + w - 1, y + 1, v_line);
 
     // Bottom border
     set_char(canvas, x, y + h - 1, bl);
@@ -1,19 +1,32 @@
 //! Flowchart ASCII rendering
 
-use super::canvas::canvas_to_string;
+use super::canvas::{block_width, canvas_to_string};
 use super::draw::draw_graph;
-use super::grid::create_mapping;
-use super::types::{AsciiConfig, AsciiEdge, AsciiGraph, AsciiNode, AsciiSubgraph};
-use crate::types::{MermaidGraph, MermaidSubgraph};
+use super::grid::{allocate_tracks, create_mapping, stretch_lanes};
+use super::types::{AsciiConfig, AsciiEdge, AsciiGraph, AsciiNode, AsciiSubgraph, GraphDirection};
+use crate::types::{Direction, MermaidGraph, MermaidSubgraph};
+
+/// Whether a node style asks for a heavier border than usual: a
+/// `stroke-width` of 2px or more
+fn is_emphasized(style: &std::collections::HashMap<String, String>) -> bool {
+    style
+        .get("stroke-width")
+        .and_then(|width| width.trim().trim_end_matches("px").parse::<f64>().ok())
+        .is_some_and(|width| width >= 2.0)
+}
 
 /// Convert MermaidGraph to AsciiGraph
 fn convert_to_ascii_graph(parsed: &MermaidGraph, config: &AsciiConfig) -> AsciiGraph {
     let mut graph = AsciiGraph::new(config.clone());
+    graph.bundle_edges = parsed.config.bundle_edges();
+    graph.swimlanes = parsed.config.swimlanes();
 
     // Build node list preserving insertion order from parser
     for (index, id) in parsed.node_order.iter().enumerate() {
         if let Some(m_node) = parsed.nodes.get(id) {
-            let ascii_node = AsciiNode::new(id.to_string(), m_node.label.clone(), index);
+            let mut ascii_node = AsciiNode::new(id.to_string(), m_node.label.clone(), index);
+            ascii_node.shape = m_node.shape;
+            ascii_node.emphasized = is_emphasized(&parsed.node_style(id));
             graph.nodes.push(ascii_node);
         }
     }
@@ -32,7 +45,17 @@ fn convert_to_ascii_graph(parsed: &MermaidGraph, config: &AsciiConfig) -> AsciiG
             id_to_idx.get(m_edge.source.as_str()),
             id_to_idx.get(m_edge.target.as_str()),
         ) {
-            let edge = AsciiEdge::new(from_idx, to_idx, m_edge.label.clone().unwrap_or_default());
+            let mut edge =
+                AsciiEdge::new(from_idx, to_idx, m_edge.label.clone().unwrap_or_default());
+            edge.start_marker = m_edge.start_marker;
+            edge.end_marker = m_edge.end_marker;
+            edge.min_length = m_edge.min_length;
+            edge.start_text = m_edge.start_label.clone().unwrap_or_default();
+            edge.end_text = m_edge.end_label.clone().unwrap_or_default();
+            edge.start_port = m_edge.start_port;
+            edge.end_port = m_edge.end_port;
+            edge.class = m_edge.class.clone();
+            edge.style = m_edge.style;
             graph.edges.push(edge);
         }
     }
@@ -57,6 +80,10 @@ fn convert_subgraph(
 ) -> usize {
     let mut sg = AsciiSubgraph::new(m_sg.label.clone());
     sg.parent_idx = parent_idx;
+    sg.direction = m_sg.direction.map(|d| match d {
+        Direction::LR | Direction::RL => GraphDirection::LR,
+        _ => GraphDirection::TD,
+    });
 
     // Resolve node references
     for node_id in &m_sg.node_ids {
@@ -196,7 +223,7 @@ fn calculate_subgraph_bounds(graph: &mut AsciiGraph) {
                 let box_width = if let Some(ref drawing) = node.drawing {
                     drawing.len() as i32 - 1
                 } else {
-                    let label_len = node.display_label.len() as i32;
+                    let label_len = block_width(&node.display_label) as i32;
                     label_len + 4 // border + padding
                 };
                 let box_height = if let Some(ref drawing) = node.drawing {
@@ -287,9 +314,60 @@ pub fn render_flowchart_ascii(parsed: &MermaidGraph, config: &AsciiConfig) -> St
     let mut graph = convert_to_ascii_graph(parsed, config);
 
     create_mapping(&mut graph);
+    allocate_tracks(&mut graph);
     calculate_subgraph_bounds(&mut graph);
+    if graph.swimlanes {
+        stretch_lanes(&mut graph);
+    }
     offset_drawing_for_subgraphs(&mut graph);
     draw_graph(&mut graph);
 
     canvas_to_string(&graph.canvas)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ascii::types::{Direction, GridCoord};
+    use crate::parser::parse_mermaid;
+    use crate::types::DiagramType;
+
+    type Routes = Vec<(Direction, Direction, Vec<GridCoord>)>;
+
+    fn routes(text: &str, padding_x: usize, padding_y: usize) -> Routes {
+        let parsed = match parse_mermaid(text).unwrap().diagram {
+            DiagramType::Flowchart(g) => g,
+            _ => panic!("expected flowchart"),
+        };
+        let config = AsciiConfig {
+            use_ascii: false,
+            padding_x,
+            padding_y,
+            box_border_padding: 1,
+            graph_direction: GraphDirection::TD,
+            sequence_header_every: 0,
+            sequence_max_gap: 0,
+            node_width: Default::default(),
+            label_align: None,
+            rounded_corners: true,
+        };
+        let mut graph = convert_to_ascii_graph(&parsed, &config);
+        create_mapping(&mut graph);
+        graph
+            .edges
+            .into_iter()
+            .map(|e| (e.start_dir, e.end_dir, e.path))
+            .collect()
+    }
+
+    #[test]
+    fn test_routing_does_not_depend_on_padding() {
+        let text =
+            "graph TD\n  A --> B\n  A --> C\n  B --> D\n  C --> D\n  D --> A\n  C --> E\n  E --> B";
+        let reference = routes(text, 5, 5);
+        for padding in [0, 1, 2, 3, 8, 13] {
+            assert_eq!(routes(text, padding, 5), reference);
+            assert_eq!(routes(text, 5, padding), reference);
+        }
+    }
+}
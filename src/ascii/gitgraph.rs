@@ -2,8 +2,13 @@
 //!
 //! Renders git graphs with proper branch/merge visualization.
 
-use crate::ascii::canvas::{canvas_to_string, draw_text, mk_canvas, set_char};
-use crate::types::{GitGraph, GitGraphDirection};
+use crate::ascii::canvas::{
+    canvas_to_string, cells_to_string, draw_text, get_char, mk_canvas, set_char, text_cells,
+    text_width,
+};
+use crate::ascii::types::Canvas;
+use crate::types::{GitCommit, GitGraph, GitGraphConfig, GitGraphDirection, GitMergeStyle};
+use indexmap::IndexMap;
 use std::collections::{HashMap, HashSet};
 
 /// Characters to use for rendering
@@ -34,6 +39,12 @@ impl GitChars {
     }
 }
 
+/// Directions a lane cell connects to, in the vertical layouts
+const LANE_N: u8 = 1;
+const LANE_S: u8 = 2;
+const LANE_E: u8 = 4;
+const LANE_W: u8 = 8;
+
 /// Render a GitGraph to ASCII/Unicode text
 pub fn render_gitgraph(graph: &GitGraph, use_ascii: bool) -> String {
     match graph.direction {
@@ -43,6 +54,33 @@ pub fn render_gitgraph(graph: &GitGraph, use_ascii: bool) -> String {
     }
 }
 
+/// Commit message shown after a commit's label, if messages are enabled
+fn message_suffix(commit: &GitCommit, config: &GitGraphConfig) -> String {
+    match commit_message(commit, config) {
+        Some(msg) if config.show_commit_label => format!(": {}", msg),
+        Some(msg) => format!(" {}", msg),
+        None => String::new(),
+    }
+}
+
+/// A commit's message, if messages are enabled
+fn commit_message<'a>(commit: &'a GitCommit, config: &GitGraphConfig) -> Option<&'a str> {
+    commit
+        .message
+        .as_deref()
+        .filter(|_| config.show_commit_message)
+}
+
+/// Columns a commit's label (including its message) takes on its branch row
+fn commit_label_len(commit: &GitCommit, config: &GitGraphConfig) -> usize {
+    let id_len = if commit.is_merge {
+        text_width(&commit.id) + 2
+    } else {
+        text_width(&commit.id)
+    };
+    id_len + text_width(&message_suffix(commit, config))
+}
+
 /// Render horizontal (left-to-right) git graph
 ///
 /// Expected output format:
@@ -63,39 +101,18 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
     } else {
         GitChars::unicode()
     };
+    // Fork and merge lines as vertical runs with square elbows, not diagonals
+    let orthogonal = !use_ascii && graph.config.merge_style == GitMergeStyle::Orthogonal;
+
+    let commits = graph.commit_index();
 
     // Step 1: Assign branches to rows, respecting order attribute
-    // Branches with order are sorted by order value
-    // Branches without order keep their creation order but come after ordered ones
-    // Exception: main branch always comes first if it has no order
-    let mut sorted_branches: Vec<_> = graph.branches.iter().enumerate().collect();
-    sorted_branches.sort_by(|(ia, a), (ib, b)| {
-        match (&a.order, &b.order) {
-            (Some(ao), Some(bo)) => ao.cmp(bo),
-            (Some(_), None) => {
-                // Ordered branch vs unordered: check if unordered is main
-                if b.name == "main" {
-                    std::cmp::Ordering::Greater
-                } else {
-                    std::cmp::Ordering::Less
-                }
-            }
-            (None, Some(_)) => {
-                // Unordered vs ordered: check if unordered is main
-                if a.name == "main" {
-                    std::cmp::Ordering::Less
-                } else {
-                    std::cmp::Ordering::Greater
-                }
-            }
-            (None, None) => ia.cmp(ib), // preserve creation order
-        }
-    });
+    let sorted_branches = graph.branches_by_order();
 
     let mut branch_rows: HashMap<String, usize> = HashMap::new();
 
     // Check if any branches have explicit order attributes
-    let has_ordered_branches = sorted_branches.iter().any(|(_, b)| b.order.is_some());
+    let has_ordered_branches = sorted_branches.iter().any(|b| b.order.is_some());
 
     // Identify which branches have tagged commits - these need extra rows above them
     let mut branches_with_tags: HashSet<String> = HashSet::new();
@@ -109,28 +126,19 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
     // These need an extra row for the horizontal bridge to route around the tag
     let mut branches_needing_bridge: HashSet<String> = HashSet::new();
     {
-        // Build a quick branch lookup for commits
-        let commit_branch: HashMap<&str, &str> = graph
-            .commits
-            .iter()
-            .map(|c| (c.id.as_str(), c.branch.as_str()))
-            .collect();
-
         for commit in &graph.commits {
             if commit.is_merge && commit.parent_ids.len() >= 2 {
                 if let Some(source_id) = commit.parent_ids.get(1) {
-                    if let Some(&source_branch) = commit_branch.get(source_id.as_str()) {
-                        // This is a merge from source_branch into commit.branch
+                    if let Some(source) = commits.get(source_id) {
+                        // This is a merge from source.branch into commit.branch
                         // Check if commit.branch has tags (the target branch)
                         if branches_with_tags.contains(&commit.branch) {
                             // Check if source branch is above target in order
                             // (i.e., the merge diagonal goes downward through the tag area)
-                            let source_idx = sorted_branches
-                                .iter()
-                                .position(|(_, b)| b.name == source_branch);
-                            let target_idx = sorted_branches
-                                .iter()
-                                .position(|(_, b)| b.name == commit.branch);
+                            let source_idx =
+                                sorted_branches.iter().position(|b| b.name == source.branch);
+                            let target_idx =
+                                sorted_branches.iter().position(|b| b.name == commit.branch);
                             if let (Some(si), Some(ti)) = (source_idx, target_idx) {
                                 if si < ti {
                                     // Downward merge into tagged branch - needs bridge
@@ -145,7 +153,7 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
     }
 
     let mut current_row = 0usize;
-    for (idx, (_, branch)) in sorted_branches.iter().enumerate() {
+    for (idx, branch) in sorted_branches.iter().enumerate() {
         if has_ordered_branches {
             let row = if idx == 0 {
                 0
@@ -185,7 +193,7 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
     let total_height = max_row + 1;
 
     // Step 2: Identify forks, merges, and cherry-picks
-    let mut fork_info: HashMap<String, String> = HashMap::new(); // first_commit_on_branch -> parent
+    let mut fork_info: IndexMap<String, String> = IndexMap::new(); // first_commit_on_branch -> parent
     let mut merge_info: HashMap<String, String> = HashMap::new(); // merge_commit -> source_commit
     let mut cherry_pick_info: HashMap<String, String> = HashMap::new(); // cherry_pick_commit -> source_commit
 
@@ -205,7 +213,7 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
 
         if !commit.parent_ids.is_empty() {
             if let Some(parent_id) = commit.parent_ids.first() {
-                if let Some(parent) = graph.commits.iter().find(|c| &c.id == parent_id) {
+                if let Some(parent) = commits.get(parent_id) {
                     if parent.branch != commit.branch {
                         fork_info.insert(commit.id.clone(), parent_id.clone());
                     }
@@ -214,15 +222,8 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
         }
     }
 
-    // Find branches that have cherry-picks - we won't draw fork lines for these
-    let branches_with_cherry_picks: HashSet<_> = cherry_pick_info
-        .keys()
-        .filter_map(|id| graph.commits.iter().find(|c| &c.id == id))
-        .map(|c| c.branch.clone())
-        .collect();
-
     // Group forks by parent to handle cascading forks (multiple branches from same commit)
-    let mut forks_by_parent: HashMap<String, Vec<String>> = HashMap::new();
+    let mut forks_by_parent: IndexMap<String, Vec<String>> = IndexMap::new();
     for (child_id, parent_id) in &fork_info {
         forks_by_parent
             .entry(parent_id.clone())
@@ -230,287 +231,157 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
             .push(child_id.clone());
     }
 
-    // Step 3: Layout - position all commits with consistent spacing
+    // Step 3: Layout - position all commits by their time index
+    // Commits are placed in the order they were made, each one right of the
+    // commit before it whatever its branch, so switching back and forth between
+    // branches never reorders them
     let mut commit_cols: HashMap<String, usize> = HashMap::new();
     let mut branch_next_col: HashMap<String, usize> = HashMap::new();
     let base_spacing = 3; // "---" between commits
+    let parallel = graph.config.parallel_commits;
+
+    // Every commit is a time slot of its own. With parallelCommits, commits are
+    // laid out one depth slot at a time instead, and every commit of a slot
+    // shares the slot's rightmost column
+    let slots: Vec<Vec<&GitCommit>> = if parallel {
+        let depths = graph.commit_depths();
+        let mut slots = vec![Vec::new(); depths.iter().max().map_or(0, |d| d + 1)];
+        for (commit, &depth) in graph.commits.iter().zip(&depths) {
+            slots[depth].push(commit);
+        }
+        slots
+    } else {
+        graph.commits.iter().map(|c| vec![c]).collect()
+    };
+    let mut slot_start = 0;
 
-    for commit in &graph.commits {
-        let label_len = if commit.is_merge {
-            commit.id.len() + 2
-        } else {
-            commit.id.len()
-        };
+    for slot in &slots {
+        for &commit in slot {
+            let label_len = commit_label_len(commit, &graph.config);
 
-        // Start with branch's current column
-        let mut col = branch_next_col.get(&commit.branch).copied().unwrap_or(0);
+            // Start with branch's current column
+            let mut col = branch_next_col.get(&commit.branch).copied().unwrap_or(0);
 
-        // If forking from another branch, position based on diagonal distance
-        if let Some(parent_id) = fork_info.get(&commit.id) {
-            if let Some(&parent_col) = commit_cols.get(parent_id) {
-                if let Some(parent) = graph.commits.iter().find(|c| &c.id == parent_id) {
-                    let parent_row = branch_rows[&parent.branch];
-                    let child_row = branch_rows[&commit.branch];
-                    let parent_len = if parent.is_merge {
-                        parent.id.len() + 2
-                    } else {
-                        parent.id.len()
-                    };
-
-                    // Check if this is part of a cascading fork (multiple branches from same parent)
-                    let siblings = forks_by_parent.get(parent_id).map(|v| v.len()).unwrap_or(1);
-
-                    let row_diff = child_row.abs_diff(parent_row);
-
-                    let fork_col = if has_ordered_branches {
-                        // For ordered branches: column = parent_end + diagonal distance
-                        parent_col + parent_len + row_diff.saturating_sub(1)
-                    } else if siblings > 1 {
-                        // Cascading fork: multiple branches from same parent
-                        // Find the maximum row among all siblings
-                        let max_sibling_row = forks_by_parent
-                            .get(parent_id)
-                            .map(|sibs| {
-                                sibs.iter()
-                                    .filter_map(|sib_id| {
-                                        graph
-                                            .commits
-                                            .iter()
-                                            .find(|c| &c.id == sib_id)
-                                            .map(|c| branch_rows[&c.branch])
-                                    })
-                                    .max()
-                                    .unwrap_or(child_row)
-                            })
-                            .unwrap_or(child_row);
-
-                        if child_row == max_sibling_row {
-                            // This is the furthest branch - position at diagonal end
-                            parent_col + parent_len + row_diff - 1
+            // If forking from another branch, position based on diagonal distance
+            if let Some(parent_id) = fork_info.get(&commit.id) {
+                if let Some(&parent_col) = commit_cols.get(parent_id) {
+                    if let Some(parent) = commits.get(parent_id) {
+                        let parent_row = branch_rows[&parent.branch];
+                        let child_row = branch_rows[&commit.branch];
+                        let parent_len = commit_label_len(parent, &graph.config);
+
+                        // Check if this is part of a cascading fork (multiple branches from same parent)
+                        let siblings = forks_by_parent.get(parent_id).map(|v| v.len()).unwrap_or(1);
+
+                        let row_diff = child_row.abs_diff(parent_row);
+
+                        let fork_col = if has_ordered_branches {
+                            // For ordered branches: column = parent_end + diagonal distance
+                            parent_col + parent_len + row_diff.saturating_sub(1)
+                        } else if siblings > 1 {
+                            // Cascading fork: multiple branches from same parent
+                            // Find the maximum row among all siblings
+                            let max_sibling_row = forks_by_parent
+                                .get(parent_id)
+                                .map(|sibs| {
+                                    sibs.iter()
+                                        .filter_map(|sib_id| {
+                                            commits.get(sib_id).map(|c| branch_rows[&c.branch])
+                                        })
+                                        .max()
+                                        .unwrap_or(child_row)
+                                })
+                                .unwrap_or(child_row);
+
+                            if child_row == max_sibling_row {
+                                // This is the furthest branch - position at diagonal end
+                                parent_col + parent_len + row_diff - 1
+                            } else {
+                                // This branch is above furthest - needs horizontal connection
+                                parent_col + parent_len + row_diff + 3
+                            }
                         } else {
-                            // This branch is above furthest - needs horizontal connection
-                            parent_col + parent_len + row_diff + 3
-                        }
-                    } else {
-                        // Single fork: diagonal advances once per intermediate row
-                        parent_col + parent_len + row_diff - 1
-                    };
-                    col = col.max(fork_col);
+                            // Single fork: diagonal advances once per intermediate row
+                            parent_col + parent_len + row_diff - 1
+                        };
+                        col = col.max(fork_col);
+                    }
                 }
             }
-        }
 
-        // If this is a merge, position after source branch end + merge diagonal
-        if let Some(source_id) = merge_info.get(&commit.id) {
-            if let Some(&source_col) = commit_cols.get(source_id) {
-                if let Some(source) = graph.commits.iter().find(|c| &c.id == source_id) {
-                    let source_row = branch_rows[&source.branch];
-                    let commit_row = branch_rows[&commit.branch];
-                    let source_len = if source.is_merge {
-                        source.id.len() + 2
-                    } else {
-                        source.id.len()
-                    };
-                    let row_diff = source_row.abs_diff(commit_row);
-                    // Diagonal spans row_diff-1 intermediate rows, landing at the target
-                    let merge_col = source_col + source_len + row_diff.max(1) - 1;
-                    col = col.max(merge_col);
+            // If this is a merge, position after source branch end + merge diagonal
+            if let Some(source_id) = merge_info.get(&commit.id) {
+                if let Some(&source_col) = commit_cols.get(source_id) {
+                    if let Some(source) = commits.get(source_id) {
+                        let source_row = branch_rows[&source.branch];
+                        let commit_row = branch_rows[&commit.branch];
+                        let source_len = commit_label_len(source, &graph.config);
+                        let row_diff = source_row.abs_diff(commit_row);
+                        // Diagonal spans row_diff-1 intermediate rows, landing at the target
+                        let merge_col = source_col + source_len + row_diff.max(1) - 1;
+                        col = col.max(merge_col);
+                    }
                 }
             }
-        }
 
-        // For cherry-picks: position at the source commit's column + offset for diagonal
-        if let Some(source_id) = cherry_pick_info.get(&commit.id) {
-            if let Some(&source_col) = commit_cols.get(source_id) {
-                if let Some(source) = graph.commits.iter().find(|c| &c.id == source_id) {
-                    let source_row = branch_rows[&source.branch];
-                    let cherry_row = branch_rows[&commit.branch];
-                    let source_len = if source.is_merge {
-                        source.id.len() + 2
-                    } else {
-                        source.id.len()
-                    };
-
-                    // Position after source + diagonal distance
-                    // Diagonal advances (row_diff - 1) columns (last step lands on target row)
-                    let row_diff = cherry_row.abs_diff(source_row);
-                    let cherry_col = source_col + source_len + row_diff - 1;
-                    col = col.max(cherry_col);
+            // For cherry-picks: position at the source commit's column + offset for diagonal
+            if let Some(source_id) = cherry_pick_info.get(&commit.id) {
+                if let Some(&source_col) = commit_cols.get(source_id) {
+                    if let Some(source) = commits.get(source_id) {
+                        let source_row = branch_rows[&source.branch];
+                        let cherry_row = branch_rows[&commit.branch];
+                        let source_len = commit_label_len(source, &graph.config);
+
+                        // Position after source + diagonal distance
+                        // Diagonal advances (row_diff - 1) columns (last step lands on target row)
+                        let row_diff = cherry_row.abs_diff(source_row);
+                        let cherry_col = source_col + source_len + row_diff - 1;
+                        col = col.max(cherry_col);
+                    }
                 }
             }
-        }
 
-        // Cherry-pick commits are invisible (don't take space) - their successor shows the connection
-        let effective_len = if commit.is_cherry_pick { 0 } else { label_len };
-
-        commit_cols.insert(commit.id.clone(), col);
-        branch_next_col.insert(
-            commit.branch.clone(),
-            col + effective_len
-                + if commit.is_cherry_pick {
-                    0
-                } else {
-                    base_spacing
-                },
-        );
-    }
-
-    // Step 4: Stretch child branches to fill space between fork and merge
-    // For each branch that merges back, redistribute commits to fill the gap
-    for branch in graph.branches.iter().skip(1) {
-        // Get commits on this branch (excluding cherry-picks)
-        let branch_commits: Vec<_> = graph
-            .commits
-            .iter()
-            .filter(|c| c.branch == branch.name && !c.is_cherry_pick)
-            .collect();
-
-        if branch_commits.is_empty() {
-            continue;
-        }
-
-        // Find if any commit on this branch is a merge parent
-        // (the merge might happen before later commits are added to the branch)
-        let mut merge_parent_idx = None;
-        let mut merge_commit_ref = None;
-
-        for (idx, branch_commit) in branch_commits.iter().enumerate() {
-            if let Some(merge) = graph.commits.iter().find(|c| {
-                c.is_merge && c.branch != branch.name && c.parent_ids.contains(&branch_commit.id)
-            }) {
-                merge_parent_idx = Some(idx);
-                merge_commit_ref = Some(merge);
-                break;
+            if !parallel {
+                col = col.max(slot_start);
             }
-        }
-
-        if merge_commit_ref.is_none() {
-            continue;
-        }
-
-        let merge = merge_commit_ref.unwrap();
-        let merge_idx = merge_parent_idx.unwrap();
-        let merge_col = commit_cols[&merge.id];
 
-        // Only stretch commits up to and including the merge parent
-        let commits_to_stretch: Vec<_> = branch_commits[..=merge_idx].to_vec();
+            // Cherry-pick commits are invisible (don't take space) - their successor shows the connection
+            let effective_len = if commit.is_cherry_pick { 0 } else { label_len };
 
-        if commits_to_stretch.is_empty() {
-            continue;
-        }
-
-        let first = commits_to_stretch.first().unwrap();
-        let last = commits_to_stretch.last().unwrap();
-
-        // First commit position (after fork)
-        let first_col = commit_cols[&first.id];
-
-        // Last commit (merge parent) should end at merge_col - 1 (for the / line)
-        let last_len = if last.is_merge {
-            last.id.len() + 2
-        } else {
-            last.id.len()
-        };
-        let target_last_col = merge_col.saturating_sub(1).saturating_sub(last_len);
-
-        // Only stretch if we need to (target is further right than current)
-        let current_last_col = commit_cols[&last.id];
-        if target_last_col <= current_last_col {
-            continue;
-        }
-
-        // Redistribute commits to stretch
-        let num_commits = commits_to_stretch.len();
-        if num_commits == 1 {
-            commit_cols.insert(first.id.clone(), target_last_col);
-        } else {
-            // Calculate total label lengths
-            let total_labels: usize = commits_to_stretch
-                .iter()
-                .map(|c| {
-                    if c.is_merge {
-                        c.id.len() + 2
+            commit_cols.insert(commit.id.clone(), col);
+            branch_next_col.insert(
+                commit.branch.clone(),
+                col + effective_len
+                    + if commit.is_cherry_pick {
+                        0
                     } else {
-                        c.id.len()
-                    }
-                })
-                .sum();
-
-            // Available space for gaps
-            let total_space = target_last_col + last_len - first_col;
-            let gap_space = total_space.saturating_sub(total_labels);
-            let num_gaps = num_commits - 1;
-            let per_gap = if num_gaps > 0 {
-                gap_space / num_gaps
-            } else {
-                0
-            };
-            let per_gap = per_gap.max(1);
-
-            // Reposition commits
-            let mut col = first_col;
-            for commit in &commits_to_stretch {
-                let label_len = if commit.is_merge {
-                    commit.id.len() + 2
-                } else {
-                    commit.id.len()
-                };
-                commit_cols.insert(commit.id.clone(), col);
-                col += label_len + per_gap;
-            }
+                        base_spacing
+                    },
+            );
         }
 
-        // Update position of commits after merge parent (if any)
-        // They should continue from where the stretched last commit ends
-        // But respect merge constraints: if a commit is itself a merge target,
-        // it must be at least at the merge_col calculated in Step 3
-        if merge_idx + 1 < branch_commits.len() {
-            let last_stretched = commits_to_stretch.last().unwrap();
-            let last_stretched_len = if last_stretched.is_merge {
-                last_stretched.id.len() + 2
-            } else {
-                last_stretched.id.len()
-            };
-            let last_stretched_end = commit_cols[&last_stretched.id] + last_stretched_len;
-            let mut col = last_stretched_end + 3; // base_spacing
-
-            for commit in &branch_commits[(merge_idx + 1)..] {
-                let label_len = if commit.is_merge {
-                    commit.id.len() + 2
+        if parallel {
+            let slot_col = slot
+                .iter()
+                .map(|c| commit_cols[&c.id])
+                .max()
+                .unwrap_or(0)
+                .max(slot_start);
+            for commit in slot {
+                let effective_len = if commit.is_cherry_pick {
+                    0
                 } else {
-                    commit.id.len()
+                    commit_label_len(commit, &graph.config) + base_spacing
                 };
-
-                // Re-check merge constraint: if this commit is a merge target,
-                // ensure it's far enough right for the merge diagonal to reach
-                if let Some(source_id) = merge_info.get(&commit.id) {
-                    if let Some(&source_col) = commit_cols.get(source_id) {
-                        if let Some(source) = graph.commits.iter().find(|c| &c.id == source_id) {
-                            let source_row = branch_rows[&source.branch];
-                            let commit_row = branch_rows[&commit.branch];
-                            let source_len = if source.is_merge {
-                                source.id.len() + 2
-                            } else {
-                                source.id.len()
-                            };
-                            let row_diff = source_row.abs_diff(commit_row);
-                            let merge_col_needed = source_col + source_len + row_diff.max(1) - 1;
-                            col = col.max(merge_col_needed);
-                        }
-                    }
-                }
-
-                commit_cols.insert(commit.id.clone(), col);
-                col += label_len + 3;
+                commit_cols.insert(commit.id.clone(), slot_col);
+                branch_next_col.insert(commit.branch.clone(), slot_col + effective_len);
+                slot_start = slot_start.max(slot_col + effective_len);
             }
+        } else {
+            slot_start = commit_cols[&slot[0].id] + 1;
         }
     }
 
-    // Step 4b: Post-stretch merge constraint fix
-    // After all stretches, some merge commits may no longer satisfy their merge constraints
-    // (the source commit may have moved, or the merge commit was compressed by stretch).
-    // Fix by iterating merges IN COMMIT ORDER (deterministic) and pushing merge commits right if needed.
-    // Use iteration limit to prevent infinite loops from cascading pushes.
     let merge_order: Vec<(String, String)> = graph
         .commits
         .iter()
@@ -518,141 +389,188 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
         .map(|c| (c.id.clone(), merge_info[&c.id].clone()))
         .collect();
 
-    for _iteration in 0..20 {
-        let mut any_changed = false;
-        for (merge_id, source_id) in &merge_order {
-            if let Some(&source_col) = commit_cols.get(source_id) {
-                if let Some(&current_merge_col) = commit_cols.get(merge_id) {
-                    if let Some(source) = graph.commits.iter().find(|c| &c.id == source_id) {
-                        if let Some(merge) = graph.commits.iter().find(|c| &c.id == merge_id) {
-                            let source_row = branch_rows[&source.branch];
-                            let merge_row = branch_rows[&merge.branch];
-                            let source_len = if source.is_merge {
-                                source.id.len() + 2
-                            } else {
-                                source.id.len()
-                            };
-                            let row_diff = source_row.abs_diff(merge_row);
-                            let needed_col = source_col + source_len + row_diff.max(1) - 1;
-
-                            if needed_col > current_merge_col {
-                                let delta = needed_col - current_merge_col;
-                                // Push this merge commit and all subsequent commits on same branch
-                                let merge_branch = merge.branch.clone();
-                                let mut found = false;
-                                for commit in &graph.commits {
-                                    if commit.branch == merge_branch && !commit.is_cherry_pick {
-                                        if commit.id == *merge_id {
-                                            found = true;
-                                        }
-                                        if found {
-                                            if let Some(col) = commit_cols.get_mut(&commit.id) {
-                                                *col += delta;
-                                            }
-                                        }
-                                    }
-                                }
-                                any_changed = true;
-                            }
-                        }
-                    }
+    // Step 4: Route cherry-pick lines
+    // A line leaves the source diagonally (\ downward, / upward) and lands on the target
+    // branch line before the commit after the pick. When it would cut through a commit
+    // label on a row it crosses, land inside a label on the target row, or clash with an
+    // earlier cherry-pick line, it first runs horizontally on the row next to the source.
+    // If it then lands past the pick, the pick and every later commit move right.
+    let mut cherry_paths: Vec<Vec<(usize, usize, char)>> = Vec::new();
+    let mut cherry_cells: HashMap<(usize, usize), char> = HashMap::new();
+    for commit in &graph.commits {
+        let Some(source_id) = cherry_pick_info.get(&commit.id) else {
+            continue;
+        };
+        let (Some(&source_col), Some(source)) =
+            (commit_cols.get(source_id), commits.get(source_id))
+        else {
+            continue;
+        };
+        let source_row = branch_rows[&source.branch];
+        let target_row = branch_rows[&commit.branch];
+        if source_row == target_row {
+            continue;
+        }
+        let source_end = source_col + commit_label_len(source, &graph.config);
+        let target_col = commit_cols[&commit.id];
+        let row_diff = source_row.abs_diff(target_row);
+        let (diag, row_at) = if target_row > source_row {
+            (chars.fork_down, source_row + 1)
+        } else {
+            (chars.merge_up, source_row - 1)
+        };
+        // Row `i` steps from the source towards the target
+        let step = |i: usize| {
+            if target_row > source_row {
+                source_row + i
+            } else {
+                source_row - i
+            }
+        };
+        let labels_on = |row: usize| -> Vec<(usize, usize)> {
+            graph
+                .commits
+                .iter()
+                .filter(|c| !c.is_cherry_pick && branch_rows[&c.branch] == row)
+                .map(|c| {
+                    let x = commit_cols[&c.id];
+                    (x, x + commit_label_len(c, &graph.config))
+                })
+                .collect()
+        };
+
+        // With a horizontal lead, the first row reads \──\ (or /──/). The line
+        // leaves the source row at `from`, normally right after the source
+        let path_with_lead = |from: usize, lead: usize| -> Vec<(usize, usize, char)> {
+            let mut path = vec![(from, row_at, diag)];
+            if lead > 0 {
+                path.extend((1..lead).map(|i| (from + i, row_at, chars.h_line)));
+                path.push((from + lead, row_at, diag));
+            }
+            path.extend((2..row_diff).map(|i| (from + lead + i - 1, step(i), diag)));
+            path
+        };
+        let fits = |from: usize, lead: usize| {
+            let landing = from + lead + row_diff - 1;
+            let clear_of_labels = path_with_lead(from, lead).iter().all(|&(x, row, ch)| {
+                cherry_cells.get(&(x, row)).is_none_or(|&c| c == ch)
+                    && labels_on(row)
+                        .iter()
+                        .all(|&(start, end)| x < start || x >= end)
+            });
+            let lands_on_line = labels_on(target_row)
+                .iter()
+                .all(|&(start, end)| start >= target_col || end < landing);
+            let leaves_from_line = labels_on(source_row)
+                .iter()
+                .all(|&(start, end)| end <= source_end || start >= from);
+            clear_of_labels && lands_on_line && leaves_from_line
+        };
+        // When no lead gets past earlier lines, follow the source row further
+        let (from, lead) = (0..200)
+            .filter(|&l| l != 1)
+            .map(|l| (source_end, l))
+            .chain((1..200).map(|offset| (source_end + offset, 0)))
+            .find(|&(from, lead)| fits(from, lead))
+            .unwrap_or((source_end, 0));
+        let path = path_with_lead(from, lead);
+
+        let landing = from + lead + row_diff - 1;
+        if landing > target_col {
+            let delta = landing - target_col;
+            let start = commits.position(&commit.id).unwrap_or(graph.commits.len());
+            for c in &graph.commits[start..] {
+                if let Some(col) = commit_cols.get_mut(&c.id) {
+                    *col += delta;
                 }
             }
         }
-        if !any_changed {
-            break;
+
+        for &(x, row, ch) in &path {
+            cherry_cells.insert((x, row), ch);
         }
+        cherry_paths.push(path);
     }
 
-    // Step 4c: Push merge sources right for clean diagonal merges
-    // When a merge target is much further right than where a pure diagonal from the source would land,
-    // push the source commit (and subsequent commits on its branch) right so the diagonal arrives cleanly.
-    // This avoids long horizontal landing segments on the merge target's branch row.
-    // Skip merges that will use horizontal bridges (those crossing tag areas).
-    for _iteration in 0..20 {
-        let mut any_changed = false;
-        for (merge_id, source_id) in &merge_order {
-            if let Some(&source_col) = commit_cols.get(source_id) {
-                if let Some(&merge_col) = commit_cols.get(merge_id) {
-                    if let Some(source) = graph.commits.iter().find(|c| &c.id == source_id) {
-                        if let Some(merge) = graph.commits.iter().find(|c| &c.id == merge_id) {
-                            let source_row = branch_rows[&source.branch];
-                            let merge_row = branch_rows[&merge.branch];
-                            let source_len = if source.is_merge {
-                                source.id.len() + 2
-                            } else {
-                                source.id.len()
-                            };
-                            let row_diff = source_row.abs_diff(merge_row);
+    // Pre-compute tag positions for collision detection
+    // tag_areas: Vec of (tag_row, tag_col_start, tag_col_end)
+    let mut tag_areas: Vec<(usize, usize, usize)> = Vec::new();
+    for commit in &graph.commits {
+        if let Some(ref tag) = commit.tag {
+            let x = commit_cols[&commit.id];
+            let commit_len = commit_label_len(commit, &graph.config);
+            let branch_row = branch_rows[&commit.branch];
+            let tag_text = format!("[{}]", tag);
+            let tag_display_len = text_width(&tag_text);
 
-                            // Skip downward merges into tagged branches (they use horizontal bridges)
-                            if source_row < merge_row
-                                && branches_needing_bridge.contains(&merge.branch)
-                            {
-                                continue;
-                            }
+            let commit_center = x + commit_len / 2;
+            let tag_start = commit_center.saturating_sub(tag_display_len / 2);
+            let tag_end = tag_start + tag_display_len;
 
-                            // Where would the pure diagonal arrive?
-                            let diag_arrival = source_col + source_len + row_diff.max(1) - 1;
-
-                            // If the merge commit is much further right, push the source right
-                            // Only do this for significant gaps (more than a few columns of horizontal dashes)
-                            if merge_col > diag_arrival + 3 {
-                                // How far right should the source be for the diagonal to arrive at merge_col?
-                                let needed_source_end = merge_col + 1 - row_diff.max(1);
-                                let needed_source_col =
-                                    needed_source_end.saturating_sub(source_len);
-
-                                if needed_source_col > source_col {
-                                    let delta = needed_source_col - source_col;
-                                    // Push this source commit and all subsequent commits on same branch
-                                    let source_branch = source.branch.clone();
-                                    let mut found = false;
-                                    for commit in &graph.commits {
-                                        if commit.branch == source_branch && !commit.is_cherry_pick
-                                        {
-                                            if commit.id == *source_id {
-                                                found = true;
-                                            }
-                                            if found {
-                                                if let Some(col) = commit_cols.get_mut(&commit.id) {
-                                                    *col += delta;
-                                                }
-                                            }
-                                        }
-                                    }
-                                    any_changed = true;
-                                }
-                            }
-                        }
-                    }
-                }
+            if branch_row >= 2 {
+                let tag_row = branch_row - 2;
+                tag_areas.push((tag_row, tag_start, tag_end));
             }
         }
-        if !any_changed {
-            break;
+    }
+
+    // Step 4b: Merge leads
+    // Commits keep their time slots, so a merge is often further right than a
+    // diagonal from its source reaches. The line then runs along the source row
+    // first and turns towards the merge just before it, unless the source
+    // branch has more commits in the way or the turned diagonal would cut
+    // through a tag.
+    let mut merge_leads: HashMap<String, usize> = HashMap::new();
+    for (merge_id, source_id) in &merge_order {
+        let (Some(source), Some(merge)) = (commits.get(source_id), commits.get(merge_id)) else {
+            continue;
+        };
+        let source_row = branch_rows[&source.branch];
+        let merge_row = branch_rows[&merge.branch];
+        if source_row == merge_row {
+            continue;
+        }
+        let source_col = commit_cols[source_id];
+        let source_end = source_col + commit_label_len(source, &graph.config);
+        let row_diff = source_row.abs_diff(merge_row);
+        let turn = (commit_cols[merge_id] + 1).saturating_sub(row_diff);
+        if turn <= source_end {
+            continue;
+        }
+        let blocked = graph.commits.iter().any(|c| {
+            c.branch == source.branch
+                && !c.is_cherry_pick
+                && commit_cols[&c.id] > source_col
+                && commit_cols[&c.id] <= turn
+        });
+        let crosses_tag = source_row < merge_row
+            && (turn..).zip((source_row + 1)..merge_row).any(|(x, row)| {
+                tag_areas
+                    .iter()
+                    .any(|&(tag_row, start, end)| row == tag_row && x >= start && x < end)
+            });
+        if !blocked && !crosses_tag {
+            merge_leads.insert(merge_id.clone(), turn - source_end);
         }
     }
 
     // Build the canvas
     let max_col = commit_cols.values().max().copied().unwrap_or(0) + 30;
     let mut canvas = mk_canvas(max_col, total_height);
+    // Directions of the cells orthogonal fork and merge lines pass through
+    let mut elbows: HashMap<(usize, usize), u8> = HashMap::new();
 
     // Step 5: Calculate branch spans (for drawing dashes)
     // end is the last column of the last commit (exclusive, so we use ..)
-    // Skip cherry-pick commits (they're invisible)
-    let mut branch_spans: HashMap<String, (usize, usize)> = HashMap::new();
+    // Cherry-pick commits are invisible; the line just reaches the cell their diagonal lands on
+    let mut branch_spans: IndexMap<String, (usize, usize)> = IndexMap::new();
 
     for commit in &graph.commits {
-        if commit.is_cherry_pick {
-            continue; // Skip cherry-picks for span calculation
-        }
-
         let c = commit_cols[&commit.id];
-        let label_len = if commit.is_merge {
-            commit.id.len() + 2
+        let label_len = if commit.is_cherry_pick {
+            1
         } else {
-            commit.id.len()
+            commit_label_len(commit, &graph.config)
         };
 
         branch_spans
@@ -663,6 +581,16 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
             })
             .or_insert((c, c + label_len));
     }
+    // Merge leads extend their source branch's line
+    for (merge_id, source_id) in &merge_order {
+        if let Some(&lead) = merge_leads.get(merge_id) {
+            let source = commits.get(source_id).unwrap();
+            let end = commit_cols[source_id] + commit_label_len(source, &graph.config) + lead;
+            if let Some(span) = branch_spans.get_mut(&source.branch) {
+                span.1 = span.1.max(end);
+            }
+        }
+    }
 
     // Step 6: Draw branch lines (dashes) - use exclusive end
     for (branch_name, (start, end)) in &branch_spans {
@@ -686,19 +614,15 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
     for parent_id in &fork_parent_order {
         let children = &forks_by_parent[parent_id];
         if let Some(&parent_col) = commit_cols.get(parent_id) {
-            if let Some(parent) = graph.commits.iter().find(|c| &c.id == parent_id) {
+            if let Some(parent) = commits.get(parent_id) {
                 let parent_row = branch_rows[&parent.branch];
-                let parent_len = if parent.is_merge {
-                    parent.id.len() + 2
-                } else {
-                    parent.id.len()
-                };
+                let parent_len = commit_label_len(parent, &graph.config);
 
                 // Find the furthest child row (for the continuous diagonal)
                 // Include all children, even those with cherry-picks
                 let mut max_child_row = parent_row;
                 for child_id in children {
-                    if let Some(child) = graph.commits.iter().find(|c| &c.id == child_id) {
+                    if let Some(child) = commits.get(child_id) {
                         let child_row = branch_rows[&child.branch];
                         if child_row > max_child_row {
                             max_child_row = child_row;
@@ -710,25 +634,46 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
                     continue; // No valid children to draw
                 }
 
+                // Orthogonal: down the column after the parent, then along each
+                // child's row (├ at the children above the last)
+                if orthogonal {
+                    let x = parent_col + parent_len;
+                    draw_elbow_path(
+                        &mut canvas,
+                        &mut elbows,
+                        &[(x - 1, parent_row), (x, parent_row), (x, max_child_row)],
+                    );
+                    for child_id in children {
+                        let Some(child) = commits.get(child_id) else {
+                            continue;
+                        };
+                        let child_row = branch_rows[&child.branch];
+                        if child.is_cherry_pick || child_row <= parent_row {
+                            continue;
+                        }
+                        draw_elbow_path(
+                            &mut canvas,
+                            &mut elbows,
+                            &[(x, child_row), (commit_cols[child_id], child_row)],
+                        );
+                    }
+                    continue;
+                }
+
                 // Draw the continuous diagonal from parent to furthest child
                 // For cascading forks, draw on ALL rows (including branch rows) to reach lower branches
-                let mut x = parent_col + parent_len;
-                for row in (parent_row + 1)..=max_child_row {
-                    // Don't draw diagonal on the final row - that's where the child commit is
-                    if row == max_child_row {
-                        break;
-                    }
+                // (not on the final row - that's where the child commit is)
+                for (x, row) in (parent_col + parent_len..).zip((parent_row + 1)..max_child_row) {
                     set_char(&mut canvas, x as i32, row as i32, chars.fork_down);
-                    x += 1;
                 }
 
-                // For each child (except those with cherry-picks), draw horizontal connection
+                // For each child (except cherry-picks), draw horizontal connection
                 for child_id in children {
                     if let Some(&child_col) = commit_cols.get(child_id) {
-                        if let Some(child) = graph.commits.iter().find(|c| &c.id == child_id) {
-                            // Skip horizontal connection for branches with cherry-picks
-                            // (they get their connection from the cherry-pick source)
-                            if branches_with_cherry_picks.contains(&child.branch) {
+                        if let Some(child) = commits.get(child_id) {
+                            // Skip horizontal connection for branches that start with a
+                            // cherry-pick (they get their connection from the cherry-pick source)
+                            if child.is_cherry_pick {
                                 continue;
                             }
 
@@ -738,8 +683,14 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
                                 // Diagonal advances once per row from parent to child
                                 let diag_x = parent_col + parent_len + (child_row - parent_row - 1);
 
-                                // Draw horizontal dashes from diagonal to child
-                                let dash_start = diag_x + 1;
+                                // Draw horizontal dashes from diagonal to child; the
+                                // diagonal stops above the furthest child, so its dashes
+                                // start right where the diagonal would have landed
+                                let dash_start = if child_row == max_child_row {
+                                    diag_x
+                                } else {
+                                    diag_x + 1
+                                };
                                 for dx in dash_start..child_col {
                                     set_char(
                                         &mut canvas,
@@ -763,23 +714,34 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
         let child_id = &commit.id;
         if let Some(parent_id) = fork_info.get(child_id) {
             if let Some(&parent_col) = commit_cols.get(parent_id) {
-                if let Some(parent) = graph.commits.iter().find(|c| &c.id == parent_id) {
-                    if let Some(child) = graph.commits.iter().find(|c| &c.id == child_id) {
+                if let Some(parent) = commits.get(parent_id) {
+                    if let Some(child) = commits.get(child_id) {
                         let parent_row = branch_rows[&parent.branch];
                         let child_row = branch_rows[&child.branch];
 
                         // Only handle upward forks (parent row > child row)
                         if parent_row > child_row {
-                            let parent_len = if parent.is_merge {
-                                parent.id.len() + 2
-                            } else {
-                                parent.id.len()
-                            };
+                            let parent_len = commit_label_len(parent, &graph.config);
+                            if orthogonal {
+                                let x = parent_col + parent_len;
+                                let child_col = commit_cols[child_id];
+                                draw_elbow_path(
+                                    &mut canvas,
+                                    &mut elbows,
+                                    &[
+                                        (x - 1, parent_row),
+                                        (x, parent_row),
+                                        (x, child_row),
+                                        (child_col, child_row),
+                                    ],
+                                );
+                                continue;
+                            }
                             // Draw / from parent upward to child
-                            let mut x = parent_col + parent_len;
-                            for row in (child_row + 1..parent_row).rev() {
+                            for (x, row) in
+                                (parent_col + parent_len..).zip((child_row + 1..parent_row).rev())
+                            {
                                 set_char(&mut canvas, x as i32, row as i32, chars.merge_up);
-                                x += 1;
                             }
                         }
                     }
@@ -793,69 +755,58 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
     // For downward merges that cross tag areas, use horizontal bridge to route around
     // Iterate in commit order for deterministic output
 
-    // Pre-compute tag positions for collision detection
-    // tag_areas: Vec of (tag_row, tag_col_start, tag_col_end)
-    let mut tag_areas: Vec<(usize, usize, usize)> = Vec::new();
-    for commit in &graph.commits {
-        if let Some(ref tag) = commit.tag {
-            let x = commit_cols[&commit.id];
-            let commit_len = if commit.is_merge {
-                commit.id.len() + 2
-            } else {
-                commit.id.len()
-            };
-            let branch_row = branch_rows[&commit.branch];
-            let tag_text = format!("[{}]", tag);
-            let tag_display_len = tag_text.chars().count();
-
-            let commit_center = x + commit_len / 2;
-            let tag_start = commit_center.saturating_sub(tag_display_len / 2);
-            let tag_end = tag_start + tag_display_len;
-
-            if branch_row >= 2 {
-                let tag_row = branch_row - 2;
-                tag_areas.push((tag_row, tag_start, tag_end));
-            }
-        }
-    }
-
     for (merge_id, source_id) in &merge_order {
         if let Some(&source_col) = commit_cols.get(source_id) {
             if let Some(&merge_col) = commit_cols.get(merge_id) {
-                if let Some(source) = graph.commits.iter().find(|c| &c.id == source_id) {
-                    if let Some(merge) = graph.commits.iter().find(|c| &c.id == merge_id) {
+                if let Some(source) = commits.get(source_id) {
+                    if let Some(merge) = commits.get(merge_id) {
                         let source_row = branch_rows[&source.branch];
                         let merge_row = branch_rows[&merge.branch];
-                        let source_len = if source.is_merge {
-                            source.id.len() + 2
-                        } else {
-                            source.id.len()
-                        };
-
-                        if source_row > merge_row {
+                        // The diagonal starts where the source's merge lead ends
+                        let source_len = commit_label_len(source, &graph.config)
+                            + merge_leads.get(merge_id).copied().unwrap_or(0);
+
+                        if orthogonal && source_row != merge_row {
+                            // Along the lower row, and across the rows in the column
+                            // after the source (going down) or before the merge (going up)
+                            let from = source_col + source_len;
+                            let turn = if source_row > merge_row {
+                                merge_col - 1
+                            } else {
+                                from
+                            };
+                            draw_elbow_path(
+                                &mut canvas,
+                                &mut elbows,
+                                &[
+                                    (from - 1, source_row),
+                                    (turn, source_row),
+                                    (turn, merge_row),
+                                    (merge_col, merge_row),
+                                ],
+                            );
+                        } else if source_row > merge_row {
                             // Source is below target: draw / upward from source toward merge
-                            let mut x = source_col + source_len;
-                            for row in (merge_row + 1..source_row).rev() {
+                            for (x, row) in
+                                (source_col + source_len..).zip((merge_row + 1..source_row).rev())
+                            {
                                 set_char(&mut canvas, x as i32, row as i32, chars.merge_up);
-                                x += 1;
                             }
                         } else if source_row < merge_row {
                             // Source is above target: draw \ downward from source toward merge
                             // Check if the diagonal would cross any tag area
                             let mut tag_collision_row: Option<usize> = None;
+                            for (check_x, row) in
+                                (source_col + source_len..).zip((source_row + 1)..merge_row)
                             {
-                                let mut check_x = source_col + source_len;
-                                for row in (source_row + 1)..merge_row {
-                                    for &(tag_row, tag_start, tag_end) in &tag_areas {
-                                        if row == tag_row
-                                            && check_x >= tag_start
-                                            && check_x < tag_end
-                                            && tag_collision_row.is_none()
-                                        {
-                                            tag_collision_row = Some(row);
-                                        }
+                                for &(tag_row, tag_start, tag_end) in &tag_areas {
+                                    if row == tag_row
+                                        && check_x >= tag_start
+                                        && check_x < tag_end
+                                        && tag_collision_row.is_none()
+                                    {
+                                        tag_collision_row = Some(row);
                                     }
-                                    check_x += 1;
                                 }
                             }
 
@@ -931,34 +882,9 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
         }
     }
 
-    // Step 8b: Draw cherry-pick lines (\) - from source commit down to cherry-pick target
-    // Iterate in commit order for deterministic output
-    for commit in &graph.commits {
-        let cherry_id = &commit.id;
-        if let Some(source_id) = cherry_pick_info.get(cherry_id) {
-            if let Some(&source_col) = commit_cols.get(source_id) {
-                if let Some(source) = graph.commits.iter().find(|c| &c.id == source_id) {
-                    if let Some(cherry) = graph.commits.iter().find(|c| &c.id == cherry_id) {
-                        let source_row = branch_rows[&source.branch];
-                        let cherry_row = branch_rows[&cherry.branch];
-                        let source_len = if source.is_merge {
-                            source.id.len() + 2
-                        } else {
-                            source.id.len()
-                        };
-
-                        if cherry_row > source_row {
-                            // Cherry-pick target is below source: draw \ diagonal on all rows
-                            let mut x = source_col + source_len;
-                            for row in (source_row + 1)..cherry_row {
-                                set_char(&mut canvas, x as i32, row as i32, chars.fork_down);
-                                x += 1;
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    // Step 8b: Draw cherry-pick lines (routed in Step 4)
+    for &(x, row, ch) in cherry_paths.iter().flatten() {
+        set_char(&mut canvas, x as i32, row as i32, ch);
     }
 
     // Step 9: Draw commits (overwriting dashes and fork lines)
@@ -978,6 +904,7 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
         } else {
             commit.id.clone()
         };
+        let label = label + &message_suffix(commit, &graph.config);
 
         draw_text(&mut canvas, x as i32, row as i32, &label);
     }
@@ -991,61 +918,39 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
 
             let mut label_pos = *end;
 
-            // Check if any cherry-pick diagonal passes through this row
-            // Iterate in commit order for deterministic output
-            for commit_iter in &graph.commits {
-                let cherry_id = &commit_iter.id;
-                if let Some(source_id) = cherry_pick_info.get(cherry_id) {
-                    if let Some(&source_col) = commit_cols.get(source_id) {
-                        if let Some(source) = graph.commits.iter().find(|c| &c.id == source_id) {
-                            if let Some(cherry) = graph.commits.iter().find(|c| &c.id == cherry_id)
-                            {
-                                let source_row = branch_rows[&source.branch];
-                                let cherry_row = branch_rows[&cherry.branch];
-                                let source_len = if source.is_merge {
-                                    source.id.len() + 2
-                                } else {
-                                    source.id.len()
-                                };
-
-                                // Check if this cherry-pick diagonal passes through our row
-                                if source_row < row && row < cherry_row {
-                                    let diag_col = source_col + source_len + (row - source_row - 1);
-                                    if diag_col >= label_pos {
-                                        label_pos = label_pos.max(diag_col + 5);
-                                    }
-                                }
-                            }
-                        }
-                    }
+            // Check if any cherry-pick line passes through this row
+            for &(x, _, _) in cherry_paths.iter().flatten().filter(|&&(_, r, _)| r == row) {
+                if x >= label_pos {
+                    label_pos = label_pos.max(x + 5);
                 }
             }
 
             // Check if any merge diagonal passes through this row
-            for (merge_id, source_id) in &merge_order {
+            for (merge_id, source_id) in merge_order.iter().filter(|_| !orthogonal) {
                 if let Some(&source_col) = commit_cols.get(source_id) {
-                    if let Some(source) = graph.commits.iter().find(|c| &c.id == source_id) {
-                        if let Some(merge) = graph.commits.iter().find(|c| &c.id == merge_id) {
+                    if let Some(source) = commits.get(source_id) {
+                        if let Some(merge) = commits.get(merge_id) {
                             let source_row = branch_rows[&source.branch];
                             let merge_row = branch_rows[&merge.branch];
-                            let source_len = if source.is_merge {
-                                source.id.len() + 2
-                            } else {
-                                source.id.len()
-                            };
+                            let source_len = commit_label_len(source, &graph.config)
+                                + merge_leads.get(merge_id).copied().unwrap_or(0);
 
                             // Downward merge diagonal (\): source above, merge below
                             if source_row < merge_row && source_row < row && row < merge_row {
                                 let diag_col = source_col + source_len + (row - source_row - 1);
                                 // Only push label if diagonal would overlap with the label text
-                                if diag_col >= label_pos && diag_col < label_pos + label.len() {
+                                if diag_col >= label_pos
+                                    && diag_col < label_pos + text_width(&label)
+                                {
                                     label_pos = diag_col + 3;
                                 }
                             }
                             // Upward merge diagonal (/): source below, merge above
                             if source_row > merge_row && merge_row < row && row < source_row {
                                 let diag_col = source_col + source_len + (source_row - row - 1);
-                                if diag_col >= label_pos && diag_col < label_pos + label.len() {
+                                if diag_col >= label_pos
+                                    && diag_col < label_pos + text_width(&label)
+                                {
                                     label_pos = diag_col + 3;
                                 }
                             }
@@ -1054,6 +959,19 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
                 }
             }
 
+            // Orthogonal merge lines ending on or crossing this row stay left of the label
+            let mut crossings: Vec<usize> = elbows
+                .keys()
+                .filter(|&&(_, r)| r == row)
+                .map(|&(x, _)| x)
+                .collect();
+            crossings.sort_unstable();
+            for x in crossings {
+                if x >= label_pos && x < label_pos + text_width(&label) {
+                    label_pos = x + 1;
+                }
+            }
+
             draw_text(&mut canvas, label_pos as i32, row as i32, &label);
         }
     } // end if show_branches
@@ -1062,14 +980,10 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
     for commit in &graph.commits {
         if let Some(ref tag) = commit.tag {
             let x = commit_cols[&commit.id];
-            let commit_len = if commit.is_merge {
-                commit.id.len() + 2
-            } else {
-                commit.id.len()
-            };
+            let commit_len = commit_label_len(commit, &graph.config);
             let branch_row = branch_rows[&commit.branch];
             let tag_text = format!("[{}]", tag);
-            let tag_display_len = tag_text.chars().count();
+            let tag_display_len = text_width(&tag_text);
 
             // Center the tag over the commit
             let commit_center = x + commit_len / 2;
@@ -1096,7 +1010,8 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
 
 /// Render vertical (top-to-bottom) git graph
 ///
-/// Expected output format:
+/// Each branch gets a lane, placed far enough right to clear the labels and
+/// tags of the lanes to its left. Expected output format:
 /// ```text
 /// A  (main)
 /// |
@@ -1105,6 +1020,7 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
 /// | C  (develop)
 /// | |
 /// | D
+/// | |
 /// E |
 /// | |
 /// F |
@@ -1112,303 +1028,511 @@ fn render_horizontal(graph: &GitGraph, use_ascii: bool) -> String {
 /// [M]
 /// ```
 fn render_vertical_tb(graph: &GitGraph, use_ascii: bool) -> String {
+    if graph.config.parallel_commits {
+        return render_vertical_parallel(graph, use_ascii);
+    }
+
     let chars = if use_ascii {
         GitChars::ascii()
     } else {
         GitChars::unicode()
     };
+    let (min_lane, label_gap) = if use_ascii { (2, 1) } else { (3, 2) };
 
-    // Assign branches to columns
-    let mut branch_cols: HashMap<String, usize> = HashMap::new();
+    // One lane per branch that has commits, in creation order
+    let mut lane_of: HashMap<&str, usize> = HashMap::new();
     for branch in &graph.branches {
-        let col = branch_cols.len();
-        branch_cols.insert(branch.name.clone(), col);
+        if graph.commits.iter().any(|c| c.branch == branch.name) {
+            let lane = lane_of.len();
+            lane_of.insert(&branch.name, lane);
+        }
+    }
+    let lane_count = lane_of.len();
+    let commits = graph.commit_index();
+    let lane = |i: usize| lane_of[graph.commits[i].branch.as_str()];
+
+    // Forks (first parent on another lane) and merges (second parent on
+    // another lane), by commit index
+    let n = graph.commits.len();
+    let first_parent: Vec<Option<usize>> = graph
+        .commits
+        .iter()
+        .map(|c| c.parent_ids.first().and_then(|p| commits.position(p)))
+        .collect();
+    let mut forks_from: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for (i, parent) in first_parent.iter().enumerate() {
+        if let Some(p) = *parent {
+            if lane(p) != lane(i) {
+                forks_from[p].push(i);
+            }
+        }
     }
+    let merge_source: Vec<Option<usize>> = graph
+        .commits
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            c.parent_ids
+                .get(1)
+                .filter(|_| c.is_merge)
+                .and_then(|p| commits.position(p))
+                .filter(|&s| lane(s) != lane(i))
+        })
+        .collect();
 
-    // Find fork and merge info
-    let mut fork_commits: HashMap<String, String> = HashMap::new(); // child -> parent (fork point)
-    let mut merge_commits: HashMap<String, String> = HashMap::new(); // merge -> source branch last commit
-    let mut merge_source_commits: HashSet<String> = HashSet::new(); // commits that are merge sources
+    // Rows: every commit has one. A fork turns on a line right below its
+    // parent, except in Unicode when the child is the very next commit and
+    // the only one forking there (├──C on the child's own line). A merge turns
+    // on a line right above the merge commit. Other commits are separated by
+    // a plain connector line.
+    let inline_fork =
+        |i: usize| !use_ascii && i > 0 && forks_from[i - 1] == [i] && merge_source[i].is_none();
+    let mut commit_row = vec![0; n];
+    let mut fork_row: Vec<Option<usize>> = vec![None; n];
+    let mut merge_row: Vec<Option<usize>> = vec![None; n];
+    let mut height = 0;
+    for i in 0..n {
+        if i > 0 {
+            let before = height;
+            if !forks_from[i - 1].is_empty() && !inline_fork(i) {
+                fork_row[i - 1] = Some(height);
+                height += 1;
+            }
+            if merge_source[i].is_some() {
+                merge_row[i] = Some(height);
+                height += 1;
+            }
+            if height == before && !inline_fork(i) {
+                height += 1;
+            }
+        }
+        commit_row[i] = height;
+        height += 1;
+    }
 
+    // Edges in lane space: the vertical parts go to `dirs`, the turns (row,
+    // upper lane, lower lane) are drawn once lanes have their columns
+    let mut dirs = vec![vec![0u8; lane_count]; height];
+    let mut turns: Vec<(usize, usize, usize)> = Vec::new();
+    let mut edge = |parent: usize, child: usize, turn: Option<usize>| {
+        let (pr, pl) = (commit_row[parent], lane(parent));
+        let (cr, cl) = (commit_row[child], lane(child));
+        let turn = turn.unwrap_or(cr);
+        dirs[pr][pl] |= LANE_S;
+        for row in &mut dirs[pr + 1..turn] {
+            row[pl] |= LANE_N | LANE_S;
+        }
+        if pl == cl {
+            dirs[cr][cl] |= LANE_N;
+            return;
+        }
+        dirs[turn][pl] |= LANE_N;
+        if turn < cr {
+            dirs[turn][cl] |= LANE_S;
+            for row in &mut dirs[turn + 1..cr] {
+                row[cl] |= LANE_N | LANE_S;
+            }
+            dirs[cr][cl] |= LANE_N;
+        }
+        turns.push((turn, pl, cl));
+    };
+    for i in 0..n {
+        if let Some(p) = first_parent[i] {
+            let turn = if lane(p) == lane(i) || inline_fork(i) {
+                None
+            } else {
+                fork_row[p]
+            };
+            edge(p, i, turn);
+        }
+        if let Some(s) = merge_source[i] {
+            edge(s, i, merge_row[i]);
+        }
+    }
+
+    // Each commit's text: label, then tag and (on a branch's first commit) the
+    // branch name
+    let mut first_on_branch: HashMap<&str, &str> = HashMap::new();
     for commit in &graph.commits {
-        if commit.is_merge && commit.parent_ids.len() >= 2 {
-            if let Some(parent_id) = commit.parent_ids.get(1) {
-                merge_commits.insert(commit.id.clone(), parent_id.clone());
-                merge_source_commits.insert(parent_id.clone());
+        first_on_branch
+            .entry(commit.branch.as_str())
+            .or_insert(commit.id.as_str());
+    }
+    let texts: Vec<String> = graph
+        .commits
+        .iter()
+        .map(|c| {
+            let mut text = vertical_label(c, &graph.config);
+            if let Some(tag) = &c.tag {
+                text.push_str(&format!("  [{}]", tag));
+            }
+            if graph.config.show_branches && first_on_branch[c.branch.as_str()] == c.id {
+                text.push_str(&format!("  ({})", c.branch));
+            }
+            text
+        })
+        .collect();
+
+    // Lane columns: each lane starts clear of the text of every commit to its
+    // left on a line where the lane is drawn
+    let occupied = |row: usize, l: usize| {
+        dirs[row][l] != 0
+            || turns
+                .iter()
+                .any(|&(t, a, b)| t == row && a.min(b) <= l && l <= a.max(b))
+    };
+    let mut lane_x = vec![0usize; lane_count];
+    for l in 1..lane_count {
+        lane_x[l] = lane_x[l - 1] + min_lane;
+        for i in 0..n {
+            if lane(i) < l && occupied(commit_row[i], l) {
+                let end = lane_x[lane(i)] + text_width(&texts[i]) + label_gap;
+                lane_x[l] = lane_x[l].max(end);
             }
         }
+    }
 
-        if !commit.parent_ids.is_empty() {
-            if let Some(parent_id) = commit.parent_ids.first() {
-                if let Some(parent) = graph.commits.iter().find(|c| &c.id == parent_id) {
-                    if parent.branch != commit.branch {
-                        fork_commits.insert(commit.id.clone(), parent_id.clone());
-                    }
+    // Draw: lanes and turns as direction sets, then labels on top
+    let width = lane_x.last().copied().unwrap_or(0) + 1;
+    let mut cells = vec![vec![0u8; width]; height];
+    for (row, lanes) in dirs.iter().enumerate() {
+        for (l, &d) in lanes.iter().enumerate() {
+            cells[row][lane_x[l]] |= d;
+        }
+    }
+    let mut overrides: Vec<(usize, usize, char)> = Vec::new();
+    for &(row, upper, lower) in &turns {
+        let (ux, lx) = (lane_x[upper], lane_x[lower]);
+        let (left, right) = (ux.min(lx), ux.max(lx));
+        cells[row][left] |= LANE_E;
+        cells[row][right] |= LANE_W;
+        for cell in &mut cells[row][left + 1..right] {
+            *cell |= LANE_E | LANE_W;
+        }
+        if use_ascii {
+            // The diagonal sits next to the lane that starts or ends here
+            // (the lower one if both run through), leaning the way the
+            // line flows
+            let passes = |x: usize| cells[row][x] & (LANE_N | LANE_S) == LANE_N | LANE_S;
+            let at_lower = !passes(lx) || passes(ux);
+            let x = if at_lower { lx } else { ux };
+            let on_right = x == right;
+            let diagonal = if on_right == at_lower {
+                chars.fork_down
+            } else {
+                chars.merge_up
+            };
+            let diagonal_x = if on_right { x - 1 } else { x + 1 };
+            overrides.push((row, diagonal_x, diagonal));
+            // A lane running through stays a plain line where the diagonal
+            // leaves it directly
+            for end in [ux, lx] {
+                if !passes(end) {
+                    overrides.push((row, end, ' '));
+                } else if end.abs_diff(diagonal_x) == 1 {
+                    overrides.push((row, end, chars.v_line));
                 }
             }
         }
     }
 
-    // Build output line by line
-    let mut lines: Vec<String> = Vec::new();
-    let num_cols = branch_cols.len().max(1);
-
-    // Track which branches are active at each point
-    let mut active_branches: Vec<bool> = vec![false; num_cols];
+    let mut canvas = mk_canvas(width, height);
+    for (row, line) in cells.iter().enumerate() {
+        for (x, &d) in line.iter().enumerate() {
+            if d != 0 {
+                set_char(&mut canvas, x as i32, row as i32, lane_glyph(d, use_ascii));
+            }
+        }
+    }
+    for &(row, x, ch) in &overrides {
+        set_char(&mut canvas, x as i32, row as i32, ch);
+    }
+    for (i, text) in texts.iter().enumerate() {
+        draw_text(
+            &mut canvas,
+            lane_x[lane(i)] as i32,
+            commit_row[i] as i32,
+            text,
+        );
+    }
 
-    for (i, commit) in graph.commits.iter().enumerate() {
-        let commit_col = branch_cols[&commit.branch];
+    let mut lines: Vec<String> = canvas_to_string(&canvas)
+        .lines()
+        .map(|l| l.trim_end().to_string())
+        .collect();
 
-        // Check if this is a fork point
-        let is_fork = fork_commits.contains_key(&commit.id);
-        let fork_parent_col = if is_fork {
-            fork_commits
-                .get(&commit.id)
-                .and_then(|parent_id| graph.commits.iter().find(|c| &c.id == parent_id))
-                .map(|parent| branch_cols[&parent.branch])
-        } else {
-            None
-        };
+    // Messages go in one column to the right of the lanes and labels
+    let messages: Vec<(usize, &str)> = graph
+        .commits
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| Some((commit_row[i], commit_message(c, &graph.config)?)))
+        .collect();
+    if !messages.is_empty() {
+        let column = lines.iter().map(|l| text_width(l)).max().unwrap_or(0) + 2;
+        for (row, msg) in messages {
+            let pad = column - text_width(&lines[row]);
+            lines[row] = format!("{}{}{}", lines[row], " ".repeat(pad), msg);
+        }
+    }
 
-        // Check if this is a merge commit
-        let is_merge_commit = merge_commits.contains_key(&commit.id);
-        let merge_source_col = if is_merge_commit {
-            merge_commits
-                .get(&commit.id)
-                .and_then(|source_id| graph.commits.iter().find(|c| &c.id == source_id))
-                .map(|source| branch_cols[&source.branch])
-        } else {
-            None
-        };
+    lines.join("\n")
+}
 
-        // Draw merge connector line BEFORE the merge commit (├──╯ style for unicode)
-        if let Some(source_col) = merge_source_col {
-            if source_col > commit_col {
-                let mut merge_line = String::new();
-                for (c, &branch_active) in active_branches[..num_cols].iter().enumerate() {
-                    if c == commit_col {
-                        if use_ascii {
-                            merge_line.push(chars.v_line);
-                            merge_line.push(chars.merge_up);
-                        } else {
-                            merge_line.push('├');
-                            merge_line.push('─');
-                            merge_line.push('─');
-                        }
-                    } else if c == source_col {
-                        if use_ascii {
-                            merge_line.push(' ');
-                            merge_line.push(' ');
-                        } else {
-                            merge_line.push('╯');
-                        }
-                    } else if c > commit_col && c < source_col {
-                        if use_ascii {
-                            merge_line.push(' ');
-                            merge_line.push(' ');
-                        } else {
-                            merge_line.push('─');
-                            merge_line.push('─');
-                        }
-                    } else if branch_active && c < source_col {
-                        merge_line.push(chars.v_line);
-                        merge_line.push(' ');
-                    } else {
-                        merge_line.push(' ');
-                        merge_line.push(' ');
-                    }
-                }
-                lines.push(merge_line.trim_end().to_string());
+/// Render vertical (top-to-bottom) git graph with `parallelCommits`
+///
+/// Commits at the same depth share a line and each branch keeps its own
+/// lane. Every edge runs down its parent's lane and turns towards the child
+/// on the line just above it:
+/// ```text
+/// (main)  (develop)
+/// A
+/// ├───────╮
+/// D       B
+/// │       │
+/// E       C
+/// ```
+fn render_vertical_parallel(graph: &GitGraph, use_ascii: bool) -> String {
+    let commits = graph.commit_index();
+    let depths = graph.commit_depths();
+    let depth_of: HashMap<&str, usize> = graph
+        .commits
+        .iter()
+        .zip(&depths)
+        .map(|(c, &d)| (c.id.as_str(), d))
+        .collect();
 
-                // Deactivate the merged branch
-                active_branches[source_col] = false;
-            }
+    // One lane per branch, wide enough for its labels
+    let min_lane = if use_ascii { 2 } else { 3 };
+    let mut lane_x: HashMap<&str, usize> = HashMap::new();
+    let mut width = 0;
+    for branch in &graph.branches {
+        let mut label_width = graph
+            .commits
+            .iter()
+            .filter(|c| c.branch == branch.name)
+            .map(|c| text_width(&vertical_label(c, &graph.config)))
+            .max()
+            .unwrap_or(0);
+        if graph.config.show_branches {
+            label_width = label_width.max(text_width(&branch.name) + 2);
         }
+        lane_x.insert(&branch.name, width);
+        width += (label_width + 2).max(min_lane);
+    }
 
-        // Now activate this branch (after processing merge)
-        active_branches[commit_col] = true;
-
-        // Draw commit line - for forks in unicode, use ├── style on same line as commit
-        let mut commit_line = String::new();
-
-        if is_fork && !use_ascii {
-            // Unicode fork: ├──C  (develop)
-            if let Some(parent_col) = fork_parent_col {
-                if commit_col > parent_col {
-                    for (c, &branch_active) in active_branches[..num_cols].iter().enumerate() {
-                        if c == parent_col {
-                            commit_line.push('├');
-                            commit_line.push('─');
-                            commit_line.push('─');
-                        } else if c == commit_col {
-                            // Draw commit label
-                            let label = if !graph.config.show_commit_label {
-                                "*".to_string()
-                            } else if commit.is_merge {
-                                format!("[{}]", commit.id)
-                            } else {
-                                commit.id.clone()
-                            };
-                            commit_line.push_str(&label);
-
-                            // Add branch label on first commit of each branch
-                            let is_first_on_branch = graph
-                                .commits
-                                .iter()
-                                .find(|cc| cc.branch == commit.branch)
-                                .map(|cc| cc.id == commit.id)
-                                .unwrap_or(false);
-
-                            if is_first_on_branch && graph.config.show_branches {
-                                commit_line.push_str(&format!("  ({})", commit.branch));
-                            }
-                        } else if c > parent_col && c < commit_col {
-                            commit_line.push('─');
-                            commit_line.push('─');
-                        } else if branch_active {
-                            commit_line.push(chars.v_line);
-                            commit_line.push(' ');
-                        } else {
-                            commit_line.push(' ');
-                            commit_line.push(' ');
-                        }
-                    }
-                    lines.push(commit_line.trim_end().to_string());
-                    // Skip the normal commit line generation
-
-                    // Draw vertical connectors (if not last commit)
-                    if i < graph.commits.len() - 1 {
-                        let next_commit = &graph.commits[i + 1];
-                        let next_is_fork = fork_commits.contains_key(&next_commit.id);
-                        let next_is_merge = merge_commits.contains_key(&next_commit.id);
-
-                        if !next_is_fork && !next_is_merge {
-                            let mut connector_line = String::new();
-                            for &branch_active in &active_branches[..num_cols] {
-                                if branch_active {
-                                    connector_line.push(chars.v_line);
-                                    connector_line.push(' ');
-                                    if !use_ascii {
-                                        connector_line.push(' ');
-                                    }
-                                } else {
-                                    connector_line.push(' ');
-                                    connector_line.push(' ');
-                                    if !use_ascii {
-                                        connector_line.push(' ');
-                                    }
-                                }
-                            }
-                            lines.push(connector_line.trim_end().to_string());
-                        }
-                    }
-                    continue;
+    // Commits sit on even rows; odd rows hold the turns between lanes
+    let height = depths.iter().max().map_or(0, |d| 2 * d + 1);
+    let mut cells = vec![vec![0u8; width]; height];
+    for commit in &graph.commits {
+        let cx = lane_x[commit.branch.as_str()];
+        let cy = 2 * depth_of[commit.id.as_str()];
+        for parent_id in &commit.parent_ids {
+            let Some(parent) = commits.get(parent_id) else {
+                continue;
+            };
+            let px = lane_x[parent.branch.as_str()];
+            let py = 2 * depth_of[parent_id.as_str()];
+            let turn = cy - 1;
+
+            cells[py][px] |= LANE_S;
+            for row in cells.iter_mut().take(turn).skip(py + 1) {
+                row[px] |= LANE_N | LANE_S;
+            }
+            if px == cx {
+                cells[turn][px] |= LANE_N | LANE_S;
+            } else {
+                let (toward, back) = if cx > px {
+                    (LANE_E, LANE_W)
+                } else {
+                    (LANE_W, LANE_E)
+                };
+                cells[turn][px] |= LANE_N | toward;
+                for cell in &mut cells[turn][px.min(cx) + 1..px.max(cx)] {
+                    *cell |= LANE_E | LANE_W;
                 }
+                cells[turn][cx] |= LANE_S | back;
             }
+            cells[cy][cx] |= LANE_N;
         }
+    }
 
-        // ASCII fork: draw |\ on separate line before commit
-        if is_fork && use_ascii {
-            if let Some(parent_col) = fork_parent_col {
-                if commit_col > parent_col {
-                    let mut fork_line = String::new();
-                    for (c, &branch_active) in active_branches[..num_cols].iter().enumerate() {
-                        if c == parent_col {
-                            fork_line.push(chars.v_line);
-                            fork_line.push(chars.fork_down);
-                        } else if c == commit_col {
-                            // Don't draw anything at the commit column - the \ leads here
-                            fork_line.push(' ');
-                            fork_line.push(' ');
-                        } else if branch_active {
-                            fork_line.push(chars.v_line);
-                            fork_line.push(' ');
-                        } else {
-                            fork_line.push(' ');
-                            fork_line.push(' ');
-                        }
-                    }
-                    lines.push(fork_line.trim_end().to_string());
-                }
-            }
+    let mut rows: Vec<Vec<char>> = cells
+        .iter()
+        .map(|row| row.iter().map(|&d| lane_glyph(d, use_ascii)).collect())
+        .collect();
+
+    // Commit labels, and the messages of each line in lane order
+    let mut messages: Vec<Vec<&str>> = vec![Vec::new(); height];
+    let mut by_lane: Vec<&GitCommit> = graph.commits.iter().collect();
+    by_lane.sort_by_key(|c| lane_x[c.branch.as_str()]);
+    for commit in by_lane {
+        let row = 2 * depth_of[commit.id.as_str()];
+        let x = lane_x[commit.branch.as_str()];
+        let label = text_cells(&vertical_label(commit, &graph.config));
+        rows[row][x..x + label.len()].copy_from_slice(&label);
+        if let Some(msg) = commit_message(commit, &graph.config) {
+            messages[row].push(msg);
         }
+    }
 
-        // Draw normal commit line
-        for (c, &branch_active) in active_branches[..num_cols].iter().enumerate() {
-            if c == commit_col {
-                // Draw commit label
-                let label = if !graph.config.show_commit_label {
-                    "*".to_string()
-                } else if commit.is_merge {
-                    format!("[{}]", commit.id)
-                } else {
-                    commit.id.clone()
-                };
-                commit_line.push_str(&label);
+    let mut lines: Vec<String> = Vec::new();
+    if graph.config.show_branches {
+        let mut header = vec![' '; width];
+        for branch in &graph.branches {
+            let label = format!("({})", branch.name);
+            let label = text_cells(&label);
+            let x = lane_x[branch.name.as_str()];
+            header[x..x + label.len()].copy_from_slice(&label);
+        }
+        lines.push(cells_to_string(&header));
+    }
+    let offset = lines.len();
+    lines.extend(rows.iter().map(|r| cells_to_string(r)));
+    for line in &mut lines {
+        *line = line.trim_end().to_string();
+    }
 
-                // Add branch label on first commit of each branch
-                let is_first_on_branch = graph
-                    .commits
-                    .iter()
-                    .find(|cc| cc.branch == commit.branch)
-                    .map(|cc| cc.id == commit.id)
-                    .unwrap_or(false);
-
-                if is_first_on_branch && graph.config.show_branches {
-                    commit_line.push_str(&format!("  ({})", commit.branch));
-                } else if c < num_cols - 1 && active_branches.iter().skip(c + 1).any(|&b| b) {
-                    // If there are active branches after this commit, add spacing
-                    let col_width = if use_ascii { 2 } else { 3 };
-                    let needed_width = col_width * (c + 1);
-                    while commit_line.chars().count() < needed_width {
-                        commit_line.push(' ');
-                    }
-                }
-            } else if branch_active {
-                commit_line.push(chars.v_line);
-                commit_line.push(' ');
-                if !use_ascii {
-                    commit_line.push(' ');
-                }
-            } else {
-                commit_line.push(' ');
-                commit_line.push(' ');
-                if !use_ascii {
-                    commit_line.push(' ');
-                }
+    // Messages go in one column to the right of the lanes
+    if messages.iter().any(|m| !m.is_empty()) {
+        let column = lines.iter().map(|l| text_width(l)).max().unwrap_or(0) + 2;
+        for (row, msgs) in messages.iter().enumerate() {
+            if !msgs.is_empty() {
+                let line = &mut lines[offset + row];
+                let pad = column - text_width(line);
+                *line = format!("{}{}{}", line, " ".repeat(pad), msgs.join("; "));
             }
         }
-        lines.push(commit_line.trim_end().to_string());
-
-        // Draw vertical connectors (if not last commit)
-        if i < graph.commits.len() - 1 {
-            let next_commit = &graph.commits[i + 1];
-            let next_is_fork = fork_commits.contains_key(&next_commit.id);
-            let next_is_merge = merge_commits.contains_key(&next_commit.id);
-
-            if !next_is_fork && !next_is_merge {
-                let mut connector_line = String::new();
-                for &branch_active in &active_branches[..num_cols] {
-                    if branch_active {
-                        connector_line.push(chars.v_line);
-                        connector_line.push(' ');
-                        if !use_ascii {
-                            connector_line.push(' ');
-                        }
-                    } else {
-                        connector_line.push(' ');
-                        connector_line.push(' ');
-                        if !use_ascii {
-                            connector_line.push(' ');
-                        }
-                    }
-                }
-                lines.push(connector_line.trim_end().to_string());
+    }
+
+    lines.join("\n")
+}
+
+/// Square-cornered box-drawing characters and the directions they connect
+/// (`LANE_*` bits), for orthogonal fork and merge lines
+const ELBOWS: [(char, u8); 11] = [
+    ('─', LANE_E | LANE_W),
+    ('│', LANE_N | LANE_S),
+    ('┌', LANE_S | LANE_E),
+    ('┐', LANE_S | LANE_W),
+    ('└', LANE_N | LANE_E),
+    ('┘', LANE_N | LANE_W),
+    ('├', LANE_N | LANE_S | LANE_E),
+    ('┤', LANE_N | LANE_S | LANE_W),
+    ('┬', LANE_S | LANE_E | LANE_W),
+    ('┴', LANE_N | LANE_E | LANE_W),
+    ('┼', LANE_N | LANE_S | LANE_E | LANE_W),
+];
+
+/// Draw an orthogonal fork or merge line through `points`, each sharing a row
+/// or a column with the next. The line starts and ends on commit labels, which
+/// are drawn over it.
+///
+/// `joined` keeps the directions of the cells drawn so far, so lines that meet
+/// join up even where the glyph alone can't tell (`│` for a line ending in the
+/// cell).
+fn draw_elbow_path(
+    canvas: &mut Canvas,
+    joined: &mut HashMap<(usize, usize), u8>,
+    points: &[(usize, usize)],
+) {
+    for pair in points.windows(2) {
+        let [(x1, y1), (x2, y2)] = [pair[0], pair[1]];
+        if y1 == y2 {
+            let (left, right) = (x1.min(x2), x1.max(x2));
+            for x in left..=right {
+                let west = if x > left { LANE_W } else { 0 };
+                let east = if x < right { LANE_E } else { 0 };
+                join_elbow(canvas, joined, x, y1, west | east);
+            }
+        } else {
+            let (top, bottom) = (y1.min(y2), y1.max(y2));
+            for y in top..=bottom {
+                let up = if y > top { LANE_N } else { 0 };
+                let down = if y < bottom { LANE_S } else { 0 };
+                join_elbow(canvas, joined, x1, y, up | down);
             }
         }
     }
+}
 
-    lines.join("\n")
+/// Connect the cell at (x, y) towards `dirs`, joining any line already drawn
+/// there. Text is left alone.
+fn join_elbow(
+    canvas: &mut Canvas,
+    joined: &mut HashMap<(usize, usize), u8>,
+    x: usize,
+    y: usize,
+    dirs: u8,
+) {
+    let existing = match joined.get(&(x, y)) {
+        Some(&d) => d,
+        None => match get_char(canvas, x as i32, y as i32) {
+            ' ' => 0,
+            c => match ELBOWS.iter().find(|&&(glyph, _)| glyph == c) {
+                Some(&(_, d)) => d,
+                None => return,
+            },
+        },
+    };
+    let dirs = existing | dirs;
+    joined.insert((x, y), dirs);
+    // A line ending in the cell is drawn straight through it
+    let glyph = if dirs & (LANE_E | LANE_W) == 0 {
+        '│'
+    } else if dirs & (LANE_N | LANE_S) == 0 {
+        '─'
+    } else {
+        ELBOWS
+            .iter()
+            .find(|&&(_, d)| d == dirs)
+            .map_or('┼', |&(c, _)| c)
+    };
+    set_char(canvas, x as i32, y as i32, glyph);
+}
+
+/// Glyph of a lane cell from the directions it connects (`LANE_*` bits)
+fn lane_glyph(dirs: u8, use_ascii: bool) -> char {
+    if dirs == 0 {
+        ' '
+    } else if dirs & (LANE_E | LANE_W) == 0 {
+        if use_ascii {
+            '|'
+        } else {
+            '│'
+        }
+    } else if dirs & (LANE_N | LANE_S) == 0 {
+        if use_ascii {
+            '-'
+        } else {
+            '─'
+        }
+    } else if use_ascii {
+        '+'
+    } else {
+        match dirs {
+            d if d == LANE_N | LANE_E => '╰',
+            d if d == LANE_N | LANE_W => '╯',
+            d if d == LANE_S | LANE_E => '╭',
+            d if d == LANE_S | LANE_W => '╮',
+            d if d == LANE_N | LANE_S | LANE_E => '├',
+            d if d == LANE_N | LANE_S | LANE_W => '┤',
+            d if d == LANE_N | LANE_E | LANE_W => '┴',
+            d if d == LANE_S | LANE_E | LANE_W => '┬',
+            _ => '┼',
+        }
+    }
+}
+
+/// A commit's label in the vertical layouts
+fn vertical_label(commit: &GitCommit, config: &GitGraphConfig) -> String {
+    if !config.show_commit_label {
+        "*".to_string()
+    } else if commit.is_merge {
+        format!("[{}]", commit.id)
+    } else {
+        commit.id.clone()
+    }
 }
 
 /// Render vertical (bottom-to-top) git graph
@@ -1427,7 +1551,7 @@ fn render_vertical_bt(graph: &GitGraph, use_ascii: bool) -> String {
         .rev()
         .map(|line| {
             // Swap fork_down (\) and merge_up (/) characters
-            // Also swap ╯ ↔ ╮ for unicode
+            // Also swap ╯ ↔ ╮ (and the ╭ ╰ ┬ ┴ of parallel lanes) for unicode
             line.chars()
                 .map(|c| {
                     if c == chars.fork_down {
@@ -1438,6 +1562,14 @@ fn render_vertical_bt(graph: &GitGraph, use_ascii: bool) -> String {
                         '╮'
                     } else if c == '╮' {
                         '╯'
+                    } else if c == '╭' {
+                        '╰'
+                    } else if c == '╰' {
+                        '╭'
+                    } else if c == '┬' {
+                        '┴'
+                    } else if c == '┴' {
+                        '┬'
                     } else {
                         c
                     }
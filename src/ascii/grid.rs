@@ -1,16 +1,21 @@
 //! Grid-based layout operations
 
-use super::canvas::set_canvas_size_to_grid;
-use super::pathfinder::{get_path, merge_path};
+use super::canvas::{block_width, set_canvas_size_to_grid, text_width};
+use super::pathfinder::{merge_path, path_cost, AStarRouter, Bundled, EdgeRouter, Obstacles};
 use super::types::{
-    determine_direction, get_opposite, grid_coord_direction, AsciiGraph, Direction, DrawingCoord,
-    GraphDirection, GridCoord, DOWN, LEFT, LOWER_LEFT, LOWER_RIGHT, RIGHT, UP, UPPER_LEFT,
-    UPPER_RIGHT,
+    determine_direction, get_opposite, grid_coord_direction, AsciiEdge, AsciiGraph, Direction,
+    DrawingCoord, GraphDirection, GridCoord, DOWN, LEFT, LOWER_LEFT, LOWER_RIGHT, RIGHT, UP,
+    UPPER_LEFT, UPPER_RIGHT,
 };
+use crate::types::Port;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 /// Grid step size: node occupies 3x3 cells, plus 1 cell gap = 4
 const GRID_STEP: i32 = 4;
 
+/// Number of alternating down/up sweeps of the crossing minimization pass
+const CROSSING_SWEEPS: usize = 4;
+
 /// Check if a node is in any subgraph
 fn is_node_in_any_subgraph(graph: &AsciiGraph, node_idx: usize) -> bool {
     graph
@@ -133,6 +138,32 @@ pub fn grid_to_drawing_coord_topleft(graph: &AsciiGraph, c: GridCoord) -> Drawin
     DrawingCoord::new(x + graph.offset_x, y + graph.offset_y)
 }
 
+/// Drawing coordinates of the points of an edge's path, with its segments
+/// moved sideways onto their tracks
+pub fn path_to_drawing(graph: &AsciiGraph, edge: &AsciiEdge) -> Vec<DrawingCoord> {
+    let path = &edge.path;
+    (0..path.len())
+        .map(|i| {
+            let base = grid_to_drawing_coord(graph, path[i], None);
+            // A bend takes the shift of the vertical segment for its column
+            // and that of the horizontal one for its row
+            let (mut dx, mut dy) = (0, 0);
+            for seg in [i.checked_sub(1), (i + 1 < path.len()).then_some(i)]
+                .into_iter()
+                .flatten()
+            {
+                let track = edge.tracks.get(seg).copied().unwrap_or(0);
+                if path[seg].x == path[seg + 1].x {
+                    dx = track;
+                } else {
+                    dy = track;
+                }
+            }
+            DrawingCoord::new(base.x + dx, base.y + dy)
+        })
+        .collect()
+}
+
 /// Convert a path of grid coords to drawing coords
 pub fn line_to_drawing(graph: &AsciiGraph, line: &[GridCoord]) -> Vec<DrawingCoord> {
     line.iter()
@@ -145,42 +176,440 @@ pub fn reserve_spot_in_grid(
     graph: &mut AsciiGraph,
     node_idx: usize,
     requested: GridCoord,
+) -> GridCoord {
+    let flow = graph.config.graph_direction;
+    reserve_spot_along(graph, node_idx, requested, flow)
+}
+
+/// Reserve a grid spot, shifting perpendicular to `flow` on collision
+fn reserve_spot_along(
+    graph: &mut AsciiGraph,
+    node_idx: usize,
+    requested: GridCoord,
+    flow: GraphDirection,
 ) -> GridCoord {
     if graph.grid.contains_key(&requested.key()) {
-        // Collision — shift perpendicular to main flow direction
-        let new_pos = if graph.config.graph_direction == GraphDirection::LR {
+        // Collision — shift perpendicular to the flow direction
+        let new_pos = if flow == GraphDirection::LR {
             GridCoord::new(requested.x, requested.y + GRID_STEP)
         } else {
             GridCoord::new(requested.x + GRID_STEP, requested.y)
         };
-        return reserve_spot_in_grid(graph, node_idx, new_pos);
+        return reserve_spot_along(graph, node_idx, new_pos, flow);
+    }
+
+    reserve_block(graph, node_idx, requested);
+    requested
+}
+
+/// Direction that applies between two nodes: the `direction` of the innermost
+/// subgraph holding both (or of its nearest ancestor that sets one), falling
+/// back to the graph direction.
+fn local_direction(graph: &AsciiGraph, a: usize, b: usize) -> GraphDirection {
+    let depth = |mut sg: usize| {
+        let mut d = 0;
+        while let Some(parent) = graph.subgraphs[sg].parent_idx {
+            sg = parent;
+            d += 1;
+        }
+        d
+    };
+    let innermost = (0..graph.subgraphs.len())
+        .filter(|&sg| {
+            let nodes = &graph.subgraphs[sg].node_indices;
+            nodes.contains(&a) && nodes.contains(&b)
+        })
+        .max_by_key(|&sg| depth(sg));
+
+    let mut current = innermost;
+    while let Some(sg) = current {
+        if let Some(direction) = graph.subgraphs[sg].direction {
+            return direction;
+        }
+        current = graph.subgraphs[sg].parent_idx;
     }
+    graph.config.graph_direction
+}
 
-    // Reserve the 3x3 block
+/// Whether a node sits in a subgraph laid out along a different direction
+/// than the graph
+fn in_redirected_subgraph(graph: &AsciiGraph, idx: usize) -> bool {
+    local_direction(graph, idx, idx) != graph.config.graph_direction
+}
+
+/// Reserve the 3x3 grid block of a node, without collision handling
+fn reserve_block(graph: &mut AsciiGraph, node_idx: usize, gc: GridCoord) {
     for dx in 0..3 {
         for dy in 0..3 {
-            let reserved = GridCoord::new(requested.x + dx, requested.y + dy);
+            let reserved = GridCoord::new(gc.x + dx, gc.y + dy);
             graph.grid.insert(reserved.key(), node_idx);
         }
     }
+    graph.nodes[node_idx].grid_coord = Some(gc);
+}
 
-    graph.nodes[node_idx].grid_coord = Some(requested);
-    requested
+/// Reorder the nodes of each level to reduce edge crossings.
+///
+/// Alternates downward and upward barycenter sweeps: each node moves towards
+/// the average position of its neighbours on the levels already swept. Nodes
+/// only trade places with nodes of the same subgraphs, so subgraph boxes stay
+/// contiguous, and the BFS order is kept unless the new one crosses less.
+fn minimize_crossings(graph: &mut AsciiGraph) {
+    let lr = graph.config.graph_direction == GraphDirection::LR;
+    let split = |gc: GridCoord| if lr { (gc.x, gc.y) } else { (gc.y, gc.x) };
+
+    let mut levels: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+    let mut level_of = HashMap::new();
+    let mut pos = HashMap::new();
+    for (idx, node) in graph.nodes.iter().enumerate() {
+        if let Some(gc) = node.grid_coord {
+            let (level, p) = split(gc);
+            levels.entry(level).or_default().push(idx);
+            level_of.insert(idx, level);
+            pos.insert(idx, p);
+        }
+    }
+
+    let edges: Vec<(usize, usize)> = graph
+        .edges
+        .iter()
+        .filter(|e| e.from_idx != e.to_idx)
+        .filter(|e| level_of.contains_key(&e.from_idx) && level_of.contains_key(&e.to_idx))
+        .map(|e| (e.from_idx, e.to_idx))
+        .collect();
+
+    // Neighbours of every node, listed once per edge between them
+    let mut neighbours_of: HashMap<usize, Vec<usize>> = HashMap::new();
+    for &(a, b) in &edges {
+        neighbours_of.entry(a).or_default().push(b);
+        neighbours_of.entry(b).or_default().push(a);
+    }
+
+    let original = pos.clone();
+    let mut best = pos.clone();
+    let mut best_crossings = count_crossings(&edges, &level_of, &pos);
+    if best_crossings == 0 {
+        return;
+    }
+
+    // Nodes of a subgraph with its own direction keep the spots they were
+    // laid out on, since they form a chain across the level
+    let groups: Vec<Option<Vec<usize>>> = (0..graph.nodes.len())
+        .map(|idx| {
+            if in_redirected_subgraph(graph, idx) {
+                return None;
+            }
+            Some(
+                (0..graph.subgraphs.len())
+                    .filter(|&sg| graph.subgraphs[sg].node_indices.contains(&idx))
+                    .collect(),
+            )
+        })
+        .collect();
+
+    for sweep in 0..CROSSING_SWEEPS {
+        let downward = sweep % 2 == 0;
+        let order: Vec<i32> = if downward {
+            levels.keys().copied().collect()
+        } else {
+            levels.keys().rev().copied().collect()
+        };
+
+        for level in order {
+            let nodes = &levels[&level];
+            let barycenter = |idx: usize| -> f64 {
+                let neighbours: Vec<i32> = neighbours_of
+                    .get(&idx)
+                    .into_iter()
+                    .flatten()
+                    .filter(|n| {
+                        let l = level_of[n];
+                        if downward {
+                            l < level
+                        } else {
+                            l > level
+                        }
+                    })
+                    .map(|n| pos[n])
+                    .collect();
+                if neighbours.is_empty() {
+                    pos[&idx] as f64
+                } else {
+                    neighbours.iter().sum::<i32>() as f64 / neighbours.len() as f64
+                }
+            };
+
+            let mut updates = Vec::new();
+            let mut handled: Vec<usize> = Vec::new();
+            for &idx in nodes {
+                if handled.contains(&idx) {
+                    continue;
+                }
+                let mut group: Vec<usize> = nodes
+                    .iter()
+                    .copied()
+                    .filter(|&n| n == idx || (groups[idx].is_some() && groups[n] == groups[idx]))
+                    .collect();
+                handled.extend(&group);
+
+                let mut slots: Vec<i32> = group.iter().map(|n| pos[n]).collect();
+                slots.sort();
+                group.sort_by(|&a, &b| {
+                    barycenter(a)
+                        .partial_cmp(&barycenter(b))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                        .then(pos[&a].cmp(&pos[&b]))
+                });
+                updates.extend(group.into_iter().zip(slots));
+            }
+            for (idx, p) in updates {
+                pos.insert(idx, p);
+            }
+        }
+
+        let crossings = count_crossings(&edges, &level_of, &pos);
+        if crossings < best_crossings {
+            best_crossings = crossings;
+            best = pos.clone();
+        }
+    }
+
+    if best == original {
+        return;
+    }
+
+    graph.grid.clear();
+    for idx in 0..graph.nodes.len() {
+        if let (Some(&level), Some(&p)) = (level_of.get(&idx), best.get(&idx)) {
+            let gc = if lr {
+                GridCoord::new(level, p)
+            } else {
+                GridCoord::new(p, level)
+            };
+            reserve_block(graph, idx, gc);
+        }
+    }
 }
 
-/// Set column widths and row heights for a node's 3x3 grid block
-pub fn set_column_width(graph: &mut AsciiGraph, node_idx: usize) {
+/// Columns between two side by side lanes: both borders and their padding
+const LANE_GAP_COLUMNS: usize = 5;
+/// Rows between two stacked lanes: both borders, their padding and the label
+const LANE_GAP_ROWS: usize = 7;
+
+/// Lay the top-level subgraphs out as lanes, for `swimlanes`, and return the
+/// position across the flow where each lane after the first starts.
+///
+/// Each lane gets a band of positions across the flow of its own, in the
+/// order the subgraphs are declared, with the nodes outside them in a last
+/// lane. Nodes keep their levels, so ranks line up across the lanes, and their
+/// order within each level.
+fn arrange_swimlanes(graph: &mut AsciiGraph) -> Vec<i32> {
+    let lr = graph.config.graph_direction == GraphDirection::LR;
+    let lanes: Vec<usize> = (0..graph.subgraphs.len())
+        .filter(|&sg| {
+            graph.subgraphs[sg].parent_idx.is_none() && !graph.subgraphs[sg].node_indices.is_empty()
+        })
+        .collect();
+    if lanes.is_empty() {
+        return Vec::new();
+    }
+    let lane_of = |idx: usize| {
+        lanes
+            .iter()
+            .position(|&sg| graph.subgraphs[sg].node_indices.contains(&idx))
+            .unwrap_or(lanes.len())
+    };
+
+    // (position, node) per lane and level
+    let mut cells: BTreeMap<(usize, i32), Vec<(i32, usize)>> = BTreeMap::new();
+    for (idx, node) in graph.nodes.iter().enumerate() {
+        if let Some(gc) = node.grid_coord {
+            let (level, position) = if lr { (gc.x, gc.y) } else { (gc.y, gc.x) };
+            cells
+                .entry((lane_of(idx), level))
+                .or_default()
+                .push((position, idx));
+        }
+    }
+    let mut width = vec![0; lanes.len() + 1];
+    for (&(lane, _), nodes) in &cells {
+        width[lane] = width[lane].max(nodes.len() as i32);
+    }
+    let mut start = vec![0; lanes.len() + 1];
+    for lane in 1..start.len() {
+        start[lane] = start[lane - 1] + width[lane - 1] * GRID_STEP;
+    }
+
+    graph.grid.clear();
+    for ((lane, level), mut nodes) in cells {
+        nodes.sort();
+        for (k, (_, idx)) in nodes.into_iter().enumerate() {
+            let position = start[lane] + k as i32 * GRID_STEP;
+            let gc = if lr {
+                GridCoord::new(level, position)
+            } else {
+                GridCoord::new(position, level)
+            };
+            reserve_block(graph, idx, gc);
+        }
+    }
+    (1..start.len())
+        .filter(|&lane| width[lane] > 0)
+        .map(|lane| start[lane])
+        .collect()
+}
+
+/// Whether the boxes of the top-level subgraphs would overlap, or take in a
+/// node that is not theirs: nodes of different subgraphs can end up
+/// interleaved across the flow when edges cross between them
+fn subgraphs_overlap(graph: &AsciiGraph) -> bool {
+    let boxes: Vec<(&[usize], GridCoord, GridCoord)> = graph
+        .subgraphs
+        .iter()
+        .filter(|sg| sg.parent_idx.is_none())
+        .filter_map(|sg| {
+            let coords = sg
+                .node_indices
+                .iter()
+                .filter_map(|&idx| graph.nodes[idx].grid_coord);
+            let min = coords
+                .clone()
+                .reduce(|a, b| GridCoord::new(a.x.min(b.x), a.y.min(b.y)))?;
+            let max = coords.reduce(|a, b| GridCoord::new(a.x.max(b.x), a.y.max(b.y)))?;
+            Some((sg.node_indices.as_slice(), min, max))
+        })
+        .collect();
+    let inside = |gc: GridCoord, min: GridCoord, max: GridCoord| {
+        (min.x..=max.x).contains(&gc.x) && (min.y..=max.y).contains(&gc.y)
+    };
+
+    boxes.iter().enumerate().any(|(i, &(nodes, min, max))| {
+        let foreign_node = graph.nodes.iter().enumerate().any(|(idx, node)| {
+            !nodes.contains(&idx) && node.grid_coord.is_some_and(|gc| inside(gc, min, max))
+        });
+        let overlapping_box = boxes[i + 1..].iter().any(|&(_, other_min, other_max)| {
+            min.x <= other_max.x
+                && other_min.x <= max.x
+                && min.y <= other_max.y
+                && other_min.y <= max.y
+        });
+        foreign_node || overlapping_box
+    })
+}
+
+/// Stretch the boxes of the top-level subgraphs to the same extent along the
+/// flow, so that with `swimlanes` they read as lanes of equal length
+pub fn stretch_lanes(graph: &mut AsciiGraph) {
+    let lr = graph.config.graph_direction == GraphDirection::LR;
+    let lanes = graph
+        .subgraphs
+        .iter_mut()
+        .filter(|sg| sg.parent_idx.is_none() && !sg.node_indices.is_empty());
+    let spans: Vec<(&mut i32, &mut i32)> = lanes
+        .map(|sg| {
+            if lr {
+                (&mut sg.min_x, &mut sg.max_x)
+            } else {
+                (&mut sg.min_y, &mut sg.max_y)
+            }
+        })
+        .collect();
+    let (Some(lo), Some(hi)) = (
+        spans.iter().map(|(lo, _)| **lo).min(),
+        spans.iter().map(|(_, hi)| **hi).max(),
+    ) else {
+        return;
+    };
+    for (min, max) in spans {
+        *min = lo;
+        *max = hi;
+    }
+}
+
+/// Shift all nodes one cell right/down when an edge is pinned to a north or
+/// west port, so that nodes on the first row/column still have a free cell
+/// on that side for the path to start from. Returns the shift applied.
+fn make_room_for_ports(graph: &mut AsciiGraph) -> (i32, i32) {
+    let ports: Vec<Port> = graph
+        .edges
+        .iter()
+        .flat_map(|e| [e.start_port, e.end_port])
+        .flatten()
+        .collect();
+    let dx = ports.contains(&Port::West) as i32;
+    let dy = ports.contains(&Port::North) as i32;
+    if dx == 0 && dy == 0 {
+        return (0, 0);
+    }
+
+    graph.grid.clear();
+    for idx in 0..graph.nodes.len() {
+        if let Some(gc) = graph.nodes[idx].grid_coord {
+            reserve_block(graph, idx, GridCoord::new(gc.x + dx, gc.y + dy));
+        }
+    }
+    (dx, dy)
+}
+
+/// Count pairs of edges that cross between the same two levels
+fn count_crossings(
+    edges: &[(usize, usize)],
+    level_of: &HashMap<usize, i32>,
+    pos: &HashMap<usize, i32>,
+) -> usize {
+    // Orient every edge from its lower to its higher level
+    let spans: Vec<(usize, usize)> = edges
+        .iter()
+        .map(|&(a, b)| {
+            if level_of[&a] <= level_of[&b] {
+                (a, b)
+            } else {
+                (b, a)
+            }
+        })
+        .collect();
+
+    let mut crossings = 0;
+    for (i, &(a1, b1)) in spans.iter().enumerate() {
+        for &(a2, b2) in &spans[i + 1..] {
+            if a1 == a2
+                || b1 == b2
+                || level_of[&a1] != level_of[&a2]
+                || level_of[&b1] != level_of[&b2]
+            {
+                continue;
+            }
+            if (pos[&a1] - pos[&a2]).signum() * (pos[&b1] - pos[&b2]).signum() < 0 {
+                crossings += 1;
+            }
+        }
+    }
+    crossings
+}
+
+/// Set column widths and row heights for a node's 3x3 grid block, in a graph
+/// whose widest label is `widest_label` columns wide
+pub fn set_column_width(graph: &mut AsciiGraph, node_idx: usize, widest_label: usize) {
     let gc = match graph.nodes[node_idx].grid_coord {
         Some(c) => c,
         None => return,
     };
-    let label_len = graph.nodes[node_idx].display_label.len();
+    let label = &graph.nodes[node_idx].display_label;
+    let label_len = block_width(label);
+    let label_lines = label.lines().count().max(1);
     let padding = graph.config.box_border_padding;
 
-    // 3 columns: [border=1] [content=2*padding+labelLen] [border=1]
-    let col_widths = [1, 2 * padding + label_len, 1];
-    // 3 rows: [border=1] [content=1+2*padding] [border=1]
-    let row_heights = [1, 1 + 2 * padding, 1];
+    // 3 columns: [border=1] [content=2*padding+labelLen] [border=1], with
+    // the content as wide as the configured node widths ask
+    let box_width = |label_len: usize| (2 * padding + label_len + 2) as f64;
+    let content = graph
+        .config
+        .node_width
+        .fit(box_width(label_len), box_width(widest_label)) as usize
+        - 2;
+    let col_widths = [1, content, 1];
+    // 3 rows: [border=1] [content=labelLines+2*padding] [border=1]
+    let row_heights = [1, label_lines + 2 * padding, 1];
 
     for (idx, &w) in col_widths.iter().enumerate() {
         let x_coord = gc.x + idx as i32;
@@ -214,6 +643,51 @@ pub fn set_column_width(graph: &mut AsciiGraph, node_idx: usize) {
     }
 }
 
+/// Give the levels skipped by a long edge (`---->`) some width, so that they
+/// still take up space when no node sits on them
+fn reserve_long_edge_space(graph: &mut AsciiGraph, edge_idx: usize) {
+    let edge = &graph.edges[edge_idx];
+    if edge.min_length <= 1 {
+        return;
+    }
+    let (from, to) = match (
+        graph.nodes[edge.from_idx].grid_coord,
+        graph.nodes[edge.to_idx].grid_coord,
+    ) {
+        (Some(f), Some(t)) => (f, t),
+        _ => return,
+    };
+
+    let lr = graph.config.graph_direction == GraphDirection::LR;
+    let level_of = |gc: GridCoord| if lr { gc.x } else { gc.y };
+    let (from_level, to_level) = (level_of(from), level_of(to));
+    let occupied: Vec<i32> = graph
+        .nodes
+        .iter()
+        .filter_map(|n| n.grid_coord.map(level_of))
+        .collect();
+
+    let (sizes, padding) = if lr {
+        (&mut graph.column_width, graph.config.padding_x)
+    } else {
+        (&mut graph.row_height, graph.config.padding_y)
+    };
+
+    let mut level = from_level + GRID_STEP;
+    while level < to_level {
+        // Gap before the level, plus its middle cell when no node sits there
+        let mut cells = vec![level - 1];
+        if !occupied.contains(&level) {
+            cells.push(level + 1);
+        }
+        for cell in cells {
+            let current = *sizes.get(&cell).unwrap_or(&0);
+            sizes.insert(cell, current.max(padding));
+        }
+        level += GRID_STEP;
+    }
+}
+
 /// Increase grid size for path coordinates
 pub fn increase_grid_size_for_path(graph: &mut AsciiGraph, path: &[GridCoord]) {
     for c in path {
@@ -286,8 +760,23 @@ pub fn determine_start_and_end_dir(
     }
 }
 
+/// Position on a node's 3x3 block that a port attaches to
+fn port_dir(port: Port) -> Direction {
+    match port {
+        Port::North => UP,
+        Port::South => DOWN,
+        Port::East => RIGHT,
+        Port::West => LEFT,
+    }
+}
+
 /// Determine the path for an edge
-pub fn determine_path(graph: &mut AsciiGraph, edge_idx: usize) {
+pub fn determine_path(
+    graph: &mut AsciiGraph,
+    edge_idx: usize,
+    router: &mut dyn EdgeRouter,
+    obstacles: &Obstacles,
+) {
     let from_idx = graph.edges[edge_idx].from_idx;
     let to_idx = graph.edges[edge_idx].to_idx;
     let is_self_ref = from_idx == to_idx;
@@ -301,17 +790,27 @@ pub fn determine_path(graph: &mut AsciiGraph, edge_idx: usize) {
         None => return,
     };
 
-    let (pref_dir, pref_opp, alt_dir, alt_opp) = determine_start_and_end_dir(
+    let (mut pref_dir, mut pref_opp, mut alt_dir, mut alt_opp) = determine_start_and_end_dir(
         from_coord,
         to_coord,
         is_self_ref,
-        graph.config.graph_direction,
+        local_direction(graph, from_idx, to_idx),
     );
 
+    // Ports pin an end of the edge to one side of its node
+    if let Some(port) = graph.edges[edge_idx].start_port {
+        pref_dir = port_dir(port);
+        alt_dir = pref_dir;
+    }
+    if let Some(port) = graph.edges[edge_idx].end_port {
+        pref_opp = port_dir(port);
+        alt_opp = pref_opp;
+    }
+
     // Try preferred path
     let pref_from = grid_coord_direction(from_coord, pref_dir);
     let pref_to = grid_coord_direction(to_coord, pref_opp);
-    let preferred_path = get_path(&graph.grid, pref_from, pref_to);
+    let preferred_path = router.route(obstacles, pref_from, pref_to);
 
     if preferred_path.is_none() {
         graph.edges[edge_idx].start_dir = alt_dir;
@@ -324,7 +823,7 @@ pub fn determine_path(graph: &mut AsciiGraph, edge_idx: usize) {
     // Try alternative path
     let alt_from = grid_coord_direction(from_coord, alt_dir);
     let alt_to = grid_coord_direction(to_coord, alt_opp);
-    let alternative_path = get_path(&graph.grid, alt_from, alt_to);
+    let alternative_path = router.route(obstacles, alt_from, alt_to);
 
     if alternative_path.is_none() {
         graph.edges[edge_idx].start_dir = pref_dir;
@@ -334,8 +833,8 @@ pub fn determine_path(graph: &mut AsciiGraph, edge_idx: usize) {
     }
     let alternative_path = merge_path(alternative_path.unwrap());
 
-    // Pick shorter path
-    if preferred_path.len() <= alternative_path.len() {
+    // Pick the cheaper path (grid geometry only, independent of padding)
+    if path_cost(&preferred_path) <= path_cost(&alternative_path) {
         graph.edges[edge_idx].start_dir = pref_dir;
         graph.edges[edge_idx].end_dir = pref_opp;
         graph.edges[edge_idx].path = preferred_path;
@@ -346,6 +845,124 @@ pub fn determine_path(graph: &mut AsciiGraph, edge_idx: usize) {
     }
 }
 
+/// How a bundled edge meets its nodes: the sides it leaves and enters by, and
+/// the hub cells just outside them that the edges of its bundles share
+#[derive(Debug, Clone, Copy)]
+struct BundledEnds {
+    start_dir: Direction,
+    end_dir: Direction,
+    start_hub: Option<GridCoord>,
+    end_hub: Option<GridCoord>,
+}
+
+/// Unit step away from a node out of the side `dir` attaches to
+fn outward(dir: Direction) -> Option<(i32, i32)> {
+    match dir {
+        d if d == UP => Some((0, -1)),
+        d if d == DOWN => Some((0, 1)),
+        d if d == LEFT => Some((-1, 0)),
+        d if d == RIGHT => Some((1, 0)),
+        _ => None,
+    }
+}
+
+/// Group the forward edges that leave (or enter) the same side of a node into
+/// bundles, when there are at least two of them.
+///
+/// Forward edges leave along the flow and enter against it, unless a port
+/// picks the side. Each bundle meets its node through one hub cell, next to
+/// the side in the gap, so its edges run as a single trunk up to there.
+fn edge_bundles(graph: &AsciiGraph, obstacles: &Obstacles) -> Vec<Option<BundledEnds>> {
+    let sides: Vec<Option<(Direction, Direction)>> = graph
+        .edges
+        .iter()
+        .map(|edge| {
+            let from = graph.nodes.get(edge.from_idx)?.grid_coord?;
+            let to = graph.nodes.get(edge.to_idx)?.grid_coord?;
+            let (start, end) = match local_direction(graph, edge.from_idx, edge.to_idx) {
+                GraphDirection::LR if to.x > from.x => (RIGHT, LEFT),
+                GraphDirection::TD if to.y > from.y => (DOWN, UP),
+                _ => return None,
+            };
+            Some((
+                edge.start_port.map_or(start, port_dir),
+                edge.end_port.map_or(end, port_dir),
+            ))
+        })
+        .collect();
+
+    // Edges per (node, leaving, side)
+    let key = |node: usize, leaves: bool, dir: Direction| (node, leaves, dir.x, dir.y);
+    let mut counts = HashMap::new();
+    for (edge, side) in graph.edges.iter().zip(&sides) {
+        if let Some((start, end)) = *side {
+            *counts.entry(key(edge.from_idx, true, start)).or_insert(0) += 1;
+            *counts.entry(key(edge.to_idx, false, end)).or_insert(0) += 1;
+        }
+    }
+    let hub = |node: usize, leaves: bool, dir: Direction| {
+        if counts[&key(node, leaves, dir)] < 2 {
+            return None;
+        }
+        let port = grid_coord_direction(graph.nodes[node].grid_coord?, dir);
+        let (dx, dy) = outward(dir)?;
+        Some(GridCoord::new(port.x + dx, port.y + dy)).filter(|&c| obstacles.is_free(c))
+    };
+
+    graph
+        .edges
+        .iter()
+        .zip(sides)
+        .map(|(edge, side)| {
+            let (start_dir, end_dir) = side?;
+            let ends = BundledEnds {
+                start_dir,
+                end_dir,
+                start_hub: hub(edge.from_idx, true, start_dir),
+                end_hub: hub(edge.to_idx, false, end_dir),
+            };
+            (ends.start_hub.is_some() || ends.end_hub.is_some()).then_some(ends)
+        })
+        .collect()
+}
+
+/// Route a bundled edge through its hubs. Returns false, leaving the edge
+/// alone, if there is no route between them.
+fn determine_bundled_path(
+    graph: &mut AsciiGraph,
+    edge_idx: usize,
+    router: &mut dyn EdgeRouter,
+    obstacles: &Obstacles,
+    ends: BundledEnds,
+) -> bool {
+    let edge = &graph.edges[edge_idx];
+    let (Some(from_coord), Some(to_coord)) = (
+        graph.nodes[edge.from_idx].grid_coord,
+        graph.nodes[edge.to_idx].grid_coord,
+    ) else {
+        return false;
+    };
+    let from = grid_coord_direction(from_coord, ends.start_dir);
+    let to = grid_coord_direction(to_coord, ends.end_dir);
+    let Some(route) = router.route(
+        obstacles,
+        ends.start_hub.unwrap_or(from),
+        ends.end_hub.unwrap_or(to),
+    ) else {
+        return false;
+    };
+
+    let mut path = Vec::with_capacity(route.len() + 2);
+    path.extend(ends.start_hub.map(|_| from));
+    path.extend(route);
+    path.extend(ends.end_hub.map(|_| to));
+    let edge = &mut graph.edges[edge_idx];
+    edge.start_dir = ends.start_dir;
+    edge.end_dir = ends.end_dir;
+    edge.path = merge_path(path);
+    true
+}
+
 /// Find the best line segment in an edge's path to place a label on.
 /// Picks the first segment wide enough for the label, or the widest segment overall.
 /// Also increases the column width at the label position to fit the text.
@@ -355,7 +972,7 @@ pub fn determine_label_line(graph: &mut AsciiGraph, edge_idx: usize) {
         return;
     }
 
-    let len_label = edge.text.len();
+    let len_label = text_width(&edge.text);
     let mut prev_step = edge.path[0];
     let mut largest_line: (GridCoord, GridCoord) = (prev_step, edge.path[1]);
     let mut largest_line_size = 0;
@@ -388,6 +1005,71 @@ pub fn determine_label_line(graph: &mut AsciiGraph, edge_idx: usize) {
     graph.edges[edge_idx].label_line = vec![largest_line.0, largest_line.1];
 }
 
+/// Make room for the source- and target-side labels of an edge.
+/// Horizontal ends need a gap column wide enough for the text; vertical ends
+/// need enough rows to keep the end labels apart from the middle label.
+fn reserve_end_label_space(graph: &mut AsciiGraph, edge_idx: usize) {
+    let edge = &graph.edges[edge_idx];
+    let path = &edge.path;
+    if path.len() < 2 || (edge.start_text.is_empty() && edge.end_text.is_empty()) {
+        return;
+    }
+
+    // The gap cell next to each end, along the first and last segments
+    let step = |from: GridCoord, to: GridCoord| {
+        GridCoord::new(
+            from.x + (to.x - from.x).signum(),
+            from.y + (to.y - from.y).signum(),
+        )
+    };
+    let n = path.len();
+    let start_cell = step(path[0], path[1]);
+    let end_cell = step(path[n - 1], path[n - 2]);
+    let start_horizontal = path[0].y == path[1].y;
+    let end_horizontal = path[n - 1].y == path[n - 2].y;
+
+    let mut columns: Vec<(i32, usize)> = Vec::new();
+    let mut rows: Vec<(i32, usize)> = Vec::new();
+    for (text, cell, horizontal, slack) in [
+        (&edge.start_text, start_cell, start_horizontal, 1),
+        (&edge.end_text, end_cell, end_horizontal, 2),
+    ] {
+        if text.is_empty() {
+            continue;
+        }
+        if horizontal {
+            match columns.iter_mut().find(|(x, _)| *x == cell.x) {
+                Some((_, w)) => *w += text_width(text) + 1,
+                None => columns.push((cell.x, text_width(text) + slack)),
+            }
+        } else {
+            match rows.iter_mut().find(|(y, _)| *y == cell.y) {
+                Some((_, h)) => *h += 2,
+                None => rows.push((cell.y, 1)),
+            }
+        }
+    }
+
+    // The middle label shares a vertical gap with the end labels
+    if !edge.text.is_empty() && edge.label_line.len() == 2 {
+        let (a, b) = (edge.label_line[0], edge.label_line[1]);
+        for (y, h) in rows.iter_mut() {
+            if a.y.min(b.y) < *y && *y < a.y.max(b.y) {
+                *h += 2;
+            }
+        }
+    }
+
+    for (x, w) in columns {
+        let current = *graph.column_width.get(&x).unwrap_or(&0);
+        graph.column_width.insert(x, current.max(w));
+    }
+    for (y, h) in rows {
+        let current = *graph.row_height.get(&y).unwrap_or(&0);
+        graph.row_height.insert(y, current.max(h));
+    }
+}
+
 /// Calculate the total character width of a line segment by summing column widths.
 fn calculate_line_width(graph: &AsciiGraph, line: (GridCoord, GridCoord)) -> usize {
     let mut total = 0;
@@ -410,19 +1092,232 @@ fn get_children(graph: &AsciiGraph, node_idx: usize) -> Vec<usize> {
     children
 }
 
+/// Edges to reverse to make the graph acyclic: the back edges of a depth-first
+/// search started from each node in definition order, nodes without incoming
+/// edges first. A cycle entered from outside is then broken at the edge that
+/// closes it, not at whichever of its nodes was defined first
+pub(crate) fn back_edges(n: usize, edges: &[(usize, usize)]) -> Vec<bool> {
+    let mut out: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut has_incoming = vec![false; n];
+    for (i, &(a, b)) in edges.iter().enumerate() {
+        out[a].push(i);
+        has_incoming[b] |= a != b;
+    }
+
+    // 0 = unvisited, 1 = on the stack, 2 = done
+    let mut state = vec![0u8; n];
+    let mut reversed = vec![false; edges.len()];
+    let sources = (0..n).filter(|&v| !has_incoming[v]);
+    for start in sources.chain(0..n) {
+        if state[start] != 0 {
+            continue;
+        }
+        state[start] = 1;
+        let mut stack = vec![(start, 0usize)];
+        while let Some(top) = stack.last_mut() {
+            let (v, i) = *top;
+            if i == out[v].len() {
+                state[v] = 2;
+                stack.pop();
+                continue;
+            }
+            top.1 += 1;
+            let e = out[v][i];
+            let w = edges[e].1;
+            match state[w] {
+                0 => {
+                    state[w] = 1;
+                    stack.push((w, 0));
+                }
+                1 => reversed[e] = true,
+                _ => {}
+            }
+        }
+    }
+    reversed
+}
+
+/// Whether `node_idx` can reach itself again through other nodes
+fn on_cycle(graph: &AsciiGraph, node_idx: usize) -> bool {
+    let mut seen = HashSet::new();
+    let mut stack: Vec<usize> = get_children(graph, node_idx)
+        .into_iter()
+        .filter(|&c| c != node_idx)
+        .collect();
+    while let Some(idx) = stack.pop() {
+        if idx == node_idx {
+            return true;
+        }
+        if seen.insert(idx) {
+            stack.extend(get_children(graph, idx));
+        }
+    }
+    false
+}
+
+/// A segment of an edge's path, on the grid column or row it runs along
+struct CorridorSegment {
+    edge: usize,
+    index: usize,
+    /// First and last grid cell along the corridor
+    lo: i32,
+    hi: i32,
+    /// The first or last segment, which meets a node side
+    pinned: bool,
+    /// Whether the `lo` and `hi` ends are the ends of the path, on a node side
+    node_at_lo: bool,
+    node_at_hi: bool,
+}
+
+/// Spread edges that share a corridor onto tracks of their own.
+///
+/// Overlapping segments on the same grid column (or row) are shifted sideways
+/// by different amounts, so that they run next to each other instead of
+/// merging into one line. Edges with the same source or target may still share
+/// a track, where their lines branch off or join. The first and last segments
+/// of a path, where it meets the node sides, keep the middle of their cells
+/// unless another edge's end is already there. Columns and rows without nodes
+/// are widened when their tracks need the room, moving the nodes after them.
+///
+/// Only the text output draws the tracks, so this runs after
+/// [`create_mapping`] rather than as part of it.
+pub fn allocate_tracks(graph: &mut AsciiGraph) {
+    let mut corridors: BTreeMap<(bool, i32), Vec<CorridorSegment>> = BTreeMap::new();
+    for (e, edge) in graph.edges.iter().enumerate() {
+        let n = edge.path.len();
+        for index in 0..n.saturating_sub(1) {
+            let (a, b) = (edge.path[index], edge.path[index + 1]);
+            if a == b {
+                continue;
+            }
+            let vertical = a.x == b.x;
+            let along = |c: GridCoord| if vertical { c.y } else { c.x };
+            let (line, lo, hi) = if vertical {
+                (a.x, a.y.min(b.y), a.y.max(b.y))
+            } else {
+                (a.y, a.x.min(b.x), a.x.max(b.x))
+            };
+            // Where the path starts or ends on this segment
+            let node_end =
+                |at: i32| (index == 0 && along(a) == at) || (index + 2 == n && along(b) == at);
+            corridors
+                .entry((vertical, line))
+                .or_default()
+                .push(CorridorSegment {
+                    edge: e,
+                    index,
+                    lo,
+                    hi,
+                    pinned: index == 0 || index + 2 == n,
+                    node_at_lo: node_end(lo),
+                    node_at_hi: node_end(hi),
+                });
+        }
+    }
+
+    for edge in graph.edges.iter_mut() {
+        edge.tracks = vec![0; edge.path.len().saturating_sub(1)];
+    }
+    let occupied: HashSet<(bool, i32)> = graph
+        .grid
+        .keys()
+        .filter_map(|key| {
+            let (x, y) = key.split_once(',')?;
+            Some([(true, x.parse().ok()?), (false, y.parse().ok()?)])
+        })
+        .flatten()
+        .collect();
+
+    for ((vertical, line), mut segments) in corridors {
+        // Pinned segments get the middle track first; the others go around them
+        segments.sort_by_key(|s| (!s.pinned, s.lo, s.hi, s.edge));
+        // Segments meeting end to end would join into one line at the bend,
+        // except where one of them leaves or enters a node
+        let conflict = |a: &CorridorSegment, b: &CorridorSegment| {
+            let (ea, eb) = (&graph.edges[a.edge], &graph.edges[b.edge]);
+            let touch = |a: &CorridorSegment, b: &CorridorSegment| {
+                a.hi == b.lo && !a.node_at_hi && !b.node_at_lo
+            };
+            let meet = (a.lo < b.hi && b.lo < a.hi) || touch(a, b) || touch(b, a);
+            meet && ea.from_idx != eb.from_idx && ea.to_idx != eb.to_idx
+        };
+        let mut track_of: Vec<usize> = Vec::with_capacity(segments.len());
+        for (i, segment) in segments.iter().enumerate() {
+            let mut track = 0;
+            while (0..i).any(|j| track_of[j] == track && conflict(&segments[j], segment)) {
+                track += 1;
+            }
+            track_of.push(track);
+        }
+        let count = track_of.iter().max().map_or(1, |t| t + 1);
+        if count == 1 {
+            continue;
+        }
+
+        // Tracks alternate around the middle: 0, +1, -1, +2, ... steps apart,
+        // two characters per step when the cell has room for a gap between them
+        let reach = (count / 2) as i32;
+        let sizes = if vertical {
+            &mut graph.column_width
+        } else {
+            &mut graph.row_height
+        };
+        // Next to the nodes, the outermost characters of the cell stay free for
+        // the arrowheads and bends of the edges crossing it
+        let has_nodes = occupied.contains(&(vertical, line));
+        let size = sizes.entry(line).or_insert(0);
+        let mut room = (*size as i32 - if has_nodes { 1 } else { 3 }) / 2;
+        if room < 2 * reach && !has_nodes {
+            *size = (4 * reach + 3) as usize;
+            room = 2 * reach;
+        }
+        let spacing = if room >= 2 * reach { 2 } else { 1 };
+        for (segment, track) in segments.iter().zip(track_of) {
+            let step = track.div_ceil(2) as i32 * if track % 2 == 1 { 1 } else { -1 };
+            graph.edges[segment.edge].tracks[segment.index] = (step * spacing).clamp(-room, room);
+        }
+    }
+    place_nodes(graph);
+}
+
 /// Create the node-to-grid mapping
 pub fn create_mapping(graph: &mut AsciiGraph) {
+    create_mapping_with_router(graph, &mut AStarRouter::default());
+}
+
+/// Create the node-to-grid mapping, routing edges with `router`
+pub fn create_mapping_with_router(graph: &mut AsciiGraph, router: &mut dyn EdgeRouter) {
     let dir = graph.config.graph_direction;
-    let mut highest_position_per_level: std::collections::HashMap<i32, i32> =
-        std::collections::HashMap::new();
+    let mut highest_position_per_level: HashMap<i32, i32> = HashMap::new();
+
+    // Break cycles at the back edges of a depth-first search. The children
+    // below are the ones along the remaining, acyclic edges
+    let pairs: Vec<(usize, usize)> = graph.edges.iter().map(|e| (e.from_idx, e.to_idx)).collect();
+    let back = back_edges(graph.nodes.len(), &pairs);
+    let mut acyclic_children: Vec<Vec<(usize, i32)>> = vec![Vec::new(); graph.nodes.len()];
+    let mut has_acyclic_parent = vec![false; graph.nodes.len()];
+    for ((edge, &(a, b)), &is_back) in graph.edges.iter().zip(&pairs).zip(&back) {
+        if a != b && !is_back {
+            // Long edges (`---->`) push the child further along the layout axis
+            let step = GRID_STEP * edge.min_length.max(1) as i32;
+            match acyclic_children[a].iter_mut().find(|(c, _)| *c == b) {
+                Some(child) => child.1 = child.1.max(step),
+                None => acyclic_children[a].push((b, step)),
+            }
+            has_acyclic_parent[b] = true;
+        }
+    }
 
     // Identify root nodes — nodes that aren't seen as children before they appear
-    // This preserves the order of first definition
-    let mut nodes_seen = std::collections::HashSet::new();
+    // This preserves the order of first definition. A node on a cycle is
+    // always someone's child, so the first one defined is only a root when
+    // nothing outside leads into the cycle
+    let mut nodes_seen = HashSet::new();
     let mut root_indices = Vec::new();
+    let entered_cycle = |idx: usize| has_acyclic_parent[idx] && on_cycle(graph, idx);
 
     for idx in 0..graph.nodes.len() {
-        if !nodes_seen.contains(&idx) {
+        if !nodes_seen.contains(&idx) && !entered_cycle(idx) {
             root_indices.push(idx);
         }
         nodes_seen.insert(idx);
@@ -503,7 +1398,7 @@ pub fn create_mapping(graph: &mut AsciiGraph) {
         .cloned()
         .collect();
     let mut queue: Vec<usize> = all_placed_roots.clone();
-    let mut visited: std::collections::HashSet<usize> = all_placed_roots.iter().cloned().collect();
+    let mut visited: HashSet<usize> = all_placed_roots.iter().cloned().collect();
 
     while !queue.is_empty() {
         let current_idx = queue.remove(0);
@@ -512,13 +1407,7 @@ pub fn create_mapping(graph: &mut AsciiGraph) {
             None => continue,
         };
 
-        let child_level = if dir == GraphDirection::LR {
-            gc.x + GRID_STEP
-        } else {
-            gc.y + GRID_STEP
-        };
-
-        for child_idx in get_children(graph, current_idx) {
+        for &(child_idx, step) in &acyclic_children[current_idx] {
             if visited.contains(&child_idx) {
                 continue;
             }
@@ -527,6 +1416,34 @@ pub fn create_mapping(graph: &mut AsciiGraph) {
                 continue; // Already placed
             }
 
+            // Inside a subgraph with its own direction, the child is laid out
+            // next to its parent along that direction instead
+            let local_dir = local_direction(graph, current_idx, child_idx);
+            if local_dir != dir {
+                let requested = if local_dir == GraphDirection::LR {
+                    GridCoord::new(gc.x + step, gc.y)
+                } else {
+                    GridCoord::new(gc.x, gc.y + step)
+                };
+                let placed = reserve_spot_along(graph, child_idx, requested, local_dir);
+                let (level, position) = if dir == GraphDirection::LR {
+                    (placed.x, placed.y)
+                } else {
+                    (placed.y, placed.x)
+                };
+                let highest = highest_position_per_level.entry(level).or_insert(0);
+                *highest = (*highest).max(position + GRID_STEP);
+
+                visited.insert(child_idx);
+                queue.push(child_idx);
+                continue;
+            }
+            let child_level = if dir == GraphDirection::LR {
+                gc.x + step
+            } else {
+                gc.y + step
+            };
+
             let highest_position = *highest_position_per_level.get(&child_level).unwrap_or(&0);
 
             let requested = if dir == GraphDirection::LR {
@@ -545,18 +1462,80 @@ pub fn create_mapping(graph: &mut AsciiGraph) {
         }
     }
 
+    minimize_crossings(graph);
+    // Subgraphs whose boxes would overlap get a band across the flow each,
+    // as lanes do
+    let lane_starts = if graph.swimlanes || subgraphs_overlap(graph) {
+        arrange_swimlanes(graph)
+    } else {
+        Vec::new()
+    };
+    let (port_dx, port_dy) = make_room_for_ports(graph);
+
     // Set column widths and row heights BEFORE determining paths
+    let widest_label = graph
+        .nodes
+        .iter()
+        .map(|n| block_width(&n.display_label))
+        .max()
+        .unwrap_or(0);
     for i in 0..graph.nodes.len() {
-        set_column_width(graph, i);
+        set_column_width(graph, i, widest_label);
     }
-
-    // Determine edge paths (now that column widths are set)
     for i in 0..graph.edges.len() {
-        determine_path(graph, i);
+        reserve_long_edge_space(graph, i);
+    }
+    // Room between two lanes for both their borders
+    let (gaps, shift, gap) = match dir {
+        GraphDirection::LR => (&mut graph.row_height, port_dy, LANE_GAP_ROWS),
+        _ => (&mut graph.column_width, port_dx, LANE_GAP_COLUMNS),
+    };
+    for start in lane_starts {
+        let current = gaps.entry(start + shift - 1).or_insert(0);
+        *current = (*current).max(gap);
+    }
+    // The gutter added for ports stays empty unless a path runs through it
+    if port_dx > 0 {
+        graph.column_width.remove(&0);
+    }
+    if port_dy > 0 {
+        graph.row_height.remove(&0);
+    }
+
+    // Determine edge paths (now that column widths are set). Nodes no longer
+    // move, so the obstacles are indexed once and parallel edges share a route
+    let obstacles = Obstacles::new(&graph.grid);
+    let mut router = Bundled::new(router);
+    let bundles = if graph.bundle_edges {
+        edge_bundles(graph, &obstacles)
+    } else {
+        vec![None; graph.edges.len()]
+    };
+    for (i, ends) in bundles.into_iter().enumerate() {
+        let bundled = ends
+            .is_some_and(|ends| determine_bundled_path(graph, i, &mut router, &obstacles, ends));
+        if !bundled {
+            determine_path(graph, i, &mut router, &obstacles);
+        }
         determine_label_line(graph, i);
+        reserve_end_label_space(graph, i);
         increase_grid_size_for_path(graph, &graph.edges[i].path.clone());
     }
 
+    // A path in the port gutter needs a cell between the line and the boxes
+    if let Some(w) = graph.column_width.get_mut(&0).filter(|_| port_dx > 0) {
+        *w = (*w).max(3);
+    }
+    if let Some(h) = graph.row_height.get_mut(&0).filter(|_| port_dy > 0) {
+        *h = (*h).max(3);
+    }
+
+    place_nodes(graph);
+}
+
+/// Convert the nodes' grid coords to drawing coords, draw their boxes and
+/// size the canvas to the grid
+fn place_nodes(graph: &mut AsciiGraph) {
     // Convert grid coords to drawing coords and generate node box drawings
     for i in 0..graph.nodes.len() {
         if let Some(gc) = graph.nodes[i].grid_coord {
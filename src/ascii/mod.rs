@@ -1,7 +1,9 @@
 //! ASCII rendering module
 
+pub mod audit;
 pub mod canvas;
 pub mod class_diagram;
+pub mod color;
 pub mod draw;
 pub mod er_diagram;
 pub mod flowchart;
@@ -11,16 +13,23 @@ pub mod pathfinder;
 pub mod sequence;
 pub mod types;
 
-use crate::parser;
-use crate::types::DiagramType;
-use crate::AsciiRenderOptions;
+use crate::limits::{Limit, TooLarge};
+use crate::parser::{self, ParseMode};
+use crate::types::{Charset, DiagramType, Direction, LabelAlign, ParsedDiagram};
+use crate::{AsciiRenderOptions, RenderOutput};
 use types::AsciiConfig;
 
-/// Parse configuration from input text (lines like paddingX=2, paddingY=1)
-fn parse_config_from_text(text: &str, base_opts: AsciiRenderOptions) -> AsciiRenderOptions {
+/// Parse configuration from input text (lines like paddingX=2, paddingY=1, headerEvery=20,
+/// maxGap=30, maxLabelWidth=12, wrapWidth=12, minNodeWidth=10, maxNodeWidth=20, uniformNodeWidth=true,
+/// labelAlign=left, maxWidth=80, charset=safe, roundedCorners=false), and the same settings in a
+/// `%% m2svg: padding_x=2 charset=ascii` directive line
+pub fn parse_config_from_text(text: &str, base_opts: AsciiRenderOptions) -> AsciiRenderOptions {
     let mut opts = base_opts;
 
-    for line in text.lines() {
+    let directive = options_directive(text)
+        .into_iter()
+        .map(|(key, value)| format!("{}={}", key, value));
+    for line in text.lines().map(str::to_string).chain(directive) {
         let line = line.trim().to_lowercase();
         if line.starts_with("paddingx=") {
             if let Some(val) = line.strip_prefix("paddingx=") {
@@ -34,12 +43,81 @@ fn parse_config_from_text(text: &str, base_opts: AsciiRenderOptions) -> AsciiRen
                     opts.padding_y = n;
                 }
             }
+        } else if let Some(val) = line.strip_prefix("headerevery=") {
+            if let Ok(n) = val.parse::<usize>() {
+                opts.sequence_header_every = n;
+            }
+        } else if let Some(val) = line.strip_prefix("maxgap=") {
+            if let Ok(n) = val.parse::<usize>() {
+                opts.sequence_max_gap = n;
+            }
+        } else if let Some(val) = line.strip_prefix("maxlabelwidth=") {
+            if let Ok(n) = val.parse::<usize>() {
+                opts.max_label_width = n;
+            }
+        } else if let Some(val) = line.strip_prefix("wrapwidth=") {
+            if let Ok(n) = val.parse::<usize>() {
+                opts.wrap_width = n;
+            }
+        } else if let Some(val) = line.strip_prefix("minnodewidth=") {
+            if let Ok(n) = val.parse::<usize>() {
+                opts.node_width.min = n;
+            }
+        } else if let Some(val) = line.strip_prefix("maxnodewidth=") {
+            if let Ok(n) = val.parse::<usize>() {
+                opts.node_width.max = n;
+            }
+        } else if let Some(val) = line.strip_prefix("uniformnodewidth=") {
+            if let Ok(b) = val.parse::<bool>() {
+                opts.node_width.uniform = b;
+            }
+        } else if let Some(val) = line.strip_prefix("maxwidth=") {
+            if let Ok(n) = val.parse::<usize>() {
+                opts.max_width = n;
+            }
+        } else if let Some(val) = line.strip_prefix("labelalign=") {
+            if let Some(align) = LabelAlign::from_name(val) {
+                opts.label_align = Some(align);
+            }
+        } else if let Some(val) = line.strip_prefix("roundedcorners=") {
+            if let Ok(b) = val.parse::<bool>() {
+                opts.rounded_corners = b;
+            }
+        } else if let Some(val) = line.strip_prefix("charset=") {
+            if let Some(charset) = Charset::from_name(val) {
+                opts.charset = Some(charset);
+            }
         }
     }
 
     opts
 }
 
+/// The `key=value` options of the `%% m2svg:` directive lines in `text`, which
+/// let piped input carry its rendering options, e.g.
+/// `%% m2svg: format=svg theme=dark padding_x=2`. Keys are lowercased with `_`
+/// and `-` dropped, so `padding_x` reads as `paddingx`; a later option wins.
+pub fn options_directive(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter_map(|line| {
+            line.trim()
+                .strip_prefix("%%")?
+                .trim()
+                .strip_prefix("m2svg:")
+        })
+        .flat_map(str::split_whitespace)
+        .filter_map(|option| {
+            let (key, value) = option.split_once('=')?;
+            let key: String = key
+                .chars()
+                .filter(|c| !matches!(c, '_' | '-'))
+                .collect::<String>()
+                .to_lowercase();
+            Some((key, value.to_string()))
+        })
+        .collect()
+}
+
 /// Render Mermaid diagram text to an ASCII/Unicode string.
 ///
 /// Synchronous — no async layout engine needed.
@@ -53,53 +131,170 @@ pub fn render_mermaid_ascii(
     // Parse any config lines from the input
     let opts = parse_config_from_text(text, base_opts);
 
+    let diagram = parser::parse_mermaid(text)?;
+    render_parsed_ascii(&diagram, Some(opts))
+}
+
+/// Render Mermaid diagram text as [`render_mermaid_ascii`] does, with a
+/// warning for each statement the output leaves out or gets wrong, as
+/// `m2svg check` reports them (see [`crate::validate()`]).
+pub fn render_mermaid_ascii_with_warnings(
+    text: &str,
+    options: Option<AsciiRenderOptions>,
+) -> Result<RenderOutput, String> {
+    let opts = parse_config_from_text(text, options.unwrap_or_default());
+    let (diagram, skipped) = parser::parse_mermaid_with_mode(text, ParseMode::Lenient)?;
+    let warnings = crate::validate::check_parsed(text, &diagram, skipped);
+    let content = render_parsed_ascii(&diagram, Some(opts))?;
+    Ok(RenderOutput { content, warnings })
+}
+
+/// Render an already parsed diagram to an ASCII/Unicode string.
+///
+/// Unlike [`render_mermaid_ascii`], there is no source text to read
+/// `paddingX=`-style config lines from, so only `options` apply.
+pub fn render_parsed_ascii(
+    diagram: &ParsedDiagram,
+    options: Option<AsciiRenderOptions>,
+) -> Result<String, String> {
+    let opts = options.unwrap_or_default();
+    match opts.max_width {
+        0 => render_ascii(diagram, &opts),
+        max_width => render_within(diagram, &opts, max_width),
+    }
+}
+
+/// `diagram` drawn at most `max_width` columns wide if it can be: with the
+/// spacing of `opts`, then with compact spacing, then, for a left-right
+/// flowchart, top-down. The narrowest of them if none fits.
+fn render_within(
+    diagram: &ParsedDiagram,
+    opts: &AsciiRenderOptions,
+    max_width: usize,
+) -> Result<String, String> {
+    let width = |text: &str| text.lines().map(canvas::text_width).max().unwrap_or(0);
+    let mut narrowest = render_ascii(diagram, opts)?;
+    if width(&narrowest) <= max_width {
+        return Ok(narrowest);
+    }
+
+    let top_down = match &diagram.diagram {
+        DiagramType::Flowchart(graph)
+            if matches!(graph.direction, Direction::LR | Direction::RL) =>
+        {
+            let mut graph = graph.clone();
+            graph.direction = Direction::TD;
+            Some(ParsedDiagram {
+                diagram: DiagramType::Flowchart(graph),
+                ..diagram.clone()
+            })
+        }
+        _ => None,
+    };
+    let compact = opts.clone().compact();
+    for diagram in std::iter::once(diagram).chain(top_down.as_ref()) {
+        let text = render_ascii(diagram, &compact)?;
+        if width(&text) <= max_width {
+            return Ok(text);
+        }
+        if width(&text) < width(&narrowest) {
+            narrowest = text;
+        }
+    }
+    Ok(narrowest)
+}
+
+/// Draw `diagram` with `opts`, whatever its width
+fn render_ascii(diagram: &ParsedDiagram, opts: &AsciiRenderOptions) -> Result<String, String> {
+    let limits = opts.limits;
+    limits.check(diagram)?;
+    let charset = opts.glyphs();
+    let use_ascii = charset == Charset::Ascii;
+    let ellipsis = if use_ascii { "..." } else { "…" };
+    let (diagram, legend) =
+        crate::truncate::truncate_diagram(diagram, opts.max_label_width, ellipsis);
+    // Wrap too at the width of the widest box, less its borders and padding
+    let box_room = opts.node_width.text_room(2 + 2 * opts.box_border_padding);
+    let wrap_width = match (opts.wrap_width, box_room) {
+        (0, room) => room.unwrap_or(0),
+        (width, room) => room.map_or(width, |room| room.min(width)),
+    };
+    let diagram = crate::wrap::wrap_diagram(&diagram, wrap_width);
+    let diagram = crate::class_members::compact_members(&diagram, opts.class_members, ellipsis);
+    let diagram = diagram.as_ref();
+
     let config = AsciiConfig {
-        use_ascii: opts.use_ascii,
+        use_ascii,
         padding_x: opts.padding_x,
         padding_y: opts.padding_y,
         box_border_padding: opts.box_border_padding,
         graph_direction: types::GraphDirection::TD,
+        sequence_header_every: opts.sequence_header_every,
+        sequence_max_gap: opts.sequence_max_gap,
+        node_width: opts.node_width,
+        label_align: opts.label_align,
+        rounded_corners: opts.rounded_corners,
     };
 
-    let diagram = parser::parse_mermaid(text)?;
-
-    let result = match diagram.diagram {
-        DiagramType::Flowchart(graph) => {
-            let mut config = config;
-            if graph.direction == crate::types::Direction::LR
-                || graph.direction == crate::types::Direction::RL
-            {
-                config.graph_direction = types::GraphDirection::LR;
-            } else {
-                config.graph_direction = types::GraphDirection::TD;
-            }
+    let (result, refused) =
+        canvas::with_cell_limit(limits.max_canvas_cells, || match &diagram.diagram {
+            DiagramType::Flowchart(graph) => {
+                let mut config = config;
+                if graph.direction == crate::types::Direction::LR
+                    || graph.direction == crate::types::Direction::RL
+                {
+                    config.graph_direction = types::GraphDirection::LR;
+                } else {
+                    config.graph_direction = types::GraphDirection::TD;
+                }
 
-            let result = flowchart::render_flowchart_ascii(&graph, &config);
+                let result = flowchart::render_flowchart_ascii(graph, &config);
 
-            // BT: flip the finished canvas vertically
-            if graph.direction == crate::types::Direction::BT {
-                Ok(canvas::flip_canvas_vertically(&result))
-            } else {
-                Ok(result)
+                // BT: flip the finished canvas vertically
+                if graph.direction == crate::types::Direction::BT {
+                    Ok(canvas::flip_canvas_vertically(&result))
+                } else {
+                    Ok(result)
+                }
             }
+            DiagramType::Sequence(diagram) => sequence::render_sequence_ascii(diagram, &config),
+            DiagramType::Class(diagram) => class_diagram::render_class_ascii(diagram, &config),
+            DiagramType::Er(diagram) => er_diagram::render_er_ascii(diagram, &config),
+            DiagramType::GitGraph(graph) => Ok(gitgraph::render_gitgraph(graph, config.use_ascii)),
+        });
+    if let Some(cells) = refused {
+        return Err(TooLarge {
+            limit: Limit::CanvasCells,
+            size: cells,
+            max: limits.max_canvas_cells,
         }
-        DiagramType::Sequence(diagram) => sequence::render_sequence_ascii(&diagram, &config),
-        DiagramType::Class(diagram) => class_diagram::render_class_ascii(&diagram, &config),
-        DiagramType::Er(diagram) => er_diagram::render_er_ascii(&diagram, &config),
-        DiagramType::GitGraph(graph) => Ok(gitgraph::render_gitgraph(&graph, config.use_ascii)),
-    }?;
+        .into());
+    }
+    let result: String = result?.chars().map(|c| charset.glyph(c)).collect();
 
     // Prepend title if present in frontmatter
-    if let Some(ref title) = diagram.frontmatter.title {
-        let diagram_width = result.lines().map(|l| l.len()).max().unwrap_or(0);
-        let title_width = title.len();
+    let result = if let Some(ref title) = diagram.frontmatter.title {
+        let diagram_width = result.lines().map(canvas::text_width).max().unwrap_or(0);
+        let title_width = canvas::text_width(title);
         let pad = if title_width < diagram_width {
             (diagram_width - title_width).div_ceil(2)
         } else {
             0
         };
-        Ok(format!("{}{}\n\n{}", " ".repeat(pad), title, result))
+        format!("{}{}\n\n{}", " ".repeat(pad), title, result)
     } else {
+        result
+    };
+
+    // Append the full text of truncated labels
+    if legend.is_empty() {
         Ok(result)
+    } else {
+        let lines = crate::truncate::legend_lines(&legend);
+        Ok(format!(
+            "{}\n\n{}",
+            result.trim_end_matches('\n'),
+            lines.join("\n")
+        ))
     }
 }
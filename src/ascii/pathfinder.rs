@@ -1,28 +1,7 @@
 //! A* pathfinding for edge routing
 
 use super::types::GridCoord;
-use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
-
-/// Priority queue item
-#[derive(Debug, Clone, Eq, PartialEq)]
-struct PQItem {
-    coord: GridCoord,
-    priority: i32,
-}
-
-impl Ord for PQItem {
-    fn cmp(&self, other: &Self) -> Ordering {
-        // Reverse ordering for min-heap behavior
-        other.priority.cmp(&self.priority)
-    }
-}
-
-impl PartialOrd for PQItem {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
+use std::collections::{HashMap, VecDeque};
 
 /// Manhattan distance with corner penalty
 pub fn heuristic(a: GridCoord, b: GridCoord) -> i32 {
@@ -38,81 +17,295 @@ pub fn heuristic(a: GridCoord, b: GridCoord) -> i32 {
 /// 4-directional movement
 const MOVE_DIRS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
 
-/// Check if a grid cell is free
-fn is_free_in_grid(grid: &HashMap<String, usize>, c: GridCoord) -> bool {
-    if c.x < 0 || c.y < 0 {
-        return false;
+/// Maximum iterations for A* to prevent infinite loops
+const MAX_ITERATIONS: usize = 100_000;
+
+/// The occupied cells of the layout grid, as a bitmap over their bounding box.
+///
+/// Built once before the edges are routed, so searches test a bit per cell
+/// instead of formatting and hashing a [`GridCoord::key`] string.
+#[derive(Debug, Clone, Default)]
+pub struct Obstacles {
+    origin: GridCoord,
+    width: usize,
+    height: usize,
+    blocked: Vec<bool>,
+}
+
+impl Obstacles {
+    /// Index the cells of `grid` (keyed by [`GridCoord::key`])
+    pub fn new(grid: &HashMap<String, usize>) -> Self {
+        let cells: Vec<GridCoord> = grid
+            .keys()
+            .filter_map(|key| {
+                let (x, y) = key.split_once(',')?;
+                Some(GridCoord::new(x.parse().ok()?, y.parse().ok()?))
+            })
+            .collect();
+        let (Some(min_x), Some(min_y)) = (
+            cells.iter().map(|c| c.x).min(),
+            cells.iter().map(|c| c.y).min(),
+        ) else {
+            return Self::default();
+        };
+        let max_x = cells.iter().map(|c| c.x).max().unwrap_or(min_x);
+        let max_y = cells.iter().map(|c| c.y).max().unwrap_or(min_y);
+        let width = (max_x - min_x + 1) as usize;
+        let height = (max_y - min_y + 1) as usize;
+        let mut blocked = vec![false; width * height];
+        for c in cells {
+            blocked[(c.y - min_y) as usize * width + (c.x - min_x) as usize] = true;
+        }
+        Self {
+            origin: GridCoord::new(min_x, min_y),
+            width,
+            height,
+            blocked,
+        }
+    }
+
+    /// The cell past the bottom-right corner of the occupied cells
+    pub(crate) fn end(&self) -> GridCoord {
+        GridCoord::new(
+            self.origin.x + self.width as i32,
+            self.origin.y + self.height as i32,
+        )
+    }
+
+    /// Whether a route may pass through `c`: it is on the grid (not left of or
+    /// above it) and no node occupies it
+    pub fn is_free(&self, c: GridCoord) -> bool {
+        if c.x < 0 || c.y < 0 {
+            return false;
+        }
+        let (x, y) = (c.x - self.origin.x, c.y - self.origin.y);
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return true;
+        }
+        !self.blocked[y as usize * self.width + x as usize]
     }
-    !grid.contains_key(&c.key())
 }
 
-/// Maximum iterations for A* to prevent infinite loops
-const MAX_ITERATIONS: usize = 100_000;
+/// Finds routes for edges on the layout grid.
+///
+/// `determine_path` asks for two candidate routes per edge (leaving from the
+/// preferred and the alternative sides of the nodes) and keeps the cheaper one.
+pub trait EdgeRouter {
+    /// Route from `from` to `to` through the free cells of `obstacles`. The
+    /// destination may itself be occupied.
+    fn route(
+        &mut self,
+        obstacles: &Obstacles,
+        from: GridCoord,
+        to: GridCoord,
+    ) -> Option<Vec<GridCoord>>;
+}
 
-/// Find a path from `from` to `to` using A*
-pub fn get_path(
-    grid: &HashMap<String, usize>,
-    from: GridCoord,
-    to: GridCoord,
-) -> Option<Vec<GridCoord>> {
-    let mut pq = BinaryHeap::new();
-    pq.push(PQItem {
-        coord: from,
-        priority: 0,
-    });
-
-    let mut cost_so_far: HashMap<String, i32> = HashMap::new();
-    cost_so_far.insert(from.key(), 0);
-
-    let mut came_from: HashMap<String, Option<GridCoord>> = HashMap::new();
-    came_from.insert(from.key(), None);
-
-    let mut iterations = 0;
-    while let Some(current) = pq.pop() {
-        iterations += 1;
-        if iterations > MAX_ITERATIONS {
-            return None; // Give up after too many iterations
+/// Routes each pair of end cells once and hands out the same route again.
+///
+/// Edges between the same two nodes and sides (`A --> B` written twice,
+/// `A -- yes --> B` next to `A -.-> B`) would otherwise each search the grid
+/// for the same path. The obstacles must stay the same while it is in use.
+pub struct Bundled<'a> {
+    router: &'a mut dyn EdgeRouter,
+    routes: HashMap<(GridCoord, GridCoord), Option<Vec<GridCoord>>>,
+}
+
+impl<'a> Bundled<'a> {
+    pub fn new(router: &'a mut dyn EdgeRouter) -> Self {
+        Self {
+            router,
+            routes: HashMap::new(),
+        }
+    }
+}
+
+impl EdgeRouter for Bundled<'_> {
+    fn route(
+        &mut self,
+        obstacles: &Obstacles,
+        from: GridCoord,
+        to: GridCoord,
+    ) -> Option<Vec<GridCoord>> {
+        self.routes
+            .entry((from, to))
+            .or_insert_with(|| self.router.route(obstacles, from, to))
+            .clone()
+    }
+}
+
+/// The default router: A* over the grid with the [`heuristic`] above.
+///
+/// Open cells wait in a bucketed priority queue (one FIFO per priority), and
+/// the search state is kept between calls so routing every edge of a graph
+/// reuses the same allocations: the cost and predecessor of each cell around
+/// the obstacles sit in a flat buffer, stamped with the search that wrote
+/// them so it never needs clearing.
+#[derive(Debug, Default)]
+pub struct AStarRouter {
+    buckets: Vec<VecDeque<GridCoord>>,
+    /// Size of the window of cells, from (0, 0), kept in `visits`
+    width: usize,
+    height: usize,
+    visits: Vec<Visit>,
+    /// Number of the current search; visits stamped otherwise are stale
+    search: u32,
+    /// Search state of the cells outside the window
+    outside: HashMap<GridCoord, Visit>,
+}
+
+/// Search state of one cell
+#[derive(Debug, Clone, Copy, Default)]
+struct Visit {
+    search: u32,
+    cost: i32,
+    came_from: Option<GridCoord>,
+}
+
+/// Free cells kept around the obstacles in the window, where routes that go
+/// around the outermost nodes run
+const WINDOW_MARGIN: i32 = 8;
+
+impl AStarRouter {
+    fn push(&mut self, coord: GridCoord, priority: usize, lowest: &mut usize) {
+        if self.buckets.len() <= priority {
+            self.buckets.resize_with(priority + 1, VecDeque::new);
         }
-        if current.coord == to {
-            // Reconstruct path
-            let mut path = Vec::new();
-            let mut c: Option<GridCoord> = Some(current.coord);
-            while let Some(coord) = c {
-                path.push(coord);
-                c = came_from.get(&coord.key()).and_then(|&o| o);
+        self.buckets[priority].push_back(coord);
+        *lowest = (*lowest).min(priority);
+    }
+
+    fn pop(&mut self, lowest: &mut usize) -> Option<(GridCoord, usize)> {
+        while *lowest < self.buckets.len() {
+            if let Some(coord) = self.buckets[*lowest].pop_front() {
+                return Some((coord, *lowest));
             }
-            path.reverse();
-            return Some(path);
+            *lowest += 1;
         }
+        None
+    }
 
-        let current_cost = *cost_so_far.get(&current.coord.key()).unwrap_or(&0);
+    /// Start a search, sizing the window to cover `obstacles` and both ends
+    fn start(&mut self, obstacles: &Obstacles, from: GridCoord, to: GridCoord) {
+        self.buckets.iter_mut().for_each(VecDeque::clear);
+        self.outside.clear();
+        let end = obstacles.end();
+        let width = (end.x.max(from.x).max(to.x) + WINDOW_MARGIN).max(0) as usize;
+        let height = (end.y.max(from.y).max(to.y) + WINDOW_MARGIN).max(0) as usize;
+        if (width, height) != (self.width, self.height) || self.search == u32::MAX {
+            self.width = width;
+            self.height = height;
+            self.visits = vec![Visit::default(); width * height];
+            self.search = 0;
+        }
+        self.search += 1;
+    }
 
-        for (dx, dy) in MOVE_DIRS {
-            let next = GridCoord::new(current.coord.x + dx, current.coord.y + dy);
+    fn slot(&self, c: GridCoord) -> Option<usize> {
+        let inside = c.x >= 0 && c.y >= 0 && (c.x as usize) < self.width;
+        (inside && (c.y as usize) < self.height).then(|| c.y as usize * self.width + c.x as usize)
+    }
 
-            // Allow moving to destination even if occupied
-            if !is_free_in_grid(grid, next) && next != to {
+    fn visit(&self, c: GridCoord) -> Option<Visit> {
+        match self.slot(c) {
+            Some(i) => Some(self.visits[i]).filter(|v| v.search == self.search),
+            None => self.outside.get(&c).copied(),
+        }
+    }
+
+    fn set_visit(&mut self, c: GridCoord, cost: i32, came_from: Option<GridCoord>) {
+        let visit = Visit {
+            search: self.search,
+            cost,
+            came_from,
+        };
+        match self.slot(c) {
+            Some(i) => self.visits[i] = visit,
+            None => {
+                self.outside.insert(c, visit);
+            }
+        }
+    }
+}
+
+impl EdgeRouter for AStarRouter {
+    fn route(
+        &mut self,
+        obstacles: &Obstacles,
+        from: GridCoord,
+        to: GridCoord,
+    ) -> Option<Vec<GridCoord>> {
+        self.start(obstacles, from, to);
+
+        let mut lowest = 0;
+        self.push(from, 0, &mut lowest);
+        self.set_visit(from, 0, None);
+
+        let mut iterations = 0;
+        while let Some((current, priority)) = self.pop(&mut lowest) {
+            let current_cost = self.visit(current).map_or(0, |v| v.cost);
+            // A cheaper way to this cell was found after it was queued
+            if current != from && priority != (current_cost + heuristic(current, to)) as usize {
                 continue;
             }
 
-            let new_cost = current_cost + 1;
-            let next_key = next.key();
+            iterations += 1;
+            if iterations > MAX_ITERATIONS {
+                return None; // Give up after too many iterations
+            }
+            if current == to {
+                let mut path = vec![current];
+                let mut c = current;
+                while let Some(prev) = self.visit(c).and_then(|v| v.came_from) {
+                    path.push(prev);
+                    c = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for (dx, dy) in MOVE_DIRS {
+                let next = GridCoord::new(current.x + dx, current.y + dy);
 
-            let existing_cost = cost_so_far.get(&next_key).copied();
+                // Allow moving to destination even if occupied
+                if next != to && !obstacles.is_free(next) {
+                    continue;
+                }
 
-            if existing_cost.is_none() || new_cost < existing_cost.unwrap() {
-                cost_so_far.insert(next_key.clone(), new_cost);
-                let priority = new_cost + heuristic(next, to);
-                pq.push(PQItem {
-                    coord: next,
-                    priority,
-                });
-                came_from.insert(next_key, Some(current.coord));
+                let new_cost = current_cost + 1;
+                if self.visit(next).is_none_or(|v| new_cost < v.cost) {
+                    self.set_visit(next, new_cost, Some(current));
+                    let priority = (new_cost + heuristic(next, to)) as usize;
+                    self.push(next, priority, &mut lowest);
+                }
             }
         }
+
+        None
     }
+}
 
-    None
+/// Find a path from `from` to `to` using A*
+pub fn get_path(
+    grid: &HashMap<String, usize>,
+    from: GridCoord,
+    to: GridCoord,
+) -> Option<Vec<GridCoord>> {
+    AStarRouter::default().route(&Obstacles::new(grid), from, to)
+}
+
+/// Cost of an already-merged path, measured on the layout grid.
+///
+/// This is the total segment length in grid cells plus one per bend. It never
+/// looks at column widths or row heights, so `padding_x`/`padding_y` cannot
+/// change which route wins.
+pub fn path_cost(path: &[GridCoord]) -> i32 {
+    let length: i32 = path
+        .windows(2)
+        .map(|w| (w[1].x - w[0].x).abs() + (w[1].y - w[0].y).abs())
+        .sum();
+    let bends = path.len().saturating_sub(2) as i32;
+    length + bends
 }
 
 /// Simplify a path by removing intermediate waypoints on straight segments
@@ -145,3 +338,67 @@ pub fn merge_path(path: Vec<GridCoord>) -> Vec<GridCoord> {
         .map(|(_, c)| c)
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_obstacles_match_grid() {
+        let grid: HashMap<String, usize> = [(3, 2), (4, 2), (5, 6)]
+            .into_iter()
+            .map(|(x, y)| (GridCoord::new(x, y).key(), 0))
+            .collect();
+        let obstacles = Obstacles::new(&grid);
+        for x in -1..9 {
+            for y in -1..9 {
+                let c = GridCoord::new(x, y);
+                let free = x >= 0 && y >= 0 && !grid.contains_key(&c.key());
+                assert_eq!(obstacles.is_free(c), free, "{:?}", c);
+            }
+        }
+    }
+
+    #[test]
+    fn test_path_cost_trades_length_against_bends() {
+        let path = |points: &[(i32, i32)]| -> Vec<GridCoord> {
+            points.iter().map(|&(x, y)| GridCoord::new(x, y)).collect()
+        };
+        // 4 cells with 3 bends loses to 5 cells with 1 bend
+        let stairs = path(&[(0, 0), (1, 0), (1, 1), (2, 1), (2, 2)]);
+        let detour = path(&[(0, 0), (0, 3), (2, 3)]);
+        assert_eq!(path_cost(&stairs), 7);
+        assert_eq!(path_cost(&detour), 6);
+
+        // ...but one bend fewer does not pay for a much longer route
+        let zigzag = path(&[(0, 0), (2, 0), (2, 1), (4, 1)]);
+        let around = path(&[(0, 0), (0, 4), (4, 4)]);
+        assert_eq!(path_cost(&zigzag), 7);
+        assert_eq!(path_cost(&around), 9);
+    }
+
+    #[test]
+    fn test_router_reuse_matches_fresh_search() {
+        // A wall at x = 2 with a gap at y = 4
+        let grid: HashMap<String, usize> = (0..4)
+            .chain(5..8)
+            .map(|y| (GridCoord::new(2, y).key(), 0))
+            .collect();
+        let cases = [
+            (GridCoord::new(0, 0), GridCoord::new(4, 0)),
+            (GridCoord::new(4, 7), GridCoord::new(0, 1)),
+            (GridCoord::new(1, 1), GridCoord::new(2, 1)),
+        ];
+
+        let mut router = AStarRouter::default();
+        for (from, to) in cases {
+            let path = router.route(&Obstacles::new(&grid), from, to).unwrap();
+            assert_eq!(Some(path.clone()), get_path(&grid, from, to));
+            assert_eq!(path.first(), Some(&from));
+            assert_eq!(path.last(), Some(&to));
+            assert!(path[1..path.len() - 1]
+                .iter()
+                .all(|c| !grid.contains_key(&c.key())));
+        }
+    }
+}
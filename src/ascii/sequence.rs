@@ -1,8 +1,10 @@
 //! Sequence diagram ASCII rendering
 
-use super::canvas::{canvas_to_string, draw_text, mk_canvas, set_char};
+use super::canvas::{canvas_to_string, draw_text, mk_canvas, set_char, text_width};
 use super::types::AsciiConfig;
-use crate::types::SequenceDiagram;
+use crate::types::{Note, NotePosition, SequenceDiagram};
+use crate::wrap::wrap_text;
+use std::collections::HashMap;
 
 /// Render a sequence diagram to ASCII
 pub fn render_sequence_ascii(
@@ -27,15 +29,17 @@ pub fn render_sequence_ascii(
     let actor_box_widths: Vec<usize> = diagram
         .actors
         .iter()
-        .map(|a| a.label.len() + 2 * box_pad + 2)
+        .map(|a| text_width(&a.label) + 2 * box_pad + 2)
         .collect();
     let half_box: Vec<usize> = actor_box_widths.iter().map(|w| w.div_ceil(2)).collect();
     let actor_box_h = 3; // top border + label row + bottom border
 
-    // Compute minimum gap between adjacent lifelines
+    // Compute minimum gap between adjacent lifelines; labels wider than the
+    // largest gap wrap instead of pushing the lifelines further apart
+    let max_gap = config.sequence_max_gap;
     let mut adj_max_width: Vec<usize> = vec![0; diagram.actors.len().saturating_sub(1)];
 
-    let actor_idx: std::collections::HashMap<&str, usize> = diagram
+    let actor_idx: HashMap<&str, usize> = diagram
         .actors
         .iter()
         .enumerate()
@@ -50,9 +54,12 @@ pub fn render_sequence_ascii(
         }
         let lo = fi.min(ti);
         let hi = fi.max(ti);
-        let needed = msg.label.len() + 4;
+        let needed = text_width(&msg.label) + 4;
         let num_gaps = hi - lo;
-        let per_gap = needed.div_ceil(num_gaps);
+        let mut per_gap = needed.div_ceil(num_gaps);
+        if max_gap > 0 {
+            per_gap = per_gap.min(max_gap.saturating_sub(2));
+        }
         for adj in &mut adj_max_width[lo..hi] {
             *adj = (*adj).max(per_gap);
         }
@@ -67,23 +74,83 @@ pub fn render_sequence_ascii(
         ll_x.push(ll_x[i - 1] + gap);
     }
 
+    // Shift everything right when a note hangs off the left edge
+    let overhang = diagram
+        .notes
+        .iter()
+        .filter_map(|note| note_span(note, &ll_x, &actor_idx))
+        .map(|(left, _)| -left)
+        .max()
+        .unwrap_or(0);
+    if overhang > 0 {
+        for x in &mut ll_x {
+            *x += overhang as usize;
+        }
+    }
+
+    // Message label rows, wrapped to the room between their lifelines
+    let labels: Vec<Vec<String>> = diagram
+        .messages
+        .iter()
+        .map(|msg| {
+            let fi = actor_idx.get(msg.from.as_str()).copied().unwrap_or(0);
+            let ti = actor_idx.get(msg.to.as_str()).copied().unwrap_or(0);
+            let room = match (max_gap, fi == ti) {
+                (0, _) => 0,
+                // Self-message labels sit right of the loop, before the next lifeline
+                (_, true) => ll_x
+                    .get(fi + 1)
+                    .map_or(0, |next| (next - ll_x[fi]).saturating_sub(8).max(1)),
+                (_, false) => ll_x[fi.max(ti)]
+                    .abs_diff(ll_x[fi.min(ti)])
+                    .saturating_sub(6)
+                    .max(1),
+            };
+            label_lines(&msg.label, room)
+        })
+        .collect();
+
     // Compute vertical positions
     let mut msg_arrow_y: Vec<usize> = Vec::new();
+    let mut header_y: Vec<usize> = Vec::new();
     let mut cur_y = actor_box_h;
+    let header_every = config.sequence_header_every;
+
+    // Notes take a box of their own rows after the message they follow
+    let last_msg = diagram.messages.len() as i32 - 1;
+    let mut note_y: Vec<usize> = vec![0; diagram.notes.len()];
+    let mut place_notes = |after: i32, cur_y: &mut usize| {
+        for (n, note) in diagram.notes.iter().enumerate() {
+            if note.after_index.min(last_msg) == after {
+                *cur_y += 1;
+                note_y[n] = *cur_y;
+                *cur_y += 3;
+            }
+        }
+    };
+    place_notes(-1, &mut cur_y);
+
+    for (m, (msg, lines)) in diagram.messages.iter().zip(&labels).enumerate() {
+        // Repeat the participant header so long diagrams stay readable
+        if header_every > 0 && m > 0 && m % header_every == 0 {
+            cur_y += 1;
+            header_y.push(cur_y);
+            cur_y += actor_box_h;
+        }
 
-    for m in 0..diagram.messages.len() {
         cur_y += 1; // blank row before message
 
-        let msg = &diagram.messages[m];
         let is_self = msg.from == msg.to;
+        let rows = lines.len();
 
         if is_self {
             msg_arrow_y.push(cur_y);
-            cur_y += 3;
+            cur_y += rows + 2;
         } else {
-            msg_arrow_y.push(cur_y + 1);
-            cur_y += 2;
+            msg_arrow_y.push(cur_y + rows);
+            cur_y += rows + 1;
         }
+        place_notes(m as i32, &mut cur_y);
     }
 
     cur_y += 1; // gap before footer
@@ -96,14 +163,21 @@ pub fn render_sequence_ascii(
     let mut total_w = last_ll + last_half + 2;
 
     // Ensure canvas is wide enough for self-message labels
-    for msg in diagram.messages.iter() {
+    for (msg, lines) in diagram.messages.iter().zip(&labels) {
         if msg.from == msg.to {
             let fi = actor_idx.get(msg.from.as_str()).copied().unwrap_or(0);
-            let self_right = ll_x[fi] + 6 + 2 + msg.label.len();
+            let width = lines.iter().map(|l| text_width(l)).max().unwrap_or(0);
+            let self_right = ll_x[fi] + 6 + 2 + width;
             total_w = total_w.max(self_right + 1);
         }
     }
 
+    for note in &diagram.notes {
+        if let Some((_, right)) = note_span(note, &ll_x, &actor_idx) {
+            total_w = total_w.max(right as usize + 2);
+        }
+    }
+
     let mut canvas = mk_canvas(total_w, total_h);
 
     // Draw actor boxes (header and footer)
@@ -129,6 +203,15 @@ pub fn render_sequence_ascii(
         for y in actor_box_h..footer_y {
             set_char(&mut canvas, cx, y as i32, v_line);
         }
+
+        // Repeated headers sit on the lifeline
+        for &y in &header_y {
+            draw_actor_box(&mut canvas, cx, y as i32, w, &actor.label, use_ascii);
+            if !use_ascii {
+                set_char(&mut canvas, cx, y as i32, '┴');
+                set_char(&mut canvas, cx, (y + actor_box_h) as i32 - 1, '┬');
+            }
+        }
     }
 
     // Draw messages
@@ -137,6 +220,8 @@ pub fn render_sequence_ascii(
         let ti = actor_idx.get(msg.to.as_str()).copied().unwrap_or(0);
         let arrow_y = msg_arrow_y[m] as i32;
         let is_self = fi == ti;
+        let lines = &labels[m];
+        let rows = lines.len() as i32;
 
         if is_self {
             // Self-message: goes right, loops down, comes back with arrow
@@ -153,18 +238,23 @@ pub fn render_sequence_ascii(
             set_char(&mut canvas, x + 3, arrow_y, h_line);
             set_char(&mut canvas, x + 4, arrow_y, corner_tr);
 
-            // Vertical down
-            set_char(&mut canvas, x + 4, arrow_y + 1, v_line);
+            // Vertical down, one row per label line
+            for y in 1..=rows {
+                set_char(&mut canvas, x + 4, arrow_y + y, v_line);
+            }
 
             // Bottom line: arrow left back to lifeline
-            set_char(&mut canvas, x, arrow_y + 2, arrow_left);
-            set_char(&mut canvas, x + 1, arrow_y + 2, h_line);
-            set_char(&mut canvas, x + 2, arrow_y + 2, h_line);
-            set_char(&mut canvas, x + 3, arrow_y + 2, h_line);
-            set_char(&mut canvas, x + 4, arrow_y + 2, corner_bl);
+            let bottom_y = arrow_y + rows + 1;
+            set_char(&mut canvas, x, bottom_y, arrow_left);
+            set_char(&mut canvas, x + 1, bottom_y, h_line);
+            set_char(&mut canvas, x + 2, bottom_y, h_line);
+            set_char(&mut canvas, x + 3, bottom_y, h_line);
+            set_char(&mut canvas, x + 4, bottom_y, corner_bl);
 
             // Label on the right of the vertical line
-            draw_text(&mut canvas, x + 6, arrow_y + 1, &msg.label);
+            for (i, line) in lines.iter().enumerate() {
+                draw_text(&mut canvas, x + 6, arrow_y + 1 + i as i32, line);
+            }
         } else {
             // Normal message
             let from_x = ll_x[fi] as i32;
@@ -216,15 +306,75 @@ pub fn render_sequence_ascii(
             let arrow_x = to_x;
             set_char(&mut canvas, arrow_x, arrow_y, arrow_char);
 
-            // Draw label above the line
-            let label_x = (from_x + to_x) / 2 - (msg.label.len() as i32) / 2;
-            draw_text(&mut canvas, label_x, arrow_y - 1, &msg.label);
+            // Draw label above the line, each row centered
+            for (i, line) in lines.iter().enumerate() {
+                let label_x = (from_x + to_x) / 2 - (text_width(line) as i32) / 2;
+                draw_text(&mut canvas, label_x, arrow_y - rows + i as i32, line);
+            }
+        }
+    }
+
+    // Notes go over the lifelines and messages
+    for (n, note) in diagram.notes.iter().enumerate() {
+        let Some((left, right)) = note_span(note, &ll_x, &actor_idx) else {
+            continue;
+        };
+        let top = note_y[n] as i32;
+        let (tl, tr, bl, br) = if use_ascii {
+            ('+', '+', '+', '+')
+        } else {
+            ('┌', '┐', '└', '┘')
+        };
+        for x in left..=right {
+            set_char(&mut canvas, x, top, h_line);
+            set_char(&mut canvas, x, top + 1, ' ');
+            set_char(&mut canvas, x, top + 2, h_line);
         }
+        set_char(&mut canvas, left, top, tl);
+        set_char(&mut canvas, right, top, tr);
+        set_char(&mut canvas, left, top + 1, v_line);
+        set_char(&mut canvas, right, top + 1, v_line);
+        set_char(&mut canvas, left, top + 2, bl);
+        set_char(&mut canvas, right, top + 2, br);
+        let text_x = (left + right + 1) / 2 - text_width(&note.text) as i32 / 2;
+        draw_text(&mut canvas, text_x, top + 1, &note.text);
     }
 
     Ok(canvas_to_string(&canvas))
 }
 
+/// The rows of a message label: the label itself, or wrapped to `room`
+/// columns (0 = no limit)
+fn label_lines(label: &str, room: usize) -> Vec<String> {
+    if room == 0 || text_width(label) <= room {
+        return vec![label.to_string()];
+    }
+    wrap_text(label, room)
+}
+
+/// Leftmost and rightmost columns of a note's box, or `None` when it names
+/// no participant in the diagram
+fn note_span(note: &Note, ll_x: &[usize], actor_idx: &HashMap<&str, usize>) -> Option<(i32, i32)> {
+    let xs: Vec<i32> = note
+        .actor_ids
+        .iter()
+        .filter_map(|id| actor_idx.get(id.as_str()))
+        .map(|&i| ll_x[i] as i32)
+        .collect();
+    let lo = *xs.iter().min()?;
+    let hi = *xs.iter().max()?;
+    let w = text_width(&note.text) as i32 + 4;
+    Some(match note.position {
+        NotePosition::Left => (lo - 1 - w, lo - 2),
+        NotePosition::Right => (hi + 2, hi + 1 + w),
+        NotePosition::Over => {
+            let w = w.max(hi - lo + 5);
+            let left = (lo + hi) / 2 - w / 2;
+            (left, left + w - 1)
+        }
+    })
+}
+
 fn draw_actor_box(
     canvas: &mut super::types::Canvas,
     cx: i32,
@@ -252,7 +402,7 @@ fn draw_actor_box(
 
     // Middle row (with label)
     set_char(canvas, left, top_y + 1, v_line);
-    let label_x = cx - (label.len() as i32) / 2;
+    let label_x = cx - (text_width(label) as i32) / 2;
     draw_text(canvas, label_x, top_y + 1, label);
     set_char(canvas, right, top_y + 1, v_line);
 
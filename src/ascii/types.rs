@@ -1,7 +1,10 @@
 //! ASCII renderer type definitions
 
+use crate::node_width::NodeWidth;
+use crate::types::{EdgeMarker, EdgeStyle, LabelAlign, NodeShape, Port};
+
 /// Logical grid coordinate — nodes occupy 3x3 blocks on this grid
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub struct GridCoord {
     pub x: i32,
     pub y: i32,
@@ -65,6 +68,17 @@ pub struct AsciiConfig {
     pub padding_y: usize,
     pub box_border_padding: usize,
     pub graph_direction: GraphDirection,
+    /// Repeat the sequence diagram header every N messages (0 = never)
+    pub sequence_header_every: usize,
+    /// Widest gap between adjacent sequence lifelines that message labels
+    /// may open up (0 = no limit)
+    pub sequence_max_gap: usize,
+    /// Widths of flowchart node and class boxes, in columns
+    pub node_width: NodeWidth,
+    /// Alignment of node label lines and class box text, if set
+    pub label_align: Option<LabelAlign>,
+    /// Draw rounded, stadium and circle nodes with rounded corners in Unicode
+    pub rounded_corners: bool,
 }
 
 /// A node in the ASCII graph
@@ -77,6 +91,9 @@ pub struct AsciiNode {
     pub drawing_coord: Option<DrawingCoord>,
     pub drawing: Option<Canvas>,
     pub drawn: bool,
+    pub shape: NodeShape,
+    /// Drawn with a double border, for a thick `stroke-width` in its style
+    pub emphasized: bool,
 }
 
 impl AsciiNode {
@@ -89,6 +106,8 @@ impl AsciiNode {
             drawing_coord: None,
             drawing: None,
             drawn: false,
+            shape: NodeShape::Rectangle,
+            emphasized: false,
         }
     }
 }
@@ -99,10 +118,28 @@ pub struct AsciiEdge {
     pub from_idx: usize,
     pub to_idx: usize,
     pub text: String,
+    /// Labels drawn next to the source and target ends of the edge
+    pub start_text: String,
+    pub end_text: String,
     pub path: Vec<GridCoord>,
+    /// Sideways shift of each segment of `path`, in characters from the middle
+    /// of its grid column (vertical segments) or row (horizontal ones), so that
+    /// edges sharing a corridor run side by side
+    pub tracks: Vec<i32>,
     pub label_line: Vec<GridCoord>,
     pub start_dir: Direction,
     pub end_dir: Direction,
+    pub start_marker: EdgeMarker,
+    pub end_marker: EdgeMarker,
+    /// Minimum number of layout levels between source and target
+    pub min_length: usize,
+    /// Sides of the source and target the edge is pinned to (`A:e --> B:w`)
+    pub start_port: Option<Port>,
+    pub end_port: Option<Port>,
+    /// Class from a `:::name` suffix on the arrow (used by SVG output)
+    pub class: Option<String>,
+    /// Line weight: solid, dotted or thick (used by SVG output)
+    pub style: EdgeStyle,
 }
 
 impl AsciiEdge {
@@ -111,10 +148,20 @@ impl AsciiEdge {
             from_idx,
             to_idx,
             text,
+            start_text: String::new(),
+            end_text: String::new(),
             path: Vec::new(),
+            tracks: Vec::new(),
             label_line: Vec::new(),
             start_dir: DOWN,
             end_dir: UP,
+            start_marker: EdgeMarker::None,
+            end_marker: EdgeMarker::Arrow,
+            min_length: 1,
+            start_port: None,
+            end_port: None,
+            class: None,
+            style: EdgeStyle::Solid,
         }
     }
 }
@@ -126,6 +173,8 @@ pub struct AsciiSubgraph {
     pub node_indices: Vec<usize>,
     pub parent_idx: Option<usize>,
     pub children_idx: Vec<usize>,
+    /// Layout direction set with `direction` inside the subgraph, if any
+    pub direction: Option<GraphDirection>,
     pub min_x: i32,
     pub min_y: i32,
     pub max_x: i32,
@@ -139,6 +188,7 @@ impl AsciiSubgraph {
             node_indices: Vec::new(),
             parent_idx: None,
             children_idx: Vec::new(),
+            direction: None,
             min_x: 0,
             min_y: 0,
             max_x: 0,
@@ -160,6 +210,11 @@ pub struct AsciiGraph {
     pub config: AsciiConfig,
     pub offset_x: i32,
     pub offset_y: i32,
+    /// Route the edges leaving or entering the same side of a node as one
+    /// trunk (`bundleEdges:` in the frontmatter config)
+    pub bundle_edges: bool,
+    /// Lay the top-level subgraphs out as lanes (`swimlanes:`)
+    pub swimlanes: bool,
 }
 
 impl AsciiGraph {
@@ -175,6 +230,8 @@ impl AsciiGraph {
             config,
             offset_x: 0,
             offset_y: 0,
+            bundle_edges: false,
+            swimlanes: false,
         }
     }
 }
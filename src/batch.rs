@@ -0,0 +1,54 @@
+//! Rendering many diagrams at once
+//!
+//! `m2svg batch` renders each of its input files on its own. With the
+//! `parallel` feature the files are spread over a thread pool; either way the
+//! results come back in input order, each with the time it took.
+
+use std::time::{Duration, Instant};
+
+/// The result of one batch job and how long it took
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timed<T> {
+    pub result: T,
+    pub elapsed: Duration,
+}
+
+/// Run `job` on each of `items`, on a thread pool with the `parallel`
+/// feature, and return the results in the order of `items`.
+///
+/// # Example
+/// ```rust
+/// use m2svg::batch::run;
+/// use m2svg::render_mermaid_ascii;
+///
+/// let sources = ["graph LR\n  A --> B", "graph LR\n  A -->", "graph TD\n  C --> D"];
+/// let results = run(&sources, |source| render_mermaid_ascii(source, None));
+///
+/// assert_eq!(results.len(), 3);
+/// assert!(results[0].result.as_ref().unwrap().contains("A"));
+/// assert!(results[2].result.as_ref().unwrap().contains("C"));
+/// ```
+pub fn run<I, T, F>(items: &[I], job: F) -> Vec<Timed<T>>
+where
+    I: Sync,
+    T: Send,
+    F: Fn(&I) -> T + Sync,
+{
+    let timed = |item: &I| {
+        let start = Instant::now();
+        let result = job(item);
+        Timed {
+            result,
+            elapsed: start.elapsed(),
+        }
+    };
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        items.par_iter().map(timed).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        items.iter().map(timed).collect()
+    }
+}
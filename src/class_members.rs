@@ -0,0 +1,92 @@
+//! Compaction of long class member lists
+//!
+//! Used by the `class_members` render options: a class with dozens of
+//! attributes or methods otherwise gets a box as tall as all of them.
+//! [`ClassMembers`] can leave out private members, and cut each list to its
+//! first entries followed by a `… (k more)` row.
+
+use std::borrow::Cow;
+
+use crate::types::{ClassMember, DiagramType, ParsedDiagram, Visibility};
+
+/// Which members the boxes of class diagrams list
+///
+/// # Example
+/// ```rust
+/// use m2svg::{render_mermaid_ascii, AsciiRenderOptions, ClassMembers};
+///
+/// let options = AsciiRenderOptions {
+///     class_members: ClassMembers { max: 2, hide_private: true },
+///     ..Default::default()
+/// };
+/// let input = "classDiagram\n  class Order {\n    -secret\n    +id\n    +total\n    +status\n  }";
+/// let text = render_mermaid_ascii(input, Some(options)).unwrap();
+/// assert!(text.contains("| +id          |"));
+/// assert!(text.contains("| ... (1 more) |"));
+/// assert!(!text.contains("secret") && !text.contains("status"));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ClassMembers {
+    /// Most attributes, and most methods, a class lists before the rest
+    /// collapse into one `… (k more)` row (0 = no limit). Default: 0
+    pub max: usize,
+    /// Leave out private (`-`) members. Default: false
+    pub hide_private: bool,
+}
+
+impl ClassMembers {
+    fn is_set(&self) -> bool {
+        self.max > 0 || self.hide_private
+    }
+
+    /// `list` without the members these options leave out, its overflow
+    /// summed up in a row ending in `ellipsis`
+    fn compact(&self, list: &[ClassMember], ellipsis: &str) -> Vec<ClassMember> {
+        let mut kept: Vec<ClassMember> = list
+            .iter()
+            .filter(|m| !(self.hide_private && m.visibility == Visibility::Private))
+            .cloned()
+            .collect();
+        if self.max > 0 && kept.len() > self.max {
+            let more = kept.len() - self.max;
+            kept.truncate(self.max);
+            kept.push(ClassMember {
+                visibility: Visibility::None,
+                name: format!("{} ({} more)", ellipsis, more),
+                member_type: None,
+                is_static: false,
+                is_abstract: false,
+                is_method: false,
+                params: None,
+            });
+        }
+        kept
+    }
+}
+
+/// The class diagram of `parsed` with its member lists compacted as
+/// `members` says, the overflow rows ending in `ellipsis`.
+///
+/// Other diagram types are returned as-is.
+pub fn compact_members<'a>(
+    parsed: &'a ParsedDiagram,
+    members: ClassMembers,
+    ellipsis: &str,
+) -> Cow<'a, ParsedDiagram> {
+    let DiagramType::Class(diagram) = &parsed.diagram else {
+        return Cow::Borrowed(parsed);
+    };
+    if !members.is_set() {
+        return Cow::Borrowed(parsed);
+    }
+
+    let mut diagram = diagram.clone();
+    for class in &mut diagram.classes {
+        class.attributes = members.compact(&class.attributes, ellipsis);
+        class.methods = members.compact(&class.methods, ellipsis);
+    }
+    Cow::Owned(ParsedDiagram {
+        diagram: DiagramType::Class(diagram),
+        frontmatter: parsed.frontmatter.clone(),
+    })
+}
@@ -0,0 +1,348 @@
+//! Plain-language descriptions of diagrams, for use as alt text
+//!
+//! Built from the parsed model only, so the description follows the diagram's
+//! content (nodes, connections, message order) rather than its layout.
+
+use crate::types::{
+    Cardinality, ClassDiagram, ClassMember, DiagramType, Direction, EdgeMarker, EdgeStyle,
+    ErDiagram, GitGraph, MermaidGraph, MermaidSubgraph, ParsedDiagram, RelationshipType,
+    SequenceDiagram,
+};
+
+/// Describe a parsed diagram in plain language, one sentence or list item per line.
+///
+/// # Example
+/// ```rust
+/// let parsed = m2svg::parse_mermaid("graph LR\n  A[Start] -->|go| B[End]").unwrap();
+/// let text = m2svg::describe::describe(&parsed);
+/// assert!(text.contains("Start leads to End, labelled \"go\"."));
+/// ```
+pub fn describe(parsed: &ParsedDiagram) -> String {
+    let mut lines = Vec::new();
+    if let Some(title) = &parsed.frontmatter.title {
+        lines.push(format!("Title: {}.", title));
+    }
+    match &parsed.diagram {
+        DiagramType::Flowchart(graph) => describe_flowchart(graph, &mut lines),
+        DiagramType::Sequence(diagram) => describe_sequence(diagram, &mut lines),
+        DiagramType::Class(diagram) => describe_class(diagram, &mut lines),
+        DiagramType::Er(diagram) => describe_er(diagram, &mut lines),
+        DiagramType::GitGraph(graph) => describe_gitgraph(graph, &mut lines),
+    }
+    lines.join("\n")
+}
+
+/// "1 node" / "3 nodes"
+fn count(n: usize, singular: &str, plural: &str) -> String {
+    format!("{} {}", n, if n == 1 { singular } else { plural })
+}
+
+/// "a", "a and b", "a, b and c"
+fn list(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [one] => one.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+/// Multi-line labels read as one line
+fn flat(label: &str) -> String {
+    label.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn describe_flowchart(graph: &MermaidGraph, lines: &mut Vec<String>) {
+    let direction = match graph.direction {
+        Direction::TD | Direction::TB => "top to bottom",
+        Direction::BT => "bottom to top",
+        Direction::LR => "left to right",
+        Direction::RL => "right to left",
+    };
+    lines.push(format!(
+        "Flowchart with {} and {}, laid out {}.",
+        count(graph.node_order.len(), "node", "nodes"),
+        count(graph.edges.len(), "connection", "connections"),
+        direction
+    ));
+
+    let name = |id: &str| {
+        graph
+            .nodes
+            .get(id)
+            .map_or_else(|| id.to_string(), |n| flat(&n.label))
+    };
+    let names: Vec<String> = graph.node_order.iter().map(|id| name(id)).collect();
+    if !names.is_empty() {
+        lines.push(format!("Nodes: {}.", list(&names)));
+    }
+
+    fn groups(subgraphs: &[MermaidSubgraph], name: &dyn Fn(&str) -> String, out: &mut Vec<String>) {
+        for subgraph in subgraphs {
+            let members: Vec<String> = subgraph.node_ids.iter().map(|id| name(id)).collect();
+            out.push(format!(
+                "Group \"{}\" contains {}.",
+                flat(&subgraph.label),
+                if members.is_empty() {
+                    "no nodes".to_string()
+                } else {
+                    list(&members)
+                }
+            ));
+            groups(&subgraph.children, name, out);
+        }
+    }
+    groups(&graph.subgraphs, &name, lines);
+
+    if !graph.edges.is_empty() {
+        lines.push("Connections:".to_string());
+    }
+    for edge in &graph.edges {
+        let verb = match (edge.start_marker, edge.end_marker) {
+            (EdgeMarker::None, EdgeMarker::None) => "is connected to",
+            (EdgeMarker::None, _) => "leads to",
+            (_, EdgeMarker::None) => "is reached from",
+            _ => "is linked both ways with",
+        };
+        let mut sentence = format!("- {} {} {}", name(&edge.source), verb, name(&edge.target));
+        if edge.style == EdgeStyle::Dotted {
+            sentence.push_str(" (dotted)");
+        }
+        if let Some(label) = &edge.label {
+            sentence.push_str(&format!(", labelled \"{}\"", flat(label)));
+        }
+        sentence.push('.');
+        lines.push(sentence);
+    }
+}
+
+fn describe_sequence(diagram: &SequenceDiagram, lines: &mut Vec<String>) {
+    let name = |id: &str| {
+        diagram
+            .actors
+            .iter()
+            .find(|a| a.id == id)
+            .map_or_else(|| id.to_string(), |a| flat(&a.label))
+    };
+    let actors: Vec<String> = diagram.actors.iter().map(|a| flat(&a.label)).collect();
+    lines.push(format!(
+        "Sequence diagram with {}: {}.",
+        count(actors.len(), "participant", "participants"),
+        list(&actors)
+    ));
+    if diagram.messages.is_empty() {
+        return;
+    }
+
+    lines.push("Messages in order:".to_string());
+    for (i, message) in diagram.messages.iter().enumerate() {
+        for block in diagram.blocks.iter().filter(|b| b.start_index == i) {
+            let kind = format!("{:?}", block.block_type).to_lowercase();
+            let label = if block.label.is_empty() {
+                String::new()
+            } else {
+                format!(" \"{}\"", flat(&block.label))
+            };
+            lines.push(format!(
+                "   Messages {} to {} are in a {} block{}.",
+                block.start_index + 1,
+                block.end_index + 1,
+                kind,
+                label
+            ));
+        }
+        let verb = if message.from == message.to {
+            format!("{} to itself", name(&message.from))
+        } else {
+            format!("{} to {}", name(&message.from), name(&message.to))
+        };
+        let reply = if message.line_style == crate::types::LineStyle::Dashed {
+            " (reply)"
+        } else {
+            ""
+        };
+        lines.push(format!(
+            "{}. {}{}: {}",
+            i + 1,
+            verb,
+            reply,
+            flat(&message.label)
+        ));
+        for note in diagram.notes.iter().filter(|n| n.after_index == i as i32) {
+            let on: Vec<String> = note.actor_ids.iter().map(|id| name(id)).collect();
+            lines.push(format!("   Note on {}: {}", list(&on), flat(&note.text)));
+        }
+    }
+}
+
+fn describe_class(diagram: &ClassDiagram, lines: &mut Vec<String>) {
+    lines.push(format!(
+        "Class diagram with {} and {}.",
+        count(diagram.classes.len(), "class", "classes"),
+        count(diagram.relationships.len(), "relationship", "relationships")
+    ));
+    let member = |m: &ClassMember| {
+        if m.is_method {
+            format!("{}()", m.name)
+        } else {
+            m.name.clone()
+        }
+    };
+    for class in &diagram.classes {
+        let mut parts = Vec::new();
+        if !class.attributes.is_empty() {
+            let names: Vec<String> = class.attributes.iter().map(member).collect();
+            parts.push(format!("attributes {}", list(&names)));
+        }
+        if !class.methods.is_empty() {
+            let names: Vec<String> = class.methods.iter().map(member).collect();
+            parts.push(format!("methods {}", list(&names)));
+        }
+        let annotation = class
+            .annotation
+            .as_ref()
+            .map(|a| format!(" ({})", a))
+            .unwrap_or_default();
+        if parts.is_empty() {
+            lines.push(format!("Class {}{}.", class.label, annotation));
+        } else {
+            lines.push(format!(
+                "Class {}{} has {}.",
+                class.label,
+                annotation,
+                parts.join(" and ")
+            ));
+        }
+    }
+
+    for rel in &diagram.relationships {
+        // The end with the marker is the parent, whole or target
+        let (marked, other) = if rel.marker_at_from {
+            (&rel.from, &rel.to)
+        } else {
+            (&rel.to, &rel.from)
+        };
+        let mut sentence = match rel.rel_type {
+            RelationshipType::Inheritance => format!("{} inherits from {}", other, marked),
+            RelationshipType::Realization => format!("{} implements {}", other, marked),
+            RelationshipType::Composition => format!("{} is composed of {}", marked, other),
+            RelationshipType::Aggregation => format!("{} aggregates {}", marked, other),
+            RelationshipType::Association => format!("{} is associated with {}", other, marked),
+            RelationshipType::Dependency => format!("{} depends on {}", other, marked),
+        };
+        if let (Some(a), Some(b)) = (&rel.from_cardinality, &rel.to_cardinality) {
+            sentence.push_str(&format!(" ({} to {})", a, b));
+        }
+        if let Some(label) = &rel.label {
+            sentence.push_str(&format!(", labelled \"{}\"", flat(label)));
+        }
+        lines.push(format!("{}.", sentence));
+    }
+}
+
+fn describe_er(diagram: &ErDiagram, lines: &mut Vec<String>) {
+    lines.push(format!(
+        "Entity-relationship diagram with {} and {}.",
+        count(diagram.entities.len(), "entity", "entities"),
+        count(diagram.relationships.len(), "relationship", "relationships")
+    ));
+    for entity in &diagram.entities {
+        if entity.attributes.is_empty() {
+            continue;
+        }
+        let attributes: Vec<String> = entity
+            .attributes
+            .iter()
+            .map(|a| {
+                let mut details = vec![a.attr_type.clone()];
+                details.extend(a.keys.iter().map(|k| format!("{:?}", k)));
+                format!("{} ({})", a.name, details.join(", "))
+            })
+            .collect();
+        lines.push(format!(
+            "{} has attributes {}.",
+            entity.label,
+            list(&attributes)
+        ));
+    }
+
+    let amount = |c: Cardinality| match c {
+        Cardinality::One => "exactly one",
+        Cardinality::ZeroOne => "zero or one",
+        Cardinality::Many => "one or more",
+        Cardinality::ZeroMany => "zero or more",
+    };
+    for rel in &diagram.relationships {
+        lines.push(format!(
+            "{} {} {}: {} {} to {} {}.",
+            rel.entity1,
+            if rel.label.is_empty() {
+                "relates to"
+            } else {
+                rel.label.as_str()
+            },
+            rel.entity2,
+            amount(rel.cardinality1),
+            rel.entity1,
+            amount(rel.cardinality2),
+            rel.entity2
+        ));
+    }
+}
+
+fn describe_gitgraph(graph: &GitGraph, lines: &mut Vec<String>) {
+    let branches: Vec<String> = graph
+        .branches_by_order()
+        .iter()
+        .map(|b| b.name.clone())
+        .collect();
+    lines.push(format!(
+        "Git graph with {} on {}: {}.",
+        count(graph.commits.len(), "commit", "commits"),
+        count(branches.len(), "branch", "branches"),
+        list(&branches)
+    ));
+    if graph.commits.is_empty() {
+        return;
+    }
+
+    lines.push("Commits in order:".to_string());
+    for (i, commit) in graph.commits.iter().enumerate() {
+        // A branch's first commit says where the branch started
+        for branch in &graph.branches {
+            if branch.commit_ids.first() == Some(&commit.id) {
+                if let Some(source) = &branch.source_commit {
+                    lines.push(format!("   Branch {} starts from {}.", branch.name, source));
+                }
+            }
+        }
+        let mut sentence = format!("{}. {} on {}", i + 1, commit.id, commit.branch);
+        if commit.is_merge {
+            let source = commit.parent_ids.get(1).and_then(|p| {
+                graph
+                    .commits
+                    .iter()
+                    .find(|c| &c.id == p)
+                    .map(|c| c.branch.clone())
+            });
+            if let Some(source) = source {
+                sentence.push_str(&format!(" merges {}", source));
+            }
+        }
+        if let Some(source) = &commit.cherry_pick_source {
+            sentence.push_str(&format!(" cherry-picks {}", source));
+        }
+        if let Some(author) = &commit.author {
+            sentence.push_str(&format!(" by {}", author));
+        }
+        if let Some(date) = &commit.date {
+            sentence.push_str(&format!(" on {}", date));
+        }
+        if let Some(tag) = &commit.tag {
+            sentence.push_str(&format!(", tagged {}", tag));
+        }
+        if let Some(message) = &commit.message {
+            sentence.push_str(&format!(": {}", flat(message)));
+        }
+        lines.push(sentence);
+    }
+}
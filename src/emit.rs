@@ -0,0 +1,1274 @@
+//! Mermaid text from parsed diagrams
+//!
+//! [`to_mermaid`] writes a [`ParsedDiagram`] back out as Mermaid source, e.g.
+//! after editing it in code or composing it with [`merge`](crate::merge).
+//! The text is normalized rather than a copy of the original: one statement
+//! per line, indented by four spaces, and a frontmatter holding only the
+//! settings m2svg reads. Parsing it gives the same diagram back.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::types::{
+    ActorType, ArrowHead, BlockType, Cardinality, ClassDiagram, ClassMember, ClassNode,
+    ClassRelationship, CommitType, CurveStyle, DiagramType, Direction, EdgeMarker, EdgeRouting,
+    EdgeStyle, ErAttribute, ErDiagram, ErEntity, ErKey, ErRelationship, FlowchartLayout, GitBranch,
+    GitGraph, GitGraphDirection, GitMergeStyle, LineStyle, MermaidEdge, MermaidGraph, MermaidNode,
+    MermaidSubgraph, NodeShape, NotePosition, ParsedDiagram, Port, RelationshipType,
+    SequenceDiagram, Visibility,
+};
+
+const INDENT: &str = "    ";
+
+/// Write `parsed` as Mermaid text, ending with a newline.
+///
+/// Statements are ordered so that the parser meets nodes, entities and
+/// classes in the order of the diagram; state diagrams keep their states
+/// and transitions but may list them in another order.
+///
+/// # Example
+/// ```rust
+/// let parsed = m2svg::parse_mermaid("graph LR\n  A[Start] -->|go| B(End)\n  A-->C").unwrap();
+/// let text = m2svg::to_mermaid(&parsed);
+/// assert_eq!(text, "flowchart LR\n    A[Start] -->|go| B(End)\n    A --> C\n");
+///
+/// let reparsed = m2svg::parse_mermaid(&text).unwrap();
+/// assert_eq!(m2svg::to_mermaid(&reparsed), text);
+/// ```
+pub fn to_mermaid(parsed: &ParsedDiagram) -> String {
+    let mut body = Vec::new();
+    match &parsed.diagram {
+        DiagramType::Flowchart(graph) if is_state_diagram(graph) => state(graph, &mut body),
+        DiagramType::Flowchart(graph) => flowchart(graph, &mut body),
+        DiagramType::Sequence(diagram) => sequence(diagram, &mut body),
+        DiagramType::Class(diagram) => class(diagram, &mut body),
+        DiagramType::Er(diagram) => er(diagram, &mut body),
+        DiagramType::GitGraph(graph) => gitgraph(graph, &mut body),
+    }
+
+    let mut lines = frontmatter(parsed);
+    let mut body = body.into_iter();
+    lines.extend(body.next());
+    // Accessibility statements go right after the header
+    let fm = &parsed.frontmatter;
+    if let Some(title) = &fm.acc_title {
+        lines.push(format!("{}accTitle: {}", INDENT, title));
+    }
+    match &fm.acc_descr {
+        Some(descr) if descr.contains('\n') => {
+            lines.push(format!("{}accDescr {{", INDENT));
+            lines.extend(descr.lines().map(|l| format!("{}{}{}", INDENT, INDENT, l)));
+            lines.push(format!("{}}}", INDENT));
+        }
+        Some(descr) => lines.push(format!("{}accDescr: {}", INDENT, descr)),
+        None => {}
+    }
+    lines.extend(body);
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+/// Push `text` at nesting `depth`
+fn push(out: &mut Vec<String>, depth: usize, text: impl AsRef<str>) {
+    out.push(format!("{}{}", INDENT.repeat(depth), text.as_ref()));
+}
+
+/// `k:v,k:v` with the keys sorted
+fn style_props(props: &HashMap<String, String>) -> String {
+    let mut pairs: Vec<_> = props.iter().collect();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(k, v)| format!("{}:{}", k, v))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The keys of `map`, sorted
+fn sorted_keys<V>(map: &HashMap<String, V>) -> Vec<&String> {
+    let mut keys: Vec<_> = map.keys().collect();
+    keys.sort();
+    keys
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::TD => "TD",
+        Direction::TB => "TB",
+        Direction::LR => "LR",
+        Direction::BT => "BT",
+        Direction::RL => "RL",
+    }
+}
+
+// ============================================================================
+// Frontmatter
+// ============================================================================
+
+/// The `---` block with the title and the config m2svg reads, if any
+fn frontmatter(parsed: &ParsedDiagram) -> Vec<String> {
+    let fm = &parsed.frontmatter;
+    let mut config = Vec::new();
+
+    let theme = match &parsed.diagram {
+        DiagramType::GitGraph(graph) if graph.config.theme != "default" => {
+            graph.config.theme.clone()
+        }
+        _ => fm.theme.to_string(),
+    };
+    if theme != "default" {
+        config.push(format!("  theme: {}", theme));
+    }
+    if fm.layout == FlowchartLayout::Layered {
+        config.push("  layout: elk".to_string());
+    }
+
+    // Edge settings sit with the flowchart ones when there are any
+    let mut edges = Vec::new();
+    if let Some(curve) = fm.curve {
+        let name = match curve {
+            CurveStyle::Linear => "linear",
+            CurveStyle::Basis => "basis",
+            CurveStyle::StepAfter => "stepAfter",
+        };
+        edges.push(("curve", name.to_string()));
+    }
+    if let Some(routing) = fm.edge_routing {
+        let name = match routing {
+            EdgeRouting::Polyline => "polyline",
+            EdgeRouting::Orthogonal => "orthogonal",
+        };
+        edges.push(("edgeRouting", name.to_string()));
+    }
+
+    match &parsed.diagram {
+        DiagramType::Flowchart(graph) => {
+            let mut values = edges;
+            if let Some(spacing) = graph.config.node_spacing {
+                values.push(("nodeSpacing", spacing.to_string()));
+            }
+            if let Some(spacing) = graph.config.rank_spacing {
+                values.push(("rankSpacing", spacing.to_string()));
+            }
+            if let Some(bundle) = graph.config.bundle_edges {
+                values.push(("bundleEdges", bundle.to_string()));
+            }
+            if let Some(swimlanes) = graph.config.swimlanes {
+                values.push(("swimlanes", swimlanes.to_string()));
+            }
+            section(&mut config, "flowchart", values);
+        }
+        _ => {
+            for (key, value) in edges {
+                config.push(format!("  {}: {}", key, value));
+            }
+        }
+    }
+    match &parsed.diagram {
+        DiagramType::Flowchart(_) => {}
+        DiagramType::Sequence(diagram) => {
+            let c = &diagram.config;
+            let margins = [
+                ("actorMargin", c.actor_margin),
+                ("messageMargin", c.message_margin),
+                ("boxMargin", c.box_margin),
+            ];
+            let mut values: Vec<_> = margins
+                .into_iter()
+                .filter_map(|(key, margin)| margin.map(|m| (key, m.to_string())))
+                .collect();
+            if let Some(mirror) = c.mirror_actors {
+                values.push(("mirrorActors", mirror.to_string()));
+            }
+            section(&mut config, "sequence", values);
+        }
+        DiagramType::Class(diagram) => {
+            if !diagram.stereotype_styles.is_empty() {
+                config.push("  class:".to_string());
+                config.push("    stereotypeStyles:".to_string());
+                for name in sorted_keys(&diagram.stereotype_styles) {
+                    let props = style_props(&diagram.stereotype_styles[name]);
+                    config.push(format!("      {}: \"{}\"", name, props));
+                }
+            }
+        }
+        DiagramType::Er(diagram) => {
+            let values = diagram
+                .config
+                .layout_direction
+                .map(|d| ("layoutDirection", direction_name(d).to_string()));
+            section(&mut config, "er", values.into_iter().collect());
+        }
+        DiagramType::GitGraph(graph) => gitgraph_config(graph, &mut config),
+    }
+
+    let mut lines = Vec::new();
+    if fm.title.is_none() && config.is_empty() {
+        return lines;
+    }
+    lines.push("---".to_string());
+    if let Some(title) = &fm.title {
+        lines.push(format!("title: {}", yaml_string(title)));
+    }
+    if !config.is_empty() {
+        lines.push("config:".to_string());
+        lines.extend(config);
+    }
+    lines.push("---".to_string());
+    lines
+}
+
+/// A `name:` map of `values` under `config:`, if there are any
+fn section(config: &mut Vec<String>, name: &str, values: Vec<(&str, String)>) {
+    if !values.is_empty() {
+        config.push(format!("  {}:", name));
+        for (key, value) in values {
+            config.push(format!("    {}: {}", key, value));
+        }
+    }
+}
+
+/// `text` as a YAML value, quoted when it would read as something else
+fn yaml_string(text: &str) -> String {
+    let plain = !text.contains(": ")
+        && !text.contains(" #")
+        && !text.starts_with(['#', '"', '\'', '[', '{', '&', '*', '!', '|', '>', '%', '@']);
+    if plain || text.contains('"') {
+        text.to_string()
+    } else {
+        format!("\"{}\"", text)
+    }
+}
+
+/// The `gitGraph:` settings that differ from the defaults, and the
+/// `themeVariables:` colors
+fn gitgraph_config(graph: &GitGraph, config: &mut Vec<String>) {
+    let c = &graph.config;
+    let mut values = Vec::new();
+    let mut flag = |key: &str, value: bool, default: bool| {
+        if value != default {
+            values.push(format!("    {}: {}", key, value));
+        }
+    };
+    flag("showBranches", c.show_branches, true);
+    flag("showCommitLabel", c.show_commit_label, true);
+    flag("showCommitMessage", c.show_commit_message, false);
+    flag("showCommitMetadata", c.show_commit_metadata, false);
+    flag("showCommitTooltip", c.show_commit_tooltip, true);
+    flag("parallelCommits", c.parallel_commits, false);
+    flag("rotateCommitLabel", c.rotate_commit_label, true);
+    if c.merge_style == GitMergeStyle::Orthogonal {
+        values.push("    mergeStyle: orthogonal".to_string());
+    }
+    if c.main_branch_name != "main" {
+        values.push(format!("    mainBranchName: {}", c.main_branch_name));
+    }
+    if let Some(order) = c.main_branch_order {
+        values.push(format!("    mainBranchOrder: {}", order));
+    }
+    if !values.is_empty() {
+        config.push("  gitGraph:".to_string());
+        config.extend(values);
+    }
+
+    let mut colors = Vec::new();
+    let indexed = [
+        ("git", &c.branch_colors),
+        ("gitBranchLabel", &c.branch_label_colors),
+        ("gitInv", &c.highlight_colors),
+    ];
+    for (prefix, list) in indexed {
+        for (i, color) in list.iter().enumerate() {
+            if let Some(color) = color {
+                colors.push(format!("    {}{}: \"{}\"", prefix, i, color));
+            }
+        }
+    }
+    let named = [
+        ("commitLabelColor", &c.commit_label_color),
+        ("commitLabelBackground", &c.commit_label_background),
+        ("commitLabelFontSize", &c.commit_label_font_size),
+        ("tagLabelColor", &c.tag_label_color),
+        ("tagLabelBackground", &c.tag_label_background),
+        ("tagLabelBorder", &c.tag_label_border),
+        ("tagLabelFontSize", &c.tag_label_font_size),
+    ];
+    for (key, value) in named {
+        if let Some(value) = value {
+            colors.push(format!("    {}: \"{}\"", key, value));
+        }
+    }
+    if !colors.is_empty() {
+        config.push("  themeVariables:".to_string());
+        config.extend(colors);
+    }
+}
+
+// ============================================================================
+// Statement order
+// ============================================================================
+
+/// A line to write: an item declared on its own, or a statement
+enum Step {
+    Declare(usize),
+    Statement(usize),
+}
+
+/// The order to write `items` and the `statements` mentioning them (by id)
+/// in, so that the parser meets the items in their order. Statements keep
+/// theirs, each coming as soon as the items it mentions first are the next
+/// ones due. An item no statement brings in on time is declared on its own,
+/// or, if it can't be (`declarable` is false), brings forward the first
+/// statement mentioning it.
+fn mention_order(
+    items: &[&str],
+    declarable: impl Fn(usize) -> bool,
+    statements: &[Vec<&str>],
+) -> Vec<Step> {
+    let mut steps = Vec::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut written = vec![false; statements.len()];
+    let mut next = 0;
+
+    for (i, &item) in items.iter().enumerate() {
+        while !seen.contains(item) {
+            while next < statements.len() && written[next] {
+                next += 1;
+            }
+            if next < statements.len() {
+                let mut new: Vec<&str> = Vec::new();
+                for &id in &statements[next] {
+                    if !seen.contains(id) && !new.contains(&id) {
+                        new.push(id);
+                    }
+                }
+                let due = items[i..].iter().filter(|id| !seen.contains(**id));
+                if new.iter().eq(due.take(new.len())) {
+                    steps.push(Step::Statement(next));
+                    written[next] = true;
+                    seen.extend(new);
+                    continue;
+                }
+            }
+            if declarable(i) {
+                steps.push(Step::Declare(i));
+                seen.insert(item);
+                break;
+            }
+            let Some(k) =
+                (next..statements.len()).find(|&k| !written[k] && statements[k].contains(&item))
+            else {
+                break;
+            };
+            steps.push(Step::Statement(k));
+            written[k] = true;
+            seen.extend(statements[k].iter().copied());
+        }
+    }
+    for (k, done) in written.into_iter().enumerate() {
+        if !done {
+            steps.push(Step::Statement(k));
+        }
+    }
+    steps
+}
+
+// ============================================================================
+// Flowcharts
+// ============================================================================
+
+fn is_state_diagram(graph: &MermaidGraph) -> bool {
+    graph
+        .nodes
+        .values()
+        .any(|n| matches!(n.shape, NodeShape::StateStart | NodeShape::StateEnd))
+}
+
+/// The brackets of `node` around its label, or nothing for a rectangle
+/// labelled with its id
+fn node_shape(node: &MermaidNode) -> String {
+    let label = &node.label;
+    match node.shape {
+        NodeShape::Rectangle if *label == node.id => String::new(),
+        NodeShape::Rectangle => format!("[{}]", label),
+        NodeShape::Rounded => format!("({})", label),
+        NodeShape::Diamond => format!("{{{}}}", label),
+        NodeShape::Stadium => format!("([{}])", label),
+        NodeShape::Circle | NodeShape::StateStart | NodeShape::StateEnd => {
+            format!("(({}))", label)
+        }
+        NodeShape::Subroutine => format!("[[{}]]", label),
+        NodeShape::DoubleCircle => format!("((({})))", label),
+        NodeShape::Hexagon => format!("{{{{{}}}}}", label),
+        NodeShape::Cylinder => format!("[({})]", label),
+        NodeShape::Asymmetric => format!(">{}]", label),
+        NodeShape::Trapezoid => format!("[/{}\\]", label),
+        NodeShape::TrapezoidAlt => format!("[\\{}/]", label),
+    }
+}
+
+/// A node as a statement mentions it: with its shape and class the first
+/// time, as a bare id after that
+fn node_ref<'a>(
+    graph: &MermaidGraph,
+    id: &'a str,
+    port: Option<Port>,
+    defined: &mut HashSet<&'a str>,
+) -> String {
+    let mut text = id.to_string();
+    let first = defined.insert(id);
+    if first {
+        if let Some(node) = graph.nodes.get(id) {
+            text.push_str(&node_shape(node));
+        }
+    }
+    if let Some(port) = port {
+        text.push_str(match port {
+            Port::North => ":n",
+            Port::South => ":s",
+            Port::East => ":e",
+            Port::West => ":w",
+        });
+    }
+    if first && graph.nodes.contains_key(id) {
+        if let Some(class) = graph.class_assignments.get(id) {
+            text.push_str(&format!(":::{}", class));
+        }
+    }
+    text
+}
+
+/// `-->`, `<-.->`, `===`, ... with the edge's labels and class
+fn flowchart_arrow(edge: &MermaidEdge) -> String {
+    let start = match edge.start_marker {
+        EdgeMarker::None => "",
+        EdgeMarker::Arrow => "<",
+        EdgeMarker::Circle => "o",
+        EdgeMarker::Cross => "x",
+    };
+    let end = match edge.end_marker {
+        EdgeMarker::None => "",
+        EdgeMarker::Arrow => ">",
+        EdgeMarker::Circle => "o",
+        EdgeMarker::Cross => "x",
+    };
+    let length = edge.min_length.max(1);
+    let body = match edge.style {
+        EdgeStyle::Dotted => format!("-{}-", ".".repeat(length)),
+        EdgeStyle::Solid | EdgeStyle::Thick => {
+            let line = if edge.style == EdgeStyle::Thick {
+                "="
+            } else {
+                "-"
+            };
+            line.repeat(if end.is_empty() {
+                length + 2
+            } else {
+                length + 1
+            })
+        }
+    };
+    let mut arrow = format!("{}{}{}", start, body, end);
+    if edge.start_label.is_some() || edge.end_label.is_some() {
+        let text = |label: &Option<String>| label.clone().unwrap_or_default();
+        arrow.push_str(&format!(
+            "||{}|{}|{}||",
+            text(&edge.start_label),
+            text(&edge.label),
+            text(&edge.end_label)
+        ));
+    } else if let Some(label) = &edge.label {
+        arrow.push_str(&format!("|{}|", label));
+    }
+    if let Some(class) = &edge.class {
+        arrow.push_str(&format!(":::{}", class));
+    }
+    arrow
+}
+
+fn flowchart(graph: &MermaidGraph, out: &mut Vec<String>) {
+    out.push(format!("flowchart {}", direction_name(graph.direction)));
+
+    let items: Vec<&str> = graph.node_order.iter().map(String::as_str).collect();
+    let mentions: Vec<Vec<&str>> = graph
+        .edges
+        .iter()
+        .map(|e| vec![e.source.as_str(), e.target.as_str()])
+        .collect();
+    let mut defined = HashSet::new();
+    for step in mention_order(&items, |_| true, &mentions) {
+        match step {
+            Step::Declare(i) => {
+                let text = node_ref(graph, items[i], None, &mut defined);
+                push(out, 1, text);
+            }
+            Step::Statement(k) => {
+                let edge = &graph.edges[k];
+                let source = node_ref(graph, &edge.source, edge.start_port, &mut defined);
+                let target = node_ref(graph, &edge.target, edge.end_port, &mut defined);
+                push(
+                    out,
+                    1,
+                    format!("{} {} {}", source, flowchart_arrow(edge), target),
+                );
+            }
+        }
+    }
+
+    for subgraph in &graph.subgraphs {
+        flowchart_subgraph(subgraph, 1, out);
+    }
+
+    for name in sorted_keys(&graph.class_defs) {
+        let props = style_props(&graph.class_defs[name]);
+        push(out, 1, format!("classDef {} {}", name, props));
+    }
+    // Classes of nodes went on their first mention
+    for id in sorted_keys(&graph.class_assignments) {
+        if !graph.nodes.contains_key(id.as_str()) {
+            push(
+                out,
+                1,
+                format!("class {} {}", id, graph.class_assignments[id]),
+            );
+        }
+    }
+    for id in sorted_keys(&graph.node_styles) {
+        let props = style_props(&graph.node_styles[id]);
+        push(out, 1, format!("style {} {}", id, props));
+    }
+    for id in sorted_keys(&graph.node_links) {
+        let link = &graph.node_links[id];
+        let mut text = format!("click {} \"{}\"", id, link.url);
+        if let Some(tooltip) = &link.tooltip {
+            text.push_str(&format!(" \"{}\"", tooltip));
+        }
+        if let Some(target) = &link.target {
+            text.push_str(&format!(" {}", target));
+        }
+        push(out, 1, text);
+    }
+}
+
+/// A subgraph block listing its nodes by id, which the statements before it
+/// have defined
+fn flowchart_subgraph(subgraph: &MermaidSubgraph, depth: usize, out: &mut Vec<String>) {
+    if subgraph.label == subgraph.id {
+        push(out, depth, format!("subgraph {}", subgraph.id));
+    } else {
+        push(
+            out,
+            depth,
+            format!("subgraph {} [{}]", subgraph.id, subgraph.label),
+        );
+    }
+    if let Some(direction) = subgraph.direction {
+        push(
+            out,
+            depth + 1,
+            format!("direction {}", direction_name(direction)),
+        );
+    }
+    for id in &subgraph.node_ids {
+        push(out, depth + 1, id);
+    }
+    for child in &subgraph.children {
+        flowchart_subgraph(child, depth + 1, out);
+    }
+    push(out, depth, "end");
+}
+
+// ============================================================================
+// State diagrams
+// ============================================================================
+
+fn is_pseudostate(graph: &MermaidGraph, id: &str) -> bool {
+    graph
+        .nodes
+        .get(id)
+        .is_some_and(|n| matches!(n.shape, NodeShape::StateStart | NodeShape::StateEnd))
+}
+
+/// A state as a transition names it, `[*]` for the start and end
+fn state_ref<'a>(graph: &MermaidGraph, id: &'a str) -> &'a str {
+    if is_pseudostate(graph, id) {
+        "[*]"
+    } else {
+        id
+    }
+}
+
+fn transition(graph: &MermaidGraph, edge: &MermaidEdge) -> String {
+    let mut text = format!(
+        "{} --> {}",
+        state_ref(graph, &edge.source),
+        state_ref(graph, &edge.target)
+    );
+    if let Some(label) = &edge.label {
+        text.push_str(&format!(" : {}", label));
+    }
+    text
+}
+
+/// Ids of all the states of `subgraph` and of the composites inside it
+fn composite_members<'a>(subgraph: &'a MermaidSubgraph, ids: &mut HashSet<&'a str>) {
+    ids.extend(subgraph.node_ids.iter().map(String::as_str));
+    for child in &subgraph.children {
+        composite_members(child, ids);
+    }
+}
+
+/// Whether `subgraph` itself holds both ends of `edge`
+fn holds_edge(subgraph: &MermaidSubgraph, edge: &MermaidEdge) -> bool {
+    subgraph.node_ids.contains(&edge.source) && subgraph.node_ids.contains(&edge.target)
+}
+
+fn held_anywhere(subgraphs: &[MermaidSubgraph], edge: &MermaidEdge) -> bool {
+    subgraphs
+        .iter()
+        .any(|sg| holds_edge(sg, edge) || held_anywhere(&sg.children, edge))
+}
+
+/// States are declared up front with their labels, so that transitions
+/// anywhere can name them by id; composites then list their states, and hold
+/// the transitions between them.
+fn state(graph: &MermaidGraph, out: &mut Vec<String>) {
+    out.push("stateDiagram-v2".to_string());
+    if graph.direction != Direction::TD {
+        push(
+            out,
+            1,
+            format!("direction {}", direction_name(graph.direction)),
+        );
+    }
+
+    let mut nested = HashSet::new();
+    for subgraph in &graph.subgraphs {
+        composite_members(subgraph, &mut nested);
+    }
+    let connected: HashSet<&str> = graph
+        .edges
+        .iter()
+        .flat_map(|e| [e.source.as_str(), e.target.as_str()])
+        .collect();
+    for id in &graph.node_order {
+        let Some(node) = graph.nodes.get(id) else {
+            continue;
+        };
+        if is_pseudostate(graph, id) {
+            continue;
+        }
+        if node.label != node.id {
+            push(out, 1, state_declaration(node));
+        } else if !connected.contains(id.as_str()) && !nested.contains(id.as_str()) {
+            push(out, 1, id);
+        }
+    }
+
+    for edge in &graph.edges {
+        if !held_anywhere(&graph.subgraphs, edge) {
+            push(out, 1, transition(graph, edge));
+        }
+    }
+    for subgraph in &graph.subgraphs {
+        state_composite(graph, subgraph, 1, out);
+    }
+}
+
+fn state_declaration(node: &MermaidNode) -> String {
+    if node.label.contains('"') {
+        format!("{} : {}", node.id, node.label)
+    } else {
+        format!("state \"{}\" as {}", node.label, node.id)
+    }
+}
+
+fn state_composite(
+    graph: &MermaidGraph,
+    subgraph: &MermaidSubgraph,
+    depth: usize,
+    out: &mut Vec<String>,
+) {
+    if subgraph.label == subgraph.id || subgraph.label.contains('"') {
+        push(out, depth, format!("state {} {{", subgraph.id));
+    } else {
+        push(
+            out,
+            depth,
+            format!("state \"{}\" as {} {{", subgraph.label, subgraph.id),
+        );
+    }
+    if let Some(direction) = subgraph.direction {
+        push(
+            out,
+            depth + 1,
+            format!("direction {}", direction_name(direction)),
+        );
+    }
+    for id in &subgraph.node_ids {
+        if !is_pseudostate(graph, id) {
+            push(out, depth + 1, id);
+        }
+    }
+    for edge in &graph.edges {
+        if holds_edge(subgraph, edge) {
+            push(out, depth + 1, transition(graph, edge));
+        }
+    }
+    for child in &subgraph.children {
+        state_composite(graph, child, depth + 1, out);
+    }
+    push(out, depth, "}");
+}
+
+// ============================================================================
+// Sequence diagrams
+// ============================================================================
+
+/// Messages in order, with the blocks, dividers and notes between them
+fn sequence(diagram: &SequenceDiagram, out: &mut Vec<String>) {
+    out.push("sequenceDiagram".to_string());
+    for actor in &diagram.actors {
+        let keyword = match actor.actor_type {
+            ActorType::Participant => "participant",
+            ActorType::Actor => "actor",
+        };
+        if actor.label == actor.id {
+            push(out, 1, format!("{} {}", keyword, actor.id));
+        } else {
+            // An alias in quotes loses them when parsed
+            let quoted = actor.label.starts_with('"') && actor.label.ends_with('"');
+            let label = if quoted {
+                format!("\"{}\"", actor.label)
+            } else {
+                actor.label.clone()
+            };
+            push(out, 1, format!("{} {} as {}", keyword, actor.id, label));
+        }
+    }
+
+    let mut open: Vec<usize> = Vec::new();
+    for i in 0..=diagram.messages.len() {
+        if i > 0 {
+            // Blocks are listed as they end, inner ones first
+            for (b, block) in diagram.blocks.iter().enumerate() {
+                if block.end_index == i - 1 && open.contains(&b) {
+                    open.retain(|&o| o != b);
+                    push(out, open.len() + 1, "end");
+                }
+            }
+        }
+        for note in &diagram.notes {
+            if note.after_index == i as i32 - 1 {
+                let position = match note.position {
+                    NotePosition::Left => "left of",
+                    NotePosition::Right => "right of",
+                    NotePosition::Over => "over",
+                };
+                push(
+                    out,
+                    open.len() + 1,
+                    format!(
+                        "Note {} {}: {}",
+                        position,
+                        note.actor_ids.join(","),
+                        note.text
+                    ),
+                );
+            }
+        }
+        for activation in &diagram.activations {
+            if activation.after_index == i as i32 - 1 {
+                let keyword = if activation.active {
+                    "activate"
+                } else {
+                    "deactivate"
+                };
+                push(
+                    out,
+                    open.len() + 1,
+                    format!("{} {}", keyword, activation.actor_id),
+                );
+            }
+        }
+        for &b in &open {
+            let block = &diagram.blocks[b];
+            for divider in block.dividers.iter().filter(|d| d.index == i) {
+                let keyword = if block.block_type == BlockType::Par {
+                    "and"
+                } else {
+                    "else"
+                };
+                push(
+                    out,
+                    open.len(),
+                    format!("{} {}", keyword, divider.label).trim_end(),
+                );
+            }
+        }
+        for (b, block) in diagram.blocks.iter().enumerate().rev() {
+            if block.start_index == i && !open.contains(&b) {
+                let keyword = match block.block_type {
+                    BlockType::Loop => "loop",
+                    BlockType::Alt => "alt",
+                    BlockType::Opt => "opt",
+                    BlockType::Par => "par",
+                    BlockType::Critical => "critical",
+                    BlockType::Break => "break",
+                    BlockType::Rect => "rect",
+                };
+                push(
+                    out,
+                    open.len() + 1,
+                    format!("{} {}", keyword, block.label).trim_end(),
+                );
+                open.push(b);
+            }
+        }
+        if let Some(message) = diagram.messages.get(i) {
+            let arrow = match (message.line_style, message.arrow_head) {
+                (LineStyle::Solid, ArrowHead::Filled) => "->>",
+                (LineStyle::Dashed, ArrowHead::Filled) => "-->>",
+                (LineStyle::Solid, ArrowHead::Open) => "->",
+                (LineStyle::Dashed, ArrowHead::Open) => "-->",
+            };
+            let activation = if message.activate {
+                "+"
+            } else if message.deactivate {
+                "-"
+            } else {
+                ""
+            };
+            push(
+                out,
+                open.len() + 1,
+                format!(
+                    "{}{}{}{}: {}",
+                    message.from, arrow, activation, message.to, message.label
+                ),
+            );
+        }
+    }
+    while open.pop().is_some() {
+        push(out, open.len() + 1, "end");
+    }
+}
+
+// ============================================================================
+// Class diagrams
+// ============================================================================
+
+/// `List<int>` as Mermaid writes it, `List~int~`
+fn tilde_generics(text: &str) -> String {
+    text.replace(['<', '>'], "~")
+}
+
+/// The type parameter of a class labelled `Id<T>`
+fn class_generic(cls: &ClassNode) -> Option<&str> {
+    let generic = cls
+        .label
+        .strip_prefix(cls.id.as_str())?
+        .strip_prefix('<')?
+        .strip_suffix('>')?;
+    let word = !generic.is_empty() && generic.chars().all(|c| c.is_alphanumeric() || c == '_');
+    word.then_some(generic)
+}
+
+fn class_member(member: &ClassMember) -> String {
+    let visibility = match member.visibility {
+        Visibility::None => String::new(),
+        v => v.to_char().to_string(),
+    };
+    let classifier = if member.is_static {
+        "$"
+    } else if member.is_abstract {
+        "*"
+    } else {
+        ""
+    };
+    let member_type = member.member_type.as_deref().map(tilde_generics);
+    if member.is_method {
+        let returns = member_type.map(|t| format!(" {}", t)).unwrap_or_default();
+        let params = member.params.as_deref().unwrap_or("");
+        return format!(
+            "{}{}({}){}{}",
+            visibility, member.name, params, classifier, returns
+        );
+    }
+    match member_type {
+        Some(t) if t.contains(char::is_whitespace) => {
+            format!("{}{} : {}{}", visibility, member.name, t, classifier)
+        }
+        Some(t) => format!("{}{} {}{}", visibility, t, member.name, classifier),
+        None => format!("{}{}{}", visibility, member.name, classifier),
+    }
+}
+
+fn has_class_body(cls: &ClassNode) -> bool {
+    cls.annotation.is_some() || !cls.attributes.is_empty() || !cls.methods.is_empty()
+}
+
+/// `class Id~T~`, with a block for its annotation and members if it has any
+fn class_declaration(cls: &ClassNode, out: &mut Vec<String>) {
+    let name = match class_generic(cls) {
+        Some(generic) => format!("{}~{}~", cls.id, generic),
+        None => cls.id.clone(),
+    };
+    if !has_class_body(cls) {
+        push(out, 1, format!("class {}", name));
+        return;
+    }
+    push(out, 1, format!("class {} {{", name));
+    if let Some(annotation) = &cls.annotation {
+        push(out, 2, format!("<<{}>>", annotation));
+    }
+    for member in cls.attributes.iter().chain(&cls.methods) {
+        push(out, 2, class_member(member));
+    }
+    push(out, 1, "}");
+}
+
+fn class_relationship(diagram: &ClassDiagram, rel: &ClassRelationship) -> String {
+    let lollipop = |id: &str| diagram.classes.iter().find(|c| c.id == id && c.is_lollipop);
+    if let Some(interface) = lollipop(&rel.from) {
+        return format!("{} ()-- {}", interface.label, rel.to);
+    }
+    if let Some(interface) = lollipop(&rel.to) {
+        return format!("{} --() {}", rel.from, interface.label);
+    }
+
+    let arrow = match (rel.rel_type, rel.marker_at_from) {
+        (RelationshipType::Inheritance, true) => "<|--",
+        (RelationshipType::Inheritance, false) => "--|>",
+        (RelationshipType::Composition, true) => "*--",
+        (RelationshipType::Composition, false) => "--*",
+        (RelationshipType::Aggregation, true) => "o--",
+        (RelationshipType::Aggregation, false) => "--o",
+        (RelationshipType::Association, true) => "<--",
+        (RelationshipType::Association, false) => "-->",
+        (RelationshipType::Dependency, true) => "<..",
+        (RelationshipType::Dependency, false) => "..>",
+        (RelationshipType::Realization, true) => "<|..",
+        (RelationshipType::Realization, false) => "..|>",
+    };
+    let mut text = rel.from.clone();
+    if let Some(card) = &rel.from_cardinality {
+        text.push_str(&format!(" \"{}\"", card));
+    }
+    text.push_str(&format!(" {}", arrow));
+    if let Some(card) = &rel.to_cardinality {
+        text.push_str(&format!(" \"{}\"", card));
+    }
+    text.push_str(&format!(" {}", rel.to));
+    if let Some(label) = &rel.label {
+        text.push_str(&format!(" : {}", label));
+    }
+    text
+}
+
+fn class(diagram: &ClassDiagram, out: &mut Vec<String>) {
+    out.push("classDiagram".to_string());
+    if diagram.direction != "TB" {
+        push(out, 1, format!("direction {}", diagram.direction));
+    }
+
+    let items: Vec<&str> = diagram.classes.iter().map(|c| c.id.as_str()).collect();
+    let mentions: Vec<Vec<&str>> = diagram
+        .relationships
+        .iter()
+        .map(|r| vec![r.from.as_str(), r.to.as_str()])
+        .collect();
+    let declarable = |i: usize| !diagram.classes[i].is_lollipop;
+    let mut declared = HashSet::new();
+    for step in mention_order(&items, declarable, &mentions) {
+        match step {
+            Step::Declare(i) => {
+                class_declaration(&diagram.classes[i], out);
+                declared.insert(i);
+            }
+            Step::Statement(k) => {
+                push(
+                    out,
+                    1,
+                    class_relationship(diagram, &diagram.relationships[k]),
+                );
+            }
+        }
+    }
+    // Members and generics of the classes relationships brought in
+    for (i, cls) in diagram.classes.iter().enumerate() {
+        let details = has_class_body(cls) || class_generic(cls).is_some();
+        if !declared.contains(&i) && !cls.is_lollipop && details {
+            class_declaration(cls, out);
+        }
+    }
+
+    for namespace in &diagram.namespaces {
+        push(out, 1, format!("namespace {} {{", namespace.name));
+        for id in &namespace.class_ids {
+            push(out, 2, format!("class {}", id));
+        }
+        push(out, 1, "}");
+    }
+    for note in &diagram.notes {
+        match &note.for_class {
+            Some(id) => push(out, 1, format!("note for {} \"{}\"", id, note.text)),
+            None => push(out, 1, format!("note \"{}\"", note.text)),
+        }
+    }
+}
+
+// ============================================================================
+// ER diagrams
+// ============================================================================
+
+fn er_attribute(attr: &ErAttribute) -> String {
+    let mut text = format!("{} {}", attr.attr_type, attr.name);
+    if !attr.keys.is_empty() {
+        let keys: Vec<&str> = attr
+            .keys
+            .iter()
+            .map(|key| match key {
+                ErKey::PK => "PK",
+                ErKey::FK => "FK",
+                ErKey::UK => "UK",
+            })
+            .collect();
+        text.push_str(&format!(" {}", keys.join(", ")));
+    }
+    if let Some(comment) = &attr.comment {
+        text.push_str(&format!(" \"{}\"", comment));
+    }
+    text
+}
+
+fn er_entity(entity: &ErEntity, out: &mut Vec<String>) {
+    push(out, 1, format!("{} {{", entity.id));
+    for attr in &entity.attributes {
+        push(out, 2, er_attribute(attr));
+    }
+    push(out, 1, "}");
+}
+
+fn er_relationship(rel: &ErRelationship) -> String {
+    let left = match rel.cardinality1 {
+        Cardinality::One => "||",
+        Cardinality::ZeroOne => "|o",
+        Cardinality::Many => "}|",
+        Cardinality::ZeroMany => "}o",
+    };
+    let right = match rel.cardinality2 {
+        Cardinality::One => "||",
+        Cardinality::ZeroOne => "o|",
+        Cardinality::Many => "|{",
+        Cardinality::ZeroMany => "o{",
+    };
+    let line = if rel.identifying { "--" } else { ".." };
+    format!(
+        "{} {}{}{} {} : {}",
+        rel.entity1, left, line, right, rel.entity2, rel.label
+    )
+}
+
+fn er(diagram: &ErDiagram, out: &mut Vec<String>) {
+    out.push("erDiagram".to_string());
+
+    let items: Vec<&str> = diagram.entities.iter().map(|e| e.id.as_str()).collect();
+    let mentions: Vec<Vec<&str>> = diagram
+        .relationships
+        .iter()
+        .map(|r| vec![r.entity1.as_str(), r.entity2.as_str()])
+        .collect();
+    let mut declared = HashSet::new();
+    for step in mention_order(&items, |_| true, &mentions) {
+        match step {
+            Step::Declare(i) => {
+                er_entity(&diagram.entities[i], out);
+                declared.insert(i);
+            }
+            Step::Statement(k) => push(out, 1, er_relationship(&diagram.relationships[k])),
+        }
+    }
+    // Attributes of the entities relationships brought in
+    for (i, entity) in diagram.entities.iter().enumerate() {
+        if !declared.contains(&i) && !entity.attributes.is_empty() {
+            er_entity(entity, out);
+        }
+    }
+}
+
+// ============================================================================
+// Git graphs
+// ============================================================================
+
+/// Branches as the parser sees them while it reads the commands
+struct GitState<'a> {
+    /// Created branches with their last commit, if any
+    tips: HashMap<&'a str, Option<&'a str>>,
+    /// Commit each created branch started from
+    sources: HashMap<&'a str, Option<&'a str>>,
+    current: &'a str,
+}
+
+impl<'a> GitState<'a> {
+    /// The commit a branch made from `branch` would start from
+    fn head(&self, branch: &str) -> Option<&'a str> {
+        self.tips
+            .get(branch)
+            .copied()
+            .flatten()
+            .or_else(|| self.sources.get(branch).copied().flatten())
+    }
+
+    fn checkout(&mut self, branch: &'a str, out: &mut Vec<String>) {
+        if self.current != branch {
+            push(out, 1, format!("checkout {}", branch));
+            self.current = branch;
+        }
+    }
+
+    /// Create the next branches of `pending` (last first) while the commit
+    /// each starts from is the head of a created branch
+    fn create_due(&mut self, pending: &mut Vec<&'a GitBranch>, out: &mut Vec<String>) {
+        while let Some(&branch) = pending.last() {
+            let source = branch.source_commit.as_deref();
+            let from = if self.head(self.current) == source {
+                Some(self.current)
+            } else {
+                let mut created: Vec<&str> = self.tips.keys().copied().collect();
+                created.sort();
+                created.into_iter().find(|b| self.head(b) == source)
+            };
+            let Some(from) = from else {
+                break;
+            };
+            self.create(branch, from, out);
+            pending.pop();
+        }
+    }
+
+    /// Create `branch` from `from`
+    fn create(&mut self, branch: &'a GitBranch, from: &'a str, out: &mut Vec<String>) {
+        self.checkout(from, out);
+        let mut text = format!("branch {}", branch.name);
+        if let Some(order) = branch.order {
+            text.push_str(&format!(" order: {}", order));
+        }
+        push(out, 1, text);
+        let head = self.head(from);
+        self.sources.insert(&branch.name, head);
+        self.tips.insert(&branch.name, None);
+        self.current = &branch.name;
+    }
+}
+
+/// Commits in order, each preceded by the `checkout` to its branch. Branches
+/// are created in their order, as soon as the commit they start from is the
+/// head of a branch.
+fn gitgraph(graph: &GitGraph, out: &mut Vec<String>) {
+    out.push(
+        match graph.direction {
+            GitGraphDirection::LR => "gitGraph",
+            GitGraphDirection::TB => "gitGraph TB:",
+            GitGraphDirection::BT => "gitGraph BT:",
+        }
+        .to_string(),
+    );
+
+    let main = graph.config.main_branch_name.as_str();
+    let mut state = GitState {
+        tips: HashMap::from([(main, None)]),
+        sources: HashMap::from([(main, None)]),
+        current: main,
+    };
+    let mut pending: Vec<&GitBranch> = graph.branches.iter().filter(|b| b.name != main).collect();
+    pending.reverse();
+
+    let mut counter = u32::from(b'A');
+    for commit in &graph.commits {
+        state.create_due(&mut pending, out);
+        let branch = commit.branch.as_str();
+        if !state.tips.contains_key(branch) {
+            // Out of order: create it where we are
+            if let Some(index) = pending.iter().position(|b| b.name == branch) {
+                let b = pending.remove(index);
+                let from = state.current;
+                state.create(b, from, out);
+            } else {
+                continue;
+            }
+        }
+        state.checkout(branch, out);
+
+        let mut text = if commit.is_cherry_pick {
+            let mut text = format!(
+                "cherry-pick id: \"{}\"",
+                commit.cherry_pick_source.as_deref().unwrap_or_default()
+            );
+            if let Some(parent) = &commit.cherry_pick_parent {
+                text.push_str(&format!(" parent: \"{}\"", parent));
+            }
+            text
+        } else if commit.is_merge {
+            let parent = commit.parent_ids.get(1).map(String::as_str);
+            let mut sources: Vec<&str> = state
+                .tips
+                .iter()
+                .filter(|(b, tip)| **b != branch && **tip == parent)
+                .map(|(b, _)| *b)
+                .collect();
+            sources.sort();
+            let merged = graph
+                .commits
+                .iter()
+                .find(|c| Some(c.id.as_str()) == parent)
+                .map(|c| c.branch.as_str())
+                .filter(|b| sources.contains(b))
+                .or(sources.first().copied())
+                .unwrap_or_default();
+            format!("merge {}", merged)
+        } else {
+            "commit".to_string()
+        };
+
+        if !commit.is_cherry_pick {
+            let auto = char::from_u32(counter).map_or_else(|| counter.to_string(), String::from);
+            counter += 1;
+            let texts = [&commit.tag, &commit.message, &commit.author, &commit.date];
+            // `id:` inside another value would be read as the id
+            let mentions_id = texts
+                .iter()
+                .filter_map(|t| t.as_deref())
+                .any(|t| t.to_lowercase().contains("id:"));
+            if commit.id != auto || mentions_id {
+                text.push_str(&format!(" id: \"{}\"", commit.id));
+            }
+            match commit.commit_type {
+                CommitType::Normal => {}
+                CommitType::Reverse => text.push_str(" type: REVERSE"),
+                CommitType::Highlight => text.push_str(" type: HIGHLIGHT"),
+            }
+            let values = [
+                ("tag", &commit.tag),
+                ("author", &commit.author),
+                ("date", &commit.date),
+                ("msg", &commit.message),
+            ];
+            for (key, value) in values {
+                if let Some(value) = value {
+                    text.push_str(&format!(" {}: \"{}\"", key, value));
+                }
+            }
+        }
+        push(out, 1, text);
+        state.tips.insert(branch, Some(commit.id.as_str()));
+    }
+
+    // Branches without commits
+    state.create_due(&mut pending, out);
+    while let Some(branch) = pending.pop() {
+        let from = state.current;
+        state.create(branch, from, out);
+    }
+}
@@ -0,0 +1,153 @@
+//! C bindings (the `ffi` feature)
+//!
+//! `extern "C"` functions for C, Python (ctypes/cffi), Go (cgo) and other
+//! callers that link against the `cdylib`. The header is `include/m2svg.h`,
+//! generated with cbindgen from `cbindgen.toml`.
+//!
+//! Strings cross the boundary as NUL-terminated UTF-8. A render function
+//! returns the output, or NULL with a message in `*error_out` (when
+//! `error_out` is not NULL); both strings belong to the caller, who releases
+//! them with [`m2svg_free_string`]. Options come as a JSON object, as for the
+//! `wasm` bindings; NULL or an empty string means the defaults. A panic inside
+//! the library is caught and reported as an error, so build with a profile that
+//! unwinds (`cargo build --profile ffi --features ffi`).
+//!
+//! # Example
+//! ```rust
+//! use std::ffi::{CStr, CString};
+//! use m2svg::ffi::{m2svg_free_string, m2svg_render_ascii};
+//!
+//! let input = CString::new("graph LR\n  A --> B").unwrap();
+//! let opts = CString::new(r#"{"useAscii": true}"#).unwrap();
+//! let mut error = std::ptr::null_mut();
+//! unsafe {
+//!     let out = m2svg_render_ascii(input.as_ptr(), opts.as_ptr(), &mut error);
+//!     assert!(CStr::from_ptr(out).to_str().unwrap().contains("+---+"));
+//!     m2svg_free_string(out);
+//! }
+//! ```
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::json_options::{ascii_options, svg_options};
+
+/// Render a Mermaid diagram to ASCII/Unicode text.
+///
+/// Returns NULL on error, with the message in `*error_out`.
+///
+/// # Safety
+/// `input` must be a valid NUL-terminated string; `opts_json` and
+/// `error_out` must each be NULL or valid.
+#[no_mangle]
+pub unsafe extern "C" fn m2svg_render_ascii(
+    input: *const c_char,
+    opts_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    render(input, opts_json, error_out, |input, opts| {
+        crate::render_mermaid_ascii(input, Some(ascii_options(opts)?))
+    })
+}
+
+/// Render a Mermaid diagram to SVG.
+///
+/// Returns NULL on error, with the message in `*error_out`.
+///
+/// # Safety
+/// `input` must be a valid NUL-terminated string; `opts_json` and
+/// `error_out` must each be NULL or valid.
+#[no_mangle]
+pub unsafe extern "C" fn m2svg_render_svg(
+    input: *const c_char,
+    opts_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    render(input, opts_json, error_out, |input, opts| {
+        crate::render_mermaid_svg(input, Some(svg_options(opts)?))
+    })
+}
+
+/// Lay out a Mermaid flowchart and return its node positions and edge routes
+/// as JSON. Takes the same options as `m2svg_render_svg`.
+///
+/// Returns NULL on error, with the message in `*error_out`.
+///
+/// # Safety
+/// `input` must be a valid NUL-terminated string; `opts_json` and
+/// `error_out` must each be NULL or valid.
+#[no_mangle]
+pub unsafe extern "C" fn m2svg_layout_json(
+    input: *const c_char,
+    opts_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    render(input, opts_json, error_out, |input, opts| {
+        crate::layout_to_json(input, Some(svg_options(opts)?))
+    })
+}
+
+/// Release a string returned by this library. NULL is ignored.
+///
+/// # Safety
+/// `s` must be NULL or a string from an `m2svg_*` function, not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn m2svg_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Read the arguments, run `f` with panics caught, and hand back its output
+/// or error as C strings
+unsafe fn render(
+    input: *const c_char,
+    opts_json: *const c_char,
+    error_out: *mut *mut c_char,
+    f: impl FnOnce(&str, &str) -> Result<String, String>,
+) -> *mut c_char {
+    if !error_out.is_null() {
+        *error_out = ptr::null_mut();
+    }
+    let result = read_str(input, "input").and_then(|input| {
+        let opts = if opts_json.is_null() {
+            ""
+        } else {
+            read_str(opts_json, "options")?
+        };
+        panic::catch_unwind(AssertUnwindSafe(|| f(input, opts))).unwrap_or_else(|payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(format!("Internal error: {}", message))
+        })
+    });
+    match result {
+        Ok(output) => into_c_string(output),
+        Err(message) => {
+            if !error_out.is_null() {
+                *error_out = into_c_string(message);
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+unsafe fn read_str<'a>(s: *const c_char, what: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("The {} is NULL", what));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| format!("The {} is not valid UTF-8", what))
+}
+
+/// Output can't contain NUL bytes unless the input did; they are dropped
+fn into_c_string(s: String) -> *mut c_char {
+    CString::new(s.replace('\0', ""))
+        .map(CString::into_raw)
+        .unwrap_or(ptr::null_mut())
+}
@@ -0,0 +1,591 @@
+//! Views of parts of large diagrams
+//!
+//! Cuts a big architecture diagram down to one of its subgraphs, or to the
+//! nodes around one node, so it can be explored piece by piece. Generated
+//! class and ER diagrams can be trimmed by class or entity name, and to some
+//! kinds of relationship.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::types::{
+    ClassDiagram, ClassNamespace, DiagramType, ErDiagram, MermaidGraph, MermaidSubgraph,
+    ParsedDiagram, RelationshipType,
+};
+
+/// Which part of a diagram to render
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderFilter {
+    /// Subgraphs, by id or label, whose nodes (nested subgraphs included) are
+    /// kept; empty keeps all of them. Default: empty
+    pub include_subgraphs: Vec<String>,
+    /// Node, by id or label, whose neighborhood is kept. Default: none
+    pub focus_node: Option<String>,
+    /// How many edges away from the focus node, in either direction, nodes
+    /// are kept. Default: 1
+    pub depth: usize,
+    /// Class or entity names (ids or labels) to keep, as globs where `*`
+    /// matches any run of characters and `?` any one; empty keeps all of
+    /// them. Default: empty
+    pub include: Vec<String>,
+    /// Class or entity names to leave out, as globs; these win over
+    /// `include`. Default: empty
+    pub exclude: Vec<String>,
+    /// Relationship types to keep: `inheritance`, `composition`,
+    /// `aggregation`, `association`, `dependency` or `realization` in class
+    /// diagrams, `identifying` or `non-identifying` in ER diagrams; empty
+    /// keeps all of them. Default: empty
+    pub only_relations: Vec<String>,
+}
+
+impl Default for RenderFilter {
+    fn default() -> Self {
+        Self {
+            include_subgraphs: Vec::new(),
+            focus_node: None,
+            depth: 1,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            only_relations: Vec::new(),
+        }
+    }
+}
+
+impl RenderFilter {
+    /// `parsed` cut down to the part the filter selects
+    ///
+    /// Subgraphs and focus nodes only apply to flowcharts, and names and
+    /// relationship types only to class and ER diagrams.
+    ///
+    /// # Example
+    /// ```rust
+    /// use m2svg::{parse_mermaid, DiagramType, RenderFilter};
+    ///
+    /// let parsed = parse_mermaid("graph LR\n  A --> B --> C --> D").unwrap();
+    /// let filter = RenderFilter {
+    ///     focus_node: Some("B".to_string()),
+    ///     ..Default::default()
+    /// };
+    /// let DiagramType::Flowchart(graph) = filter.apply(&parsed).unwrap().diagram else {
+    ///     unreachable!()
+    /// };
+    /// assert_eq!(graph.node_order, ["A", "B", "C"]);
+    /// assert_eq!(graph.edges.len(), 2);
+    /// ```
+    pub fn apply(&self, parsed: &ParsedDiagram) -> Result<ParsedDiagram, String> {
+        let by_name =
+            !self.include.is_empty() || !self.exclude.is_empty() || !self.only_relations.is_empty();
+        let by_part = !self.include_subgraphs.is_empty() || self.focus_node.is_some();
+        let diagram = match &parsed.diagram {
+            DiagramType::Flowchart(graph) if !by_name => {
+                DiagramType::Flowchart(filter_flowchart(graph, self)?)
+            }
+            DiagramType::Class(diagram) if !by_part => {
+                DiagramType::Class(filter_class_diagram(diagram, self)?)
+            }
+            DiagramType::Er(diagram) if !by_part => {
+                DiagramType::Er(filter_er_diagram(diagram, self)?)
+            }
+            _ if by_part => {
+                return Err("Only flowcharts can be filtered to a subgraph or node".to_string())
+            }
+            _ => {
+                return Err(
+                    "Only class and ER diagrams can be filtered by name or relationship"
+                        .to_string(),
+                )
+            }
+        };
+        Ok(ParsedDiagram {
+            diagram,
+            frontmatter: parsed.frontmatter.clone(),
+        })
+    }
+
+    /// Whether the class or entity with this id and label is kept
+    fn keeps_name(&self, id: &str, label: &str) -> bool {
+        let matches = |pattern: &String| glob_match(pattern, id) || glob_match(pattern, label);
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// Keep only the classes of `diagram` whose names `filter` selects, and the
+/// relationships of the selected types between them.
+///
+/// Notes on dropped classes go, as do namespaces left without a class.
+pub fn filter_class_diagram(
+    diagram: &ClassDiagram,
+    filter: &RenderFilter,
+) -> Result<ClassDiagram, String> {
+    let types = filter
+        .only_relations
+        .iter()
+        .map(|name| {
+            relationship_type(name)
+                .ok_or_else(|| format!("Unknown class relationship type: {}", name))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let kept: HashSet<&str> = diagram
+        .classes
+        .iter()
+        .filter(|c| filter.keeps_name(&c.id, &c.label))
+        .map(|c| c.id.as_str())
+        .collect();
+    let keep = |id: &String| kept.contains(id.as_str());
+
+    Ok(ClassDiagram {
+        classes: diagram
+            .classes
+            .iter()
+            .filter(|c| keep(&c.id))
+            .cloned()
+            .collect(),
+        relationships: diagram
+            .relationships
+            .iter()
+            .filter(|r| keep(&r.from) && keep(&r.to))
+            .filter(|r| types.is_empty() || types.contains(&r.rel_type))
+            .cloned()
+            .collect(),
+        namespaces: diagram
+            .namespaces
+            .iter()
+            .filter_map(|ns| {
+                let class_ids: Vec<String> =
+                    ns.class_ids.iter().filter(|id| keep(id)).cloned().collect();
+                (!class_ids.is_empty()).then(|| ClassNamespace {
+                    class_ids,
+                    ..ns.clone()
+                })
+            })
+            .collect(),
+        notes: diagram
+            .notes
+            .iter()
+            .filter(|n| n.for_class.as_ref().is_none_or(keep))
+            .cloned()
+            .collect(),
+        ..diagram.clone()
+    })
+}
+
+/// Keep only the entities of `diagram` whose names `filter` selects, and the
+/// relationships of the selected kinds between them.
+pub fn filter_er_diagram(diagram: &ErDiagram, filter: &RenderFilter) -> Result<ErDiagram, String> {
+    let mut identifying = HashSet::new();
+    for name in &filter.only_relations {
+        match name.to_ascii_lowercase().as_str() {
+            "identifying" => identifying.insert(true),
+            "non-identifying" => identifying.insert(false),
+            _ => return Err(format!("Unknown ER relationship type: {}", name)),
+        };
+    }
+    let kept: HashSet<&str> = diagram
+        .entities
+        .iter()
+        .filter(|e| filter.keeps_name(&e.id, &e.label))
+        .map(|e| e.id.as_str())
+        .collect();
+
+    Ok(ErDiagram {
+        entities: diagram
+            .entities
+            .iter()
+            .filter(|e| kept.contains(e.id.as_str()))
+            .cloned()
+            .collect(),
+        relationships: diagram
+            .relationships
+            .iter()
+            .filter(|r| kept.contains(r.entity1.as_str()) && kept.contains(r.entity2.as_str()))
+            .filter(|r| identifying.is_empty() || identifying.contains(&r.identifying))
+            .cloned()
+            .collect(),
+        config: diagram.config,
+    })
+}
+
+/// The class relationship type called `name` (any case)
+fn relationship_type(name: &str) -> Option<RelationshipType> {
+    Some(match name.to_ascii_lowercase().as_str() {
+        "inheritance" => RelationshipType::Inheritance,
+        "composition" => RelationshipType::Composition,
+        "aggregation" => RelationshipType::Aggregation,
+        "association" => RelationshipType::Association,
+        "dependency" => RelationshipType::Dependency,
+        "realization" => RelationshipType::Realization,
+        _ => return None,
+    })
+}
+
+/// Whether `text` matches `pattern`, where `*` stands for any run of
+/// characters and `?` for any one character
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // Greedy matching that backtracks to the last `*` on a mismatch
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Keep only the nodes of `graph` that `filter` selects.
+///
+/// A node is kept if it is in one of the included subgraphs (when any are
+/// named) and within `depth` edges of the focus node (when there is one).
+/// Edges between kept nodes stay, as do the subgraphs that still hold a kept
+/// node; the styles, classes and links of dropped nodes go.
+pub fn filter_flowchart(
+    graph: &MermaidGraph,
+    filter: &RenderFilter,
+) -> Result<MermaidGraph, String> {
+    let mut kept: HashSet<&str> = graph.node_order.iter().map(String::as_str).collect();
+
+    if !filter.include_subgraphs.is_empty() {
+        let mut inside = HashSet::new();
+        for name in &filter.include_subgraphs {
+            let subgraph = find_subgraph(&graph.subgraphs, name)
+                .ok_or_else(|| format!("No subgraph named {} in the diagram", name))?;
+            subgraph_nodes(subgraph, &mut inside);
+        }
+        kept.retain(|id| inside.contains(id));
+    }
+
+    if let Some(name) = &filter.focus_node {
+        let focus = graph
+            .node_order
+            .iter()
+            .find(|id| *id == name)
+            .or_else(|| {
+                graph
+                    .node_order
+                    .iter()
+                    .find(|id| graph.nodes.get(*id).is_some_and(|n| n.label == *name))
+            })
+            .ok_or_else(|| format!("No node named {} in the diagram", name))?;
+        let near = neighborhood(graph, focus, filter.depth);
+        kept.retain(|id| near.contains(id));
+    }
+
+    let keep = |id: &String| kept.contains(id.as_str());
+    let mut filtered = MermaidGraph {
+        direction: graph.direction,
+        nodes: graph
+            .nodes
+            .iter()
+            .filter(|(id, _)| keep(id))
+            .map(|(id, node)| (id.clone(), node.clone()))
+            .collect(),
+        node_order: graph
+            .node_order
+            .iter()
+            .filter(|id| keep(id))
+            .cloned()
+            .collect(),
+        edges: graph
+            .edges
+            .iter()
+            .filter(|e| keep(&e.source) && keep(&e.target))
+            .cloned()
+            .collect(),
+        subgraphs: prune_subgraphs(&graph.subgraphs, &keep),
+        class_defs: graph.class_defs.clone(),
+        config: graph.config,
+        ..MermaidGraph::new(graph.direction)
+    };
+    for (id, class) in &graph.class_assignments {
+        if keep(id) {
+            filtered.class_assignments.insert(id.clone(), class.clone());
+        }
+    }
+    for (id, style) in &graph.node_styles {
+        if keep(id) {
+            filtered.node_styles.insert(id.clone(), style.clone());
+        }
+    }
+    for (id, link) in &graph.node_links {
+        if keep(id) {
+            filtered.node_links.insert(id.clone(), link.clone());
+        }
+    }
+    Ok(filtered)
+}
+
+/// The subgraph with id or label `name`, at any depth
+fn find_subgraph<'a>(subgraphs: &'a [MermaidSubgraph], name: &str) -> Option<&'a MermaidSubgraph> {
+    subgraphs.iter().find_map(|sg| {
+        if sg.id == name || sg.label == name {
+            Some(sg)
+        } else {
+            find_subgraph(&sg.children, name)
+        }
+    })
+}
+
+/// Add the nodes of `subgraph` and of the subgraphs nested in it to `nodes`
+fn subgraph_nodes<'a>(subgraph: &'a MermaidSubgraph, nodes: &mut HashSet<&'a str>) {
+    nodes.extend(subgraph.node_ids.iter().map(String::as_str));
+    for child in &subgraph.children {
+        subgraph_nodes(child, nodes);
+    }
+}
+
+/// The nodes at most `depth` edges from `focus`, following edges either way
+fn neighborhood<'a>(graph: &'a MermaidGraph, focus: &'a str, depth: usize) -> HashSet<&'a str> {
+    let mut near = HashSet::from([focus]);
+    let mut queue = VecDeque::from([(focus, 0)]);
+    while let Some((id, distance)) = queue.pop_front() {
+        if distance == depth {
+            continue;
+        }
+        for edge in &graph.edges {
+            let next = if edge.source == id {
+                edge.target.as_str()
+            } else if edge.target == id {
+                edge.source.as_str()
+            } else {
+                continue;
+            };
+            if near.insert(next) {
+                queue.push_back((next, distance + 1));
+            }
+        }
+    }
+    near
+}
+
+/// `subgraphs` with only the kept nodes, dropping those left empty
+fn prune_subgraphs(
+    subgraphs: &[MermaidSubgraph],
+    keep: &impl Fn(&String) -> bool,
+) -> Vec<MermaidSubgraph> {
+    subgraphs
+        .iter()
+        .filter_map(|sg| {
+            let node_ids: Vec<String> = sg.node_ids.iter().filter(|id| keep(id)).cloned().collect();
+            let children = prune_subgraphs(&sg.children, keep);
+            if node_ids.is_empty() && children.is_empty() {
+                return None;
+            }
+            Some(MermaidSubgraph {
+                node_ids,
+                children,
+                ..sg.clone()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_mermaid;
+
+    fn flowchart(text: &str) -> MermaidGraph {
+        match parse_mermaid(text).unwrap().diagram {
+            DiagramType::Flowchart(g) => g,
+            _ => panic!("expected flowchart"),
+        }
+    }
+
+    const ARCHITECTURE: &str = "graph LR
+  subgraph web [Web tier]
+    LB --> App[App server]
+  end
+  subgraph data [Data tier]
+    DB --> Replica
+  end
+  App --> Cache
+  App --> DB
+  Cache --> Metrics
+  style Metrics fill:#f00";
+
+    #[test]
+    fn test_focus_keeps_the_neighborhood() {
+        let graph = flowchart(ARCHITECTURE);
+        let filter = RenderFilter {
+            focus_node: Some("App server".to_string()),
+            depth: 2,
+            ..Default::default()
+        };
+        let filtered = filter_flowchart(&graph, &filter).unwrap();
+
+        assert_eq!(
+            filtered.node_order,
+            vec!["LB", "App", "DB", "Replica", "Cache", "Metrics"]
+        );
+        assert_eq!(filtered.node_styles["Metrics"]["fill"], "#f00");
+
+        let filter = RenderFilter {
+            focus_node: Some("Metrics".to_string()),
+            ..Default::default()
+        };
+        let filtered = filter_flowchart(&graph, &filter).unwrap();
+        assert_eq!(filtered.node_order, vec!["Cache", "Metrics"]);
+        assert!(filtered.subgraphs.is_empty());
+    }
+
+    #[test]
+    fn test_subgraphs_keep_their_nodes() {
+        let graph = flowchart(ARCHITECTURE);
+        let filter = RenderFilter {
+            include_subgraphs: vec!["Data tier".to_string()],
+            ..Default::default()
+        };
+        let filtered = filter_flowchart(&graph, &filter).unwrap();
+
+        assert_eq!(filtered.node_order, vec!["DB", "Replica"]);
+        assert_eq!(filtered.edges.len(), 1);
+        assert_eq!(filtered.subgraphs.len(), 1);
+        assert_eq!(filtered.subgraphs[0].id, "data");
+        assert!(filtered.node_styles.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_names_are_errors() {
+        let graph = flowchart(ARCHITECTURE);
+        let filter = RenderFilter {
+            focus_node: Some("Nope".to_string()),
+            ..Default::default()
+        };
+        let error = filter_flowchart(&graph, &filter).unwrap_err();
+        assert_eq!(error, "No node named Nope in the diagram");
+
+        let filter = RenderFilter {
+            include_subgraphs: vec!["api".to_string()],
+            ..Default::default()
+        };
+        let error = filter_flowchart(&graph, &filter).unwrap_err();
+        assert_eq!(error, "No subgraph named api in the diagram");
+    }
+
+    const MODEL: &str = "classDiagram
+  namespace domain {
+    class Order
+    class OrderTest
+  }
+  Entity <|-- Order
+  Order *-- LineItem
+  Order --> Customer
+  OrderTest ..> Order
+  note for OrderTest \"generated\"";
+
+    fn class_diagram(text: &str) -> ClassDiagram {
+        match parse_mermaid(text).unwrap().diagram {
+            DiagramType::Class(d) => d,
+            _ => panic!("expected class diagram"),
+        }
+    }
+
+    #[test]
+    fn test_class_names_and_relations() {
+        let diagram = class_diagram(MODEL);
+        let filter = RenderFilter {
+            exclude: vec!["*Test*".to_string()],
+            only_relations: vec!["inheritance".to_string(), "composition".to_string()],
+            ..Default::default()
+        };
+        let filtered = filter_class_diagram(&diagram, &filter).unwrap();
+
+        let ids: Vec<&str> = filtered.classes.iter().map(|c| c.id.as_str()).collect();
+        assert!(!ids.contains(&"OrderTest"));
+        assert!(ids.contains(&"Customer"));
+        assert_eq!(filtered.relationships.len(), 2);
+        assert_eq!(filtered.namespaces[0].class_ids, vec!["Order"]);
+        assert!(filtered.notes.is_empty());
+
+        let filter = RenderFilter {
+            include: vec!["Order*".to_string()],
+            ..Default::default()
+        };
+        let filtered = filter_class_diagram(&diagram, &filter).unwrap();
+        assert_eq!(filtered.classes.len(), 2);
+        assert_eq!(filtered.relationships.len(), 1);
+
+        let filter = RenderFilter {
+            only_relations: vec!["friendship".to_string()],
+            ..Default::default()
+        };
+        let error = filter_class_diagram(&diagram, &filter).unwrap_err();
+        assert_eq!(error, "Unknown class relationship type: friendship");
+    }
+
+    #[test]
+    fn test_er_names_and_relations() {
+        let DiagramType::Er(diagram) = parse_mermaid(
+            "erDiagram
+  CUSTOMER ||--o{ ORDER : places
+  ORDER ||--|{ LINE_ITEM : contains
+  ORDER }o..o| AUDIT_LOG : logs",
+        )
+        .unwrap()
+        .diagram
+        else {
+            panic!("expected ER diagram")
+        };
+        let filter = RenderFilter {
+            exclude: vec!["AUDIT_?OG".to_string()],
+            only_relations: vec!["identifying".to_string()],
+            ..Default::default()
+        };
+        let filtered = filter_er_diagram(&diagram, &filter).unwrap();
+
+        assert_eq!(filtered.entities.len(), 3);
+        assert_eq!(filtered.relationships.len(), 2);
+    }
+
+    #[test]
+    fn test_filters_must_suit_the_diagram() {
+        let parsed = parse_mermaid(MODEL).unwrap();
+        let filter = RenderFilter {
+            focus_node: Some("Order".to_string()),
+            ..Default::default()
+        };
+        let error = filter.apply(&parsed).unwrap_err();
+        assert_eq!(
+            error,
+            "Only flowcharts can be filtered to a subgraph or node"
+        );
+
+        let parsed = parse_mermaid(ARCHITECTURE).unwrap();
+        let filter = RenderFilter {
+            exclude: vec!["DB".to_string()],
+            ..Default::default()
+        };
+        let error = filter.apply(&parsed).unwrap_err();
+        assert_eq!(
+            error,
+            "Only class and ER diagrams can be filtered by name or relationship"
+        );
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*Test*", "OrderTest"));
+        assert!(glob_match("*Test*", "TestSuite"));
+        assert!(glob_match("a*b*c", "aXbYbc"));
+        assert!(glob_match("?rder", "Order"));
+        assert!(!glob_match("*Test", "Testing"));
+        assert!(!glob_match("Order", "Orders"));
+    }
+}
@@ -0,0 +1,40 @@
+//! Example diagrams embedded in the binary (`gallery` feature)
+//!
+//! The sources live in `gallery/` at the crate root and are compiled in with
+//! `include_str!`, so `m2svg --example NAME` works without any files around.
+
+/// Embedded examples as (name, Mermaid source), in display order
+pub const EXAMPLES: &[(&str, &str)] = &[
+    ("flowchart", include_str!("../gallery/flowchart.mmd")),
+    ("sequence", include_str!("../gallery/sequence.mmd")),
+    ("class", include_str!("../gallery/class.mmd")),
+    ("er", include_str!("../gallery/er.mmd")),
+    ("gitgraph", include_str!("../gallery/gitgraph.mmd")),
+];
+
+/// Mermaid source of the named example
+///
+/// # Example
+/// ```rust
+/// let source = m2svg::gallery::example("flowchart").unwrap();
+/// assert!(m2svg::render(source, false).is_ok());
+/// ```
+pub fn example(name: &str) -> Option<&'static str> {
+    EXAMPLES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, source)| *source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_example_renders() {
+        for (name, source) in EXAMPLES {
+            crate::render(source, false).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            crate::render_to_svg(source).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        }
+    }
+}
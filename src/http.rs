@@ -0,0 +1,120 @@
+//! HTTP rendering service (the `http` feature)
+//!
+//! `m2svg --http ADDR` listens on `ADDR` and renders the Mermaid source posted
+//! to `/render`, so CI jobs and wikis can run m2svg as a sidecar. The query
+//! string picks the output: `format` is `svg` (the default), `unicode` or
+//! `ascii`, and `theme` is `default` or `dark` for SVG. Errors come back as
+//! `400 Bad Request` with the message as plain text.
+//!
+//! ```bash
+//! curl --data-binary @diagram.mmd 'http://127.0.0.1:8080/render?format=svg&theme=dark'
+//! ```
+
+use std::io::Read;
+
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::{AsciiRenderOptions, MermaidTheme};
+
+/// Largest request body accepted, in bytes
+const MAX_BODY: u64 = 1 << 20;
+
+/// Serve render requests on `addr` (e.g. `127.0.0.1:8080`) until the process
+/// is stopped. Requests are answered one at a time.
+pub fn serve_http(addr: &str) -> Result<(), String> {
+    let server = Server::http(addr).map_err(|e| format!("Cannot listen on {}: {}", addr, e))?;
+    for mut request in server.incoming_requests() {
+        let mut body = String::new();
+        let read = request
+            .as_reader()
+            .take(MAX_BODY + 1)
+            .read_to_string(&mut body);
+        let (status, content_type, text) = match read {
+            Err(_) => (400, "text/plain", "Request body is not UTF-8".to_string()),
+            Ok(n) if n as u64 > MAX_BODY => {
+                (413, "text/plain", "Request body is too large".to_string())
+            }
+            Ok(_) => handle(request.method(), request.url(), &body),
+        };
+        let header = Header::from_bytes(
+            "Content-Type",
+            format!("{}; charset=utf-8", content_type).as_bytes(),
+        )
+        .expect("valid header");
+        let response = Response::from_string(text)
+            .with_status_code(status)
+            .with_header(header);
+        // The client may have gone; keep serving the others
+        let _ = request.respond(response);
+    }
+    Ok(())
+}
+
+/// Status code, content type and body of the response to a request
+fn handle(method: &Method, url: &str, body: &str) -> (u16, &'static str, String) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    if path != "/render" {
+        return (404, "text/plain", format!("Not found: {}", path));
+    }
+    if *method != Method::Post {
+        return (405, "text/plain", "Use POST /render".to_string());
+    }
+    match render(query, body) {
+        Ok((content_type, output)) => (200, content_type, output),
+        Err(e) => (400, "text/plain", e),
+    }
+}
+
+fn render(query: &str, body: &str) -> Result<(&'static str, String), String> {
+    let mut format = "svg";
+    let mut theme = None;
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        match name {
+            "format" => {
+                format = match value {
+                    "svg" | "unicode" | "ascii" => value,
+                    _ => {
+                        return Err(format!(
+                            "Unknown format: {} (expected svg, unicode or ascii)",
+                            value
+                        ))
+                    }
+                }
+            }
+            "theme" => {
+                theme = match value {
+                    "default" => Some(MermaidTheme::Default),
+                    "dark" => Some(MermaidTheme::Dark),
+                    _ => {
+                        return Err(format!(
+                            "Unknown theme: {} (expected default or dark)",
+                            value
+                        ))
+                    }
+                }
+            }
+            _ => return Err(format!("Unknown query parameter: {}", name)),
+        }
+    }
+
+    let mut parsed = crate::parse_mermaid(body)?;
+    match format {
+        "svg" => {
+            // The query overrides a theme set in the diagram's frontmatter
+            if let Some(theme) = theme {
+                parsed.frontmatter.theme = theme;
+            }
+            let svg = crate::try_render_parsed_svg(&parsed, None)?;
+            Ok(("image/svg+xml", svg))
+        }
+        _ => {
+            let options = AsciiRenderOptions {
+                use_ascii: format == "ascii",
+                ..Default::default()
+            };
+            let output = crate::render_parsed_ascii(&parsed, Some(options))?;
+            Ok(("text/plain", output))
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Render options as JSON, for the language bindings and the server
+//!
+//! The `wasm` and `ffi` bindings and `m2svg serve` take their options as one
+//! JSON object with the camelCase names of the render option fields, e.g.
+//! `{"useAscii": false, "paddingX": 2}`, and the charset by name, e.g.
+//! `{"charset": "safe"}`. The [`RenderLimits`] fields sit next to them, e.g.
+//! `{"maxNodes": 500}`, and the [`NodeWidth`], [`ClassMembers`] and
+//! [`SequenceConfig`] fields are objects, e.g. `{"nodeWidth": {"uniform":
+//! true}}`, `{"classMembers": {"max": 10, "hidePrivate": true}}` and
+//! `{"sequence": {"mirrorActors": false}}`. The SVG size is one of
+//! `{"responsive": true}`, `{"width": 600}` (and/or `height`) and
+//! `{"maxWidth": 800}`, and `{"systemFonts": true}` falls back to fonts every
+//! system has instead of importing one. An empty string means the defaults,
+//! and unknown fields are an error.
+
+use serde::Deserialize;
+
+use crate::svg::{FontSource, SvgSize};
+use crate::{
+    AsciiRenderOptions, Charset, ClassMembers, CurveStyle, EdgeRouting, LabelAlign, NodeWidth,
+    RenderLimits, SequenceConfig, SvgRenderOptions,
+};
+
+/// [`AsciiRenderOptions`] with camelCase field names, and the charset and
+/// label alignment by name
+#[derive(Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+struct AsciiOptionsJson {
+    use_ascii: bool,
+    charset: Option<String>,
+    rounded_corners: bool,
+    padding_x: usize,
+    padding_y: usize,
+    box_border_padding: usize,
+    sequence_header_every: usize,
+    sequence_max_gap: usize,
+    max_label_width: usize,
+    wrap_width: usize,
+    node_width: NodeWidthJson,
+    label_align: Option<String>,
+    max_width: usize,
+    max_nodes: Option<usize>,
+    max_edges: Option<usize>,
+    max_canvas_cells: Option<usize>,
+    class_members: ClassMembersJson,
+}
+
+impl Default for AsciiOptionsJson {
+    fn default() -> Self {
+        let opts = AsciiRenderOptions::default();
+        Self {
+            use_ascii: opts.use_ascii,
+            charset: None,
+            rounded_corners: opts.rounded_corners,
+            padding_x: opts.padding_x,
+            padding_y: opts.padding_y,
+            box_border_padding: opts.box_border_padding,
+            sequence_header_every: opts.sequence_header_every,
+            sequence_max_gap: opts.sequence_max_gap,
+            max_label_width: opts.max_label_width,
+            wrap_width: opts.wrap_width,
+            node_width: NodeWidthJson::default(),
+            label_align: None,
+            max_width: opts.max_width,
+            max_nodes: None,
+            max_edges: None,
+            max_canvas_cells: None,
+            class_members: ClassMembersJson::default(),
+        }
+    }
+}
+
+/// [`SvgRenderOptions`] with camelCase field names, and the curve, routing and
+/// label alignment by name
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+struct SvgOptionsJson {
+    curve: Option<String>,
+    routing: Option<String>,
+    max_label_width: usize,
+    wrap_width: usize,
+    node_width: NodeWidthJson,
+    label_align: Option<String>,
+    class_members: ClassMembersJson,
+    fonts: Vec<String>,
+    system_fonts: bool,
+    sequence: SequenceJson,
+    fragment_namespace: Option<String>,
+    width: Option<f64>,
+    height: Option<f64>,
+    max_width: Option<f64>,
+    responsive: bool,
+    prefers_dark: bool,
+    max_nodes: Option<usize>,
+    max_edges: Option<usize>,
+    max_svg_bytes: Option<usize>,
+}
+
+/// [`SequenceConfig`], the `sequence` object
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+struct SequenceJson {
+    actor_margin: Option<f64>,
+    message_margin: Option<f64>,
+    box_margin: Option<f64>,
+    mirror_actors: Option<bool>,
+}
+
+impl From<SequenceJson> for SequenceConfig {
+    fn from(json: SequenceJson) -> Self {
+        Self {
+            actor_margin: json.actor_margin,
+            message_margin: json.message_margin,
+            box_margin: json.box_margin,
+            mirror_actors: json.mirror_actors,
+        }
+    }
+}
+
+/// [`NodeWidth`], the `nodeWidth` object
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct NodeWidthJson {
+    uniform: bool,
+    min: usize,
+    max: usize,
+}
+
+impl From<NodeWidthJson> for NodeWidth {
+    fn from(json: NodeWidthJson) -> Self {
+        Self {
+            uniform: json.uniform,
+            min: json.min,
+            max: json.max,
+        }
+    }
+}
+
+/// [`ClassMembers`], the `classMembers` object
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+struct ClassMembersJson {
+    max: usize,
+    hide_private: bool,
+}
+
+impl From<ClassMembersJson> for ClassMembers {
+    fn from(json: ClassMembersJson) -> Self {
+        Self {
+            max: json.max,
+            hide_private: json.hide_private,
+        }
+    }
+}
+
+/// A label alignment by name
+fn label_align(name: Option<String>) -> Result<Option<LabelAlign>, String> {
+    name.map(|name| {
+        LabelAlign::from_name(&name)
+            .ok_or_else(|| format!("Invalid options: unknown labelAlign: {}", name))
+    })
+    .transpose()
+}
+
+/// Parse an options object, treating an empty string as `{}`
+fn parse_options<T: Default + for<'de> Deserialize<'de>>(json: &str) -> Result<T, String> {
+    if json.trim().is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_str(json).map_err(|e| format!("Invalid options: {}", e))
+}
+
+/// Text render options from a JSON object
+pub(crate) fn ascii_options(json: &str) -> Result<AsciiRenderOptions, String> {
+    let opts: AsciiOptionsJson = parse_options(json)?;
+    let charset = opts
+        .charset
+        .map(|name| {
+            Charset::from_name(&name)
+                .ok_or_else(|| format!("Invalid options: unknown charset: {}", name))
+        })
+        .transpose()?;
+    let defaults = RenderLimits::default();
+    Ok(AsciiRenderOptions {
+        use_ascii: opts.use_ascii,
+        charset,
+        rounded_corners: opts.rounded_corners,
+        padding_x: opts.padding_x,
+        padding_y: opts.padding_y,
+        box_border_padding: opts.box_border_padding,
+        sequence_header_every: opts.sequence_header_every,
+        sequence_max_gap: opts.sequence_max_gap,
+        max_label_width: opts.max_label_width,
+        wrap_width: opts.wrap_width,
+        node_width: opts.node_width.into(),
+        label_align: label_align(opts.label_align)?,
+        max_width: opts.max_width,
+        limits: RenderLimits {
+            max_nodes: opts.max_nodes.unwrap_or(defaults.max_nodes),
+            max_edges: opts.max_edges.unwrap_or(defaults.max_edges),
+            max_canvas_cells: opts.max_canvas_cells.unwrap_or(defaults.max_canvas_cells),
+            ..defaults
+        },
+        class_members: opts.class_members.into(),
+    })
+}
+
+/// SVG render options from a JSON object
+pub(crate) fn svg_options(json: &str) -> Result<SvgRenderOptions, String> {
+    let opts: SvgOptionsJson = parse_options(json)?;
+    let curve = match opts.curve {
+        Some(name) => CurveStyle::from_name(&name)
+            .ok_or_else(|| format!("Invalid options: unknown curve: {}", name))?,
+        None => CurveStyle::default(),
+    };
+    let routing = match opts.routing {
+        Some(name) => EdgeRouting::from_name(&name)
+            .ok_or_else(|| format!("Invalid options: unknown routing: {}", name))?,
+        None => EdgeRouting::default(),
+    };
+    let sizes = [
+        ("width", opts.width),
+        ("height", opts.height),
+        ("maxWidth", opts.max_width),
+    ];
+    for (name, px) in sizes {
+        if px.is_some_and(|px| !px.is_finite() || px <= 0.0) {
+            return Err(format!(
+                "Invalid options: {} must be a positive number",
+                name
+            ));
+        }
+    }
+    let size = match (opts.responsive, opts.width, opts.height, opts.max_width) {
+        (false, None, None, None) => SvgSize::Natural,
+        (true, None, None, None) => SvgSize::Responsive,
+        (false, width, height, None) => SvgSize::Fixed { width, height },
+        (false, None, None, Some(max)) => SvgSize::MaxWidth(max),
+        _ => {
+            return Err(
+                "Invalid options: set only one of responsive, width/height and maxWidth"
+                    .to_string(),
+            )
+        }
+    };
+    let margins = [
+        opts.sequence.actor_margin,
+        opts.sequence.message_margin,
+        opts.sequence.box_margin,
+    ];
+    if margins.into_iter().flatten().any(|m| m < 0.0) {
+        return Err("Invalid options: sequence margins can't be negative".to_string());
+    }
+    let defaults = RenderLimits::default();
+    let limits = RenderLimits {
+        max_nodes: opts.max_nodes.unwrap_or(defaults.max_nodes),
+        max_edges: opts.max_edges.unwrap_or(defaults.max_edges),
+        max_svg_bytes: opts.max_svg_bytes.unwrap_or(defaults.max_svg_bytes),
+        ..defaults
+    };
+    Ok(SvgRenderOptions {
+        curve,
+        routing,
+        max_label_width: opts.max_label_width,
+        wrap_width: opts.wrap_width,
+        node_width: opts.node_width.into(),
+        label_align: label_align(opts.label_align)?,
+        class_members: opts.class_members.into(),
+        fonts: opts.fonts,
+        limits,
+        fragment_namespace: opts.fragment_namespace,
+        size,
+        font_source: if opts.system_fonts {
+            FontSource::System
+        } else {
+            FontSource::Web
+        },
+        sequence: opts.sequence.into(),
+        prefers_dark: opts.prefers_dark,
+        ..Default::default()
+    })
+}
@@ -24,13 +24,57 @@
 //! - ER diagrams (erDiagram)
 
 pub mod ascii;
+pub mod batch;
+pub mod class_members;
+pub mod describe;
+pub mod emit;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+#[cfg(feature = "gallery")]
+pub mod gallery;
+#[cfg(feature = "http")]
+pub mod http;
+mod json_options;
+pub mod limits;
+pub mod merge;
+pub mod node_width;
 pub mod parser;
+pub mod project;
+mod render_cache;
+pub mod server;
+pub mod session;
 pub mod svg;
+pub mod truncate;
 pub mod types;
+pub mod unsupported;
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wrap;
 
-pub use ascii::render_mermaid_ascii;
-pub use parser::parse_mermaid;
+pub use ascii::{render_mermaid_ascii, render_mermaid_ascii_with_warnings, render_parsed_ascii};
+pub use class_members::ClassMembers;
+pub use emit::to_mermaid;
+pub use filter::{filter_class_diagram, filter_er_diagram, filter_flowchart, RenderFilter};
+pub use limits::{RenderLimits, TooLarge};
+pub use merge::{merge, merge_namespaced, merge_with, prefix_ids, IdConflict, MergeOptions};
+pub use node_width::NodeWidth;
+#[cfg(feature = "d2")]
+pub use parser::d2::parse_d2;
+#[cfg(feature = "dot")]
+pub use parser::dot::parse_dot;
+pub use parser::plantuml::parse_plantuml;
+#[cfg(feature = "rust")]
+pub use parser::rust::parse_rust;
+pub use parser::{detect_diagram_type, parse_mermaid, parse_mermaid_with_mode, ParseMode};
+pub use project::project_participant;
+pub use render_cache::Renderer;
 pub use types::*;
+pub use validate::{validate, Diagnostic, Severity};
+
+use std::borrow::Cow;
+use std::sync::Arc;
 
 /// Render a Mermaid diagram to ASCII/Unicode text.
 ///
@@ -60,35 +104,310 @@ pub fn render(input: &str, use_ascii: bool) -> Result<String, String> {
 /// let svg = m2svg::render_to_svg("graph LR\n  A --> B").unwrap();
 /// ```
 pub fn render_to_svg(input: &str) -> Result<String, String> {
+    render_mermaid_svg(input, None)
+}
+
+/// Render a Mermaid diagram to SVG text with explicit options.
+///
+/// Settings from the diagram's frontmatter (such as `curve:` or `edgeRouting:`) take precedence
+/// over the options.
+///
+/// # Example
+/// ```rust
+/// use m2svg::{CurveStyle, SvgRenderOptions};
+///
+/// let opts = SvgRenderOptions {
+///     curve: CurveStyle::Basis,
+///     ..Default::default()
+/// };
+/// let svg = m2svg::render_mermaid_svg("graph LR\n  A --> B", Some(opts)).unwrap();
+/// ```
+pub fn render_mermaid_svg(
+    input: &str,
+    options: Option<SvgRenderOptions>,
+) -> Result<String, String> {
     let parsed = parse_mermaid(input)?;
-    let colors = svg::DiagramColors::from_theme(parsed.frontmatter.theme);
-    let font = "Inter";
-    let transparent = false;
+    Ok(try_render_parsed_svg(&parsed, options)?)
+}
 
-    let svg_output = match parsed.diagram {
-        DiagramType::Flowchart(graph) => {
-            svg::render_mermaid_to_svg(&graph, &colors, font, transparent)
-        }
-        DiagramType::Sequence(diagram) => {
-            svg::render_sequence_svg(&diagram, &colors, font, transparent)
-        }
-        DiagramType::Class(diagram) => svg::render_class_svg(&diagram, &colors, font, transparent),
-        DiagramType::Er(diagram) => svg::render_er_svg(&diagram, &colors, font, transparent),
-        DiagramType::GitGraph(graph) => {
-            svg::render_gitgraph_svg(&graph, &colors, font, transparent)
+/// Render a Mermaid diagram to SVG text as [`render_mermaid_svg`] does, with a
+/// warning for each statement the output leaves out or gets wrong, as
+/// `m2svg check` reports them (see [`validate()`]).
+///
+/// # Example
+/// ```rust
+/// let text = "graph LR\n  A --> B\n  linkStyle 0 stroke:#f00\n  style C fill:#f00";
+/// let output = m2svg::render_mermaid_svg_with_warnings(text, None).unwrap();
+///
+/// assert!(output.content.starts_with("<svg"));
+/// let warnings: Vec<String> = output.warnings.iter().map(|w| w.to_string()).collect();
+/// assert_eq!(
+///     warnings,
+///     [
+///         "3: warning: Unsupported feature linkStyle: edge styles are ignored",
+///         "4: warning: Undefined node C in style statement",
+///     ]
+/// );
+/// ```
+pub fn render_mermaid_svg_with_warnings(
+    input: &str,
+    options: Option<SvgRenderOptions>,
+) -> Result<RenderOutput, String> {
+    let (parsed, skipped) = parse_mermaid_with_mode(input, ParseMode::Lenient)?;
+    let warnings = validate::check_parsed(input, &parsed, skipped);
+    let content = try_render_parsed_svg(&parsed, options)?;
+    Ok(RenderOutput { content, warnings })
+}
+
+/// Render an already parsed diagram to SVG text.
+///
+/// Together with [`ParsedDiagram::from_json`], this lets other tools build the
+/// diagram model themselves and use m2svg only for layout and rendering.
+///
+/// # Example
+/// ```rust
+/// use m2svg::{parse_mermaid, render_parsed_svg, ParsedDiagram};
+///
+/// let json = parse_mermaid("graph LR\n  A --> B").unwrap().to_json().unwrap();
+/// let parsed = ParsedDiagram::from_json(&json).unwrap();
+/// assert_eq!(
+///     render_parsed_svg(&parsed, None),
+///     m2svg::render_to_svg("graph LR\n  A --> B").unwrap()
+/// );
+/// ```
+///
+/// This doesn't enforce [`SvgRenderOptions::limits`]; see
+/// [`try_render_parsed_svg`] for input that may be too large.
+pub fn render_parsed_svg(parsed: &ParsedDiagram, options: Option<SvgRenderOptions>) -> String {
+    with_svg_measurer(options.unwrap_or_default(), |opts| {
+        let (parsed, legend) = prepare_svg(parsed, opts);
+        let colors = svg::DiagramColors::from_theme(parsed.frontmatter.theme);
+        match render_cache::Layout::new(&parsed, opts, &colors) {
+            Some(layout) => finish_svg(
+                layout.document(&parsed, opts, &colors),
+                &parsed,
+                &legend,
+                opts,
+            ),
+            None => String::new(),
         }
+    })
+}
+
+/// Render an already parsed diagram to SVG text, unless it is larger than
+/// [`SvgRenderOptions::limits`] allows.
+///
+/// The number of nodes and edges is checked before laying the diagram out,
+/// the length of the SVG text after rendering it.
+///
+/// # Example
+/// ```rust
+/// use m2svg::{parse_mermaid, try_render_parsed_svg, RenderLimits, SvgRenderOptions};
+///
+/// let parsed = parse_mermaid("graph LR\n  A --> B").unwrap();
+/// let opts = SvgRenderOptions {
+///     limits: RenderLimits { max_svg_bytes: 100, ..Default::default() },
+///     ..Default::default()
+/// };
+/// let error = try_render_parsed_svg(&parsed, Some(opts)).unwrap_err();
+/// assert!(error.to_string().ends_with("bytes of SVG (limit 100)"));
+/// ```
+pub fn try_render_parsed_svg(
+    parsed: &ParsedDiagram,
+    options: Option<SvgRenderOptions>,
+) -> Result<String, TooLarge> {
+    let opts = options.unwrap_or_default();
+    let limits = opts.limits;
+    limits.check(parsed)?;
+    let svg = render_parsed_svg(parsed, Some(opts));
+    limits.check_svg(&svg)?;
+    Ok(svg)
+}
+
+/// Lay out a flowchart and describe where everything went as JSON, without
+/// rendering it, so other tools can draw it their own way.
+///
+/// The JSON has the `width` and `height` of the diagram, and its `nodes`
+/// (position of the top-left corner and size), `edges` (the `points` they run
+/// through and the center of their label) and subgraph `groups`, all in the
+/// px the SVG would be drawn at. It is laid out as [`render_mermaid_svg`]
+/// would with `options`, with the layout, edge routing and curve of the
+/// frontmatter taking precedence.
+///
+/// # Example
+/// ```rust
+/// use m2svg::svg::PositionedGraph;
+///
+/// let json = m2svg::layout_to_json("graph TD\n  A --> B", None).unwrap();
+/// let layout: PositionedGraph = serde_json::from_str(&json).unwrap();
+///
+/// let (a, b) = (&layout.nodes[0], &layout.nodes[1]);
+/// assert_eq!((a.id.as_str(), b.id.as_str()), ("A", "B"));
+/// assert!(a.y + a.height <= b.y);
+/// assert_eq!(layout.edges[0].points.len(), 2);
+/// ```
+pub fn layout_to_json(input: &str, options: Option<SvgRenderOptions>) -> Result<String, String> {
+    layout_parsed(&parse_mermaid(input)?, options)?.to_json()
+}
+
+/// Lay out an already parsed flowchart, as [`layout_to_json`] does, unless it
+/// is larger than [`SvgRenderOptions::limits`] allows.
+pub fn layout_parsed(
+    parsed: &ParsedDiagram,
+    options: Option<SvgRenderOptions>,
+) -> Result<svg::PositionedGraph, String> {
+    let opts = options.unwrap_or_default();
+    opts.limits.check(parsed)?;
+    with_svg_measurer(opts, |opts| {
+        let (parsed, _) = prepare_svg(parsed, opts);
+        let DiagramType::Flowchart(graph) = &parsed.diagram else {
+            return Err("Only flowcharts can be laid out to JSON".to_string());
+        };
+        let curve = parsed.frontmatter.curve.unwrap_or(opts.curve);
+        let routing = parsed.frontmatter.edge_routing.unwrap_or(opts.routing);
+        Ok(match parsed.frontmatter.layout {
+            FlowchartLayout::Grid => {
+                svg::layout_grid_flowchart(graph, curve).unwrap_or(svg::PositionedGraph {
+                    width: 0.0,
+                    height: 0.0,
+                    nodes: Vec::new(),
+                    edges: Vec::new(),
+                    groups: Vec::new(),
+                })
+            }
+            FlowchartLayout::Layered => svg::layout_flowchart_with_routing(graph, routing),
+        })
+    })
+}
+
+/// Run `f` with the text measurer (taken out of `opts`), node widths, label
+/// alignment and font source of `opts` in place
+fn with_svg_measurer<R>(mut opts: SvgRenderOptions, f: impl FnOnce(&SvgRenderOptions) -> R) -> R {
+    let measurer = opts.measurer.take();
+    // Size text with the first of the fonts that is installed
+    #[cfg(feature = "font-metrics")]
+    let measurer = measurer.or_else(|| {
+        let families: Vec<&str> = opts.fonts.iter().map(String::as_str).collect();
+        svg::FontMetricsMeasurer::from_families(&families)
+            .map(|m| Arc::new(m) as Arc<dyn svg::TextMeasurer>)
+    });
+    let (node_width, label_align) = (opts.node_width, opts.label_align);
+    let web_fonts = matches!(opts.font_source, svg::FontSource::Web);
+    let f = || {
+        svg::styles::with_node_width(node_width, || {
+            svg::styles::with_label_align(label_align, || {
+                svg::styles::with_web_fonts(web_fonts, || f(&opts))
+            })
+        })
     };
+    match measurer {
+        Some(measurer) => svg::styles::with_text_measurer(measurer, f),
+        None => f(),
+    }
+}
 
-    // If title is present, inject it into the SVG
-    if let Some(ref title) = parsed.frontmatter.title {
-        Ok(inject_svg_title(&svg_output, title, &colors))
+/// The diagram with labels cut and wrapped as `opts` asks, and the legend
+/// listing the cut ones
+fn prepare_svg<'a>(
+    parsed: &'a ParsedDiagram,
+    opts: &SvgRenderOptions,
+) -> (Cow<'a, ParsedDiagram>, Vec<String>) {
+    let (parsed, legend) = truncate::truncate_diagram(parsed, opts.max_label_width, "…");
+    let parsed = match class_members::compact_members(&parsed, opts.class_members, "…") {
+        Cow::Owned(compacted) => Cow::Owned(compacted),
+        Cow::Borrowed(_) => parsed,
+    };
+    let parsed = match wrap::wrap_diagram(&parsed, opts.wrap_width) {
+        Cow::Owned(wrapped) => Cow::Owned(wrapped),
+        Cow::Borrowed(_) => parsed,
+    };
+    // Node labels wider than fits the widest box: the grid layout draws text
+    // 8px a column, in a box 4 columns wider; the layered one pads it by 32px
+    let fitted = match parsed.frontmatter.layout {
+        FlowchartLayout::Grid => opts
+            .node_width
+            .text_room(0)
+            .map(|px| wrap::wrap_diagram(&parsed, (px / 8).saturating_sub(4).max(1))),
+        FlowchartLayout::Layered => opts.node_width.text_room(32).map(|px| {
+            wrap::wrap_diagram_with(&parsed, px as f64, |line| {
+                svg::styles::estimate_text_width(
+                    line,
+                    svg::styles::FontSizes::NODE_LABEL,
+                    svg::styles::FontWeights::NODE_LABEL,
+                )
+            })
+        }),
+    };
+    let parsed = match fitted {
+        Some(Cow::Owned(wrapped)) => Cow::Owned(wrapped),
+        _ => parsed,
+    };
+    (parsed, legend)
+}
+
+/// Font families for SVG text
+fn svg_font(opts: &SvgRenderOptions) -> String {
+    if opts.fonts.is_empty() {
+        "Inter".to_string()
     } else {
-        Ok(svg_output)
+        opts.fonts.join(", ")
     }
 }
 
-/// Inject a title `<text>` element into an SVG string, shifting content down.
-fn inject_svg_title(svg: &str, title: &str, colors: &svg::DiagramColors) -> String {
+/// Add the title, legend and accessibility text of `parsed` to its laid out
+/// `document`, as a fragment if `opts` asks for one
+fn finish_svg(
+    mut document: svg::SvgDocument,
+    parsed: &ParsedDiagram,
+    legend: &[String],
+    opts: &SvgRenderOptions,
+) -> String {
+    if let Some(ref title) = parsed.frontmatter.title {
+        add_svg_title(&mut document, title);
+    }
+    if !legend.is_empty() {
+        add_svg_legend(&mut document, &truncate::legend_lines(legend));
+    }
+
+    // `accTitle:` / `accDescr:`, with the frontmatter title as the fallback title
+    document.title = parsed
+        .frontmatter
+        .acc_title
+        .clone()
+        .or_else(|| parsed.frontmatter.title.clone());
+    document.description = parsed.frontmatter.acc_descr.clone();
+    document.size = opts.size;
+    if opts.prefers_dark
+        && opts.fragment_namespace.is_none()
+        && parsed.frontmatter.theme != MermaidTheme::Dark
+    {
+        let dark = svg::DiagramColors::from_theme(MermaidTheme::Dark);
+        document.head.push(svg::dark_scheme_block(&dark));
+    }
+    // Fragments leave fonts to the page they are embedded in
+    let embedded_font = match &opts.font_source {
+        svg::FontSource::Embedded(font) if opts.fragment_namespace.is_none() => Some(font),
+        _ => None,
+    };
+    if let Some(font) = embedded_font {
+        let text = [
+            document.body.as_str(),
+            document.title.as_deref().unwrap_or_default(),
+            document.description.as_deref().unwrap_or_default(),
+        ];
+        let text = svg::fonts::text_content(&text.join("\n"));
+        let font_face = svg::fonts::font_face_block(font, &svg_font(opts), &text);
+        document.head.insert(0, font_face);
+    }
+
+    match &opts.fragment_namespace {
+        Some(namespace) => svg::svg_fragment(&document.to_string(), namespace),
+        None => document.to_string(),
+    }
+}
+
+/// Put a title above the diagram, widening the canvas if the title is wider.
+fn add_svg_title(document: &mut svg::SvgDocument, title: &str) {
     use svg::styles::estimate_text_width;
 
     let title_font_size = 16.0;
@@ -96,84 +415,58 @@ fn inject_svg_title(svg: &str, title: &str, colors: &svg::DiagramColors) -> Stri
     let title_height = 30.0; // Space reserved for title (font size + padding)
     let title_text_width = estimate_text_width(title, title_font_size, title_font_weight);
 
-    // Parse existing viewBox to adjust dimensions
-    if let (Some(vb_start), Some(vb_end)) = (svg.find("viewBox=\""), svg.find("\" width=\"")) {
-        let vb_content_start = vb_start + "viewBox=\"".len();
-        let vb_str = &svg[vb_content_start..vb_end];
-        let parts: Vec<f64> = vb_str
-            .split_whitespace()
-            .filter_map(|s| s.parse::<f64>().ok())
-            .collect();
-
-        if parts.len() == 4 {
-            let (vb_x, vb_y, vb_w, vb_h) = (parts[0], parts[1], parts[2], parts[3]);
-            let new_w = vb_w.max(title_text_width + 40.0);
-            let new_h = vb_h + title_height;
-            let title_x = new_w / 2.0;
-
-            // Build the new viewBox and dimensions
-            let format_dim = |d: f64| -> String {
-                if d.fract() == 0.0 {
-                    format!("{}", d as i64)
-                } else {
-                    let s = format!("{}", d);
-                    s.trim_end_matches('0').trim_end_matches('.').to_string()
-                }
-            };
-
-            // Also update width="..." height="..."
-            let old_dims_start = vb_end + "\" width=\"".len();
-            if let Some(w_end) = svg[old_dims_start..].find('"') {
-                let h_start_search = old_dims_start + w_end + "\" height=\"".len();
-                if let Some(h_end) = svg[h_start_search..].find('"') {
-                    let old_section = &svg[vb_start..h_start_search + h_end + 1];
-                    let new_section = format!(
-                        "viewBox=\"{} {} {} {}\" width=\"{}\" height=\"{}\"",
-                        format_dim(vb_x),
-                        format_dim(vb_y),
-                        format_dim(new_w),
-                        format_dim(new_h),
-                        format_dim(new_w),
-                        format_dim(new_h)
-                    );
-
-                    // Build title text element
-                    let title_elem = format!(
-                        r#"<text x="{}" y="{}" text-anchor="middle" font-size="{}" font-weight="{}" fill="{}">{}</text>"#,
-                        format_dim(title_x),
-                        format_dim(title_height - 8.0),
-                        format_dim(title_font_size),
-                        title_font_weight,
-                        colors.fg,
-                        html_escape(title)
-                    );
-
-                    // Wrap existing content in a <g> with translate to shift it down
-                    let close_tag = "</svg>";
-                    if let Some(close_pos) = svg.rfind(close_tag) {
-                        // Find end of opening <svg ...> tag
-                        if let Some(svg_tag_end) = svg.find('>') {
-                            let before_content = &svg[..svg_tag_end + 1];
-                            let content = &svg[svg_tag_end + 1..close_pos];
-                            let new_before = before_content.replace(old_section, &new_section);
-
-                            return format!(
-                                "{}\n{}\n<g transform=\"translate(0,{})\">{}</g>\n{}",
-                                new_before,
-                                title_elem,
-                                format_dim(title_height),
-                                content,
-                                close_tag
-                            );
-                        }
-                    }
-                }
-            }
-        }
-    }
+    let width = document.width.max(title_text_width + 40.0);
+    let title_elem = format!(
+        r#"<text x="{}" y="{}" text-anchor="middle" font-size="{}" font-weight="{}" fill="var(--fg)">{}</text>"#,
+        format_dim(width / 2.0),
+        format_dim(title_height - 8.0),
+        format_dim(title_font_size),
+        title_font_weight,
+        html_escape(title)
+    );
+    document.add_header(title_height, width, &title_elem);
+}
+
+/// List legend lines under the diagram, growing the canvas to fit.
+fn add_svg_legend(document: &mut svg::SvgDocument, lines: &[String]) {
+    use svg::styles::estimate_text_width;
+
+    let font_size = 11.0;
+    let line_height = 16.0;
+    let margin = 12.0;
 
-    // Fallback: return SVG unchanged if parsing fails
-    svg.to_string()
+    let text_width = lines
+        .iter()
+        .map(|l| estimate_text_width(l, font_size, 400))
+        .fold(0.0, f64::max);
+    let legend: Vec<String> = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            format!(
+                "<text x=\"{}\" y=\"{}\" font-size=\"{}\" fill=\"var(--muted, var(--fg))\">{}</text>",
+                format_dim(margin),
+                format_dim((i + 1) as f64 * line_height),
+                format_dim(font_size),
+                html_escape(line)
+            )
+        })
+        .collect();
+    document.add_footer(
+        lines.len() as f64 * line_height + margin,
+        (text_width + 2.0 * margin).ceil(),
+        &legend.join("\n"),
+    );
+}
+
+/// An SVG dimension without trailing zeros
+fn format_dim(d: f64) -> String {
+    if d.fract() == 0.0 {
+        format!("{}", d as i64)
+    } else {
+        let s = format!("{}", d);
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
 }
 
 /// Escape special HTML characters in text content
@@ -184,26 +477,187 @@ fn html_escape(s: &str) -> String {
         .replace('"', "&quot;")
 }
 
+/// A rendered diagram and what it had to leave out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderOutput {
+    /// The SVG or text diagram
+    pub content: String,
+    /// Statements that were skipped, or drawn other than written, in line order
+    pub warnings: Vec<Diagnostic>,
+}
+
 /// Configuration options for ASCII rendering
 #[derive(Debug, Clone)]
 pub struct AsciiRenderOptions {
     /// true = ASCII chars (+,-,|,>), false = Unicode box-drawing (┌,─,│,►). Default: true
     pub use_ascii: bool,
+    /// Characters to draw with, e.g. [`Charset::SafeUnicode`] for fonts
+    /// without arrowhead glyphs; overrides `use_ascii` when set. Default:
+    /// `None`, as `use_ascii` says
+    pub charset: Option<Charset>,
+    /// Draw rounded, stadium and circle flowchart nodes with rounded corners
+    /// (`╭╮╰╯`) in Unicode output, so they stand apart from rectangles; off,
+    /// all nodes get square corners. Default: true
+    pub rounded_corners: bool,
     /// Horizontal spacing between nodes. Default: 5
     pub padding_x: usize,
     /// Vertical spacing between nodes. Default: 5
     pub padding_y: usize,
     /// Padding inside node boxes. Default: 1
     pub box_border_padding: usize,
+    /// Repeat the sequence diagram participant header every N messages (0 = never). Default: 0
+    pub sequence_header_every: usize,
+    /// Widest gap in columns between adjacent sequence diagram lifelines that
+    /// message labels may open up; longer labels wrap onto more rows (0 = no
+    /// limit). Participant boxes still get the room they need. Default: 0
+    pub sequence_max_gap: usize,
+    /// Widest flowchart label in columns before it is cut with a `…(n)`
+    /// (`...(n)` in ASCII) marker and listed in a legend under the diagram
+    /// (0 = no limit). Default: 0
+    pub max_label_width: usize,
+    /// Widest flowchart node label in columns before it wraps onto more lines
+    /// inside its box (0 = no wrapping). Default: 0
+    pub wrap_width: usize,
+    /// Widths of flowchart node and class boxes, in columns. Default: as wide
+    /// as their text
+    pub node_width: NodeWidth,
+    /// Alignment of the lines of flowchart node labels and of the text of
+    /// class boxes. Default: node labels and class names centered, class
+    /// members on the left
+    pub label_align: Option<LabelAlign>,
+    /// Widest output in columns; wider diagrams are drawn again with
+    /// [`compact`](Self::compact) spacing, and left-right flowcharts top-down,
+    /// until one fits (0 = no limit). Default: 0
+    pub max_width: usize,
+    /// Largest diagram and canvas to render; larger ones fail with a
+    /// [`TooLarge`] message. Default: [`RenderLimits::default`]
+    pub limits: RenderLimits,
+    /// Members listed in class boxes, the overflow of long lists collapsed
+    /// into a `… (k more)` row (`... (k more)` in ASCII). Default: all
+    pub class_members: ClassMembers,
 }
 
 impl Default for AsciiRenderOptions {
     fn default() -> Self {
         Self {
             use_ascii: true,
+            charset: None,
+            rounded_corners: true,
             padding_x: 5,
             padding_y: 5,
             box_border_padding: 1,
+            sequence_header_every: 0,
+            sequence_max_gap: 0,
+            max_label_width: 0,
+            wrap_width: 0,
+            node_width: NodeWidth::default(),
+            label_align: None,
+            max_width: 0,
+            limits: RenderLimits::default(),
+            class_members: ClassMembers::default(),
+        }
+    }
+}
+
+impl AsciiRenderOptions {
+    /// These options with the tightest spacing, for narrow terminals: one
+    /// column and row between nodes, so arrows are as short as they get, and
+    /// no padding between labels and their borders
+    ///
+    /// # Example
+    /// ```rust
+    /// use m2svg::{render_mermaid_ascii, AsciiRenderOptions};
+    ///
+    /// let options = AsciiRenderOptions::default().compact();
+    /// let text = render_mermaid_ascii("graph LR\n  A --> B", Some(options)).unwrap();
+    /// assert_eq!(text.lines().next().unwrap().trim_end(), "+-+ +-+");
+    /// ```
+    pub fn compact(self) -> Self {
+        Self {
+            padding_x: 1,
+            padding_y: 1,
+            box_border_padding: 0,
+            ..self
         }
     }
+
+    /// The characters these options draw with: `charset` if set, else ASCII
+    /// or Unicode as `use_ascii` says
+    ///
+    /// # Example
+    /// ```rust
+    /// use m2svg::{render_mermaid_ascii, AsciiRenderOptions, Charset};
+    ///
+    /// let options = AsciiRenderOptions {
+    ///     charset: Some(Charset::SafeUnicode),
+    ///     ..Default::default()
+    /// };
+    /// let text = render_mermaid_ascii("graph LR\n  A --> B", Some(options)).unwrap();
+    /// assert!(text.contains("│ A ├────>│ B │"));
+    /// ```
+    pub fn glyphs(&self) -> Charset {
+        match (self.charset, self.use_ascii) {
+            (Some(charset), _) => charset,
+            (None, true) => Charset::Ascii,
+            (None, false) => Charset::Unicode,
+        }
+    }
+}
+
+/// Configuration options for SVG rendering
+#[derive(Debug, Clone, Default)]
+pub struct SvgRenderOptions {
+    /// Shape of flowchart edges, unless the diagram sets `curve:`. Default: linear
+    pub curve: CurveStyle,
+    /// Edge routing of layered flowcharts, unless the diagram sets
+    /// `edgeRouting:`. Default: polyline
+    pub routing: EdgeRouting,
+    /// Widest flowchart label in columns before it is cut with a `…(n)`
+    /// marker and listed in a legend under the diagram (0 = no limit). Default: 0
+    pub max_label_width: usize,
+    /// Widest flowchart node label in characters before it wraps onto more
+    /// lines inside its box (0 = no wrapping). Default: 0
+    pub wrap_width: usize,
+    /// Widths of flowchart node and class boxes, in px. Default: as wide as
+    /// their text
+    pub node_width: NodeWidth,
+    /// Alignment of the lines of flowchart node labels and of the text of
+    /// class boxes. Default: node labels and class names centered, class
+    /// members on the left
+    pub label_align: Option<LabelAlign>,
+    /// Members listed in class boxes, the overflow of long lists collapsed
+    /// into a `… (k more)` row. Default: all
+    pub class_members: ClassMembers,
+    /// Measures label text for sizing; with the `font-metrics` feature,
+    /// `svg::FontMetricsMeasurer` uses a real font's metrics. Default: the
+    /// Inter heuristic (`svg::HeuristicMeasurer`)
+    pub measurer: Option<Arc<dyn svg::TextMeasurer>>,
+    /// Font families for text, in order of preference; `system-ui` and
+    /// `sans-serif` follow as the last resort. With the `font-metrics` feature
+    /// and no `measurer`, text is sized with the first of them installed.
+    /// Default: Inter
+    pub fonts: Vec<String>,
+    /// Largest diagram and SVG text that [`render_mermaid_svg`] and
+    /// [`try_render_parsed_svg`] render; larger ones fail with a [`TooLarge`]
+    /// error. Default: [`RenderLimits::default`]
+    pub limits: RenderLimits,
+    /// Render an SVG fragment to embed in a page next to other diagrams, its
+    /// ids prefixed with this namespace and its styles inlined as attributes
+    /// (see [`svg::svg_fragment`]). Default: none, a standalone document
+    pub fragment_namespace: Option<String>,
+    /// Size the SVG is displayed at: natural, responsive, fixed or at most
+    /// some width. Default: [`svg::SvgSize::Natural`], the size of the layout
+    pub size: svg::SvgSize,
+    /// Where text gets its font from: imported from Google Fonts, fonts
+    /// every system has, or a font file embedded in the SVG (except in
+    /// fragments). Default: [`svg::FontSource::Web`]
+    pub font_source: svg::FontSource,
+    /// Spacing of sequence diagrams, for the values their frontmatter config
+    /// leaves unset. Default: [`SequenceConfig::default`], the built-in
+    /// spacing with participants repeated at the bottom
+    pub sequence: SequenceConfig,
+    /// Also give the SVG the dark theme's colors, for readers whose system
+    /// prefers a dark color scheme (an `@media (prefers-color-scheme: dark)`
+    /// block). Fragments keep one palette. Default: false
+    pub prefers_dark: bool,
 }
@@ -0,0 +1,137 @@
+//! Size limits for rendering untrusted input
+//!
+//! A diagram with a huge number of nodes, or with a few very long labels, can
+//! make the text renderers allocate canvases of billions of cells and the SVG
+//! renderer write hundreds of megabytes. [`RenderLimits`] caps both; the
+//! fallible render functions stop with a [`TooLarge`] error instead.
+
+use std::fmt;
+
+use crate::types::{DiagramType, ParsedDiagram};
+
+/// Largest diagram a render may produce. A limit of 0 means no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderLimits {
+    /// Most nodes, participants, classes, entities or commits. Default: 10000
+    pub max_nodes: usize,
+    /// Most edges, messages, relationships or commit parent links. Default: 50000
+    pub max_edges: usize,
+    /// Most cells (columns times rows) of a text canvas. Default: 25000000
+    pub max_canvas_cells: usize,
+    /// Longest SVG output in bytes. Default: 64 MiB
+    pub max_svg_bytes: usize,
+}
+
+impl Default for RenderLimits {
+    fn default() -> Self {
+        Self {
+            max_nodes: 10_000,
+            max_edges: 50_000,
+            max_canvas_cells: 25_000_000,
+            max_svg_bytes: 64 << 20,
+        }
+    }
+}
+
+/// The limit a [`TooLarge`] diagram went over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Nodes,
+    Edges,
+    CanvasCells,
+    SvgBytes,
+}
+
+impl Limit {
+    /// What the limit counts, as in "12000 nodes"
+    fn unit(self) -> &'static str {
+        match self {
+            Limit::Nodes => "nodes",
+            Limit::Edges => "edges",
+            Limit::CanvasCells => "canvas cells",
+            Limit::SvgBytes => "bytes of SVG",
+        }
+    }
+}
+
+/// A diagram over one of the [`RenderLimits`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooLarge {
+    pub limit: Limit,
+    /// How many the diagram has or would need
+    pub size: usize,
+    /// The limit it went over
+    pub max: usize,
+}
+
+impl fmt::Display for TooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Diagram too large: {} {} (limit {})",
+            self.size,
+            self.limit.unit(),
+            self.max
+        )
+    }
+}
+
+impl std::error::Error for TooLarge {}
+
+impl From<TooLarge> for String {
+    fn from(e: TooLarge) -> Self {
+        e.to_string()
+    }
+}
+
+impl RenderLimits {
+    /// No limits at all
+    pub const UNLIMITED: Self = Self {
+        max_nodes: 0,
+        max_edges: 0,
+        max_canvas_cells: 0,
+        max_svg_bytes: 0,
+    };
+
+    /// Check the number of nodes and edges of `parsed`, before laying it out
+    ///
+    /// # Example
+    /// ```rust
+    /// use m2svg::limits::{Limit, RenderLimits};
+    ///
+    /// let parsed = m2svg::parse_mermaid("graph LR\n  A --> B --> C").unwrap();
+    /// let limits = RenderLimits { max_nodes: 2, ..Default::default() };
+    ///
+    /// let error = limits.check(&parsed).unwrap_err();
+    /// assert_eq!((error.limit, error.size, error.max), (Limit::Nodes, 3, 2));
+    /// assert!(RenderLimits::default().check(&parsed).is_ok());
+    /// ```
+    pub fn check(&self, parsed: &ParsedDiagram) -> Result<(), TooLarge> {
+        let (nodes, edges) = match &parsed.diagram {
+            DiagramType::Flowchart(graph) => (graph.nodes.len(), graph.edges.len()),
+            DiagramType::Sequence(diagram) => (diagram.actors.len(), diagram.messages.len()),
+            DiagramType::Class(diagram) => (diagram.classes.len(), diagram.relationships.len()),
+            DiagramType::Er(diagram) => (diagram.entities.len(), diagram.relationships.len()),
+            DiagramType::GitGraph(graph) => (
+                graph.commits.len(),
+                graph.commits.iter().map(|c| c.parent_ids.len()).sum(),
+            ),
+        };
+        Self::within(Limit::Nodes, nodes, self.max_nodes)?;
+        Self::within(Limit::Edges, edges, self.max_edges)
+    }
+
+    /// Check the length of rendered SVG text
+    pub fn check_svg(&self, svg: &str) -> Result<(), TooLarge> {
+        Self::within(Limit::SvgBytes, svg.len(), self.max_svg_bytes)
+    }
+
+    /// `Err` if `size` is over a `max` other than 0
+    pub(crate) fn within(limit: Limit, size: usize, max: usize) -> Result<(), TooLarge> {
+        if max != 0 && size > max {
+            Err(TooLarge { limit, size, max })
+        } else {
+            Ok(())
+        }
+    }
+}
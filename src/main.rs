@@ -1,87 +1,1392 @@
-use m2svg::{render_mermaid_ascii, render_to_svg, AsciiRenderOptions};
+use m2svg::ascii::color::Background;
+use m2svg::svg::{FontSource, SvgSize};
+use m2svg::{
+    layout_parsed, parse_mermaid, parse_plantuml, render_parsed_ascii, try_render_parsed_svg,
+    AsciiRenderOptions, Charset, ClassMembers, CurveStyle, Diagnostic, DiagramType, EdgeRouting,
+    LabelAlign, MermaidTheme, NodeWidth, ParsedDiagram, RenderFilter, RenderLimits, SequenceConfig,
+    Severity, SvgRenderOptions,
+};
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// What to do when text output is larger than the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Overflow {
+    /// Print the required width on stderr
+    Hint,
+    /// Re-render with tighter spacing
+    Compact,
+    /// Send the output through `$PAGER`
+    Pager,
+    /// Print as-is
+    Ignore,
+}
+
+/// Flags that take their value as the next argument, which is then not an
+/// input file
+const FLAGS_WITH_VALUES: &[&str] = &[
+    "--from",
+    "--charset",
+    "--example",
+    "--font",
+    "--font-file",
+    "--embed-font",
+    "--fragment",
+    "--out-dir",
+    "--max-width",
+    "--focus",
+    "--depth",
+    "--subgraph",
+    "--header-every",
+    "--max-lifeline-gap",
+    "--max-label-width",
+    "--wrap-width",
+    "--max-members",
+    "--max-nodes",
+    "--max-edges",
+    "--max-canvas-cells",
+    "--max-svg-bytes",
+    "--min-node-width",
+    "--max-node-width",
+    "--include",
+    "--exclude",
+    "--only-relations",
+];
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
     if args.iter().any(|a| a == "-h" || a == "--help") {
-        println!("m2svg - Convert Mermaid diagrams to ASCII art or SVG");
-        println!();
-        println!("Usage: m2svg [OPTIONS] [INPUT]");
-        println!();
-        println!("Reads Mermaid diagram from argument or stdin and outputs ASCII art or SVG.");
-        println!();
-        println!("Options:");
-        println!("  -h, --help     Show this help message");
-        println!("  -a, --ascii    Use plain ASCII characters (default: Unicode)");
-        println!("  -s, --svg      Output SVG instead of ASCII");
-        println!();
-        println!("Examples:");
-        println!("  echo 'graph LR\\n  A --> B' | m2svg");
-        println!("  m2svg 'graph LR\\n  A --> B'");
-        println!("  m2svg --svg 'graph TD\\n  A --> B' > diagram.svg");
+        print_help();
+        return;
+    }
+    if args.iter().any(|a| a == "--emit-schema") {
+        println!("{}", ParsedDiagram::json_schema());
+        return;
+    }
+    #[cfg(feature = "gallery")]
+    if args.iter().any(|a| a == "--list-examples") {
+        for (name, _) in m2svg::gallery::EXAMPLES {
+            println!("{}", name);
+        }
         return;
     }
 
-    let use_ascii = args.iter().any(|a| a == "-a" || a == "--ascii");
-    let use_svg = args.iter().any(|a| a == "-s" || a == "--svg");
+    let result = match args.get(1).map(String::as_str) {
+        Some("serve") => serve(),
+        #[cfg(unix)]
+        Some("daemon") => daemon(&args),
+        #[cfg(feature = "http")]
+        _ if option_value(&args, "--http").is_some() => serve_http(&args),
+        Some("check") => check(&args),
+        Some(command) => Options::parse(&args).and_then(|options| match command {
+            "batch" => batch(&args, &options),
+            "picture" => picture(&args, &options),
+            "describe" => describe(&args, &options),
+            "from-rust" => from_rust(&args, &options),
+            _ => render(&args, &options),
+        }),
+        None => Options::parse(&args).and_then(|options| render(&args, &options)),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
 
-    // Get input from argument or stdin
-    let input: String = args
-        .iter()
-        .skip(1)
-        .find(|a| !a.starts_with('-'))
-        .cloned()
-        .map(|s| {
-            // Check if it's a file path (either "-" for stdin, or an existing file)
-            if s == "-" {
-                let mut buf = String::new();
-                io::stdin()
-                    .read_to_string(&mut buf)
-                    .expect("Failed to read from stdin");
-                buf
-            } else if Path::new(&s).exists() {
-                fs::read_to_string(&s).unwrap_or_else(|_| panic!("Failed to read file: {}", s))
+/// Print the command line help
+fn print_help() {
+    println!("m2svg - Convert Mermaid diagrams to ASCII art or SVG");
+    println!();
+    println!("Usage: m2svg [OPTIONS] [INPUT]");
+    println!("       m2svg describe [INPUT]");
+    println!("       m2svg check [INPUT]");
+    println!("       m2svg batch [OPTIONS] [--out-dir DIR] FILES...");
+    println!("       m2svg picture [OPTIONS] [--out-dir DIR] FILE");
+    #[cfg(feature = "rust")]
+    println!("       m2svg from-rust [OPTIONS] [PATH]");
+    println!("       m2svg serve");
+    #[cfg(unix)]
+    println!("       m2svg daemon --socket PATH");
+    println!();
+    println!("Reads Mermaid diagram from argument or stdin and outputs ASCII art or SVG.");
+    println!("`describe` prints a plain-language description instead, e.g. for alt text.");
+    println!("`check` reports likely mistakes without rendering, and exits 1 if it finds any.");
+    println!("`batch` renders each file to FILE.svg (with -s) or FILE.txt and prints the");
+    println!("time each took; with the parallel feature the files render concurrently.");
+    println!("`picture` renders FILE-light.svg and FILE-dark.svg and prints the Markdown");
+    println!("<picture> element that shows the one matching GitHub's color mode.");
+    #[cfg(feature = "rust")]
+    {
+        println!("`from-rust` renders a class diagram of the structs, enums and traits in the");
+        println!("Rust files under PATH (default: the current directory).");
+    }
+    println!("`serve` answers JSON render requests on stdin until it is closed.");
+    #[cfg(unix)]
+    println!("`daemon` answers the same requests from any number of clients on a Unix socket.");
+    println!();
+    println!("Options:");
+    println!("  -h, --help     Show this help message");
+    println!("  -a, --ascii    Use plain ASCII characters (default: Unicode)");
+    println!("  -u, --unicode  Use Unicode even on a legacy Windows console");
+    println!("  --charset=SET  Characters of text output: unicode, safe (box drawing with");
+    println!("                 ASCII arrowheads, for fonts without ► or △) or ascii");
+    println!("  --square-corners  Draw rounded and stadium nodes with square corners too");
+    println!("  -s, --svg      Output SVG instead of ASCII");
+    println!("  --emit-json    Print the parsed diagram model as JSON instead of rendering");
+    println!("  --emit-layout  Print the node positions and edge routes of a flowchart, as the");
+    println!("                 SVG would place them, as JSON instead of rendering");
+    println!("  --from FORMAT  Input format: mermaid (default), json (from --emit-json) or");
+    println!("                 plantuml (sequence and class diagrams)");
+    #[cfg(feature = "dot")]
+    println!("                 dot (Graphviz graphs, with the dot feature)");
+    #[cfg(feature = "d2")]
+    println!("                 d2 (D2 diagrams, with the d2 feature)");
+    println!("  --emit-schema  Print the JSON Schema of the --emit-json output and exit");
+    #[cfg(feature = "gallery")]
+    {
+        println!("  --example NAME    Render a built-in example diagram instead of the input");
+        println!("  --list-examples   List the built-in example diagrams and exit");
+    }
+    println!("  --curve=STYLE     SVG flowchart edges: linear (default), basis or stepAfter");
+    println!("  --routing=MODE    Layered SVG flowchart edges: polyline (default) or orthogonal");
+    println!("  --font=FAMILIES   SVG font families, comma-separated in order of preference");
+    #[cfg(feature = "font-metrics")]
+    println!("  --font-file=PATH  Size SVG text with the metrics of this .ttf/.otf font");
+    println!("  --system-fonts    Fall back to fonts every system has instead of importing");
+    println!("                    the SVG font from Google Fonts");
+    println!("  --embed-font=PATH Embed this .ttf/.otf/.woff/.woff2 font in the SVG as the");
+    #[cfg(feature = "font-embed")]
+    println!("                    first --font family, cut down to the glyphs it uses");
+    #[cfg(not(feature = "font-embed"))]
+    println!("                    first --font family");
+    println!("  --fragment=NS     Print an SVG fragment to embed in a page: no XML prolog,");
+    println!("                    ids prefixed with NS and styles inlined as attributes");
+    println!("  --svg-width=PX, --svg-height=PX  Display the SVG at this size, keeping its");
+    println!("                    proportions (one of them is enough)");
+    println!("  --svg-max-width=PX  Scale the SVG down to this width if it is wider");
+    println!("  --responsive      Make the SVG 100% wide, to fill the page or its container");
+    println!("  --prefers-dark    Switch the SVG to the dark theme when the viewer's system");
+    println!("                    prefers a dark color scheme");
+    println!("  --header-every=N  Repeat sequence diagram headers every N messages");
+    println!("  --max-lifeline-gap=N  Wrap text sequence message labels rather than put");
+    println!("                    lifelines more than N columns apart");
+    println!("  --actor-margin=PX, --message-margin=PX, --box-margin=PX  Space between SVG");
+    println!("                    sequence participants, between messages and above notes");
+    println!("  --no-mirror-actors  Leave out the participant boxes at the bottom of SVG");
+    println!("                    sequence diagrams");
+    println!("  --max-label-width=N  Cut flowchart labels longer than N characters and list");
+    println!("                    them in full under the diagram");
+    println!("  --wrap-width=N    Wrap flowchart node labels wider than N onto more lines");
+    println!("  --uniform-node-width  Make flowchart node and class boxes all as wide as");
+    println!("                    the widest one");
+    println!("  --min-node-width=N, --max-node-width=N  Keep node and class boxes at least");
+    println!("                    and at most N wide (columns, or px with --svg)");
+    println!("  --swimlanes       Lay the top-level flowchart subgraphs out as lanes side");
+    println!("                    by side, with ranks lined up across them");
+    println!("  --max-members=N   List at most N attributes and N methods per class, the");
+    println!("                    rest as one `… (k more)` row");
+    println!("  --hide-private    Leave private (-) members out of class boxes");
+    println!("  --label-align=SIDE  Align node label lines and class box text left, center");
+    println!("                    or right (default: class members left, the rest centered)");
+    println!("  --max-nodes=N, --max-edges=N  Refuse diagrams with more nodes or edges");
+    println!("                    (default: 10000 and 50000; 0 for no limit)");
+    println!("  --max-canvas-cells=N  Refuse text output of more cells (default: 25000000)");
+    println!("  --max-svg-bytes=N     Refuse SVG output of more bytes (default: 64 MiB)");
+    println!("  --focus NODE      Render only the flowchart nodes near NODE (id or label)");
+    println!("  --depth N         How many edges away from --focus to keep nodes (default: 1)");
+    println!("  --subgraph NAMES  Render only the flowchart nodes in these subgraphs");
+    println!("                    (ids or labels, comma-separated)");
+    println!("  --include GLOBS   Render only the class diagram classes or ER entities named");
+    println!("                    like these (comma-separated, `*` and `?` wildcards)");
+    println!("  --exclude GLOBS   Leave out the classes or entities named like these");
+    println!("  --only-relations TYPES  Render only these relationships: inheritance,");
+    println!("                    composition, aggregation, association, dependency,");
+    println!("                    realization; identifying or non-identifying in ER diagrams");
+    println!("  --strict       Fail on the warnings `check` reports (Mermaid input only)");
+    println!("  -c, --compact  Use the tightest spacing between nodes and inside boxes");
+    println!("  --max-width=N|auto  Tighten the spacing, then draw left-right flowcharts");
+    println!("                    top-down, to fit text output in N columns (auto: the");
+    println!("                    terminal width)");
+    println!("  --annotate[=side|below]  Print the numbered source next to (default) or");
+    println!("                    below the text diagram");
+    println!("  --color[=WHEN]    Color lines and arrows in Unicode output: auto (with");
+    println!("                    no value; when stdout is a terminal), always or never");
+    println!("  --background=light|dark  Palette for --color (default: ask the terminal)");
+    println!("  --overflow=MODE   When text output is wider or taller than the terminal:");
+    println!("                    hint (default, print the required width), compact,");
+    println!("                    pager (pipe through $PAGER, default `less -RS`) or none");
+    #[cfg(feature = "http")]
+    println!("  --http ADDR       Serve POST /render on ADDR (e.g. 127.0.0.1:8080)");
+    println!("  --audit-junctions  Debug: report box-drawing characters that do not join up");
+    println!("                    (as line:column on stderr) and exit with status 1 if any");
+    println!();
+    println!("Environment (below the flags and the diagram's frontmatter):");
+    println!("  M2SVG_FORMAT         Output format: svg, unicode or ascii");
+    println!("  M2SVG_ASCII_CHARSET  Characters of text output: unicode, safe or ascii");
+    println!("  M2SVG_THEME          SVG theme: default or dark");
+    println!();
+    println!("A `%% m2svg: format=svg theme=dark padding_x=2` line in the diagram sets");
+    println!("the same options, and the text config lines, for piped input; flags and");
+    println!("frontmatter still win. Input starting with `{{` is read as --from json.");
+    println!();
+    println!("Examples:");
+    println!("  echo 'graph LR\\n  A --> B' | m2svg");
+    println!("  m2svg 'graph LR\\n  A --> B'");
+    println!("  m2svg --svg 'graph TD\\n  A --> B' > diagram.svg");
+    #[cfg(feature = "gallery")]
+    println!("  m2svg --example sequence");
+}
+
+/// The rendering options given as flags, or in the environment
+struct Options {
+    from: &'static str,
+    env_theme: Option<MermaidTheme>,
+    svg_flag: bool,
+    ascii_flag: bool,
+    unicode_flag: bool,
+    charset_flag: Option<Charset>,
+    /// SVG output from the flags or M2SVG_FORMAT, before the input's
+    /// directive is read
+    use_svg: bool,
+    charset: Charset,
+    square_corners: bool,
+    emit_json: bool,
+    emit_layout: bool,
+    header_every: usize,
+    max_gap: usize,
+    max_label_width: usize,
+    wrap_width: usize,
+    limits: RenderLimits,
+    node_width: NodeWidth,
+    class_members: ClassMembers,
+    prefers_dark: bool,
+    size: SvgSize,
+    sequence: SequenceConfig,
+    label_align: Option<LabelAlign>,
+    fonts: Vec<String>,
+    fragment_namespace: Option<String>,
+    font_source: FontSource,
+    curve: CurveStyle,
+    routing: EdgeRouting,
+    max_width: usize,
+    swimlanes: bool,
+    filter: Option<RenderFilter>,
+    strict: bool,
+    compact: bool,
+    audit: bool,
+    annotate: Option<Annotate>,
+    /// Color requested for the lines of Unicode output
+    color: bool,
+    background: Option<Background>,
+    overflow: Overflow,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        // Flags win over the environment, and M2SVG_ASCII_CHARSET over
+        // M2SVG_FORMAT for the characters; -a and -u also ask for text output
+        let env_format = env_choice("M2SVG_FORMAT", &["svg", "unicode", "ascii"])?;
+        let env_charset = env_choice("M2SVG_ASCII_CHARSET", &["unicode", "safe", "ascii"])?;
+        let env_theme =
+            env_choice("M2SVG_THEME", &["default", "dark"])?.map(MermaidTheme::from_str);
+        let svg_flag = args.iter().any(|a| a == "-s" || a == "--svg");
+        let ascii_flag = args.iter().any(|a| a == "-a" || a == "--ascii");
+        let unicode_flag = args.iter().any(|a| a == "-u" || a == "--unicode");
+        let use_svg = svg_flag || (!ascii_flag && !unicode_flag && env_format == Some("svg"));
+        let charset_flag = option_value(args, "--charset")
+            .map(|name| {
+                Charset::from_name(name).ok_or_else(|| format!("invalid --charset value: {}", name))
+            })
+            .transpose()?;
+        let charset = if ascii_flag {
+            Some(Charset::Ascii)
+        } else if unicode_flag {
+            Some(Charset::Unicode)
+        } else {
+            charset_flag.or_else(|| {
+                env_charset
+                    .or(env_format.filter(|&f| f != "svg"))
+                    .and_then(Charset::from_name)
+            })
+        };
+        let charset = charset.unwrap_or_else(|| {
+            // Legacy Windows consoles usually lack box-drawing glyphs
+            if legacy_console() {
+                Charset::Ascii
             } else {
-                // Treat as inline mermaid content
-                s.replace("\\n", "\n")
+                Charset::Unicode
             }
-        })
-        .unwrap_or_else(|| {
-            let mut buf = String::new();
-            io::stdin()
-                .read_to_string(&mut buf)
-                .expect("Failed to read from stdin");
-            buf
         });
+        let from = match option_value(args, "--from") {
+            None | Some("mermaid") => "mermaid",
+            Some("json") => "json",
+            Some("plantuml") => "plantuml",
+            #[cfg(feature = "dot")]
+            Some("dot") => "dot",
+            #[cfg(feature = "d2")]
+            Some("d2") => "d2",
+            Some(format) => return Err(format!("invalid --from value: {}", format)),
+        };
 
-    if input.trim().is_empty() {
-        eprintln!("Error: No input provided");
-        std::process::exit(1);
+        let mut limits = RenderLimits::default();
+        for (flag, limit) in [
+            ("--max-nodes", &mut limits.max_nodes),
+            ("--max-edges", &mut limits.max_edges),
+            ("--max-canvas-cells", &mut limits.max_canvas_cells),
+            ("--max-svg-bytes", &mut limits.max_svg_bytes),
+        ] {
+            if let Some(n) = usize_option(args, flag)? {
+                *limit = n;
+            }
+        }
+        let mut node_width = NodeWidth {
+            uniform: args.iter().any(|a| a == "--uniform-node-width"),
+            ..Default::default()
+        };
+        for (flag, width) in [
+            ("--min-node-width", &mut node_width.min),
+            ("--max-node-width", &mut node_width.max),
+        ] {
+            if let Some(n) = usize_option(args, flag)? {
+                *width = n;
+            }
+        }
+        let class_members = ClassMembers {
+            max: usize_option(args, "--max-members")?.unwrap_or(0),
+            hide_private: args.iter().any(|a| a == "--hide-private"),
+        };
+
+        let svg_px = |flag: &str| {
+            args.iter()
+                .find_map(|a| a.strip_prefix(flag))
+                .map(|n| {
+                    let px = n.parse::<f64>().ok();
+                    px.filter(|px| px.is_finite() && *px > 0.0).ok_or_else(|| {
+                        format!("invalid {} value: {}", flag.trim_end_matches('='), n)
+                    })
+                })
+                .transpose()
+        };
+        let (svg_width, svg_height) = (svg_px("--svg-width=")?, svg_px("--svg-height=")?);
+        let svg_max_width = svg_px("--svg-max-width=")?;
+        let size = if args.iter().any(|a| a == "--responsive") {
+            SvgSize::Responsive
+        } else if svg_width.is_some() || svg_height.is_some() {
+            SvgSize::Fixed {
+                width: svg_width,
+                height: svg_height,
+            }
+        } else {
+            svg_max_width.map_or(SvgSize::Natural, SvgSize::MaxWidth)
+        };
+        let sequence_margin = |flag: &str| {
+            args.iter()
+                .find_map(|a| a.strip_prefix(flag))
+                .map(|n| {
+                    n.parse::<f64>()
+                        .ok()
+                        .filter(|m| m.is_finite() && *m >= 0.0)
+                        .ok_or_else(|| {
+                            format!("invalid {} value: {}", flag.trim_end_matches('='), n)
+                        })
+                })
+                .transpose()
+        };
+        let sequence = SequenceConfig {
+            actor_margin: sequence_margin("--actor-margin=")?,
+            message_margin: sequence_margin("--message-margin=")?,
+            box_margin: sequence_margin("--box-margin=")?,
+            mirror_actors: args
+                .iter()
+                .any(|a| a == "--no-mirror-actors")
+                .then_some(false),
+        };
+        let label_align = args
+            .iter()
+            .find_map(|a| a.strip_prefix("--label-align="))
+            .map(|name| {
+                LabelAlign::from_name(name)
+                    .ok_or_else(|| format!("invalid --label-align value: {}", name))
+            })
+            .transpose()?;
+        let font_source = match option_value(args, "--embed-font") {
+            Some(path) => fs::read(path)
+                .map_err(|e| format!("Failed to read font file {}: {}", path, e))
+                .and_then(FontSource::embed)?,
+            None if args.iter().any(|a| a == "--system-fonts") => FontSource::System,
+            None => FontSource::Web,
+        };
+        let curve = args
+            .iter()
+            .find_map(|a| a.strip_prefix("--curve="))
+            .map(|name| {
+                CurveStyle::from_name(name)
+                    .ok_or_else(|| format!("invalid --curve value: {}", name))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let routing = args
+            .iter()
+            .find_map(|a| a.strip_prefix("--routing="))
+            .map(|name| {
+                EdgeRouting::from_name(name)
+                    .ok_or_else(|| format!("invalid --routing value: {}", name))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let max_width = match option_value(args, "--max-width") {
+            Some("auto") => terminal_size().map_or(0, |(columns, _)| columns),
+            _ => usize_option(args, "--max-width")?.unwrap_or(0),
+        };
+
+        let include_subgraphs = list_option(args, "--subgraph");
+        let focus_node = option_value(args, "--focus").map(str::to_string);
+        let include = list_option(args, "--include");
+        let exclude = list_option(args, "--exclude");
+        let only_relations = list_option(args, "--only-relations");
+        let filter = (!include_subgraphs.is_empty()
+            || focus_node.is_some()
+            || !include.is_empty()
+            || !exclude.is_empty()
+            || !only_relations.is_empty())
+        .then_some(RenderFilter {
+            include_subgraphs,
+            focus_node,
+            depth: usize_option(args, "--depth")?.unwrap_or(1),
+            include,
+            exclude,
+            only_relations,
+        });
+
+        let annotate = args
+            .iter()
+            .find_map(|a| match a.as_str() {
+                "--annotate" | "--annotate=side" => Some(Ok(Annotate::Side)),
+                "--annotate=below" => Some(Ok(Annotate::Below)),
+                _ => a
+                    .strip_prefix("--annotate=")
+                    .map(|mode| Err(format!("invalid --annotate value: {}", mode))),
+            })
+            .transpose()?;
+        let color = args
+            .iter()
+            .find_map(|a| match a.as_str() {
+                "--color" | "--color=auto" => Some(Ok(
+                    io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+                )),
+                "--color=always" => Some(Ok(true)),
+                "--color=never" => Some(Ok(false)),
+                _ => a
+                    .strip_prefix("--color=")
+                    .map(|mode| Err(format!("invalid --color value: {}", mode))),
+            })
+            .transpose()?;
+        let background = args
+            .iter()
+            .find_map(|a| a.strip_prefix("--background="))
+            .map(|mode| match mode {
+                "light" => Ok(Background::Light),
+                "dark" => Ok(Background::Dark),
+                _ => Err(format!("invalid --background value: {}", mode)),
+            })
+            .transpose()?;
+        let overflow = args
+            .iter()
+            .find_map(|a| a.strip_prefix("--overflow="))
+            .map(|mode| match mode {
+                "hint" => Ok(Overflow::Hint),
+                "compact" => Ok(Overflow::Compact),
+                "pager" => Ok(Overflow::Pager),
+                "none" => Ok(Overflow::Ignore),
+                _ => Err(format!("invalid --overflow value: {}", mode)),
+            })
+            .transpose()?
+            .unwrap_or(Overflow::Hint);
+
+        Ok(Self {
+            from,
+            env_theme,
+            svg_flag,
+            ascii_flag,
+            unicode_flag,
+            charset_flag,
+            use_svg,
+            charset,
+            square_corners: args.iter().any(|a| a == "--square-corners"),
+            emit_json: args.iter().any(|a| a == "--emit-json"),
+            emit_layout: args.iter().any(|a| a == "--emit-layout"),
+            header_every: usize_option(args, "--header-every")?.unwrap_or(0),
+            max_gap: usize_option(args, "--max-lifeline-gap")?.unwrap_or(0),
+            max_label_width: usize_option(args, "--max-label-width")?.unwrap_or(0),
+            wrap_width: usize_option(args, "--wrap-width")?.unwrap_or(0),
+            limits,
+            node_width,
+            class_members,
+            prefers_dark: args.iter().any(|a| a == "--prefers-dark"),
+            size,
+            sequence,
+            label_align,
+            fonts: list_option(args, "--font"),
+            fragment_namespace: option_value(args, "--fragment").map(str::to_string),
+            font_source,
+            curve,
+            routing,
+            max_width,
+            swimlanes: args.iter().any(|a| a == "--swimlanes"),
+            filter,
+            strict: args.iter().any(|a| a == "--strict"),
+            compact: args.iter().any(|a| a == "-c" || a == "--compact"),
+            audit: args.iter().any(|a| a == "--audit-junctions"),
+            annotate,
+            color: color.unwrap_or(false),
+            background,
+            overflow,
+        })
     }
 
-    if use_svg {
-        match render_to_svg(&input) {
-            Ok(output) => println!("{}", output),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+    /// Parse `input`, written in the `from` format
+    fn parse_input(&self, from: &str, input: &str) -> Result<ParsedDiagram, String> {
+        parse_input(
+            from,
+            input,
+            self.env_theme,
+            self.swimlanes,
+            self.filter.as_ref(),
+        )
+    }
+
+    /// Whether `input` is rendered to SVG, and with which characters
+    /// otherwise: a `%% m2svg: format=...` directive in the input wins over
+    /// M2SVG_FORMAT, but not over the format flags
+    fn output_format(&self, input: &str) -> (bool, Charset) {
+        let format = directive_option(input, "format");
+        let use_svg = match &format {
+            Some(format) if !self.svg_flag && !self.ascii_flag && !self.unicode_flag => {
+                format.eq_ignore_ascii_case("svg")
+            }
+            _ => self.use_svg,
+        };
+        let charset = match format.and_then(|f| Charset::from_name(&f)) {
+            Some(format)
+                if !self.ascii_flag && !self.unicode_flag && self.charset_flag.is_none() =>
+            {
+                format
             }
+            _ => self.charset,
+        };
+        (use_svg, charset)
+    }
+
+    fn svg_options(&self, args: &[String]) -> Result<SvgRenderOptions, String> {
+        Ok(SvgRenderOptions {
+            curve: self.curve,
+            routing: self.routing,
+            max_label_width: self.max_label_width,
+            wrap_width: self.wrap_width,
+            node_width: self.node_width,
+            label_align: self.label_align,
+            class_members: self.class_members,
+            measurer: font_measurer(args)?,
+            fonts: self.fonts.clone(),
+            limits: self.limits,
+            fragment_namespace: self.fragment_namespace.clone(),
+            size: self.size,
+            font_source: self.font_source.clone(),
+            sequence: self.sequence,
+            prefers_dark: self.prefers_dark,
+        })
+    }
+
+    fn ascii_options(&self, charset: Charset) -> AsciiRenderOptions {
+        AsciiRenderOptions {
+            use_ascii: charset == Charset::Ascii,
+            charset: Some(charset),
+            rounded_corners: !self.square_corners,
+            sequence_header_every: self.header_every,
+            sequence_max_gap: self.max_gap,
+            max_label_width: self.max_label_width,
+            wrap_width: self.wrap_width,
+            node_width: self.node_width,
+            label_align: self.label_align,
+            class_members: self.class_members,
+            max_width: self.max_width,
+            limits: self.limits,
+            ..Default::default()
         }
+    }
+}
+
+/// `m2svg serve`: answer JSON render requests on stdin
+fn serve() -> Result<(), String> {
+    m2svg::server::serve(io::stdin().lock(), io::stdout().lock()).map_err(|e| e.to_string())
+}
+
+/// `m2svg daemon --socket PATH`: answer render requests on a Unix socket
+#[cfg(unix)]
+fn daemon(args: &[String]) -> Result<(), String> {
+    let socket = option_value(args, "--socket").ok_or("daemon needs --socket PATH")?;
+    eprintln!("Listening on {}", socket);
+    m2svg::server::serve_socket(Path::new(socket)).map_err(|e| e.to_string())
+}
+
+/// `m2svg --http ADDR`: serve POST /render
+#[cfg(feature = "http")]
+fn serve_http(args: &[String]) -> Result<(), String> {
+    let addr = option_value(args, "--http").unwrap_or_default();
+    eprintln!("Listening on http://{}/render", addr);
+    m2svg::http::serve_http(addr).map_err(|e| e.to_string())
+}
+
+/// `m2svg batch FILES...`: render each file next to it (or into --out-dir),
+/// and exit with status 1 if any failed
+fn batch(args: &[String], options: &Options) -> Result<(), String> {
+    let out_dir = option_value(args, "--out-dir");
+    let files: Vec<&String> = args
+        .iter()
+        .enumerate()
+        .skip(2)
+        .filter(|(i, a)| !a.starts_with('-') && !FLAGS_WITH_VALUES.contains(&args[i - 1].as_str()))
+        .map(|(_, a)| a)
+        .collect();
+    if files.is_empty() {
+        return Err("batch needs at least one input file".to_string());
+    }
+    let svg_options = if options.use_svg {
+        Some(options.svg_options(args)?)
     } else {
-        let options = AsciiRenderOptions {
-            use_ascii,
-            ..Default::default()
+        None
+    };
+    let from = options.from;
+    let extension = if options.use_svg { "svg" } else { "txt" };
+    let render_file = |file: &&String| -> Result<String, String> {
+        let input = fs::read_to_string(file).map_err(|e| e.to_string())?;
+        let parsed = options.parse_input(from, &input)?;
+        if options.strict && from == "mermaid" {
+            if let Some(warning) = m2svg::validate(&input).first() {
+                let line = warning.line.map(|n| format!("line {}: ", n));
+                return Err(format!("{}{}", line.unwrap_or_default(), warning.message));
+            }
+        }
+        let output = match &svg_options {
+            Some(svg_options) => try_render_parsed_svg(&parsed, Some(svg_options.clone()))?,
+            None => {
+                let text = options.ascii_options(options.charset);
+                let text = if options.compact {
+                    text.compact()
+                } else {
+                    text
+                };
+                render_parsed_ascii(&parsed, Some(text_options(from, &input, text)))?
+            }
         };
+        let path = Path::new(file.as_str()).with_extension(extension);
+        let path = match out_dir {
+            Some(dir) => Path::new(dir).join(path.file_name().unwrap_or_default()),
+            None => path,
+        };
+        fs::write(&path, output + "\n").map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(path.display().to_string())
+    };
 
-        match render_mermaid_ascii(&input, Some(options)) {
-            Ok(output) => println!("{}", output),
+    if let Some(dir) = out_dir {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    }
+    let start = std::time::Instant::now();
+    let results = m2svg::batch::run(&files, render_file);
+    let mut failed = 0;
+    for (file, timed) in files.iter().zip(&results) {
+        let ms = timed.elapsed.as_secs_f64() * 1000.0;
+        match &timed.result {
+            Ok(output) => println!("{} -> {} ({:.1} ms)", file, output, ms),
             Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+                failed += 1;
+                eprintln!("{}: error: {} ({:.1} ms)", file, e, ms);
+            }
+        }
+    }
+    println!(
+        "{} rendered, {} failed in {:.1} ms",
+        files.len() - failed,
+        failed,
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// `m2svg picture FILE`: write FILE-light.svg and FILE-dark.svg and print
+/// the `<picture>` element that shows the one matching the color mode
+fn picture(args: &[String], options: &Options) -> Result<(), String> {
+    let file = args
+        .iter()
+        .enumerate()
+        .skip(2)
+        .find(|(i, a)| !a.starts_with('-') && !FLAGS_WITH_VALUES.contains(&args[i - 1].as_str()))
+        .map(|(_, a)| a)
+        .ok_or("picture needs an input file")?;
+    let input = fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+    let mut parsed = options.parse_input(options.from, &input)?;
+    // Each picture is a whole document with its own fixed theme
+    let svg_options = SvgRenderOptions {
+        fragment_namespace: None,
+        prefers_dark: false,
+        ..options.svg_options(args)?
+    };
+
+    let path = Path::new(file.as_str());
+    let dir = option_value(args, "--out-dir").map_or_else(
+        || path.parent().unwrap_or(Path::new("")).to_path_buf(),
+        |dir| Path::new(dir).to_path_buf(),
+    );
+    if !dir.as_os_str().is_empty() {
+        fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut sources = Vec::new();
+    for (theme, name) in [
+        (MermaidTheme::Default, "light"),
+        (MermaidTheme::Dark, "dark"),
+    ] {
+        parsed.frontmatter.theme = theme;
+        let svg = try_render_parsed_svg(&parsed, Some(svg_options.clone()))?;
+        let out = dir.join(format!("{}-{}.svg", stem, name));
+        fs::write(&out, svg + "\n").map_err(|e| format!("{}: {}", out.display(), e))?;
+        // Markdown links use forward slashes on every platform
+        sources.push(out.display().to_string().replace('\\', "/"));
+    }
+
+    // The alt text is the diagram's own title, else the first sentence of
+    // its description
+    let description = m2svg::describe::describe(&parsed);
+    let alt = parsed
+        .frontmatter
+        .acc_title
+        .as_deref()
+        .or(parsed.frontmatter.title.as_deref())
+        .unwrap_or_else(|| description.lines().next().unwrap_or_default());
+    println!("<picture>");
+    println!(
+        "  <source media=\"(prefers-color-scheme: dark)\" srcset=\"{}\">",
+        attribute_escape(&sources[1])
+    );
+    println!(
+        "  <img alt=\"{}\" src=\"{}\">",
+        attribute_escape(alt),
+        attribute_escape(&sources[0])
+    );
+    println!("</picture>");
+    Ok(())
+}
+
+/// `m2svg check [INPUT]`: print what `validate` reports, and exit with
+/// status 1 if it reports anything
+fn check(args: &[String]) -> Result<(), String> {
+    let (input, name) = read_input(args, 2)?;
+    let diagnostics = m2svg::validate(&input);
+    for diagnostic in &diagnostics {
+        match diagnostic.line {
+            Some(_) => println!("{}:{}", name, diagnostic),
+            None => println!("{}: {}", name, diagnostic),
+        }
+    }
+    if !diagnostics.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// `m2svg describe [INPUT]`: print a plain-language description
+fn describe(args: &[String], options: &Options) -> Result<(), String> {
+    let (input, name) = read_input(args, 2)?;
+    let from = input_format(args, options, &input);
+    let parsed = parse_strict(options, from, &input, &name)?;
+    println!("{}", m2svg::describe::describe(&parsed));
+    Ok(())
+}
+
+/// `m2svg from-rust [PATH]`: render the class diagram of the Rust source,
+/// read as --emit-json output so the usual options and output formats apply
+fn from_rust(args: &[String], options: &Options) -> Result<(), String> {
+    let path = input_arg(args, 2).map_or(".", String::as_str);
+    let input = rust_class_diagram(path)?;
+    render_input(args, options, "json", &input, path)
+}
+
+/// `m2svg [INPUT]`: render a diagram to text or SVG
+fn render(args: &[String], options: &Options) -> Result<(), String> {
+    let (input, name) = read_input(args, 1)?;
+    let from = input_format(args, options, &input);
+    render_input(args, options, from, &input, &name)
+}
+
+/// Render `input`, written in the `from` format and named `name` in
+/// diagnostics, as the options ask
+fn render_input(
+    args: &[String],
+    options: &Options,
+    from: &str,
+    input: &str,
+    name: &str,
+) -> Result<(), String> {
+    let (use_svg, charset) = options.output_format(input);
+    let parsed = parse_strict(options, from, input, name)?;
+
+    if options.emit_json {
+        println!("{}", parsed.to_json()?);
+        return Ok(());
+    }
+    if options.emit_layout || use_svg {
+        let svg_options = options.svg_options(args)?;
+        let output = if options.emit_layout {
+            layout_parsed(&parsed, Some(svg_options))?.to_json()?
+        } else {
+            try_render_parsed_svg(&parsed, Some(svg_options))?
+        };
+        println!("{}", output);
+        return Ok(());
+    }
+
+    let use_ascii = charset == Charset::Ascii;
+    let text = options.ascii_options(charset);
+    let render = |compact: bool| -> Result<String, String> {
+        let text = if compact {
+            text.clone().compact()
+        } else {
+            text.clone()
+        };
+        let diagram = render_parsed_ascii(&parsed, Some(text_options(from, input, text)))?;
+        Ok(match options.annotate {
+            Some(mode) => annotate_with_source(&diagram, input, mode, use_ascii),
+            None => diagram,
+        })
+    };
+    // Lines are only colored in Unicode output
+    let paint = |text: &str| {
+        if options.color && !use_ascii {
+            let background = options.background.unwrap_or_else(terminal_background);
+            m2svg::ascii::color::colorize(text, background)
+        } else {
+            text.to_string()
+        }
+    };
+
+    let mut output = render(options.compact)?;
+    if options.audit {
+        println!("{}", output);
+        let issues = m2svg::ascii::audit::audit_junctions(&output);
+        for issue in &issues {
+            eprintln!("{}", issue);
+        }
+        std::process::exit(if issues.is_empty() { 0 } else { 1 });
+    }
+    let terminal = match options.overflow {
+        Overflow::Ignore => None,
+        _ if !io::stdout().is_terminal() => None,
+        _ => terminal_size(),
+    };
+    let Some((columns, rows)) = terminal else {
+        println!("{}", paint(&output));
+        return Ok(());
+    };
+
+    let (mut width, height) = text_size(&output);
+    let overflows = width > columns || height > rows;
+    if options.overflow == Overflow::Compact && !options.compact && overflows {
+        output = render(true)?;
+        width = text_size(&output).0;
+    }
+    if options.overflow == Overflow::Pager && overflows {
+        if page(&paint(&output)).is_ok() {
+            return Ok(());
+        }
+    } else if width > columns {
+        eprintln!(
+            "Note: the diagram is {} columns wide but the terminal has {}; widen the terminal \
+             or try --overflow=compact or --overflow=pager",
+            width, columns
+        );
+    }
+    println!("{}", paint(&output));
+    Ok(())
+}
+
+/// The first argument from index `skip` on that is neither a flag nor a
+/// flag's value
+fn input_arg(args: &[String], skip: usize) -> Option<&String> {
+    args.iter()
+        .enumerate()
+        .skip(skip)
+        .find(|(i, a)| !a.starts_with('-') && !FLAGS_WITH_VALUES.contains(&args[i - 1].as_str()))
+        .map(|(_, a)| a)
+}
+
+/// The diagram text from a built-in example, the input argument (a file,
+/// `-` for stdin, or the text itself) or stdin, and the name diagnostics
+/// give it
+fn read_input(args: &[String], skip: usize) -> Result<(String, String), String> {
+    #[cfg(feature = "gallery")]
+    let example = option_value(args, "--example")
+        .map(|name| {
+            m2svg::gallery::example(name)
+                .ok_or_else(|| format!("unknown example: {} (see --list-examples)", name))
+        })
+        .transpose()?;
+    #[cfg(not(feature = "gallery"))]
+    let example: Option<&str> = None;
+    let arg = input_arg(args, skip).filter(|_| example.is_none());
+
+    let read_stdin = || {
+        let mut buf = String::new();
+        io::stdin()
+            .read_to_string(&mut buf)
+            .map_err(|e| format!("Failed to read from stdin: {}", e))?;
+        Ok::<_, String>(buf)
+    };
+    let input = match (arg, example) {
+        (Some(s), _) if s == "-" => read_stdin()?,
+        (Some(s), _) if Path::new(s).exists() => {
+            fs::read_to_string(s).map_err(|e| format!("Failed to read file {}: {}", s, e))?
+        }
+        // Treat as inline mermaid content
+        (Some(s), _) => s.replace("\\n", "\n"),
+        (None, Some(example)) => example.to_string(),
+        (None, None) => read_stdin()?,
+    };
+    if input.trim().is_empty() {
+        return Err("No input provided".to_string());
+    }
+
+    // Diagnostics read `file:line: severity: message`, like a compiler's
+    let name = arg
+        .filter(|a| Path::new(a).exists())
+        .map_or("<stdin>", |a| a.as_str());
+    Ok((input, name.to_string()))
+}
+
+/// The `--from` format, or json for piped --emit-json output without one
+fn input_format(args: &[String], options: &Options, input: &str) -> &'static str {
+    if option_value(args, "--from").is_none() && input.trim_start().starts_with('{') {
+        "json"
+    } else {
+        options.from
+    }
+}
+
+/// Parse `input`; with --strict, fail after printing the warnings `check`
+/// would report on Mermaid input
+fn parse_strict(
+    options: &Options,
+    from: &str,
+    input: &str,
+    name: &str,
+) -> Result<ParsedDiagram, String> {
+    let parsed = options.parse_input(from, input)?;
+    if options.strict && from == "mermaid" {
+        // The input parsed, so these are warnings, which --strict makes errors;
+        // without it they are left to `m2svg check`
+        let warnings = m2svg::validate(input);
+        for warning in &warnings {
+            let diagnostic = Diagnostic {
+                severity: Severity::Error,
+                ..warning.clone()
+            };
+            match diagnostic.line {
+                Some(_) => eprintln!("{}:{}", name, diagnostic),
+                None => eprintln!("{}: {}", name, diagnostic),
             }
         }
+        if !warnings.is_empty() {
+            std::process::exit(1);
+        }
+    }
+    Ok(parsed)
+}
+
+/// Split the comma-separated value of option `name` into its items
+fn list_option(args: &[String], name: &str) -> Vec<String> {
+    option_value(args, name)
+        .map(|list| {
+            list.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse `input` written in the `--from` format, themed by its `%% m2svg:`
+/// directive or else M2SVG_THEME unless its frontmatter picks a theme, laid out in lanes for `--swimlanes`, and cut
+/// down to the part `filter` selects
+fn parse_input(
+    from: &str,
+    input: &str,
+    env_theme: Option<MermaidTheme>,
+    swimlanes: bool,
+    filter: Option<&RenderFilter>,
+) -> Result<ParsedDiagram, String> {
+    let mut parsed = match from {
+        "json" => ParsedDiagram::from_json(input),
+        "plantuml" => parse_plantuml(input),
+        #[cfg(feature = "dot")]
+        "dot" => m2svg::parse_dot(input),
+        #[cfg(feature = "d2")]
+        "d2" => m2svg::parse_d2(input),
+        _ => parse_mermaid(input),
+    }?;
+    let sets_theme = parsed
+        .frontmatter
+        .raw_lines
+        .iter()
+        .any(|line| line.trim().trim_start_matches("- ").starts_with("theme:"));
+    let theme = directive_option(input, "theme")
+        .map(|theme| MermaidTheme::from_str(&theme))
+        .or(env_theme);
+    if let Some(theme) = theme.filter(|_| !sets_theme) {
+        parsed.frontmatter.theme = theme;
+    }
+    if let DiagramType::Flowchart(graph) = &mut parsed.diagram {
+        if swimlanes {
+            graph.config.swimlanes = Some(true);
+        }
+    }
+    match filter {
+        Some(filter) => filter.apply(&parsed),
+        None => Ok(parsed),
+    }
+}
+
+/// `text` escaped for an HTML attribute value in double quotes
+fn attribute_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The value of `key` in the `%% m2svg:` directive of `input`, if it sets one
+fn directive_option(input: &str, key: &str) -> Option<String> {
+    m2svg::ascii::options_directive(input)
+        .into_iter()
+        .rev()
+        .find_map(|(k, value)| (k == key).then_some(value))
+}
+
+/// Text render `options`, with the `paddingX=`-style config lines that
+/// Mermaid input may carry applied
+fn text_options(from: &str, input: &str, options: AsciiRenderOptions) -> AsciiRenderOptions {
+    if from == "mermaid" {
+        m2svg::ascii::parse_config_from_text(input, options)
+    } else {
+        options
+    }
+}
+
+/// Text measurer for `--font-file=PATH`, or `None` to use the built-in heuristic
+#[cfg(feature = "font-metrics")]
+fn font_measurer(
+    args: &[String],
+) -> Result<Option<std::sync::Arc<dyn m2svg::svg::TextMeasurer>>, String> {
+    let Some(path) = option_value(args, "--font-file") else {
+        return Ok(None);
+    };
+    let measurer = fs::read(path)
+        .map_err(|e| format!("Failed to read font file {}: {}", path, e))
+        .and_then(m2svg::svg::FontMetricsMeasurer::new)?;
+    Ok(Some(std::sync::Arc::new(measurer)))
+}
+
+#[cfg(not(feature = "font-metrics"))]
+fn font_measurer(
+    _args: &[String],
+) -> Result<Option<std::sync::Arc<dyn m2svg::svg::TextMeasurer>>, String> {
+    Ok(None)
+}
+
+/// The class diagram of the Rust files at `path` (one file, or a directory
+/// searched recursively) as diagram model JSON
+#[cfg(feature = "rust")]
+fn rust_class_diagram(path: &str) -> Result<String, String> {
+    let mut files = Vec::new();
+    rust_files(Path::new(path), &mut files)?;
+    if files.is_empty() {
+        return Err(format!("No .rs files in {}", path));
+    }
+    let texts = files
+        .iter()
+        .map(|file| {
+            fs::read_to_string(file)
+                .map(|text| (file.display().to_string(), text))
+                .map_err(|e| format!("Failed to read {}: {}", file.display(), e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let sources: Vec<(&str, &str)> = texts
+        .iter()
+        .map(|(name, text)| (name.as_str(), text.as_str()))
+        .collect();
+    m2svg::parse_rust(&sources)?.to_json()
+}
+
+#[cfg(not(feature = "rust"))]
+fn rust_class_diagram(_path: &str) -> Result<String, String> {
+    Err("from-rust needs m2svg built with the rust feature".to_string())
+}
+
+/// Add the `.rs` files at `path` to `files` in name order, skipping hidden
+/// and `target` directories
+#[cfg(feature = "rust")]
+fn rust_files(path: &Path, files: &mut Vec<std::path::PathBuf>) -> Result<(), String> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries: Vec<std::path::PathBuf> = fs::read_dir(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+    for entry in entries {
+        let name = entry.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if entry.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                rust_files(&entry, files)?;
+            }
+        } else if entry.extension().is_some_and(|ext| ext == "rs") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// The value of environment variable `name`, which must be one of `choices`
+/// (ignoring case), or `None` when it is unset or empty
+fn env_choice(name: &str, choices: &[&'static str]) -> Result<Option<&'static str>, String> {
+    let Ok(value) = std::env::var(name) else {
+        return Ok(None);
+    };
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    match choices.iter().find(|c| c.eq_ignore_ascii_case(value)) {
+        Some(choice) => Ok(Some(*choice)),
+        None => Err(format!(
+            "invalid {} value: {} (expected {})",
+            name,
+            value,
+            choices.join(", ")
+        )),
+    }
+}
+
+/// Value of an option given as `--name=value` or `--name value`
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().enumerate().find_map(|(i, a)| {
+        if a == name {
+            args.get(i + 1).map(String::as_str)
+        } else {
+            a.strip_prefix(name)?.strip_prefix('=')
+        }
+    })
+}
+
+/// Value of an option taking a count, given as `--name=N` or `--name N`
+fn usize_option(args: &[String], name: &str) -> Result<Option<usize>, String> {
+    option_value(args, name)
+        .map(|n| {
+            n.parse::<usize>()
+                .map_err(|_| format!("invalid {} value: {}", name, n))
+        })
+        .transpose()
+}
+
+/// Where `--annotate` puts the source relative to the diagram
+#[derive(Clone, Copy, PartialEq)]
+enum Annotate {
+    Side,
+    Below,
+}
+
+/// Lay out the diagram together with its numbered source lines
+fn annotate_with_source(diagram: &str, source: &str, mode: Annotate, use_ascii: bool) -> String {
+    let rule = if use_ascii { '|' } else { '│' };
+    let source: Vec<String> = source
+        .trim_end()
+        .lines()
+        .map(|l| l.trim_end().replace('\t', "    "))
+        .collect();
+    let number_width = source.len().to_string().len();
+    let numbered: Vec<String> = source
+        .iter()
+        .enumerate()
+        .map(|(i, line)| format!("{:>w$} {} {}", i + 1, rule, line, w = number_width))
+        .collect();
+
+    match mode {
+        Annotate::Below => format!("{}\n\n{}", diagram, numbered.join("\n")),
+        Annotate::Side => {
+            let source_width = text_size(&numbered.join("\n")).0;
+            let diagram: Vec<&str> = diagram.lines().collect();
+            (0..numbered.len().max(diagram.len()))
+                .map(|i| {
+                    let left = numbered.get(i).map(String::as_str).unwrap_or("");
+                    let right = diagram.get(i).copied().unwrap_or("");
+                    let pad = source_width - left.chars().count();
+                    format!("{}{}   {}", left, " ".repeat(pad), right)
+                        .trim_end()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    }
+}
+
+/// Width (in characters) and height (in lines) of rendered text
+fn text_size(text: &str) -> (usize, usize) {
+    let width = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+    (width, text.lines().count())
+}
+
+/// Terminal size as (columns, rows), from `COLUMNS`/`LINES` or `stty size`
+fn terminal_size() -> Option<(usize, usize)> {
+    let env = |name: &str| std::env::var(name).ok()?.trim().parse::<usize>().ok();
+    if let (Some(columns), Some(rows)) = (env("COLUMNS"), env("LINES")) {
+        return Some((columns, rows));
+    }
+
+    let tty = fs::File::open("/dev/tty").ok()?;
+    let out = Command::new("stty")
+        .arg("size")
+        .stdin(tty)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let text = String::from_utf8(out.stdout).ok()?;
+    let mut sizes = text.split_whitespace().map(|n| n.parse::<usize>().ok());
+    let rows = sizes.next()??;
+    let columns = sizes.next()??;
+    Some((
+        env("COLUMNS").unwrap_or(columns),
+        env("LINES").unwrap_or(rows),
+    ))
+}
+
+/// Background of the terminal, from an OSC 11 query answered on /dev/tty,
+/// else from `COLORFGBG`, else assumed dark
+fn terminal_background() -> Background {
+    query_background()
+        .or_else(|| Background::from_colorfgbg(&std::env::var("COLORFGBG").ok()?))
+        .unwrap_or(Background::Dark)
+}
+
+/// Ask the terminal for its background color. The tty is put in raw mode for
+/// the reply, and terminals that do not answer time out after 0.2s.
+#[cfg(unix)]
+fn query_background() -> Option<Background> {
+    let stty = |args: &[&str]| -> Option<String> {
+        let out = Command::new("stty")
+            .args(args)
+            .stdin(fs::File::open("/dev/tty").ok()?)
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|out| out.status.success())?;
+        Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+    };
+    let saved = stty(&["-g"])?;
+    stty(&["raw", "-echo", "min", "0", "time", "2"])?;
+    let reply = (|| {
+        let mut tty = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .ok()?;
+        tty.write_all(b"\x1b]11;?\x07").ok()?;
+        let mut reply = Vec::new();
+        let mut byte = [0u8; 1];
+        // Up to BEL or ST, or until the read times out
+        while reply.len() < 64 && tty.read(&mut byte).ok()? == 1 {
+            reply.push(byte[0]);
+            if byte[0] == 0x07 || reply.ends_with(b"\x1b\\") {
+                break;
+            }
+        }
+        Some(String::from_utf8_lossy(&reply).into_owned())
+    })();
+    stty(&[&saved]);
+    Background::from_osc11_reply(&reply?)
+}
+
+#[cfg(not(unix))]
+fn query_background() -> Option<Background> {
+    None
+}
+
+/// Whether stdout is a console that predates VT support (the Windows console
+/// host before Windows 10). Checking also turns VT processing on for the
+/// consoles that have it, so ANSI sequences work there.
+fn legacy_console() -> bool {
+    #[cfg(windows)]
+    {
+        io::stdout().is_terminal() && !windows_console::enable_virtual_terminal()
+    }
+    #[cfg(not(windows))]
+    {
+        false
+    }
+}
+
+/// Console API calls, declared directly to avoid a dependency
+#[cfg(windows)]
+mod windows_console {
+    use std::ffi::c_void;
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        fn GetConsoleMode(handle: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(handle: *mut c_void, mode: u32) -> i32;
+    }
+
+    /// Turn on VT processing for stdout; false if the console doesn't support it
+    pub fn enable_virtual_terminal() -> bool {
+        // SAFETY: plain Win32 calls on the process's own stdout handle, with a
+        // valid pointer for the mode out-parameter
+        unsafe {
+            let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+            let mut mode = 0;
+            if handle.is_null() || GetConsoleMode(handle, &mut mode) == 0 {
+                return false;
+            }
+            mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+                || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+        }
+    }
+}
+
+/// Show text through `$PAGER` (`less -RS` if unset, so colors show and wide lines don't wrap)
+fn page(text: &str) -> io::Result<()> {
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| "less -RS".to_string());
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&pager)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user may quit the pager before reading everything
+        let _ = writeln!(stdin, "{}", text);
     }
+    child.wait()?;
+    Ok(())
 }
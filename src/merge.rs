@@ -0,0 +1,633 @@
+//! Merging of multiple flowchart graphs into one
+//!
+//! Lets tools compose per-module diagrams into a single system overview
+//! before rendering.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::types::{
+    Direction, EdgeMarker, EdgeStyle, MermaidEdge, MermaidGraph, MermaidNode, MermaidSubgraph,
+    NodeShape, Port,
+};
+
+/// Merge several flowcharts into a single graph.
+///
+/// - Nodes are unioned by id, keeping the first-seen insertion order. The first
+///   explicit definition wins; a bare reference (`A` with no label or shape) is
+///   upgraded when a later graph defines the node.
+/// - Edges are concatenated in input order, dropping exact duplicates.
+/// - Subgraphs with the same id are merged, unioning their node lists.
+/// - `classDef`, `class` and `style` declarations are combined; for the same
+///   property on the same target, later graphs override earlier ones. The same
+///   goes for `click` links.
+/// - The direction and spacing are taken from the first graph (TD and the
+///   default spacing if the list is empty).
+pub fn merge(graphs: &[MermaidGraph]) -> MermaidGraph {
+    let direction = graphs.first().map(|g| g.direction).unwrap_or(Direction::TD);
+    let mut merged = MermaidGraph::new(direction);
+    merged.config = graphs.first().map(|g| g.config).unwrap_or_default();
+
+    let mut edges = HashSet::new();
+    for graph in graphs {
+        for id in &graph.node_order {
+            if let Some(node) = graph.nodes.get(id) {
+                merge_node(&mut merged, node);
+            }
+        }
+
+        for edge in &graph.edges {
+            if edges.insert(edge_key(edge)) {
+                merged.edges.push(edge.clone());
+            }
+        }
+
+        for sg in &graph.subgraphs {
+            merge_subgraph(&mut merged.subgraphs, sg);
+        }
+
+        for (name, props) in &graph.class_defs {
+            let entry = merged.class_defs.entry(name.clone()).or_default();
+            for (k, v) in props {
+                entry.insert(k.clone(), v.clone());
+            }
+        }
+
+        for (node_id, class_name) in &graph.class_assignments {
+            merged
+                .class_assignments
+                .insert(node_id.clone(), class_name.clone());
+        }
+
+        for (node_id, props) in &graph.node_styles {
+            let entry = merged.node_styles.entry(node_id.clone()).or_default();
+            for (k, v) in props {
+                entry.insert(k.clone(), v.clone());
+            }
+        }
+
+        for (node_id, link) in &graph.node_links {
+            merged.node_links.insert(node_id.clone(), link.clone());
+        }
+    }
+
+    merged
+}
+
+/// Merge several flowcharts, keeping each fragment's ids in its own namespace.
+///
+/// Every node and subgraph id of a fragment is prefixed with `"<namespace>_"`
+/// before merging, so `A` in `auth` and `A` in `billing` stay distinct nodes
+/// (`auth_A` and `billing_A`). Labels are left untouched, so both still render
+/// as `A`. A fragment with an empty namespace keeps its ids as-is, which lets
+/// it declare shared nodes or link the others together by their prefixed ids.
+///
+/// Fails when a prefixed id is one that another namespace ends up with too
+/// (`b_c` in `a` and `c` in `a_b` are both `a_b_c`), or one that a fragment
+/// without a namespace defines with a label or shape of its own, rather than
+/// only linking to it.
+pub fn merge_namespaced(fragments: &[(&str, MermaidGraph)]) -> Result<MermaidGraph, String> {
+    let prefixed: Vec<MermaidGraph> = fragments
+        .iter()
+        .map(|(namespace, graph)| {
+            if namespace.is_empty() {
+                graph.clone()
+            } else {
+                prefix_ids(graph, &format!("{}_", namespace))
+            }
+        })
+        .collect();
+
+    // The namespace each id came from
+    let mut owners: HashMap<&str, &str> = HashMap::new();
+    for ((namespace, _), graph) in fragments.iter().zip(&prefixed) {
+        if namespace.is_empty() {
+            continue;
+        }
+        for id in &graph.node_order {
+            match owners.insert(id, *namespace) {
+                Some(other) if other != *namespace => {
+                    return Err(format!(
+                        "Node '{}' is in both namespaces '{}' and '{}'",
+                        id, other, namespace
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+    for graph in fragments
+        .iter()
+        .filter(|(namespace, _)| namespace.is_empty())
+    {
+        for node in graph.1.nodes.values().filter(|n| !is_bare_reference(n)) {
+            if let Some(namespace) = owners.get(node.id.as_str()) {
+                return Err(format!(
+                    "Node '{}' is defined outside namespace '{}', which has it too",
+                    node.id, namespace
+                ));
+            }
+        }
+    }
+    Ok(merge(&prefixed))
+}
+
+/// How [`merge_with`] handles a node id that two fragments define differently
+///
+/// A bare reference (`A` with no label or shape) never conflicts: it links to
+/// the node whichever fragment defines it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdConflict {
+    /// One node; the first definition wins, as in [`merge`]
+    #[default]
+    KeepFirst,
+    /// One node; the last definition wins
+    KeepLast,
+    /// Two nodes: the later fragment's node is renamed to `<name>_<id>` (or
+    /// `<position>_<id>` for a fragment without a name, counting from 1),
+    /// with `_2`, `_3`, ... added while another node or subgraph has that id
+    Rename,
+    /// Fail with an error naming the id and both fragments
+    Error,
+}
+
+/// Options for [`merge_with`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergeOptions {
+    /// Wrap each named fragment in a subgraph whose id and label are the
+    /// fragment's name
+    pub wrap_in_subgraphs: bool,
+    /// What to do when fragments define the same node id differently
+    pub conflicts: IdConflict,
+}
+
+/// Merge named flowchart fragments, e.g. one per service, into a system diagram.
+///
+/// Works like [`merge`], with two additions:
+///
+/// - With `wrap_in_subgraphs`, each fragment with a non-empty name becomes a
+///   subgraph holding its nodes and subgraphs. A node shared by several
+///   fragments goes in the subgraph of the fragment whose definition is kept
+///   (the first to mention it if none defines it); an unnamed fragment isn't
+///   wrapped, so it can declare shared nodes or link the services together.
+/// - `conflicts` picks how a node id defined with a different label or shape
+///   in two fragments is resolved, see [`IdConflict`].
+///
+/// ```
+/// use m2svg::merge::{merge_with, IdConflict, MergeOptions};
+/// use m2svg::{parse_mermaid, DiagramType, MermaidGraph};
+///
+/// fn flowchart(text: &str) -> MermaidGraph {
+///     match parse_mermaid(text).unwrap().diagram {
+///         DiagramType::Flowchart(graph) => graph,
+///         _ => unreachable!(),
+///     }
+/// }
+///
+/// let options = MergeOptions {
+///     wrap_in_subgraphs: true,
+///     conflicts: IdConflict::Rename,
+/// };
+/// let system = merge_with(
+///     &[
+///         ("auth", flowchart("graph LR\n  api[Auth API] --> db[(Users)]")),
+///         ("billing", flowchart("graph LR\n  api[Billing API] --> db[(Ledger)]")),
+///     ],
+///     &options,
+/// )
+/// .unwrap();
+/// assert_eq!(system.node_order, ["api", "db", "billing_api", "billing_db"]);
+/// assert_eq!(system.subgraphs[1].node_ids, ["billing_api", "billing_db"]);
+/// ```
+pub fn merge_with(
+    fragments: &[(&str, MermaidGraph)],
+    options: &MergeOptions,
+) -> Result<MermaidGraph, String> {
+    // The fragment whose definition each node id keeps
+    let mut definitions: HashMap<String, (usize, MermaidNode)> = HashMap::new();
+    let mut graphs = Vec::with_capacity(fragments.len());
+    // Ids a renamed node must not take
+    let mut taken: HashSet<String> = HashSet::new();
+    for (_, graph) in fragments {
+        taken.extend(graph.nodes.keys().cloned());
+        collect_subgraph_ids(&graph.subgraphs, &mut taken);
+    }
+
+    for (index, (name, graph)) in fragments.iter().enumerate() {
+        let mut conflicting = HashSet::new();
+        for node in graph.nodes.values().filter(|n| !is_bare_reference(n)) {
+            match definitions.get(&node.id) {
+                Some((first, kept)) if kept.label != node.label || kept.shape != node.shape => {
+                    match options.conflicts {
+                        IdConflict::KeepFirst => {}
+                        IdConflict::KeepLast => {
+                            definitions.insert(node.id.clone(), (index, node.clone()));
+                        }
+                        IdConflict::Rename => {
+                            conflicting.insert(node.id.clone());
+                        }
+                        IdConflict::Error => {
+                            return Err(format!(
+                                "Node '{}' is defined differently in '{}' and '{}'",
+                                node.id,
+                                fragment_name(fragments, *first),
+                                fragment_name(fragments, index)
+                            ));
+                        }
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    definitions.insert(node.id.clone(), (index, node.clone()));
+                }
+            }
+        }
+
+        if conflicting.is_empty() {
+            graphs.push(graph.clone());
+            continue;
+        }
+        let prefix = if name.is_empty() {
+            format!("{}_", index + 1)
+        } else {
+            format!("{}_", name)
+        };
+        let mut renames = HashMap::new();
+        for id in graph
+            .node_order
+            .iter()
+            .filter(|id| conflicting.contains(*id))
+        {
+            let base = format!("{}{}", prefix, id);
+            let mut new_id = base.clone();
+            let mut n = 2;
+            while taken.contains(&new_id) {
+                new_id = format!("{}_{}", base, n);
+                n += 1;
+            }
+            taken.insert(new_id.clone());
+            renames.insert(id.clone(), new_id);
+        }
+        let renamed = rename_ids(graph, &|id| {
+            renames.get(id).cloned().unwrap_or_else(|| id.to_string())
+        });
+        for new_id in renames.values() {
+            let node = &renamed.nodes[new_id];
+            definitions.insert(node.id.clone(), (index, node.clone()));
+        }
+        graphs.push(renamed);
+    }
+
+    if options.wrap_in_subgraphs {
+        let mut owners: HashMap<&str, usize> = HashMap::new();
+        for (index, graph) in graphs.iter().enumerate() {
+            for id in &graph.node_order {
+                let owner = definitions.get(id).map(|(i, _)| *i).unwrap_or(index);
+                owners.entry(id).or_insert(owner);
+            }
+        }
+        let wrapped: Vec<MermaidGraph> = graphs
+            .iter()
+            .enumerate()
+            .map(|(index, graph)| {
+                let name = fragments[index].0;
+                if name.is_empty() {
+                    return graph.clone();
+                }
+                let mut in_subgraph = HashSet::new();
+                collect_members(&graph.subgraphs, &mut in_subgraph);
+                let mut graph = graph.clone();
+                let node_ids = graph
+                    .node_order
+                    .iter()
+                    .filter(|id| owners[id.as_str()] == index && !in_subgraph.contains(*id))
+                    .cloned()
+                    .collect();
+                graph.subgraphs = vec![MermaidSubgraph {
+                    id: name.to_string(),
+                    label: name.to_string(),
+                    node_ids,
+                    children: std::mem::take(&mut graph.subgraphs),
+                    direction: None,
+                }];
+                graph
+            })
+            .collect();
+        graphs = wrapped;
+    }
+
+    let mut merged = merge(&graphs);
+    if options.conflicts == IdConflict::KeepLast {
+        for (id, (_, node)) in definitions {
+            merged.nodes.insert(id, node);
+        }
+    }
+    Ok(merged)
+}
+
+fn fragment_name(fragments: &[(&str, MermaidGraph)], index: usize) -> String {
+    match fragments[index].0 {
+        "" => format!("fragment {}", index + 1),
+        name => name.to_string(),
+    }
+}
+
+fn collect_subgraph_ids(subgraphs: &[MermaidSubgraph], ids: &mut HashSet<String>) {
+    for sg in subgraphs {
+        ids.insert(sg.id.clone());
+        collect_subgraph_ids(&sg.children, ids);
+    }
+}
+
+fn collect_members<'a>(subgraphs: &'a [MermaidSubgraph], members: &mut HashSet<&'a String>) {
+    for sg in subgraphs {
+        members.extend(sg.node_ids.iter());
+        collect_members(&sg.children, members);
+    }
+}
+
+/// Return a copy of `graph` with `prefix` prepended to every node and subgraph id.
+///
+/// Edges, subgraph membership, `class` assignments, `style` declarations and
+/// `click` links are rewritten to the new ids. Labels and `classDef` names are preserved; a node
+/// that had no explicit label keeps its original id as label.
+pub fn prefix_ids(graph: &MermaidGraph, prefix: &str) -> MermaidGraph {
+    rename_ids(graph, &|id| format!("{}{}", prefix, id))
+}
+
+/// Return a copy of `graph` with every node and subgraph id passed through `rename`
+fn rename_ids(graph: &MermaidGraph, rename: &dyn Fn(&str) -> String) -> MermaidGraph {
+    let mut result = MermaidGraph::new(graph.direction);
+    result.config = graph.config;
+    result.node_order = graph.node_order.iter().map(|id| rename(id)).collect();
+    result.nodes = graph
+        .nodes
+        .values()
+        .map(|node| {
+            let mut node = node.clone();
+            node.id = rename(&node.id);
+            (node.id.clone(), node)
+        })
+        .collect();
+    result.edges = graph
+        .edges
+        .iter()
+        .map(|edge| {
+            let mut edge = edge.clone();
+            edge.source = rename(&edge.source);
+            edge.target = rename(&edge.target);
+            edge
+        })
+        .collect();
+    result.subgraphs = graph
+        .subgraphs
+        .iter()
+        .map(|sg| rename_subgraph(sg, rename))
+        .collect();
+    result.class_defs = graph.class_defs.clone();
+    result.class_assignments = rename_keys(&graph.class_assignments, rename);
+    result.node_styles = rename_keys(&graph.node_styles, rename);
+    result.node_links = rename_keys(&graph.node_links, rename);
+    result
+}
+
+fn rename_subgraph(sg: &MermaidSubgraph, rename: &dyn Fn(&str) -> String) -> MermaidSubgraph {
+    MermaidSubgraph {
+        id: rename(&sg.id),
+        label: sg.label.clone(),
+        node_ids: sg.node_ids.iter().map(|id| rename(id)).collect(),
+        children: sg
+            .children
+            .iter()
+            .map(|child| rename_subgraph(child, rename))
+            .collect(),
+        direction: sg.direction,
+    }
+}
+
+fn rename_keys<V: Clone>(
+    map: &HashMap<String, V>,
+    rename: &dyn Fn(&str) -> String,
+) -> HashMap<String, V> {
+    map.iter().map(|(k, v)| (rename(k), v.clone())).collect()
+}
+
+/// Add a node to the merged graph, or upgrade an existing bare reference.
+fn merge_node(merged: &mut MermaidGraph, node: &MermaidNode) {
+    match merged.nodes.get_mut(&node.id) {
+        Some(existing) => {
+            if is_bare_reference(existing) && !is_bare_reference(node) {
+                *existing = node.clone();
+            }
+        }
+        None => {
+            merged.nodes.insert(node.id.clone(), node.clone());
+            merged.node_order.push(node.id.clone());
+        }
+    }
+}
+
+/// A node that was only referenced by id, without a label or shape
+fn is_bare_reference(node: &MermaidNode) -> bool {
+    node.label == node.id && node.shape == NodeShape::Rectangle
+}
+
+/// What makes two edges the same edge: its ends, labels and how it is drawn
+type EdgeKey<'a> = (
+    (&'a str, &'a str),
+    (Option<&'a str>, Option<&'a str>, Option<&'a str>),
+    (EdgeStyle, EdgeMarker, EdgeMarker, usize),
+    (Option<Port>, Option<Port>),
+);
+
+fn edge_key(edge: &MermaidEdge) -> EdgeKey<'_> {
+    (
+        (&edge.source, &edge.target),
+        (
+            edge.label.as_deref(),
+            edge.start_label.as_deref(),
+            edge.end_label.as_deref(),
+        ),
+        (
+            edge.style,
+            edge.start_marker,
+            edge.end_marker,
+            edge.min_length,
+        ),
+        (edge.start_port, edge.end_port),
+    )
+}
+
+/// Merge a subgraph into a list, combining it with an existing one of the same id
+fn merge_subgraph(list: &mut Vec<MermaidSubgraph>, sg: &MermaidSubgraph) {
+    let Some(existing) = list.iter_mut().find(|s| s.id == sg.id) else {
+        list.push(sg.clone());
+        return;
+    };
+
+    for id in &sg.node_ids {
+        if !existing.node_ids.contains(id) {
+            existing.node_ids.push(id.clone());
+        }
+    }
+    if existing.direction.is_none() {
+        existing.direction = sg.direction;
+    }
+    for child in &sg.children {
+        merge_subgraph(&mut existing.children, child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_mermaid;
+    use crate::types::DiagramType;
+
+    fn flowchart(text: &str) -> MermaidGraph {
+        match parse_mermaid(text).unwrap().diagram {
+            DiagramType::Flowchart(g) => g,
+            _ => panic!("expected flowchart"),
+        }
+    }
+
+    #[test]
+    fn test_merge_unions_nodes_and_edges() {
+        let a = flowchart("graph LR\n  A --> B\n  B --> C");
+        let b = flowchart("graph TD\n  B[Service B] --> D\n  A --> B\n  A -.-> B");
+        let merged = merge(&[a, b]);
+
+        assert_eq!(merged.direction, Direction::LR);
+        assert_eq!(merged.node_order, vec!["A", "B", "C", "D"]);
+        assert_eq!(merged.nodes["B"].label, "Service B");
+        // `A --> B` is dropped as a duplicate, the dotted one is kept
+        assert_eq!(merged.edges.len(), 4);
+        assert_eq!(merged.edges[3].style, EdgeStyle::Dotted);
+    }
+
+    #[test]
+    fn test_merge_combines_styles() {
+        let a = flowchart("graph TD\n  A --> B\n  classDef hot fill:#f00\n  style A fill:#0f0");
+        let b = flowchart(
+            "graph TD\n  A --> C\n  classDef hot stroke:#000\n  class C hot\n  style A stroke:#00f",
+        );
+        let merged = merge(&[a, b]);
+
+        assert_eq!(merged.class_defs["hot"].len(), 2);
+        assert_eq!(merged.class_assignments["C"], "hot");
+        assert_eq!(merged.node_styles["A"]["fill"], "#0f0");
+        assert_eq!(merged.node_styles["A"]["stroke"], "#00f");
+    }
+
+    #[test]
+    fn test_merge_namespaced_keeps_fragments_apart() {
+        let auth = flowchart("graph LR\n  A[Login] --> B\n  style A fill:#f00");
+        let billing = flowchart("graph LR\n  A --> B\n  subgraph S\n    B\n  end");
+        let shared = flowchart("graph LR\n  auth_B --> billing_A");
+        let merged =
+            merge_namespaced(&[("auth", auth), ("billing", billing), ("", shared)]).unwrap();
+
+        assert_eq!(
+            merged.node_order,
+            vec!["auth_A", "auth_B", "billing_A", "billing_B"]
+        );
+        assert_eq!(merged.nodes["auth_A"].label, "Login");
+        assert_eq!(merged.nodes["billing_A"].label, "A");
+        assert_eq!(merged.edges.len(), 3);
+        assert_eq!(merged.subgraphs[0].id, "billing_S");
+        assert_eq!(merged.subgraphs[0].node_ids, vec!["billing_B"]);
+        assert_eq!(merged.node_styles["auth_A"]["fill"], "#f00");
+    }
+
+    #[test]
+    fn test_merge_namespaced_rejects_colliding_ids() {
+        let nested = flowchart("graph LR\n  b_c --> d");
+        let outer = flowchart("graph LR\n  c --> e");
+        let error = merge_namespaced(&[("a", nested), ("a_b", outer)]).unwrap_err();
+        assert_eq!(error, "Node 'a_b_c' is in both namespaces 'a' and 'a_b'");
+
+        // Linking to a prefixed id is fine, defining it again is not
+        let auth = || flowchart("graph LR\n  A[Login] --> B");
+        let link = flowchart("graph LR\n  auth_A --> C");
+        assert!(merge_namespaced(&[("auth", auth()), ("", link)]).is_ok());
+        let literal = flowchart("graph LR\n  auth_A[Legacy login] --> C");
+        let error = merge_namespaced(&[("auth", auth()), ("", literal)]).unwrap_err();
+        assert_eq!(
+            error,
+            "Node 'auth_A' is defined outside namespace 'auth', which has it too"
+        );
+    }
+
+    #[test]
+    fn test_merge_with_wraps_fragments_in_subgraphs() {
+        let auth = flowchart("graph LR\n  login --> gateway\n  subgraph Store\n    users\n  end");
+        let billing = flowchart("graph LR\n  gateway[Gateway] --> invoices");
+        let links = flowchart("graph LR\n  login --> invoices");
+        let options = MergeOptions {
+            wrap_in_subgraphs: true,
+            ..Default::default()
+        };
+        let merged = merge_with(
+            &[("auth", auth), ("billing", billing), ("", links)],
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(merged.subgraphs.len(), 2);
+        assert_eq!(merged.subgraphs[0].id, "auth");
+        assert_eq!(merged.subgraphs[0].node_ids, vec!["login"]);
+        assert_eq!(merged.subgraphs[0].children[0].id, "Store");
+        assert_eq!(merged.subgraphs[1].node_ids, vec!["gateway", "invoices"]);
+        assert_eq!(merged.edges.len(), 3);
+    }
+
+    #[test]
+    fn test_merge_with_resolves_conflicts() {
+        let fragments = [
+            ("a", flowchart("graph TD\n  X[First] --> Y")),
+            ("b", flowchart("graph TD\n  X[Second] --> Z\n  Y")),
+        ];
+        let merge_as = |conflicts| {
+            let options = MergeOptions {
+                conflicts,
+                ..Default::default()
+            };
+            merge_with(&fragments, &options)
+        };
+
+        assert_eq!(
+            merge_as(IdConflict::KeepFirst).unwrap().nodes["X"].label,
+            "First"
+        );
+        assert_eq!(
+            merge_as(IdConflict::KeepLast).unwrap().nodes["X"].label,
+            "Second"
+        );
+
+        let renamed = merge_as(IdConflict::Rename).unwrap();
+        assert_eq!(renamed.node_order, vec!["X", "Y", "b_X", "Z"]);
+        assert_eq!(renamed.nodes["b_X"].label, "Second");
+        assert_eq!(renamed.edges[1].source, "b_X");
+
+        let err = merge_as(IdConflict::Error).unwrap_err();
+        assert_eq!(err, "Node 'X' is defined differently in 'a' and 'b'");
+    }
+
+    #[test]
+    fn test_merge_with_renames_around_taken_ids() {
+        let fragments = [
+            ("a", flowchart("graph TD\n  X[First] --> b_X[Taken]")),
+            ("b", flowchart("graph TD\n  X[Second] --> b_X_2")),
+            ("c", flowchart("graph TD\n  b_X_3[Later] --> X[Third]")),
+        ];
+        let options = MergeOptions {
+            conflicts: IdConflict::Rename,
+            ..Default::default()
+        };
+        let merged = merge_with(&fragments, &options).unwrap();
+        assert_eq!(merged.nodes["b_X"].label, "Taken");
+        assert_eq!(merged.nodes["b_X_4"].label, "Second");
+        assert_eq!(merged.nodes["b_X_3"].label, "Later");
+        assert_eq!(merged.nodes["c_X"].label, "Third");
+        assert_eq!(merged.nodes.len(), 6);
+    }
+}
@@ -0,0 +1,56 @@
+//! Box widths of flowchart nodes and classes
+//!
+//! Used by the `node_width` render options: boxes are normally as wide as
+//! their text, and [`NodeWidth`] can make them all as wide as the widest one,
+//! or keep them within a minimum and maximum. Widths are in columns for text
+//! output and in px for SVG.
+
+use crate::ascii::canvas::{text_width, truncate_to_width};
+
+/// How wide to make the boxes of flowchart nodes and classes
+///
+/// # Example
+/// ```rust
+/// use m2svg::{render_mermaid_ascii, AsciiRenderOptions, NodeWidth};
+///
+/// let options = AsciiRenderOptions {
+///     node_width: NodeWidth { uniform: true, ..Default::default() },
+///     ..Default::default()
+/// };
+/// let text = render_mermaid_ascii("graph TD\n  A[Start] --> B[Keep going]", Some(options)).unwrap();
+/// assert!(text.contains("|   Start    |"));
+/// assert!(text.contains("| Keep going |"));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct NodeWidth {
+    /// Make every box as wide as the widest one. Default: false
+    pub uniform: bool,
+    /// Narrowest box (0 = no minimum). Default: 0
+    pub min: usize,
+    /// Widest box; flowchart labels wider than fits wrap onto more lines and
+    /// class members are cut with an ellipsis (0 = no maximum). Default: 0
+    pub max: usize,
+}
+
+impl NodeWidth {
+    /// The width of a box `width` wide, in a diagram whose widest box is
+    /// `widest` wide
+    pub fn fit(&self, width: f64, widest: f64) -> f64 {
+        let width = if self.uniform { widest } else { width };
+        width.max(self.min as f64)
+    }
+
+    /// Room for text in a box with `padding` around it, under the maximum
+    pub fn text_room(&self, padding: usize) -> Option<usize> {
+        (self.max > 0).then(|| self.max.saturating_sub(padding).max(1))
+    }
+}
+
+/// `text` cut to at most `room` columns, ending in `ellipsis` if it was cut
+pub(crate) fn cut_to_width(text: &str, room: usize, ellipsis: &str) -> String {
+    if text_width(text) <= room {
+        return text.to_string();
+    }
+    let keep = truncate_to_width(text, room.saturating_sub(text_width(ellipsis)));
+    format!("{}{}", keep.trim_end(), ellipsis)
+}
@@ -1,9 +1,11 @@
 //! Class diagram parser
 
+use super::flowchart::parse_style_props;
 use crate::types::{
     ClassDiagram, ClassMember, ClassNamespace, ClassNode, ClassNote, ClassRelationship,
-    RelationshipType, Visibility,
+    FrontmatterConfig, RelationshipType, Visibility,
 };
+use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::HashMap;
@@ -25,19 +27,89 @@ lazy_static! {
     static ref RE_LOLLIPOP_RIGHT: Regex = Regex::new(r"^(\S+)\s+--\(\)\s+(\S+)$").unwrap();
     static ref RE_LOLLIPOP_LEFT: Regex = Regex::new(r"^(\S+)\s+\(\)--\s+(\S+)$").unwrap();
     static ref RE_CLASS_INLINE_ANNOT: Regex = Regex::new(r"^class\s+(\S+?)\s+<<(\w+)>>$").unwrap();
+
+    /// Relationship lines by arrow, as `[FROM] ["card1"] ARROW ["card2"] [TO] [: label]`;
+    /// `true` puts the marker at the `from` end
+    static ref RELATIONSHIP_PATTERNS: Vec<(Regex, RelationshipType, bool)> = [
+        // Prefix markers - marker at 'from' side
+        ("<|--", RelationshipType::Inheritance, true),
+        ("*--", RelationshipType::Composition, true),
+        ("o--", RelationshipType::Aggregation, true),
+        // Suffix markers - marker at 'to' side
+        ("-->", RelationshipType::Association, false),
+        ("..>", RelationshipType::Dependency, false),
+        ("..|>", RelationshipType::Realization, false),
+        // Reversed patterns
+        ("--|>", RelationshipType::Inheritance, false),
+        ("--*", RelationshipType::Composition, false),
+        ("--o", RelationshipType::Aggregation, false),
+        ("<--", RelationshipType::Association, true),
+        ("<..", RelationshipType::Dependency, true),
+        ("<|..", RelationshipType::Realization, true),
+        // Plain links (must come after longer patterns)
+        ("--", RelationshipType::Association, false),
+        ("..", RelationshipType::Dependency, false),
+    ]
+    .into_iter()
+    .map(|(arrow, rel_type, marker_at_from)| {
+        let pattern = format!(
+            r#"^(\S+)\s+(?:"([^"]*)")?\s*{}\s*(?:"([^"]*)")?\s*(\S+)(?:\s*:\s*(.+))?$"#,
+            regex::escape(arrow)
+        );
+        (Regex::new(&pattern).unwrap(), rel_type, marker_at_from)
+    })
+    .collect();
 }
 
-/// Parse a Mermaid class diagram
-pub fn parse_class_diagram(lines: &[&str]) -> Result<ClassDiagram, String> {
+/// Parse the `stereotypeStyles:` map of the frontmatter: one
+/// `stereotype: key:value,key:value` line per stereotype, indented under it.
+/// Stereotype names are lowercased.
+pub fn parse_stereotype_styles(
+    frontmatter: &FrontmatterConfig,
+) -> HashMap<String, HashMap<String, String>> {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let mut styles = HashMap::new();
+    let mut block_indent = None;
+    for line in &frontmatter.raw_lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match block_indent {
+            Some(base) if indent(line) > base => {
+                if let Some((name, props)) = line.trim().split_once(':') {
+                    let props = props.trim().trim_matches('\'').trim_matches('"');
+                    styles.insert(
+                        name.trim()
+                            .trim_matches('\'')
+                            .trim_matches('"')
+                            .to_lowercase(),
+                        parse_style_props(props),
+                    );
+                }
+            }
+            _ => {
+                block_indent = (line.trim() == "stereotypeStyles:").then(|| indent(line));
+            }
+        }
+    }
+    styles
+}
+
+/// Parse a Mermaid class diagram. The indices of the lines that aren't
+/// statements go to `skipped`.
+pub fn parse_class_diagram(
+    lines: &[&str],
+    skipped: &mut Vec<usize>,
+) -> Result<ClassDiagram, String> {
     let mut diagram = ClassDiagram::new();
-    let mut class_map: HashMap<String, ClassNode> = HashMap::new();
-    let mut class_order: Vec<String> = Vec::new(); // Track insertion order
+    // In the order the classes are first mentioned
+    let mut class_map: IndexMap<String, ClassNode> = IndexMap::new();
     let mut current_namespace: Option<ClassNamespace> = None;
     let mut current_class: Option<String> = None;
     let mut brace_depth = 0;
     let mut lollipop_counter: usize = 0;
 
-    for line in lines.iter().skip(1) {
+    for (index, line) in lines.iter().enumerate().skip(1) {
         let line = *line;
 
         // Inside a class body block
@@ -61,14 +133,16 @@ pub fn parse_class_diagram(lines: &[&str]) -> Result<ClassDiagram, String> {
             }
 
             // Parse member
-            if let Some(parsed) = parse_member(line) {
-                if let Some(ref class_id) = current_class {
-                    if let Some(cls) = class_map.get_mut(class_id) {
-                        if parsed.is_method {
-                            cls.methods.push(parsed.member);
-                        } else {
-                            cls.attributes.push(parsed.member);
-                        }
+            let Some(parsed) = parse_member(line) else {
+                skipped.push(index);
+                continue;
+            };
+            if let Some(ref class_id) = current_class {
+                if let Some(cls) = class_map.get_mut(class_id) {
+                    if parsed.is_method {
+                        cls.methods.push(parsed.member);
+                    } else {
+                        cls.attributes.push(parsed.member);
                     }
                 }
             }
@@ -121,7 +195,7 @@ pub fn parse_class_diagram(lines: &[&str]) -> Result<ClassDiagram, String> {
             let id = caps[1].to_string();
             let generic = caps.get(2).map(|m| m.as_str());
 
-            let cls = ensure_class(&mut class_map, &mut class_order, &id);
+            let cls = ensure_class(&mut class_map, &id);
             if let Some(g) = generic {
                 cls.label = format!("{}<{}>", id, g);
             }
@@ -139,7 +213,7 @@ pub fn parse_class_diagram(lines: &[&str]) -> Result<ClassDiagram, String> {
             let id = caps[1].to_string();
             let generic = caps.get(2).map(|m| m.as_str());
 
-            let cls = ensure_class(&mut class_map, &mut class_order, &id);
+            let cls = ensure_class(&mut class_map, &id);
             if let Some(g) = generic {
                 cls.label = format!("{}<{}>", id, g);
             }
@@ -152,21 +226,21 @@ pub fn parse_class_diagram(lines: &[&str]) -> Result<ClassDiagram, String> {
 
         // Inline annotation: `class ClassName { <<interface>> }`
         if let Some(caps) = RE_INLINE_ANNOT.captures(line) {
-            let cls = ensure_class(&mut class_map, &mut class_order, &caps[1]);
+            let cls = ensure_class(&mut class_map, &caps[1]);
             cls.annotation = Some(caps[2].to_string());
             continue;
         }
 
         // Class with inline annotation: `class Shape <<interface>>`
         if let Some(caps) = RE_CLASS_INLINE_ANNOT.captures(line) {
-            let cls = ensure_class(&mut class_map, &mut class_order, &caps[1]);
+            let cls = ensure_class(&mut class_map, &caps[1]);
             cls.annotation = Some(caps[2].to_string());
             continue;
         }
 
         // Separate annotation: `<<interface>> Shape`
         if let Some(caps) = RE_SEPARATE_ANNOTATION.captures(line) {
-            let cls = ensure_class(&mut class_map, &mut class_order, &caps[2]);
+            let cls = ensure_class(&mut class_map, &caps[2]);
             cls.annotation = Some(caps[1].to_string());
             continue;
         }
@@ -178,8 +252,8 @@ pub fn parse_class_diagram(lines: &[&str]) -> Result<ClassDiagram, String> {
             // Each lollipop interface gets a unique ID so duplicates don't merge
             let to_id = format!("__lollipop_{}_{}", to_label, lollipop_counter);
             lollipop_counter += 1;
-            ensure_class(&mut class_map, &mut class_order, &from);
-            ensure_class(&mut class_map, &mut class_order, &to_id);
+            ensure_class(&mut class_map, &from);
+            ensure_class(&mut class_map, &to_id);
             // Set the display label and mark as lollipop
             if let Some(cls) = class_map.get_mut(&to_id) {
                 cls.label = to_label;
@@ -204,8 +278,8 @@ pub fn parse_class_diagram(lines: &[&str]) -> Result<ClassDiagram, String> {
             // Each lollipop interface gets a unique ID so duplicates don't merge
             let from_id = format!("__lollipop_{}_{}", from_label, lollipop_counter);
             lollipop_counter += 1;
-            ensure_class(&mut class_map, &mut class_order, &from_id);
-            ensure_class(&mut class_map, &mut class_order, &to);
+            ensure_class(&mut class_map, &from_id);
+            ensure_class(&mut class_map, &to);
             // Set the display label and mark as lollipop
             if let Some(cls) = class_map.get_mut(&from_id) {
                 cls.label = from_label;
@@ -235,7 +309,7 @@ pub fn parse_class_diagram(lines: &[&str]) -> Result<ClassDiagram, String> {
                 && !rest.contains("..>")
                 && !rest.contains("..|>")
             {
-                let cls = ensure_class(&mut class_map, &mut class_order, &caps[1]);
+                let cls = ensure_class(&mut class_map, &caps[1]);
                 if let Some(parsed) = parse_member(rest) {
                     if parsed.is_method {
                         cls.methods.push(parsed.member);
@@ -249,41 +323,30 @@ pub fn parse_class_diagram(lines: &[&str]) -> Result<ClassDiagram, String> {
 
         // Relationship
         if let Some(rel) = parse_relationship(line) {
-            ensure_class(&mut class_map, &mut class_order, &rel.from);
-            ensure_class(&mut class_map, &mut class_order, &rel.to);
+            ensure_class(&mut class_map, &rel.from);
+            ensure_class(&mut class_map, &rel.to);
             diagram.relationships.push(rel);
             continue;
         }
+
+        skipped.push(index);
     }
 
-    // Convert to ordered list
-    diagram.classes = class_order
-        .iter()
-        .filter_map(|id| class_map.remove(id))
-        .collect();
+    diagram.classes = class_map.into_values().collect();
     Ok(diagram)
 }
 
-fn ensure_class<'a>(
-    class_map: &'a mut HashMap<String, ClassNode>,
-    class_order: &mut Vec<String>,
-    id: &str,
-) -> &'a mut ClassNode {
-    if !class_map.contains_key(id) {
-        class_map.insert(
-            id.to_string(),
-            ClassNode {
-                id: id.to_string(),
-                label: id.to_string(),
-                attributes: Vec::new(),
-                methods: Vec::new(),
-                annotation: None,
-                is_lollipop: false,
-            },
-        );
-        class_order.push(id.to_string());
-    }
-    class_map.get_mut(id).unwrap()
+fn ensure_class<'a>(class_map: &'a mut IndexMap<String, ClassNode>, id: &str) -> &'a mut ClassNode {
+    class_map
+        .entry(id.to_string())
+        .or_insert_with(|| ClassNode {
+            id: id.to_string(),
+            label: id.to_string(),
+            attributes: Vec::new(),
+            methods: Vec::new(),
+            annotation: None,
+            is_lollipop: false,
+        })
 }
 
 struct ParsedMember {
@@ -316,7 +379,11 @@ fn parse_member(line: &str) -> Option<ParsedMember> {
     if let Some(caps) = RE_METHOD.captures(rest) {
         let name = caps[1].trim().to_string();
         let params_raw = caps[2].trim();
-        let type_str = caps.get(3).map(|m| m.as_str().trim().to_string());
+        // The return type, without a classifier before or after it (`()$ int`)
+        let type_str = caps
+            .get(3)
+            .map(|m| m.as_str().trim_matches(['$', '*']).trim().to_string())
+            .filter(|t| !t.is_empty());
 
         // Extract just the parameter names (strip types)
         // e.g. "int amount, String name" -> "amount, name"
@@ -454,41 +521,8 @@ fn convert_tilde_generics(s: &str) -> String {
 }
 
 fn parse_relationship(line: &str) -> Option<ClassRelationship> {
-    // Pattern: [FROM] ["card1"] ARROW ["card2"] [TO] [: label]
-    // Arrows: <|--, *--, o--, -->, ..|>, ..>, --, ..
-    // marker_at_from: true = marker at 'from' end, false = marker at 'to' end
-
-    let arrow_patterns: &[(&str, RelationshipType, bool)] = &[
-        // Prefix markers - marker at 'from' side
-        ("<|--", RelationshipType::Inheritance, true),
-        ("*--", RelationshipType::Composition, true),
-        ("o--", RelationshipType::Aggregation, true),
-        // Suffix markers - marker at 'to' side
-        ("-->", RelationshipType::Association, false),
-        ("..>", RelationshipType::Dependency, false),
-        ("..|>", RelationshipType::Realization, false),
-        // Reversed patterns
-        ("--|>", RelationshipType::Inheritance, false),
-        ("--*", RelationshipType::Composition, false),
-        ("--o", RelationshipType::Aggregation, false),
-        ("<--", RelationshipType::Association, true),
-        ("<..", RelationshipType::Dependency, true),
-        ("<|..", RelationshipType::Realization, true),
-        // Plain links (must come after longer patterns)
-        ("--", RelationshipType::Association, false),
-        ("..", RelationshipType::Dependency, false),
-    ];
-
-    for &(arrow, rel_type, marker_at_from) in arrow_patterns {
-        // Try to find the arrow in the line, with spaces around it
-        // Must handle: FROM ARROW TO, FROM "card" ARROW "card" TO
-        let arrow_escaped = regex::escape(arrow);
-        let pattern = format!(
-            r#"^(\S+)\s+(?:"([^"]*)")?\s*{}\s*(?:"([^"]*)")?\s*(\S+)(?:\s*:\s*(.+))?$"#,
-            arrow_escaped
-        );
-
-        if let Some(caps) = Regex::new(&pattern).ok()?.captures(line) {
+    for (re, rel_type, marker_at_from) in RELATIONSHIP_PATTERNS.iter() {
+        if let Some(caps) = re.captures(line) {
             let from = caps[1].to_string();
             let from_card = caps.get(2).map(|m| m.as_str().to_string());
             let to_card = caps.get(3).map(|m| m.as_str().to_string());
@@ -498,11 +532,11 @@ fn parse_relationship(line: &str) -> Option<ClassRelationship> {
             return Some(ClassRelationship {
                 from,
                 to,
-                rel_type,
+                rel_type: *rel_type,
                 from_cardinality: from_card,
                 to_cardinality: to_card,
                 label,
-                marker_at_from,
+                marker_at_from: *marker_at_from,
             });
         }
     }
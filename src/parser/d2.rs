@@ -0,0 +1,465 @@
+//! D2 front-end (the `d2` feature)
+//!
+//! Reads simple D2 diagrams into a [`MermaidGraph`], so the flowchart
+//! renderers can draw them. Shapes, labels, connections (`->`, `<-`, `<->`,
+//! `--`) with labels, chains of them, containers (`a: { b -> c }` or
+//! `a.b -> a.c`), `direction`, `shape`, dashed strokes and `fill`/`stroke`
+//! colors are understood; icons, classes, layers and other styling are
+//! skipped.
+
+use std::collections::HashMap;
+
+use crate::types::{
+    DiagramType, Direction, EdgeMarker, EdgeStyle, FrontmatterConfig, MermaidEdge, MermaidGraph,
+    MermaidNode, MermaidSubgraph, NodeShape, ParsedDiagram,
+};
+
+/// Parse a D2 diagram.
+///
+/// A shape that holds other shapes is drawn as a box around them (a
+/// subgraph); ids of shapes inside one are qualified with its id (`cloud.db`).
+///
+/// # Example
+/// ```rust
+/// use m2svg::{parse_d2, DiagramType, NodeShape};
+///
+/// let parsed = parse_d2(
+///     "direction: right\nclient -> api: HTTPS\napi -> cloud.db: SQL\ncloud.db.shape: cylinder",
+/// )
+/// .unwrap();
+/// let DiagramType::Flowchart(graph) = &parsed.diagram else { unreachable!() };
+/// assert_eq!(graph.node_order, ["client", "api", "cloud.db"]);
+/// assert_eq!(graph.nodes["cloud.db"].shape, NodeShape::Cylinder);
+/// assert_eq!(graph.subgraphs[0].label, "cloud");
+/// assert_eq!(graph.edges[0].label.as_deref(), Some("HTTPS"));
+/// ```
+pub fn parse_d2(text: &str) -> Result<ParsedDiagram, String> {
+    let chars: Vec<char> = strip_comments(text).chars().collect();
+    let mut parser = Parser {
+        chars,
+        pos: 0,
+        direction: Direction::TB,
+        shapes: Vec::new(),
+        edges: Vec::new(),
+    };
+    parser.block("")?;
+    if parser.pos < parser.chars.len() {
+        return Err("Unmatched '}' in D2 input".to_string());
+    }
+    Ok(ParsedDiagram {
+        diagram: DiagramType::Flowchart(parser.into_graph()),
+        frontmatter: FrontmatterConfig::default(),
+    })
+}
+
+/// Drop `#` comments, which start a line or follow whitespace outside quotes
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.lines() {
+        let mut quote = None;
+        let mut prev = ' ';
+        for c in line.chars() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None if c == '"' || c == '\'' => quote = Some(c),
+                None if c == '#' && prev.is_whitespace() => break,
+                None => {}
+            }
+            out.push(c);
+            prev = c;
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// A shape as declared so far
+struct Shape {
+    /// Full id, qualified with its containers: `cloud.db`
+    id: String,
+    label: Option<String>,
+    shape: NodeShape,
+    style: HashMap<String, String>,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    direction: Direction,
+    /// In the order they are first mentioned
+    shapes: Vec<Shape>,
+    edges: Vec<MermaidEdge>,
+}
+
+/// Keywords that set a property of the shape (or connection) they follow
+const PROPERTIES: &[&str] = &[
+    "shape",
+    "label",
+    "style",
+    "icon",
+    "near",
+    "width",
+    "height",
+    "tooltip",
+    "link",
+    "class",
+    "constraint",
+    "direction",
+    "source-arrowhead",
+    "target-arrowhead",
+    "grid-rows",
+    "grid-columns",
+];
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    /// Statements up to the `}` closing the block of `prefix` (or the end of
+    /// the input at the top level). Returns whether any shape or connection
+    /// was declared inside.
+    fn block(&mut self, prefix: &str) -> Result<bool, String> {
+        let mut declared = false;
+        loop {
+            while self.peek().is_some_and(|c| c.is_whitespace() || c == ';') {
+                self.pos += 1;
+            }
+            match self.peek() {
+                None => return Ok(declared),
+                Some('}') if !prefix.is_empty() => {
+                    self.pos += 1;
+                    return Ok(declared);
+                }
+                Some('}') => return Ok(declared),
+                _ => declared |= self.statement(prefix)?,
+            }
+        }
+    }
+
+    /// Text up to one of `stops` (outside quotes), with its quotes removed
+    /// and trimmed, or `None` if it is empty
+    fn text(&mut self, stops: &[char]) -> Option<String> {
+        let mut text = String::new();
+        let mut quote = None;
+        while let Some(c) = self.peek() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) if c == '\\' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some('n') => text.push('\n'),
+                        Some(other) => text.push(other),
+                        None => break,
+                    }
+                }
+                Some(_) => text.push(c),
+                None if c == '"' || c == '\'' => quote = Some(c),
+                None if stops.contains(&c) => break,
+                None => text.push(c),
+            }
+            self.pos += 1;
+        }
+        let text = text.trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    fn statement(&mut self, prefix: &str) -> Result<bool, String> {
+        let key = self.text(&[':', '{', '}', ';', '\n']).unwrap_or_default();
+        let value = if self.peek() == Some(':') {
+            self.pos += 1;
+            self.text(&['{', '}', ';', '\n'])
+        } else {
+            None
+        };
+        let has_block = self.peek() == Some('{');
+        if has_block {
+            self.pos += 1;
+        }
+
+        // A connection, or a chain of them
+        let parts = split_connections(&key);
+        if parts.len() > 1 {
+            let ends: Vec<String> = parts
+                .iter()
+                .step_by(2)
+                .map(|p| self.ensure_shape(&qualify(prefix, p)))
+                .collect();
+            let mut style = EdgeStyle::Solid;
+            if has_block {
+                // Only the stroke of a connection is read from its block
+                let start = self.pos;
+                self.block(&format!("{}\u{0}", prefix))?;
+                let body: String = self.chars[start..self.pos].iter().collect();
+                if body.contains("stroke-dash") {
+                    style = EdgeStyle::Dotted;
+                }
+            }
+            for (i, pair) in ends.windows(2).enumerate() {
+                let op = parts[2 * i + 1].as_str();
+                let mut edge = MermaidEdge::new(&pair[0], &pair[1]);
+                edge.label = value.clone();
+                edge.style = style;
+                (edge.start_marker, edge.end_marker) = match op {
+                    "<-" => (EdgeMarker::Arrow, EdgeMarker::None),
+                    "<->" => (EdgeMarker::Arrow, EdgeMarker::Arrow),
+                    "--" => (EdgeMarker::None, EdgeMarker::None),
+                    _ => (EdgeMarker::None, EdgeMarker::Arrow),
+                };
+                self.edges.push(edge);
+            }
+            return Ok(true);
+        }
+
+        // A property: `direction: right`, `x.shape: circle`, `style.fill: red`
+        let segments: Vec<&str> = key.split('.').map(str::trim).collect();
+        if let Some(at) = segments.iter().position(|s| PROPERTIES.contains(s)) {
+            let owner = segments[..at].join(".");
+            let property = segments[at..].join(".");
+            if has_block {
+                // `style: { fill: red }`
+                self.property_block(prefix, &owner, &property)?;
+            } else if let Some(value) = value {
+                self.set_property(prefix, &owner, &property, &value);
+            }
+            return Ok(false);
+        }
+
+        // A shape, with an optional label and block
+        if key.is_empty() {
+            if has_block {
+                self.block(&format!("{}\u{0}", prefix))?;
+            }
+            return Ok(false);
+        }
+        let id = self.ensure_shape(&qualify(prefix, &key));
+        if let Some(label) = value {
+            self.shape_mut(&id).label = Some(label);
+        }
+        if has_block {
+            self.block(&id)?;
+        }
+        Ok(true)
+    }
+
+    /// `style: { fill: red; stroke: blue }` and the like
+    fn property_block(&mut self, prefix: &str, owner: &str, property: &str) -> Result<(), String> {
+        loop {
+            while self.peek().is_some_and(|c| c.is_whitespace() || c == ';') {
+                self.pos += 1;
+            }
+            match self.peek() {
+                None => return Err("Unterminated block in D2 input".to_string()),
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => {}
+            }
+            let key = self.text(&[':', '{', '}', ';', '\n']).unwrap_or_default();
+            if self.peek() == Some(':') {
+                self.pos += 1;
+            }
+            if self.peek() == Some('{') {
+                self.pos += 1;
+                self.property_block(prefix, owner, &format!("{}.{}", property, key))?;
+            } else if let Some(value) = self.text(&['{', '}', ';', '\n']) {
+                self.set_property(prefix, owner, &format!("{}.{}", property, key), &value);
+            }
+        }
+    }
+
+    fn set_property(&mut self, prefix: &str, owner: &str, property: &str, value: &str) {
+        // Properties without an owner belong to the shape whose block they
+        // are in, or to the diagram at the top level
+        let owner = match (owner.is_empty(), prefix.is_empty()) {
+            (true, true) => {
+                if property == "direction" {
+                    self.direction = match value {
+                        "right" => Direction::LR,
+                        "left" => Direction::RL,
+                        "up" => Direction::BT,
+                        _ => Direction::TB,
+                    };
+                }
+                return;
+            }
+            (true, false) => prefix.to_string(),
+            (false, _) => qualify(prefix, owner),
+        };
+        // Blocks of connections and other non-shapes are marked with a NUL
+        if owner.contains('\u{0}') {
+            return;
+        }
+        let id = self.ensure_shape(&owner);
+        let shape = self.shape_mut(&id);
+        match property {
+            "label" => shape.label = Some(value.to_string()),
+            "shape" => {
+                shape.shape = match value {
+                    "circle" => NodeShape::Circle,
+                    "oval" | "cloud" => NodeShape::Rounded,
+                    "diamond" => NodeShape::Diamond,
+                    "hexagon" => NodeShape::Hexagon,
+                    "cylinder" | "stored_data" => NodeShape::Cylinder,
+                    "parallelogram" => NodeShape::Trapezoid,
+                    "queue" => NodeShape::Stadium,
+                    "step" | "callout" => NodeShape::Asymmetric,
+                    _ => NodeShape::Rectangle,
+                }
+            }
+            "style.fill" => {
+                shape.style.insert("fill".to_string(), value.to_string());
+            }
+            "style.stroke" => {
+                shape.style.insert("stroke".to_string(), value.to_string());
+            }
+            "style.font-color" => {
+                shape.style.insert("color".to_string(), value.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    /// Declare `id` and the containers in its path, returning it
+    fn ensure_shape(&mut self, id: &str) -> String {
+        let mut path = String::new();
+        for segment in id.split('.') {
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path.push_str(segment.trim());
+            if !self.shapes.iter().any(|s| s.id == path) {
+                self.shapes.push(Shape {
+                    id: path.clone(),
+                    label: None,
+                    shape: NodeShape::Rectangle,
+                    style: HashMap::new(),
+                });
+            }
+        }
+        path
+    }
+
+    fn shape_mut(&mut self, id: &str) -> &mut Shape {
+        let index = self
+            .shapes
+            .iter()
+            .position(|s| s.id == id)
+            .expect("shape declared before use");
+        &mut self.shapes[index]
+    }
+
+    /// Shapes holding others become subgraphs, the rest nodes
+    fn into_graph(self) -> MermaidGraph {
+        let mut graph = MermaidGraph::new(self.direction);
+        let parent = |id: &str| id.rsplit_once('.').map(|(p, _)| p.to_string());
+        let is_container = |id: &str| {
+            self.shapes
+                .iter()
+                .any(|s| parent(&s.id).as_deref() == Some(id))
+        };
+        for shape in &self.shapes {
+            if is_container(&shape.id) {
+                continue;
+            }
+            let label = shape
+                .label
+                .clone()
+                .unwrap_or_else(|| last_segment(&shape.id).to_string());
+            graph.node_order.push(shape.id.clone());
+            graph.nodes.insert(
+                shape.id.clone(),
+                MermaidNode {
+                    id: shape.id.clone(),
+                    label,
+                    shape: shape.shape,
+                },
+            );
+            if !shape.style.is_empty() {
+                graph
+                    .node_styles
+                    .insert(shape.id.clone(), shape.style.clone());
+            }
+        }
+
+        fn subgraph(
+            shapes: &[Shape],
+            id: &str,
+            is_container: &dyn Fn(&str) -> bool,
+        ) -> MermaidSubgraph {
+            let shape = shapes.iter().find(|s| s.id == id);
+            let children_of = |container: bool| {
+                shapes
+                    .iter()
+                    .filter(move |s| {
+                        s.id.rsplit_once('.').map(|(p, _)| p) == Some(id)
+                            && is_container(&s.id) == container
+                    })
+                    .map(|s| s.id.clone())
+            };
+            MermaidSubgraph {
+                id: id.to_string(),
+                label: shape
+                    .and_then(|s| s.label.clone())
+                    .unwrap_or_else(|| last_segment(id).to_string()),
+                node_ids: children_of(false).collect(),
+                children: children_of(true)
+                    .map(|child| subgraph(shapes, &child, is_container))
+                    .collect(),
+                direction: None,
+            }
+        }
+        graph.subgraphs = self
+            .shapes
+            .iter()
+            .filter(|s| !s.id.contains('.') && is_container(&s.id))
+            .map(|s| subgraph(&self.shapes, &s.id, &is_container))
+            .collect();
+        graph.edges = self.edges;
+        graph
+    }
+}
+
+fn last_segment(id: &str) -> &str {
+    id.rsplit('.').next().unwrap_or(id)
+}
+
+/// `key` inside the block of `prefix`
+fn qualify(prefix: &str, key: &str) -> String {
+    let key = key.trim();
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+/// Split `a -> b <- c` into `["a", "->", "b", "<-", "c"]`
+fn split_connections(key: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let chars: Vec<char> = key.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+        let op = ["<->", "->", "<-", "--"]
+            .into_iter()
+            .find(|op| rest.starts_with(op));
+        match op {
+            Some(op) => {
+                parts.push(current.trim().to_string());
+                parts.push(op.to_string());
+                current.clear();
+                i += op.len();
+            }
+            None => {
+                current.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    parts.push(current.trim().to_string());
+    parts
+}
@@ -0,0 +1,626 @@
+//! Graphviz DOT front-end (the `dot` feature)
+//!
+//! Reads simple `graph` and `digraph` files into a [`MermaidGraph`], so the
+//! flowchart renderers can draw them, in the terminal too. Nodes, edges and
+//! edge chains, `cluster` subgraphs, `rankdir`, default `node`/`edge`
+//! attributes and the common `label`, `shape`, `style`, `dir`, `arrowhead`
+//! and color attributes are understood, as are the `n`, `s`, `e` and `w`
+//! compass points of edge ends; layout hints such as `rank` or `weight` are
+//! skipped, since m2svg does its own layout.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::types::{
+    DiagramType, Direction, EdgeMarker, EdgeStyle, FrontmatterConfig, MermaidEdge, MermaidGraph,
+    MermaidNode, MermaidSubgraph, NodeShape, ParsedDiagram, Port,
+};
+
+/// Parse a DOT graph.
+///
+/// Nodes are ellipses unless given another `shape`, as in Graphviz. Only
+/// subgraphs named `cluster...` are drawn, as boxes around their nodes; other
+/// subgraphs only group statements.
+///
+/// # Example
+/// ```rust
+/// use m2svg::{parse_dot, render_parsed_ascii, DiagramType};
+///
+/// let parsed = parse_dot(
+///     r#"digraph { rankdir=LR; start [shape=box]; start -> check -> done [label="ok"] }"#,
+/// )
+/// .unwrap();
+/// let DiagramType::Flowchart(graph) = &parsed.diagram else { unreachable!() };
+/// assert_eq!(graph.node_order, ["start", "check", "done"]);
+/// assert_eq!(graph.edges[1].label.as_deref(), Some("ok"));
+/// assert!(render_parsed_ascii(&parsed, None).unwrap().contains("start"));
+/// ```
+pub fn parse_dot(text: &str) -> Result<ParsedDiagram, String> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        graph: MermaidGraph::new(Direction::TB),
+        directed: true,
+        node_defaults: HashMap::new(),
+        edge_defaults: HashMap::new(),
+        placed: HashSet::new(),
+        graph_label: None,
+    };
+    parser.parse_graph()?;
+    Ok(ParsedDiagram {
+        diagram: DiagramType::Flowchart(parser.graph),
+        frontmatter: FrontmatterConfig::default(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// An identifier, number, quoted string or HTML string
+    Id(String),
+    /// `->` or `--`
+    EdgeOp,
+    Punct(char),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line_start = true;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '\n' {
+            line_start = true;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        // `#` lines are preprocessor output; `//` and `/* */` are comments
+        if (c == '#' && line_start) || (c == '/' && next == Some('/')) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        line_start = false;
+        if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+            continue;
+        }
+        if c == '-' && matches!(next, Some('>') | Some('-')) {
+            tokens.push(Token::EdgeOp);
+            i += 2;
+            continue;
+        }
+        if c == '"' {
+            let mut value = String::new();
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                // Only an escaped quote loses its backslash; `\n` and the
+                // like are label escapes, handled with the label
+                if chars[i] == '\\' && chars.get(i + 1) == Some(&'"') {
+                    i += 1;
+                } else if chars[i] == '\\' && chars.get(i + 1) == Some(&'\n') {
+                    i += 2;
+                    continue;
+                }
+                value.push(chars[i]);
+                i += 1;
+            }
+            if i == chars.len() {
+                return Err("Unterminated string in DOT input".to_string());
+            }
+            i += 1;
+            tokens.push(Token::Id(value));
+            continue;
+        }
+        if c == '<' {
+            // HTML string: keep the text, drop the markup
+            let mut depth = 0;
+            let mut value = String::new();
+            let mut in_tag = false;
+            while i < chars.len() {
+                match chars[i] {
+                    '<' => {
+                        depth += 1;
+                        if depth > 1 {
+                            in_tag = true;
+                        }
+                    }
+                    '>' => {
+                        depth -= 1;
+                        in_tag = false;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    ch if !in_tag => value.push(ch),
+                    _ => {}
+                }
+                i += 1;
+            }
+            if depth != 0 {
+                return Err("Unterminated HTML string in DOT input".to_string());
+            }
+            i += 1;
+            tokens.push(Token::Id(value.trim().to_string()));
+            continue;
+        }
+        if c.is_alphanumeric()
+            || c == '_'
+            || c == '.'
+            || (c == '-' && next.is_some_and(|n| n.is_ascii_digit() || n == '.'))
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Id(chars[start..i].iter().collect()));
+            continue;
+        }
+        if "{}[]=;,:+".contains(c) {
+            tokens.push(Token::Punct(c));
+            i += 1;
+            continue;
+        }
+        return Err(format!("Unexpected character '{}' in DOT input", c));
+    }
+
+    // Join `"a" + "b"` string concatenations
+    let mut joined: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut iter = tokens.into_iter().peekable();
+    while let Some(token) = iter.next() {
+        if token == Token::Punct('+') {
+            if let (Some(Token::Id(left)), Some(Token::Id(right))) =
+                (joined.last_mut(), iter.peek())
+            {
+                left.push_str(right);
+                iter.next();
+                continue;
+            }
+        }
+        joined.push(token);
+    }
+    Ok(joined)
+}
+
+type Attrs = HashMap<String, String>;
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    graph: MermaidGraph,
+    directed: bool,
+    node_defaults: Attrs,
+    edge_defaults: Attrs,
+    /// Nodes already placed in a cluster
+    placed: HashSet<String>,
+    /// `label` of the graph or subgraph being read
+    graph_label: Option<String>,
+}
+
+/// Nodes mentioned in a scope, and the clusters found in it
+#[derive(Default)]
+struct Scope {
+    nodes: Vec<String>,
+    clusters: Vec<MermaidSubgraph>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_id(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Id(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Expected '{}' in DOT input, found {}",
+                c,
+                self.describe()
+            ))
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.peek() {
+            Some(Token::Id(id)) => format!("'{}'", id),
+            Some(Token::EdgeOp) => "an edge operator".to_string(),
+            Some(Token::Punct(c)) => format!("'{}'", c),
+            None => "the end of the input".to_string(),
+        }
+    }
+
+    fn id(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Id(id)) => {
+                let id = id.clone();
+                self.pos += 1;
+                Ok(id)
+            }
+            _ => Err(format!(
+                "Expected a name in DOT input, found {}",
+                self.describe()
+            )),
+        }
+    }
+
+    fn keyword(&self, word: &str) -> bool {
+        self.peek_id()
+            .is_some_and(|id| id.eq_ignore_ascii_case(word))
+    }
+
+    fn parse_graph(&mut self) -> Result<(), String> {
+        if self.keyword("strict") {
+            self.pos += 1;
+        }
+        if self.keyword("digraph") {
+            self.directed = true;
+        } else if self.keyword("graph") {
+            self.directed = false;
+        } else {
+            return Err(format!(
+                "Expected 'graph' or 'digraph' in DOT input, found {}",
+                self.describe()
+            ));
+        }
+        self.pos += 1;
+        if self.peek_id().is_some() {
+            self.pos += 1;
+        }
+        self.expect('{')?;
+        let scope = self.statements()?;
+        self.expect('}')?;
+        self.graph.subgraphs = scope.clusters;
+        Ok(())
+    }
+
+    /// Statements up to the closing `}` of the current block
+    fn statements(&mut self) -> Result<Scope, String> {
+        let mut scope = Scope::default();
+        while self.peek().is_some() && self.peek() != Some(&Token::Punct('}')) {
+            self.statement(&mut scope)?;
+            while self.eat(';') || self.eat(',') {}
+        }
+        Ok(scope)
+    }
+
+    fn statement(&mut self, scope: &mut Scope) -> Result<(), String> {
+        for (keyword, is_node) in [("node", true), ("edge", false)] {
+            if self.keyword(keyword) && self.tokens.get(self.pos + 1) == Some(&Token::Punct('[')) {
+                self.pos += 1;
+                let attrs = self.attr_lists()?;
+                let defaults = if is_node {
+                    &mut self.node_defaults
+                } else {
+                    &mut self.edge_defaults
+                };
+                defaults.extend(attrs);
+                return Ok(());
+            }
+        }
+        if self.keyword("graph") && self.tokens.get(self.pos + 1) == Some(&Token::Punct('[')) {
+            self.pos += 1;
+            let attrs = self.attr_lists()?;
+            self.graph_attrs(&attrs);
+            return Ok(());
+        }
+        // `name = value` sets a graph attribute
+        if self.peek_id().is_some() && self.tokens.get(self.pos + 1) == Some(&Token::Punct('=')) {
+            let name = self.id()?;
+            self.pos += 1;
+            let value = self.id()?;
+            self.graph_attrs(&HashMap::from([(name, value)]));
+            return Ok(());
+        }
+
+        let mut operands = vec![self.operand(scope)?];
+        while self.peek() == Some(&Token::EdgeOp) {
+            self.pos += 1;
+            operands.push(self.operand(scope)?);
+        }
+        let attrs = self.attr_lists()?;
+        if operands.len() == 1 {
+            // A node statement
+            if let Some((id, _)) = operands[0].first().filter(|_| !attrs.is_empty()) {
+                let id = id.clone();
+                self.apply_node_attrs(&id, &attrs);
+            }
+            return Ok(());
+        }
+        let mut attrs_with_defaults = self.edge_defaults.clone();
+        attrs_with_defaults.extend(attrs);
+        for pair in operands.windows(2) {
+            for (source, tail_port) in &pair[0] {
+                for (target, head_port) in &pair[1] {
+                    let mut edge = self.edge(source, target, &attrs_with_defaults);
+                    edge.start_port = tail_port.or(edge.start_port);
+                    edge.end_port = head_port.or(edge.end_port);
+                    self.graph.edges.push(edge);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A node or a subgraph, as the node ids it stands for. A node's port
+    /// (`a:p`, `a:p:n` or `a:n`) is kept if it ends in a compass point that
+    /// names a side.
+    fn operand(&mut self, scope: &mut Scope) -> Result<Vec<(String, Option<Port>)>, String> {
+        if self.keyword("subgraph") || self.peek() == Some(&Token::Punct('{')) {
+            let ids = self.subgraph(scope)?;
+            return Ok(ids.into_iter().map(|id| (id, None)).collect());
+        }
+        let id = self.id()?;
+        let mut port = None;
+        if self.eat(':') {
+            port = Port::from_str(&self.id()?);
+            if self.eat(':') {
+                port = Port::from_str(&self.id()?);
+            }
+        }
+        self.ensure_node(&id);
+        if !scope.nodes.contains(&id) {
+            scope.nodes.push(id.clone());
+        }
+        Ok(vec![(id, port)])
+    }
+
+    fn subgraph(&mut self, scope: &mut Scope) -> Result<Vec<String>, String> {
+        let mut name = None;
+        if self.keyword("subgraph") {
+            self.pos += 1;
+            if self.peek_id().is_some() {
+                name = Some(self.id()?);
+            }
+        }
+        self.expect('{')?;
+        // Defaults set inside a subgraph end with it
+        let (node_defaults, edge_defaults) =
+            (self.node_defaults.clone(), self.edge_defaults.clone());
+        let label_before = self.graph_label.take();
+        let inner = self.statements()?;
+        self.expect('}')?;
+        let label = std::mem::replace(&mut self.graph_label, label_before);
+        self.node_defaults = node_defaults;
+        self.edge_defaults = edge_defaults;
+
+        let nodes = inner.nodes.clone();
+        for id in &inner.nodes {
+            if !scope.nodes.contains(id) {
+                scope.nodes.push(id.clone());
+            }
+        }
+        match name.filter(|n| n.starts_with("cluster")) {
+            Some(name) => {
+                // A node belongs to the first cluster it appears in
+                let own: Vec<String> = inner
+                    .nodes
+                    .iter()
+                    .filter(|id| {
+                        !self.placed.contains(*id)
+                            && !inner.clusters.iter().any(|c| contains_node(c, id))
+                    })
+                    .cloned()
+                    .collect();
+                for id in &own {
+                    self.placed.insert(id.clone());
+                }
+                for cluster in &inner.clusters {
+                    mark_placed(cluster, &mut self.placed);
+                }
+                let label = label.unwrap_or_else(|| {
+                    name.trim_start_matches("cluster")
+                        .trim_start_matches('_')
+                        .to_string()
+                });
+                scope.clusters.push(MermaidSubgraph {
+                    id: name,
+                    label,
+                    node_ids: own,
+                    children: inner.clusters,
+                    direction: None,
+                });
+            }
+            None => scope.clusters.extend(inner.clusters),
+        }
+        Ok(nodes)
+    }
+
+    /// `[a=b, c=d][e=f]`, or nothing
+    fn attr_lists(&mut self) -> Result<Attrs, String> {
+        let mut attrs = Attrs::new();
+        while self.eat('[') {
+            while !self.eat(']') {
+                let name = self.id()?;
+                let value = if self.eat('=') {
+                    self.id()?
+                } else {
+                    "true".to_string()
+                };
+                attrs.insert(name.to_lowercase(), value);
+                while self.eat(',') || self.eat(';') {}
+                if self.peek().is_none() {
+                    return Err("Unterminated attribute list in DOT input".to_string());
+                }
+            }
+        }
+        Ok(attrs)
+    }
+
+    fn graph_attrs(&mut self, attrs: &Attrs) {
+        for (name, value) in attrs {
+            match name.to_lowercase().as_str() {
+                "rankdir" => {
+                    self.graph.direction = match value.to_uppercase().as_str() {
+                        "LR" => Direction::LR,
+                        "RL" => Direction::RL,
+                        "BT" => Direction::BT,
+                        _ => Direction::TB,
+                    }
+                }
+                "label" => self.graph_label = Some(label_text(value, "")),
+                _ => {}
+            }
+        }
+    }
+
+    fn ensure_node(&mut self, id: &str) {
+        if self.graph.nodes.contains_key(id) {
+            return;
+        }
+        self.graph.node_order.push(id.to_string());
+        self.graph.nodes.insert(
+            id.to_string(),
+            MermaidNode {
+                id: id.to_string(),
+                label: id.to_string(),
+                shape: NodeShape::Rounded,
+            },
+        );
+        let defaults = self.node_defaults.clone();
+        self.apply_node_attrs(id, &defaults);
+    }
+
+    fn apply_node_attrs(&mut self, id: &str, attrs: &Attrs) {
+        let Some(node) = self.graph.nodes.get_mut(id) else {
+            return;
+        };
+        if let Some(label) = attrs.get("label") {
+            node.label = label_text(label, id);
+        }
+        if let Some(shape) = attrs.get("shape") {
+            node.shape = match shape.to_lowercase().as_str() {
+                "box" | "rect" | "rectangle" | "square" | "record" | "mrecord" | "plaintext"
+                | "plain" | "none" | "note" | "tab" | "folder" | "component" => {
+                    NodeShape::Rectangle
+                }
+                "circle" | "point" => NodeShape::Circle,
+                "doublecircle" => NodeShape::DoubleCircle,
+                "diamond" | "mdiamond" => NodeShape::Diamond,
+                "hexagon" => NodeShape::Hexagon,
+                "cylinder" => NodeShape::Cylinder,
+                "trapezium" => NodeShape::Trapezoid,
+                "invtrapezium" => NodeShape::TrapezoidAlt,
+                "parallelogram" => NodeShape::Trapezoid,
+                _ => NodeShape::Rounded,
+            };
+        }
+        for (attr, property) in [
+            ("fillcolor", "fill"),
+            ("color", "stroke"),
+            ("fontcolor", "color"),
+        ] {
+            if let Some(color) = attrs.get(attr) {
+                self.graph
+                    .node_styles
+                    .entry(id.to_string())
+                    .or_default()
+                    .insert(property.to_string(), color.clone());
+            }
+        }
+    }
+
+    fn edge(&self, source: &str, target: &str, attrs: &Attrs) -> MermaidEdge {
+        let mut edge = MermaidEdge::new(source, target);
+        edge.label = attrs.get("label").map(|l| label_text(l, ""));
+        edge.start_port = attrs.get("tailport").and_then(|p| port_compass(p));
+        edge.end_port = attrs.get("headport").and_then(|p| port_compass(p));
+        edge.style = match attrs.get("style").map(|s| s.to_lowercase()).as_deref() {
+            Some("dashed") | Some("dotted") => EdgeStyle::Dotted,
+            Some("bold") => EdgeStyle::Thick,
+            _ => EdgeStyle::Solid,
+        };
+        let default_dir = if self.directed { "forward" } else { "none" };
+        let dir = attrs.get("dir").map_or(default_dir, String::as_str);
+        let marker = |name: Option<&String>| match name.map(|s| s.to_lowercase()).as_deref() {
+            Some("none") => EdgeMarker::None,
+            Some("dot") | Some("odot") => EdgeMarker::Circle,
+            Some("tee") => EdgeMarker::Cross,
+            _ => EdgeMarker::Arrow,
+        };
+        let (head, tail) = match dir {
+            "back" => (false, true),
+            "both" => (true, true),
+            "none" => (false, false),
+            _ => (true, false),
+        };
+        edge.end_marker = if head {
+            marker(attrs.get("arrowhead"))
+        } else {
+            EdgeMarker::None
+        };
+        edge.start_marker = if tail {
+            marker(attrs.get("arrowtail"))
+        } else {
+            EdgeMarker::None
+        };
+        edge
+    }
+}
+
+/// The side named by the compass point at the end of a port (`p:n` or `n`).
+/// Corners (`ne`, ...), the centre and record fields pick no side.
+fn port_compass(port: &str) -> Option<Port> {
+    Port::from_str(port.rsplit(':').next()?)
+}
+
+fn contains_node(cluster: &MermaidSubgraph, id: &str) -> bool {
+    cluster.node_ids.iter().any(|n| n == id)
+        || cluster.children.iter().any(|c| contains_node(c, id))
+}
+
+fn mark_placed(cluster: &MermaidSubgraph, placed: &mut HashSet<String>) {
+    placed.extend(cluster.node_ids.iter().cloned());
+    for child in &cluster.children {
+        mark_placed(child, placed);
+    }
+}
+
+/// A label with DOT's escapes resolved: `\n`, `\l` and `\r` end lines and
+/// `\N` is the node's name
+fn label_text(label: &str, node: &str) -> String {
+    let mut text = String::new();
+    let mut chars = label.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('l') | Some('r') => text.push('\n'),
+            Some('N') => text.push_str(node),
+            Some(other) => text.push(other),
+            None => {}
+        }
+    }
+    text.trim_end_matches('\n').to_string()
+}
@@ -1,9 +1,13 @@
 //! ER diagram parser
 
-use crate::types::{Cardinality, ErAttribute, ErDiagram, ErEntity, ErKey, ErRelationship};
+use super::extract_yaml_value;
+use crate::types::{
+    Cardinality, Direction, ErAttribute, ErConfig, ErDiagram, ErEntity, ErKey, ErRelationship,
+    FrontmatterConfig,
+};
+use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::HashMap;
 
 lazy_static! {
     static ref RE_ENTITY_BLOCK: Regex = Regex::new(r"^(\S+)\s*\{$").unwrap();
@@ -14,14 +18,29 @@ lazy_static! {
     static ref RE_LINE_STYLE: Regex = Regex::new(r"^([|o}{]+)(--|\.\.?)([|o}{]+)$").unwrap();
 }
 
-/// Parse a Mermaid ER diagram
-pub fn parse_er_diagram(lines: &[&str]) -> Result<ErDiagram, String> {
+/// Parse the ER layout of the frontmatter config: `layoutDirection:` (`TB`,
+/// `BT`, `LR` or `RL`). Unknown directions are ignored.
+pub fn parse_er_config(frontmatter: &FrontmatterConfig) -> ErConfig {
+    let mut config = ErConfig::default();
+    for line in &frontmatter.raw_lines {
+        let trimmed = line.trim().trim_start_matches("- ");
+        if let Some(val) = extract_yaml_value(trimmed, "layoutDirection:") {
+            let name = val.trim().trim_matches('\'').trim_matches('"');
+            config.layout_direction = Direction::from_str(name).or(config.layout_direction);
+        }
+    }
+    config
+}
+
+/// Parse a Mermaid ER diagram. The indices of the lines that aren't
+/// statements go to `skipped`.
+pub fn parse_er_diagram(lines: &[&str], skipped: &mut Vec<usize>) -> Result<ErDiagram, String> {
     let mut diagram = ErDiagram::new();
-    let mut entity_map: HashMap<String, ErEntity> = HashMap::new();
-    let mut entity_order: Vec<String> = Vec::new(); // Track insertion order
+    // In the order the entities are first mentioned
+    let mut entity_map: IndexMap<String, ErEntity> = IndexMap::new();
     let mut current_entity: Option<String> = None;
 
-    for line in lines.iter().skip(1) {
+    for (index, line) in lines.iter().enumerate().skip(1) {
         let line = *line;
 
         // Inside entity body
@@ -32,10 +51,13 @@ pub fn parse_er_diagram(lines: &[&str]) -> Result<ErDiagram, String> {
             }
 
             // Attribute line: type name [PK|FK|UK] ["comment"]
-            if let Some(attr) = parse_attribute(line) {
-                if let Some(entity) = entity_map.get_mut(entity_id) {
-                    entity.attributes.push(attr);
+            match parse_attribute(line) {
+                Some(attr) => {
+                    if let Some(entity) = entity_map.get_mut(entity_id) {
+                        entity.attributes.push(attr);
+                    }
                 }
+                None => skipped.push(index),
             }
             continue;
         }
@@ -43,44 +65,34 @@ pub fn parse_er_diagram(lines: &[&str]) -> Result<ErDiagram, String> {
         // Entity block start: `ENTITY_NAME {`
         if let Some(caps) = RE_ENTITY_BLOCK.captures(line) {
             let id = caps[1].to_string();
-            ensure_entity(&mut entity_map, &mut entity_order, &id);
+            ensure_entity(&mut entity_map, &id);
             current_entity = Some(id);
             continue;
         }
 
         // Relationship: `ENTITY1 cardinality1--cardinality2 ENTITY2 : label`
         if let Some(rel) = parse_relationship_line(line) {
-            ensure_entity(&mut entity_map, &mut entity_order, &rel.entity1);
-            ensure_entity(&mut entity_map, &mut entity_order, &rel.entity2);
+            ensure_entity(&mut entity_map, &rel.entity1);
+            ensure_entity(&mut entity_map, &rel.entity2);
             diagram.relationships.push(rel);
             continue;
         }
+
+        skipped.push(index);
     }
 
-    // Collect entities in insertion order
-    diagram.entities = entity_order
-        .into_iter()
-        .filter_map(|id| entity_map.remove(&id))
-        .collect();
+    diagram.entities = entity_map.into_values().collect();
     Ok(diagram)
 }
 
-fn ensure_entity(
-    entity_map: &mut HashMap<String, ErEntity>,
-    entity_order: &mut Vec<String>,
-    id: &str,
-) {
-    if !entity_map.contains_key(id) {
-        entity_order.push(id.to_string());
-        entity_map.insert(
-            id.to_string(),
-            ErEntity {
-                id: id.to_string(),
-                label: id.to_string(),
-                attributes: Vec::new(),
-            },
-        );
-    }
+fn ensure_entity(entity_map: &mut IndexMap<String, ErEntity>, id: &str) {
+    entity_map
+        .entry(id.to_string())
+        .or_insert_with(|| ErEntity {
+            id: id.to_string(),
+            label: id.to_string(),
+            attributes: Vec::new(),
+        });
 }
 
 fn parse_attribute(line: &str) -> Option<ErAttribute> {
@@ -94,10 +106,10 @@ fn parse_attribute(line: &str) -> Option<ErAttribute> {
     // Extract quoted comment first
     let comment = RE_COMMENT.captures(rest).map(|c| c[1].to_string());
 
-    // Extract key constraints
+    // Extract key constraints, separated by commas (`PK, FK`) or spaces
     let rest_without_comment = RE_COMMENT.replace_all(rest, "");
     let mut keys = Vec::new();
-    for part in rest_without_comment.split_whitespace() {
+    for part in rest_without_comment.split([' ', '\t', ',']) {
         let upper = part.to_uppercase();
         match upper.as_str() {
             "PK" => keys.push(ErKey::PK),
@@ -155,7 +167,7 @@ fn parse_cardinality(s: &str) -> Option<Cardinality> {
         "||" => Some(Cardinality::One),
         "|o" | "o|" => Some(Cardinality::ZeroOne),
         "|}" | "{|" => Some(Cardinality::Many),
-        "{o" | "o{" => Some(Cardinality::ZeroMany),
+        "{o" | "o{" | "o}" => Some(Cardinality::ZeroMany),
         _ => None,
     }
 }
@@ -1,7 +1,9 @@
 //! Flowchart and state diagram parser
 
+use super::extract_yaml_value;
 use crate::types::{
-    Direction, EdgeStyle, MermaidEdge, MermaidGraph, MermaidNode, MermaidSubgraph, NodeShape,
+    Direction, EdgeMarker, EdgeStyle, FlowchartConfig, FrontmatterConfig, MermaidEdge,
+    MermaidGraph, MermaidNode, MermaidSubgraph, NodeLink, NodeShape, Port,
 };
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -12,6 +14,8 @@ lazy_static! {
     static ref RE_CLASSDEF: Regex = Regex::new(r"^classDef\s+(\w+)\s+(.+)$").unwrap();
     static ref RE_CLASS: Regex = Regex::new(r"^class\s+([\w,-]+)\s+(\w+)$").unwrap();
     static ref RE_STYLE: Regex = Regex::new(r"^style\s+([\w,-]+)\s+(.+)$").unwrap();
+    static ref RE_CLICK: Regex = Regex::new(r"^click\s+(\w[\w-]*)\s*(.*)$").unwrap();
+    static ref RE_CLICK_LINK: Regex = Regex::new(r#"^(?:href\s+)?"([^"]*)"(?:\s+"([^"]*)")?(?:\s+(_\w+))?\s*$"#).unwrap();
     static ref RE_DIRECTION: Regex = Regex::new(r"(?i)^direction\s+(TD|TB|LR|BT|RL)\s*$").unwrap();
     static ref RE_SUBGRAPH: Regex = Regex::new(r"^subgraph\s+(.+)$").unwrap();
     static ref RE_SUBGRAPH_BRACKET: Regex = Regex::new(r"^([\w-]+)\s*\[(.+)\]$").unwrap();
@@ -19,9 +23,12 @@ lazy_static! {
     static ref RE_STATE_LABEL: Regex = Regex::new(r#"^state\s+"([^"]+)"\s+as\s+(\w+)\s*$"#).unwrap();
     static ref RE_STATE_TRANS: Regex = Regex::new(r"^(\[\*\]|[\w-]+)\s*(-->)\s*(\[\*\]|[\w-]+)(?:\s*:\s*(.+))?$").unwrap();
     static ref RE_NODE_LABEL: Regex = Regex::new(r"^([\w-]+)\s*:\s*(.+)$").unwrap();
-    static ref RE_ARROW: Regex = Regex::new(r"^(<)?(-->|-.->|==>|---|-\.-|===)(?:\|([^|]*)\|)?").unwrap();
+    static ref RE_STATE_ID: Regex = Regex::new(r"^[\w-]+$").unwrap();
+    static ref RE_ARROW: Regex = Regex::new(r"^([<ox])?(-{2,}[>ox]|-{3,}|-\.+-[>ox]?|={2,}[>ox]|={3,})(?:\|\|([^|]*)\|([^|]*)\|([^|]*)\|\||\|([^|]*)\|)?").unwrap();
     static ref RE_CLASS_SUFFIX: Regex = Regex::new(r"^:::([\w][\w-]*)").unwrap();
-    static ref RE_BARE_ID: Regex = Regex::new(r"^([\w-]+)").unwrap();
+    static ref RE_PORT: Regex = Regex::new(r"^:([nsew])\b").unwrap();
+    // Hyphens only between word characters, so `A-->B` isn't node `A--`
+    static ref RE_BARE_ID: Regex = Regex::new(r"^(\w+(?:-\w+)*)").unwrap();
 
     // Node shape patterns (in order of specificity - triple, double, single delimiters)
     static ref RE_NODE_DOUBLE_CIRCLE: Regex = Regex::new(r"^([\w-]+)\(\(\((.+?)\)\)\)").unwrap();
@@ -38,8 +45,48 @@ lazy_static! {
     static ref RE_NODE_DIAMOND: Regex = Regex::new(r"^([\w-]+)\{(.+?)\}").unwrap();
 }
 
-/// Parse a flowchart/graph diagram
-pub fn parse_flowchart(lines: &[&str]) -> Result<MermaidGraph, String> {
+/// Parse the flowchart settings of the frontmatter config: `nodeSpacing:`,
+/// `rankSpacing:`, `bundleEdges:` and `swimlanes:`. Negative and unreadable
+/// values are ignored.
+pub fn parse_flowchart_config(frontmatter: &FrontmatterConfig) -> FlowchartConfig {
+    let mut config = FlowchartConfig::default();
+    let spacing = |val: &str| {
+        val.trim()
+            .trim_matches('\'')
+            .trim_matches('"')
+            .parse::<f64>()
+            .ok()
+            .filter(|m| m.is_finite() && *m >= 0.0)
+    };
+    for line in &frontmatter.raw_lines {
+        let trimmed = line.trim().trim_start_matches("- ");
+        if let Some(val) = extract_yaml_value(trimmed, "nodeSpacing:") {
+            config.node_spacing = spacing(val).or(config.node_spacing);
+        }
+        if let Some(val) = extract_yaml_value(trimmed, "rankSpacing:") {
+            config.rank_spacing = spacing(val).or(config.rank_spacing);
+        }
+        if let Some(val) = extract_yaml_value(trimmed, "bundleEdges:") {
+            match val.trim() {
+                "true" => config.bundle_edges = Some(true),
+                "false" => config.bundle_edges = Some(false),
+                _ => {}
+            }
+        }
+        if let Some(val) = extract_yaml_value(trimmed, "swimlanes:") {
+            match val.trim() {
+                "true" => config.swimlanes = Some(true),
+                "false" => config.swimlanes = Some(false),
+                _ => {}
+            }
+        }
+    }
+    config
+}
+
+/// Parse a flowchart/graph diagram. The indices of the lines that aren't
+/// statements go to `skipped`.
+pub fn parse_flowchart(lines: &[&str], skipped: &mut Vec<usize>) -> Result<MermaidGraph, String> {
     let header = lines[0];
 
     // Match "graph TD" or "flowchart LR" etc
@@ -56,7 +103,7 @@ pub fn parse_flowchart(lines: &[&str]) -> Result<MermaidGraph, String> {
     let mut graph = MermaidGraph::new(direction);
     let mut subgraph_stack: Vec<MermaidSubgraph> = Vec::new();
 
-    for line in lines.iter().skip(1) {
+    for (index, line) in lines.iter().enumerate().skip(1) {
         let line = *line;
 
         // classDef
@@ -92,6 +139,21 @@ pub fn parse_flowchart(lines: &[&str]) -> Result<MermaidGraph, String> {
             continue;
         }
 
+        // click interaction: only URL links are kept, JS callbacks are ignored
+        if let Some(caps) = RE_CLICK.captures(line) {
+            if let Some(link) = RE_CLICK_LINK.captures(caps[2].trim()) {
+                graph.node_links.insert(
+                    caps[1].to_string(),
+                    NodeLink {
+                        url: link[1].to_string(),
+                        tooltip: link.get(2).map(|m| m.as_str().to_string()),
+                        target: link.get(3).map(|m| m.as_str().to_string()),
+                    },
+                );
+            }
+            continue;
+        }
+
         // direction override inside subgraph
         if let Some(caps) = RE_DIRECTION.captures(line) {
             if let Some(sg) = subgraph_stack.last_mut() {
@@ -138,20 +200,26 @@ pub fn parse_flowchart(lines: &[&str]) -> Result<MermaidGraph, String> {
         }
 
         // Edge/node definitions
-        parse_edge_line(line, &mut graph, &mut subgraph_stack);
+        if !parse_edge_line(line, &mut graph, &mut subgraph_stack) {
+            skipped.push(index);
+        }
     }
 
     Ok(graph)
 }
 
-/// Parse a state diagram
-pub fn parse_state_diagram(lines: &[&str]) -> Result<MermaidGraph, String> {
+/// Parse a state diagram. The indices of the lines that aren't statements go
+/// to `skipped`.
+pub fn parse_state_diagram(
+    lines: &[&str],
+    skipped: &mut Vec<usize>,
+) -> Result<MermaidGraph, String> {
     let mut graph = MermaidGraph::new(Direction::TD);
     let mut composite_stack: Vec<MermaidSubgraph> = Vec::new();
     let mut start_count = 0;
     let mut end_count = 0;
 
-    for line in lines.iter().skip(1) {
+    for (index, line) in lines.iter().enumerate().skip(1) {
         let line = *line;
 
         // direction override
@@ -262,9 +330,15 @@ pub fn parse_state_diagram(lines: &[&str]) -> Result<MermaidGraph, String> {
                 source: source_id,
                 target: target_id,
                 label: edge_label,
+                start_label: None,
+                end_label: None,
                 style: EdgeStyle::Solid,
-                has_arrow_start: false,
-                has_arrow_end: true,
+                start_marker: EdgeMarker::None,
+                end_marker: EdgeMarker::Arrow,
+                min_length: 1,
+                start_port: None,
+                end_port: None,
+                class: None,
             });
             continue;
         }
@@ -284,6 +358,14 @@ pub fn parse_state_diagram(lines: &[&str]) -> Result<MermaidGraph, String> {
             );
             continue;
         }
+
+        // bare state, e.g. listed inside a composite
+        if RE_STATE_ID.is_match(line) {
+            ensure_state_node(&mut graph, &mut composite_stack, line);
+            continue;
+        }
+
+        skipped.push(index);
     }
 
     Ok(graph)
@@ -324,7 +406,7 @@ fn ensure_state_node(graph: &mut MermaidGraph, composite_stack: &mut [MermaidSub
     }
 }
 
-fn parse_style_props(props_str: &str) -> HashMap<String, String> {
+pub(super) fn parse_style_props(props_str: &str) -> HashMap<String, String> {
     let mut props = HashMap::new();
     for pair in props_str.split(',') {
         if let Some(colon_idx) = pair.find(':') {
@@ -403,186 +485,230 @@ fn get_node_patterns() -> Vec<NodePattern> {
     ]
 }
 
-/// Parse a line that contains node definitions and edges
-fn parse_edge_line(line: &str, graph: &mut MermaidGraph, subgraph_stack: &mut [MermaidSubgraph]) {
-    let mut remaining = line.trim();
+/// A node as an edge statement mentions it
+struct NodeRef {
+    /// The node, with the label and shape given here, or its id as the label
+    /// and a rectangle for a bare id
+    node: MermaidNode,
+    /// Side its edges attach to (`A:e`)
+    port: Option<Port>,
+    /// Class from a `:::name` suffix
+    class: Option<String>,
+}
 
-    // Parse the first node group
-    let first_group = consume_node_group(remaining, graph, subgraph_stack);
-    if first_group.is_none() {
-        return;
-    }
-    let (first_ids, rest) = first_group.unwrap();
-    if first_ids.is_empty() {
-        return;
-    }
-    remaining = rest;
+/// An arrow of an edge statement and the nodes it points to
+struct Link {
+    /// The edge, without its source and target
+    edge: MermaidEdge,
+    targets: Vec<NodeRef>,
+}
 
-    let mut prev_ids = first_ids;
+/// Parse a line that contains node definitions and edges. Nothing is added to
+/// the graph unless the whole line parses; returns whether it did.
+fn parse_edge_line(
+    line: &str,
+    graph: &mut MermaidGraph,
+    subgraph_stack: &mut [MermaidSubgraph],
+) -> bool {
+    let Some((first, rest)) = parse_node_group(line.trim()) else {
+        return false;
+    };
+    let mut remaining = rest;
+    let mut links = Vec::new();
+
+    // Parse chains of edges. Ids are read before their arrows, so the start
+    // marker of `helloo--oB` is not the last `o` of `helloo`
+    while let Some(caps) = RE_ARROW.captures(remaining) {
+        let start_marker = match caps.get(1).map(|m| m.as_str()) {
+            Some("<") => EdgeMarker::Arrow,
+            Some("o") => EdgeMarker::Circle,
+            Some("x") => EdgeMarker::Cross,
+            _ => EdgeMarker::None,
+        };
+        let arrow_op = &caps[2];
+        // `|label|`, or `||start|label|end||` with labels at both ends. The
+        // doubled bars keep `A-->|yes|B---|no|C` a chain of two edges
+        let text = |i: usize| {
+            caps.get(i)
+                .map(|m| m.as_str().trim().to_string())
+                .filter(|t| !t.is_empty())
+        };
+        let (start_label, label, end_label) = if caps.get(3).is_some() {
+            (text(3), text(4), text(5))
+        } else {
+            (None, caps.get(6).map(|m| m.as_str().to_string()), None)
+        };
 
-    // Parse chains of edges
-    while !remaining.is_empty() {
-        // Try to match an arrow
-        if let Some(caps) = RE_ARROW.captures(remaining) {
-            let has_arrow_start = caps.get(1).is_some();
-            let arrow_op = &caps[2];
-            let label = caps.get(3).map(|m| m.as_str().to_string());
+        remaining = remaining[caps[0].len()..].trim_start();
 
+        // `-->|label|:::name` gives the edge a class
+        let class = RE_CLASS_SUFFIX.captures(remaining).map(|caps| {
             remaining = remaining[caps[0].len()..].trim_start();
-
-            // Determine edge style and arrow end
-            let (style, has_arrow_end) = match arrow_op {
-                "-->" => (EdgeStyle::Solid, true),
-                "---" => (EdgeStyle::Solid, false),
-                "-.->" => (EdgeStyle::Dotted, true),
-                "-.-" => (EdgeStyle::Dotted, false),
-                "==>" => (EdgeStyle::Thick, true),
-                "===" => (EdgeStyle::Thick, false),
-                _ => (EdgeStyle::Solid, true),
-            };
-
-            // Parse target node group
-            if let Some((target_ids, rest2)) = consume_node_group(remaining, graph, subgraph_stack)
-            {
-                remaining = rest2;
-
-                // Create edges for all combinations
-                for source in &prev_ids {
-                    for target in &target_ids {
-                        graph.edges.push(MermaidEdge {
-                            source: source.clone(),
-                            target: target.clone(),
-                            label: label.clone(),
-                            style,
-                            has_arrow_start,
-                            has_arrow_end,
-                        });
-                    }
-                }
-
-                prev_ids = target_ids;
+            caps[1].to_string()
+        });
+
+        // Line style comes from the body, the end marker from the last character
+        let style = if arrow_op.starts_with("-.") {
+            EdgeStyle::Dotted
+        } else if arrow_op.starts_with("==") {
+            EdgeStyle::Thick
+        } else {
+            EdgeStyle::Solid
+        };
+        let end_marker = match arrow_op.chars().last() {
+            Some('>') => EdgeMarker::Arrow,
+            Some('o') => EdgeMarker::Circle,
+            Some('x') => EdgeMarker::Cross,
+            _ => EdgeMarker::None,
+        };
+
+        // Extra dashes/dots/equals lengthen the edge: `-->` and `---` are
+        // length 1, `--->` and `----` length 2, `-..->` length 2, etc.
+        let min_length = if style == EdgeStyle::Dotted {
+            arrow_op.matches('.').count()
+        } else {
+            let body = arrow_op.chars().filter(|&c| c == '-' || c == '=').count();
+            if end_marker == EdgeMarker::None {
+                body - 2
             } else {
-                break;
+                body - 1
+            }
+        };
+
+        // Parse target node group
+        let Some((targets, rest)) = parse_node_group(remaining) else {
+            return false;
+        };
+        remaining = rest;
+        links.push(Link {
+            edge: MermaidEdge {
+                source: String::new(),
+                target: String::new(),
+                label,
+                start_label,
+                end_label,
+                style,
+                start_marker,
+                end_marker,
+                min_length,
+                start_port: None,
+                end_port: None,
+                class,
+            },
+            targets,
+        });
+    }
+    // A statement may end with a semicolon
+    if !remaining.trim_end().trim_end_matches(';').is_empty() {
+        return false;
+    }
+
+    register_nodes(&first, graph, subgraph_stack);
+    let mut sources = first;
+    for link in links {
+        register_nodes(&link.targets, graph, subgraph_stack);
+        // Create edges for all combinations
+        for source in &sources {
+            for target in &link.targets {
+                graph.edges.push(MermaidEdge {
+                    source: source.node.id.clone(),
+                    target: target.node.id.clone(),
+                    start_port: source.port,
+                    end_port: target.port,
+                    ..link.edge.clone()
+                });
             }
-        } else {
-            break;
         }
+        sources = link.targets;
     }
+    true
 }
 
-/// Consume a node group (possibly with & separators)
-fn consume_node_group<'a>(
-    input: &'a str,
+/// Add the nodes of `refs` that are new to the graph, and all of them to the
+/// innermost open subgraph; apply their `:::name` classes
+fn register_nodes(
+    refs: &[NodeRef],
     graph: &mut MermaidGraph,
     subgraph_stack: &mut [MermaidSubgraph],
-) -> Option<(Vec<String>, &'a str)> {
-    let mut remaining = input.trim();
-    let mut ids = Vec::new();
-
-    loop {
-        // Try to parse a node
-        if let Some((id, rest)) = consume_single_node(remaining, graph, subgraph_stack) {
-            ids.push(id);
-            remaining = rest.trim_start();
-
-            // Check for class shorthand :::className
-            if remaining.starts_with(":::") {
-                if let Some(caps) = RE_CLASS_SUFFIX.captures(remaining) {
-                    let class_name = caps[1].to_string();
-                    if let Some(last_id) = ids.last() {
-                        graph.class_assignments.insert(last_id.clone(), class_name);
-                    }
-                    remaining = remaining[caps[0].len()..].trim_start();
-                }
+) {
+    for node_ref in refs {
+        let id = &node_ref.node.id;
+        if !graph.nodes.contains_key(id) {
+            graph.nodes.insert(id.clone(), node_ref.node.clone());
+            graph.node_order.push(id.clone()); // Track insertion order
+        }
+        if let Some(sg) = subgraph_stack.last_mut() {
+            if !sg.node_ids.contains(id) {
+                sg.node_ids.push(id.clone());
             }
+        }
+        if let Some(class) = &node_ref.class {
+            graph.class_assignments.insert(id.clone(), class.clone());
+        }
+    }
+}
 
-            // Check for & separator
-            if remaining.starts_with('&') {
-                remaining = remaining[1..].trim_start();
-                continue;
-            }
+/// Parse a node group (possibly with & separators).
+/// Each node may carry a port suffix (`A:e`) pinning its edges to that side.
+fn parse_node_group(input: &str) -> Option<(Vec<NodeRef>, &str)> {
+    let mut remaining = input.trim();
+    let mut refs = Vec::new();
+
+    // Try to parse a node
+    while let Some((node, rest)) = parse_single_node(remaining) {
+        remaining = rest;
+        let port = RE_PORT.captures(remaining).and_then(|caps| {
+            remaining = &remaining[caps[0].len()..];
+            Port::from_str(&caps[1])
+        });
+        remaining = remaining.trim_start();
+
+        // Check for class shorthand :::className
+        let class = RE_CLASS_SUFFIX.captures(remaining).map(|caps| {
+            remaining = remaining[caps[0].len()..].trim_start();
+            caps[1].to_string()
+        });
+        refs.push(NodeRef { node, port, class });
+
+        // Check for & separator
+        match remaining.strip_prefix('&') {
+            Some(rest) => remaining = rest.trim_start(),
+            None => break,
         }
-        break;
     }
 
-    if ids.is_empty() {
+    if refs.is_empty() {
         None
     } else {
-        Some((ids, remaining))
+        Some((refs, remaining))
     }
 }
 
-/// Consume a single node definition
-fn consume_single_node<'a>(
-    input: &'a str,
-    graph: &mut MermaidGraph,
-    subgraph_stack: &mut [MermaidSubgraph],
-) -> Option<(String, &'a str)> {
+/// Parse a single node definition
+fn parse_single_node(input: &str) -> Option<(MermaidNode, &str)> {
     let input = input.trim();
     if input.is_empty() {
         return None;
     }
 
-    let patterns = get_node_patterns();
-
     // Try each pattern
-    for pattern in &patterns {
+    for pattern in &get_node_patterns() {
         if let Some(caps) = pattern.regex.captures(input) {
-            let id = caps[1].to_string();
-            let label = caps[2].to_string();
-            let matched_len = caps[0].len();
-
-            // Register node if new
-            if !graph.nodes.contains_key(&id) {
-                graph.nodes.insert(
-                    id.clone(),
-                    MermaidNode {
-                        id: id.clone(),
-                        label,
-                        shape: pattern.shape,
-                    },
-                );
-                graph.node_order.push(id.clone()); // Track insertion order
-            }
-
-            // Track in subgraph
-            if let Some(sg) = subgraph_stack.last_mut() {
-                if !sg.node_ids.contains(&id) {
-                    sg.node_ids.push(id.clone());
-                }
-            }
-
-            return Some((id, &input[matched_len..]));
-        }
-    }
-
-    // Try bare node (just an ID)
-    if let Some(caps) = RE_BARE_ID.captures(input) {
-        let id = caps[1].to_string();
-        let matched_len = caps[0].len();
-
-        // Register node if new (with default rectangle shape)
-        if !graph.nodes.contains_key(&id) {
-            graph.nodes.insert(
-                id.clone(),
-                MermaidNode {
-                    id: id.clone(),
-                    label: id.clone(),
-                    shape: NodeShape::Rectangle,
-                },
-            );
-            graph.node_order.push(id.clone()); // Track insertion order
-        }
-
-        // Track in subgraph
-        if let Some(sg) = subgraph_stack.last_mut() {
-            if !sg.node_ids.contains(&id) {
-                sg.node_ids.push(id.clone());
-            }
+            let node = MermaidNode {
+                id: caps[1].to_string(),
+                label: caps[2].to_string(),
+                shape: pattern.shape,
+            };
+            return Some((node, &input[caps[0].len()..]));
         }
-
-        return Some((id, &input[matched_len..]));
     }
 
-    None
+    // Try bare node (just an ID), with the default rectangle shape
+    let caps = RE_BARE_ID.captures(input)?;
+    let node = MermaidNode {
+        id: caps[1].to_string(),
+        label: caps[1].to_string(),
+        shape: NodeShape::Rectangle,
+    };
+    Some((node, &input[caps[0].len()..]))
 }
@@ -1,9 +1,9 @@
 //! Parser for Mermaid GitGraph diagrams
 
-use super::extract_yaml_value;
+use super::{extract_yaml_value, find_ignore_case};
 use crate::types::{
     CommitType, FrontmatterConfig, GitBranch, GitCommit, GitGraph, GitGraphConfig,
-    GitGraphDirection,
+    GitGraphDirection, GitMergeStyle,
 };
 
 /// Parse gitGraph-specific configuration from frontmatter raw lines.
@@ -33,6 +33,15 @@ fn parse_config_values(text: &str, config: &mut GitGraphConfig) {
         if let Some(val) = extract_yaml_value(trimmed, "showCommitLabel:") {
             config.show_commit_label = val.trim() != "false";
         }
+        if let Some(val) = extract_yaml_value(trimmed, "showCommitMessage:") {
+            config.show_commit_message = val.trim() == "true";
+        }
+        if let Some(val) = extract_yaml_value(trimmed, "showCommitMetadata:") {
+            config.show_commit_metadata = val.trim() == "true";
+        }
+        if let Some(val) = extract_yaml_value(trimmed, "showCommitTooltip:") {
+            config.show_commit_tooltip = val.trim() != "false";
+        }
         if let Some(val) = extract_yaml_value(trimmed, "mainBranchName:") {
             let name = val.trim().trim_matches('\'').trim_matches('"').to_string();
             if !name.is_empty() {
@@ -48,8 +57,13 @@ fn parse_config_values(text: &str, config: &mut GitGraphConfig) {
             config.rotate_commit_label = val.trim() != "false";
         }
         if let Some(val) = extract_yaml_value(trimmed, "parallelCommits:") {
-            if val.trim() == "true" {
-                eprintln!("Warning: parallelCommits is not yet supported and will be ignored");
+            config.parallel_commits = val.trim() == "true";
+        }
+        if let Some(val) = extract_yaml_value(trimmed, "mergeStyle:") {
+            if let Some(style) =
+                GitMergeStyle::from_name(val.trim().trim_matches('\'').trim_matches('"'))
+            {
+                config.merge_style = style;
             }
         }
 
@@ -146,16 +160,31 @@ pub fn parse_gitgraph_from_text(
         return Err("Empty gitGraph diagram".to_string());
     }
 
-    parse_gitgraph_with_config(&lines, config)
+    parse_gitgraph_with_config(&lines, config, &mut Vec::new())
+}
+
+/// Parse the body lines of a gitGraph diagram, header first, with the
+/// settings of `frontmatter`. The indices of the lines that aren't commands
+/// go to `skipped`.
+pub(super) fn parse_gitgraph_lines(
+    lines: &[&str],
+    frontmatter: &FrontmatterConfig,
+    skipped: &mut Vec<usize>,
+) -> Result<GitGraph, String> {
+    parse_gitgraph_with_config(lines, parse_gitgraph_config(frontmatter), skipped)
 }
 
 /// Parse a gitGraph diagram from pre-filtered lines (called from parse_mermaid)
 pub fn parse_gitgraph(lines: &[&str]) -> Result<GitGraph, String> {
-    parse_gitgraph_with_config(lines, GitGraphConfig::default())
+    parse_gitgraph_with_config(lines, GitGraphConfig::default(), &mut Vec::new())
 }
 
 /// Core parser with explicit config
-fn parse_gitgraph_with_config(lines: &[&str], config: GitGraphConfig) -> Result<GitGraph, String> {
+fn parse_gitgraph_with_config(
+    lines: &[&str],
+    config: GitGraphConfig,
+    skipped: &mut Vec<usize>,
+) -> Result<GitGraph, String> {
     // Parse direction from header line
     let header = lines[0].to_lowercase();
     let direction = if header.contains("tb:") || header.contains("tb ") {
@@ -167,9 +196,9 @@ fn parse_gitgraph_with_config(lines: &[&str], config: GitGraphConfig) -> Result<
     };
 
     let mut graph = GitGraph::with_config(direction, config);
-    let mut commit_counter: u8 = b'A';
+    let mut commit_counter = u32::from(b'A');
 
-    for line in lines.iter().skip(1) {
+    for (index, line) in lines.iter().enumerate().skip(1) {
         let line = line.trim();
         if line.is_empty() || line.starts_with("%%") {
             continue;
@@ -186,6 +215,8 @@ fn parse_gitgraph_with_config(lines: &[&str], config: GitGraphConfig) -> Result<
             parse_merge(line, &mut graph, &mut commit_counter)?;
         } else if line.starts_with("cherry-pick") {
             parse_cherry_pick(line, &mut graph, &mut commit_counter)?;
+        } else {
+            skipped.push(index);
         }
     }
 
@@ -193,7 +224,7 @@ fn parse_gitgraph_with_config(lines: &[&str], config: GitGraphConfig) -> Result<
 }
 
 /// Parse a commit command
-fn parse_commit(line: &str, graph: &mut GitGraph, counter: &mut u8) -> Result<(), String> {
+fn parse_commit(line: &str, graph: &mut GitGraph, counter: &mut u32) -> Result<(), String> {
     let mut commit_id: Option<String> = None;
     let mut commit_type = CommitType::Normal;
     let mut tag: Option<String> = None;
@@ -217,8 +248,15 @@ fn parse_commit(line: &str, graph: &mut GitGraph, counter: &mut u8) -> Result<()
         tag = Some(tag_match);
     }
 
+    // Parse msg: "value"
+    let message = extract_quoted_value(line, "msg:");
+
+    // Parse author: "value" and date: "value"
+    let author = extract_quoted_value(line, "author:");
+    let date = extract_quoted_value(line, "date:");
+
     // Generate ID if not provided, but always consume a counter slot
-    let id = commit_id.unwrap_or_else(|| (*counter as char).to_string());
+    let id = commit_id.unwrap_or_else(|| auto_commit_id(*counter));
     // Always advance counter (custom ID consumes a slot too)
     *counter += 1;
 
@@ -234,12 +272,15 @@ fn parse_commit(line: &str, graph: &mut GitGraph, counter: &mut u8) -> Result<()
         id: id.clone(),
         commit_type,
         tag,
+        message,
         branch: graph.current_branch.clone(),
         parent_ids,
         is_merge: false,
         is_cherry_pick: false,
         cherry_pick_source: None,
         cherry_pick_parent: None,
+        author,
+        date,
     };
 
     graph.commits.push(commit);
@@ -308,7 +349,7 @@ fn parse_checkout(line: &str, graph: &mut GitGraph) -> Result<(), String> {
 }
 
 /// Parse a merge command
-fn parse_merge(line: &str, graph: &mut GitGraph, counter: &mut u8) -> Result<(), String> {
+fn parse_merge(line: &str, graph: &mut GitGraph, counter: &mut u32) -> Result<(), String> {
     let parts: Vec<&str> = line.split_whitespace().collect();
     if parts.len() < 2 {
         return Err("Invalid merge command".to_string());
@@ -317,13 +358,14 @@ fn parse_merge(line: &str, graph: &mut GitGraph, counter: &mut u8) -> Result<(),
     let source_branch = parts[1].to_string();
 
     // Merge commits get a unique auto-generated ID from the counter (like regular commits)
-    let commit_id =
-        extract_quoted_value(line, "id:").unwrap_or_else(|| (*counter as char).to_string());
+    let commit_id = extract_quoted_value(line, "id:").unwrap_or_else(|| auto_commit_id(*counter));
     // Always advance counter
     *counter += 1;
 
     // Parse optional tag
     let tag = extract_quoted_value(line, "tag:");
+    let author = extract_quoted_value(line, "author:");
+    let date = extract_quoted_value(line, "date:");
 
     // Parse type
     let commit_type = if line.contains("REVERSE") {
@@ -347,12 +389,15 @@ fn parse_merge(line: &str, graph: &mut GitGraph, counter: &mut u8) -> Result<(),
         id: commit_id.clone(),
         commit_type,
         tag,
+        message: None,
         branch: graph.current_branch.clone(),
         parent_ids,
         is_merge: true,
         is_cherry_pick: false,
         cherry_pick_source: None,
         cherry_pick_parent: None,
+        author,
+        date,
     };
 
     graph.commits.push(commit);
@@ -370,7 +415,7 @@ fn parse_merge(line: &str, graph: &mut GitGraph, counter: &mut u8) -> Result<(),
 }
 
 /// Parse a cherry-pick command
-fn parse_cherry_pick(line: &str, graph: &mut GitGraph, counter: &mut u8) -> Result<(), String> {
+fn parse_cherry_pick(line: &str, graph: &mut GitGraph, counter: &mut u32) -> Result<(), String> {
     // Parse the source commit id
     let source_id = extract_quoted_value(line, "id:")
         .ok_or_else(|| "cherry-pick requires id: parameter".to_string())?;
@@ -378,8 +423,11 @@ fn parse_cherry_pick(line: &str, graph: &mut GitGraph, counter: &mut u8) -> Resu
     // Parse optional parent: parameter
     let cherry_pick_parent = extract_quoted_value(line, "parent:");
 
-    // Generate new commit id
-    let commit_id = format!("{}'", source_id);
+    // Generate new commit id; picking the same commit again adds another prime
+    let mut commit_id = format!("{}'", source_id);
+    while graph.commits.iter().any(|c| c.id == commit_id) {
+        commit_id.push('\'');
+    }
 
     // Get parent: last commit on current branch
     let parent_ids = get_last_commit_on_branch(graph, &graph.current_branch.clone())
@@ -390,12 +438,15 @@ fn parse_cherry_pick(line: &str, graph: &mut GitGraph, counter: &mut u8) -> Resu
         id: commit_id.clone(),
         commit_type: CommitType::Normal,
         tag: None,
+        message: None,
         branch: graph.current_branch.clone(),
         parent_ids,
         is_merge: false,
         is_cherry_pick: true,
         cherry_pick_source: Some(source_id),
         cherry_pick_parent,
+        author: None,
+        date: None,
     };
 
     // We used counter logic elsewhere, but not here - increment anyway to stay consistent
@@ -415,12 +466,15 @@ fn parse_cherry_pick(line: &str, graph: &mut GitGraph, counter: &mut u8) -> Resu
     Ok(())
 }
 
+/// Id of a commit without an `id:`: the character numbered by the counter
+/// (`A`, `B`, ...), or the number itself where that isn't a character
+fn auto_commit_id(counter: u32) -> String {
+    char::from_u32(counter).map_or_else(|| counter.to_string(), String::from)
+}
+
 /// Extract a quoted value after a key (e.g., id: "value" -> "value")
 fn extract_quoted_value(line: &str, key: &str) -> Option<String> {
-    let lower = line.to_lowercase();
-    let key_lower = key.to_lowercase();
-
-    if let Some(pos) = lower.find(&key_lower) {
+    if let Some(pos) = find_ignore_case(line, key) {
         let after_key = &line[pos + key.len()..];
         // Find quoted string
         if let Some(start) = after_key.find('"') {
@@ -443,10 +497,7 @@ fn extract_quoted_value(line: &str, key: &str) -> Option<String> {
 
 /// Extract an unquoted value after a key
 fn extract_value(line: &str, key: &str) -> Option<String> {
-    let lower = line.to_lowercase();
-    let key_lower = key.to_lowercase();
-
-    if let Some(pos) = lower.find(&key_lower) {
+    if let Some(pos) = find_ignore_case(line, key) {
         let after_key = &line[pos + key.len()..];
         let trimmed = after_key.trim();
         let word: String = trimmed.chars().take_while(|c| !c.is_whitespace()).collect();
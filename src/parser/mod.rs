@@ -1,56 +1,224 @@
 //! Parser module for Mermaid diagrams
 
 pub mod class;
+#[cfg(feature = "d2")]
+pub mod d2;
+#[cfg(feature = "dot")]
+pub mod dot;
 pub mod er;
 pub mod flowchart;
 pub mod gitgraph;
+pub mod plantuml;
+#[cfg(feature = "rust")]
+pub mod rust;
 pub mod sequence;
 
-use crate::types::{DiagramType, FrontmatterConfig, MermaidTheme, ParsedDiagram};
+use crate::types::{
+    CurveStyle, DiagramKind, DiagramType, EdgeRouting, FlowchartLayout, FrontmatterConfig,
+    MermaidTheme, ParsedDiagram,
+};
+use crate::validate::{Diagnostic, Severity};
+
+/// How [`parse_mermaid_with_mode`] treats lines that aren't statements of the
+/// diagram type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Fail at the first such line
+    Strict,
+    /// Skip such lines with a warning, as [`parse_mermaid`] does (without the
+    /// warning)
+    #[default]
+    Lenient,
+}
 
 /// Parse Mermaid diagram text and return the diagram type plus frontmatter config
+///
+/// Lines that aren't statements of the diagram type are skipped; see
+/// [`parse_mermaid_with_mode`] to be told about them.
 pub fn parse_mermaid(text: &str) -> Result<ParsedDiagram, String> {
+    parse_mermaid_with_mode(text, ParseMode::Lenient).map(|(parsed, _)| parsed)
+}
+
+/// Parse Mermaid diagram text, failing on lines that aren't statements of the
+/// diagram type in [`ParseMode::Strict`], or returning a warning for each in
+/// [`ParseMode::Lenient`].
+///
+/// # Example
+/// ```rust
+/// use m2svg::parser::{parse_mermaid_with_mode, ParseMode};
+///
+/// let text = "graph LR\n  A --> B\n  linkStyle 0 stroke:#f00";
+/// let (parsed, warnings) = parse_mermaid_with_mode(text, ParseMode::Lenient).unwrap();
+/// assert_eq!(warnings.len(), 1);
+/// assert_eq!(
+///     warnings[0].to_string(),
+///     "3: warning: Skipped unrecognized statement: linkStyle 0 stroke:#f00"
+/// );
+///
+/// let error = parse_mermaid_with_mode(text, ParseMode::Strict).unwrap_err();
+/// assert_eq!(error, "Line 3: unrecognized statement: linkStyle 0 stroke:#f00");
+/// ```
+pub fn parse_mermaid_with_mode(
+    text: &str,
+    mode: ParseMode,
+) -> Result<(ParsedDiagram, Vec<Diagnostic>), String> {
+    // Accessibility statements work the same in every diagram type
+    let (acc_title, acc_descr, text) = extract_accessibility(text);
+    let text = text.as_str();
+
     // Parse frontmatter for common config (theme, etc.)
-    let (frontmatter, text_without_frontmatter) = parse_frontmatter(text);
+    let (mut frontmatter, text_without_frontmatter) = parse_frontmatter(text);
+    frontmatter.acc_title = acc_title;
+    frontmatter.acc_descr = acc_descr;
 
-    let lines: Vec<&str> = text_without_frontmatter
+    // Line numbers are those of the whole input, frontmatter included
+    let offset = text.lines().count() - text_without_frontmatter.lines().count();
+    let body: Vec<(usize, &str)> = text_without_frontmatter
         .lines()
-        .map(|l| l.trim())
-        .filter(|l| !l.is_empty() && !l.starts_with("%%"))
+        .enumerate()
+        .map(|(i, l)| (offset + i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty() && !l.starts_with("%%"))
+        .collect();
+    let statements: Vec<(usize, &str)> = body
+        .iter()
+        .copied()
         // Skip configuration lines like paddingX=, paddingY=, etc.
-        .filter(|l| !l.contains('=') || l.contains("-->") || l.contains("--") || l.contains("->"))
+        // (click lines are kept: their URLs often carry query strings, and
+        // so are thick `==>` edges)
+        .filter(|(_, l)| {
+            !l.contains('=')
+                || l.contains("-->")
+                || l.contains("--")
+                || l.contains("->")
+                || l.contains("==")
+                || l.starts_with("click ")
+        })
         .collect();
+    let lines: Vec<&str> = statements.iter().map(|(_, l)| *l).collect();
 
     if lines.is_empty() {
         return Err("Empty mermaid diagram".to_string());
     }
 
-    let header = lines[0].to_lowercase();
-
-    let diagram = if header.starts_with("sequencediagram") {
-        let diagram = sequence::parse_sequence_diagram(&lines)?;
-        DiagramType::Sequence(diagram)
-    } else if header.starts_with("classdiagram") {
-        let diagram = class::parse_class_diagram(&lines)?;
-        DiagramType::Class(diagram)
-    } else if header.starts_with("erdiagram") {
-        let diagram = er::parse_er_diagram(&lines)?;
-        DiagramType::Er(diagram)
-    } else if header.starts_with("statediagram") {
-        let graph = flowchart::parse_state_diagram(&lines)?;
-        DiagramType::Flowchart(graph)
-    } else if header.starts_with("gitgraph") {
-        let graph = gitgraph::parse_gitgraph_from_text(text, &frontmatter)?;
-        DiagramType::GitGraph(graph)
-    } else {
-        let graph = flowchart::parse_flowchart(&lines)?;
-        DiagramType::Flowchart(graph)
+    let kind = DiagramKind::from_header(lines[0]);
+    if kind != DiagramKind::Unknown && !kind.is_supported() {
+        return Err(unsupported_kind_error(kind));
+    }
+
+    // Indices into `numbered` of the lines the diagram parser didn't take
+    let mut skipped = Vec::new();
+    let mut numbered = &statements;
+    let diagram = match kind {
+        DiagramKind::Sequence => {
+            let mut diagram = sequence::parse_sequence_diagram(&lines, &mut skipped)?;
+            diagram.config = sequence::parse_sequence_config(&frontmatter);
+            DiagramType::Sequence(diagram)
+        }
+        DiagramKind::Class => {
+            let mut diagram = class::parse_class_diagram(&lines, &mut skipped)?;
+            diagram.stereotype_styles = class::parse_stereotype_styles(&frontmatter);
+            DiagramType::Class(diagram)
+        }
+        DiagramKind::Er => {
+            let mut diagram = er::parse_er_diagram(&lines, &mut skipped)?;
+            diagram.config = er::parse_er_config(&frontmatter);
+            DiagramType::Er(diagram)
+        }
+        DiagramKind::State => {
+            let mut graph = flowchart::parse_state_diagram(&lines, &mut skipped)?;
+            graph.config = flowchart::parse_flowchart_config(&frontmatter);
+            DiagramType::Flowchart(graph)
+        }
+        DiagramKind::GitGraph => {
+            // Commands keep their `=`s
+            numbered = &body;
+            let lines: Vec<&str> = body.iter().map(|(_, l)| *l).collect();
+            let graph = gitgraph::parse_gitgraph_lines(&lines, &frontmatter, &mut skipped)?;
+            DiagramType::GitGraph(graph)
+        }
+        // Unknown headers get the flowchart parser's header error
+        _ => {
+            let mut graph = flowchart::parse_flowchart(&lines, &mut skipped)?;
+            graph.config = flowchart::parse_flowchart_config(&frontmatter);
+            DiagramType::Flowchart(graph)
+        }
     };
 
-    Ok(ParsedDiagram {
+    let mut warnings = Vec::new();
+    for (line, statement) in skipped.into_iter().map(|i| numbered[i]) {
+        match mode {
+            ParseMode::Strict => {
+                return Err(format!(
+                    "Line {}: unrecognized statement: {}",
+                    line, statement
+                ))
+            }
+            ParseMode::Lenient => warnings.push(Diagnostic {
+                severity: Severity::Warning,
+                line: Some(line),
+                message: format!("Skipped unrecognized statement: {}", statement),
+            }),
+        }
+    }
+
+    let parsed = ParsedDiagram {
         diagram,
         frontmatter,
-    })
+    };
+    Ok((parsed, warnings))
+}
+
+/// Take the `accTitle: ...`, `accDescr: ...` and `accDescr { ... }` statements
+/// out of the diagram text. Returns the title, the description and the text
+/// with blank lines in their place, so the other lines keep their numbers.
+fn extract_accessibility(text: &str) -> (Option<String>, Option<String>, String) {
+    let mut title = None;
+    let mut descr = None;
+    let mut rest = Vec::new();
+    let mut block: Option<Vec<&str>> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(lines) = &mut block {
+            match trimmed.strip_suffix('}') {
+                Some(last) => {
+                    lines.push(last.trim());
+                    let text = lines.iter().filter(|l| !l.is_empty()).copied();
+                    descr = Some(text.collect::<Vec<_>>().join("\n"));
+                    block = None;
+                }
+                None => lines.push(trimmed),
+            }
+            rest.push("");
+            continue;
+        }
+
+        if let Some(value) = statement_value(trimmed, "accTitle") {
+            title = Some(value.to_string());
+        } else if let Some(value) = statement_value(trimmed, "accDescr") {
+            descr = Some(value.to_string());
+        } else if let Some(open) = trimmed
+            .strip_prefix("accDescr")
+            .and_then(|r| r.trim_start().strip_prefix('{'))
+        {
+            match open.trim().strip_suffix('}') {
+                Some(inline) => descr = Some(inline.trim().to_string()),
+                None => block = Some(vec![open.trim()]),
+            }
+        } else {
+            rest.push(line);
+            continue;
+        }
+        rest.push("");
+    }
+
+    (title, descr, rest.join("\n"))
+}
+
+/// Value of a `keyword: value` statement
+fn statement_value<'a>(line: &'a str, keyword: &str) -> Option<&'a str> {
+    let value = line.strip_prefix(keyword)?.trim_start().strip_prefix(':')?;
+    Some(value.trim())
 }
 
 /// Parse YAML frontmatter and return common config + remaining text.
@@ -101,6 +269,11 @@ pub fn parse_frontmatter(text: &str) -> (FrontmatterConfig, String) {
     let mut config = FrontmatterConfig {
         theme: MermaidTheme::Default,
         title: None,
+        layout: FlowchartLayout::Grid,
+        curve: None,
+        edge_routing: None,
+        acc_title: None,
+        acc_descr: None,
         raw_lines: fm_lines,
     };
 
@@ -116,6 +289,20 @@ pub fn parse_frontmatter(text: &str) -> (FrontmatterConfig, String) {
                 config.title = Some(title);
             }
         }
+        if let Some(val) = extract_yaml_value(trimmed, "layout:") {
+            if let Some(layout) =
+                FlowchartLayout::from_name(val.trim().trim_matches('\'').trim_matches('"'))
+            {
+                config.layout = layout;
+            }
+        }
+        if let Some(val) = extract_yaml_value(trimmed, "curve:") {
+            config.curve = CurveStyle::from_name(val.trim().trim_matches('\'').trim_matches('"'));
+        }
+        if let Some(val) = extract_yaml_value(trimmed, "edgeRouting:") {
+            config.edge_routing =
+                EdgeRouting::from_name(val.trim().trim_matches('\'').trim_matches('"'));
+        }
     }
 
     // Reconstruct text without frontmatter
@@ -126,40 +313,60 @@ pub fn parse_frontmatter(text: &str) -> (FrontmatterConfig, String) {
 
 /// Extract value after a YAML key (case-insensitive key match)
 pub fn extract_yaml_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
-    let lower = line.to_lowercase();
-    let key_lower = key.to_lowercase();
-    if let Some(pos) = lower.find(&key_lower) {
-        let before = &lower[..pos];
-        if before
-            .chars()
-            .all(|c| c.is_whitespace() || c == '\'' || c == '"')
-        {
-            let after = &line[pos + key.len()..];
-            return Some(after.trim());
-        }
+    let pos = find_ignore_case(line, key)?;
+    let before = &line[..pos];
+    if before
+        .chars()
+        .all(|c| c.is_whitespace() || c == '\'' || c == '"')
+    {
+        let after = &line[pos + key.len()..];
+        return Some(after.trim());
     }
     None
 }
 
-/// Detect the diagram type from the mermaid source text
-pub fn detect_diagram_type(text: &str) -> &'static str {
+/// Byte offset of the first match of the ASCII `key` in `line`, ignoring
+/// ASCII case.
+///
+/// Lowercasing the whole line instead would shift the offsets of the
+/// characters after one whose lowercase takes more or fewer bytes (`İ`, `K`).
+pub(crate) fn find_ignore_case(line: &str, key: &str) -> Option<usize> {
+    line.char_indices().map(|(i, _)| i).find(|&i| {
+        line.as_bytes()
+            .get(i..i + key.len())
+            .is_some_and(|bytes| bytes.eq_ignore_ascii_case(key.as_bytes()))
+    })
+}
+
+/// The error for a diagram of a kind m2svg doesn't render, naming the kinds
+/// it does
+fn unsupported_kind_error(kind: DiagramKind) -> String {
+    let supported: Vec<&str> = DiagramKind::SUPPORTED.iter().map(|k| k.name()).collect();
+    format!(
+        "{} diagrams are not supported. Supported diagram types: {}",
+        kind.name(),
+        supported.join(", ")
+    )
+}
+
+/// Detect the kind of diagram from the mermaid source text, by the first line
+/// after the frontmatter and comments
+///
+/// # Example
+/// ```rust
+/// use m2svg::{detect_diagram_type, DiagramKind};
+///
+/// assert_eq!(detect_diagram_type("graph LR\n  A --> B"), DiagramKind::Flowchart);
+/// let kind = detect_diagram_type("%% pets\npie title Pets\n  \"Dogs\" : 386");
+/// assert_eq!(kind, DiagramKind::Pie);
+/// assert!(!kind.is_supported());
+/// ```
+pub fn detect_diagram_type(text: &str) -> DiagramKind {
     let (_, text_clean) = parse_frontmatter(text);
-    let first_line = text_clean
-        .trim()
+    text_clean
         .lines()
-        .next()
-        .map(|l| l.trim().to_lowercase())
-        .unwrap_or_default();
-
-    if first_line.starts_with("sequencediagram") {
-        "sequence"
-    } else if first_line.starts_with("classdiagram") {
-        "class"
-    } else if first_line.starts_with("erdiagram") {
-        "er"
-    } else if first_line.starts_with("gitgraph") {
-        "gitgraph"
-    } else {
-        "flowchart"
-    }
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with("%%"))
+        .map(DiagramKind::from_header)
+        .unwrap_or(DiagramKind::Unknown)
 }
@@ -0,0 +1,539 @@
+//! PlantUML front-end
+//!
+//! Reads the common subset of PlantUML sequence and class diagrams into the
+//! same types as the Mermaid parsers, so they go through the same renderers.
+//! Sequence diagrams are read directly; class diagram lines are rewritten into
+//! Mermaid's class syntax, which is close, and handed to the class parser.
+//! Styling (`skinparam`, colors), `hide`/`show` and other statements with no
+//! Mermaid counterpart are skipped.
+
+use super::class::parse_class_diagram;
+use crate::types::{
+    Activation, Actor, ActorType, ArrowHead, Block, BlockDivider, BlockType, DiagramType,
+    FrontmatterConfig, LineStyle, Message, Note, NotePosition, ParsedDiagram, SequenceDiagram,
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref PARTICIPANT_RE: Regex = Regex::new(
+        r#"^(participant|actor|boundary|control|entity|database|collections|queue)\s+("[^"]+"|\S+)(?:\s+as\s+("[^"]+"|\S+))?"#
+    )
+    .unwrap();
+    static ref MESSAGE_RE: Regex = Regex::new(
+        r#"^("[^"]+"|[\w.]+)\s*(<<?|x|o)?(-{1,2})(?:\[[^\]]*\])?-?(>>?|x|o)?\s*("[^"]+"|[\w.]+)\s*(\+\+|--)?\s*(?::\s*(.*))?$"#
+    )
+    .unwrap();
+    static ref NOTE_RE: Regex =
+        Regex::new(r"^[hr]?note\s+(left|right|over)(?:\s+of)?\s*([^:]*?)\s*(?::\s*(.*))?$").unwrap();
+    static ref BLOCK_RE: Regex =
+        Regex::new(r"^(loop|alt|opt|par|critical|break|group)\b\s*(.*)$").unwrap();
+    static ref CLASS_DECL_RE: Regex = Regex::new(
+        r#"^(abstract\s+class|abstract|class|interface|enum|annotation|entity)\s+("[^"]+"|[\w.]+)(?:\s+as\s+([\w.]+))?\s*(<[^<>]+>)?\s*(<<[^>]+>>)?[^{]*?(\{)?(\s*\})?$"#
+    )
+    .unwrap();
+    static ref RELATION_RE: Regex = Regex::new(
+        r#"^(\w+)\s*("[^"]*")?\s*(<\||\*|o|<)?([-.])[-.]*(?:(?:up|down|left|right|u|d|l|r)[-.]+)?(\|>|>|\*|o)?\s*("[^"]*")?\s*(\w+)\s*(?::\s*(.*))?$"#
+    )
+    .unwrap();
+    static ref INLINE_MEMBER_RE: Regex = Regex::new(r"^(\w+)\s*:\s*(.+)$").unwrap();
+    static ref CLASS_NOTE_RE: Regex = Regex::new(
+        r#"^note\s+(?:(?:top|bottom|left|right)\s+of\s+(\w+)|"([^"]+)"(?:\s+as\s+\w+)?)\s*(?::\s*(.*))?$"#
+    )
+    .unwrap();
+}
+
+/// Parse a PlantUML sequence or class diagram.
+///
+/// The `@startuml`/`@enduml` lines are optional. A `title` becomes the
+/// diagram title. The diagram is read as a class diagram when it declares a
+/// class, interface or enum or uses a class relationship arrow, and as a
+/// sequence diagram otherwise.
+///
+/// # Example
+/// ```rust
+/// use m2svg::{parse_plantuml, render_parsed_ascii, DiagramType};
+///
+/// let parsed = parse_plantuml(
+///     "@startuml\nactor User\nUser -> API : GET /items\nAPI --> User : 200 OK\n@enduml",
+/// )
+/// .unwrap();
+/// let DiagramType::Sequence(diagram) = &parsed.diagram else { unreachable!() };
+/// assert_eq!(diagram.actors.len(), 2);
+/// assert_eq!(diagram.messages[1].label, "200 OK");
+/// assert!(render_parsed_ascii(&parsed, None).unwrap().contains("GET /items"));
+///
+/// let parsed = parse_plantuml("interface Shape\nclass Circle {\n  + area() : double\n}\nShape <|.. Circle").unwrap();
+/// let DiagramType::Class(diagram) = &parsed.diagram else { unreachable!() };
+/// assert_eq!(diagram.classes[0].annotation.as_deref(), Some("interface"));
+/// assert_eq!(diagram.classes[1].methods[0].member_type.as_deref(), Some("double"));
+/// assert_eq!(diagram.relationships.len(), 1);
+/// ```
+pub fn parse_plantuml(text: &str) -> Result<ParsedDiagram, String> {
+    let mut frontmatter = FrontmatterConfig::default();
+    let lines: Vec<&str> = strip_comments(text)
+        .into_iter()
+        .filter(|l| !l.starts_with("@startuml") && !l.starts_with("@enduml"))
+        .filter(|l| {
+            let title = l.strip_prefix("title ").map(str::trim);
+            if let Some(title) = title.filter(|t| !t.is_empty()) {
+                frontmatter.title = Some(title.to_string());
+            }
+            title.is_none()
+        })
+        .collect();
+    if lines.is_empty() {
+        return Err("Empty PlantUML diagram".to_string());
+    }
+
+    let diagram = if is_class_diagram(&lines) {
+        DiagramType::Class(parse_class(&lines)?)
+    } else {
+        DiagramType::Sequence(parse_sequence(&lines))
+    };
+    Ok(ParsedDiagram {
+        diagram,
+        frontmatter,
+    })
+}
+
+/// The trimmed, non-empty lines of `text` without `'` and `/' ... '/` comments
+fn strip_comments(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut in_block = false;
+    for line in text.lines().map(str::trim) {
+        if in_block {
+            in_block = !line.ends_with("'/");
+            continue;
+        }
+        if line.starts_with("/'") {
+            in_block = !line.ends_with("'/") || line.len() < 4;
+            continue;
+        }
+        if !line.is_empty() && !line.starts_with('\'') {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+fn is_class_diagram(lines: &[&str]) -> bool {
+    lines.iter().any(|line| {
+        let first = line.split_whitespace().next().unwrap_or("");
+        matches!(first, "class" | "interface" | "enum" | "abstract")
+            || ["<|--", "--|>", "<|..", "..|>", "*--", "--*", "o--", "--o"]
+                .iter()
+                .any(|arrow| line.contains(arrow))
+    })
+}
+
+/// Names may be quoted: `"Web Server"`
+fn unquote(name: &str) -> &str {
+    name.trim_matches('"')
+}
+
+fn parse_sequence(lines: &[&str]) -> SequenceDiagram {
+    let mut diagram = SequenceDiagram::new();
+    let mut blocks: Vec<Block> = Vec::new();
+    // Multi-line note being read, up to `end note`
+    let mut open_note: Option<Note> = None;
+
+    for &line in lines {
+        if let Some(note) = open_note.as_mut() {
+            if line == "end note" || line == "endnote" {
+                diagram.notes.extend(open_note.take());
+            } else {
+                if !note.text.is_empty() {
+                    note.text.push('\n');
+                }
+                note.text.push_str(line);
+            }
+            continue;
+        }
+
+        if let Some(caps) = PARTICIPANT_RE.captures(line) {
+            // `participant "Long Name" as L` or `participant L as "Long Name"`
+            let (first, alias) = (unquote(&caps[2]), caps.get(3).map(|m| unquote(m.as_str())));
+            let (id, label) = match alias {
+                Some(alias) if caps[2].starts_with('"') => (alias, first),
+                Some(alias) => (first, alias),
+                None => (first, first),
+            };
+            let actor_type = if &caps[1] == "actor" {
+                ActorType::Actor
+            } else {
+                ActorType::Participant
+            };
+            match diagram.actors.iter_mut().find(|a| a.id == id) {
+                Some(actor) => {
+                    actor.label = label.to_string();
+                    actor.actor_type = actor_type;
+                }
+                None => diagram.actors.push(Actor {
+                    id: id.to_string(),
+                    label: label.to_string(),
+                    actor_type,
+                }),
+            }
+            continue;
+        }
+
+        if let Some(caps) = NOTE_RE.captures(line) {
+            let position = match &caps[1] {
+                "left" => NotePosition::Left,
+                "right" => NotePosition::Right,
+                _ => NotePosition::Over,
+            };
+            // `note left: ...` sits next to the sender of the last message
+            let mut actor_ids: Vec<String> = caps[2]
+                .split(',')
+                .map(|a| unquote(a.trim()).to_string())
+                .filter(|a| !a.is_empty())
+                .collect();
+            if actor_ids.is_empty() {
+                let Some(last) = diagram.messages.last() else {
+                    continue;
+                };
+                actor_ids.push(last.from.clone());
+            }
+            for id in &actor_ids {
+                ensure_actor(&mut diagram, id);
+            }
+            let note = Note {
+                actor_ids,
+                text: caps.get(3).map_or("", |m| m.as_str().trim()).to_string(),
+                position,
+                after_index: diagram.messages.len() as i32 - 1,
+            };
+            match caps.get(3) {
+                Some(_) => diagram.notes.push(note),
+                None => open_note = Some(note),
+            }
+            continue;
+        }
+
+        if let Some(caps) = BLOCK_RE.captures(line) {
+            let block_type = match &caps[1] {
+                "loop" => BlockType::Loop,
+                "alt" => BlockType::Alt,
+                "opt" => BlockType::Opt,
+                "par" => BlockType::Par,
+                "critical" => BlockType::Critical,
+                "break" => BlockType::Break,
+                _ => BlockType::Rect,
+            };
+            blocks.push(Block {
+                block_type,
+                label: caps[2].trim().to_string(),
+                start_index: diagram.messages.len(),
+                end_index: 0,
+                dividers: Vec::new(),
+            });
+            continue;
+        }
+        if line == "else" || line.starts_with("else ") {
+            if let Some(block) = blocks.last_mut() {
+                block.dividers.push(BlockDivider {
+                    index: diagram.messages.len(),
+                    label: line["else".len()..].trim().to_string(),
+                });
+            }
+            continue;
+        }
+        if line == "end" {
+            if let Some(mut block) = blocks.pop() {
+                block.end_index = diagram
+                    .messages
+                    .len()
+                    .saturating_sub(1)
+                    .max(block.start_index);
+                diagram.blocks.push(block);
+            }
+            continue;
+        }
+
+        // `activate B` right after a message to B, and `deactivate B` right
+        // after one from B, become that message's activation marks; others
+        // stay statements of their own
+        let activation = match line.split_once(' ') {
+            Some(("activate", id)) => Some((true, unquote(id.trim()))),
+            Some(("deactivate", id)) => Some((false, unquote(id.trim()))),
+            _ => None,
+        };
+        if let Some((active, id)) = activation {
+            // A message carries one mark at most
+            let unmarked = diagram
+                .messages
+                .last_mut()
+                .filter(|m| !m.activate && !m.deactivate);
+            match unmarked {
+                Some(last) if active && last.to == id => last.activate = true,
+                Some(last) if !active && last.from == id => last.deactivate = true,
+                _ => diagram.activations.push(Activation {
+                    actor_id: id.to_string(),
+                    active,
+                    after_index: diagram.messages.len() as i32 - 1,
+                }),
+            }
+            continue;
+        }
+
+        if let Some(caps) = MESSAGE_RE.captures(line) {
+            let (mut from, mut to) = (unquote(&caps[1]), unquote(&caps[5]));
+            let left = caps.get(2).map_or("", |m| m.as_str());
+            let right = caps.get(4).map_or("", |m| m.as_str());
+            // `B <- A` points the other way, unless it is two-headed
+            let reversed = left.starts_with('<') && right.is_empty();
+            if reversed {
+                std::mem::swap(&mut from, &mut to);
+            }
+            let head = if reversed { left } else { right };
+            ensure_actor(&mut diagram, from);
+            ensure_actor(&mut diagram, to);
+            let activation = caps.get(6).map_or("", |m| m.as_str());
+            diagram.messages.push(Message {
+                from: from.to_string(),
+                to: to.to_string(),
+                label: caps.get(7).map_or("", |m| m.as_str().trim()).to_string(),
+                line_style: if &caps[3] == "--" {
+                    LineStyle::Dashed
+                } else {
+                    LineStyle::Solid
+                },
+                // PlantUML's `->` has a filled head and `->>` a thin one
+                arrow_head: if head == ">>" || head == "<<" {
+                    ArrowHead::Open
+                } else {
+                    ArrowHead::Filled
+                },
+                activate: activation == "++",
+                deactivate: activation == "--",
+            });
+        }
+    }
+
+    diagram
+}
+
+fn ensure_actor(diagram: &mut SequenceDiagram, id: &str) {
+    if !diagram.actors.iter().any(|a| a.id == id) {
+        diagram.actors.push(Actor {
+            id: id.to_string(),
+            label: id.to_string(),
+            actor_type: ActorType::Participant,
+        });
+    }
+}
+
+fn parse_class(lines: &[&str]) -> Result<crate::types::ClassDiagram, String> {
+    let mut mermaid = vec!["classDiagram".to_string()];
+    let mut in_body = false;
+    // Multi-line note: its class (or none for a floating note) and text
+    let mut open_note: Option<(Option<String>, Vec<String>)> = None;
+
+    for &line in lines {
+        if let Some((class, text)) = open_note.as_mut() {
+            if line == "end note" || line == "endnote" {
+                mermaid.push(class_note(class.as_deref(), &text.join(" ")));
+                open_note = None;
+            } else {
+                text.push(line.to_string());
+            }
+            continue;
+        }
+
+        if in_body {
+            if line == "}" {
+                in_body = false;
+                mermaid.push("}".to_string());
+            } else if let Some(member) = class_member(line) {
+                mermaid.push(member);
+            }
+            continue;
+        }
+
+        if let Some(caps) = CLASS_DECL_RE.captures(line) {
+            let name = caps[2].trim_matches('"');
+            let id = caps
+                .get(3)
+                .map_or(name, |m| m.as_str())
+                .replace([' ', '.'], "_");
+            // Mermaid takes one type parameter: `Box<T>` becomes `Box~T~`
+            let generic = caps
+                .get(4)
+                .map(|g| g.as_str().trim_matches(['<', '>']).trim())
+                .filter(|g| g.chars().all(|c| c.is_alphanumeric() || c == '_'))
+                .map(|g| format!("~{}~", g))
+                .unwrap_or_default();
+            let annotation = match &caps[1] {
+                "interface" => Some("interface".to_string()),
+                "enum" => Some("enumeration".to_string()),
+                "annotation" => Some("annotation".to_string()),
+                "entity" => Some("entity".to_string()),
+                "class" => None,
+                _ => Some("abstract".to_string()),
+            }
+            .or_else(|| {
+                caps.get(5)
+                    .map(|s| s.as_str().trim_matches(['<', '>']).trim().to_string())
+            });
+            mermaid.push(format!("class {}{} {{", id, generic));
+            if let Some(annotation) = annotation {
+                mermaid.push(format!("<<{}>>", annotation.replace(' ', "_")));
+            }
+            if caps.get(6).is_some() && caps.get(7).is_none() {
+                in_body = true;
+            } else {
+                mermaid.push("}".to_string());
+            }
+            continue;
+        }
+
+        if let Some(caps) = CLASS_NOTE_RE.captures(line) {
+            let class = caps.get(1).map(|m| m.as_str().to_string());
+            match (caps.get(2), caps.get(3)) {
+                (Some(floating), _) => mermaid.push(class_note(None, floating.as_str())),
+                (None, Some(text)) => mermaid.push(class_note(class.as_deref(), text.as_str())),
+                (None, None) => open_note = Some((class, Vec::new())),
+            }
+            continue;
+        }
+
+        if let Some(rest) = line.strip_suffix('{') {
+            // `package a.b {` and `namespace a.b {`
+            if let Some(name) = rest
+                .strip_prefix("package ")
+                .or_else(|| rest.strip_prefix("namespace "))
+            {
+                let name = name.trim().trim_matches('"').replace([' ', '.'], "_");
+                mermaid.push(format!("namespace {} {{", name));
+                continue;
+            }
+        }
+        if line == "}" {
+            mermaid.push("}".to_string());
+            continue;
+        }
+
+        match line {
+            "left to right direction" => mermaid.push("direction LR".to_string()),
+            "top to bottom direction" => mermaid.push("direction TB".to_string()),
+            _ => {
+                if let Some(relation) = class_relation(line) {
+                    mermaid.push(relation);
+                } else if let Some(caps) = INLINE_MEMBER_RE.captures(line) {
+                    // `Foo : +bar()` adds a member to Foo
+                    if let Some(member) = class_member(&caps[2]) {
+                        mermaid.push(format!("{} : {}", &caps[1], member));
+                    }
+                }
+            }
+        }
+    }
+
+    let mermaid: Vec<&str> = mermaid.iter().map(String::as_str).collect();
+    parse_class_diagram(&mermaid, &mut Vec::new())
+}
+
+fn class_note(class: Option<&str>, text: &str) -> String {
+    let text = text.replace('"', "'");
+    match class {
+        Some(class) => format!("note for {} \"{}\"", class, text),
+        None => format!("note \"{}\"", text),
+    }
+}
+
+/// A member line in Mermaid's syntax: `{static}` and `{abstract}` become a
+/// trailing `$` or `*`, a method's `: Type` its trailing return type, and
+/// `name : Type` parameters just their names. Separator lines (`--`, `..`,
+/// `==`, `__`) are dropped.
+fn class_member(line: &str) -> Option<String> {
+    let line = line.trim_end_matches(';').trim();
+    let separator = ["--", "..", "==", "__"]
+        .iter()
+        .any(|s| line.starts_with(s) && line.ends_with(s));
+    if line.is_empty() || separator {
+        return None;
+    }
+    let mut mark = "";
+    let mut member = line.to_string();
+    for (modifier, symbol) in [
+        ("{static}", "$"),
+        ("{abstract}", "*"),
+        ("{classifier}", "$"),
+    ] {
+        if member.contains(modifier) {
+            member = member.replace(modifier, "").trim().to_string();
+            mark = symbol;
+        }
+    }
+    let (Some(open), Some(close)) = (member.find('('), member.rfind(')')) else {
+        return Some(format!("{}{}", member, mark));
+    };
+    // `+name(a : int) : Type` -> `+name(a) Type`
+    let params: Vec<&str> = member[open + 1..close]
+        .split(',')
+        .map(|p| p.split(':').next().unwrap_or("").trim())
+        .filter(|p| !p.is_empty())
+        .collect();
+    let return_type = member[close + 1..].trim().trim_start_matches(':').trim();
+    let mut method = format!("{}({})", member[..open].trim(), params.join(", "));
+    if !return_type.is_empty() {
+        method.push(' ');
+        method.push_str(return_type);
+    }
+    method.push_str(mark);
+    Some(method)
+}
+
+/// A relationship line in Mermaid's syntax. Direction hints (`-up->`), longer
+/// arrows (`<|---`) and label arrows (`: owns >`) are dropped, and PlantUML's
+/// dotted composition and aggregation become solid.
+fn class_relation(line: &str) -> Option<String> {
+    let caps = RELATION_RE.captures(line)?;
+    let left = caps.get(3).map_or("", |m| m.as_str());
+    let right = caps.get(5).map_or("", |m| m.as_str());
+    let dotted = &caps[4] == ".";
+    let arrow = match (left, right, dotted) {
+        ("<|", _, false) => "<|--",
+        ("<|", _, true) => "<|..",
+        ("*", _, _) => "*--",
+        ("o", _, _) => "o--",
+        ("<", _, false) => "<--",
+        ("<", _, true) => "<..",
+        (_, "|>", false) => "--|>",
+        (_, "|>", true) => "..|>",
+        (_, "*", _) => "--*",
+        (_, "o", _) => "--o",
+        (_, ">", false) => "-->",
+        (_, ">", true) => "..>",
+        (_, _, false) => "--",
+        (_, _, true) => "..",
+    };
+    let mut relation = caps[1].to_string();
+    if let Some(card) = caps.get(2) {
+        relation.push(' ');
+        relation.push_str(card.as_str());
+    }
+    relation.push(' ');
+    relation.push_str(arrow);
+    if let Some(card) = caps.get(6) {
+        relation.push(' ');
+        relation.push_str(card.as_str());
+    }
+    relation.push(' ');
+    relation.push_str(&caps[7]);
+    if let Some(label) = caps.get(8) {
+        let label = label
+            .as_str()
+            .trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .trim();
+        if !label.is_empty() {
+            relation.push_str(" : ");
+            relation.push_str(label);
+        }
+    }
+    Some(relation)
+}
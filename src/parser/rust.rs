@@ -0,0 +1,393 @@
+//! Rust source front-end (the `rust` feature)
+//!
+//! Reads the structs, enums and traits of Rust files into a [`ClassDiagram`],
+//! so a crate can be drawn as an architecture overview. Fields and enum
+//! variants become attributes, and the functions of inherent impls and
+//! traits become methods. `impl Trait for Type` is a realization, a
+//! supertrait an inheritance, and a field holding another of the types a
+//! composition (or an association when it is behind a reference, `Rc`, `Arc`
+//! or `Weak`). Only types defined in the sources are drawn, so derives and
+//! impls of standard library traits are left out.
+
+use std::collections::HashSet;
+
+use quote::ToTokens;
+use syn::{
+    Fields, FnArg, GenericArgument, GenericParam, Generics, ImplItem, Item, Pat, PathArguments,
+    ReturnType, Signature, TraitItem, Type, TypeParamBound,
+};
+
+use crate::types::{
+    ClassDiagram, ClassMember, ClassNode, ClassRelationship, DiagramType, FrontmatterConfig,
+    ParsedDiagram, RelationshipType, Visibility,
+};
+
+/// Parse Rust source files, given as `(name, text)` pairs, into one class
+/// diagram.
+///
+/// Items of inline `mod` blocks are read too. Types are known by their name
+/// alone, so same-named types of different modules are drawn as one class.
+///
+/// # Example
+/// ```rust
+/// use m2svg::{parse_rust, render_parsed_ascii, DiagramType, RelationshipType};
+///
+/// let source = "
+///     pub trait Shape { fn area(&self) -> f64; }
+///     pub struct Circle { pub radius: f64 }
+///     impl Shape for Circle { fn area(&self) -> f64 { 3.14 * self.radius * self.radius } }
+///     pub struct Canvas { shapes: Vec<Circle> }
+/// ";
+/// let parsed = parse_rust(&[("shapes.rs", source)]).unwrap();
+/// let DiagramType::Class(diagram) = &parsed.diagram else { unreachable!() };
+/// assert_eq!(diagram.classes.len(), 3);
+/// assert_eq!(diagram.classes[1].attributes[0].name, "radius");
+/// assert_eq!(diagram.relationships[0].rel_type, RelationshipType::Realization);
+/// assert_eq!(diagram.relationships[1].rel_type, RelationshipType::Composition);
+/// assert!(render_parsed_ascii(&parsed, None).unwrap().contains("Canvas"));
+/// ```
+pub fn parse_rust(sources: &[(&str, &str)]) -> Result<ParsedDiagram, String> {
+    let mut files = Vec::new();
+    for (name, text) in sources {
+        files.push(syn::parse_file(text).map_err(|e| format!("{}: {}", name, e))?);
+    }
+    let mut items = Vec::new();
+    for file in &files {
+        flatten_items(&file.items, &mut items);
+    }
+
+    let mut diagram = ClassDiagram::default();
+    // Types first, so impls and fields can refer to types of later files
+    for item in &items {
+        let class = match item {
+            Item::Struct(s) => ClassNode {
+                attributes: fields(&s.fields),
+                ..class_node(&s.ident.to_string(), &s.generics, None)
+            },
+            Item::Enum(e) => ClassNode {
+                attributes: e
+                    .variants
+                    .iter()
+                    .map(|v| attribute(Visibility::None, v.ident.to_string(), None))
+                    .collect(),
+                ..class_node(&e.ident.to_string(), &e.generics, Some("enumeration"))
+            },
+            Item::Trait(t) => ClassNode {
+                methods: t
+                    .items
+                    .iter()
+                    .filter_map(|item| match item {
+                        TraitItem::Fn(f) => Some(ClassMember {
+                            is_abstract: f.default.is_none(),
+                            ..method(Visibility::Public, &f.sig)
+                        }),
+                        _ => None,
+                    })
+                    .collect(),
+                ..class_node(&t.ident.to_string(), &t.generics, Some("trait"))
+            },
+            _ => continue,
+        };
+        if !diagram.classes.iter().any(|c| c.id == class.id) {
+            diagram.classes.push(class);
+        }
+    }
+
+    let known: HashSet<String> = diagram.classes.iter().map(|c| c.id.clone()).collect();
+    let mut relationships = Vec::new();
+    for item in &items {
+        match item {
+            Item::Struct(s) => {
+                for field in &s.fields {
+                    field_relationships(
+                        &s.ident.to_string(),
+                        &field.ty,
+                        &known,
+                        &mut relationships,
+                    );
+                }
+            }
+            Item::Enum(e) => {
+                for field in e.variants.iter().flat_map(|v| &v.fields) {
+                    field_relationships(
+                        &e.ident.to_string(),
+                        &field.ty,
+                        &known,
+                        &mut relationships,
+                    );
+                }
+            }
+            Item::Trait(t) => {
+                for bound in &t.supertraits {
+                    let TypeParamBound::Trait(bound) = bound else {
+                        continue;
+                    };
+                    let Some(supertrait) = bound.path.segments.last() else {
+                        continue;
+                    };
+                    let supertrait = supertrait.ident.to_string();
+                    if known.contains(&supertrait) {
+                        relationships.push(relationship(
+                            supertrait,
+                            t.ident.to_string(),
+                            RelationshipType::Inheritance,
+                        ));
+                    }
+                }
+            }
+            Item::Impl(imp) => {
+                let Some(self_type) = type_name(&imp.self_ty).filter(|n| known.contains(n)) else {
+                    continue;
+                };
+                match &imp.trait_ {
+                    Some((_, path, _)) => {
+                        let Some(tr) = path.segments.last() else {
+                            continue;
+                        };
+                        let tr = tr.ident.to_string();
+                        if known.contains(&tr) {
+                            relationships.push(ClassRelationship {
+                                marker_at_from: false,
+                                ..relationship(self_type, tr, RelationshipType::Realization)
+                            });
+                        }
+                    }
+                    None => {
+                        let class = diagram
+                            .classes
+                            .iter_mut()
+                            .find(|c| c.id == self_type)
+                            .expect("impl of a known type");
+                        for item in &imp.items {
+                            if let ImplItem::Fn(f) = item {
+                                class.methods.push(method(visibility(&f.vis), &f.sig));
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    for rel in relationships {
+        let duplicate = diagram
+            .relationships
+            .iter()
+            .any(|r| r.from == rel.from && r.to == rel.to && r.rel_type == rel.rel_type);
+        if !duplicate {
+            diagram.relationships.push(rel);
+        }
+    }
+
+    Ok(ParsedDiagram {
+        diagram: DiagramType::Class(diagram),
+        frontmatter: FrontmatterConfig::default(),
+    })
+}
+
+/// Add `items`, and the items of the inline modules among them, to `out`
+fn flatten_items<'a>(items: &'a [Item], out: &mut Vec<&'a Item>) {
+    for item in items {
+        match item {
+            Item::Mod(m) => {
+                if let Some((_, content)) = &m.content {
+                    flatten_items(content, out);
+                }
+            }
+            _ => out.push(item),
+        }
+    }
+}
+
+/// A class named `name`, labelled with its type parameters as `Name<T, U>`
+fn class_node(name: &str, generics: &Generics, annotation: Option<&str>) -> ClassNode {
+    let params: Vec<String> = generics
+        .params
+        .iter()
+        .filter_map(|p| match p {
+            GenericParam::Type(t) => Some(t.ident.to_string()),
+            _ => None,
+        })
+        .collect();
+    let label = if params.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}<{}>", name, params.join(", "))
+    };
+    ClassNode {
+        id: name.to_string(),
+        label,
+        attributes: Vec::new(),
+        methods: Vec::new(),
+        annotation: annotation.map(str::to_string),
+        is_lollipop: false,
+    }
+}
+
+/// The fields of a struct or variant as attributes; tuple fields are named
+/// by their index
+fn fields(fields: &Fields) -> Vec<ClassMember> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let name = field
+                .ident
+                .as_ref()
+                .map_or_else(|| i.to_string(), |ident| ident.to_string());
+            attribute(visibility(&field.vis), name, Some(tokens(&field.ty)))
+        })
+        .collect()
+}
+
+fn attribute(visibility: Visibility, name: String, member_type: Option<String>) -> ClassMember {
+    ClassMember {
+        visibility,
+        name,
+        member_type,
+        is_static: false,
+        is_abstract: false,
+        is_method: false,
+        params: None,
+    }
+}
+
+/// A function as a method: its parameter names and return type, static when
+/// it takes no `self`
+fn method(visibility: Visibility, sig: &Signature) -> ClassMember {
+    let params: Vec<String> = sig
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(arg) => Some(match &*arg.pat {
+                Pat::Ident(p) => p.ident.to_string(),
+                pat => tokens(pat),
+            }),
+            FnArg::Receiver(_) => None,
+        })
+        .collect();
+    ClassMember {
+        visibility,
+        name: sig.ident.to_string(),
+        member_type: match &sig.output {
+            ReturnType::Default => None,
+            ReturnType::Type(_, ty) => Some(tokens(ty)),
+        },
+        is_static: sig.receiver().is_none(),
+        is_abstract: false,
+        is_method: true,
+        params: (!params.is_empty()).then(|| params.join(", ")),
+    }
+}
+
+/// `pub` items are public, `pub(crate)` and the like package-wide, and the
+/// rest private
+fn visibility(vis: &syn::Visibility) -> Visibility {
+    match vis {
+        syn::Visibility::Public(_) => Visibility::Public,
+        syn::Visibility::Restricted(_) => Visibility::Package,
+        syn::Visibility::Inherited => Visibility::Private,
+    }
+}
+
+fn relationship(from: String, to: String, rel_type: RelationshipType) -> ClassRelationship {
+    ClassRelationship {
+        from,
+        to,
+        rel_type,
+        from_cardinality: None,
+        to_cardinality: None,
+        label: None,
+        marker_at_from: true,
+    }
+}
+
+/// Add a relationship from `owner` to each known type that `ty` mentions:
+/// a composition, or an association when the type is only referred to
+fn field_relationships(
+    owner: &str,
+    ty: &Type,
+    known: &HashSet<String>,
+    out: &mut Vec<ClassRelationship>,
+) {
+    let mut mentioned = Vec::new();
+    mentioned_types(ty, false, &mut mentioned);
+    for (name, shared) in mentioned {
+        if name == owner || !known.contains(&name) {
+            continue;
+        }
+        out.push(if shared {
+            ClassRelationship {
+                marker_at_from: false,
+                ..relationship(owner.to_string(), name, RelationshipType::Association)
+            }
+        } else {
+            relationship(owner.to_string(), name, RelationshipType::Composition)
+        });
+    }
+}
+
+/// Collect the type names in `ty`, each with whether it sits behind a
+/// reference or pointer
+fn mentioned_types(ty: &Type, shared: bool, out: &mut Vec<(String, bool)>) {
+    match ty {
+        Type::Path(path) => {
+            let Some(last) = path.path.segments.last() else {
+                return;
+            };
+            let name = last.ident.to_string();
+            let shared = shared || matches!(name.as_str(), "Rc" | "Arc" | "Weak");
+            out.push((name, shared));
+            if let PathArguments::AngleBracketed(args) = &last.arguments {
+                for arg in &args.args {
+                    if let GenericArgument::Type(ty) = arg {
+                        mentioned_types(ty, shared, out);
+                    }
+                }
+            }
+        }
+        Type::Reference(r) => mentioned_types(&r.elem, true, out),
+        Type::Ptr(p) => mentioned_types(&p.elem, true, out),
+        Type::Array(a) => mentioned_types(&a.elem, shared, out),
+        Type::Slice(s) => mentioned_types(&s.elem, shared, out),
+        Type::Paren(p) => mentioned_types(&p.elem, shared, out),
+        Type::Group(g) => mentioned_types(&g.elem, shared, out),
+        Type::Tuple(t) => {
+            for elem in &t.elems {
+                mentioned_types(elem, shared, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The name of the type an impl is for, without its path and generics
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// Source text of a type or pattern, without the spaces the token stream
+/// puts around punctuation (`Vec < & 'a str >` reads `Vec<&'a str>`)
+fn tokens(node: &impl ToTokens) -> String {
+    let text = node.to_token_stream().to_string();
+    [
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        (" ;", ";"),
+        (" :: ", "::"),
+        (":: ", "::"),
+        ("& ", "&"),
+        ("* ", "*"),
+        ("( ", "("),
+        (" )", ")"),
+        ("[ ", "["),
+        (" ]", "]"),
+    ]
+    .iter()
+    .fold(text, |text, (from, to)| text.replace(from, to))
+}
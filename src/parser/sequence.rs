@@ -1,8 +1,9 @@
 //! Sequence diagram parser
 
+use super::extract_yaml_value;
 use crate::types::{
-    Actor, ActorType, ArrowHead, Block, BlockDivider, BlockType, LineStyle, Message, Note,
-    NotePosition, SequenceDiagram,
+    Activation, Actor, ActorType, ArrowHead, Block, BlockDivider, BlockType, FrontmatterConfig,
+    LineStyle, Message, Note, NotePosition, SequenceConfig, SequenceDiagram,
 };
 use regex::Regex;
 use std::collections::HashSet;
@@ -11,18 +12,60 @@ lazy_static::lazy_static! {
     static ref ACTOR_RE: Regex = Regex::new(r"^(participant|actor)\s+(\S+?)(?:\s+as\s+(.+))?$").unwrap();
     static ref NOTE_RE: Regex = Regex::new(r"(?i)^Note\s+(left of|right of|over)\s+([^:]+):\s*(.+)$").unwrap();
     static ref BLOCK_RE: Regex = Regex::new(r"^(loop|alt|opt|par|critical|break|rect)\s*(.*)$").unwrap();
+    static ref ACTIVATION_RE: Regex = Regex::new(r"^(activate|deactivate)\s+(\S+)$").unwrap();
     static ref DIVIDER_RE: Regex = Regex::new(r"^(else|and)\s*(.*)$").unwrap();
     static ref MSG_RE: Regex = Regex::new(r"^(\S+?)\s*(--?>?>|--?[)x]|--?>>|--?>)\s*([+-]?)(\S+?)\s*:\s*(.+)$").unwrap();
     static ref SIMPLE_MSG_RE: Regex = Regex::new(r"^(\S+?)\s*(->>|-->>|-\)|--\)|-x|--x|->|-->)\s*([+-]?)(\S+?)\s*:\s*(.+)$").unwrap();
 }
 
-/// Parse a Mermaid sequence diagram
-pub fn parse_sequence_diagram(lines: &[&str]) -> Result<SequenceDiagram, String> {
+/// Parse the sequence spacing of the frontmatter config: `actorMargin:`,
+/// `messageMargin:`, `boxMargin:` and `mirrorActors:`. Negative and
+/// unreadable values are ignored.
+pub fn parse_sequence_config(frontmatter: &FrontmatterConfig) -> SequenceConfig {
+    let mut config = SequenceConfig::default();
+    let margin = |val: &str| {
+        val.trim()
+            .trim_matches('\'')
+            .trim_matches('"')
+            .parse::<f64>()
+            .ok()
+            .filter(|m| m.is_finite() && *m >= 0.0)
+    };
+    for line in &frontmatter.raw_lines {
+        let trimmed = line.trim().trim_start_matches("- ");
+        if let Some(val) = extract_yaml_value(trimmed, "actorMargin:") {
+            config.actor_margin = margin(val).or(config.actor_margin);
+        }
+        if let Some(val) = extract_yaml_value(trimmed, "messageMargin:") {
+            config.message_margin = margin(val).or(config.message_margin);
+        }
+        if let Some(val) = extract_yaml_value(trimmed, "boxMargin:") {
+            config.box_margin = margin(val).or(config.box_margin);
+        }
+        if let Some(val) = extract_yaml_value(trimmed, "mirrorActors:") {
+            match val.trim() {
+                "true" => config.mirror_actors = Some(true),
+                "false" => config.mirror_actors = Some(false),
+                _ => {}
+            }
+        }
+    }
+    config
+}
+
+/// Parse a Mermaid sequence diagram. The indices of the lines that aren't
+/// statements go to `skipped`.
+pub fn parse_sequence_diagram(
+    lines: &[&str],
+    skipped: &mut Vec<usize>,
+) -> Result<SequenceDiagram, String> {
     let mut diagram = SequenceDiagram::new();
     let mut actor_ids: HashSet<String> = HashSet::new();
+    // Actors with a `participant`/`actor` line, rather than only messages
+    let mut declared: HashSet<String> = HashSet::new();
     let mut block_stack: Vec<BlockStackEntry> = Vec::new();
 
-    for line in lines.iter().skip(1) {
+    for (index, line) in lines.iter().enumerate().skip(1) {
         let line = *line;
 
         // Participant / Actor declaration
@@ -31,22 +74,30 @@ pub fn parse_sequence_diagram(lines: &[&str]) -> Result<SequenceDiagram, String>
             let id = caps[2].to_string();
             let label = caps
                 .get(3)
-                .map(|m| m.as_str().trim())
+                .map(|m| unquote(m.as_str().trim()))
                 .unwrap_or(&id)
                 .to_string();
+            let actor_type = if type_str == "actor" {
+                ActorType::Actor
+            } else {
+                ActorType::Participant
+            };
 
-            if !actor_ids.contains(&id) {
-                actor_ids.insert(id.clone());
+            if actor_ids.insert(id.clone()) {
                 diagram.actors.push(Actor {
-                    id,
+                    id: id.clone(),
                     label,
-                    actor_type: if type_str == "actor" {
-                        ActorType::Actor
-                    } else {
-                        ActorType::Participant
-                    },
+                    actor_type,
                 });
+            } else if !declared.contains(&id) {
+                // Declared after its first message: it keeps its place, but
+                // takes the alias and type of the declaration
+                if let Some(actor) = diagram.actors.iter_mut().find(|a| a.id == id) {
+                    actor.label = label;
+                    actor.actor_type = actor_type;
+                }
             }
+            declared.insert(id);
             continue;
         }
 
@@ -81,6 +132,16 @@ pub fn parse_sequence_diagram(lines: &[&str]) -> Result<SequenceDiagram, String>
             continue;
         }
 
+        // Activation
+        if let Some(caps) = ACTIVATION_RE.captures(line) {
+            diagram.activations.push(Activation {
+                actor_id: caps[2].to_string(),
+                active: &caps[1] == "activate",
+                after_index: diagram.messages.len() as i32 - 1,
+            });
+            continue;
+        }
+
         // Block start
         if let Some(caps) = BLOCK_RE.captures(line) {
             let block_type = match &caps[1] {
@@ -209,6 +270,8 @@ pub fn parse_sequence_diagram(lines: &[&str]) -> Result<SequenceDiagram, String>
             });
             continue;
         }
+
+        skipped.push(index);
     }
 
     Ok(diagram)
@@ -221,6 +284,13 @@ struct BlockStackEntry {
     dividers: Vec<BlockDivider>,
 }
 
+/// `text` without the double quotes around it, if it has them
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text)
+}
+
 fn ensure_actor(diagram: &mut SequenceDiagram, actor_ids: &mut HashSet<String>, id: &str) {
     if !actor_ids.contains(id) {
         actor_ids.insert(id.to_string());
@@ -0,0 +1,182 @@
+//! Per-participant views of sequence diagrams
+//!
+//! Cuts a large interaction trace down to what one service sees, so it can be
+//! rendered on its own.
+
+use crate::types::{Activation, Block, BlockDivider, Note, NotePosition, SequenceDiagram};
+
+/// Keep only the messages that `participant` sends or receives.
+///
+/// `participant` is matched against the ids, then the labels, of the
+/// participants. The view keeps that participant and the ones it talks to, in
+/// their original order. Where messages between others are dropped, a note
+/// over the participant says how many (`... 2 messages ...`). Notes on dropped
+/// participants and their activations go; blocks are kept around the messages
+/// left inside them.
+///
+/// # Example
+/// ```rust
+/// use m2svg::{parse_mermaid, project_participant, DiagramType};
+///
+/// let parsed = parse_mermaid(
+///     "sequenceDiagram\n  Alice->>Bob: Hi\n  Bob->>Carol: Forward\n  Carol->>Bob: Ack\n  Bob->>Alice: Done",
+/// )
+/// .unwrap();
+/// let DiagramType::Sequence(diagram) = &parsed.diagram else { unreachable!() };
+/// let view = project_participant(diagram, "Alice").unwrap();
+/// assert_eq!(view.messages.len(), 2);
+/// assert_eq!(view.notes[0].text, "... 2 messages ...");
+/// ```
+pub fn project_participant(
+    diagram: &SequenceDiagram,
+    participant: &str,
+) -> Result<SequenceDiagram, String> {
+    let id = diagram
+        .actors
+        .iter()
+        .find(|a| a.id == participant)
+        .or_else(|| diagram.actors.iter().find(|a| a.label == participant))
+        .map(|a| a.id.clone())
+        .ok_or_else(|| format!("No participant named {} in the diagram", participant))?;
+
+    let involved = |from: &str, to: &str| from == id || to == id;
+    let kept: Vec<bool> = diagram
+        .messages
+        .iter()
+        .map(|m| involved(&m.from, &m.to))
+        .collect();
+
+    // New index of each kept message, and of the last kept message at or before
+    // each original one (-1 before the first)
+    let mut new_index: Vec<Option<usize>> = Vec::with_capacity(kept.len());
+    let mut last_kept: Vec<i32> = Vec::with_capacity(kept.len());
+    let mut count = 0;
+    for &keep in &kept {
+        new_index.push(keep.then_some(count));
+        if keep {
+            count += 1;
+        }
+        last_kept.push(count as i32 - 1);
+    }
+
+    let mut projected = SequenceDiagram::new();
+    projected.actors = diagram
+        .actors
+        .iter()
+        .filter(|a| {
+            a.id == id
+                || diagram
+                    .messages
+                    .iter()
+                    .any(|m| involved(&m.from, &m.to) && (m.from == a.id || m.to == a.id))
+        })
+        .cloned()
+        .collect();
+
+    projected.messages = diagram
+        .messages
+        .iter()
+        .zip(&kept)
+        .filter(|(_, &keep)| keep)
+        .map(|(m, _)| m.clone())
+        .collect();
+
+    // A note over the participant for each run of dropped messages, and the
+    // notes on participants still shown. Each is keyed by where it falls in
+    // the original diagram: 2j + 2 for a note after message j, 2s + 1 for the
+    // elision of a run starting at message s
+    let mut notes: Vec<(i64, Note)> = Vec::new();
+    let mut i = 0;
+    while i < kept.len() {
+        if kept[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < kept.len() && !kept[i] {
+            i += 1;
+        }
+        let dropped = i - start;
+        let note = Note {
+            actor_ids: vec![id.clone()],
+            text: format!(
+                "... {} message{} ...",
+                dropped,
+                if dropped == 1 { "" } else { "s" }
+            ),
+            position: NotePosition::Over,
+            after_index: if start == 0 { -1 } else { last_kept[start - 1] },
+        };
+        notes.push((2 * start as i64 + 1, note));
+    }
+    for note in &diagram.notes {
+        let shown = note
+            .actor_ids
+            .iter()
+            .all(|a| projected.actors.iter().any(|p| &p.id == a));
+        if !shown {
+            continue;
+        }
+        let after_index = match note.after_index {
+            i if i < 0 => i,
+            i => last_kept[i as usize],
+        };
+        let key = 2 * note.after_index as i64 + 2;
+        notes.push((
+            key,
+            Note {
+                after_index,
+                ..note.clone()
+            },
+        ));
+    }
+    notes.sort_by_key(|(key, _)| *key);
+    projected.notes = notes.into_iter().map(|(_, note)| note).collect();
+
+    // Activations of the participants still shown, which keep their depths
+    projected.activations = diagram
+        .activations
+        .iter()
+        .filter(|a| projected.actors.iter().any(|p| p.id == a.actor_id))
+        .map(|a| Activation {
+            after_index: match a.after_index {
+                i if i < 0 => i,
+                i => last_kept[i as usize],
+            },
+            ..a.clone()
+        })
+        .collect();
+
+    // Blocks shrink to the messages left inside them
+    projected.blocks = diagram
+        .blocks
+        .iter()
+        .filter_map(|block| {
+            let inside: Vec<usize> = (block.start_index..=block.end_index)
+                .filter_map(|i| new_index.get(i).copied().flatten())
+                .collect();
+            let (&start, &end) = (inside.first()?, inside.last()?);
+            let dividers = block
+                .dividers
+                .iter()
+                .filter_map(|d| {
+                    // A divider sits before the message at its index
+                    let index = (d.index..=block.end_index)
+                        .find_map(|i| new_index.get(i).copied().flatten())?;
+                    Some(BlockDivider {
+                        index,
+                        label: d.label.clone(),
+                    })
+                })
+                .collect();
+            Some(Block {
+                start_index: start,
+                end_index: end,
+                dividers,
+                ..block.clone()
+            })
+        })
+        .collect();
+
+    Ok(projected)
+}
@@ -0,0 +1,266 @@
+//! Layout caching for repeated SVG renders
+//!
+//! Watch mode, the render server and editor previews render the same diagram
+//! again and again, often with only the theme or the edge curve changed.
+//! Laying a diagram out is most of the work of rendering it, so a
+//! [`Renderer`] keeps the layouts it computed, keyed by a hash of the diagram
+//! and of the options that shape the layout, and only paints them again when
+//! those are unchanged.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use crate::svg::{self, DiagramColors, PositionedGraph, SvgDocument};
+use crate::types::{DiagramType, FlowchartLayout, ParsedDiagram};
+use crate::{finish_svg, prepare_svg, svg_font, with_svg_measurer, SvgRenderOptions};
+
+/// A laid out diagram, ready to be painted
+pub(crate) enum Layout {
+    /// A layered flowchart, painted again for each curve style
+    Layered(PositionedGraph),
+    /// Any other diagram, painted once; its colors are CSS variables on the
+    /// root, which are swapped for each theme
+    Document(SvgDocument),
+}
+
+impl Layout {
+    /// Lay out `parsed`, or `None` if there is nothing to draw
+    pub(crate) fn new(
+        parsed: &ParsedDiagram,
+        opts: &SvgRenderOptions,
+        colors: &DiagramColors,
+    ) -> Option<Self> {
+        let font = svg_font(opts);
+        let font = font.as_str();
+        let transparent = false;
+        let curve = parsed.frontmatter.curve.unwrap_or(opts.curve);
+        let routing = parsed.frontmatter.edge_routing.unwrap_or(opts.routing);
+
+        let document = match &parsed.diagram {
+            DiagramType::Flowchart(graph) => match parsed.frontmatter.layout {
+                FlowchartLayout::Grid => svg::render_mermaid_to_document_with_curve(
+                    graph,
+                    colors,
+                    font,
+                    transparent,
+                    curve,
+                ),
+                FlowchartLayout::Layered if graph.nodes.is_empty() => None,
+                FlowchartLayout::Layered => {
+                    let layout = svg::layout_flowchart_with_routing(graph, routing);
+                    return Some(Layout::Layered(layout));
+                }
+            },
+            DiagramType::Sequence(diagram) => svg::render_sequence_document_with_config(
+                diagram,
+                colors,
+                font,
+                transparent,
+                opts.sequence,
+            ),
+            DiagramType::Class(diagram) => {
+                svg::render_class_document(diagram, colors, font, transparent)
+            }
+            DiagramType::Er(diagram) => svg::render_er_document(diagram, colors, font, transparent),
+            DiagramType::GitGraph(graph) => Some(svg::render_gitgraph_document(
+                graph,
+                colors,
+                font,
+                transparent,
+            )),
+        };
+        document.map(Layout::Document)
+    }
+
+    /// Paint the layout of `parsed` in `colors`
+    pub(crate) fn document(
+        &self,
+        parsed: &ParsedDiagram,
+        opts: &SvgRenderOptions,
+        colors: &DiagramColors,
+    ) -> SvgDocument {
+        match self {
+            Layout::Layered(layout) => {
+                let curve = parsed.frontmatter.curve.unwrap_or(opts.curve);
+                svg::render_document_with_curve(layout, colors, &svg_font(opts), false, curve)
+            }
+            Layout::Document(document) => {
+                let mut document = document.clone();
+                // Git graphs paint their colors into the elements, and are
+                // cached per theme instead
+                if !matches!(parsed.diagram, DiagramType::GitGraph(_)) {
+                    svg::retheme_document(&mut document, colors, false);
+                }
+                document
+            }
+        }
+    }
+}
+
+/// Hash of the parts of `parsed` that its layout depends on
+fn diagram_key(parsed: &ParsedDiagram, opts: &SvgRenderOptions) -> u64 {
+    fn json<T: serde::Serialize>(value: &T) -> String {
+        serde_json::to_string(value).unwrap_or_default()
+    }
+    let frontmatter = &parsed.frontmatter;
+    let mut hasher = DefaultHasher::new();
+    json(&parsed.diagram).hash(&mut hasher);
+    json(&frontmatter.layout).hash(&mut hasher);
+    json(&frontmatter.edge_routing).hash(&mut hasher);
+    match &parsed.diagram {
+        DiagramType::Flowchart(_) if frontmatter.layout == FlowchartLayout::Layered => {}
+        DiagramType::GitGraph(_) => json(frontmatter).hash(&mut hasher),
+        _ => json(&frontmatter.curve.unwrap_or(opts.curve)).hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+/// Hash of the options that shape layouts
+fn options_key(opts: &SvgRenderOptions) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    opts.max_label_width.hash(&mut hasher);
+    opts.wrap_width.hash(&mut hasher);
+    opts.node_width.hash(&mut hasher);
+    opts.label_align.hash(&mut hasher);
+    serde_json::to_string(&opts.routing)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    opts.fonts.hash(&mut hasher);
+    serde_json::to_string(&opts.sequence)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    matches!(opts.font_source, svg::FontSource::Web).hash(&mut hasher);
+    // Measurers are told apart by identity
+    opts.measurer
+        .as_ref()
+        .map(|m| Arc::as_ptr(m) as *const () as usize)
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Layouts by (diagram key, options key), oldest first in `order`
+#[derive(Default)]
+struct Layouts {
+    entries: HashMap<(u64, u64), Arc<Layout>>,
+    order: VecDeque<(u64, u64)>,
+}
+
+/// Renders diagrams to SVG like [`render_parsed_svg`](crate::render_parsed_svg),
+/// reusing the layouts of diagrams it has rendered before.
+///
+/// A layout is reused when the diagram and the options that shape it (label
+/// limits, node widths, label alignment, edge routing, sequence spacing,
+/// fonts, web font import and text measurer) are the same; the theme and,
+/// for layered flowcharts, the edge curve only change how it is painted. The
+/// oldest layouts are dropped beyond the capacity. A `Renderer` can be shared
+/// between threads.
+///
+/// # Example
+/// ```rust
+/// use m2svg::{parse_mermaid, render_parsed_svg, Renderer};
+///
+/// let renderer = Renderer::new();
+/// let light = parse_mermaid("graph LR\n  A --> B").unwrap();
+/// let dark = parse_mermaid("---\ntheme: dark\n---\ngraph LR\n  A --> B").unwrap();
+///
+/// assert_eq!(renderer.render_svg(&light, None), render_parsed_svg(&light, None));
+/// // Same layout, other colors
+/// assert_eq!(renderer.render_svg(&dark, None), render_parsed_svg(&dark, None));
+/// assert_eq!(renderer.len(), 1);
+/// ```
+pub struct Renderer {
+    layouts: Mutex<Layouts>,
+    capacity: usize,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    /// A renderer keeping up to 64 layouts
+    pub fn new() -> Self {
+        Self::with_capacity(64)
+    }
+
+    /// A renderer keeping up to `capacity` layouts
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            layouts: Mutex::default(),
+            capacity,
+        }
+    }
+
+    /// Render `parsed` to SVG text, as [`render_parsed_svg`](crate::render_parsed_svg) does
+    pub fn render_svg(&self, parsed: &ParsedDiagram, options: Option<SvgRenderOptions>) -> String {
+        let opts = options.unwrap_or_default();
+        let key = (diagram_key(parsed, &opts), options_key(&opts));
+        with_svg_measurer(opts, |opts| {
+            let (parsed, legend) = prepare_svg(parsed, opts);
+            let colors = DiagramColors::from_theme(parsed.frontmatter.theme);
+            let cached = self.lock().entries.get(&key).cloned();
+            let layout = match cached {
+                Some(layout) => layout,
+                None => {
+                    // Lay out without holding the lock, so other threads
+                    // aren't held up
+                    let Some(layout) = Layout::new(&parsed, opts, &colors) else {
+                        return String::new();
+                    };
+                    self.insert(key, Arc::new(layout))
+                }
+            };
+            finish_svg(
+                layout.document(&parsed, opts, &colors),
+                &parsed,
+                &legend,
+                opts,
+            )
+        })
+    }
+
+    /// Number of layouts kept
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Whether no layout is kept
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop the layouts computed with the layout options of `options`, e.g.
+    /// after the font files behind its measurer changed
+    pub fn invalidate(&self, options: &SvgRenderOptions) {
+        let options = options_key(options);
+        let mut layouts = self.lock();
+        layouts.entries.retain(|key, _| key.1 != options);
+        layouts.order.retain(|key| key.1 != options);
+    }
+
+    /// Drop all layouts
+    pub fn clear(&self) {
+        *self.lock() = Layouts::default();
+    }
+
+    fn insert(&self, key: (u64, u64), layout: Arc<Layout>) -> Arc<Layout> {
+        let mut layouts = self.lock();
+        if layouts.entries.insert(key, Arc::clone(&layout)).is_none() {
+            layouts.order.push_back(key);
+        }
+        while layouts.order.len() > self.capacity {
+            if let Some(oldest) = layouts.order.pop_front() {
+                layouts.entries.remove(&oldest);
+            }
+        }
+        layout
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Layouts> {
+        self.layouts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
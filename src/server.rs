@@ -0,0 +1,309 @@
+//! Long-running render server (`m2svg serve`)
+//!
+//! Editor plugins and other tools that render many diagrams can keep one
+//! process open instead of spawning the CLI for each one. Messages in both
+//! directions are JSON objects framed as in the Language Server Protocol: a
+//! `Content-Length: N` header, a blank line, then N bytes of UTF-8 JSON.
+//!
+//! A request names the input, the output format (`svg`, `unicode` or `ascii`;
+//! `unicode` by default) and optionally the render options, as for the
+//! `wasm` bindings, plus an `id` that is echoed back:
+//!
+//! ```json
+//! {"id": 1, "input": "graph LR\n  A --> B", "format": "svg", "options": {"curve": "basis"}}
+//! ```
+//!
+//! The response has either the rendered `output` or an `error` message:
+//!
+//! ```json
+//! {"id": 1, "output": "<svg ...>"}
+//! ```
+//!
+//! Bodies over 1 MiB are skipped unread and answered with an error.
+//!
+//! A request may also name the `document` it renders (a path or URI). A
+//! parse [`Session`] and the last result are kept for each of the 64
+//! documents used most recently, so an editor that re-sends a diagram with
+//! nothing but comments or blank lines changed gets it back without a new
+//! parse or render. SVG layouts are kept across documents, so a diagram
+//! rendered again in another theme is not laid out again. `m2svg daemon
+//! --socket PATH` ([`serve_socket`], Unix only) speaks the same protocol on a
+//! Unix socket, to any number of clients that share those results.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::ascii::parse_config_from_text;
+use crate::json_options::{ascii_options, svg_options};
+use crate::session::Session;
+use crate::{ParsedDiagram, Renderer};
+
+/// Largest message body accepted, in bytes
+const MAX_MESSAGE: u64 = 1 << 20;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    input: String,
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    options: Option<Value>,
+    #[serde(default)]
+    document: Option<String>,
+}
+
+/// Most documents whose sessions are kept
+const MAX_DOCUMENTS: usize = 64;
+
+/// A document's parse session, and its last result with the format and
+/// options it was rendered with (as a key)
+struct Document {
+    session: Session,
+    last: Option<(String, Result<String, String>)>,
+}
+
+/// The documents used most recently, least recent first in `order`
+#[derive(Default)]
+struct Documents {
+    entries: HashMap<String, Arc<Mutex<Document>>>,
+    order: VecDeque<String>,
+}
+
+/// The sessions of the documents used most recently, and the SVG layouts of
+/// all requests
+#[derive(Default)]
+struct Cache {
+    documents: Mutex<Documents>,
+    renderer: Renderer,
+}
+
+impl Cache {
+    /// Render `request`, from its document's session when it names one, and
+    /// reuse the document's last result when the edit left the diagram and
+    /// the format and options are the same
+    fn render(&self, request: &Request) -> Result<String, String> {
+        let Some(document) = &request.document else {
+            let parsed = crate::parse_mermaid(&request.input);
+            let parsed = parsed.as_ref().map_err(String::as_str);
+            return render(request, parsed, &self.renderer);
+        };
+        let document = self.document(document);
+        // Other documents' clients only wait for the map, not this render
+        let mut document = lock(&document);
+        let update = document.session.replace(&request.input);
+        let key = json!([request.format, request.options]).to_string();
+        if let Some((last, result)) = &document.last {
+            if !update.reparsed && *last == key {
+                return result.clone();
+            }
+        }
+        let result = render(request, document.session.parsed(), &self.renderer);
+        document.last = Some((key, result.clone()));
+        result
+    }
+
+    /// The entry of `document`, made the most recently used; a new one has an
+    /// empty session
+    fn document(&self, document: &str) -> Arc<Mutex<Document>> {
+        let mut documents = lock(&self.documents);
+        documents.order.retain(|d| d != document);
+        documents.order.push_back(document.to_string());
+        let entry = documents
+            .entries
+            .entry(document.to_string())
+            .or_insert_with(|| {
+                Arc::new(Mutex::new(Document {
+                    session: Session::new(""),
+                    last: None,
+                }))
+            })
+            .clone();
+        while documents.order.len() > MAX_DOCUMENTS {
+            if let Some(oldest) = documents.order.pop_front() {
+                documents.entries.remove(&oldest);
+            }
+        }
+        entry
+    }
+}
+
+/// Lock `mutex`, going on after a panic in another client's thread
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Answer requests from `reader` on `writer` until `reader` is exhausted.
+///
+/// A request that can't be parsed or rendered gets an error response; only
+/// I/O errors and broken framing end the loop.
+///
+/// # Example
+/// ```rust
+/// use m2svg::server::serve;
+///
+/// let body = r#"{"id": 7, "input": "graph LR\n  A --> B", "format": "ascii"}"#;
+/// let request = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+/// let mut response = Vec::new();
+/// serve(request.as_bytes(), &mut response).unwrap();
+///
+/// let response = String::from_utf8(response).unwrap();
+/// assert!(response.starts_with("Content-Length: "));
+/// assert!(response.contains(r#""id":7"#));
+/// assert!(response.contains("+---+"));
+/// ```
+pub fn serve(reader: impl BufRead, writer: impl Write) -> io::Result<()> {
+    serve_with(reader, writer, &Cache::default())
+}
+
+/// Accept clients on a Unix socket at `path`, answering each as [`serve`]
+/// does on its own thread, until the process is stopped.
+///
+/// A stale socket file left at `path` by an earlier run is replaced; if
+/// another daemon is still listening there, this fails instead.
+#[cfg(unix)]
+pub fn serve_socket(path: &std::path::Path) -> io::Result<()> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("A daemon is already listening on {}", path.display()),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    let cache = Arc::new(Cache::default());
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let cache = Arc::clone(&cache);
+        std::thread::spawn(move || {
+            let reader = io::BufReader::new(&stream);
+            // A client that hangs up or sends broken framing only loses its
+            // own connection
+            let _ = serve_with(reader, &stream, &cache);
+        });
+    }
+    Ok(())
+}
+
+fn serve_with(mut reader: impl BufRead, mut writer: impl Write, cache: &Cache) -> io::Result<()> {
+    while let Some(body) = read_message(&mut reader)? {
+        let response = match body.map(|body| serde_json::from_slice::<Request>(&body)) {
+            Ok(Ok(request)) => match cache.render(&request) {
+                Ok(output) => json!({ "id": request.id, "output": output }),
+                Err(error) => json!({ "id": request.id, "error": error }),
+            },
+            Ok(Err(e)) => json!({ "id": Value::Null, "error": format!("Invalid request: {}", e) }),
+            Err(error) => json!({ "id": Value::Null, "error": error }),
+        };
+        let response = response.to_string();
+        write!(
+            writer,
+            "Content-Length: {}\r\n\r\n{}",
+            response.len(),
+            response
+        )?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn render(
+    request: &Request,
+    parsed: Result<&ParsedDiagram, &str>,
+    renderer: &Renderer,
+) -> Result<String, String> {
+    let options = match &request.options {
+        Some(options) => options.to_string(),
+        None => String::new(),
+    };
+    match request.format.as_deref().unwrap_or("unicode") {
+        "svg" => {
+            let options = svg_options(&options)?;
+            let parsed = parsed?;
+            let limits = options.limits;
+            limits.check(parsed)?;
+            let svg = renderer.render_svg(parsed, Some(options));
+            limits.check_svg(&svg)?;
+            Ok(svg)
+        }
+        format @ ("unicode" | "ascii") => {
+            let options = crate::AsciiRenderOptions {
+                use_ascii: format == "ascii",
+                ..ascii_options(&options)?
+            };
+            let options = parse_config_from_text(&request.input, options);
+            crate::render_parsed_ascii(parsed?, Some(options))
+        }
+        format => Err(format!(
+            "Unknown format: {} (expected svg, unicode or ascii)",
+            format
+        )),
+    }
+}
+
+/// Read one framed message body, or `None` at the end of the input.
+///
+/// A body longer than [`MAX_MESSAGE`] is skipped without being stored, and
+/// comes back as an error to report to the client.
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Result<Vec<u8>, String>>> {
+    let mut length = None;
+    let mut in_header = false;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            if in_header {
+                return Err(invalid("Unexpected end of input in message header"));
+            }
+            return Ok(None);
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            // Tolerate blank lines between messages
+            if in_header {
+                break;
+            }
+            continue;
+        }
+        in_header = true;
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid(&format!("Invalid message header: {}", line)))?;
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            let value = value.trim();
+            length = Some(
+                value
+                    .parse::<u64>()
+                    .map_err(|_| invalid(&format!("Invalid Content-Length: {}", value)))?,
+            );
+        }
+    }
+    let length = length.ok_or_else(|| invalid("Message header without Content-Length"))?;
+    if length > MAX_MESSAGE {
+        let skipped = io::copy(&mut reader.by_ref().take(length), &mut io::sink())?;
+        if skipped < length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        return Ok(Some(Err(format!(
+            "Message body is too large ({} bytes, at most {})",
+            length, MAX_MESSAGE
+        ))));
+    }
+    let mut body = vec![0; length as usize];
+    reader.read_exact(&mut body)?;
+    Ok(Some(Ok(body)))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
@@ -0,0 +1,284 @@
+//! Parsing for live editor previews
+//!
+//! A live preview parses the document on every keystroke. A [`Session`]
+//! keeps the document as lines, takes edits to line ranges, and only parses
+//! again when an edit can change the diagram: edits that touch nothing but
+//! blank lines and `%%` comments keep the previous result. Any other edit
+//! parses the whole document again, as statements depend on the ones before
+//! them (subgraph nesting, first definitions, commit order). Each [`Update`]
+//! says which lines changed and which diagram elements (node, participant,
+//! class, entity or commit ids) now look or connect differently, so the
+//! preview can redraw or scroll to just those.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+use crate::parser::parse_mermaid;
+use crate::types::{DiagramType, ParsedDiagram};
+
+/// A Mermaid document being edited
+pub struct Session {
+    lines: Vec<String>,
+    parsed: Result<ParsedDiagram, String>,
+    /// Serialized definition of each element, to find the changed ones
+    elements: BTreeMap<String, String>,
+}
+
+/// What an edit changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Update {
+    /// Whether the document was parsed again; `false` when the edit only
+    /// touched blank lines and comments
+    pub reparsed: bool,
+    /// Lines of the new document that the edit wrote
+    pub lines: Range<usize>,
+    /// Ids of the elements added, removed or changed by the edit, sorted
+    pub dirty: Vec<String>,
+}
+
+impl Session {
+    /// Start a session on `text`, parsing it once
+    pub fn new(text: &str) -> Self {
+        let parsed = parse_mermaid(text);
+        let elements = parsed.as_ref().map(elements).unwrap_or_default();
+        Self {
+            lines: text.lines().map(str::to_string).collect(),
+            parsed,
+            elements,
+        }
+    }
+
+    /// The current document
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// The diagram parsed from the current document, or the parse error
+    pub fn parsed(&self) -> Result<&ParsedDiagram, &str> {
+        self.parsed.as_ref().map_err(String::as_str)
+    }
+
+    /// Replace the lines in `range` (0-based, end exclusive) with those of
+    /// `text`; an empty range inserts before its start, an empty `text`
+    /// deletes the range.
+    ///
+    /// # Example
+    /// ```rust
+    /// use m2svg::session::Session;
+    ///
+    /// let mut session = Session::new("graph LR\n  A --> B\n  B --> C");
+    ///
+    /// // A comment leaves the diagram as it was
+    /// let update = session.edit(1..1, "  %% the happy path").unwrap();
+    /// assert!(!update.reparsed);
+    ///
+    /// // Relabeling B marks it, and not A or C, as changed
+    /// let update = session.edit(2..3, "  A --> B[Billing]").unwrap();
+    /// assert!(update.reparsed);
+    /// assert_eq!(update.lines, 2..3);
+    /// assert_eq!(update.dirty, ["B"]);
+    /// assert!(session.parsed().is_ok());
+    /// ```
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> Result<Update, String> {
+        if range.start > range.end || range.end > self.lines.len() {
+            return Err(format!(
+                "Edit range {}..{} is outside the document's {} lines",
+                range.start,
+                range.end,
+                self.lines.len()
+            ));
+        }
+        Ok(self.splice(range, text.lines().map(str::to_string).collect()))
+    }
+
+    /// Replace the whole document with `text`, as one edit of the lines that
+    /// differ, for editors that send the full text on every change
+    ///
+    /// # Example
+    /// ```rust
+    /// use m2svg::session::Session;
+    ///
+    /// let mut session = Session::new("graph LR\n  A --> B\n  B --> C");
+    /// let update = session.replace("graph LR\n  A --> B[Billing]\n  B --> C");
+    /// assert_eq!(update.lines, 1..2);
+    /// assert_eq!(update.dirty, ["B"]);
+    /// ```
+    pub fn replace(&mut self, text: &str) -> Update {
+        let new_lines: Vec<&str> = text.lines().collect();
+        let prefix = self
+            .lines
+            .iter()
+            .zip(&new_lines)
+            .take_while(|(old, new)| old == new)
+            .count();
+        let suffix = self.lines[prefix..]
+            .iter()
+            .rev()
+            .zip(new_lines[prefix..].iter().rev())
+            .take_while(|(old, new)| old == new)
+            .count();
+        let range = prefix..self.lines.len() - suffix;
+        let written = new_lines[prefix..new_lines.len() - suffix].iter();
+        self.splice(range, written.map(|l| l.to_string()).collect())
+    }
+
+    fn splice(&mut self, range: Range<usize>, new_lines: Vec<String>) -> Update {
+        let inert = !self.in_preamble_or_block(range.start)
+            && self.lines[range.clone()].iter().all(|l| is_inert(l))
+            && new_lines.iter().all(|l| is_inert(l));
+        let written = range.start..range.start + new_lines.len();
+        self.lines.splice(range, new_lines);
+        if inert {
+            return Update {
+                reparsed: false,
+                lines: written,
+                dirty: Vec::new(),
+            };
+        }
+
+        self.parsed = parse_mermaid(&self.text());
+        let elements = self.parsed.as_ref().map(elements).unwrap_or_default();
+        let mut dirty: Vec<String> = elements
+            .iter()
+            .filter(|(id, definition)| self.elements.get(*id) != Some(definition))
+            .map(|(id, _)| id.clone())
+            .collect();
+        dirty.extend(
+            self.elements
+                .keys()
+                .filter(|id| !elements.contains_key(*id))
+                .cloned(),
+        );
+        dirty.sort();
+        self.elements = elements;
+        Update {
+            reparsed: true,
+            lines: written,
+            dirty,
+        }
+    }
+
+    /// Whether line `index` lies in the frontmatter or in an `accDescr { }`
+    /// block, where blank lines and comments are content
+    fn in_preamble_or_block(&self, index: usize) -> bool {
+        let mut lines = self.lines.iter().enumerate();
+        if self.lines.first().is_some_and(|l| l.trim() == "---") {
+            lines.next();
+            if index == 0 {
+                return true;
+            }
+            for (i, line) in lines.by_ref() {
+                if i == index {
+                    return true;
+                }
+                if line.trim() == "---" {
+                    break;
+                }
+            }
+        }
+        let mut in_block = false;
+        for (i, line) in lines {
+            if i == index {
+                return in_block;
+            }
+            let trimmed = line.trim();
+            if in_block {
+                in_block = !trimmed.ends_with('}');
+            } else if let Some(rest) = trimmed.strip_prefix("accDescr") {
+                let rest = rest.trim_start();
+                in_block = rest.starts_with('{') && !rest.ends_with('}');
+            }
+        }
+        false
+    }
+}
+
+/// Blank lines and `%%` comments, which the parsers skip; `%%{` directives
+/// are not comments
+fn is_inert(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || (line.starts_with("%%") && !line.starts_with("%%{"))
+}
+
+/// Each element id of the diagram with what defines it: its own fields, its
+/// styles and the connections it takes part in
+fn elements(parsed: &ParsedDiagram) -> BTreeMap<String, String> {
+    fn json<T: serde::Serialize>(value: &T) -> String {
+        serde_json::to_string(value).unwrap_or_default()
+    }
+    fn sorted(map: &HashMap<String, String>) -> BTreeMap<&String, &String> {
+        map.iter().collect()
+    }
+    let mut elements = BTreeMap::new();
+    match &parsed.diagram {
+        DiagramType::Flowchart(graph) => {
+            let edges = by_end(&graph.edges, |e| [&e.source, &e.target]);
+            for (id, node) in &graph.nodes {
+                // Sorted, as maps serialize in their hashing order
+                let class = graph.class_assignments.get(id);
+                let class_def = class.and_then(|c| graph.class_defs.get(c)).map(sorted);
+                let definition = (
+                    node,
+                    graph.node_styles.get(id).map(sorted),
+                    class,
+                    class_def,
+                    graph.node_links.get(id),
+                    edges.get(id.as_str()),
+                );
+                elements.insert(id.clone(), json(&definition));
+            }
+            let mut stack: Vec<_> = graph.subgraphs.iter().collect();
+            while let Some(subgraph) = stack.pop() {
+                let definition = (&subgraph.label, &subgraph.node_ids, &subgraph.direction);
+                elements.insert(subgraph.id.clone(), json(&definition));
+                stack.extend(&subgraph.children);
+            }
+        }
+        DiagramType::Sequence(diagram) => {
+            let messages = by_end(&diagram.messages, |m| [&m.from, &m.to]);
+            let activations = by_end(&diagram.activations, |a| [&a.actor_id, &a.actor_id]);
+            for actor in &diagram.actors {
+                let id = actor.id.as_str();
+                let definition = (actor, messages.get(id), activations.get(id));
+                elements.insert(actor.id.clone(), json(&definition));
+            }
+        }
+        DiagramType::Class(diagram) => {
+            let relationships = by_end(&diagram.relationships, |r| [&r.from, &r.to]);
+            for class in &diagram.classes {
+                let definition = (class, relationships.get(class.id.as_str()));
+                elements.insert(class.id.clone(), json(&definition));
+            }
+        }
+        DiagramType::Er(diagram) => {
+            let relationships = by_end(&diagram.relationships, |r| [&r.entity1, &r.entity2]);
+            for entity in &diagram.entities {
+                let definition = (entity, relationships.get(entity.id.as_str()));
+                elements.insert(entity.id.clone(), json(&definition));
+            }
+        }
+        DiagramType::GitGraph(graph) => {
+            for commit in &graph.commits {
+                elements.insert(commit.id.clone(), json(commit));
+            }
+        }
+    }
+    elements
+}
+
+/// The connections each id takes part in, in order, found in one pass; a
+/// connection from an id to itself is listed once
+fn by_end<'a, T, F>(connections: &'a [T], ends: F) -> HashMap<&'a str, Vec<&'a T>>
+where
+    F: Fn(&'a T) -> [&'a String; 2],
+{
+    let mut by_end: HashMap<&str, Vec<&T>> = HashMap::new();
+    for connection in connections {
+        let [from, to] = ends(connection);
+        by_end.entry(from).or_default().push(connection);
+        if to != from {
+            by_end.entry(to).or_default().push(connection);
+        }
+    }
+    by_end
+}
@@ -1,14 +1,21 @@
 //! Class diagram SVG rendering
 
+use super::document::SvgDocument;
 use super::renderer::escape_xml;
-use super::theme::{build_style_block, svg_open_tag, DiagramColors};
-use crate::types::{ClassDiagram, ClassMember, RelationshipType, Visibility};
-use std::collections::{HashMap, HashSet};
+use super::styles::{label_align, node_width};
+use super::theme::{build_style_block, svg_document, DiagramColors};
+use crate::node_width::cut_to_width;
+use crate::types::{
+    ClassDiagram, ClassMember, ClassRelationship, LabelAlign, RelationshipType, Visibility,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 const BOX_PADDING: f64 = 12.0;
 const LINE_HEIGHT: f64 = 20.0;
 const H_GAP: f64 = 60.0;
 const V_GAP: f64 = 50.0;
+/// How far a self-relationship loop rises above its box and reaches right of it
+const LOOP_SIZE: f64 = 30.0;
 
 #[allow(dead_code)]
 struct ClassBox {
@@ -22,6 +29,8 @@ struct ClassBox {
     x: f64,
     y: f64,
     is_lollipop: bool,
+    /// Style of the class's stereotype, if one is configured
+    style: HashMap<String, String>,
 }
 
 /// Render a class diagram to SVG
@@ -31,12 +40,26 @@ pub fn render_class_svg(
     font: &str,
     transparent: bool,
 ) -> String {
+    render_class_document(diagram, colors, font, transparent)
+        .map(|document| document.to_string())
+        .unwrap_or_default()
+}
+
+/// Render a class diagram to an SVG document, or `None` when it has no classes
+pub fn render_class_document(
+    diagram: &ClassDiagram,
+    colors: &DiagramColors,
+    font: &str,
+    transparent: bool,
+) -> Option<SvgDocument> {
     if diagram.classes.is_empty() {
-        return String::new();
+        return None;
     }
 
     // Build box dimensions for each class
-    let mut class_boxes: HashMap<String, ClassBox> = HashMap::new();
+    let node_width = node_width();
+    // By id, the order boxes are laid out and drawn in
+    let mut class_boxes: BTreeMap<String, ClassBox> = BTreeMap::new();
 
     for cls in &diagram.classes {
         // Lollipop interface nodes are rendered as plain text (no box)
@@ -55,19 +78,40 @@ pub fn render_class_svg(
                     x: 0.0,
                     y: 0.0,
                     is_lollipop: true,
+                    style: HashMap::new(),
                 },
             );
             continue;
         }
 
-        let annotation_str = cls.annotation.as_ref().map(|a| format!("<<{}>>", a));
-
-        let attr_lines: Vec<String> = cls.attributes.iter().map(format_member).collect();
-        let method_lines: Vec<String> = cls.methods.iter().map(format_member).collect();
+        // Text wider than the widest box allows is cut, at 8px a character
+        let room = node_width
+            .text_room(2 * BOX_PADDING as usize)
+            .map(|px| px / 8);
+        let cut = |text: &str, room: usize| cut_to_width(text, room, "...");
+        let annotation = match room {
+            Some(room) => cls
+                .annotation
+                .as_deref()
+                .map(|a| cut(a, room.saturating_sub(4))),
+            None => cls.annotation.clone(),
+        };
+        let annotation_str = annotation.as_ref().map(|a| format!("<<{}>>", a));
+        let mut label = cls.label.clone();
+        let mut attr_lines: Vec<String> = cls.attributes.iter().map(format_member).collect();
+        let mut method_lines: Vec<String> = cls.methods.iter().map(format_member).collect();
+        if let Some(room) = room {
+            for text in std::iter::once(&mut label)
+                .chain(&mut attr_lines)
+                .chain(&mut method_lines)
+            {
+                *text = cut(text, room);
+            }
+        }
 
         // Calculate width based on widest line
         let annotation_width = annotation_str.as_ref().map(|s| s.len()).unwrap_or(0);
-        let header_width = cls.label.len();
+        let header_width = label.len();
         let attr_width = attr_lines.iter().map(|s| s.len()).max().unwrap_or(0);
         let method_width = method_lines.iter().map(|s| s.len()).max().unwrap_or(0);
 
@@ -97,8 +141,8 @@ pub fn render_class_svg(
             cls.id.clone(),
             ClassBox {
                 id: cls.id.clone(),
-                label: cls.label.clone(),
-                annotation: cls.annotation.clone(),
+                label,
+                annotation,
                 attr_lines,
                 method_lines,
                 width: box_width,
@@ -106,15 +150,26 @@ pub fn render_class_svg(
                 x: 0.0,
                 y: 0.0,
                 is_lollipop: false,
+                style: stereotype_style(diagram, cls.annotation.as_deref()),
             },
         );
     }
 
+    // Widen boxes to the configured node widths
+    let widest = class_boxes
+        .values()
+        .filter(|b| !b.is_lollipop)
+        .map(|b| b.width)
+        .fold(0.0, f64::max);
+    for b in class_boxes.values_mut().filter(|b| !b.is_lollipop) {
+        b.width = node_width.fit(b.width, widest);
+    }
+
     // Assign levels using relationship hierarchy
-    let mut parents: HashMap<String, HashSet<String>> = HashMap::new();
-    let mut children: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut parents: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut children: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
 
-    for rel in &diagram.relationships {
+    for rel in diagram.relationships.iter().filter(|r| r.from != r.to) {
         let is_hierarchical = matches!(
             rel.rel_type,
             RelationshipType::Inheritance | RelationshipType::Realization
@@ -135,10 +190,9 @@ pub fn render_class_svg(
     }
 
     // Compute levels (BFS from roots)
-    let mut levels: HashMap<String, usize> = HashMap::new();
-    let allids: HashSet<_> = class_boxes.keys().cloned().collect();
-    let roots: Vec<_> = allids
-        .iter()
+    let mut levels: BTreeMap<String, usize> = BTreeMap::new();
+    let roots: Vec<_> = class_boxes
+        .keys()
         .filter(|id| parents.get(*id).map(|p| p.is_empty()).unwrap_or(true))
         .cloned()
         .collect();
@@ -164,29 +218,45 @@ pub fn render_class_svg(
     }
 
     // Assign unconnected nodes to level 0
-    for id in &allids {
+    for id in class_boxes.keys() {
         levels.entry(id.clone()).or_insert(0);
     }
 
-    // Group by level and position - sort by id for deterministic output
+    // Group by level and position, by id within a level
     let max_level = levels.values().copied().max().unwrap_or(0);
     let mut level_nodes: Vec<Vec<String>> = vec![Vec::new(); max_level + 1];
-    let mut sorted_ids: Vec<_> = levels.iter().collect();
-    sorted_ids.sort_by_key(|(id, _)| *id);
-    for (id, level) in sorted_ids {
+    for (id, level) in &levels {
         level_nodes[*level].push(id.clone());
     }
 
-    // Position boxes
+    // Labels of the relationships of each class with itself
+    let mut loop_labels: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for rel in diagram.relationships.iter().filter(|r| r.from == r.to) {
+        let labels = loop_labels.entry(rel.from.as_str()).or_default();
+        labels.extend(rel.label.as_deref().filter(|l| !l.is_empty()));
+    }
+    // Columns a loop and its label take right of its box
+    let loop_width = |id: &str| -> f64 {
+        loop_labels.get(id).map_or(0.0, |labels| {
+            LOOP_SIZE + 6.0 + labels.join(", ").len() as f64 * 8.0
+        })
+    };
+
+    // Position boxes, leaving room above the first level for loops
+    let top = if loop_labels.is_empty() {
+        20.0
+    } else {
+        20.0 + LOOP_SIZE
+    };
     for (level, nodes) in level_nodes.iter().enumerate() {
         let mut cur_x = 20.0;
-        let level_y = level as f64 * (150.0 + V_GAP) + 20.0;
+        let level_y = level as f64 * (150.0 + V_GAP) + top;
 
         for id in nodes {
             if let Some(b) = class_boxes.get_mut(id) {
                 b.x = cur_x;
                 b.y = level_y;
-                cur_x += b.width + H_GAP;
+                cur_x += b.width + loop_width(id) + H_GAP;
             }
         }
     }
@@ -194,7 +264,7 @@ pub fn render_class_svg(
     // Calculate canvas size
     let total_width = class_boxes
         .values()
-        .map(|b| b.x + b.width)
+        .map(|b| b.x + b.width + loop_width(&b.id))
         .fold(0.0f64, |a, b| a.max(b))
         + 40.0;
     let total_height = class_boxes
@@ -203,19 +273,23 @@ pub fn render_class_svg(
         .fold(0.0f64, |a, b| a.max(b))
         + 40.0;
 
+    let mut document = svg_document(total_width, total_height, colors, transparent);
+    document.head.push(build_style_block(font));
     let mut svg = String::new();
-    svg.push_str(&svg_open_tag(
-        total_width,
-        total_height,
-        colors,
-        transparent,
-    ));
-    svg.push_str(&build_style_block(font));
 
     // Draw relationships first (behind boxes)
+    let mut looped: BTreeSet<&str> = BTreeSet::new();
     for rel in &diagram.relationships {
         let from_box = class_boxes.get(&rel.from);
         let to_box = class_boxes.get(&rel.to);
+        if rel.from == rel.to {
+            // All of a class's loops are drawn as one, with the first's line and marker
+            if let Some(b) = from_box.filter(|_| looped.insert(&rel.from)) {
+                let label = loop_labels[rel.from.as_str()].join(", ");
+                svg.push_str(&draw_self_loop(b, rel, &label));
+            }
+            continue;
+        }
         if let (Some(fb), Some(tb)) = (from_box, to_box) {
             svg.push_str(&draw_relationship(
                 fb,
@@ -226,10 +300,8 @@ pub fn render_class_svg(
         }
     }
 
-    // Draw class boxes - sort by id for deterministic output
-    let mut sorted_boxes: Vec<_> = class_boxes.values().collect();
-    sorted_boxes.sort_by_key(|b| &b.id);
-    for b in sorted_boxes {
+    // Draw class boxes
+    for b in class_boxes.values() {
         if b.is_lollipop {
             svg.push_str(&draw_lollipop_label(b));
         } else {
@@ -237,8 +309,8 @@ pub fn render_class_svg(
         }
     }
 
-    svg.push_str("</svg>");
-    svg
+    document.body = svg;
+    Some(document)
 }
 
 fn format_member(m: &ClassMember) -> String {
@@ -266,33 +338,54 @@ fn format_member(m: &ClassMember) -> String {
     }
 }
 
+/// The configured style of a stereotype, matched case-insensitively
+fn stereotype_style(diagram: &ClassDiagram, annotation: Option<&str>) -> HashMap<String, String> {
+    annotation
+        .and_then(|a| diagram.stereotype_styles.get(&a.to_lowercase()))
+        .cloned()
+        .unwrap_or_default()
+}
+
 fn draw_class_box(b: &ClassBox) -> String {
     let mut s = String::new();
 
-    // Main box
+    // Main box, filled and bordered as its stereotype says
+    let paint: String = ["fill", "stroke", "stroke-width", "stroke-dasharray"]
+        .iter()
+        .filter_map(|key| {
+            let value = b.style.get(*key)?;
+            Some(format!(r#" {}="{}""#, key, escape_xml(value)))
+        })
+        .collect();
     s.push_str(&format!(
-        r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" class="node"/>"#,
-        b.x, b.y, b.width, b.height
+        r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" class="node"{}/>"#,
+        b.x, b.y, b.width, b.height, paint
     ));
     s.push('\n');
 
     let mut cur_y = b.y + BOX_PADDING + LINE_HEIGHT * 0.7;
-    let cx = b.x + b.width / 2.0;
+    // Text inside the padding, as aligned as configured or else as `default` is
+    let anchor = |default: LabelAlign| {
+        let align = label_align().unwrap_or(default);
+        align.anchor(b.x + BOX_PADDING, b.x + b.width - BOX_PADDING)
+    };
+    let (header_x, header_anchor) = anchor(LabelAlign::Center);
 
     // Annotation (if any)
     if let Some(ref ann) = b.annotation {
         s.push_str(&format!(
-            r#"<text x="{:.1}" y="{:.1}" class="annotation" text-anchor="middle">&lt;&lt;{}&gt;&gt;</text>"#,
-            cx, cur_y, escape_xml(ann)
+            r#"<text x="{:.1}" y="{:.1}" class="annotation" text-anchor="{}">&lt;&lt;{}&gt;&gt;</text>"#,
+            header_x, cur_y, header_anchor, escape_xml(ann)
         ));
         cur_y += LINE_HEIGHT;
     }
 
     // Class name (bold)
     s.push_str(&format!(
-        r#"<text x="{:.1}" y="{:.1}" class="class-name" text-anchor="middle">{}</text>"#,
-        cx,
+        r#"<text x="{:.1}" y="{:.1}" class="class-name" text-anchor="{}">{}</text>"#,
+        header_x,
         cur_y,
+        header_anchor,
         escape_xml(&b.label)
     ));
     cur_y += LINE_HEIGHT;
@@ -310,12 +403,18 @@ fn draw_class_box(b: &ClassBox) -> String {
         cur_y += BOX_PADDING / 2.0;
     }
 
-    // Attributes
+    // Attributes, and methods below, on the left by default
+    let (member_x, member_anchor) = anchor(LabelAlign::Left);
+    let member_anchor = match member_anchor {
+        "start" => String::new(),
+        anchor => format!(r#" text-anchor="{}""#, anchor),
+    };
     for attr in &b.attr_lines {
         s.push_str(&format!(
-            r#"<text x="{:.1}" y="{:.1}" class="member">{}</text>"#,
-            b.x + BOX_PADDING,
+            r#"<text x="{:.1}" y="{:.1}" class="member"{}>{}</text>"#,
+            member_x,
             cur_y,
+            member_anchor,
             escape_xml(attr)
         ));
         cur_y += LINE_HEIGHT;
@@ -337,9 +436,10 @@ fn draw_class_box(b: &ClassBox) -> String {
     // Methods
     for method in &b.method_lines {
         s.push_str(&format!(
-            r#"<text x="{:.1}" y="{:.1}" class="member">{}</text>"#,
-            b.x + BOX_PADDING,
+            r#"<text x="{:.1}" y="{:.1}" class="member"{}>{}</text>"#,
+            member_x,
             cur_y,
+            member_anchor,
             escape_xml(method)
         ));
         cur_y += LINE_HEIGHT;
@@ -434,6 +534,44 @@ fn draw_relationship(
     s
 }
 
+/// A relationship of a class with itself: a loop up out of the top of the
+/// box and back into its right side, with the label right of it
+fn draw_self_loop(b: &ClassBox, rel: &ClassRelationship, label: &str) -> String {
+    let right = b.x + b.width;
+    let (start_x, side_y) = (right - LOOP_SIZE, b.y + LOOP_SIZE / 2.0);
+    let (top_y, corner_x) = (b.y - LOOP_SIZE, right + LOOP_SIZE);
+
+    let is_dashed = matches!(
+        rel.rel_type,
+        RelationshipType::Dependency | RelationshipType::Realization
+    );
+    let line_class = if is_dashed { "rel-dashed" } else { "rel-line" };
+
+    let mut s = format!(
+        r#"<path d="M {:.1} {:.1} L {:.1} {:.1} L {:.1} {:.1} L {:.1} {:.1} L {:.1} {:.1}" class="{}" fill="none"/>"#,
+        start_x, b.y, start_x, top_y, corner_x, top_y, corner_x, side_y, right, side_y, line_class
+    );
+    s.push('\n');
+
+    // The marker points into the box at the end it belongs to
+    if rel.marker_at_from {
+        s.push_str(&draw_marker(start_x, b.y, 0.0, -1.0, &rel.rel_type));
+    } else {
+        s.push_str(&draw_marker(right, side_y, 1.0, 0.0, &rel.rel_type));
+    }
+
+    if !label.is_empty() {
+        s.push_str(&format!(
+            r#"<text x="{:.1}" y="{:.1}" class="edge-label" text-anchor="start">{}</text>"#,
+            corner_x + 6.0,
+            b.y - LOOP_SIZE / 2.0 + 4.0,
+            escape_xml(label)
+        ));
+        s.push('\n');
+    }
+    s
+}
+
 fn draw_marker(x: f64, y: f64, dx: f64, dy: f64, rel_type: &RelationshipType) -> String {
     let size = 12.0;
 
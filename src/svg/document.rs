@@ -0,0 +1,210 @@
+//! SVG document model - the root element of a rendered diagram.
+//!
+//! Renderers build an `SvgDocument` instead of a finished string, so the
+//! entry points can compose regions around the diagram (title, legend) and
+//! set root attributes (accessibility) without searching the output text.
+
+use super::renderer::escape_xml;
+
+/// A rendered diagram: the `<svg>` root, its canvas size and its children.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgDocument {
+    /// Text before the root element, e.g. an XML declaration
+    pub prolog: String,
+    /// Canvas width, used for the viewBox and, at the natural size, the
+    /// `width` attribute
+    pub width: f64,
+    /// Canvas height, used for the viewBox and, at the natural size, the
+    /// `height` attribute
+    pub height: f64,
+    /// Root attributes other than xmlns, viewBox, width and height
+    pub attributes: Vec<(String, String)>,
+    /// Elements that apply to the whole canvas (styles, defs, backgrounds);
+    /// they stay in place when regions are added around the body
+    pub head: Vec<String>,
+    /// The drawing itself, in canvas coordinates
+    pub body: String,
+    /// Accessible name, rendered as the root's `<title>`
+    pub title: Option<String>,
+    /// Accessible description, rendered as the root's `<desc>`
+    pub description: Option<String>,
+    /// Size the diagram is displayed at; the viewBox is always the canvas
+    pub size: SvgSize,
+}
+
+/// Size an SVG diagram is displayed at, set with its `width` and `height`
+/// attributes. The drawing keeps its proportions whatever the size.
+///
+/// # Example
+/// ```rust
+/// use m2svg::svg::SvgSize;
+/// use m2svg::SvgRenderOptions;
+///
+/// let opts = SvgRenderOptions {
+///     size: SvgSize::Fixed { width: Some(400.0), height: None },
+///     ..Default::default()
+/// };
+/// let svg = m2svg::render_mermaid_svg("graph LR\n  A --> B", Some(opts)).unwrap();
+/// assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 136 120" width="400" height="352.94""#));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SvgSize {
+    /// The size of the layout, 1px per unit
+    #[default]
+    Natural,
+    /// `width="100%"` and no height, to fill the width of the container
+    Responsive,
+    /// These px; a missing one follows from the other and the proportions,
+    /// and with both, the drawing is centered in them
+    Fixed {
+        width: Option<f64>,
+        height: Option<f64>,
+    },
+    /// The size of the layout, scaled down to this width in px if wider
+    MaxWidth(f64),
+}
+
+impl SvgSize {
+    /// The `width` and `height` attributes of a `width` x `height` canvas
+    fn attributes(&self, width: f64, height: f64) -> (String, Option<String>) {
+        let natural = (format_dim(width), Some(format_dim(height)));
+        let scaled = |w: f64, h: f64| {
+            let round = |d: f64| format_dim((d * 100.0).round() / 100.0);
+            (round(w), Some(round(h)))
+        };
+        let ratio = if height > 0.0 { width / height } else { 1.0 };
+        match *self {
+            SvgSize::Natural => natural,
+            SvgSize::Responsive => ("100%".to_string(), None),
+            SvgSize::Fixed {
+                width: w,
+                height: h,
+            } => match (w, h) {
+                (Some(w), Some(h)) => scaled(w, h),
+                (Some(w), None) => scaled(w, w / ratio),
+                (None, Some(h)) => scaled(h * ratio, h),
+                (None, None) => natural,
+            },
+            SvgSize::MaxWidth(max) if width > max => scaled(max, max / ratio),
+            SvgSize::MaxWidth(_) => natural,
+        }
+    }
+}
+
+impl SvgDocument {
+    /// An empty document with a `width` x `height` canvas
+    pub fn new(width: f64, height: f64) -> Self {
+        Self {
+            prolog: String::new(),
+            width,
+            height,
+            attributes: Vec::new(),
+            head: Vec::new(),
+            body: String::new(),
+            title: None,
+            description: None,
+            size: SvgSize::Natural,
+        }
+    }
+
+    /// Add a region of `height` above the body, shifting the body down.
+    ///
+    /// `content` is drawn in the region's own coordinates (y = 0 at its top),
+    /// and the canvas widens to `min_width` if it is narrower.
+    pub fn add_header(&mut self, height: f64, min_width: f64, content: &str) {
+        self.body = format!(
+            "{}\n<g transform=\"translate(0,{})\">\n{}\n</g>",
+            content,
+            format_dim(height),
+            self.body
+        );
+        self.height += height;
+        self.width = self.width.max(min_width);
+    }
+
+    /// Add a region of `height` below the body.
+    ///
+    /// `content` is drawn in the region's own coordinates (y = 0 at its top),
+    /// and the canvas widens to `min_width` if it is narrower.
+    pub fn add_footer(&mut self, height: f64, min_width: f64, content: &str) {
+        self.body.push_str(&format!(
+            "\n<g transform=\"translate(0,{})\">\n{}\n</g>",
+            format_dim(self.height),
+            content
+        ));
+        self.height += height;
+        self.width = self.width.max(min_width);
+    }
+
+    /// Id prefix for the `<title>`/`<desc>` elements. It is derived from the
+    /// text, so several diagrams inlined in one page keep distinct ids.
+    fn accessibility_id(&self) -> String {
+        // FNV-1a over the accessible text
+        let mut hash: u32 = 0x811c9dc5;
+        let text = self.title.iter().chain(&self.description);
+        for byte in text.flat_map(|t| t.bytes()) {
+            hash = (hash ^ byte as u32).wrapping_mul(0x01000193);
+        }
+        format!("m2svg-{:08x}", hash)
+    }
+}
+
+impl std::fmt::Display for SvgDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (w, h) = (format_dim(self.width), format_dim(self.height));
+        let (width, height) = self.size.attributes(self.width, self.height);
+        write!(
+            f,
+            r#"{}<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}" width="{}""#,
+            self.prolog, w, h, width
+        )?;
+        if let Some(height) = height {
+            write!(f, " height=\"{}\"", height)?;
+        }
+        for (name, value) in &self.attributes {
+            write!(f, " {}=\"{}\"", name, value)?;
+        }
+
+        // An image labelled and described by its <title> and <desc>
+        let id = self.accessibility_id();
+        let mut accessible = Vec::new();
+        if self.title.is_some() || self.description.is_some() {
+            write!(f, " role=\"img\"")?;
+        }
+        if let Some(title) = &self.title {
+            write!(f, " aria-labelledby=\"{}-title\"", id)?;
+            accessible.push(format!(
+                "<title id=\"{}-title\">{}</title>",
+                id,
+                escape_xml(title)
+            ));
+        }
+        if let Some(description) = &self.description {
+            write!(f, " aria-describedby=\"{}-desc\"", id)?;
+            accessible.push(format!(
+                "<desc id=\"{}-desc\">{}</desc>",
+                id,
+                escape_xml(description)
+            ));
+        }
+        writeln!(f, ">")?;
+
+        for element in accessible.iter().chain(&self.head) {
+            writeln!(f, "{}", element)?;
+        }
+        if !self.body.is_empty() {
+            writeln!(f, "{}", self.body)?;
+        }
+        write!(f, "</svg>")
+    }
+}
+
+/// An SVG dimension without trailing zeros
+pub(crate) fn format_dim(d: f64) -> String {
+    if d.fract() == 0.0 {
+        format!("{}", d as i64)
+    } else {
+        let s = format!("{}", d);
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
@@ -1,12 +1,19 @@
 //! ER diagram SVG rendering
 
+use super::document::SvgDocument;
 use super::renderer::escape_xml;
-use super::theme::{build_style_block, svg_open_tag, DiagramColors};
-use crate::types::{Cardinality, ErDiagram};
+use super::theme::{build_style_block, svg_document, DiagramColors};
+use crate::types::{Cardinality, Direction, ErDiagram, ErRelationship};
 
 const BOX_PADDING: f64 = 16.0;
 const LINE_HEIGHT: f64 = 22.0;
 const H_GAP: f64 = 100.0;
+/// Gap between the boxes of a top-to-bottom layout, room for a marker at
+/// each end of the line
+const V_GAP: f64 = 80.0;
+/// How far a self-relationship loop rises above its box and reaches right of
+/// it, room for the cardinality markers at both of its ends
+const LOOP_SIZE: f64 = 45.0;
 
 struct EntityBox {
     id: String,
@@ -25,8 +32,20 @@ pub fn render_er_svg(
     font: &str,
     transparent: bool,
 ) -> String {
+    render_er_document(diagram, colors, font, transparent)
+        .map(|document| document.to_string())
+        .unwrap_or_default()
+}
+
+/// Render an ER diagram to an SVG document, or `None` when it has no entities
+pub fn render_er_document(
+    diagram: &ErDiagram,
+    colors: &DiagramColors,
+    font: &str,
+    transparent: bool,
+) -> Option<SvgDocument> {
     if diagram.entities.is_empty() && diagram.relationships.is_empty() {
-        return String::new();
+        return None;
     }
 
     // Build entity boxes
@@ -74,18 +93,87 @@ pub fn render_er_svg(
         });
     }
 
-    // Simple horizontal layout
-    let mut cur_x = 20.0;
-    for eb in &mut entity_boxes {
-        eb.x = cur_x;
-        eb.y = 50.0;
-        cur_x += eb.width + H_GAP;
+    // Relationships of each entity with itself, drawn as one loop
+    let self_rels = |id: &str| -> Vec<&ErRelationship> {
+        diagram
+            .relationships
+            .iter()
+            .filter(|r| r.entity1 == id && r.entity2 == id)
+            .collect()
+    };
+    let loop_label = |id: &str| -> String {
+        self_rels(id)
+            .iter()
+            .map(|r| r.label.as_str())
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    // Width a loop and its label take right of its box
+    let loop_width = |id: &str| -> f64 {
+        if self_rels(id).is_empty() {
+            0.0
+        } else {
+            LOOP_SIZE + 6.0 + loop_label(id).len() as f64 * 8.0
+        }
+    };
+    let has_loops = diagram.relationships.iter().any(|r| r.entity1 == r.entity2);
+
+    // Simple layout in one row (LR, RL) or one column (TB, BT), in reverse
+    // order for RL and BT
+    let direction = diagram.config.layout_direction();
+    let vertical = matches!(direction, Direction::TD | Direction::TB | Direction::BT);
+    if matches!(direction, Direction::BT | Direction::RL) {
+        entity_boxes.reverse();
+    }
+    if vertical {
+        // One column, with room above each box its loop rises into. Lines
+        // meet the boxes in the middle, so a loop needs a box wide enough
+        // to rise clear of them.
+        for eb in &mut entity_boxes {
+            if !self_rels(&eb.id).is_empty() {
+                eb.width = eb.width.max(2.0 * (LOOP_SIZE + 25.0));
+            }
+        }
+        let center_x = 20.0
+            + entity_boxes
+                .iter()
+                .map(|b| b.width)
+                .fold(0.0f64, |a, b| a.max(b))
+                / 2.0;
+        let mut cur_y = 50.0;
+        for eb in &mut entity_boxes {
+            if !self_rels(&eb.id).is_empty() {
+                cur_y += LOOP_SIZE;
+            }
+            eb.x = center_x - eb.width / 2.0;
+            eb.y = cur_y;
+            cur_y += eb.height + V_GAP;
+        }
+    } else {
+        // One row, lower when loops rise above the boxes
+        let mut cur_x = 20.0;
+        for eb in &mut entity_boxes {
+            eb.x = cur_x;
+            eb.y = if has_loops { 50.0 + LOOP_SIZE } else { 50.0 };
+            cur_x += eb.width + loop_width(&eb.id) + H_GAP;
+        }
     }
 
-    // Calculate canvas size
+    // Calculate canvas size, with the labels right of the lines of a column
+    let label_right = |rel: &ErRelationship| -> f64 {
+        let from_box = entity_boxes.iter().find(|b| b.id == rel.entity1);
+        match from_box {
+            Some(fb) if vertical && rel.entity1 != rel.entity2 => {
+                fb.x + fb.width / 2.0 + 12.0 + rel.label.len() as f64 * 8.0
+            }
+            _ => 0.0,
+        }
+    };
     let total_width = entity_boxes
         .iter()
-        .map(|b| b.x + b.width)
+        .map(|b| b.x + b.width + loop_width(&b.id))
+        .chain(diagram.relationships.iter().map(label_right))
         .fold(0.0f64, |a, b| a.max(b))
         + 40.0;
     let total_height = entity_boxes
@@ -94,25 +182,30 @@ pub fn render_er_svg(
         .fold(0.0f64, |a, b| a.max(b))
         + 60.0;
 
+    let mut document = svg_document(total_width, total_height, colors, transparent);
+    document.head.push(build_style_block(font));
     let mut svg = String::new();
-    svg.push_str(&svg_open_tag(
-        total_width,
-        total_height,
-        colors,
-        transparent,
-    ));
-    svg.push_str(&build_style_block(font));
 
     // Add ER-specific styles
-    svg.push_str(
+    document.head.push(
         r#"<style>
 .er-line { stroke: var(--line); stroke-width: 1.5; }
 .cardinality { font-size: 12px; fill: var(--fg); }
-</style>"#,
+</style>"#
+            .to_string(),
     );
 
     // Draw relationships first
-    for rel in &diagram.relationships {
+    for eb in &entity_boxes {
+        if let Some(first) = self_rels(&eb.id).first() {
+            svg.push_str(&draw_er_self_loop(eb, first, &loop_label(&eb.id)));
+        }
+    }
+    for rel in diagram
+        .relationships
+        .iter()
+        .filter(|r| r.entity1 != r.entity2)
+    {
         let from_box = entity_boxes.iter().find(|b| b.id == rel.entity1);
         let to_box = entity_boxes.iter().find(|b| b.id == rel.entity2);
 
@@ -123,6 +216,7 @@ pub fn render_er_svg(
                 &rel.cardinality1,
                 &rel.cardinality2,
                 &rel.label,
+                vertical,
             ));
         }
     }
@@ -132,8 +226,8 @@ pub fn render_er_svg(
         svg.push_str(&draw_entity_box(eb));
     }
 
-    svg.push_str("</svg>");
-    svg
+    document.body = svg;
+    Some(document)
 }
 
 fn draw_entity_box(eb: &EntityBox) -> String {
@@ -184,22 +278,34 @@ fn draw_entity_box(eb: &EntityBox) -> String {
     s
 }
 
+/// Draw a relationship between two boxes of a row, or of a column when
+/// `vertical`
 fn draw_er_relationship(
     from: &EntityBox,
     to: &EntityBox,
     from_card: &Cardinality,
     to_card: &Cardinality,
     label: &str,
+    vertical: bool,
 ) -> String {
     let mut s = String::new();
 
-    // Calculate connection points (horizontal line between boxes)
-    let (from_x, from_y, to_x, to_y) = if from.x < to.x {
+    // Connection points and the direction the line leaves `from` in: between
+    // facing sides of the boxes
+    let (from_x, from_y, to_x, to_y, dir) = if vertical {
+        let cx = |b: &EntityBox| b.x + b.width / 2.0;
+        if from.y < to.y {
+            (cx(from), from.y + from.height, cx(to), to.y, (0.0, 1.0))
+        } else {
+            (cx(from), from.y, cx(to), to.y + to.height, (0.0, -1.0))
+        }
+    } else if from.x < to.x {
         (
             from.x + from.width,
             from.y + from.height / 2.0,
             to.x,
             to.y + to.height / 2.0,
+            (1.0, 0.0),
         )
     } else {
         (
@@ -207,6 +313,7 @@ fn draw_er_relationship(
             from.y + from.height / 2.0,
             to.x + to.width,
             to.y + to.height / 2.0,
+            (-1.0, 0.0),
         )
     };
 
@@ -217,29 +324,28 @@ fn draw_er_relationship(
     ));
     s.push('\n');
 
-    // From side marker
-    s.push_str(&draw_cardinality_marker(
-        from_x,
-        from_y,
-        if from.x < to.x { 1.0 } else { -1.0 },
-        from_card,
-    ));
-
-    // To side marker
+    // Markers at both ends
+    s.push_str(&draw_cardinality_marker(from_x, from_y, dir, from_card));
     s.push_str(&draw_cardinality_marker(
         to_x,
         to_y,
-        if from.x < to.x { -1.0 } else { 1.0 },
+        (-dir.0, -dir.1),
         to_card,
     ));
 
-    // Label in the middle
+    // Label in the middle, above a horizontal line and right of a vertical one
     let mid_x = (from_x + to_x) / 2.0;
-    let mid_y = (from_y + to_y) / 2.0 - 10.0;
+    let mid_y = (from_y + to_y) / 2.0;
+    let (label_x, label_y, anchor) = if vertical {
+        (mid_x + 12.0, mid_y + 4.0, "start")
+    } else {
+        (mid_x, mid_y - 10.0, "middle")
+    };
     s.push_str(&format!(
-        r#"<text x="{:.1}" y="{:.1}" class="edge-label" text-anchor="middle">{}</text>"#,
-        mid_x,
-        mid_y,
+        r#"<text x="{:.1}" y="{:.1}" class="edge-label" text-anchor="{}">{}</text>"#,
+        label_x,
+        label_y,
+        anchor,
         escape_xml(label)
     ));
     s.push('\n');
@@ -247,106 +353,96 @@ fn draw_er_relationship(
     s
 }
 
-fn draw_cardinality_marker(x: f64, y: f64, dir: f64, card: &Cardinality) -> String {
+/// Draw the marker of `card` on the line leaving an entity at (`x`, `y`) in
+/// direction `dir`, either (±1, 0) or (0, ±1)
+fn draw_cardinality_marker(x: f64, y: f64, dir: (f64, f64), card: &Cardinality) -> String {
     let mut s = String::new();
     let offset = 15.0;
+    // The point `along` the line from the entity and `across` it
+    let p = |along: f64, across: f64| {
+        (
+            x + dir.0 * along + dir.1.abs() * across,
+            y + dir.1 * along + dir.0.abs() * across,
+        )
+    };
+    let line = |s: &mut String, (x1, y1): (f64, f64), (x2, y2): (f64, f64)| {
+        s.push_str(&format!(
+            r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" class="er-line"/>"#,
+            x1, y1, x2, y2
+        ));
+    };
+    let circle = |s: &mut String, (cx, cy): (f64, f64)| {
+        s.push_str(&format!(
+            r#"<circle cx="{:.1}" cy="{:.1}" r="5" class="marker-hollow"/>"#,
+            cx, cy
+        ));
+    };
 
     match card {
         Cardinality::One => {
-            // Two vertical lines (||)
-            s.push_str(&format!(
-                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" class="er-line"/>"#,
-                x + dir * offset,
-                y - 8.0,
-                x + dir * offset,
-                y + 8.0
-            ));
-            s.push_str(&format!(
-                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" class="er-line"/>"#,
-                x + dir * (offset + 5.0),
-                y - 8.0,
-                x + dir * (offset + 5.0),
-                y + 8.0
-            ));
+            // Two bars across the line (||)
+            line(&mut s, p(offset, -8.0), p(offset, 8.0));
+            line(&mut s, p(offset + 5.0, -8.0), p(offset + 5.0, 8.0));
         }
         Cardinality::ZeroOne => {
-            // Circle + vertical line (o|)
-            s.push_str(&format!(
-                r#"<circle cx="{:.1}" cy="{:.1}" r="5" class="marker-hollow"/>"#,
-                x + dir * offset,
-                y
-            ));
-            s.push_str(&format!(
-                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" class="er-line"/>"#,
-                x + dir * (offset + 10.0),
-                y - 8.0,
-                x + dir * (offset + 10.0),
-                y + 8.0
-            ));
+            // Circle + bar (o|)
+            circle(&mut s, p(offset, 0.0));
+            line(&mut s, p(offset + 10.0, -8.0), p(offset + 10.0, 8.0));
         }
         Cardinality::ZeroMany => {
             // Circle + crow's foot (o{)
-            s.push_str(&format!(
-                r#"<circle cx="{:.1}" cy="{:.1}" r="5" class="marker-hollow"/>"#,
-                x + dir * (offset + 15.0),
-                y
-            ));
+            circle(&mut s, p(offset + 15.0, 0.0));
             // Crow's foot (three lines)
-            s.push_str(&format!(
-                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" class="er-line"/>"#,
-                x,
-                y,
-                x + dir * offset,
-                y - 8.0
-            ));
-            s.push_str(&format!(
-                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" class="er-line"/>"#,
-                x,
-                y,
-                x + dir * offset,
-                y
-            ));
-            s.push_str(&format!(
-                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" class="er-line"/>"#,
-                x,
-                y,
-                x + dir * offset,
-                y + 8.0
-            ));
+            line(&mut s, p(0.0, 0.0), p(offset, -8.0));
+            line(&mut s, p(0.0, 0.0), p(offset, 0.0));
+            line(&mut s, p(0.0, 0.0), p(offset, 8.0));
         }
         Cardinality::Many => {
-            // Vertical line + crow's foot (}|)
-            s.push_str(&format!(
-                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" class="er-line"/>"#,
-                x + dir * (offset + 10.0),
-                y - 8.0,
-                x + dir * (offset + 10.0),
-                y + 8.0
-            ));
+            // Bar + crow's foot (}|)
+            line(&mut s, p(offset + 10.0, -8.0), p(offset + 10.0, 8.0));
             // Crow's foot
-            s.push_str(&format!(
-                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" class="er-line"/>"#,
-                x,
-                y,
-                x + dir * offset,
-                y - 8.0
-            ));
-            s.push_str(&format!(
-                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" class="er-line"/>"#,
-                x,
-                y,
-                x + dir * offset,
-                y
-            ));
-            s.push_str(&format!(
-                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" class="er-line"/>"#,
-                x,
-                y,
-                x + dir * offset,
-                y + 8.0
-            ));
+            line(&mut s, p(0.0, 0.0), p(offset, -8.0));
+            line(&mut s, p(0.0, 0.0), p(offset, 0.0));
+            line(&mut s, p(0.0, 0.0), p(offset, 8.0));
         }
     }
     s.push('\n');
     s
 }
+
+/// A relationship of an entity with itself: a loop up out of the top of the
+/// box and back into its right side, with the label right of it
+fn draw_er_self_loop(eb: &EntityBox, rel: &ErRelationship, label: &str) -> String {
+    let right = eb.x + eb.width;
+    let (start_x, side_y) = (right - LOOP_SIZE, eb.y + 20.0);
+    let (top_y, corner_x) = (eb.y - LOOP_SIZE, right + LOOP_SIZE);
+
+    let mut s = format!(
+        r#"<path d="M {:.1} {:.1} L {:.1} {:.1} L {:.1} {:.1} L {:.1} {:.1} L {:.1} {:.1}" class="er-line" fill="none"/>"#,
+        start_x, eb.y, start_x, top_y, corner_x, top_y, corner_x, side_y, right, side_y
+    );
+    s.push('\n');
+    s.push_str(&draw_cardinality_marker(
+        start_x,
+        eb.y,
+        (0.0, -1.0),
+        &rel.cardinality1,
+    ));
+    s.push_str(&draw_cardinality_marker(
+        right,
+        side_y,
+        (1.0, 0.0),
+        &rel.cardinality2,
+    ));
+
+    if !label.is_empty() {
+        s.push_str(&format!(
+            r#"<text x="{:.1}" y="{:.1}" class="edge-label" text-anchor="start">{}</text>"#,
+            corner_x + 6.0,
+            eb.y - LOOP_SIZE / 2.0 + 4.0,
+            escape_xml(label)
+        ));
+        s.push('\n');
+    }
+    s
+}
@@ -0,0 +1,218 @@
+//! Text measurement from real font metrics (the `font-metrics` feature)
+//!
+//! Reads glyph advances from a TrueType/OpenType font, so labels with wide,
+//! narrow or non-ASCII characters get boxes that fit them.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use super::styles::{HeuristicMeasurer, TextMeasurer};
+
+/// A [`TextMeasurer`] backed by the advance widths of a font.
+///
+/// Characters the font has no glyph for are measured with the heuristic.
+/// Without a separate bold face, text of weight 600 and up is widened the way
+/// the heuristic widens it.
+///
+/// # Example
+/// ```rust,no_run
+/// use std::sync::Arc;
+/// use m2svg::svg::FontMetricsMeasurer;
+/// use m2svg::SvgRenderOptions;
+///
+/// let font = std::fs::read("Inter-Regular.ttf").unwrap();
+/// let measurer = FontMetricsMeasurer::new(font).unwrap();
+/// let opts = SvgRenderOptions {
+///     measurer: Some(Arc::new(measurer)),
+///     ..Default::default()
+/// };
+/// let svg = m2svg::render_mermaid_svg("graph LR\n  A --> B", Some(opts)).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct FontMetricsMeasurer {
+    regular: Vec<u8>,
+    bold: Option<Vec<u8>>,
+}
+
+impl FontMetricsMeasurer {
+    /// Measure with the font in `data` (the contents of a .ttf or .otf file)
+    pub fn new(data: Vec<u8>) -> Result<Self, String> {
+        check_font(&data)?;
+        Ok(Self {
+            regular: data,
+            bold: None,
+        })
+    }
+
+    /// Measure text of weight 600 and up with the bold font in `data`
+    pub fn with_bold(mut self, data: Vec<u8>) -> Result<Self, String> {
+        check_font(&data)?;
+        self.bold = Some(data);
+        Ok(self)
+    }
+
+    /// Measure with the first of `families` installed on the system, with its
+    /// bold face when there is one, or `None` if none of them is.
+    ///
+    /// Families are matched by name, ignoring case. Generic families such as
+    /// `sans-serif` never match. The font directories are scanned once per
+    /// process.
+    pub fn from_families(families: &[&str]) -> Option<Self> {
+        if families.is_empty() {
+            return None;
+        }
+        let installed = installed_fonts();
+        families.iter().find_map(|family| {
+            let family = family.to_lowercase();
+            let faces: Vec<&InstalledFont> =
+                installed.iter().filter(|f| f.family == family).collect();
+            // The face nearest each weight, preferring normal widths
+            let nearest = |weight: i32, faces: &[&InstalledFont]| {
+                faces
+                    .iter()
+                    .min_by_key(|f| (!f.normal_width, (f.weight as i32 - weight).abs()))
+                    .and_then(|f| std::fs::read(&f.path).ok())
+            };
+            let regular = nearest(400, &faces)?;
+            let bold_faces: Vec<&InstalledFont> =
+                faces.iter().copied().filter(|f| f.weight >= 600).collect();
+            let measurer = Self::new(regular).ok()?;
+            Some(match nearest(700, &bold_faces) {
+                Some(bold) => measurer.clone().with_bold(bold).unwrap_or(measurer),
+                None => measurer,
+            })
+        })
+    }
+}
+
+/// An upright face of a font found on the system
+struct InstalledFont {
+    /// Lowercase family name
+    family: String,
+    weight: u16,
+    /// Neither condensed nor expanded
+    normal_width: bool,
+    path: PathBuf,
+}
+
+/// The upright .ttf and .otf faces in the system and user font directories
+fn installed_fonts() -> &'static [InstalledFont] {
+    static FONTS: OnceLock<Vec<InstalledFont>> = OnceLock::new();
+    FONTS.get_or_init(|| {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let mut dirs: Vec<PathBuf> = [
+            "/usr/share/fonts",
+            "/usr/local/share/fonts",
+            "/System/Library/Fonts",
+            "/Library/Fonts",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        if let Some(home) = home {
+            for dir in [".local/share/fonts", ".fonts", "Library/Fonts"] {
+                dirs.push(home.join(dir));
+            }
+        }
+        if let Some(windir) = std::env::var_os("WINDIR") {
+            dirs.push(PathBuf::from(windir).join("Fonts"));
+        }
+        if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+            dirs.push(PathBuf::from(local).join("Microsoft/Windows/Fonts"));
+        }
+
+        let mut fonts = Vec::new();
+        for dir in dirs {
+            scan_fonts(&dir, &mut fonts, 0);
+        }
+        fonts
+    })
+}
+
+fn scan_fonts(dir: &Path, fonts: &mut Vec<InstalledFont>, depth: usize) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            // Font trees are shallow; the limit guards against symlink loops
+            if depth < 8 {
+                scan_fonts(&path, fonts, depth + 1);
+            }
+            continue;
+        }
+        let is_font = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("ttf") || e.eq_ignore_ascii_case("otf"));
+        if !is_font {
+            continue;
+        }
+        let Ok(data) = std::fs::read(&path) else {
+            continue;
+        };
+        let Ok(face) = ttf_parser::Face::parse(&data, 0) else {
+            continue;
+        };
+        if face.is_italic() || face.is_oblique() {
+            continue;
+        }
+        // The typographic family groups all weights; older fonts only have
+        // the legacy one
+        let family = [
+            ttf_parser::name_id::TYPOGRAPHIC_FAMILY,
+            ttf_parser::name_id::FAMILY,
+        ]
+        .iter()
+        .find_map(|&id| {
+            face.names()
+                .into_iter()
+                .filter(|name| name.name_id == id && name.is_unicode())
+                .find_map(|name| name.to_string())
+        });
+        if let Some(family) = family {
+            fonts.push(InstalledFont {
+                family: family.to_lowercase(),
+                weight: face.weight().to_number(),
+                normal_width: face.width() == ttf_parser::Width::Normal,
+                path,
+            });
+        }
+    }
+}
+
+fn check_font(data: &[u8]) -> Result<(), String> {
+    ttf_parser::Face::parse(data, 0)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid font data: {}", e))
+}
+
+impl TextMeasurer for FontMetricsMeasurer {
+    fn text_width(&self, text: &str, font_size: f64, font_weight: u32) -> f64 {
+        let (data, widen) = match &self.bold {
+            Some(bold) if font_weight >= 600 => (bold, 1.0),
+            _ => (
+                &self.regular,
+                HeuristicMeasurer::width_ratio(font_weight) / HeuristicMeasurer::width_ratio(400),
+            ),
+        };
+        // Checked when the measurer was made
+        let Ok(face) = ttf_parser::Face::parse(data, 0) else {
+            return HeuristicMeasurer.text_width(text, font_size, font_weight);
+        };
+        let scale = font_size / face.units_per_em() as f64;
+
+        text.chars()
+            .map(|c| {
+                match face
+                    .glyph_index(c)
+                    .and_then(|glyph| face.glyph_hor_advance(glyph))
+                {
+                    Some(advance) => advance as f64 * scale * widen,
+                    None => font_size * HeuristicMeasurer::width_ratio(font_weight),
+                }
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,386 @@
+//! Where SVG text gets its font from
+//!
+//! By default the style block imports the first font family from Google
+//! Fonts, which needs a network connection when the SVG is viewed. A diagram
+//! can instead rely on fonts every system has, or carry its font inside it as
+//! a `data:` URI, so it looks the same on machines without the font installed.
+//! With the `font-embed` feature, an embedded font is cut down to the glyphs
+//! the diagram uses and compressed to WOFF2.
+
+use std::sync::Arc;
+
+use super::theme::{font_families, quote_family};
+
+/// Fonts every system has, after the chosen families
+pub const SYSTEM_FONT_STACK: &str =
+    "system-ui, -apple-system, 'Segoe UI', Roboto, 'Helvetica Neue', Arial, sans-serif";
+
+/// Where SVG text gets its font from
+///
+/// # Example
+/// ```rust
+/// use m2svg::svg::FontSource;
+/// use m2svg::SvgRenderOptions;
+///
+/// let opts = SvgRenderOptions {
+///     font_source: FontSource::System,
+///     ..Default::default()
+/// };
+/// let svg = m2svg::render_mermaid_svg("graph LR\n  A --> B", Some(opts)).unwrap();
+/// assert!(!svg.contains("@import"));
+/// assert!(svg.contains("'Inter', system-ui, -apple-system, 'Segoe UI'"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub enum FontSource {
+    /// Import the first family from Google Fonts
+    #[default]
+    Web,
+    /// Import nothing, and fall back to fonts every system has
+    System,
+    /// Embed this font (the contents of a .ttf, .otf, .woff or .woff2 file)
+    /// as the first of the fonts, which should not be a generic family,
+    /// falling back to fonts every system has
+    Embedded(Arc<Vec<u8>>),
+}
+
+impl FontSource {
+    /// Embed the font in `data`, the contents of a .ttf, .otf, .woff or
+    /// .woff2 file
+    pub fn embed(data: Vec<u8>) -> Result<Self, String> {
+        font_format(&data).ok_or("Invalid font data: not a TrueType, OpenType or WOFF font")?;
+        Ok(FontSource::Embedded(Arc::new(data)))
+    }
+}
+
+/// The CSS `format()` of a font file, from its signature
+fn font_format(data: &[u8]) -> Option<&'static str> {
+    match data.get(..4)? {
+        b"wOF2" => Some("woff2"),
+        b"wOFF" => Some("woff"),
+        b"OTTO" => Some("opentype"),
+        [0, 1, 0, 0] | b"true" => Some("truetype"),
+        _ => None,
+    }
+}
+
+/// An `@font-face` rule with `data` as the font of the first family in
+/// `font`, in a `<style>` block. With the `font-embed` feature, a TrueType or
+/// OpenType font is cut down to the glyphs of `text` and compressed to WOFF2
+/// first.
+pub(crate) fn font_face_block(data: &[u8], font: &str, text: &str) -> String {
+    #[cfg(feature = "font-embed")]
+    let subset = subset::woff2_subset(data, text);
+    #[cfg(not(feature = "font-embed"))]
+    let subset: Option<Vec<u8>> = {
+        let _ = text;
+        None
+    };
+    let (data, format) = match &subset {
+        Some(woff2) => (woff2.as_slice(), "woff2"),
+        None => (data, font_format(data).unwrap_or("truetype")),
+    };
+    let mime = match format {
+        "truetype" => "ttf",
+        "opentype" => "otf",
+        other => other,
+    };
+    format!(
+        "<style>\n  @font-face {{ font-family: {}; src: url(data:font/{};base64,{}) format('{}'); }}\n</style>",
+        quote_family(font_families(font).first().copied().unwrap_or("Inter")),
+        mime,
+        base64(data),
+        format
+    )
+}
+
+/// The characters of the text content of SVG markup, with the entities the
+/// renderers write decoded
+pub(crate) fn text_content(svg: &str) -> String {
+    let mut text = String::new();
+    let mut rest = svg;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = rest[start..]
+            .find('>')
+            .map_or("", |end| &rest[start + end + 1..]);
+    }
+    text.push_str(rest);
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Standard base64 with padding
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(feature = "font-embed")]
+mod subset {
+    //! Font subsetting and WOFF2 compression, with null transforms
+
+    use std::io::Write;
+
+    /// Tags with a one-byte code in the WOFF2 table directory, in code order
+    const KNOWN_TAGS: [&[u8; 4]; 63] = [
+        b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+        b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+        b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+        b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+        b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+        b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+        b"Gloc", b"Feat", b"Sill",
+    ];
+
+    /// The TrueType or OpenType font in `data` with only the glyphs of
+    /// `text`, as WOFF2, or `None` if it can't be subset
+    pub(super) fn woff2_subset(data: &[u8], text: &str) -> Option<Vec<u8>> {
+        let face = ttf_parser::Face::parse(data, 0).ok()?;
+        let mut glyphs: Vec<u16> = text
+            .chars()
+            .chain([' ', '…'])
+            .filter_map(|c| face.glyph_index(c))
+            .map(|g| g.0)
+            .collect();
+        glyphs.push(0);
+        glyphs.sort_unstable();
+        glyphs.dedup();
+        let sfnt = subsetter::subset(data, 0, subsetter::Profile::pdf(&glyphs)).ok()?;
+        woff2(&sfnt)
+    }
+
+    /// An sfnt font packed as WOFF2, its tables untransformed
+    fn woff2(sfnt: &[u8]) -> Option<Vec<u8>> {
+        let u16_at = |at: usize| Some(u16::from_be_bytes(sfnt.get(at..at + 2)?.try_into().ok()?));
+        let u32_at = |at: usize| Some(u32::from_be_bytes(sfnt.get(at..at + 4)?.try_into().ok()?));
+
+        let num_tables = u16_at(4)?;
+        let mut tables: Vec<([u8; 4], &[u8])> = (0..num_tables as usize)
+            .map(|i| {
+                let record = 12 + 16 * i;
+                let tag: [u8; 4] = sfnt.get(record..record + 4)?.try_into().ok()?;
+                let offset = u32_at(record + 8)? as usize;
+                let length = u32_at(record + 12)? as usize;
+                Some((tag, sfnt.get(offset..offset + length)?))
+            })
+            .collect::<Option<_>>()?;
+        // glyf must come before loca
+        tables.sort_by_key(|(tag, _)| *tag);
+
+        let mut directory = Vec::new();
+        let mut stream = Vec::new();
+        for (tag, table) in &tables {
+            // Transform 3 is the null transform for glyf and loca, 0 for the rest
+            let transform = if tag == b"glyf" || tag == b"loca" {
+                3 << 6
+            } else {
+                0
+            };
+            match KNOWN_TAGS.iter().position(|known| *known == tag) {
+                Some(code) => directory.push(transform | code as u8),
+                None => {
+                    directory.push(transform | 63);
+                    directory.extend_from_slice(tag);
+                }
+            }
+            base128(table.len() as u32, &mut directory);
+            stream.extend_from_slice(table);
+        }
+
+        let mut compressed = Vec::new();
+        {
+            let params = brotli::enc::BrotliEncoderParams {
+                quality: 11,
+                mode: brotli::enc::backward_references::BrotliEncoderMode::BROTLI_MODE_FONT,
+                ..Default::default()
+            };
+            let mut writer = brotli::CompressorWriter::with_params(&mut compressed, 4096, &params);
+            writer.write_all(&stream).ok()?;
+        }
+
+        let sfnt_size: usize = 12
+            + 16 * tables.len()
+            + tables
+                .iter()
+                .map(|(_, t)| t.len().next_multiple_of(4))
+                .sum::<usize>();
+        let length = (48 + directory.len() + compressed.len()).next_multiple_of(4);
+        let mut out = Vec::with_capacity(length);
+        out.extend_from_slice(b"wOF2");
+        out.extend_from_slice(&u32_at(0)?.to_be_bytes());
+        out.extend_from_slice(&(length as u32).to_be_bytes());
+        out.extend_from_slice(&num_tables.to_be_bytes());
+        out.extend_from_slice(&0u16.to_be_bytes());
+        out.extend_from_slice(&(sfnt_size as u32).to_be_bytes());
+        out.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+        // Version, then no metadata or private data
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&[0; 20]);
+        out.extend_from_slice(&directory);
+        out.extend_from_slice(&compressed);
+        out.resize(length, 0);
+        Some(out)
+    }
+
+    /// Append `n` as a WOFF2 UIntBase128: big-endian groups of 7 bits, all
+    /// but the last with the high bit set
+    fn base128(n: u32, out: &mut Vec<u8>) {
+        let mut groups = vec![(n & 0x7f) as u8];
+        let mut rest = n >> 7;
+        while rest > 0 {
+            groups.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        out.extend(groups.iter().rev());
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::io::Read;
+
+        /// The sfnt font packed in `woff2`, which has null transforms
+        fn unpack(woff2: &[u8]) -> Vec<u8> {
+            let u32_at = |at: usize| u32::from_be_bytes(woff2[at..at + 4].try_into().unwrap());
+            let num_tables = u16::from_be_bytes([woff2[12], woff2[13]]) as usize;
+            let mut at = 48;
+            let mut tables = Vec::new();
+            for _ in 0..num_tables {
+                let flags = woff2[at];
+                at += 1;
+                let tag = match flags & 63 {
+                    63 => {
+                        at += 4;
+                        woff2[at - 4..at].try_into().unwrap()
+                    }
+                    code => *KNOWN_TAGS[code as usize],
+                };
+                let mut length = 0;
+                loop {
+                    let byte = woff2[at];
+                    at += 1;
+                    length = length << 7 | (byte & 0x7f) as usize;
+                    if byte & 0x80 == 0 {
+                        break;
+                    }
+                }
+                tables.push((tag, length));
+            }
+            let compressed = &woff2[at..at + u32_at(20) as usize];
+            let mut stream = Vec::new();
+            brotli::Decompressor::new(compressed, 4096)
+                .read_to_end(&mut stream)
+                .unwrap();
+
+            let mut sfnt = woff2[4..8].to_vec();
+            sfnt.extend_from_slice(&(num_tables as u16).to_be_bytes());
+            sfnt.extend_from_slice(&[0; 6]);
+            let mut offset = 12 + 16 * num_tables;
+            let mut data = Vec::new();
+            for (tag, length) in &tables {
+                sfnt.extend_from_slice(tag);
+                sfnt.extend_from_slice(&[0; 4]);
+                sfnt.extend_from_slice(&(offset as u32).to_be_bytes());
+                sfnt.extend_from_slice(&(*length as u32).to_be_bytes());
+                let start = data.len();
+                data.extend_from_slice(&stream[..*length]);
+                stream.drain(..*length);
+                data.resize(start + length.next_multiple_of(4), 0);
+                offset += length.next_multiple_of(4);
+            }
+            sfnt.extend_from_slice(&data);
+            assert_eq!(sfnt.len() as u32, u32_at(16));
+            sfnt
+        }
+
+        #[test]
+        fn test_subset_keeps_only_the_glyphs_of_the_text() {
+            // Any installed TrueType font will do
+            fn find_ttf(dir: &std::path::Path) -> Option<std::path::PathBuf> {
+                std::fs::read_dir(dir).ok()?.flatten().find_map(|entry| {
+                    let path = entry.path();
+                    if path.is_dir() {
+                        find_ttf(&path)
+                    } else {
+                        path.extension().is_some_and(|e| e == "ttf").then_some(path)
+                    }
+                })
+            }
+            let Some(font) =
+                find_ttf("/usr/share/fonts".as_ref()).and_then(|p| std::fs::read(p).ok())
+            else {
+                return;
+            };
+            let Some(woff2) = woff2_subset(&font, "Hi") else {
+                return;
+            };
+            assert_eq!(&woff2[..4], b"wOF2");
+            assert_eq!(woff2.len() % 4, 0);
+            assert!(woff2.len() < font.len());
+
+            let sfnt = unpack(&woff2);
+            let face = ttf_parser::Face::parse(&sfnt, 0).unwrap();
+            let original = ttf_parser::Face::parse(&font, 0).unwrap();
+            for c in ['H', 'i'] {
+                let glyph = face.glyph_index(c).unwrap();
+                assert!(face.glyph_bounding_box(glyph).is_some());
+                assert_eq!(
+                    face.glyph_hor_advance(glyph),
+                    original.glyph_hor_advance(glyph)
+                );
+            }
+            let unused = face.glyph_index('x').unwrap();
+            assert!(face.glyph_bounding_box(unused).is_none());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+    }
+
+    #[test]
+    fn test_text_content() {
+        let svg = r#"<g><text x="1">A &amp; B</text><text>&lt;&lt;C&gt;&gt;</text></g>"#;
+        assert_eq!(text_content(svg), "A & B<<C>>");
+    }
+
+    #[test]
+    fn test_embed_checks_the_signature() {
+        assert!(FontSource::embed(b"wOF2rest".to_vec()).is_ok());
+        assert!(FontSource::embed(vec![0, 1, 0, 0, 0]).is_ok());
+        let error = FontSource::embed(b"<svg>".to_vec()).unwrap_err();
+        assert_eq!(
+            error,
+            "Invalid font data: not a TrueType, OpenType or WOFF font"
+        );
+    }
+}
@@ -0,0 +1,424 @@
+//! Embeddable SVG fragments
+//!
+//! Several diagrams inlined in one HTML page share one id space and one set
+//! of stylesheets: the `arrowhead` marker of one diagram is drawn on the edges
+//! of another, and each `<style>` block restyles the others. A fragment has
+//! its ids prefixed with a namespace, its styles written onto the elements as
+//! attributes with the theme colors filled in, and no XML prolog.
+
+use std::collections::HashMap;
+
+use super::renderer::safe_link;
+
+/// `svg` as a fragment to embed next to others: without the XML prolog, with
+/// its ids (and the references to them) prefixed by `namespace`, and with the
+/// rules of its `<style>` blocks and its CSS variables inlined as attributes.
+///
+/// The web font `@import` goes with the `<style>` blocks, and links other
+/// than `http:`, `https:`, `mailto:` and relative ones are dropped. Characters
+/// of `namespace` that can't be in an id become `-`.
+///
+/// # Example
+/// ```rust
+/// use m2svg::svg::svg_fragment;
+///
+/// let svg = m2svg::render_to_svg("graph LR\n  A --> B").unwrap();
+/// let fragment = svg_fragment(&svg, "orders");
+///
+/// assert!(fragment.starts_with("<svg"));
+/// assert!(!fragment.contains("<style>") && !fragment.contains("var(--"));
+/// assert!(fragment.contains(r##"id="orders-arrowhead""##));
+/// assert!(fragment.contains(r##"marker-end="url(#orders-arrowhead)""##));
+/// ```
+pub fn svg_fragment(svg: &str, namespace: &str) -> String {
+    let namespace: String = namespace
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let svg = &svg[svg.find("<svg").unwrap_or(0)..];
+    let (svg, css) = take_style_blocks(svg);
+    let rules = parse_rules(&css);
+
+    // Variables of the `svg` rule, then the root's own, which override them
+    let mut vars = HashMap::new();
+    for rule in rules
+        .iter()
+        .filter(|r| r.selector.tag.as_deref() == Some("svg"))
+    {
+        vars.extend(custom_properties(&rule.declarations));
+    }
+    let root = first_tag(&svg);
+    if let Some(style) = root
+        .as_ref()
+        .and_then(|r| attribute(&r.attributes, "style"))
+    {
+        vars.extend(custom_properties(&declarations(style)));
+    }
+
+    let mut out = String::with_capacity(svg.len());
+    let mut rest = svg.as_str();
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(tag) = rest[1..]
+            .starts_with(|c: char| c.is_ascii_alphabetic())
+            .then(|| parse_tag(rest))
+            .flatten()
+        else {
+            out.push('<');
+            rest = &rest[1..];
+            continue;
+        };
+        out.push_str(&rewrite_tag(&tag, &rules, &vars, &namespace));
+        rest = &rest[tag.len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A start tag, `len` bytes long in the text it was read from
+struct Tag {
+    name: String,
+    attributes: Vec<(String, String)>,
+    self_closing: bool,
+    len: usize,
+}
+
+/// A CSS selector of the forms `tag`, `.class` and `tag.class`
+struct Selector {
+    tag: Option<String>,
+    class: Option<String>,
+}
+
+struct Rule {
+    selector: Selector,
+    declarations: Vec<(String, String)>,
+}
+
+/// `svg` without its `<style>` elements, and their contents
+fn take_style_blocks(svg: &str) -> (String, String) {
+    let (mut rest, mut kept, mut css) = (svg, String::new(), String::new());
+    while let Some(start) = rest.find("<style") {
+        let Some(end) = rest[start..].find("</style>").map(|e| start + e) else {
+            break;
+        };
+        kept.push_str(&rest[..start]);
+        let open = rest[start..end].find('>').map_or(end, |o| start + o + 1);
+        css.push_str(&rest[open..end]);
+        css.push('\n');
+        rest = &rest[end + "</style>".len()..];
+        rest = rest.strip_prefix('\n').unwrap_or(rest);
+    }
+    kept.push_str(rest);
+    (kept, css)
+}
+
+/// The rules of a stylesheet with simple selectors; comments, at-rules and
+/// other selectors are skipped
+fn parse_rules(css: &str) -> Vec<Rule> {
+    let mut text = String::new();
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        text.push_str(&rest[..start]);
+        rest = rest[start..]
+            .find("*/")
+            .map_or("", |end| &rest[start + end + 2..]);
+    }
+    text.push_str(rest);
+
+    let mut rules = Vec::new();
+    for block in text.split('}') {
+        let Some((selectors, body)) = block.split_once('{') else {
+            continue;
+        };
+        // `@import url(...);` and the like end with a semicolon
+        let selectors = selectors.rsplit(';').next().unwrap_or(selectors).trim();
+        if selectors.starts_with('@') {
+            continue;
+        }
+        for selector in selectors.split(',').filter_map(parse_selector) {
+            rules.push(Rule {
+                selector,
+                declarations: declarations(body),
+            });
+        }
+    }
+    rules
+}
+
+fn parse_selector(selector: &str) -> Option<Selector> {
+    let selector = selector.trim();
+    let valid = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    let (tag, class) = match selector.split_once('.') {
+        Some((tag, class)) => ((!tag.is_empty()).then_some(tag), Some(class)),
+        None => (Some(selector), None),
+    };
+    if !tag.is_none_or(valid) || !class.is_none_or(valid) {
+        return None;
+    }
+    Some(Selector {
+        tag: tag.map(str::to_string),
+        class: class.map(str::to_string),
+    })
+}
+
+/// The `property: value` pairs of a rule body or a `style` attribute
+fn declarations(body: &str) -> Vec<(String, String)> {
+    body.split(';')
+        .filter_map(|decl| {
+            let (property, value) = decl.split_once(':')?;
+            let (property, value) = (property.trim(), value.trim());
+            (!property.is_empty()).then(|| (property.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn custom_properties(declarations: &[(String, String)]) -> Vec<(String, String)> {
+    declarations
+        .iter()
+        .filter(|(property, _)| property.starts_with("--"))
+        .cloned()
+        .collect()
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+fn first_tag(svg: &str) -> Option<Tag> {
+    parse_tag(&svg[svg.find('<')?..])
+}
+
+/// The start tag at the beginning of `text`, which starts with `<`
+fn parse_tag(text: &str) -> Option<Tag> {
+    let bytes = text.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() && !matches!(bytes[i], b' ' | b'\n' | b'\t' | b'/' | b'>') {
+        i += 1;
+    }
+    let name = text[1..i].to_string();
+    let mut attributes = Vec::new();
+    loop {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        match bytes.get(i)? {
+            b'>' => {
+                return Some(Tag {
+                    name,
+                    attributes,
+                    self_closing: false,
+                    len: i + 1,
+                })
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'>') => {
+                return Some(Tag {
+                    name,
+                    attributes,
+                    self_closing: true,
+                    len: i + 2,
+                })
+            }
+            _ => {
+                let eq = i + text[i..].find('=')?;
+                let quote = *bytes.get(eq + 1)?;
+                if quote != b'"' && quote != b'\'' {
+                    return None;
+                }
+                let end = eq + 2 + text[eq + 2..].find(quote as char)?;
+                attributes.push((
+                    text[i..eq].trim().to_string(),
+                    text[eq + 2..end].to_string(),
+                ));
+                i = end + 1;
+            }
+        }
+    }
+}
+
+/// `tag` written out again with the matching rules as attributes, its
+/// variables filled in, its ids namespaced and unsafe links dropped
+fn rewrite_tag(
+    tag: &Tag,
+    rules: &[Rule],
+    vars: &HashMap<String, String>,
+    namespace: &str,
+) -> String {
+    let mut attributes = tag.attributes.clone();
+    let classes: Vec<&str> = attribute(&tag.attributes, "class")
+        .map(|c| c.split_whitespace().collect())
+        .unwrap_or_default();
+    let inline = attribute(&tag.attributes, "style")
+        .map(declarations)
+        .unwrap_or_default();
+
+    // Stylesheet rules beat presentation attributes, and lose to `style`
+    for rule in rules {
+        let Selector { tag: name, class } = &rule.selector;
+        if name.as_ref().is_some_and(|n| *n != tag.name)
+            || class
+                .as_ref()
+                .is_some_and(|c| !classes.contains(&c.as_str()))
+        {
+            continue;
+        }
+        for (property, value) in &rule.declarations {
+            if property.starts_with("--") || inline.iter().any(|(p, _)| p == property) {
+                continue;
+            }
+            match attributes.iter_mut().find(|(n, _)| n == property) {
+                Some(existing) => existing.1 = value.clone(),
+                None => attributes.push((property.clone(), value.clone())),
+            }
+        }
+    }
+
+    let mut out = format!("<{}", tag.name);
+    for (name, value) in attributes {
+        let value = match name.as_str() {
+            _ if name.starts_with("on") => continue,
+            "href" | "xlink:href" if !safe_link(&value) => continue,
+            "href" | "xlink:href" if value.starts_with('#') => {
+                format!("#{}-{}", namespace, &value[1..])
+            }
+            "id" => format!("{}-{}", namespace, value),
+            "aria-labelledby" | "aria-describedby" => value
+                .split_whitespace()
+                .map(|id| format!("{}-{}", namespace, id))
+                .collect::<Vec<_>>()
+                .join(" "),
+            "style" => {
+                let style: Vec<String> = declarations(&value)
+                    .into_iter()
+                    .filter(|(property, _)| !property.starts_with("--"))
+                    .map(|(property, value)| format!("{}:{}", property, resolve(&value, vars, 0)))
+                    .collect();
+                if style.is_empty() {
+                    continue;
+                }
+                style.join(";")
+            }
+            _ => resolve(&value, vars, 0),
+        };
+        let value = value
+            .replace("url(#", &format!("url(#{}-", namespace))
+            .replace('"', "&quot;");
+        out.push_str(&format!(" {}=\"{}\"", name, value));
+    }
+    out.push_str(if tag.self_closing { " />" } else { ">" });
+    out
+}
+
+/// `value` with its `var()`s replaced by their values and `color-mix()`es of
+/// two hex colors in sRGB worked out
+fn resolve(value: &str, vars: &HashMap<String, String>, depth: usize) -> String {
+    if depth > 8 {
+        return value.to_string();
+    }
+    let value = replace_calls(value, "var(", |args| {
+        let (name, fallback) = match split_top_level(args).as_slice() {
+            [name] => (name.trim(), None),
+            [name, rest @ ..] => (name.trim(), Some(rest.join(",").trim().to_string())),
+            [] => return None,
+        };
+        match (vars.get(name), fallback) {
+            (Some(v), _) => Some(resolve(v, vars, depth + 1)),
+            (None, Some(fallback)) => Some(resolve(&fallback, vars, depth + 1)),
+            (None, None) => None,
+        }
+    });
+    replace_calls(&value, "color-mix(", |args| {
+        let [space, a, b] = split_top_level(args).try_into().ok()?;
+        if space.trim() != "in srgb" {
+            return None;
+        }
+        let (a, weight) = a.trim().rsplit_once(' ')?;
+        let weight = weight.strip_suffix('%')?.parse::<f64>().ok()? / 100.0;
+        let (a, b) = (hex_color(a)?, hex_color(b.trim())?);
+        let mix = |i: usize| (a[i] as f64 * weight + b[i] as f64 * (1.0 - weight)).round() as u8;
+        Some(format!("#{:02X}{:02X}{:02X}", mix(0), mix(1), mix(2)))
+    })
+}
+
+/// `value` with each `name...)` call replaced by what `f` makes of its
+/// arguments, or left as it is when `f` gives `None`
+fn replace_calls(value: &str, name: &str, f: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find(name) {
+        let args_start = start + name.len();
+        let mut depth = 1;
+        let close = rest[args_start..].char_indices().find_map(|(i, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(args_start + i)
+        });
+        let Some(close) = close else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        match f(&rest[args_start..close]) {
+            Some(replacement) => out.push_str(&replacement),
+            None => out.push_str(&rest[start..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `args` split at the commas outside parentheses
+fn split_top_level(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&args[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&args[start..]);
+    parts
+}
+
+/// The channels of a `#RGB` or `#RRGGBB` color
+fn hex_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#').filter(|hex| hex.is_ascii())?;
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+    match hex.len() {
+        3 => {
+            let mut rgb = [0; 3];
+            for (i, c) in hex.chars().enumerate() {
+                rgb[i] = channel(&c.to_string())? * 17;
+            }
+            Some(rgb)
+        }
+        6 => Some([
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        ]),
+        _ => None,
+    }
+}
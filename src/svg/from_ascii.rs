@@ -3,13 +3,24 @@
 //! This takes the same grid-based layout as ASCII and converts to SVG.
 //! Much simpler than using a separate layout engine like dagre.
 
-use super::renderer::escape_xml;
-use super::theme::{build_style_block, svg_open_tag, DiagramColors};
-use crate::ascii::grid::create_mapping;
+use super::document::SvgDocument;
+use super::layered::{positioned_edge, svg_shape};
+use super::renderer::{
+    curve_path_data, edge_stroke_attrs, escape_xml, link_element, open_arrow_markers,
+};
+use super::styles::{label_align, ArrowHead, StrokeWidths};
+use super::theme::{build_style_block, svg_document, DiagramColors};
+use super::types::{Point, PositionedGraph, PositionedGroup, PositionedLink, PositionedNode};
+use crate::ascii::canvas::block_width;
+use crate::ascii::grid::{create_mapping, stretch_lanes};
 use crate::ascii::types::{
     AsciiConfig, AsciiEdge, AsciiGraph, AsciiNode, AsciiSubgraph, GraphDirection,
 };
-use crate::types::{Direction as MermaidDirection, MermaidGraph};
+use crate::node_width::NodeWidth;
+use crate::types::{
+    CurveStyle, Direction as MermaidDirection, EdgeMarker, EdgeStyle, FlowchartConfig, LabelAlign,
+    MermaidGraph, MermaidSubgraph, Port,
+};
 use std::collections::HashMap;
 
 /// Scale factor: how many pixels per ASCII character cell
@@ -26,41 +37,192 @@ pub fn render_mermaid_to_svg(
     font: &str,
     transparent: bool,
 ) -> String {
+    render_mermaid_to_svg_with_curve(parsed, colors, font, transparent, CurveStyle::Linear)
+}
+
+/// Render a MermaidGraph to SVG using the ASCII layout, drawing edges with `curve`.
+///
+/// With a non-linear curve, each edge leaves and enters its nodes along the
+/// flow direction and bends halfway, instead of running straight across.
+pub fn render_mermaid_to_svg_with_curve(
+    parsed: &MermaidGraph,
+    colors: &DiagramColors,
+    font: &str,
+    transparent: bool,
+    curve: CurveStyle,
+) -> String {
+    render_mermaid_to_document_with_curve(parsed, colors, font, transparent, curve)
+        .map(|document| document.to_string())
+        .unwrap_or_default()
+}
+
+/// Render a MermaidGraph to an SVG document using the ASCII layout, or `None`
+/// for a flowchart without nodes.
+pub fn render_mermaid_to_document_with_curve(
+    parsed: &MermaidGraph,
+    colors: &DiagramColors,
+    font: &str,
+    transparent: bool,
+    curve: CurveStyle,
+) -> Option<SvgDocument> {
+    let graph = grid_layout(parsed)?;
+
+    // Now convert the positioned ASCII graph to SVG
+    Some(ascii_graph_to_svg(
+        &graph,
+        parsed,
+        colors,
+        font,
+        transparent,
+        curve,
+    ))
+}
+
+/// Lay out a flowchart on the grid of the text output, in the px the SVG is
+/// drawn at, or `None` for a flowchart without nodes.
+///
+/// Edges run straight between the sides of their nodes; with a non-linear
+/// `curve` they bend halfway, as [`render_mermaid_to_svg_with_curve`] draws them.
+pub fn layout_grid_flowchart(parsed: &MermaidGraph, curve: CurveStyle) -> Option<PositionedGraph> {
+    let graph = grid_layout(parsed)?;
+    let (canvas_width, canvas_height) = calculate_canvas_size(&graph);
+
+    let nodes = graph
+        .nodes
+        .iter()
+        .filter_map(|node| {
+            let (x, y, width, height) = node_rect(node, &graph.config)?;
+            let style = parsed.node_style(&node.name);
+            Some(PositionedNode {
+                id: node.name.clone(),
+                label: node.display_label.clone(),
+                shape: svg_shape(node.shape),
+                x,
+                y,
+                width,
+                height,
+                inline_style: (!style.is_empty()).then_some(style),
+                link: parsed.node_links.get(&node.name).map(|l| PositionedLink {
+                    url: l.url.clone(),
+                    tooltip: l.tooltip.clone(),
+                    target: l.target.clone(),
+                }),
+            })
+        })
+        .collect();
+
+    // Edges were converted in order, skipping those with an unknown end
+    let known = |id: &String| parsed.nodes.contains_key(id);
+    let bends = bundle_bends(&graph);
+    let edges = (0..parsed.edges.len())
+        .filter(|&i| known(&parsed.edges[i].source) && known(&parsed.edges[i].target))
+        .zip(graph.edges.iter().zip(bends))
+        .filter_map(|(i, (edge, bend))| {
+            let ((x1, y1), (x2, y2)) = edge_ends(&graph, edge)?;
+            let direction = graph.config.graph_direction;
+            let route = edge_route((x1, y1), (x2, y2), direction, curve, bend);
+            let label = Point {
+                x: (x1 + x2) / 2.0,
+                y: (y1 + y2) / 2.0 - 8.0,
+            };
+            Some(positioned_edge(parsed, i, route, Some(label)))
+        })
+        .collect();
+
+    // Subgraphs were converted depth first, in the order they are declared
+    fn group(sg: &MermaidSubgraph, next: &mut usize, graph: &AsciiGraph) -> PositionedGroup {
+        let bounds = &graph.subgraphs[*next];
+        *next += 1;
+        let placed = !(bounds.min_x == 0 && bounds.max_x == 0);
+        let px = |v: f64| placed.then_some(v);
+        PositionedGroup {
+            id: sg.id.clone(),
+            label: sg.label.clone(),
+            x: px(bounds.min_x as f64 * CHAR_WIDTH + 20.0),
+            y: px(bounds.min_y as f64 * CHAR_HEIGHT + 20.0),
+            width: px((bounds.max_x - bounds.min_x) as f64 * CHAR_WIDTH),
+            height: px((bounds.max_y - bounds.min_y) as f64 * CHAR_HEIGHT),
+            children: sg.children.iter().map(|c| group(c, next, graph)).collect(),
+        }
+    }
+    let mut next = 0;
+    let groups = parsed
+        .subgraphs
+        .iter()
+        .map(|sg| group(sg, &mut next, &graph))
+        .collect();
+
+    Some(PositionedGraph {
+        width: canvas_width as f64 * CHAR_WIDTH + 40.0,
+        height: canvas_height as f64 * CHAR_HEIGHT + 40.0,
+        nodes,
+        edges,
+        groups,
+    })
+}
+
+/// The flowchart laid out on the grid of the text output, or `None` for a
+/// flowchart without nodes
+fn grid_layout(parsed: &MermaidGraph) -> Option<AsciiGraph> {
     if parsed.nodes.is_empty() {
-        return String::new();
+        return None;
     }
 
+    // The frontmatter spacing scales the default padding, rounded to cells
+    let defaults = FlowchartConfig::default();
+    let across = parsed.config.node_spacing() / defaults.node_spacing();
+    let along = parsed.config.rank_spacing() / defaults.rank_spacing();
+    let horizontal = matches!(
+        parsed.direction,
+        MermaidDirection::LR | MermaidDirection::RL
+    );
+    let (scale_x, scale_y) = if horizontal {
+        (along, across)
+    } else {
+        (across, along)
+    };
+    let cells = |default: f64, scale: f64| ((default * scale).round() as usize).max(1);
+
     // Create ASCII graph and compute layout
     let config = AsciiConfig {
         use_ascii: false,
-        padding_x: 2,
-        padding_y: 1,
+        padding_x: cells(2.0, scale_x),
+        padding_y: cells(1.0, scale_y),
         box_border_padding: 1,
-        graph_direction: match parsed.direction {
-            MermaidDirection::LR | MermaidDirection::RL => GraphDirection::LR,
-            _ => GraphDirection::TD,
+        graph_direction: if horizontal {
+            GraphDirection::LR
+        } else {
+            GraphDirection::TD
         },
+        sequence_header_every: 0,
+        sequence_max_gap: 0,
+        node_width: node_columns(parsed),
+        label_align: label_align(),
+        rounded_corners: true,
     };
 
     let mut graph = convert_to_ascii_graph(parsed, &config);
     create_mapping(&mut graph);
     calculate_subgraph_bounds(&mut graph);
+    if graph.swimlanes {
+        stretch_lanes(&mut graph);
+    }
     offset_drawing_for_subgraphs(&mut graph);
 
-    // Now convert the positioned ASCII graph to SVG
-    ascii_graph_to_svg(&graph, colors, font, transparent)
+    Some(graph)
 }
 
 /// Convert MermaidGraph to AsciiGraph (copied from flowchart.rs to avoid circular deps)
 fn convert_to_ascii_graph(parsed: &MermaidGraph, config: &AsciiConfig) -> AsciiGraph {
-    use crate::types::MermaidSubgraph;
-
     let mut graph = AsciiGraph::new(config.clone());
+    graph.bundle_edges = parsed.config.bundle_edges();
+    graph.swimlanes = parsed.config.swimlanes();
 
     // Build node list preserving insertion order
     for (index, id) in parsed.node_order.iter().enumerate() {
         if let Some(m_node) = parsed.nodes.get(id) {
-            let ascii_node = AsciiNode::new(id.to_string(), m_node.label.clone(), index);
+            let mut ascii_node = AsciiNode::new(id.to_string(), m_node.label.clone(), index);
+            ascii_node.shape = m_node.shape;
             graph.nodes.push(ascii_node);
         }
     }
@@ -79,7 +241,17 @@ fn convert_to_ascii_graph(parsed: &MermaidGraph, config: &AsciiConfig) -> AsciiG
             id_to_idx.get(m_edge.source.as_str()),
             id_to_idx.get(m_edge.target.as_str()),
         ) {
-            let edge = AsciiEdge::new(from_idx, to_idx, m_edge.label.clone().unwrap_or_default());
+            let mut edge =
+                AsciiEdge::new(from_idx, to_idx, m_edge.label.clone().unwrap_or_default());
+            edge.start_marker = m_edge.start_marker;
+            edge.end_marker = m_edge.end_marker;
+            edge.min_length = m_edge.min_length;
+            edge.start_text = m_edge.start_label.clone().unwrap_or_default();
+            edge.end_text = m_edge.end_label.clone().unwrap_or_default();
+            edge.start_port = m_edge.start_port;
+            edge.end_port = m_edge.end_port;
+            edge.class = m_edge.class.clone();
+            edge.style = m_edge.style;
             graph.edges.push(edge);
         }
     }
@@ -93,6 +265,10 @@ fn convert_to_ascii_graph(parsed: &MermaidGraph, config: &AsciiConfig) -> AsciiG
     ) -> usize {
         let mut sg = AsciiSubgraph::new(m_sg.label.clone());
         sg.parent_idx = parent_idx;
+        sg.direction = m_sg.direction.map(|d| match d {
+            MermaidDirection::LR | MermaidDirection::RL => GraphDirection::LR,
+            _ => GraphDirection::TD,
+        });
 
         for node_id in &m_sg.node_ids {
             if let Some(&idx) = id_to_idx.get(node_id.as_str()) {
@@ -131,8 +307,8 @@ fn calculate_subgraph_bounds(graph: &mut AsciiGraph) {
         for node_idx in all_node_indices {
             let node = &graph.nodes[node_idx];
             if let Some(dc) = node.drawing_coord {
-                let box_width = node.display_label.len() as i32 + 4;
-                let box_height = 4;
+                let (box_width, box_height) =
+                    node_cells(&node.display_label, &graph.config.node_width);
 
                 min_x = min_x.min(dc.x);
                 min_y = min_y.min(dc.y);
@@ -198,25 +374,24 @@ fn offset_drawing_for_subgraphs(graph: &mut AsciiGraph) {
     }
 }
 
-/// Convert positioned ASCII graph to SVG string
+/// Convert positioned ASCII graph to an SVG document
 fn ascii_graph_to_svg(
     graph: &AsciiGraph,
+    parsed: &MermaidGraph,
     colors: &DiagramColors,
     font: &str,
     transparent: bool,
-) -> String {
+    curve: CurveStyle,
+) -> SvgDocument {
     // Calculate SVG dimensions from ASCII character grid
     let (canvas_width, canvas_height) = calculate_canvas_size(graph);
     let svg_width = (canvas_width as f64) * CHAR_WIDTH + 40.0; // padding
     let svg_height = (canvas_height as f64) * CHAR_HEIGHT + 40.0;
 
+    let mut document = svg_document(svg_width, svg_height, colors, transparent);
+    document.head = vec![build_style_block(font), arrow_defs(graph)];
     let mut parts: Vec<String> = Vec::new();
 
-    // SVG header
-    parts.push(svg_open_tag(svg_width, svg_height, colors, transparent));
-    parts.push(build_style_block(font));
-    parts.push(arrow_defs());
-
     // 1. Render subgraphs (backgrounds)
     for sg in &graph.subgraphs {
         if sg.min_x == 0 && sg.max_x == 0 {
@@ -226,34 +401,61 @@ fn ascii_graph_to_svg(
     }
 
     // 2. Render edges
-    for edge in &graph.edges {
-        let from_node = &graph.nodes[edge.from_idx];
-        let to_node = &graph.nodes[edge.to_idx];
-        if let (Some(from_dc), Some(to_dc)) = (from_node.drawing_coord, to_node.drawing_coord) {
-            parts.push(render_edge_svg(
-                from_dc,
-                to_dc,
-                from_node,
-                to_node,
-                &edge.text,
-                &graph.config,
-            ));
-        }
+    for (edge, bend) in graph.edges.iter().zip(bundle_bends(graph)) {
+        let style = resolve_edge_style(parsed, edge.class.as_deref());
+        parts.push(render_edge_svg(graph, edge, &style, curve, bend));
     }
 
     // 3. Render nodes
     for node in &graph.nodes {
         if let Some(dc) = node.drawing_coord {
-            parts.push(render_node_svg(dc, &node.display_label));
+            let style = parsed.node_style(&node.name);
+            let cells = node_cells(&node.display_label, &graph.config.node_width);
+            let align = graph.config.label_align;
+            let node_svg = render_node_svg(dc, &node.display_label, cells, align, &style);
+            match parsed.node_links.get(&node.name) {
+                Some(link) => parts.push(link_element(
+                    &node_svg,
+                    &link.url,
+                    link.target.as_deref(),
+                    link.tooltip.as_deref(),
+                )),
+                None => parts.push(node_svg),
+            }
         }
     }
 
-    parts.push("</svg>".to_string());
-    parts
+    document.body = parts
         .into_iter()
         .filter(|s| !s.is_empty())
         .collect::<Vec<_>>()
-        .join("\n")
+        .join("\n");
+    document
+}
+
+/// The configured node widths in columns of `CHAR_WIDTH` px, with uniform
+/// widths as a minimum of the widest node's
+fn node_columns(parsed: &MermaidGraph) -> NodeWidth {
+    let px = super::styles::node_width();
+    let mut min = (px.min as f64 / CHAR_WIDTH).ceil() as usize;
+    if px.uniform {
+        let widest = parsed.nodes.values().map(|n| block_width(&n.label));
+        min = min.max(widest.max().unwrap_or(0) + 4);
+    }
+    NodeWidth {
+        uniform: false,
+        min,
+        max: (px.max as f64 / CHAR_WIDTH) as usize,
+    }
+}
+
+/// Size of a node box in grid cells, as the text output draws it: the widest
+/// label line plus border and padding across, or as wide as `node_width`
+/// asks, and a row per line plus border and padding down
+fn node_cells(label: &str, node_width: &NodeWidth) -> (i32, i32) {
+    let lines = label.lines().count().max(1) as i32;
+    let cols = node_width.fit((block_width(label) + 4) as f64, 0.0);
+    (cols as i32, lines + 3)
 }
 
 fn calculate_canvas_size(graph: &AsciiGraph) -> (i32, i32) {
@@ -262,9 +464,9 @@ fn calculate_canvas_size(graph: &AsciiGraph) -> (i32, i32) {
 
     for node in &graph.nodes {
         if let Some(dc) = node.drawing_coord {
-            let box_width = node.display_label.len() as i32 + 4;
+            let (box_width, box_height) = node_cells(&node.display_label, &graph.config.node_width);
             max_x = max_x.max(dc.x + box_width);
-            max_y = max_y.max(dc.y + 5);
+            max_y = max_y.max(dc.y + box_height + 1);
         }
     }
 
@@ -276,13 +478,87 @@ fn calculate_canvas_size(graph: &AsciiGraph) -> (i32, i32) {
     (max_x, max_y)
 }
 
-fn arrow_defs() -> String {
-    r#"<defs>
+fn arrow_defs(graph: &AsciiGraph) -> String {
+    let mut defs = vec![r#"<defs>
   <marker id="arrowhead" markerWidth="8" markerHeight="4.8" refX="8" refY="2.4" orient="auto">
     <polygon points="0 0, 8 2.4, 0 4.8" fill="var(--_arrow)" />
-  </marker>
-</defs>"#
-        .to_string()
+  </marker>"#
+        .to_string()];
+
+    // Extra markers are only emitted when an edge uses them
+    let uses = |m: EdgeMarker, at_start: bool| {
+        graph.edges.iter().any(|e| {
+            if at_start {
+                e.start_marker == m
+            } else {
+                e.end_marker == m
+            }
+        })
+    };
+    if uses(EdgeMarker::Arrow, true) {
+        defs.push(
+            r#"  <marker id="arrowhead-start" markerWidth="8" markerHeight="4.8" refX="8" refY="2.4" orient="auto-start-reverse">
+    <polygon points="0 0, 8 2.4, 0 4.8" fill="var(--_arrow)" />
+  </marker>"#
+                .to_string(),
+        );
+    }
+    let dotted_arrows = graph.edges.iter().any(|e| {
+        e.style == EdgeStyle::Dotted
+            && (e.start_marker == EdgeMarker::Arrow || e.end_marker == EdgeMarker::Arrow)
+    });
+    if dotted_arrows {
+        defs.push(open_arrow_markers(ArrowHead::WIDTH, ArrowHead::HEIGHT));
+    }
+    for (marker, name) in [(EdgeMarker::Circle, "circle"), (EdgeMarker::Cross, "cross")] {
+        for (at_start, suffix, ref_x) in [(false, "end", 8), (true, "start", 0)] {
+            if !uses(marker, at_start) {
+                continue;
+            }
+            let shape = match marker {
+                EdgeMarker::Circle => r#"<circle cx="4" cy="4" r="3.5" fill="var(--_arrow)" />"#,
+                _ => {
+                    r#"<path d="M 1 1 L 7 7 M 7 1 L 1 7" stroke="var(--_arrow)" stroke-width="1.5" />"#
+                }
+            };
+            defs.push(format!(
+                r#"  <marker id="{name}-{suffix}" markerWidth="8" markerHeight="8" refX="{ref_x}" refY="4" orient="auto">
+    {shape}
+  </marker>"#
+            ));
+        }
+    }
+
+    defs.push("</defs>".to_string());
+    defs.join("\n")
+}
+
+/// `marker-start`/`marker-end` attributes for an edge; dotted edges get open
+/// arrowheads
+fn marker_attrs(edge: &AsciiEdge) -> String {
+    let dotted = edge.style == EdgeStyle::Dotted;
+    let mut attrs = String::new();
+    let start = match edge.start_marker {
+        EdgeMarker::None => None,
+        EdgeMarker::Arrow if dotted => Some("arrowhead-open-start"),
+        EdgeMarker::Arrow => Some("arrowhead-start"),
+        EdgeMarker::Circle => Some("circle-start"),
+        EdgeMarker::Cross => Some("cross-start"),
+    };
+    if let Some(id) = start {
+        attrs.push_str(&format!(r#" marker-start="url(#{})""#, id));
+    }
+    let end = match edge.end_marker {
+        EdgeMarker::None => None,
+        EdgeMarker::Arrow if dotted => Some("arrowhead-open"),
+        EdgeMarker::Arrow => Some("arrowhead"),
+        EdgeMarker::Circle => Some("circle-end"),
+        EdgeMarker::Cross => Some("cross-end"),
+    };
+    if let Some(id) = end {
+        attrs.push_str(&format!(r#" marker-end="url(#{})""#, id));
+    }
+    attrs
 }
 
 fn render_subgraph_svg(sg: &AsciiSubgraph) -> String {
@@ -307,73 +583,112 @@ fn render_subgraph_svg(sg: &AsciiSubgraph) -> String {
     )
 }
 
-fn render_node_svg(dc: crate::ascii::types::DrawingCoord, label: &str) -> String {
+/// Collect the style properties of an edge from the classDef of its `:::class`.
+/// Edges have no `default` class: `classDef default` only styles nodes.
+pub(super) fn resolve_edge_style(
+    parsed: &MermaidGraph,
+    class: Option<&str>,
+) -> HashMap<String, String> {
+    class
+        .and_then(|name| parsed.class_defs.get(name))
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn render_node_svg(
+    dc: crate::ascii::types::DrawingCoord,
+    label: &str,
+    (cols, rows): (i32, i32),
+    align: Option<LabelAlign>,
+    style: &HashMap<String, String>,
+) -> String {
+    let prop = |key: &str, default: &str| {
+        style
+            .get(key)
+            .map(|v| escape_xml(v))
+            .unwrap_or_else(|| default.to_string())
+    };
+    let dash = style
+        .get("stroke-dasharray")
+        .map(|v| format!(r#" stroke-dasharray="{}""#, escape_xml(v)))
+        .unwrap_or_default();
+
     let x = (dc.x as f64) * CHAR_WIDTH + 20.0;
     let y = (dc.y as f64) * CHAR_HEIGHT + 20.0;
-    let width = (label.len() as f64 + 4.0) * CHAR_WIDTH;
-    let height = 4.0 * CHAR_HEIGHT;
-    let text_x = x + width / 2.0;
-    let text_y = y + height / 2.0;
+    let width = cols as f64 * CHAR_WIDTH;
+    let height = rows as f64 * CHAR_HEIGHT;
+    // Text is padded by 2 columns either side
+    let (text_x, anchor) = align
+        .unwrap_or(LabelAlign::Center)
+        .anchor(x + 2.0 * CHAR_WIDTH, x + width - 2.0 * CHAR_WIDTH);
 
-    format!(
-        r#"<rect x="{x}" y="{y}" width="{width}" height="{height}" rx="0" ry="0" fill="var(--_node-fill)" stroke="var(--_node-stroke)" stroke-width="0.75" />
-<text x="{text_x}" y="{text_y}" text-anchor="middle" dy="0.35em" font-size="13" font-weight="500" fill="var(--_text)">{label}</text>"#,
+    let mut svg = format!(
+        r#"<rect x="{x}" y="{y}" width="{width}" height="{height}" rx="0" ry="0" fill="{fill}" stroke="{stroke}" stroke-width="{stroke_width}"{dash} />"#,
         x = x,
         y = y,
         width = width,
         height = height,
-        text_x = text_x,
-        text_y = text_y,
-        label = escape_xml(label),
-    )
+        fill = prop("fill", "var(--_node-fill)"),
+        stroke = prop("stroke", "var(--_node-stroke)"),
+        stroke_width = prop("stroke-width", "0.75"),
+        dash = dash,
+    );
+    // One row per line of a wrapped label, centered as a block
+    let lines: Vec<&str> = label.lines().collect();
+    let first_y = y + height / 2.0 - (lines.len().max(1) - 1) as f64 * CHAR_HEIGHT / 2.0;
+    for (i, line) in lines.iter().enumerate() {
+        svg.push_str(&format!(
+            r#"
+<text x="{}" y="{}" text-anchor="{}" dy="0.35em" font-size="13" font-weight="500" fill="{}">{}</text>"#,
+            text_x,
+            first_y + i as f64 * CHAR_HEIGHT,
+            anchor,
+            prop("color", "var(--_text)"),
+            escape_xml(line),
+        ));
+    }
+    svg
 }
 
 fn render_edge_svg(
-    from_dc: crate::ascii::types::DrawingCoord,
-    to_dc: crate::ascii::types::DrawingCoord,
-    from_node: &AsciiNode,
-    to_node: &AsciiNode,
-    label: &str,
-    config: &AsciiConfig,
+    graph: &AsciiGraph,
+    edge: &AsciiEdge,
+    style: &HashMap<String, String>,
+    curve: CurveStyle,
+    bend: Option<f64>,
 ) -> String {
-    // Calculate node centers and sizes
-    let from_w = (from_node.display_label.len() as f64 + 4.0) * CHAR_WIDTH;
-    let from_h = 4.0 * CHAR_HEIGHT;
-    let to_w = (to_node.display_label.len() as f64 + 4.0) * CHAR_WIDTH;
-    let _to_h = 4.0 * CHAR_HEIGHT;
-
-    let from_center_x = (from_dc.x as f64) * CHAR_WIDTH + 20.0 + from_w / 2.0;
-    let from_center_y = (from_dc.y as f64) * CHAR_HEIGHT + 20.0 + from_h / 2.0;
-    let to_center_x = (to_dc.x as f64) * CHAR_WIDTH + 20.0 + to_w / 2.0;
-    let to_center_y = (to_dc.y as f64) * CHAR_HEIGHT + 20.0 + _to_h / 2.0;
-
-    // Determine connection points based on graph direction
-    let (x1, y1, x2, y2) = match config.graph_direction {
-        GraphDirection::LR => {
-            // Connect right side of from to left side of to
-            let x1 = (from_dc.x as f64) * CHAR_WIDTH + 20.0 + from_w;
-            let y1 = from_center_y;
-            let x2 = (to_dc.x as f64) * CHAR_WIDTH + 20.0;
-            let y2 = to_center_y;
-            (x1, y1, x2, y2)
-        }
-        GraphDirection::TD => {
-            // Connect bottom of from to top of to
-            let x1 = from_center_x;
-            let y1 = (from_dc.y as f64) * CHAR_HEIGHT + 20.0 + from_h;
-            let x2 = to_center_x;
-            let y2 = (to_dc.y as f64) * CHAR_HEIGHT + 20.0;
-            (x1, y1, x2, y2)
-        }
+    let Some(((x1, y1), (x2, y2))) = edge_ends(graph, edge) else {
+        return String::new();
+    };
+    let label = edge.text.as_str();
+    let config = &graph.config;
+    // Arrowheads are sized in stroke widths, so they grow with thick lines
+    let (width, dash_array) = match edge.style {
+        EdgeStyle::Solid => (StrokeWidths::CONNECTOR, None),
+        EdgeStyle::Dotted => (StrokeWidths::CONNECTOR, Some("4 4")),
+        EdgeStyle::Thick => (StrokeWidths::CONNECTOR * 2.0, None),
+    };
+    let stroke = edge_stroke_attrs(style, width, dash_array);
+
+    let mut svg = if curve == CurveStyle::Linear && bend.is_none() {
+        format!(
+            r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}"{stroke}{markers} />"#,
+            x1 = x1,
+            y1 = y1,
+            x2 = x2,
+            y2 = y2,
+            stroke = stroke,
+            markers = marker_attrs(edge),
+        )
+    } else {
+        let points = edge_route((x1, y1), (x2, y2), config.graph_direction, curve, bend);
+        format!(
+            r#"<path d="{}" fill="none"{}{} />"#,
+            curve_path_data(&points, curve),
+            stroke,
+            marker_attrs(edge),
+        )
     };
-
-    let mut svg = format!(
-        r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="var(--_line)" stroke-width="0.75" marker-end="url(#arrowhead)" />"#,
-        x1 = x1,
-        y1 = y1,
-        x2 = x2,
-        y2 = y2,
-    );
 
     // Add label if present
     if !label.is_empty() {
@@ -381,14 +696,172 @@ fn render_edge_svg(
         let label_y = (y1 + y2) / 2.0 - 8.0;
         svg.push_str(&format!(
             r#"
-<text x="{}" y="{}" text-anchor="middle" dy="0.35em" font-size="11" fill="var(--_text-sec)">{}</text>"#,
-            label_x, label_y, escape_xml(label),
+<text x="{}" y="{}" text-anchor="middle" dy="0.35em" font-size="11" fill="{}">{}</text>"#,
+            label_x,
+            label_y,
+            style
+                .get("color")
+                .map_or_else(|| "var(--_text-sec)".to_string(), |c| escape_xml(c)),
+            escape_xml(label),
         ));
     }
 
+    // Source- and target-side labels sit next to the line ends
+    let (start_pos, end_pos) = match config.graph_direction {
+        GraphDirection::LR => (
+            (x1 + 4.0, y1 + 10.0, "start"),
+            (x2 - 10.0, y2 + 10.0, "end"),
+        ),
+        GraphDirection::TD => (
+            (x1 + 6.0, y1 + 12.0, "start"),
+            (x2 + 6.0, y2 - 12.0, "start"),
+        ),
+    };
+    for (text, (x, y, anchor)) in [(&edge.start_text, start_pos), (&edge.end_text, end_pos)] {
+        if !text.is_empty() {
+            svg.push_str(&format!(
+                r#"
+<text x="{}" y="{}" text-anchor="{}" dy="0.35em" font-size="11" fill="var(--_text-sec)">{}</text>"#,
+                x,
+                y,
+                anchor,
+                escape_xml(text),
+            ));
+        }
+    }
+
     svg
 }
 
+/// Position and size in px of a placed node's box
+fn node_rect(node: &AsciiNode, config: &AsciiConfig) -> Option<(f64, f64, f64, f64)> {
+    let dc = node.drawing_coord?;
+    let (cols, rows) = node_cells(&node.display_label, &config.node_width);
+    Some((
+        (dc.x as f64) * CHAR_WIDTH + 20.0,
+        (dc.y as f64) * CHAR_HEIGHT + 20.0,
+        cols as f64 * CHAR_WIDTH,
+        rows as f64 * CHAR_HEIGHT,
+    ))
+}
+
+/// Where an edge leaves its source and enters its target: the sides facing
+/// along the flow direction, unless ports say otherwise
+fn edge_ends(graph: &AsciiGraph, edge: &AsciiEdge) -> Option<((f64, f64), (f64, f64))> {
+    let (from_x, from_y, from_w, from_h) = node_rect(&graph.nodes[edge.from_idx], &graph.config)?;
+    let (to_x, to_y, to_w, to_h) = node_rect(&graph.nodes[edge.to_idx], &graph.config)?;
+    let (start, end) = match graph.config.graph_direction {
+        // Connect right side of from to left side of to
+        GraphDirection::LR => (Port::East, Port::West),
+        // Connect bottom of from to top of to
+        GraphDirection::TD => (Port::South, Port::North),
+    };
+    let start = edge.start_port.unwrap_or(start);
+    let end = edge.end_port.unwrap_or(end);
+    Some((
+        port_anchor(from_x, from_y, from_w, from_h, start),
+        port_anchor(to_x, to_y, to_w, to_h, end),
+    ))
+}
+
+/// Points an edge from `start` to `end` follows: straight across, or with a
+/// non-linear `curve`, bending halfway along the flow direction so the curve
+/// has something to follow. A bundled edge bends at its bundle's `bend`
+/// instead, whatever the curve.
+fn edge_route(
+    (x1, y1): (f64, f64),
+    (x2, y2): (f64, f64),
+    direction: GraphDirection,
+    curve: CurveStyle,
+    bend: Option<f64>,
+) -> Vec<Point> {
+    let (mx, my) = ((x1 + x2) / 2.0, (y1 + y2) / 2.0);
+    let route = match (curve, direction) {
+        (CurveStyle::Linear, _) if bend.is_none() => vec![(x1, y1), (x2, y2)],
+        (_, GraphDirection::LR) => {
+            let mx = bend.unwrap_or(mx);
+            vec![(x1, y1), (mx, y1), (mx, y2), (x2, y2)]
+        }
+        (_, GraphDirection::TD) => {
+            let my = bend.unwrap_or(my);
+            vec![(x1, y1), (x1, my), (x2, my), (x2, y2)]
+        }
+    };
+    route.into_iter().map(|(x, y)| Point { x, y }).collect()
+}
+
+/// Where each edge bends to join its bundles, with `bundleEdges:` on.
+///
+/// Edges running forward from the same side of a node, or into the same side
+/// of one, make up a bundle when there are at least two of them. They share
+/// the line up to a bend halfway to the nearest node at their other ends, and
+/// branch off from there. An edge in two bundles bends with its source's.
+fn bundle_bends(graph: &AsciiGraph) -> Vec<Option<f64>> {
+    let along = |(x, y): (f64, f64)| match graph.config.graph_direction {
+        GraphDirection::LR => x,
+        GraphDirection::TD => y,
+    };
+    // (start, end) along the flow of the edges that run forward
+    let spans: Vec<Option<(f64, f64)>> = graph
+        .edges
+        .iter()
+        .map(|edge| {
+            let (start, end) = edge_ends(graph, edge)?;
+            let span = (along(start), along(end));
+            (graph.bundle_edges && edge.from_idx != edge.to_idx && span.1 > span.0).then_some(span)
+        })
+        .collect();
+
+    // Per (node, leaving, port side): the shortest gap its edges cross
+    let side = |edge: &AsciiEdge, leaves: bool| {
+        let port = if leaves {
+            edge.start_port
+        } else {
+            edge.end_port
+        };
+        let node = if leaves { edge.from_idx } else { edge.to_idx };
+        (node, leaves, port.map(|p| p as u8))
+    };
+    let mut bundles: HashMap<(usize, bool, Option<u8>), (usize, f64)> = HashMap::new();
+    for (edge, span) in graph.edges.iter().zip(&spans) {
+        if let Some((start, end)) = *span {
+            for leaves in [true, false] {
+                let entry = bundles
+                    .entry(side(edge, leaves))
+                    .or_insert((0, f64::INFINITY));
+                entry.0 += 1;
+                entry.1 = entry.1.min(end - start);
+            }
+        }
+    }
+
+    graph
+        .edges
+        .iter()
+        .zip(spans)
+        .map(|(edge, span)| {
+            let (start, end) = span?;
+            let gap = |leaves: bool| {
+                let (count, gap) = bundles[&side(edge, leaves)];
+                (count >= 2).then_some(gap / 2.0)
+            };
+            gap(true)
+                .map(|g| start + g)
+                .or_else(|| gap(false).map(|g| end - g))
+        })
+        .collect()
+}
+
+/// Midpoint of the side of a node box that a port designates
+fn port_anchor(x: f64, y: f64, w: f64, h: f64, port: Port) -> (f64, f64) {
+    match port {
+        Port::North => (x + w / 2.0, y),
+        Port::South => (x + w / 2.0, y + h),
+        Port::East => (x + w, y + h / 2.0),
+        Port::West => (x, y + h / 2.0),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
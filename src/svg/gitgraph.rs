@@ -1,7 +1,12 @@
 //! SVG renderer for GitGraph diagrams
 
-use super::DiagramColors;
-use crate::types::{CommitType, GitGraph, GitGraphConfig, GitGraphDirection};
+use super::document::SvgDocument;
+use super::renderer::escape_xml;
+use super::styles::estimate_text_width;
+use super::theme::svg_document;
+use super::{font_family_stack, DiagramColors};
+use crate::types::{CommitType, GitCommit, GitGraph, GitGraphConfig, GitGraphDirection};
+use indexmap::IndexMap;
 use std::collections::HashMap;
 
 /// Render a GitGraph to SVG
@@ -11,13 +16,91 @@ pub fn render_gitgraph_svg(
     font: &str,
     transparent: bool,
 ) -> String {
+    render_gitgraph_document(graph, colors, font, transparent).to_string()
+}
+
+/// Render a GitGraph to an SVG document
+pub fn render_gitgraph_document(
+    graph: &GitGraph,
+    colors: &DiagramColors,
+    font: &str,
+    transparent: bool,
+) -> SvgDocument {
     match graph.direction {
-        GitGraphDirection::LR => render_horizontal_svg(graph, colors, font, transparent),
-        GitGraphDirection::TB => render_vertical_svg(graph, colors, font, transparent, false),
-        GitGraphDirection::BT => render_vertical_svg(graph, colors, font, transparent, true),
+        GitGraphDirection::LR => render_horizontal(graph, colors, font, transparent),
+        GitGraphDirection::TB => render_vertical(graph, colors, font, transparent, false),
+        GitGraphDirection::BT => render_vertical(graph, colors, font, transparent, true),
+    }
+}
+
+/// Font size of commit messages
+const MESSAGE_FONT_SIZE: f64 = 11.0;
+
+/// A commit's author and date, as shown under its label: `alice, 2024-05-01`
+fn commit_metadata(commit: &GitCommit) -> Option<String> {
+    let parts: Vec<&str> = [commit.author.as_deref(), commit.date.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+/// Text drawn under a commit's label: its message (showCommitMessage) and its
+/// author and date (showCommitMetadata)
+fn commit_note(commit: &GitCommit, config: &GitGraphConfig) -> Option<String> {
+    let message = commit
+        .message
+        .clone()
+        .filter(|_| config.show_commit_message);
+    let metadata = commit_metadata(commit).filter(|_| config.show_commit_metadata);
+    match (message, metadata) {
+        (Some(message), Some(metadata)) => Some(format!("{} ({})", message, metadata)),
+        (message, metadata) => message.or(metadata),
     }
 }
 
+/// A `<title>` tooltip for a commit with an author or date, listing its id,
+/// author, date and message
+fn commit_tooltip(commit: &GitCommit, config: &GitGraphConfig) -> Option<String> {
+    if !config.show_commit_tooltip || (commit.author.is_none() && commit.date.is_none()) {
+        return None;
+    }
+    let mut lines = vec![commit.id.clone()];
+    if let Some(author) = &commit.author {
+        lines.push(format!("Author: {}", author));
+    }
+    if let Some(date) = &commit.date {
+        lines.push(format!("Date: {}", date));
+    }
+    lines.extend(commit.message.clone());
+    Some(format!("<title>{}</title>", escape_xml(&lines.join("\n"))))
+}
+
+/// Rendered width of the note under each commit (0 when it has none)
+fn message_widths(graph: &GitGraph) -> Vec<f64> {
+    graph
+        .commits
+        .iter()
+        .map(|c| match commit_note(c, &graph.config) {
+            Some(note) => estimate_text_width(&note, MESSAGE_FONT_SIZE, 400),
+            None => 0.0,
+        })
+        .collect()
+}
+
+/// Draw a commit message at (x, y)
+fn draw_message(svg: &mut String, x: f64, y: f64, anchor: &str, msg: &str) {
+    svg.push_str(&format!(
+        r#"<text x="{}" y="{}" class="commit-text" text-anchor="{}" font-size="{}px" fill="var(--muted, var(--fg))">{}</text>"#,
+        x,
+        y,
+        anchor,
+        MESSAGE_FONT_SIZE,
+        escape_xml(msg)
+    ));
+    svg.push('\n');
+}
+
 /// Branch colors (matching mermaid.js default theme)
 const BRANCH_COLORS: &[&str] = &[
     "#0000ED", // main - blue
@@ -41,12 +124,92 @@ fn get_branch_color_with_config(branch_index: usize, config: &GitGraphConfig) ->
 }
 
 /// Get highlight commit color, checking config overrides first
-#[allow(dead_code)]
 fn get_highlight_color_with_config(branch_index: usize, config: &GitGraphConfig) -> Option<String> {
     let idx = branch_index % 8;
     config.highlight_colors[idx].clone()
 }
 
+/// Fill, stroke and stroke width of a commit marker. Highlight commits get a
+/// ring in the branch's `gitInv` color (gold when unset).
+fn commit_style(
+    commit: &GitCommit,
+    branch_index: usize,
+    config: &GitGraphConfig,
+) -> (String, String, f64) {
+    let color = get_branch_color_with_config(branch_index, config);
+    match commit.commit_type {
+        CommitType::Normal => (color.clone(), color, 0.0),
+        CommitType::Reverse => ("var(--bg)".to_string(), color, 3.0),
+        CommitType::Highlight => {
+            let ring = get_highlight_color_with_config(branch_index, config)
+                .unwrap_or_else(|| "#FFD700".to_string());
+            (color, ring, 3.0)
+        }
+    }
+}
+
+/// Draw a commit ID centered at (x, y), over a `commitLabelBackground` box when set
+fn draw_commit_label(svg: &mut String, x: f64, y: f64, id: &str, config: &GitGraphConfig) {
+    let font_size = config.commit_label_font_size.as_deref().unwrap_or("12px");
+    if let Some(background) = &config.commit_label_background {
+        let size = font_size.trim_end_matches("px").parse().unwrap_or(12.0);
+        let w = estimate_text_width(id, size, 400) + 8.0;
+        let h = size + 4.0;
+        svg.push_str(&format!(
+            r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="2" fill="{}" opacity="0.5"/>"#,
+            x - w / 2.0,
+            y - size * 0.8 - 2.0,
+            w,
+            h,
+            background
+        ));
+    }
+    let label_color = config.commit_label_color.as_deref().unwrap_or("var(--fg)");
+    svg.push_str(&format!(
+        r#"<text x="{}" y="{}" class="commit-text" fill="{}" font-size="{}">{}</text>"#,
+        x, y, label_color, font_size, id
+    ));
+}
+
+/// Draw a branch name at (x, y). With a `gitBranchLabel` color set for the
+/// branch, the name is drawn in that color on a box of the branch color, as
+/// Mermaid does.
+fn draw_branch_label(
+    svg: &mut String,
+    x: f64,
+    y: f64,
+    anchor: &str,
+    name: &str,
+    branch_index: usize,
+    config: &GitGraphConfig,
+) {
+    let color = get_branch_color_with_config(branch_index, config);
+    let Some(label_color) = &config.branch_label_colors[branch_index % 8] else {
+        svg.push_str(&format!(
+            r#"<text x="{}" y="{}" class="branch-text" text-anchor="{}" fill="{}">{}</text>"#,
+            x, y, anchor, color, name
+        ));
+        return;
+    };
+    let w = estimate_text_width(name, 12.0, 400) + 8.0;
+    let left = match anchor {
+        "end" => x - w + 4.0,
+        "middle" => x - w / 2.0,
+        _ => x - 4.0,
+    };
+    svg.push_str(&format!(
+        r#"<rect x="{:.1}" y="{}" width="{:.1}" height="18" rx="2" fill="{}"/>"#,
+        left,
+        y - 13.0,
+        w,
+        color
+    ));
+    svg.push_str(&format!(
+        r#"<text x="{}" y="{}" class="branch-text" text-anchor="{}" fill="{}">{}</text>"#,
+        x, y, anchor, label_color, name
+    ));
+}
+
 /// Get tag label styling from config
 fn get_tag_fill(config: &GitGraphConfig) -> &str {
     config.tag_label_background.as_deref().unwrap_or("#FFFFDE")
@@ -85,94 +248,128 @@ fn draw_tag_label(svg: &mut String, cx: f64, tag_y: f64, tag_text: &str, config:
 }
 
 /// Render horizontal (LR) git graph to SVG
-fn render_horizontal_svg(
+/// Start a gitgraph document: its styles and a background covering the whole canvas
+fn gitgraph_document(
+    width: f64,
+    height: f64,
+    colors: &DiagramColors,
+    font: &str,
+    transparent: bool,
+    center_commit_text: bool,
+) -> SvgDocument {
+    let fallback = if super::styles::web_fonts() {
+        "sans-serif"
+    } else {
+        super::fonts::SYSTEM_FONT_STACK
+    };
+    let font = font_family_stack(font, fallback);
+    // The background is a rect rather than the root's style, but the colors
+    // are CSS variables on the root as in the other diagrams
+    let mut document = svg_document(width, height, colors, true);
+    document.prolog = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n".to_string();
+    document.head.push(format!(
+        r#"<style>
+  .commit {{ fill: var(--surface, var(--bg)); }}
+  .commit-text {{ font-family: {}; font-size: 12px; fill: var(--fg);{} }}
+  .branch-text {{ font-family: {}; font-size: 12px; fill: var(--fg); }}
+  .tag-text {{ font-family: {}; font-size: 10px; fill: #333; }}
+</style>"#,
+        font,
+        if center_commit_text {
+            " text-anchor: middle;"
+        } else {
+            ""
+        },
+        font,
+        font
+    ));
+    let bg_color = if transparent { "none" } else { "var(--bg)" };
+    document.head.push(format!(
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        bg_color
+    ));
+    document
+}
+
+fn render_horizontal(
     graph: &GitGraph,
     colors: &DiagramColors,
     font: &str,
     transparent: bool,
-) -> String {
+) -> SvgDocument {
     let commit_radius = 10.0;
-    let commit_spacing_x = 50.0;
-    let branch_spacing_y = 50.0;
+    let commit_spacing_x: f64 = 50.0;
     let label_margin = 80.0;
     let padding = 40.0;
     let left_offset = label_margin + padding;
     let label_offset = 20.0;
+    let message_offset = 14.0;
 
-    // Assign branches to rows
-    let mut branch_rows: HashMap<String, usize> = HashMap::new();
+    // Messages sit under the commit labels, so rows and commits move apart to fit them
+    let message_widths = message_widths(graph);
+    let branch_spacing_y = if message_widths.iter().any(|&w| w > 0.0) {
+        50.0 + message_offset
+    } else {
+        50.0
+    };
 
-    // main first
-    for branch in &graph.branches {
-        if branch.name == "main" {
-            branch_rows.insert(branch.name.clone(), 0);
-        }
-    }
-    let mut row = 1;
+    // Assign branches to rows, following their order attribute (main first)
+    let branch_rows: IndexMap<String, usize> = graph
+        .branches_by_order()
+        .iter()
+        .enumerate()
+        .map(|(row, branch)| (branch.name.clone(), row))
+        .collect();
 
-    for branch in &graph.branches {
-        if branch.name != "main" && !branch_rows.contains_key(&branch.name) {
-            branch_rows.insert(branch.name.clone(), row);
-            row += 1;
-        }
-    }
-
-    let num_rows = row.max(1);
+    let num_rows = branch_rows.len().max(1);
 
     // Calculate commit positions (skip cherry-picks in x advancement)
     let mut commit_positions: HashMap<String, (f64, f64)> = HashMap::new();
     let mut x = left_offset;
 
-    for commit in &graph.commits {
-        let y = padding + (branch_rows[&commit.branch] as f64) * branch_spacing_y;
-        commit_positions.insert(commit.id.clone(), (x, y));
-        x += commit_spacing_x;
+    if graph.config.parallel_commits {
+        // Commits at the same depth share an x, spaced by their widest message
+        let depths = graph.commit_depths();
+        let num_slots = depths.iter().max().map_or(0, |d| d + 1);
+        let mut slot_widths = vec![0.0f64; num_slots + 1];
+        for (&depth, &w) in depths.iter().zip(&message_widths) {
+            slot_widths[depth] = slot_widths[depth].max(w);
+        }
+        let mut slot_x = Vec::with_capacity(num_slots);
+        for slot in 0..num_slots {
+            slot_x.push(x);
+            x += commit_spacing_x
+                .max(((slot_widths[slot] + slot_widths[slot + 1]) / 2.0 + 10.0).ceil());
+        }
+        for (commit, &depth) in graph.commits.iter().zip(&depths) {
+            let y = padding + (branch_rows[&commit.branch] as f64) * branch_spacing_y;
+            commit_positions.insert(commit.id.clone(), (slot_x[depth], y));
+        }
+    } else {
+        for (i, commit) in graph.commits.iter().enumerate() {
+            let y = padding + (branch_rows[&commit.branch] as f64) * branch_spacing_y;
+            commit_positions.insert(commit.id.clone(), (x, y));
+            let next_width = message_widths.get(i + 1).copied().unwrap_or(0.0);
+            x += commit_spacing_x.max(((message_widths[i] + next_width) / 2.0 + 10.0).ceil());
+        }
     }
 
     let width = x + padding;
     let height = padding * 2.0 + (num_rows as f64) * branch_spacing_y;
 
+    let mut document = gitgraph_document(width, height, colors, font, transparent, true);
     let mut svg = String::new();
 
-    // SVG header
-    let bg_color = if transparent { "none" } else { &colors.bg };
-    svg.push_str(&format!(
-        r#"<?xml version="1.0" encoding="UTF-8"?>
-<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">
-<style>
-  .commit {{ fill: {}; }}
-  .commit-text {{ font-family: '{}', sans-serif; font-size: 12px; fill: {}; text-anchor: middle; }}
-  .branch-text {{ font-family: '{}', sans-serif; font-size: 12px; fill: {}; }}
-  .tag-text {{ font-family: '{}', sans-serif; font-size: 10px; fill: #333; }}
-</style>
-<rect width="100%" height="100%" fill="{}"/>
-"#,
-        width,
-        height,
-        width,
-        height,
-        colors.surface.as_deref().unwrap_or(&colors.bg),
-        font,
-        colors.fg,
-        font,
-        colors.fg,
-        font,
-        bg_color
-    ));
-
-    // Draw branch lines (sorted by row for deterministic output)
-    let mut sorted_branches: Vec<_> = branch_rows.iter().collect();
-    sorted_branches.sort_by_key(|(_, &row)| row);
-
-    for (branch_name, branch_row) in &sorted_branches {
-        let y = padding + (**branch_row as f64) * branch_spacing_y;
-        let color = get_branch_color_with_config(**branch_row, &graph.config);
+    // Draw branch lines, by row
+    for (branch_name, branch_row) in &branch_rows {
+        let y = padding + (*branch_row as f64) * branch_spacing_y;
+        let color = get_branch_color_with_config(*branch_row, &graph.config);
 
         // Find first and last commit on this branch
         let commits_on_branch: Vec<_> = graph
             .commits
             .iter()
-            .filter(|c| &c.branch == *branch_name)
+            .filter(|c| &c.branch == branch_name)
             .collect();
 
         if let (Some(first), Some(last)) = (commits_on_branch.first(), commits_on_branch.last()) {
@@ -208,6 +405,8 @@ fn render_horizontal_svg(
         }
     }
 
+    let commits = graph.commit_index();
+
     // Draw connections (branches and merges)
     // Track parents that have already used their horizontal exit (only one allowed)
     let mut used_horizontal_exit: std::collections::HashSet<String> =
@@ -217,11 +416,7 @@ fn render_horizontal_svg(
 
         for parent_id in &commit.parent_ids {
             if let Some(&(px, py)) = commit_positions.get(parent_id) {
-                let parent_branch = graph
-                    .commits
-                    .iter()
-                    .find(|c| &c.id == parent_id)
-                    .map(|c| &c.branch);
+                let parent_branch = commits.get(parent_id).map(|c| &c.branch);
 
                 let color = if let Some(pb) = parent_branch {
                     get_branch_color_with_config(*branch_rows.get(pb).unwrap_or(&0), &graph.config)
@@ -385,11 +580,7 @@ fn render_horizontal_svg(
             if let Some(ref source_id) = commit.cherry_pick_source {
                 if let Some(&(sx, sy)) = commit_positions.get(source_id) {
                     let (cx, cy) = commit_positions[&commit.id];
-                    let source_branch = graph
-                        .commits
-                        .iter()
-                        .find(|c| c.id == *source_id)
-                        .map(|c| &c.branch);
+                    let source_branch = commits.get(source_id).map(|c| &c.branch);
                     let color = if let Some(sb) = source_branch {
                         get_branch_color_with_config(
                             *branch_rows.get(sb).unwrap_or(&0),
@@ -502,12 +693,11 @@ fn render_horizontal_svg(
         }
 
         // Draw commit circle
-        let color_str = color.as_str();
-        let (fill, stroke, stroke_width): (&str, &str, f64) = match commit.commit_type {
-            CommitType::Normal => (color_str, color_str, 0.0),
-            CommitType::Reverse => (colors.bg.as_str(), color_str, 3.0),
-            CommitType::Highlight => (color_str, "#FFD700", 3.0),
-        };
+        let (fill, stroke, stroke_width) = commit_style(commit, branch_row, &graph.config);
+        let tooltip = commit_tooltip(commit, &graph.config);
+        if let Some(tooltip) = &tooltip {
+            svg.push_str(&format!("<g>{}", tooltip));
+        }
 
         if commit.is_merge {
             // Merge commits get a diamond shape
@@ -525,31 +715,24 @@ fn render_horizontal_svg(
                 cx, cy, commit_radius, fill, stroke, stroke_width
             ));
         }
+        if tooltip.is_some() {
+            svg.push_str("</g>");
+        }
         svg.push('\n');
 
         // Draw commit ID
         if graph.config.show_commit_label {
-            let font_size = graph
-                .config
-                .commit_label_font_size
-                .as_deref()
-                .unwrap_or("12px");
-            let label_color = graph
-                .config
-                .commit_label_color
-                .as_deref()
-                .unwrap_or(&colors.fg);
-            svg.push_str(&format!(
-                r#"<text x="{}" y="{}" class="commit-text" fill="{}" font-size="{}">{}</text>"#,
-                cx,
-                cy + commit_radius + label_offset,
-                label_color,
-                font_size,
-                commit.id
-            ));
+            let y = cy + commit_radius + label_offset;
+            draw_commit_label(&mut svg, cx, y, &commit.id, &graph.config);
             svg.push('\n');
         }
 
+        // Draw message and metadata under the label
+        if let Some(note) = commit_note(commit, &graph.config) {
+            let y = cy + commit_radius + label_offset + message_offset;
+            draw_message(&mut svg, cx, y, "middle", &note);
+        }
+
         // Draw tag if present
         if let Some(ref tag) = commit.tag {
             let tag_y = cy - commit_radius - 15.0;
@@ -560,33 +743,33 @@ fn render_horizontal_svg(
 
     // Draw branch labels on the left (sorted by row for deterministic output)
     if graph.config.show_branches {
-        for (branch_name, branch_row) in &sorted_branches {
-            let y = padding + (**branch_row as f64) * branch_spacing_y;
-            let color = get_branch_color_with_config(**branch_row, &graph.config);
-
-            svg.push_str(&format!(
-                r#"<text x="{}" y="{}" class="branch-text" text-anchor="end" fill="{}">{}</text>"#,
+        for (branch_name, branch_row) in &branch_rows {
+            let y = padding + (*branch_row as f64) * branch_spacing_y;
+            draw_branch_label(
+                &mut svg,
                 left_offset - 15.0,
                 y + 4.0,
-                color,
-                branch_name
-            ));
+                "end",
+                branch_name,
+                *branch_row,
+                &graph.config,
+            );
             svg.push('\n');
         }
     } // end show_branches
 
-    svg.push_str("</svg>\n");
-    svg
+    document.body = svg;
+    document
 }
 
 /// Render vertical (TB/BT) git graph to SVG
-fn render_vertical_svg(
+fn render_vertical(
     graph: &GitGraph,
     colors: &DiagramColors,
     font: &str,
     transparent: bool,
     reverse: bool,
-) -> String {
+) -> SvgDocument {
     let commit_radius = 10.0;
     let commit_spacing_y = 50.0;
     let branch_spacing_x = 50.0;
@@ -594,73 +777,88 @@ fn render_vertical_svg(
     let padding = 40.0;
     let top_offset = padding + label_margin;
 
-    // Assign branches to columns
-    let mut branch_cols: HashMap<String, usize> = HashMap::new();
-    branch_cols.insert("main".to_string(), 0);
-    let mut col = 1;
+    // Assign branches to columns, following their order attribute (main first)
+    let branch_cols: IndexMap<String, usize> = graph
+        .branches_by_order()
+        .iter()
+        .enumerate()
+        .map(|(col, branch)| (branch.name.clone(), col))
+        .collect();
 
-    for branch in &graph.branches {
-        if branch.name != "main" && !branch_cols.contains_key(&branch.name) {
-            branch_cols.insert(branch.name.clone(), col);
-            col += 1;
-        }
-    }
-
-    let num_cols = col.max(1);
+    let num_cols = branch_cols.len().max(1);
 
     // Calculate commit positions
     let mut commit_positions: HashMap<String, (f64, f64)> = HashMap::new();
-    let num_commits = graph.commits.len();
+    // One row per commit, or per depth when commits run in parallel
+    let rows: Vec<usize> = if graph.config.parallel_commits {
+        graph.commit_depths()
+    } else {
+        (0..graph.commits.len()).collect()
+    };
+    let num_commits = rows.iter().max().map_or(0, |r| r + 1);
 
-    for (i, commit) in graph.commits.iter().enumerate() {
+    for (commit, &i) in graph.commits.iter().zip(&rows) {
         let x = padding + (branch_cols[&commit.branch] as f64) * branch_spacing_x;
         let row = if reverse { num_commits - 1 - i } else { i };
         let y = top_offset + (row as f64) * commit_spacing_y;
         commit_positions.insert(commit.id.clone(), (x, y));
     }
 
-    let width = padding * 2.0 + (num_cols as f64) * branch_spacing_x + 100.0;
+    // Messages line up in one column right of the rightmost lane's commit labels.
+    // Commits sharing a row (parallelCommits) share its message line, in lane order,
+    // drawn with the row's first commit that has a message.
+    let mut row_messages: Vec<Vec<String>> = vec![Vec::new(); num_commits];
+    let mut message_owners: Vec<Option<&str>> = vec![None; num_commits];
+    let mut by_lane: Vec<(&GitCommit, usize)> =
+        graph.commits.iter().zip(rows.iter().copied()).collect();
+    by_lane.sort_by_key(|(c, _)| branch_cols[&c.branch]);
+    for (commit, row) in by_lane {
+        if let Some(note) = commit_note(commit, &graph.config) {
+            row_messages[row].push(note);
+            message_owners[row].get_or_insert(&commit.id);
+        }
+    }
+    let row_messages: Vec<String> = row_messages.iter().map(|m| m.join("; ")).collect();
+    let widest_label = if graph.config.show_commit_label {
+        graph
+            .commits
+            .iter()
+            .map(|c| estimate_text_width(&c.id, 12.0, 400))
+            .fold(0.0, f64::max)
+            .ceil()
+    } else {
+        0.0
+    };
+    let message_x = padding
+        + ((num_cols - 1) as f64) * branch_spacing_x
+        + commit_radius
+        + 5.0
+        + widest_label
+        + 16.0;
+    let widest_message = row_messages
+        .iter()
+        .map(|m| estimate_text_width(m, MESSAGE_FONT_SIZE, 400))
+        .fold(0.0, f64::max)
+        .ceil();
+
+    let mut width = padding * 2.0 + (num_cols as f64) * branch_spacing_x + 100.0;
+    if widest_message > 0.0 {
+        width = width.max(message_x + widest_message + padding);
+    }
     let height = top_offset + padding + (num_commits as f64) * commit_spacing_y;
 
+    let mut document = gitgraph_document(width, height, colors, font, transparent, false);
     let mut svg = String::new();
 
-    let bg_color = if transparent { "none" } else { &colors.bg };
-    svg.push_str(&format!(
-        r#"<?xml version="1.0" encoding="UTF-8"?>
-<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">
-<style>
-  .commit {{ fill: {}; }}
-  .commit-text {{ font-family: '{}', sans-serif; font-size: 12px; fill: {}; }}
-  .branch-text {{ font-family: '{}', sans-serif; font-size: 12px; fill: {}; }}
-  .tag-text {{ font-family: '{}', sans-serif; font-size: 10px; fill: #333; }}
-</style>
-<rect width="100%" height="100%" fill="{}"/>
-"#,
-        width,
-        height,
-        width,
-        height,
-        colors.surface.as_deref().unwrap_or(&colors.bg),
-        font,
-        colors.fg,
-        font,
-        colors.fg,
-        font,
-        bg_color
-    ));
-
-    // Draw branch lines (sorted by col for deterministic output)
-    let mut sorted_branches: Vec<_> = branch_cols.iter().collect();
-    sorted_branches.sort_by_key(|(_, &col)| col);
-
-    for (branch_name, branch_col) in &sorted_branches {
-        let x = padding + (**branch_col as f64) * branch_spacing_x;
-        let color = get_branch_color_with_config(**branch_col, &graph.config);
+    // Draw branch lines, by column
+    for (branch_name, branch_col) in &branch_cols {
+        let x = padding + (*branch_col as f64) * branch_spacing_x;
+        let color = get_branch_color_with_config(*branch_col, &graph.config);
 
         let commits_on_branch: Vec<_> = graph
             .commits
             .iter()
-            .filter(|c| &c.branch == *branch_name)
+            .filter(|c| &c.branch == branch_name)
             .collect();
 
         if let (Some(first), Some(last)) = (commits_on_branch.first(), commits_on_branch.last()) {
@@ -770,17 +968,15 @@ fn render_vertical_svg(
     }
 
     // Draw commits
-    for commit in &graph.commits {
+    for (commit, &row) in graph.commits.iter().zip(&rows) {
         let (cx, cy) = commit_positions[&commit.id];
         let branch_col = *branch_cols.get(&commit.branch).unwrap_or(&0);
-        let color = get_branch_color_with_config(branch_col, &graph.config);
 
-        let color_str = color.as_str();
-        let (fill, stroke, stroke_width): (&str, &str, f64) = match commit.commit_type {
-            CommitType::Normal => (color_str, color_str, 0.0),
-            CommitType::Reverse => (colors.bg.as_str(), color_str, 3.0),
-            CommitType::Highlight => (color_str, "#FFD700", 3.0),
-        };
+        let (fill, stroke, stroke_width) = commit_style(commit, branch_col, &graph.config);
+        let tooltip = commit_tooltip(commit, &graph.config);
+        if let Some(tooltip) = &tooltip {
+            svg.push_str(&format!("<g>{}", tooltip));
+        }
 
         if commit.is_merge {
             svg.push_str(&format!(
@@ -797,19 +993,22 @@ fn render_vertical_svg(
                 cx, cy, commit_radius, fill, stroke, stroke_width
             ));
         }
+        if tooltip.is_some() {
+            svg.push_str("</g>");
+        }
         svg.push('\n');
 
         // Draw commit ID to the right
         if graph.config.show_commit_label {
-            svg.push_str(&format!(
-                r#"<text x="{}" y="{}" class="commit-text">{}</text>"#,
-                cx + commit_radius + 5.0,
-                cy + 4.0,
-                commit.id
-            ));
+            let x = cx + commit_radius + 5.0;
+            draw_commit_label(&mut svg, x, cy + 4.0, &commit.id, &graph.config);
             svg.push('\n');
         }
 
+        if message_owners[row] == Some(commit.id.as_str()) {
+            draw_message(&mut svg, message_x, cy + 4.0, "start", &row_messages[row]);
+        }
+
         // Draw tag if present (to the left of the commit)
         if let Some(ref tag) = commit.tag {
             let tag_x = cx - commit_radius - 15.0;
@@ -820,27 +1019,27 @@ fn render_vertical_svg(
 
     // Draw branch labels at top (TB) or bottom (BT), sorted by col for deterministic output
     if graph.config.show_branches {
-        for (branch_name, branch_col) in &sorted_branches {
-            let x = padding + (**branch_col as f64) * branch_spacing_x;
-            let color = get_branch_color_with_config(**branch_col, &graph.config);
-
-            if reverse {
-                // BT: labels at the bottom
-                svg.push_str(&format!(
-                r#"<text x="{}" y="{}" class="branch-text" text-anchor="middle" fill="{}">{}</text>"#,
-                x, height - padding + 20.0, color, branch_name
-            ));
+        for (branch_name, branch_col) in &branch_cols {
+            let x = padding + (*branch_col as f64) * branch_spacing_x;
+            // TB: labels at the top, BT: at the bottom
+            let y = if reverse {
+                height - padding + 20.0
             } else {
-                // TB: labels at the top
-                svg.push_str(&format!(
-                r#"<text x="{}" y="{}" class="branch-text" text-anchor="middle" fill="{}">{}</text>"#,
-                x, padding, color, branch_name
-            ));
-            }
+                padding
+            };
+            draw_branch_label(
+                &mut svg,
+                x,
+                y,
+                "middle",
+                branch_name,
+                *branch_col,
+                &graph.config,
+            );
             svg.push('\n');
         }
     } // end show_branches
 
-    svg.push_str("</svg>\n");
-    svg
+    document.body = svg;
+    document
 }
@@ -0,0 +1,1606 @@
+//! Layered layout for SVG flowcharts
+//!
+//! A Sugiyama-style pipeline that works in floating point instead of on the
+//! character grid shared with the ASCII renderer:
+//!
+//! 1. Cycles are broken by reversing the back edges of a depth-first search
+//! 2. Ranks come from a longest-path pass honouring `min_length`; sources are
+//!    then pulled down next to their successors
+//! 3. Edges spanning several ranks are split by dummy nodes. A labelled edge
+//!    gets a dummy the size of its label on its middle rank
+//! 4. Each rank is ordered by alternating barycenter sweeps. Subgraphs stay
+//!    contiguous, bracketed by a left and right border node on every rank
+//! 5. Positions within a rank come from Brandes-Köpf: four extreme alignments
+//!    are compacted separately, then balanced
+//!
+//! The layout always runs top to bottom. LR, RL and BT graphs are laid out
+//! with swapped node sizes and transformed afterwards.
+//!
+//! Edges are polylines through their dummy nodes by default. With orthogonal
+//! routing, every segment that has to bend between two ranks gets a channel of
+//! its own in the gap between them, and nodes spread the ends of their edges
+//! along their sides.
+
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
+
+use super::document::SvgDocument;
+use super::from_ascii::resolve_edge_style;
+use super::renderer::{node_outline, render_document_with_curve};
+use super::styles::{estimate_text_width, node_width, FontSizes, FontWeights, NODE_LINE_HEIGHT};
+use super::theme::DiagramColors;
+use super::types::{
+    EdgeStyle, NodeShape, Point, PositionedEdge, PositionedGraph, PositionedGroup, PositionedLink,
+    PositionedNode,
+};
+use crate::ascii::grid::back_edges;
+use crate::types::{
+    CurveStyle, Direction, EdgeMarker, EdgeRouting, EdgeStyle as MermaidEdgeStyle, MermaidGraph,
+    MermaidNode, MermaidSubgraph, NodeShape as MermaidShape, Port,
+};
+
+/// Gap kept around dummy and border nodes
+const EDGE_SEP: f64 = 16.0;
+/// Space around the whole drawing
+const MARGIN: f64 = 20.0;
+/// Space between a subgraph's border and its content
+const CLUSTER_PAD: f64 = 16.0;
+/// Height of a subgraph's header band
+const CLUSTER_HEADER: f64 = FontSizes::GROUP_HEADER + 16.0;
+/// Number of barycenter sweeps when ordering ranks
+const ORDER_SWEEPS: usize = 8;
+/// How far a self-loop sticks out of its node
+const SELF_LOOP: f64 = 20.0;
+/// Distance between neighbouring channels of orthogonal edges
+const CHANNEL_SEP: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Real,
+    Dummy,
+    Border { left: bool },
+}
+
+/// A node of the layered graph. Sizes and coordinates are in layout space:
+/// `width`/`x` run along the rank, `height`/`y` across ranks.
+#[derive(Debug, Clone)]
+struct LNode {
+    kind: Kind,
+    width: f64,
+    height: f64,
+    rank: usize,
+    /// Enclosing subgraphs, outermost first
+    clusters: Vec<usize>,
+    x: f64,
+}
+
+impl LNode {
+    fn is_virtual(&self) -> bool {
+        self.kind != Kind::Real
+    }
+}
+
+/// A subgraph flattened out of the subgraph tree
+struct Cluster {
+    parent: Option<usize>,
+    children: Vec<usize>,
+    min_rank: usize,
+    max_rank: usize,
+    left: Vec<usize>,
+    right: Vec<usize>,
+}
+
+/// An edge of the input graph, oriented along the ranks
+struct Route {
+    edge_idx: usize,
+    reversed: bool,
+    /// Nodes the edge runs through, from the upper end to the lower end
+    chain: Vec<usize>,
+    label_node: Option<usize>,
+}
+
+/// Lay out a flowchart with the layered engine.
+pub fn layout_flowchart(graph: &MermaidGraph) -> PositionedGraph {
+    layout_flowchart_with_routing(graph, EdgeRouting::Polyline)
+}
+
+/// Lay out a flowchart with the layered engine, routing edges as `routing` says.
+pub fn layout_flowchart_with_routing(
+    graph: &MermaidGraph,
+    routing: EdgeRouting,
+) -> PositionedGraph {
+    let horizontal = matches!(graph.direction, Direction::LR | Direction::RL);
+    let ids: Vec<&str> = graph
+        .node_order
+        .iter()
+        .filter(|id| graph.nodes.contains_key(*id))
+        .map(String::as_str)
+        .collect();
+    let index: HashMap<&str, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    let sizes: Vec<(f64, f64)> = ids.iter().map(|id| node_size(&graph.nodes[*id])).collect();
+    let sizes = fit_node_widths(&ids, graph, sizes);
+    let to_layout = |(w, h): (f64, f64)| if horizontal { (h, w) } else { (w, h) };
+
+    // Edges between known nodes; self-loops are drawn separately
+    let edges: Vec<(usize, usize, usize)> = graph
+        .edges
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| {
+            Some((
+                i,
+                *index.get(e.source.as_str())?,
+                *index.get(e.target.as_str())?,
+            ))
+        })
+        .collect();
+    let self_loops: Vec<(usize, usize)> = edges
+        .iter()
+        .filter(|(_, a, b)| a == b)
+        .map(|&(i, a, _)| (i, a))
+        .collect();
+    let edges: Vec<(usize, usize, usize)> = edges.into_iter().filter(|(_, a, b)| a != b).collect();
+
+    let mut nodes: Vec<LNode> = sizes
+        .iter()
+        .enumerate()
+        .map(|(i, &size)| {
+            let (mut width, height) = to_layout(size);
+            if self_loops.iter().any(|&(_, n)| n == i) {
+                width += 2.0 * SELF_LOOP;
+            }
+            LNode {
+                kind: Kind::Real,
+                width,
+                height,
+                rank: 0,
+                clusters: Vec::new(),
+                x: 0.0,
+            }
+        })
+        .collect();
+
+    // 1. Break cycles, 2. assign ranks. With labels, every edge spans twice
+    // as many ranks so that label dummies get a rank of their own.
+    let pairs: Vec<(usize, usize)> = edges.iter().map(|&(_, a, b)| (a, b)).collect();
+    let reversed = back_edges(ids.len(), &pairs);
+    let has_labels = edges
+        .iter()
+        .any(|&(i, _, _)| graph.edges[i].label.is_some());
+    let scale = if has_labels { 2 } else { 1 };
+    let ranked: Vec<(usize, usize, usize)> = edges
+        .iter()
+        .zip(&reversed)
+        .map(|(&(i, a, b), &rev)| {
+            let len = graph.edges[i].min_length.max(1) * scale;
+            if rev {
+                (b, a, len)
+            } else {
+                (a, b, len)
+            }
+        })
+        .collect();
+    let ranks = assign_ranks(ids.len(), &ranked);
+    for (node, rank) in nodes.iter_mut().zip(ranks) {
+        node.rank = rank;
+    }
+
+    // Subgraph membership of the real nodes
+    let mut clusters: Vec<Cluster> = Vec::new();
+    let mut cluster_ids: Vec<&MermaidSubgraph> = Vec::new();
+    let mut owner: IndexMap<usize, usize> = IndexMap::new();
+    for sg in &graph.subgraphs {
+        flatten_subgraph(
+            sg,
+            None,
+            &index,
+            &mut clusters,
+            &mut cluster_ids,
+            &mut owner,
+        );
+    }
+    let path_of = |c: usize, clusters: &[Cluster]| {
+        let mut path = vec![c];
+        while let Some(p) = clusters[*path.last().unwrap()].parent {
+            path.push(p);
+        }
+        path.reverse();
+        path
+    };
+    for (&n, &c) in &owner {
+        nodes[n].clusters = path_of(c, &clusters);
+    }
+
+    // 3. Split long edges with dummies
+    let mut routes: Vec<Route> = Vec::new();
+    for (&(edge_idx, _, _), (&(upper, lower, _), &rev)) in
+        edges.iter().zip(ranked.iter().zip(&reversed))
+    {
+        let common: Vec<usize> = nodes[upper]
+            .clusters
+            .iter()
+            .zip(&nodes[lower].clusters)
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| *a)
+            .collect();
+        let mut chain = vec![upper];
+        for rank in nodes[upper].rank + 1..nodes[lower].rank {
+            chain.push(nodes.len());
+            nodes.push(LNode {
+                kind: Kind::Dummy,
+                width: 0.0,
+                height: 0.0,
+                rank,
+                clusters: common.clone(),
+                x: 0.0,
+            });
+        }
+        chain.push(lower);
+
+        let label_node = graph.edges[edge_idx].label.as_ref().and_then(|label| {
+            let mid = chain[chain.len() / 2];
+            (nodes[mid].kind == Kind::Dummy).then(|| {
+                let (w, h) = to_layout(label_size(label));
+                nodes[mid].width = w;
+                nodes[mid].height = h;
+                mid
+            })
+        });
+        routes.push(Route {
+            edge_idx,
+            reversed: rev,
+            chain,
+            label_node,
+        });
+    }
+
+    // Border nodes bracket each subgraph on every rank it spans
+    for c in 0..clusters.len() {
+        let spanned: Vec<usize> = nodes
+            .iter()
+            .filter(|n| n.clusters.contains(&c))
+            .map(|n| n.rank)
+            .collect();
+        let (Some(&min_rank), Some(&max_rank)) = (spanned.iter().min(), spanned.iter().max())
+        else {
+            continue;
+        };
+        clusters[c].min_rank = min_rank;
+        clusters[c].max_rank = max_rank;
+        let path = path_of(c, &clusters);
+        for rank in min_rank..=max_rank {
+            for left in [true, false] {
+                // In horizontal graphs the left border ends up on top, where
+                // the header goes
+                let width = if horizontal && left {
+                    CLUSTER_HEADER
+                } else {
+                    0.0
+                };
+                let idx = nodes.len();
+                nodes.push(LNode {
+                    kind: Kind::Border { left },
+                    width,
+                    height: 0.0,
+                    rank,
+                    clusters: path.clone(),
+                    x: 0.0,
+                });
+                if left {
+                    clusters[c].left.push(idx);
+                } else {
+                    clusters[c].right.push(idx);
+                }
+            }
+        }
+    }
+
+    // Segments between adjacent ranks
+    let n = nodes.len();
+    let mut preds: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut succs: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let chains = routes.iter().map(|r| r.chain.as_slice());
+    let borders = clusters
+        .iter()
+        .flat_map(|c| [c.left.as_slice(), c.right.as_slice()]);
+    for chain in chains.chain(borders) {
+        for pair in chain.windows(2) {
+            succs[pair[0]].push(pair[1]);
+            preds[pair[1]].push(pair[0]);
+        }
+    }
+
+    // 4. Order the ranks
+    let rank_count = nodes.iter().map(|n| n.rank + 1).max().unwrap_or(0);
+    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); rank_count];
+    for (idx, node) in nodes.iter().enumerate() {
+        layers[node.rank].push(idx);
+    }
+    order_layers(&nodes, &mut layers, &preds, &succs);
+
+    // 5. Positions along each rank, then across ranks
+    let xs = assign_x(&nodes, &layers, &preds, &succs, graph.config.node_spacing());
+    for (node, x) in nodes.iter_mut().zip(xs) {
+        node.x = x;
+    }
+
+    let (unit_before, unit_after) = match graph.direction {
+        Direction::TD | Direction::TB => (CLUSTER_HEADER + CLUSTER_PAD, CLUSTER_PAD),
+        Direction::BT => (CLUSTER_PAD, CLUSTER_HEADER + CLUSTER_PAD),
+        Direction::LR | Direction::RL => (CLUSTER_PAD, CLUSTER_PAD),
+    };
+    let stack_before = cluster_stacks(&clusters, |c| c.min_rank);
+    let stack_after = cluster_stacks(&clusters, |c| c.max_rank);
+    let mut extra_before = vec![0.0f64; rank_count];
+    let mut extra_after = vec![0.0f64; rank_count];
+    for (c, cluster) in clusters.iter().enumerate() {
+        if cluster.left.is_empty() {
+            continue;
+        }
+        let before = &mut extra_before[cluster.min_rank];
+        *before = before.max(stack_before[c] as f64 * unit_before);
+        let after = &mut extra_after[cluster.max_rank];
+        *after = after.max(stack_after[c] as f64 * unit_after);
+    }
+
+    // Where each segment of a route leaves its upper node and enters its lower
+    // one, along the rank
+    let mut ends: Vec<Vec<(f64, f64)>> = routes
+        .iter()
+        .map(|r| {
+            r.chain
+                .windows(2)
+                .map(|p| (nodes[p[0]].x, nodes[p[1]].x))
+                .collect()
+        })
+        .collect();
+    // Bundled edges need the channels, so they are always orthogonal
+    let bundle = graph.config.bundle_edges();
+    let orthogonal = routing == EdgeRouting::Orthogonal || bundle;
+    let mut channels: Vec<Vec<Option<usize>>> = ends.iter().map(|e| vec![None; e.len()]).collect();
+    let mut channel_count = vec![0usize; rank_count];
+    if orthogonal {
+        if !bundle {
+            spread_ports(&nodes, ids.len(), &routes, &mut ends);
+        }
+        let mut gaps: Vec<Vec<(usize, usize)>> = vec![Vec::new(); rank_count];
+        for (ri, route) in routes.iter().enumerate() {
+            for (k, &(from, to)) in ends[ri].iter().enumerate() {
+                if (from - to).abs() > 0.5 {
+                    gaps[nodes[route.chain[k]].rank].push((ri, k));
+                }
+            }
+        }
+        for (r, segments) in gaps.iter().enumerate() {
+            let spans: Vec<(f64, f64)> = segments.iter().map(|&(ri, k)| ends[ri][k]).collect();
+            // A bundle of segments leaving (or else entering) the same node
+            // shares the channel of its first one, and branches off from there
+            let ends_of = |&(ri, k): &(usize, usize)| {
+                let route: &Route = &routes[ri];
+                let real = |v: usize| (bundle && !route.reversed && v < ids.len()).then_some(v);
+                (real(route.chain[k]), real(route.chain[k + 1]))
+            };
+            let mut sizes: HashMap<(bool, usize), usize> = HashMap::new();
+            for (upper, lower) in segments.iter().map(ends_of) {
+                let keys = [upper.map(|v| (true, v)), lower.map(|v| (false, v))];
+                for key in keys.into_iter().flatten() {
+                    *sizes.entry(key).or_insert(0) += 1;
+                }
+            }
+            let bundled = |key: (bool, usize)| sizes.get(&key).is_some_and(|&n| n >= 2);
+            let mut bundle_channels: HashMap<(bool, usize), usize> = HashMap::new();
+            let mut count = 0;
+            for i in order_channels(&spans) {
+                let (upper, lower) = ends_of(&segments[i]);
+                let (ri, k) = segments[i];
+                let key = upper
+                    .map(|v| (true, v))
+                    .filter(|&key| bundled(key))
+                    .or_else(|| lower.map(|v| (false, v)).filter(|&key| bundled(key)));
+                let c = match key.and_then(|key| bundle_channels.get(&key)) {
+                    Some(&c) => c,
+                    None => {
+                        count += 1;
+                        count - 1
+                    }
+                };
+                if let Some(key) = key {
+                    bundle_channels.insert(key, c);
+                }
+                channels[ri][k] = Some(c);
+            }
+            channel_count[r] = count;
+        }
+    }
+
+    let rank_sep = graph.config.rank_spacing() / scale as f64;
+    let gap: Vec<f64> = channel_count
+        .iter()
+        .map(|&n| rank_sep.max((n + 1) as f64 * CHANNEL_SEP))
+        .collect();
+    let mut rank_height = vec![0.0f64; rank_count];
+    for node in &nodes {
+        rank_height[node.rank] = rank_height[node.rank].max(node.height);
+    }
+    let mut rank_top = vec![0.0f64; rank_count];
+    let mut y = 0.0;
+    for r in 0..rank_count {
+        y += extra_before[r];
+        rank_top[r] = y;
+        y += rank_height[r] + extra_after[r] + gap[r];
+    }
+    // Channels are centred in the gap below their rank
+    let channel_y = |r: usize, c: usize| {
+        let middle = rank_top[r] + rank_height[r] + extra_after[r] + gap[r] / 2.0;
+        middle + (c as f64 - (channel_count[r] as f64 - 1.0) / 2.0) * CHANNEL_SEP
+    };
+    let center = |v: usize| {
+        let node = &nodes[v];
+        Point {
+            x: node.x,
+            y: rank_top[node.rank] + rank_height[node.rank] / 2.0,
+        }
+    };
+
+    // Back from layout space to the graph's direction
+    let transform = |p: Point| match graph.direction {
+        Direction::TD | Direction::TB => p,
+        Direction::BT => Point { x: p.x, y: -p.y },
+        Direction::LR => Point { x: p.y, y: p.x },
+        Direction::RL => Point { x: -p.y, y: p.x },
+    };
+
+    let centers: Vec<Point> = (0..ids.len()).map(|v| transform(center(v))).collect();
+    let mut positioned_nodes: Vec<PositionedNode> = ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let node = &graph.nodes[*id];
+            let (w, h) = sizes[i];
+            let style = graph.node_style(id);
+            PositionedNode {
+                id: id.to_string(),
+                label: node.label.clone(),
+                shape: svg_shape(node.shape),
+                x: centers[i].x - w / 2.0,
+                y: centers[i].y - h / 2.0,
+                width: w,
+                height: h,
+                inline_style: (!style.is_empty()).then_some(style),
+                link: graph.node_links.get(*id).map(|l| PositionedLink {
+                    url: l.url.clone(),
+                    tooltip: l.tooltip.clone(),
+                    target: l.target.clone(),
+                }),
+            }
+        })
+        .collect();
+
+    let mut positioned_edges: Vec<PositionedEdge> = Vec::new();
+    for (ri, route) in routes.iter().enumerate() {
+        let edge = &graph.edges[route.edge_idx];
+        if orthogonal {
+            let (upper, lower) = (route.chain[0], route.chain[route.chain.len() - 1]);
+            let (x0, x1) = (ends[ri][0].0, ends[ri][ends[ri].len() - 1].1);
+            // The ends start on the bounding boxes and are clipped to the
+            // outlines below
+            let half_height = |v: usize| to_layout(sizes[v]).1 / 2.0;
+            let mut points = vec![Point {
+                x: x0,
+                y: center(upper).y + half_height(upper),
+            }];
+            for (k, &(from, to)) in ends[ri].iter().enumerate() {
+                if let Some(c) = channels[ri][k] {
+                    let y = channel_y(nodes[route.chain[k]].rank, c);
+                    points.push(Point { x: from, y });
+                    points.push(Point { x: to, y });
+                }
+            }
+            points.push(Point {
+                x: x1,
+                y: center(lower).y - half_height(lower),
+            });
+            let mut points: Vec<Point> = points.into_iter().map(transform).collect();
+            if route.reversed {
+                points.reverse();
+            }
+            let (source, target) = if route.reversed {
+                (lower, upper)
+            } else {
+                (upper, lower)
+            };
+            if let Some(port) = edge.start_port {
+                let anchor = port_anchor(&positioned_nodes[source], port);
+                points[0] = elbow(anchor, points[1], port);
+                points.insert(0, anchor);
+            }
+            if let Some(port) = edge.end_port {
+                let anchor = port_anchor(&positioned_nodes[target], port);
+                let last = points.len() - 1;
+                points[last] = elbow(anchor, points[last - 1], port);
+                points.push(anchor);
+            }
+            positioned_edges.push(positioned_edge(
+                graph,
+                route.edge_idx,
+                points,
+                route.label_node.map(|v| transform(center(v))),
+            ));
+            continue;
+        }
+        let mut chain = route.chain.clone();
+        if route.reversed {
+            chain.reverse();
+        }
+        let (source, target) = (chain[0], chain[chain.len() - 1]);
+        let inner: Vec<Point> = chain[1..chain.len() - 1]
+            .iter()
+            .map(|&v| transform(center(v)))
+            .collect();
+
+        let first_toward = inner.first().copied().unwrap_or(centers[target]);
+        let last_toward = inner.last().copied().unwrap_or(centers[source]);
+        let start = match edge.start_port {
+            Some(port) => port_anchor(&positioned_nodes[source], port),
+            None => boundary_point(&positioned_nodes[source], first_toward),
+        };
+        let end = match edge.end_port {
+            Some(port) => port_anchor(&positioned_nodes[target], port),
+            None => boundary_point(&positioned_nodes[target], last_toward),
+        };
+
+        let mut points = vec![start];
+        points.extend(inner);
+        points.push(end);
+        positioned_edges.push(positioned_edge(
+            graph,
+            route.edge_idx,
+            points,
+            route.label_node.map(|v| transform(center(v))),
+        ));
+    }
+
+    for &(edge_idx, v) in &self_loops {
+        let node = &positioned_nodes[v];
+        let (points, label_at) = if horizontal {
+            let bottom = node.y + node.height;
+            let (x1, x2) = (node.x + node.width / 4.0, node.x + node.width * 3.0 / 4.0);
+            (
+                vec![
+                    Point { x: x1, y: bottom },
+                    Point {
+                        x: x1,
+                        y: bottom + SELF_LOOP,
+                    },
+                    Point {
+                        x: x2,
+                        y: bottom + SELF_LOOP,
+                    },
+                    Point { x: x2, y: bottom },
+                ],
+                Point {
+                    x: node.x + node.width / 2.0,
+                    y: bottom + SELF_LOOP,
+                },
+            )
+        } else {
+            let right = node.x + node.width;
+            let (y1, y2) = (node.y + node.height / 4.0, node.y + node.height * 3.0 / 4.0);
+            (
+                vec![
+                    Point { x: right, y: y1 },
+                    Point {
+                        x: right + SELF_LOOP,
+                        y: y1,
+                    },
+                    Point {
+                        x: right + SELF_LOOP,
+                        y: y2,
+                    },
+                    Point { x: right, y: y2 },
+                ],
+                Point {
+                    x: right + SELF_LOOP,
+                    y: node.y + node.height / 2.0,
+                },
+            )
+        };
+        positioned_edges.push(positioned_edge(graph, edge_idx, points, Some(label_at)));
+    }
+
+    // Edges end where they meet a node's outline rather than its bounding box:
+    // each end slides along its last segment until it touches the shape
+    for edge in &mut positioned_edges {
+        let n = edge.points.len();
+        if n < 2 {
+            continue;
+        }
+        if let Some(&v) = index.get(edge.source.as_str()) {
+            edge.points[0] = clip_to_outline(&positioned_nodes[v], edge.points[1], edge.points[0]);
+        }
+        if let Some(&v) = index.get(edge.target.as_str()) {
+            edge.points[n - 1] =
+                clip_to_outline(&positioned_nodes[v], edge.points[n - 2], edge.points[n - 1]);
+        }
+    }
+
+    // Subgraph boxes
+    let mut boxes: Vec<Option<(Point, Point)>> = vec![None; clusters.len()];
+    for (c, cluster) in clusters.iter().enumerate() {
+        if cluster.left.is_empty() {
+            continue;
+        }
+        let x0 = cluster
+            .left
+            .iter()
+            .map(|&v| nodes[v].x - nodes[v].width / 2.0)
+            .fold(f64::INFINITY, f64::min);
+        let x1 = cluster
+            .right
+            .iter()
+            .map(|&v| nodes[v].x + nodes[v].width / 2.0)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let y0 = rank_top[cluster.min_rank] - stack_before[c] as f64 * unit_before;
+        let y1 = rank_top[cluster.max_rank]
+            + rank_height[cluster.max_rank]
+            + stack_after[c] as f64 * unit_after;
+        let (a, b) = (
+            transform(Point { x: x0, y: y0 }),
+            transform(Point { x: x1, y: y1 }),
+        );
+        boxes[c] = Some((
+            Point {
+                x: a.x.min(b.x),
+                y: a.y.min(b.y),
+            },
+            Point {
+                x: a.x.max(b.x),
+                y: a.y.max(b.y),
+            },
+        ));
+    }
+
+    // Shift everything so the drawing starts at the margin
+    let mut min = Point {
+        x: f64::INFINITY,
+        y: f64::INFINITY,
+    };
+    let mut max = Point {
+        x: f64::NEG_INFINITY,
+        y: f64::NEG_INFINITY,
+    };
+    let mut extend = |p: Point| {
+        min.x = min.x.min(p.x);
+        min.y = min.y.min(p.y);
+        max.x = max.x.max(p.x);
+        max.y = max.y.max(p.y);
+    };
+    for node in &positioned_nodes {
+        extend(Point {
+            x: node.x,
+            y: node.y,
+        });
+        extend(Point {
+            x: node.x + node.width,
+            y: node.y + node.height,
+        });
+    }
+    for edge in &positioned_edges {
+        edge.points.iter().copied().for_each(&mut extend);
+        if let (Some(label), Some(at)) = (&edge.label, edge.label_position) {
+            let (w, h) = label_size(label);
+            extend(Point {
+                x: at.x - w / 2.0,
+                y: at.y - h / 2.0,
+            });
+            extend(Point {
+                x: at.x + w / 2.0,
+                y: at.y + h / 2.0,
+            });
+        }
+    }
+    for (a, b) in boxes.iter().flatten() {
+        extend(*a);
+        extend(*b);
+    }
+    if positioned_nodes.is_empty() {
+        min = Point { x: 0.0, y: 0.0 };
+        max = min;
+    }
+
+    let dx = MARGIN - min.x;
+    let dy = MARGIN - min.y;
+    let shift = |p: &mut Point| {
+        p.x += dx;
+        p.y += dy;
+    };
+    for node in &mut positioned_nodes {
+        node.x += dx;
+        node.y += dy;
+    }
+    for edge in &mut positioned_edges {
+        edge.points.iter_mut().for_each(shift);
+        edge.label_position.iter_mut().for_each(shift);
+    }
+    for (a, b) in boxes.iter_mut().flatten() {
+        shift(a);
+        shift(b);
+    }
+
+    let groups = graph
+        .subgraphs
+        .iter()
+        .map(|sg| positioned_group(sg, &cluster_ids, &boxes))
+        .collect();
+
+    PositionedGraph {
+        width: max.x - min.x + 2.0 * MARGIN,
+        height: max.y - min.y + 2.0 * MARGIN,
+        nodes: positioned_nodes,
+        edges: positioned_edges,
+        groups,
+    }
+}
+
+/// Render a flowchart to SVG with the layered layout, routing edges as
+/// `routing` says and drawing them with `curve`.
+pub fn render_layered_svg(
+    parsed: &MermaidGraph,
+    colors: &DiagramColors,
+    font: &str,
+    transparent: bool,
+    curve: CurveStyle,
+    routing: EdgeRouting,
+) -> String {
+    render_layered_document(parsed, colors, font, transparent, curve, routing)
+        .map(|document| document.to_string())
+        .unwrap_or_default()
+}
+
+/// Render a flowchart to an SVG document with the layered layout, or `None`
+/// for a flowchart without nodes.
+pub fn render_layered_document(
+    parsed: &MermaidGraph,
+    colors: &DiagramColors,
+    font: &str,
+    transparent: bool,
+    curve: CurveStyle,
+    routing: EdgeRouting,
+) -> Option<SvgDocument> {
+    if parsed.nodes.is_empty() {
+        return None;
+    }
+    let layout = layout_flowchart_with_routing(parsed, routing);
+    Some(render_document_with_curve(
+        &layout,
+        colors,
+        font,
+        transparent,
+        curve,
+    ))
+}
+
+/// Rendered size of a node, by shape
+fn node_size(node: &MermaidNode) -> (f64, f64) {
+    // Wrapped labels are as wide as their widest line, and a line taller each
+    let text = node
+        .label
+        .lines()
+        .map(|line| estimate_text_width(line, FontSizes::NODE_LABEL, FontWeights::NODE_LABEL))
+        .fold(0.0, f64::max);
+    let extra = (node.label.lines().count().max(1) - 1) as f64 * NODE_LINE_HEIGHT;
+    let (w, h) = (text + 32.0, 36.0 + extra);
+    match node.shape {
+        MermaidShape::Diamond => {
+            // Tall enough for the label's corners to stay inside the outline
+            let w = text + 48.0;
+            (w, (14.0 * w / 48.0 + 4.0).max(40.0 + 2.0 * extra))
+        }
+        MermaidShape::Circle => {
+            let d = (text + 24.0).max(40.0 + extra);
+            (d, d)
+        }
+        MermaidShape::DoubleCircle => {
+            let d = (text + 32.0).max(48.0 + extra);
+            (d, d)
+        }
+        MermaidShape::Hexagon => (text + 48.0, h),
+        MermaidShape::Subroutine => (w + 16.0, h),
+        MermaidShape::Cylinder => (w, h + 12.0),
+        MermaidShape::Asymmetric | MermaidShape::Trapezoid | MermaidShape::TrapezoidAlt => {
+            (w + 24.0, h)
+        }
+        MermaidShape::StateStart | MermaidShape::StateEnd => (24.0, 24.0),
+        _ => (w, h),
+    }
+}
+
+/// `sizes` of the nodes `ids`, widened to the configured node widths. Round
+/// shapes keep their size.
+fn fit_node_widths(ids: &[&str], graph: &MermaidGraph, sizes: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    let node_width = node_width();
+    let is_round = |id: &str| {
+        matches!(
+            graph.nodes[id].shape,
+            MermaidShape::Circle
+                | MermaidShape::DoubleCircle
+                | MermaidShape::StateStart
+                | MermaidShape::StateEnd
+        )
+    };
+    let widest = ids
+        .iter()
+        .zip(&sizes)
+        .filter(|(id, _)| !is_round(id))
+        .map(|(_, &(w, _))| w)
+        .fold(0.0, f64::max);
+    ids.iter()
+        .zip(sizes)
+        .map(|(id, (w, h))| match is_round(id) {
+            true => (w, h),
+            false => (node_width.fit(w, widest), h),
+        })
+        .collect()
+}
+
+/// Size of the background pill the renderer draws behind an edge label
+fn label_size(label: &str) -> (f64, f64) {
+    let text = estimate_text_width(label, FontSizes::EDGE_LABEL, FontWeights::EDGE_LABEL);
+    (text + 16.0, FontSizes::EDGE_LABEL + 16.0)
+}
+
+pub(super) fn svg_shape(shape: MermaidShape) -> NodeShape {
+    match shape {
+        MermaidShape::Rectangle => NodeShape::Rectangle,
+        MermaidShape::Rounded => NodeShape::Rounded,
+        MermaidShape::Diamond => NodeShape::Diamond,
+        MermaidShape::Stadium => NodeShape::Stadium,
+        MermaidShape::Circle => NodeShape::Circle,
+        MermaidShape::Subroutine => NodeShape::Subroutine,
+        MermaidShape::DoubleCircle => NodeShape::Doublecircle,
+        MermaidShape::Hexagon => NodeShape::Hexagon,
+        MermaidShape::Cylinder => NodeShape::Cylinder,
+        MermaidShape::Asymmetric => NodeShape::Asymmetric,
+        MermaidShape::Trapezoid => NodeShape::Trapezoid,
+        MermaidShape::TrapezoidAlt => NodeShape::TrapezoidAlt,
+        MermaidShape::StateStart => NodeShape::StateStart,
+        MermaidShape::StateEnd => NodeShape::StateEnd,
+    }
+}
+
+pub(super) fn positioned_edge(
+    graph: &MermaidGraph,
+    edge_idx: usize,
+    points: Vec<Point>,
+    label_position: Option<Point>,
+) -> PositionedEdge {
+    let edge = &graph.edges[edge_idx];
+    PositionedEdge {
+        source: edge.source.clone(),
+        target: edge.target.clone(),
+        label: edge.label.clone(),
+        style: match edge.style {
+            MermaidEdgeStyle::Solid => EdgeStyle::Solid,
+            MermaidEdgeStyle::Dotted => EdgeStyle::Dotted,
+            MermaidEdgeStyle::Thick => EdgeStyle::Thick,
+        },
+        has_arrow_start: edge.start_marker == EdgeMarker::Arrow,
+        has_arrow_end: edge.end_marker == EdgeMarker::Arrow,
+        points,
+        label_position: edge.label.as_ref().and(label_position),
+        start_label: edge.start_label.clone(),
+        end_label: edge.end_label.clone(),
+        inline_style: Some(resolve_edge_style(graph, edge.class.as_deref()))
+            .filter(|style| !style.is_empty()),
+    }
+}
+
+/// Midpoint of the side of a node that a port designates
+fn port_anchor(node: &PositionedNode, port: Port) -> Point {
+    let (cx, cy) = (node.x + node.width / 2.0, node.y + node.height / 2.0);
+    match port {
+        Port::North => Point { x: cx, y: node.y },
+        Port::South => Point {
+            x: cx,
+            y: node.y + node.height,
+        },
+        Port::East => Point {
+            x: node.x + node.width,
+            y: cy,
+        },
+        Port::West => Point { x: node.x, y: cy },
+    }
+}
+
+/// Corner joining a port anchor to the next point of an orthogonal route,
+/// leaving the anchor straight out of the port's side
+fn elbow(anchor: Point, next: Point, port: Port) -> Point {
+    match port {
+        Port::North | Port::South => Point {
+            x: anchor.x,
+            y: next.y,
+        },
+        Port::East | Port::West => Point {
+            x: next.x,
+            y: anchor.y,
+        },
+    }
+}
+
+/// Spread the ends of the segments meeting a real node along its side, in the
+/// order of the nodes at their other ends, so no two edges share a stub
+fn spread_ports(
+    nodes: &[LNode],
+    real_count: usize,
+    routes: &[Route],
+    ends: &mut [Vec<(f64, f64)>],
+) {
+    // (route, segment, leaves the node, x of the other end)
+    let mut meeting: Vec<Vec<(usize, usize, bool, f64)>> = vec![Vec::new(); real_count];
+    for (ri, route) in routes.iter().enumerate() {
+        for (k, pair) in route.chain.windows(2).enumerate() {
+            if pair[0] < real_count {
+                meeting[pair[0]].push((ri, k, true, nodes[pair[1]].x));
+            }
+            if pair[1] < real_count {
+                meeting[pair[1]].push((ri, k, false, nodes[pair[0]].x));
+            }
+        }
+    }
+    for (v, meeting) in meeting.iter().enumerate() {
+        for leaves in [true, false] {
+            let mut side: Vec<_> = meeting.iter().filter(|m| m.2 == leaves).collect();
+            if side.len() < 2 {
+                continue;
+            }
+            side.sort_by(|a, b| a.3.total_cmp(&b.3));
+            let half = (side.len() - 1) as f64 / 2.0;
+            let step = (nodes[v].width / 2.0 / (side.len() - 1) as f64).min(EDGE_SEP);
+            for (i, &&(ri, k, _, _)) in side.iter().enumerate() {
+                let x = nodes[v].x + (i as f64 - half) * step;
+                if leaves {
+                    ends[ri][k].0 = x;
+                } else {
+                    ends[ri][k].1 = x;
+                }
+            }
+        }
+    }
+}
+
+/// Top-to-bottom order of the channels of segments running from `.0` to `.1`
+/// in the same gap.
+///
+/// A segment drops from its start into its channel, runs along it, then drops
+/// to its end. Placing `a` above `b` makes `a` cross `b`'s drop from its start
+/// and `b` cross `a`'s drop to its end whenever those lie within the other's
+/// span, so each segment is ranked by how much it gains from being higher.
+fn order_channels(spans: &[(f64, f64)]) -> Vec<usize> {
+    let within = |x: f64, (a, b): (f64, f64)| x > a.min(b) && x < a.max(b);
+    let cost = |a: (f64, f64), b: (f64, f64)| within(b.0, a) as i32 + within(a.1, b) as i32;
+    let score: Vec<i32> = spans
+        .iter()
+        .map(|&a| spans.iter().map(|&b| cost(b, a) - cost(a, b)).sum())
+        .collect();
+    let mut order: Vec<usize> = (0..spans.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(score[i]));
+    order
+}
+
+/// Where the line from a node's centre towards `toward` leaves its outline
+fn boundary_point(node: &PositionedNode, toward: Point) -> Point {
+    let centre = Point {
+        x: node.x + node.width / 2.0,
+        y: node.y + node.height / 2.0,
+    };
+    ray_hit(&node_outline(node), centre, toward).unwrap_or(centre)
+}
+
+/// Where the line from `outside` through `end` first meets a node's outline,
+/// or `end` when it misses the node
+fn clip_to_outline(node: &PositionedNode, outside: Point, end: Point) -> Point {
+    ray_hit(&node_outline(node), outside, end).unwrap_or(end)
+}
+
+/// First point of a closed polygon hit by the ray from `origin` through
+/// `toward`
+fn ray_hit(polygon: &[Point], origin: Point, toward: Point) -> Option<Point> {
+    let (dx, dy) = (toward.x - origin.x, toward.y - origin.y);
+    if dx == 0.0 && dy == 0.0 {
+        return None;
+    }
+    let cross = |ax: f64, ay: f64, bx: f64, by: f64| ax * by - ay * bx;
+    let mut best: Option<f64> = None;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let (ex, ey) = (b.x - a.x, b.y - a.y);
+        let denom = cross(dx, dy, ex, ey);
+        if denom.abs() < 1e-12 {
+            continue;
+        }
+        let (ox, oy) = (a.x - origin.x, a.y - origin.y);
+        // Distance along the ray (in units of origin→toward) and the edge
+        let t = cross(ox, oy, ex, ey) / denom;
+        let s = cross(ox, oy, dx, dy) / denom;
+        if t >= -1e-9 && (-1e-9..=1.0 + 1e-9).contains(&s) && best.is_none_or(|b| t < b) {
+            best = Some(t);
+        }
+    }
+    best.map(|t| Point {
+        x: origin.x + dx * t,
+        y: origin.y + dy * t,
+    })
+}
+
+/// Flatten a subgraph tree into `clusters`, recording which cluster owns each
+/// node. Children claim their nodes before their parents do.
+fn flatten_subgraph<'a>(
+    sg: &'a MermaidSubgraph,
+    parent: Option<usize>,
+    index: &HashMap<&str, usize>,
+    clusters: &mut Vec<Cluster>,
+    cluster_ids: &mut Vec<&'a MermaidSubgraph>,
+    owner: &mut IndexMap<usize, usize>,
+) {
+    let c = clusters.len();
+    clusters.push(Cluster {
+        parent,
+        children: Vec::new(),
+        min_rank: 0,
+        max_rank: 0,
+        left: Vec::new(),
+        right: Vec::new(),
+    });
+    cluster_ids.push(sg);
+    if let Some(p) = parent {
+        clusters[p].children.push(c);
+    }
+    for child in &sg.children {
+        flatten_subgraph(child, Some(c), index, clusters, cluster_ids, owner);
+    }
+    for id in &sg.node_ids {
+        if let Some(&n) = index.get(id.as_str()) {
+            owner.entry(n).or_insert(c);
+        }
+    }
+}
+
+fn positioned_group(
+    sg: &MermaidSubgraph,
+    cluster_ids: &[&MermaidSubgraph],
+    boxes: &[Option<(Point, Point)>],
+) -> PositionedGroup {
+    let c = cluster_ids
+        .iter()
+        .position(|s| std::ptr::eq(*s, sg))
+        .expect("subgraph was flattened");
+    let bounds = boxes[c];
+    PositionedGroup {
+        id: sg.id.clone(),
+        label: sg.label.clone(),
+        x: bounds.map(|(a, _)| a.x),
+        y: bounds.map(|(a, _)| a.y),
+        width: bounds.map(|(a, b)| b.x - a.x),
+        height: bounds.map(|(a, b)| b.y - a.y),
+        children: sg
+            .children
+            .iter()
+            .map(|child| positioned_group(child, cluster_ids, boxes))
+            .collect(),
+    }
+}
+
+/// How many subgraph borders are stacked on the first (or last) rank of each
+/// subgraph: one for itself plus those of nested subgraphs sharing that rank
+fn cluster_stacks(clusters: &[Cluster], rank: impl Fn(&Cluster) -> usize) -> Vec<usize> {
+    fn stack(c: usize, clusters: &[Cluster], rank: &dyn Fn(&Cluster) -> usize) -> usize {
+        1 + clusters[c]
+            .children
+            .iter()
+            .filter(|&&child| {
+                !clusters[child].left.is_empty() && rank(&clusters[child]) == rank(&clusters[c])
+            })
+            .map(|&child| stack(child, clusters, rank))
+            .max()
+            .unwrap_or(0)
+    }
+    (0..clusters.len())
+        .map(|c| stack(c, clusters, &rank))
+        .collect()
+}
+
+/// Longest-path ranking of an acyclic graph given as `(from, to, min_length)`,
+/// with sources then moved down as far as their successors allow
+fn assign_ranks(n: usize, edges: &[(usize, usize, usize)]) -> Vec<usize> {
+    let mut in_degree = vec![0usize; n];
+    for &(_, b, _) in edges {
+        in_degree[b] += 1;
+    }
+    let mut remaining = in_degree.clone();
+    let mut order: Vec<usize> = (0..n).filter(|&v| remaining[v] == 0).collect();
+    let mut i = 0;
+    while i < order.len() {
+        let v = order[i];
+        for &(a, b, _) in edges {
+            if a == v {
+                remaining[b] -= 1;
+                if remaining[b] == 0 {
+                    order.push(b);
+                }
+            }
+        }
+        i += 1;
+    }
+
+    let mut rank = vec![0i64; n];
+    for &v in &order {
+        for &(a, b, len) in edges {
+            if a == v {
+                rank[b] = rank[b].max(rank[v] + len as i64);
+            }
+        }
+    }
+    for &v in order.iter().rev() {
+        if in_degree[v] > 0 {
+            continue;
+        }
+        let tightest = edges
+            .iter()
+            .filter(|&&(a, _, _)| a == v)
+            .map(|&(_, b, len)| rank[b] - len as i64)
+            .min();
+        if let Some(r) = tightest {
+            rank[v] = r;
+        }
+    }
+
+    let lowest = rank.iter().copied().min().unwrap_or(0);
+    rank.into_iter().map(|r| (r - lowest) as usize).collect()
+}
+
+/// Order every rank with alternating down/up barycenter sweeps, keeping the
+/// ordering with the fewest crossings
+fn order_layers(
+    nodes: &[LNode],
+    layers: &mut [Vec<usize>],
+    preds: &[Vec<usize>],
+    succs: &[Vec<usize>],
+) {
+    let mut pos = vec![0.0f64; nodes.len()];
+    let record = |layer: &[usize], pos: &mut Vec<f64>| {
+        for (i, &v) in layer.iter().enumerate() {
+            pos[v] = i as f64;
+        }
+    };
+    for layer in layers.iter_mut() {
+        record(layer, &mut pos);
+        *layer = arrange(layer, 0, &pos, nodes);
+        record(layer, &mut pos);
+    }
+
+    let mut best = layers.to_vec();
+    let mut best_crossings = count_crossings(layers, succs);
+    for sweep in 0..ORDER_SWEEPS {
+        if best_crossings == 0 {
+            break;
+        }
+        let downward = sweep % 2 == 0;
+        let ranks: Vec<usize> = if downward {
+            (1..layers.len()).collect()
+        } else {
+            (0..layers.len().saturating_sub(1)).rev().collect()
+        };
+        for r in ranks {
+            let mut key = pos.clone();
+            for &v in &layers[r] {
+                let neighbours = if downward { &preds[v] } else { &succs[v] };
+                if !neighbours.is_empty() {
+                    key[v] =
+                        neighbours.iter().map(|&w| pos[w]).sum::<f64>() / neighbours.len() as f64;
+                }
+            }
+            layers[r] = arrange(&layers[r], 0, &key, nodes);
+            record(&layers[r], &mut pos);
+        }
+
+        let crossings = count_crossings(layers, succs);
+        if crossings < best_crossings {
+            best_crossings = crossings;
+            best = layers.to_vec();
+        }
+    }
+    layers.clone_from_slice(&best);
+}
+
+/// Sort a rank by `key`, keeping the members of each subgraph together and
+/// its border nodes at either end. `depth` is the nesting level of `items`.
+fn arrange(items: &[usize], depth: usize, key: &[f64], nodes: &[LNode]) -> Vec<usize> {
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    // (key, first position, members)
+    let mut entries: Vec<(f64, usize, Vec<usize>)> = Vec::new();
+    let mut blocks: Vec<(usize, usize, Vec<usize>)> = Vec::new();
+
+    for (i, &v) in items.iter().enumerate() {
+        match (nodes[v].clusters.get(depth), nodes[v].kind) {
+            (Some(&c), _) => match blocks.iter_mut().find(|b| b.0 == c) {
+                Some(block) => block.2.push(v),
+                None => blocks.push((c, i, vec![v])),
+            },
+            (None, Kind::Border { left: true }) => left.push(v),
+            (None, Kind::Border { left: false }) => right.push(v),
+            (None, _) => entries.push((key[v], i, vec![v])),
+        }
+    }
+    for (_, first, members) in blocks {
+        let sorted = arrange(&members, depth + 1, key, nodes);
+        let inner: Vec<f64> = sorted
+            .iter()
+            .filter(|&&v| !matches!(nodes[v].kind, Kind::Border { .. }))
+            .map(|&v| key[v])
+            .collect();
+        let block_key = if inner.is_empty() {
+            sorted.iter().map(|&v| key[v]).sum::<f64>() / sorted.len() as f64
+        } else {
+            inner.iter().sum::<f64>() / inner.len() as f64
+        };
+        entries.push((block_key, first, sorted));
+    }
+    entries.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+    left.into_iter()
+        .chain(entries.into_iter().flat_map(|(_, _, members)| members))
+        .chain(right)
+        .collect()
+}
+
+/// Number of segment crossings between all pairs of adjacent ranks
+fn count_crossings(layers: &[Vec<usize>], succs: &[Vec<usize>]) -> usize {
+    let mut pos: HashMap<usize, usize> = HashMap::new();
+    for layer in layers {
+        for (i, &v) in layer.iter().enumerate() {
+            pos.insert(v, i);
+        }
+    }
+    let mut crossings = 0;
+    for layer in layers {
+        let mut segments: Vec<(usize, usize)> = Vec::new();
+        for (i, &v) in layer.iter().enumerate() {
+            segments.extend(succs[v].iter().map(|w| (i, pos[w])));
+        }
+        for (k, &(a1, b1)) in segments.iter().enumerate() {
+            for &(a2, b2) in &segments[k + 1..] {
+                if (a1 < a2 && b1 > b2) || (a1 > a2 && b1 < b2) {
+                    crossings += 1;
+                }
+            }
+        }
+    }
+    crossings
+}
+
+/// Brandes-Köpf positions along the ranks: each of the four alignments
+/// (towards upper/lower neighbours, packed left/right) is compacted, all are
+/// aligned to the narrowest, and every node takes the average of its two
+/// median candidates. Neighbouring real nodes are kept `node_sep` apart.
+fn assign_x(
+    nodes: &[LNode],
+    layers: &[Vec<usize>],
+    preds: &[Vec<usize>],
+    succs: &[Vec<usize>],
+    node_sep: f64,
+) -> Vec<f64> {
+    let conflicts = type1_conflicts(nodes, layers, preds);
+
+    let mut candidates: Vec<(bool, Vec<f64>)> = Vec::new();
+    for upward in [true, false] {
+        for rightward in [false, true] {
+            let mut adjusted: Vec<Vec<usize>> = if upward {
+                layers.to_vec()
+            } else {
+                layers.iter().rev().cloned().collect()
+            };
+            if rightward {
+                adjusted.iter_mut().for_each(|l| l.reverse());
+            }
+            let neighbours = if upward { preds } else { succs };
+            let root = vertical_alignment(nodes.len(), &adjusted, &conflicts, neighbours);
+            let mut xs = horizontal_compaction(nodes, &adjusted, &root, node_sep);
+            if rightward {
+                xs.iter_mut().for_each(|x| *x = -*x);
+            }
+            candidates.push((rightward, xs));
+        }
+    }
+
+    let extent = |xs: &[f64]| {
+        let lo = (0..nodes.len())
+            .map(|v| xs[v] - nodes[v].width / 2.0)
+            .fold(f64::INFINITY, f64::min);
+        let hi = (0..nodes.len())
+            .map(|v| xs[v] + nodes[v].width / 2.0)
+            .fold(f64::NEG_INFINITY, f64::max);
+        (lo, hi)
+    };
+    let narrowest = candidates
+        .iter()
+        .map(|(_, xs)| extent(xs))
+        .min_by(|a, b| (a.1 - a.0).total_cmp(&(b.1 - b.0)))
+        .unwrap_or((0.0, 0.0));
+    for (rightward, xs) in &mut candidates {
+        let (lo, hi) = extent(xs);
+        let delta = if *rightward {
+            narrowest.1 - hi
+        } else {
+            narrowest.0 - lo
+        };
+        xs.iter_mut().for_each(|x| *x += delta);
+    }
+
+    (0..nodes.len())
+        .map(|v| {
+            let mut values: Vec<f64> = candidates.iter().map(|(_, xs)| xs[v]).collect();
+            values.sort_by(f64::total_cmp);
+            (values[1] + values[2]) / 2.0
+        })
+        .collect()
+}
+
+fn pair(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/// Segments that cross an inner segment (one between two virtual nodes).
+/// Alignment skips them so long edges stay straight.
+fn type1_conflicts(
+    nodes: &[LNode],
+    layers: &[Vec<usize>],
+    preds: &[Vec<usize>],
+) -> HashSet<(usize, usize)> {
+    let mut pos = vec![0usize; nodes.len()];
+    for layer in layers {
+        for (i, &v) in layer.iter().enumerate() {
+            pos[v] = i;
+        }
+    }
+
+    let mut conflicts = HashSet::new();
+    for r in 1..layers.len() {
+        let (prev, layer) = (&layers[r - 1], &layers[r]);
+        let mut k0 = 0;
+        let mut scan = 0;
+        for (l, &v) in layer.iter().enumerate() {
+            let inner = if nodes[v].is_virtual() {
+                preds[v].iter().copied().find(|&u| nodes[u].is_virtual())
+            } else {
+                None
+            };
+            if inner.is_none() && l + 1 != layer.len() {
+                continue;
+            }
+            let k1 = inner.map(|u| pos[u]).unwrap_or(prev.len());
+            for &w in &layer[scan..=l] {
+                for &u in &preds[w] {
+                    let crosses = pos[u] < k0 || k1 < pos[u];
+                    if crosses && !(nodes[u].is_virtual() && nodes[w].is_virtual()) {
+                        conflicts.insert(pair(u, w));
+                    }
+                }
+            }
+            scan = l + 1;
+            k0 = k1;
+        }
+    }
+    conflicts
+}
+
+/// Group nodes into blocks that share a coordinate, each aligned with the
+/// median of its neighbours on the previous rank. Returns each node's block
+/// root.
+fn vertical_alignment(
+    n: usize,
+    layers: &[Vec<usize>],
+    conflicts: &HashSet<(usize, usize)>,
+    neighbours: &[Vec<usize>],
+) -> Vec<usize> {
+    let mut root: Vec<usize> = (0..n).collect();
+    let mut align: Vec<usize> = (0..n).collect();
+    let mut pos = vec![0usize; n];
+    for layer in layers {
+        for (i, &v) in layer.iter().enumerate() {
+            pos[v] = i;
+        }
+    }
+
+    for layer in layers {
+        let mut prev: Option<usize> = None;
+        for &v in layer {
+            let mut ws = neighbours[v].clone();
+            if ws.is_empty() {
+                continue;
+            }
+            ws.sort_by_key(|&w| pos[w]);
+            let mid = (ws.len() - 1) as f64 / 2.0;
+            for &w in &ws[mid.floor() as usize..=mid.ceil() as usize] {
+                if align[v] == v
+                    && prev.is_none_or(|p| p < pos[w])
+                    && !conflicts.contains(&pair(v, w))
+                {
+                    align[w] = v;
+                    root[v] = root[w];
+                    align[v] = root[v];
+                    prev = Some(pos[w]);
+                }
+            }
+        }
+    }
+    root
+}
+
+/// Place each block as far left as its left neighbours allow, then pull it
+/// right towards its right neighbours where there is slack
+fn horizontal_compaction(
+    nodes: &[LNode],
+    layers: &[Vec<usize>],
+    root: &[usize],
+    node_sep: f64,
+) -> Vec<f64> {
+    let spacing = |v: usize| {
+        if nodes[v].is_virtual() {
+            EDGE_SEP
+        } else {
+            node_sep
+        }
+    };
+    let separation = |u: usize, v: usize| {
+        nodes[u].width / 2.0 + nodes[v].width / 2.0 + (spacing(u) + spacing(v)) / 2.0
+    };
+
+    // Block graph: an edge from each block to the block right of it
+    let mut seps: IndexMap<(usize, usize), f64> = IndexMap::new();
+    for layer in layers {
+        for pair in layer.windows(2) {
+            let (u, v) = (pair[0], pair[1]);
+            let sep = seps.entry((root[u], root[v])).or_insert(0.0);
+            *sep = sep.max(separation(u, v));
+        }
+    }
+    let roots: Vec<usize> = (0..nodes.len()).filter(|&v| root[v] == v).collect();
+    let mut incoming: IndexMap<usize, Vec<(usize, f64)>> = IndexMap::new();
+    let mut outgoing: IndexMap<usize, Vec<(usize, f64)>> = IndexMap::new();
+    for (&(a, b), &sep) in &seps {
+        outgoing.entry(a).or_default().push((b, sep));
+        incoming.entry(b).or_default().push((a, sep));
+    }
+
+    // Topological order of the blocks
+    let mut remaining: HashMap<usize, usize> = roots
+        .iter()
+        .map(|&r| (r, incoming.get(&r).map_or(0, Vec::len)))
+        .collect();
+    let mut order: Vec<usize> = roots
+        .iter()
+        .copied()
+        .filter(|r| remaining[r] == 0)
+        .collect();
+    let mut i = 0;
+    while i < order.len() {
+        for &(next, _) in outgoing.get(&order[i]).into_iter().flatten() {
+            let count = remaining.get_mut(&next).expect("block root");
+            *count -= 1;
+            if *count == 0 {
+                order.push(next);
+            }
+        }
+        i += 1;
+    }
+
+    let mut xs: HashMap<usize, f64> = HashMap::new();
+    for &r in &order {
+        let x = incoming
+            .get(&r)
+            .into_iter()
+            .flatten()
+            .map(|&(p, sep)| xs[&p] + sep)
+            .fold(0.0, f64::max);
+        xs.insert(r, x);
+    }
+    for &r in order.iter().rev() {
+        let limit = outgoing
+            .get(&r)
+            .into_iter()
+            .flatten()
+            .map(|&(s, sep)| xs[&s] - sep)
+            .fold(f64::INFINITY, f64::min);
+        if limit.is_finite() && limit > xs[&r] {
+            xs.insert(r, limit);
+        }
+    }
+
+    (0..nodes.len())
+        .map(|v| xs.get(&root[v]).copied().unwrap_or(0.0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_mermaid;
+    use crate::types::DiagramType;
+
+    fn layout(text: &str) -> PositionedGraph {
+        match parse_mermaid(text).unwrap().diagram {
+            DiagramType::Flowchart(g) => layout_flowchart(&g),
+            _ => panic!("expected flowchart"),
+        }
+    }
+
+    fn node<'a>(graph: &'a PositionedGraph, id: &str) -> &'a PositionedNode {
+        graph.nodes.iter().find(|n| n.id == id).unwrap()
+    }
+
+    fn overlaps(a: &PositionedNode, b: &PositionedNode) -> bool {
+        a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+    }
+
+    #[test]
+    fn test_layered_ranks_follow_edges() {
+        let g = layout("graph TD\n  A --> B\n  B --> C\n  A --> C\n  C --> A");
+        let (a, b, c) = (node(&g, "A"), node(&g, "B"), node(&g, "C"));
+        assert!(a.y + a.height < b.y);
+        assert!(b.y + b.height < c.y);
+        // The long edge A --> C runs through a dummy beside B
+        assert!(g.edges[2].points.len() > 2);
+    }
+
+    #[test]
+    fn test_layered_lr_runs_left_to_right() {
+        let g = layout("graph LR\n  A --> B\n  A --> C");
+        let (a, b, c) = (node(&g, "A"), node(&g, "B"), node(&g, "C"));
+        assert!(a.x + a.width < b.x);
+        assert_eq!(b.x, c.x);
+        assert!(!overlaps(b, c));
+    }
+
+    #[test]
+    fn test_layered_subgraph_keeps_outsiders_out() {
+        let g = layout(
+            "graph TD\n  A --> B\n  A --> X\n  A --> C\n  subgraph S\n    B\n    C\n  end\n  X --> Y",
+        );
+        let group = &g.groups[0];
+        let (gx, gw) = (group.x.unwrap(), group.width.unwrap());
+        for id in ["B", "C"] {
+            let n = node(&g, id);
+            assert!(n.x > gx && n.x + n.width < gx + gw);
+        }
+        let x = node(&g, "X");
+        assert!(x.x + x.width < gx || x.x > gx + gw);
+    }
+
+    #[test]
+    fn test_orthogonal_edges_bend_at_right_angles_in_own_channels() {
+        let text = "graph TD\n  A --> B\n  A --> C\n  A --> D\n  B --> E\n  C --> E\n  D --> E";
+        let g = match parse_mermaid(text).unwrap().diagram {
+            DiagramType::Flowchart(g) => layout_flowchart_with_routing(&g, EdgeRouting::Orthogonal),
+            _ => panic!("expected flowchart"),
+        };
+        let mut channels = Vec::new();
+        for edge in &g.edges {
+            for pair in edge.points.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                assert!(
+                    a.x == b.x || a.y == b.y,
+                    "diagonal segment in {:?}",
+                    edge.points
+                );
+                if a.y == b.y && a.x != b.x {
+                    channels.push(a.y);
+                }
+            }
+        }
+        let count = channels.len();
+        channels.sort_by(f64::total_cmp);
+        channels.dedup();
+        assert_eq!(channels.len(), count);
+    }
+}
@@ -1,29 +1,54 @@
 //! SVG renderer - converts diagrams into SVG strings.
 //!
 //! Supports all diagram types:
-//! - Flowcharts (render_mermaid_to_svg)
+//! - Flowcharts (render_mermaid_to_svg, or render_layered_svg for the layered layout)
 //! - Sequence diagrams (render_sequence_svg)
 //! - Class diagrams (render_class_svg)
 //! - ER diagrams (render_er_svg)
 //! - GitGraph (render_gitgraph_svg)
 //!
-//! Pure string building, no DOM manipulation.
+//! Each renderer also has a `*_document` variant returning an [`SvgDocument`],
+//! which the entry points use to add a title, legend or accessibility
+//! attributes before turning it into a string. Pure string building, no DOM
+//! manipulation; [`svg_fragment`] turns the string into one to embed in a
+//! page next to other diagrams.
 
 mod class_diagram;
+mod document;
 mod er_diagram;
+#[cfg(feature = "font-metrics")]
+mod font_metrics;
+pub(crate) mod fonts;
+mod fragment;
 mod from_ascii;
 mod gitgraph;
+mod layered;
 mod renderer;
 mod sequence;
 pub mod styles;
 mod theme;
 mod types;
 
-pub use class_diagram::render_class_svg;
-pub use er_diagram::render_er_svg;
-pub use from_ascii::render_mermaid_to_svg;
-pub use gitgraph::render_gitgraph_svg;
-pub use renderer::render_svg;
-pub use sequence::render_sequence_svg;
-pub use theme::DiagramColors;
+pub use class_diagram::{render_class_document, render_class_svg};
+pub use document::{SvgDocument, SvgSize};
+pub use er_diagram::{render_er_document, render_er_svg};
+#[cfg(feature = "font-metrics")]
+pub use font_metrics::FontMetricsMeasurer;
+pub use fonts::{FontSource, SYSTEM_FONT_STACK};
+pub use fragment::svg_fragment;
+pub use from_ascii::{
+    layout_grid_flowchart, render_mermaid_to_document_with_curve, render_mermaid_to_svg,
+    render_mermaid_to_svg_with_curve,
+};
+pub use gitgraph::{render_gitgraph_document, render_gitgraph_svg};
+pub use layered::{
+    layout_flowchart, layout_flowchart_with_routing, render_layered_document, render_layered_svg,
+};
+pub use renderer::{render_document_with_curve, render_svg, render_svg_with_curve};
+pub use sequence::{
+    render_sequence_document, render_sequence_document_with_config, render_sequence_svg,
+};
+pub use styles::{HeuristicMeasurer, TextMeasurer};
+pub(crate) use theme::{dark_scheme_block, retheme_document};
+pub use theme::{font_family_stack, DiagramColors};
 pub use types::*;
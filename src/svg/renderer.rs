@@ -3,12 +3,18 @@
 //! Pure string building, no DOM manipulation.
 //! Renders back-to-front: groups → edges → edge labels → nodes → node labels.
 
+use super::document::SvgDocument;
 use super::styles::{
-    estimate_text_width, ArrowHead, FontSizes, FontWeights, StrokeWidths, TEXT_BASELINE_SHIFT,
+    estimate_text_width, label_align, ArrowHead, FontSizes, FontWeights, StrokeWidths,
+    NODE_LINE_HEIGHT, TEXT_BASELINE_SHIFT,
 };
-use super::theme::{build_style_block, svg_open_tag, DiagramColors};
+use super::theme::{build_style_block, svg_document, DiagramColors};
+use crate::types::{CurveStyle, LabelAlign};
+use std::collections::HashMap;
+
 use super::types::{
-    EdgeStyle, NodeShape, Point, PositionedEdge, PositionedGraph, PositionedGroup, PositionedNode,
+    EdgeStyle, NodeShape, Point, PositionedEdge, PositionedGraph, PositionedGroup, PositionedLink,
+    PositionedNode,
 };
 
 /// Render a positioned graph as an SVG string.
@@ -18,13 +24,34 @@ pub fn render_svg(
     font: &str,
     transparent: bool,
 ) -> String {
-    let mut parts: Vec<String> = vec![
-        svg_open_tag(graph.width, graph.height, colors, transparent),
+    render_svg_with_curve(graph, colors, font, transparent, CurveStyle::Linear)
+}
+
+/// Render a positioned graph as an SVG string, drawing edges with `curve`.
+pub fn render_svg_with_curve(
+    graph: &PositionedGraph,
+    colors: &DiagramColors,
+    font: &str,
+    transparent: bool,
+    curve: CurveStyle,
+) -> String {
+    render_document_with_curve(graph, colors, font, transparent, curve).to_string()
+}
+
+/// Render a positioned graph as an SVG document, drawing edges with `curve`.
+pub fn render_document_with_curve(
+    graph: &PositionedGraph,
+    colors: &DiagramColors,
+    font: &str,
+    transparent: bool,
+    curve: CurveStyle,
+) -> SvgDocument {
+    let mut document = svg_document(graph.width, graph.height, colors, transparent);
+    document.head = vec![
         build_style_block(font),
-        "<defs>".to_string(),
-        arrow_marker_defs(),
-        "</defs>".to_string(),
+        format!("<defs>\n{}\n</defs>", arrow_marker_defs(graph)),
     ];
+    let mut parts: Vec<String> = Vec::new();
 
     // 1. Group backgrounds (subgraph rectangles with header bands)
     for group in &graph.groups {
@@ -34,51 +61,93 @@ pub fn render_svg(
         }
     }
 
-    // 2. Edges (polylines — rendered behind nodes)
+    // 2. Edges (polylines or curves — rendered behind nodes)
     for edge in &graph.edges {
-        parts.push(render_edge(edge));
+        parts.push(render_edge(edge, curve));
     }
 
-    // 3. Edge labels (positioned at midpoint of edge)
+    // 3. Edge labels (positioned at midpoint of edge), then end labels
     for edge in &graph.edges {
         if edge.label.is_some() {
             parts.push(render_edge_label(edge));
         }
+        parts.push(render_edge_end_labels(edge));
     }
 
-    // 4. Node shapes
+    // 4. Node shapes (linked nodes carry their label inside the link)
     for node in &graph.nodes {
-        parts.push(render_node_shape(node));
+        match &node.link {
+            Some(link) => parts.push(render_linked_node(node, link)),
+            None => parts.push(render_node_shape(node)),
+        }
     }
 
     // 5. Node labels
-    for node in &graph.nodes {
+    for node in graph.nodes.iter().filter(|n| n.link.is_none()) {
         parts.push(render_node_label(node));
     }
 
-    parts.push("</svg>".to_string());
+    parts.retain(|p| !p.is_empty());
 
-    parts.join("\n")
+    document.body = parts.join("\n");
+    document
 }
 
 // ============================================================================
 // Arrow marker definitions
 // ============================================================================
 
-fn arrow_marker_defs() -> String {
+/// Filled arrowheads, and open ones when a dotted edge has an arrow.
+///
+/// Markers are sized in stroke widths, so the arrowheads of thick edges (and
+/// of edges whose class widens the stroke) grow with the line.
+fn arrow_marker_defs(graph: &PositionedGraph) -> String {
     let w = ArrowHead::WIDTH;
     let h = ArrowHead::HEIGHT;
-    format!(
+    let mut defs = format!(
         r#"  <marker id="arrowhead" markerWidth="{w}" markerHeight="{h}" refX="{w}" refY="{half_h}" orient="auto">
     <polygon points="0 0, {w} {half_h}, 0 {h}" fill="var(--_arrow)" />
   </marker>
-  <marker id="arrowhead-start" markerWidth="{w}" markerHeight="{h}" refX="0" refY="{half_h}" orient="auto-start-reverse">
-    <polygon points="{w} 0, 0 {half_h}, {w} {h}" fill="var(--_arrow)" />
+  <marker id="arrowhead-start" markerWidth="{w}" markerHeight="{h}" refX="{w}" refY="{half_h}" orient="auto-start-reverse">
+    <polygon points="0 0, {w} {half_h}, 0 {h}" fill="var(--_arrow)" />
   </marker>"#,
         w = w,
         h = h,
         half_h = h / 2.0
-    )
+    );
+    let dotted_arrows = graph
+        .edges
+        .iter()
+        .any(|e| e.style == EdgeStyle::Dotted && (e.has_arrow_start || e.has_arrow_end));
+    if dotted_arrows {
+        defs.push('\n');
+        defs.push_str(&open_arrow_markers(w, h));
+    }
+    defs
+}
+
+/// `arrowhead-open` and `arrowhead-open-start`: `w` by `h` chevrons, drawn
+/// inside the marker box so their stroke isn't clipped
+pub(super) fn open_arrow_markers(w: f64, h: f64) -> String {
+    let inset = 0.5;
+    let (tip, half_h) = (w - inset, h / 2.0);
+    ["", "-start"]
+        .iter()
+        .map(|suffix| {
+            let orient = if suffix.is_empty() {
+                "auto"
+            } else {
+                "auto-start-reverse"
+            };
+            format!(
+                r#"  <marker id="arrowhead-open{suffix}" markerWidth="{w}" markerHeight="{h}" refX="{tip}" refY="{half_h}" orient="{orient}">
+    <polyline points="{inset} {inset}, {tip} {half_h}, {inset} {bottom}" fill="none" stroke="var(--_arrow)" stroke-width="1" />
+  </marker>"#,
+                bottom = h - inset,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 // ============================================================================
@@ -130,38 +199,136 @@ fn render_group(group: &PositionedGroup) -> String {
 // Edge rendering
 // ============================================================================
 
-fn render_edge(edge: &PositionedEdge) -> String {
+/// The `stroke`, `stroke-width` and `stroke-dasharray` attributes of an edge
+/// line: the edge's class styles where it sets them, else the defaults
+pub(super) fn edge_stroke_attrs(
+    style: &HashMap<String, String>,
+    width: f64,
+    dash_array: Option<&str>,
+) -> String {
+    let stroke = style.get("stroke").map_or("var(--_line)", String::as_str);
+    let width = style.get("stroke-width").map_or_else(
+        || width.to_string(),
+        |w| w.trim_end_matches("px").to_string(),
+    );
+    let mut attrs = format!(
+        r#" stroke="{}" stroke-width="{}""#,
+        escape_xml(stroke),
+        escape_xml(&width)
+    );
+    if let Some(dash) = style
+        .get("stroke-dasharray")
+        .map(String::as_str)
+        .or(dash_array)
+    {
+        attrs.push_str(&format!(r#" stroke-dasharray="{}""#, escape_xml(dash)));
+    }
+    attrs
+}
+
+fn render_edge(edge: &PositionedEdge, curve: CurveStyle) -> String {
     if edge.points.len() < 2 {
         return String::new();
     }
 
-    let path_data = points_to_polyline_path(&edge.points);
-    let dash_array = if edge.style == EdgeStyle::Dotted {
-        " stroke-dasharray=\"4 4\""
-    } else {
-        ""
-    };
+    let dash_array = (edge.style == EdgeStyle::Dotted).then_some("4 4");
     let stroke_width = if edge.style == EdgeStyle::Thick {
         StrokeWidths::CONNECTOR * 2.0
     } else {
         StrokeWidths::CONNECTOR
     };
+    let stroke = edge_stroke_attrs(
+        edge.inline_style.as_ref().unwrap_or(&HashMap::new()),
+        stroke_width,
+        dash_array,
+    );
 
-    // Build marker attributes based on arrow direction flags
+    // Build marker attributes based on arrow direction flags; dotted edges
+    // get open arrowheads
+    let arrow = if edge.style == EdgeStyle::Dotted {
+        "arrowhead-open"
+    } else {
+        "arrowhead"
+    };
     let mut markers = String::new();
     if edge.has_arrow_end {
-        markers.push_str(" marker-end=\"url(#arrowhead)\"");
+        markers.push_str(&format!(" marker-end=\"url(#{})\"", arrow));
     }
     if edge.has_arrow_start {
-        markers.push_str(" marker-start=\"url(#arrowhead-start)\"");
+        markers.push_str(&format!(" marker-start=\"url(#{}-start)\"", arrow));
     }
 
+    if curve == CurveStyle::Linear {
+        return format!(
+            r#"<polyline points="{}" fill="none"{}{} />"#,
+            points_to_polyline_path(&edge.points),
+            stroke,
+            markers
+        );
+    }
     format!(
-        r#"<polyline points="{}" fill="none" stroke="var(--_line)" stroke-width="{}"{}{} />"#,
-        path_data, stroke_width, dash_array, markers
+        r#"<path d="{}" fill="none"{}{} />"#,
+        curve_path_data(&edge.points, curve),
+        stroke,
+        markers
     )
 }
 
+/// Build an SVG path `d` attribute running through `points` with the given curve.
+///
+/// Follows d3's curve factories, which Mermaid uses: `Basis` is a uniform cubic
+/// B-spline that starts and ends on the first and last points, `StepAfter`
+/// moves horizontally then vertically between consecutive points.
+pub(super) fn curve_path_data(points: &[Point], curve: CurveStyle) -> String {
+    let Some(first) = points.first() else {
+        return String::new();
+    };
+    let mut d = format!("M{},{}", fmt_num(first.x), fmt_num(first.y));
+    fn line_to(d: &mut String, x: f64, y: f64) {
+        d.push_str(&format!(" L{},{}", fmt_num(x), fmt_num(y)));
+    }
+
+    match curve {
+        CurveStyle::Linear => {
+            for p in &points[1..] {
+                line_to(&mut d, p.x, p.y);
+            }
+        }
+        CurveStyle::StepAfter => {
+            for pair in points.windows(2) {
+                line_to(&mut d, pair[1].x, pair[0].y);
+                line_to(&mut d, pair[1].x, pair[1].y);
+            }
+        }
+        CurveStyle::Basis => {
+            if points.len() == 2 {
+                line_to(&mut d, points[1].x, points[1].y);
+            } else {
+                let (p0, p1) = (&points[0], &points[1]);
+                line_to(&mut d, (5.0 * p0.x + p1.x) / 6.0, (5.0 * p0.y + p1.y) / 6.0);
+                // Every window of three points contributes one cubic segment;
+                // repeating the last point makes the spline end on it.
+                let last = points[points.len() - 1];
+                let padded: Vec<Point> = points.iter().copied().chain([last]).collect();
+                for w in padded.windows(3) {
+                    let (a, b, c) = (&w[0], &w[1], &w[2]);
+                    d.push_str(&format!(
+                        " C{},{} {},{} {},{}",
+                        fmt_num((2.0 * a.x + b.x) / 3.0),
+                        fmt_num((2.0 * a.y + b.y) / 3.0),
+                        fmt_num((a.x + 2.0 * b.x) / 3.0),
+                        fmt_num((a.y + 2.0 * b.y) / 3.0),
+                        fmt_num((a.x + 4.0 * b.x + c.x) / 6.0),
+                        fmt_num((a.y + 4.0 * b.y + c.y) / 6.0),
+                    ));
+                }
+                line_to(&mut d, last.x, last.y);
+            }
+        }
+    }
+    d
+}
+
 /// Convert points to SVG polyline points attribute: "x1,y1 x2,y2 ..."
 fn points_to_polyline_path(points: &[Point]) -> String {
     points
@@ -185,6 +352,11 @@ fn render_edge_label(edge: &PositionedEdge) -> String {
 
     let text_width = estimate_text_width(label, FontSizes::EDGE_LABEL, FontWeights::EDGE_LABEL);
     let padding = 8.0;
+    let color = edge
+        .inline_style
+        .as_ref()
+        .and_then(|style| style.get("color"))
+        .map_or_else(|| "var(--_text-muted)".to_string(), |c| escape_xml(c));
 
     // Background pill behind text for readability
     let bg_width = text_width + padding * 2.0;
@@ -192,7 +364,7 @@ fn render_edge_label(edge: &PositionedEdge) -> String {
 
     format!(
         r#"<rect x="{}" y="{}" width="{}" height="{}" rx="4" ry="4" fill="var(--bg)" stroke="var(--_inner-stroke)" stroke-width="0.5" />
-<text x="{}" y="{}" text-anchor="middle" dy="{}" font-size="{}" font-weight="{}" fill="var(--_text-muted)">{}</text>"#,
+<text x="{}" y="{}" text-anchor="middle" dy="{}" font-size="{}" font-weight="{}" fill="{}">{}</text>"#,
         mid.x - bg_width / 2.0,
         mid.y - bg_height / 2.0,
         bg_width,
@@ -202,10 +374,43 @@ fn render_edge_label(edge: &PositionedEdge) -> String {
         TEXT_BASELINE_SHIFT,
         FontSizes::EDGE_LABEL,
         FontWeights::EDGE_LABEL,
+        color,
         escape_xml(label)
     )
 }
 
+/// Labels next to the source and target ends, offset from the line
+fn render_edge_end_labels(edge: &PositionedEdge) -> String {
+    let n = edge.points.len();
+    if n < 2 {
+        return String::new();
+    }
+    let ends = [
+        (&edge.start_label, edge.points[0], edge.points[1]),
+        (&edge.end_label, edge.points[n - 1], edge.points[n - 2]),
+    ];
+
+    let mut parts = Vec::new();
+    for (label, at, toward) in ends {
+        let Some(label) = label else { continue };
+        let len = dist(&at, &toward).max(1.0);
+        let (ux, uy) = ((toward.x - at.x) / len, (toward.y - at.y) / len);
+        // Step along the line, then off to its side
+        let x = at.x + ux * 16.0 - uy * 10.0;
+        let y = at.y + uy * 16.0 + ux * 10.0;
+        parts.push(format!(
+            r#"<text x="{}" y="{}" text-anchor="middle" dy="{}" font-size="{}" font-weight="{}" fill="var(--_text-muted)">{}</text>"#,
+            x,
+            y,
+            TEXT_BASELINE_SHIFT,
+            FontSizes::EDGE_LABEL,
+            FontWeights::EDGE_LABEL,
+            escape_xml(label)
+        ));
+    }
+    parts.join("\n")
+}
+
 /// Get the midpoint of a polyline (by walking segments)
 fn edge_midpoint(points: &[Point]) -> Point {
     if points.is_empty() {
@@ -273,7 +478,7 @@ fn render_node_shape(node: &PositionedNode) -> String {
         .map(|s| s.as_str())
         .unwrap_or(&default_sw);
 
-    match node.shape {
+    let shape = match node.shape {
         NodeShape::Diamond => render_diamond(x, y, w, h, fill, stroke, sw),
         NodeShape::Rounded => render_rounded_rect(x, y, w, h, fill, stroke, sw),
         NodeShape::Stadium => render_stadium(x, y, w, h, fill, stroke, sw),
@@ -288,9 +493,34 @@ fn render_node_shape(node: &PositionedNode) -> String {
         NodeShape::StateStart => render_state_start(x, y, w, h),
         NodeShape::StateEnd => render_state_end(x, y, w, h),
         NodeShape::Rectangle => render_rect(x, y, w, h, fill, stroke, sw),
+    };
+
+    // Shapes don't set a dash array themselves, so a group can supply one
+    match node
+        .inline_style
+        .as_ref()
+        .and_then(|s| s.get("stroke-dasharray"))
+    {
+        Some(dash) => format!(
+            "<g stroke-dasharray=\"{}\">\n{}\n</g>",
+            escape_xml(dash),
+            shape
+        ),
+        None => shape,
     }
 }
 
+/// A node wrapped in an `<a>` element, with the tooltip as `<title>`
+fn render_linked_node(node: &PositionedNode, link: &PositionedLink) -> String {
+    let content = format!("{}\n{}", render_node_shape(node), render_node_label(node));
+    link_element(
+        &content,
+        &link.url,
+        link.target.as_deref(),
+        link.tooltip.as_deref(),
+    )
+}
+
 // --- Basic shapes ---
 
 fn render_rect(x: f64, y: f64, w: f64, h: f64, fill: &str, stroke: &str, sw: &str) -> String {
@@ -573,6 +803,104 @@ fn render_state_end(x: f64, y: f64, w: f64, h: f64) -> String {
     )
 }
 
+// ============================================================================
+// Node outlines
+// ============================================================================
+
+/// Outline of a node as drawn by the shape functions above, as a closed
+/// polygon with arcs flattened into short segments. Edges are clipped to it.
+pub(super) fn node_outline(node: &PositionedNode) -> Vec<Point> {
+    let (x, y, w, h) = (node.x, node.y, node.width, node.height);
+    let (cx, cy) = (x + w / 2.0, y + h / 2.0);
+    let p = |x: f64, y: f64| Point { x, y };
+    match node.shape {
+        NodeShape::Rectangle | NodeShape::Subroutine => {
+            vec![p(x, y), p(x + w, y), p(x + w, y + h), p(x, y + h)]
+        }
+        NodeShape::Rounded => rounded_outline(x, y, w, h, 6.0),
+        NodeShape::Stadium => rounded_outline(x, y, w, h, h / 2.0),
+        NodeShape::Circle | NodeShape::Doublecircle => {
+            let r = w.min(h) / 2.0;
+            arc(cx, cy, r, r, 0.0, 360.0).collect()
+        }
+        NodeShape::StateStart | NodeShape::StateEnd => {
+            let r = w.min(h) / 2.0 - 2.0;
+            arc(cx, cy, r, r, 0.0, 360.0).collect()
+        }
+        NodeShape::Diamond => vec![p(cx, y), p(x + w, cy), p(cx, y + h), p(x, cy)],
+        NodeShape::Hexagon => {
+            let inset = h / 4.0;
+            vec![
+                p(x + inset, y),
+                p(x + w - inset, y),
+                p(x + w, cy),
+                p(x + w - inset, y + h),
+                p(x + inset, y + h),
+                p(x, cy),
+            ]
+        }
+        NodeShape::Cylinder => {
+            // Upper half of the top ellipse, lower half of the bottom one
+            let ry = 7.0;
+            arc(cx, y + ry, w / 2.0, ry, 180.0, 360.0)
+                .chain(arc(cx, y + h - ry, w / 2.0, ry, 0.0, 180.0))
+                .collect()
+        }
+        NodeShape::Asymmetric => {
+            let indent = 12.0;
+            vec![
+                p(x + indent, y),
+                p(x + w, y),
+                p(x + w, y + h),
+                p(x + indent, y + h),
+                p(x, cy),
+            ]
+        }
+        NodeShape::Trapezoid => {
+            let inset = w * 0.15;
+            vec![
+                p(x + inset, y),
+                p(x + w - inset, y),
+                p(x + w, y + h),
+                p(x, y + h),
+            ]
+        }
+        NodeShape::TrapezoidAlt => {
+            let inset = w * 0.15;
+            vec![
+                p(x, y),
+                p(x + w, y),
+                p(x + w - inset, y + h),
+                p(x + inset, y + h),
+            ]
+        }
+    }
+}
+
+/// A rectangle with corners rounded to radius `r`, as SVG clamps `rx`
+fn rounded_outline(x: f64, y: f64, w: f64, h: f64, r: f64) -> Vec<Point> {
+    let r = r.min(w / 2.0).min(h / 2.0);
+    arc(x + w - r, y + r, r, r, 270.0, 360.0)
+        .chain(arc(x + w - r, y + h - r, r, r, 0.0, 90.0))
+        .chain(arc(x + r, y + h - r, r, r, 90.0, 180.0))
+        .chain(arc(x + r, y + r, r, r, 180.0, 270.0))
+        .collect()
+}
+
+/// Points along an elliptical arc, clockwise on screen from `from` to `to`
+/// degrees (0 pointing right, 90 down)
+fn arc(cx: f64, cy: f64, rx: f64, ry: f64, from: f64, to: f64) -> impl Iterator<Item = Point> {
+    // A segment every 5 degrees stays within a fraction of a pixel of the curve
+    let steps = ((to - from) / 5.0).ceil().max(1.0) as usize;
+    (0..=steps).map(move |i| {
+        let angle = (from + (to - from) * i as f64 / steps as f64).to_radians();
+        Point {
+            x: cx + rx * angle.cos(),
+            y: cy + ry * angle.sin(),
+        }
+    })
+}
+
 // ============================================================================
 // Node label rendering
 // ============================================================================
@@ -594,16 +922,62 @@ fn render_node_label(node: &PositionedNode) -> String {
         .map(|s| s.as_str())
         .unwrap_or("var(--_text)");
 
-    format!(
-        r#"<text x="{}" y="{}" text-anchor="middle" dy="{}" font-size="{}" font-weight="{}" fill="{}">{}</text>"#,
-        cx,
-        cy,
-        TEXT_BASELINE_SHIFT,
-        FontSizes::NODE_LABEL,
-        FontWeights::NODE_LABEL,
-        text_color,
-        escape_xml(&node.label)
-    )
+    // One line per row of a wrapped label, centered as a block
+    let lines: Vec<&str> = node.label.split('\n').collect();
+    let first_y = cy - (lines.len() - 1) as f64 * NODE_LINE_HEIGHT / 2.0;
+    let (x, anchor) = match label_align() {
+        Some(align) if align != LabelAlign::Center => {
+            let (left, right) = label_bounds(node, &lines);
+            align.anchor(left, right)
+        }
+        _ => (cx, "middle"),
+    };
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            format!(
+                r#"<text x="{}" y="{}" text-anchor="{}" dy="{}" font-size="{}" font-weight="{}" fill="{}">{}</text>"#,
+                x,
+                first_y + i as f64 * NODE_LINE_HEIGHT,
+                anchor,
+                TEXT_BASELINE_SHIFT,
+                FontSizes::NODE_LABEL,
+                FontWeights::NODE_LABEL,
+                text_color,
+                escape_xml(line)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Left and right of the text of `node`: inside the padding the layout gave
+/// its shape, or, for round and diamond shapes, the widest of its `lines`
+fn label_bounds(node: &PositionedNode, lines: &[&str]) -> (f64, f64) {
+    let inset = match node.shape {
+        NodeShape::Rectangle | NodeShape::Rounded | NodeShape::Stadium | NodeShape::Cylinder => {
+            Some(16.0)
+        }
+        NodeShape::Subroutine | NodeShape::Hexagon => Some(24.0),
+        NodeShape::Asymmetric | NodeShape::Trapezoid | NodeShape::TrapezoidAlt => Some(28.0),
+        NodeShape::Diamond
+        | NodeShape::Circle
+        | NodeShape::Doublecircle
+        | NodeShape::StateStart
+        | NodeShape::StateEnd => None,
+    };
+    match inset {
+        Some(inset) => (node.x + inset, node.x + node.width - inset),
+        None => {
+            let widest = lines
+                .iter()
+                .map(|l| estimate_text_width(l, FontSizes::NODE_LABEL, FontWeights::NODE_LABEL))
+                .fold(0.0, f64::max);
+            let cx = node.x + node.width / 2.0;
+            (cx - widest / 2.0, cx + widest / 2.0)
+        }
+    }
 }
 
 // ============================================================================
@@ -619,6 +993,47 @@ pub fn escape_xml(text: &str) -> String {
         .replace('\'', "&#39;")
 }
 
+/// `content` wrapped in an `<a>` element, with the tooltip as `<title>`; left
+/// unlinked when the URL is not one [`safe_link`] allows
+pub(crate) fn link_element(
+    content: &str,
+    url: &str,
+    target: Option<&str>,
+    tooltip: Option<&str>,
+) -> String {
+    if !safe_link(url) {
+        return content.to_string();
+    }
+    let target = target
+        .map(|t| format!(r#" target="{}""#, escape_xml(t)))
+        .unwrap_or_default();
+    let title = tooltip
+        .map(|t| format!("<title>{}</title>\n", escape_xml(t)))
+        .unwrap_or_default();
+    format!(
+        "<a href=\"{}\"{}>\n{}{}\n</a>",
+        escape_xml(url),
+        target,
+        title,
+        content
+    )
+}
+
+/// Whether a link is relative, to a fragment, or `http:`, `https:` or `mailto:`
+pub(crate) fn safe_link(url: &str) -> bool {
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    match url.find(':') {
+        Some(colon) if !url[..colon].contains(['/', '?', '#']) => {
+            let scheme = url[..colon].to_ascii_lowercase();
+            matches!(scheme.as_str(), "http" | "https" | "mailto")
+        }
+        _ => true,
+    }
+}
+
 /// Format a float to match JavaScript's number-to-string behavior.
 /// JavaScript outputs full precision for floating point numbers.
 fn fmt_num(n: f64) -> String {
@@ -1,14 +1,15 @@
 //! Sequence diagram SVG rendering
 
+use super::document::SvgDocument;
 use super::renderer::escape_xml;
-use super::theme::{build_style_block, svg_open_tag, DiagramColors};
-use crate::types::SequenceDiagram;
+use super::theme::{build_style_block, svg_document, DiagramColors};
+use crate::types::{Note, NotePosition, SequenceConfig, SequenceDiagram};
 use std::collections::HashMap;
 
 const ACTOR_BOX_HEIGHT: f64 = 40.0;
 const ACTOR_PADDING: f64 = 16.0;
 const LIFELINE_MIN_GAP: f64 = 120.0;
-const MESSAGE_SPACING: f64 = 50.0;
+const NOTE_HEIGHT: f64 = 30.0;
 
 /// Render a sequence diagram to SVG
 pub fn render_sequence_svg(
@@ -17,9 +18,42 @@ pub fn render_sequence_svg(
     font: &str,
     transparent: bool,
 ) -> String {
+    render_sequence_document(diagram, colors, font, transparent)
+        .map(|document| document.to_string())
+        .unwrap_or_default()
+}
+
+/// Render a sequence diagram to an SVG document, or `None` when it has no participants
+pub fn render_sequence_document(
+    diagram: &SequenceDiagram,
+    colors: &DiagramColors,
+    font: &str,
+    transparent: bool,
+) -> Option<SvgDocument> {
+    render_sequence_document_with_config(
+        diagram,
+        colors,
+        font,
+        transparent,
+        SequenceConfig::default(),
+    )
+}
+
+/// Render a sequence diagram to an SVG document with the spacing of its
+/// frontmatter config, falling back to `config`, or `None` when it has no
+/// participants
+pub fn render_sequence_document_with_config(
+    diagram: &SequenceDiagram,
+    colors: &DiagramColors,
+    font: &str,
+    transparent: bool,
+    config: SequenceConfig,
+) -> Option<SvgDocument> {
     if diagram.actors.is_empty() {
-        return String::new();
+        return None;
     }
+    let config = diagram.config.or(config);
+    let message_spacing = config.message_margin();
 
     // Calculate actor box widths based on label lengths
     let actor_widths: Vec<f64> = diagram
@@ -57,45 +91,83 @@ pub fn render_sequence_svg(
     // Calculate lifeline X positions
     let mut ll_x: Vec<f64> = vec![actor_widths[0] / 2.0 + 20.0];
     for i in 1..diagram.actors.len() {
-        let gap = gaps[i - 1].max((actor_widths[i - 1] + actor_widths[i]) / 2.0 + 20.0);
+        let boxes = (actor_widths[i - 1] + actor_widths[i]) / 2.0 + config.actor_margin();
+        let gap = gaps[i - 1].max(boxes);
         ll_x.push(ll_x[i - 1] + gap);
     }
 
+    // Shift everything right when a note hangs off the left edge
+    let overhang = diagram
+        .notes
+        .iter()
+        .filter_map(|note| note_span(note, &ll_x, &actor_idx))
+        .map(|(left, _)| 20.0 - left)
+        .fold(0.0, f64::max);
+    for x in &mut ll_x {
+        *x += overhang;
+    }
+
     // Calculate vertical positions for messages
     let header_y = ACTOR_BOX_HEIGHT + 20.0;
     let mut msg_y: Vec<f64> = Vec::new();
     let mut cur_y = header_y;
 
-    for msg in &diagram.messages {
+    // Notes take a box of their own after the message they follow
+    let last_msg = diagram.messages.len() as i32 - 1;
+    let mut note_y: Vec<f64> = vec![0.0; diagram.notes.len()];
+    let mut place_notes = |after: i32, cur_y: &mut f64| {
+        for (n, note) in diagram.notes.iter().enumerate() {
+            if note.after_index.min(last_msg) == after {
+                note_y[n] = *cur_y + config.box_margin();
+                *cur_y += config.box_margin() + NOTE_HEIGHT;
+            }
+        }
+    };
+    place_notes(-1, &mut cur_y);
+
+    for (m, msg) in diagram.messages.iter().enumerate() {
         let is_self = msg.from == msg.to;
-        cur_y += MESSAGE_SPACING;
+        cur_y += message_spacing;
         if is_self {
             msg_y.push(cur_y);
             cur_y += 30.0; // Extra space for self-loop
         } else {
             msg_y.push(cur_y);
         }
+        place_notes(m as i32, &mut cur_y);
     }
 
-    let footer_y = cur_y + MESSAGE_SPACING;
-    let total_height = footer_y + ACTOR_BOX_HEIGHT + 20.0;
-    let total_width = ll_x.last().copied().unwrap_or(0.0)
-        + actor_widths.last().copied().unwrap_or(60.0) / 2.0
-        + 40.0;
+    let footer_y = cur_y + message_spacing;
+    let total_height = if config.mirror_actors() {
+        footer_y + ACTOR_BOX_HEIGHT + 20.0
+    } else {
+        footer_y + 20.0
+    };
+    let total_width = diagram
+        .notes
+        .iter()
+        .filter_map(|note| note_span(note, &ll_x, &actor_idx))
+        .map(|(_, right)| right + 20.0)
+        .fold(
+            ll_x.last().copied().unwrap_or(0.0)
+                + actor_widths.last().copied().unwrap_or(60.0) / 2.0
+                + 40.0,
+            f64::max,
+        );
 
+    let mut document = svg_document(total_width, total_height, colors, transparent);
+    document.head.push(build_style_block(font));
     let mut svg = String::new();
-    svg.push_str(&svg_open_tag(
-        total_width,
-        total_height,
-        colors,
-        transparent,
-    ));
-    svg.push_str(&build_style_block(font));
 
-    // Draw lifelines (dashed lines between actor boxes)
+    // Draw lifelines (dashed lines between actor boxes), grouped with the
+    // participant's boxes under its id for interactive viewers
     for (i, &x) in ll_x.iter().enumerate() {
         let top = ACTOR_BOX_HEIGHT;
         let bottom = footer_y;
+        svg.push_str(&format!(
+            r#"<g data-participant="{}">"#,
+            escape_xml(&diagram.actors[i].id)
+        ));
         svg.push_str(&format!(
             r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" class="lifeline"/>"#,
             x, top, x, bottom
@@ -108,9 +180,18 @@ pub fn render_sequence_svg(
         svg.push_str(&draw_actor_box(x, 0.0, w, ACTOR_BOX_HEIGHT, label));
 
         // Draw actor boxes (footer)
-        svg.push_str(&draw_actor_box(x, footer_y, w, ACTOR_BOX_HEIGHT, label));
+        if config.mirror_actors() {
+            svg.push_str(&draw_actor_box(x, footer_y, w, ACTOR_BOX_HEIGHT, label));
+        }
+        svg.push_str("</g>\n");
     }
 
+    // Activations open on each participant's lifeline (`+` on a message
+    // activates its target, `-` deactivates its sender, and `activate X` /
+    // `deactivate X` statements apply where they stand)
+    let mut depths: HashMap<&str, usize> = HashMap::new();
+    let mut activations = diagram.activations.iter().peekable();
+
     // Draw messages
     for (m, msg) in diagram.messages.iter().enumerate() {
         let fi = actor_idx.get(msg.from.as_str()).copied().unwrap_or(0);
@@ -124,6 +205,32 @@ pub fn render_sequence_svg(
             "message"
         };
 
+        // Each message is a group carrying its index, its ends and the
+        // sender's activation depth when it is sent
+        while let Some(activation) = activations.next_if(|a| a.after_index < m as i32) {
+            let depth = depths.entry(activation.actor_id.as_str()).or_default();
+            if activation.active {
+                *depth += 1;
+            } else {
+                *depth = depth.saturating_sub(1);
+            }
+        }
+        let depth = depths.get(msg.from.as_str()).copied().unwrap_or(0);
+        if msg.activate {
+            *depths.entry(msg.to.as_str()).or_default() += 1;
+        }
+        if msg.deactivate {
+            let sender = depths.entry(msg.from.as_str()).or_default();
+            *sender = sender.saturating_sub(1);
+        }
+        svg.push_str(&format!(
+            r#"<g data-message-index="{}" data-from="{}" data-to="{}" data-activation-depth="{}">"#,
+            m,
+            escape_xml(&msg.from),
+            escape_xml(&msg.to),
+            depth
+        ));
+
         if is_self {
             // Self-message loop
             let x = ll_x[fi];
@@ -186,11 +293,57 @@ pub fn render_sequence_svg(
                 escape_xml(&msg.label)
             ));
         }
+        svg.push_str("</g>\n");
+    }
+
+    // Notes go over the lifelines
+    for (n, note) in diagram.notes.iter().enumerate() {
+        let Some((left, right)) = note_span(note, &ll_x, &actor_idx) else {
+            continue;
+        };
+        svg.push_str(&format!(
+            r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" class="note"/>"#,
+            left,
+            note_y[n],
+            right - left,
+            NOTE_HEIGHT
+        ));
+        svg.push_str(&format!(
+            r#"<text x="{:.1}" y="{:.1}" class="note-label" text-anchor="middle" dominant-baseline="middle">{}</text>"#,
+            (left + right) / 2.0,
+            note_y[n] + NOTE_HEIGHT / 2.0,
+            escape_xml(&note.text)
+        ));
         svg.push('\n');
     }
 
-    svg.push_str("</svg>");
-    svg
+    document.body = svg;
+    Some(document)
+}
+
+/// Left and right edges of a note's box, or `None` when it names no
+/// participant in the diagram
+fn note_span(note: &Note, ll_x: &[f64], actor_idx: &HashMap<&str, usize>) -> Option<(f64, f64)> {
+    let xs: Vec<f64> = note
+        .actor_ids
+        .iter()
+        .filter_map(|id| actor_idx.get(id.as_str()))
+        .map(|&i| ll_x[i])
+        .collect();
+    if xs.is_empty() {
+        return None;
+    }
+    let lo = xs.iter().copied().fold(f64::INFINITY, f64::min);
+    let hi = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let w = (note.text.len() as f64 * 8.0 + 20.0).max(60.0);
+    Some(match note.position {
+        NotePosition::Left => (lo - 10.0 - w, lo - 10.0),
+        NotePosition::Right => (hi + 10.0, hi + 10.0 + w),
+        NotePosition::Over => {
+            let w = w.max(hi - lo + 40.0);
+            ((lo + hi - w) / 2.0, (lo + hi + w) / 2.0)
+        }
+    })
 }
 
 fn draw_actor_box(cx: f64, top_y: f64, width: f64, height: f64, label: &str) -> String {
@@ -1,19 +1,158 @@
 //! Font metrics and styling constants.
 //!
-//! Calibrated for Inter font with fallback to system UI fonts.
+//! Calibrated for Inter font with fallback to system UI fonts, unless a
+//! [`TextMeasurer`] with real font metrics is set.
 
-/// Average character width in px at the given font size and weight
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+
+use crate::node_width::NodeWidth;
+use crate::types::LabelAlign;
+
+/// Measures the rendered width of text. All label sizing in the SVG renderers
+/// goes through [`estimate_text_width`], which asks the measurer set with
+/// [`with_text_measurer`] (or [`HeuristicMeasurer`] when none is set).
+pub trait TextMeasurer: Send + Sync + std::fmt::Debug {
+    /// Width in px of `text` set in one line at the given font size and weight
+    fn text_width(&self, text: &str, font_size: f64, font_weight: u32) -> f64;
+}
+
+/// Average character widths calibrated for Inter; no font data needed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicMeasurer;
+
+impl HeuristicMeasurer {
+    /// Inter average character width as a fraction of the font size, per
+    /// weight. Heavier weights are slightly wider.
+    pub fn width_ratio(font_weight: u32) -> f64 {
+        if font_weight >= 600 {
+            0.58
+        } else if font_weight >= 500 {
+            0.55
+        } else {
+            0.52
+        }
+    }
+}
+
+impl TextMeasurer for HeuristicMeasurer {
+    fn text_width(&self, text: &str, font_size: f64, font_weight: u32) -> f64 {
+        text.len() as f64 * font_size * Self::width_ratio(font_weight)
+    }
+}
+
+thread_local! {
+    static MEASURER: RefCell<Option<Arc<dyn TextMeasurer>>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with `measurer` sizing all text rendered on this thread
+///
+/// # Example
+/// ```rust
+/// use std::sync::Arc;
+/// use m2svg::svg::styles::{estimate_text_width, with_text_measurer, TextMeasurer};
+///
+/// #[derive(Debug)]
+/// struct Monospace;
+///
+/// impl TextMeasurer for Monospace {
+///     fn text_width(&self, text: &str, font_size: f64, _font_weight: u32) -> f64 {
+///         text.chars().count() as f64 * font_size * 0.6
+///     }
+/// }
+///
+/// let width = with_text_measurer(Arc::new(Monospace), || estimate_text_width("née", 10.0, 400));
+/// assert_eq!(width, 18.0);
+/// ```
+pub fn with_text_measurer<R>(measurer: Arc<dyn TextMeasurer>, f: impl FnOnce() -> R) -> R {
+    // Restores the previous measurer even if `f` panics
+    struct Restore(Option<Arc<dyn TextMeasurer>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            MEASURER.with(|m| *m.borrow_mut() = self.0.take());
+        }
+    }
+    let _restore = Restore(MEASURER.with(|m| m.borrow_mut().replace(measurer)));
+    f()
+}
+
+thread_local! {
+    static NODE_WIDTH: Cell<NodeWidth> = const { Cell::new(NodeWidth { uniform: false, min: 0, max: 0 }) };
+}
+
+/// Run `f` with `node_width` sizing the flowchart node and class boxes
+/// rendered on this thread
+pub fn with_node_width<R>(node_width: NodeWidth, f: impl FnOnce() -> R) -> R {
+    // Restores the previous widths even if `f` panics
+    struct Restore(NodeWidth);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            NODE_WIDTH.with(|w| w.set(self.0));
+        }
+    }
+    let _restore = Restore(NODE_WIDTH.with(|w| w.replace(node_width)));
+    f()
+}
+
+/// The box widths set by [`with_node_width`], as wide as their text if none
+pub fn node_width() -> NodeWidth {
+    NODE_WIDTH.with(Cell::get)
+}
+
+thread_local! {
+    static LABEL_ALIGN: Cell<Option<LabelAlign>> = const { Cell::new(None) };
+}
+
+/// Run `f` with `label_align` aligning the text of the flowchart nodes and
+/// class boxes rendered on this thread
+pub fn with_label_align<R>(label_align: Option<LabelAlign>, f: impl FnOnce() -> R) -> R {
+    // Restores the previous alignment even if `f` panics
+    struct Restore(Option<LabelAlign>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            LABEL_ALIGN.with(|a| a.set(self.0));
+        }
+    }
+    let _restore = Restore(LABEL_ALIGN.with(|a| a.replace(label_align)));
+    f()
+}
+
+/// The alignment set by [`with_label_align`], if any
+pub fn label_align() -> Option<LabelAlign> {
+    LABEL_ALIGN.with(Cell::get)
+}
+
+thread_local! {
+    static WEB_FONTS: Cell<bool> = const { Cell::new(true) };
+}
+
+/// Run `f` with the SVG rendered on this thread importing its font from
+/// Google Fonts, or not
+pub fn with_web_fonts<R>(web_fonts: bool, f: impl FnOnce() -> R) -> R {
+    // Restores the previous setting even if `f` panics
+    struct Restore(bool);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            WEB_FONTS.with(|w| w.set(self.0));
+        }
+    }
+    let _restore = Restore(WEB_FONTS.with(|w| w.replace(web_fonts)));
+    f()
+}
+
+/// Whether fonts are imported from Google Fonts, as set by
+/// [`with_web_fonts`]; they are unless it says otherwise
+pub fn web_fonts() -> bool {
+    WEB_FONTS.with(Cell::get)
+}
+
+/// Width in px of `text` at the given font size and weight, from the current measurer
 pub fn estimate_text_width(text: &str, font_size: f64, font_weight: u32) -> f64 {
-    // Inter average character widths as fraction of fontSize, per weight.
-    // Heavier weights are slightly wider.
-    let width_ratio = if font_weight >= 600 {
-        0.58
-    } else if font_weight >= 500 {
-        0.55
-    } else {
-        0.52
-    };
-    text.len() as f64 * font_size * width_ratio
+    let measurer = MEASURER.with(|m| m.borrow().clone());
+    match measurer {
+        Some(measurer) => measurer.text_width(text, font_size, font_weight),
+        None => HeuristicMeasurer.text_width(text, font_size, font_weight),
+    }
 }
 
 /// Fixed font sizes used in the renderer (in px)
@@ -54,3 +193,6 @@ impl ArrowHead {
 /// Vertical shift applied to all text elements for font-agnostic centering.
 /// Using 0.35em ensures it scales with font size.
 pub const TEXT_BASELINE_SHIFT: &str = "0.35em";
+
+/// Distance between the lines of a wrapped node label
+pub const NODE_LINE_HEIGHT: f64 = 17.0;
@@ -4,7 +4,10 @@
 //!   - Two required variables: --bg (background) and --fg (foreground)
 //!   - Optional enrichment variables: --line, --accent, --muted, --surface, --border
 //!   - Unset optionals fall back to color-mix() derivations from bg + fg
+//!   - Elements only refer to the variables, so a second palette is one
+//!     `@media (prefers-color-scheme: dark)` rule (see `dark_scheme_block`)
 
+use super::document::SvgDocument;
 use crate::types::MermaidTheme;
 use serde::{Deserialize, Serialize};
 
@@ -88,9 +91,95 @@ impl Mix {
     pub const KEY_BADGE: u8 = 10;
 }
 
+/// CSS generic families, which are keywords and stay unquoted
+const GENERIC_FAMILIES: &[&str] = &[
+    "serif",
+    "sans-serif",
+    "monospace",
+    "cursive",
+    "fantasy",
+    "system-ui",
+    "ui-serif",
+    "ui-sans-serif",
+    "ui-monospace",
+    "ui-rounded",
+    "emoji",
+    "math",
+];
+
+/// The families in `font`, a comma-separated list in order of preference
+/// (`Inter, 'Noto Sans'`), without their quotes
+pub fn font_families(font: &str) -> Vec<&str> {
+    font.split(',')
+        .map(|name| {
+            let name = name.trim();
+            ['\'', '"']
+                .iter()
+                .find_map(|&q| name.strip_prefix(q)?.strip_suffix(q))
+                .unwrap_or(name)
+                .trim()
+        })
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// The `font-family` value for `font`, a comma-separated list of families,
+/// ending in `fallback`. Names are quoted, and escaped for CSS inside an SVG
+/// `<style>` element.
+///
+/// # Example
+/// ```rust
+/// use m2svg::svg::font_family_stack;
+///
+/// assert_eq!(
+///     font_family_stack("Inter, O'Font <Pro>, monospace", "sans-serif"),
+///     r"'Inter', 'O\'Font &lt;Pro>', monospace, sans-serif"
+/// );
+/// ```
+pub fn font_family_stack(font: &str, fallback: &str) -> String {
+    let mut stack: Vec<String> = font_families(font)
+        .into_iter()
+        .map(|name| {
+            if GENERIC_FAMILIES.contains(&name.to_ascii_lowercase().as_str()) {
+                name.to_string()
+            } else {
+                quote_family(name)
+            }
+        })
+        .collect();
+    stack.push(fallback.to_string());
+    stack.join(", ")
+}
+
+/// `name` quoted, and escaped for CSS inside an SVG `<style>` element
+pub(crate) fn quote_family(name: &str) -> String {
+    let escaped = name
+        .replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace('&', "&amp;")
+        .replace('<', "&lt;");
+    format!("'{}'", escaped)
+}
+
 /// Build the <style> block with font imports and derived CSS variables.
+///
+/// `font` is a comma-separated list of families; the first is imported from
+/// Google Fonts unless it is a generic family.
+/// Without web fonts (see [`with_web_fonts`](super::styles::with_web_fonts)),
+/// nothing is imported and the families fall back to fonts every system has.
 pub fn build_style_block(font: &str) -> String {
-    let font_encoded = font.replace(' ', "%20");
+    let web_fonts = super::styles::web_fonts();
+    let import = match font_families(font).first() {
+        Some(first)
+            if web_fonts && !GENERIC_FAMILIES.contains(&first.to_ascii_lowercase().as_str()) =>
+        {
+            format!(
+                "\n  @import url('https://fonts.googleapis.com/css2?family={}:wght@400;500;600;700&amp;display=swap');",
+                percent_encode(first)
+            )
+        }
+        _ => String::new(),
+    };
 
     let derived_vars = format!(
         r#"
@@ -120,18 +209,75 @@ pub fn build_style_block(font: &str) -> String {
     );
 
     format!(
-        r#"<style>
-  @import url('https://fonts.googleapis.com/css2?family={}:wght@400;500;600;700&amp;display=swap');
-  text {{ font-family: '{}', system-ui, sans-serif; }}
+        r#"<style>{}
+  text {{ font-family: {}; }}
   svg {{{}
   }}
 </style>"#,
-        font_encoded, font, derived_vars
+        import,
+        font_family_stack(
+            font,
+            if web_fonts {
+                "system-ui, sans-serif"
+            } else {
+                super::fonts::SYSTEM_FONT_STACK
+            }
+        ),
+        derived_vars
+    )
+}
+
+/// Percent-encode a family name for a URL query, with spaces as `%20`
+fn percent_encode(name: &str) -> String {
+    let mut encoded = String::new();
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Start an SVG document with the CSS variables set as inline styles on its root.
+pub fn svg_document(
+    width: f64,
+    height: f64,
+    colors: &DiagramColors,
+    transparent: bool,
+) -> SvgDocument {
+    let vars_str = color_vars(colors).join(";");
+    let bg_style = if transparent {
+        ""
+    } else {
+        ";background:var(--bg)"
+    };
+
+    let mut document = SvgDocument::new(width, height);
+    document
+        .attributes
+        .push(("style".to_string(), format!("{}{}", vars_str, bg_style)));
+    document
+}
+
+/// A `<style>` block that gives a document started with [`svg_document`] the
+/// `dark` colors when the reader's system prefers a dark color scheme. They
+/// are `!important` to win over the root's inline style.
+pub fn dark_scheme_block(dark: &DiagramColors) -> String {
+    let vars: Vec<String> = color_vars(dark)
+        .into_iter()
+        .map(|var| format!("{} !important;", var))
+        .collect();
+    format!(
+        "<style>\n  @media (prefers-color-scheme: dark) {{\n    svg {{ {} }}\n  }}\n</style>",
+        vars.join(" ")
     )
 }
 
-/// Build the SVG opening tag with CSS variables set as inline styles.
-pub fn svg_open_tag(width: f64, height: f64, colors: &DiagramColors, transparent: bool) -> String {
+/// The CSS variables of `colors`, as `--name:value` declarations
+fn color_vars(colors: &DiagramColors) -> Vec<String> {
     let mut vars = vec![format!("--bg:{}", colors.bg), format!("--fg:{}", colors.fg)];
 
     if let Some(ref line) = colors.line {
@@ -149,30 +295,13 @@ pub fn svg_open_tag(width: f64, height: f64, colors: &DiagramColors, transparent
     if let Some(ref border) = colors.border {
         vars.push(format!("--border:{}", border));
     }
+    vars
+}
 
-    let vars_str = vars.join(";");
-    let bg_style = if transparent {
-        ""
-    } else {
-        ";background:var(--bg)"
-    };
-
-    // Format dimensions - use integer if whole number, otherwise preserve decimals
-    let format_dim = |d: f64| -> String {
-        if d.fract() == 0.0 {
-            format!("{}", d as i64)
-        } else {
-            // Remove trailing zeros after decimal
-            let s = format!("{}", d);
-            s.trim_end_matches('0').trim_end_matches('.').to_string()
-        }
-    };
-
-    let w_str = format_dim(width);
-    let h_str = format_dim(height);
-
-    format!(
-        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}" width="{}" height="{}" style="{}{}">"#,
-        w_str, h_str, w_str, h_str, vars_str, bg_style
-    )
+/// Give a document started with [`svg_document`] other colors. Its elements
+/// only refer to the CSS variables, so the root's inline style is all that
+/// changes.
+pub fn retheme_document(document: &mut SvgDocument, colors: &DiagramColors, transparent: bool) {
+    document.attributes =
+        svg_document(document.width, document.height, colors, transparent).attributes;
 }
@@ -13,6 +13,13 @@ pub struct PositionedGraph {
     pub groups: Vec<PositionedGroup>,
 }
 
+impl PositionedGraph {
+    /// Serialize the layout to pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize layout: {}", e))
+    }
+}
+
 /// A positioned node with computed coordinates
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PositionedNode {
@@ -26,6 +33,19 @@ pub struct PositionedNode {
     /// Inline styles from classDef + explicit style statements
     #[serde(default, rename = "inlineStyle")]
     pub inline_style: Option<HashMap<String, String>>,
+    /// Hyperlink from a `click` statement
+    #[serde(default)]
+    pub link: Option<PositionedLink>,
+}
+
+/// A hyperlink attached to a node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionedLink {
+    pub url: String,
+    #[serde(default)]
+    pub tooltip: Option<String>,
+    #[serde(default)]
+    pub target: Option<String>,
 }
 
 /// Node shape variants
@@ -65,6 +85,14 @@ pub struct PositionedEdge {
     /// Layout-computed label center position
     #[serde(default, rename = "labelPosition")]
     pub label_position: Option<Point>,
+    /// Labels drawn next to the source and target ends
+    #[serde(default, rename = "startLabel")]
+    pub start_label: Option<String>,
+    #[serde(default, rename = "endLabel")]
+    pub end_label: Option<String>,
+    /// Styles from the classDef of the edge's `:::class`
+    #[serde(default, rename = "inlineStyle")]
+    pub inline_style: Option<HashMap<String, String>>,
 }
 
 /// Edge style variants
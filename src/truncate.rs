@@ -0,0 +1,112 @@
+//! Truncation of long labels, with a numbered legend of the full text
+//!
+//! Used by the `max_label_width` render options: a label longer than the limit
+//! is cut to fit with a `…(n)` marker (`...(n)` in plain ASCII output), and the
+//! renderers list the full labels under the diagram as `(n) full label`.
+
+use std::borrow::Cow;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::ascii::canvas::{text_width, truncate_to_width};
+use crate::types::{DiagramType, MermaidGraph, MermaidSubgraph, ParsedDiagram};
+
+/// Cut the flowchart labels (nodes, subgraphs and edges) of `parsed` that are
+/// wider than `max_width` columns. Returns the diagram to render and the
+/// full text of the truncated labels, marker `(1)` first.
+///
+/// A `max_width` of 0 means no limit. Other diagram types are returned as-is.
+pub fn truncate_diagram<'a>(
+    parsed: &'a ParsedDiagram,
+    max_width: usize,
+    ellipsis: &str,
+) -> (Cow<'a, ParsedDiagram>, Vec<String>) {
+    let DiagramType::Flowchart(graph) = &parsed.diagram else {
+        return (Cow::Borrowed(parsed), Vec::new());
+    };
+    if max_width == 0 {
+        return (Cow::Borrowed(parsed), Vec::new());
+    }
+
+    let mut graph = graph.clone();
+    let legend = truncate_labels(&mut graph, max_width, ellipsis);
+    if legend.is_empty() {
+        return (Cow::Borrowed(parsed), legend);
+    }
+    let truncated = ParsedDiagram {
+        diagram: DiagramType::Flowchart(graph),
+        frontmatter: parsed.frontmatter.clone(),
+    };
+    (Cow::Owned(truncated), legend)
+}
+
+/// Cut every label of `graph` wider than `max_width` columns to
+/// `prefix…(n)` (with the given ellipsis), numbering labels in node, subgraph, then edge order. A label
+/// that appears more than once keeps a single number.
+///
+/// # Example
+/// ```rust
+/// use m2svg::{parse_mermaid, truncate::truncate_labels, DiagramType};
+///
+/// let parsed = parse_mermaid("graph LR\n  A[A rather long label] --> B[Short]").unwrap();
+/// let DiagramType::Flowchart(mut graph) = parsed.diagram else { unreachable!() };
+/// let legend = truncate_labels(&mut graph, 10, "…");
+/// assert_eq!(graph.nodes["A"].label, "A rath…(1)");
+/// assert_eq!(legend, vec!["A rather long label"]);
+/// ```
+pub fn truncate_labels(graph: &mut MermaidGraph, max_width: usize, ellipsis: &str) -> Vec<String> {
+    let mut legend: Vec<String> = Vec::new();
+    let mut truncate = |label: &mut String| {
+        if text_width(label) <= max_width {
+            return;
+        }
+        let number = match legend.iter().position(|l| l == label) {
+            Some(i) => i + 1,
+            None => {
+                legend.push(label.clone());
+                legend.len()
+            }
+        };
+        let marker = format!("{}({})", ellipsis, number);
+        let keep = max_width.saturating_sub(text_width(&marker));
+        // At least one grapheme cluster, so the label stays recognizable
+        let prefix = match truncate_to_width(label, keep) {
+            "" => label.graphemes(true).next().unwrap_or_default(),
+            prefix => prefix,
+        };
+        *label = format!("{}{}", prefix.trim_end(), marker);
+    };
+
+    for id in &graph.node_order {
+        if let Some(node) = graph.nodes.get_mut(id) {
+            truncate(&mut node.label);
+        }
+    }
+    visit_subgraphs(&mut graph.subgraphs, &mut truncate);
+    for edge in &mut graph.edges {
+        for label in [&mut edge.label, &mut edge.start_label, &mut edge.end_label]
+            .into_iter()
+            .flatten()
+        {
+            truncate(label);
+        }
+    }
+    legend
+}
+
+/// Call `f` on the label of every subgraph, parents before their children
+fn visit_subgraphs(subgraphs: &mut [MermaidSubgraph], f: &mut impl FnMut(&mut String)) {
+    for subgraph in subgraphs {
+        f(&mut subgraph.label);
+        visit_subgraphs(&mut subgraph.children, f);
+    }
+}
+
+/// Legend lines for the full labels: `(1) first label`, `(2) ...`
+pub fn legend_lines(legend: &[String]) -> Vec<String> {
+    legend
+        .iter()
+        .enumerate()
+        .map(|(i, label)| format!("({}) {}", i + 1, label.replace('\n', " ")))
+        .collect()
+}
@@ -1,9 +1,35 @@
 //! Type definitions for Mermaid graph structures
 
-use std::collections::HashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Serialize a map with its keys in order, so a diagram always gives the same
+/// JSON (a `HashMap` iterates in a different order on each run)
+fn sorted<S, V>(map: &HashMap<String, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: Serialize,
+{
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+/// [`sorted`] for maps of style properties
+fn sorted_styles<S>(
+    map: &HashMap<String, HashMap<String, String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    map.iter()
+        .map(|(key, props)| (key, props.iter().collect::<BTreeMap<_, _>>()))
+        .collect::<BTreeMap<_, _>>()
+        .serialize(serializer)
+}
 
 /// The direction of a flowchart/graph
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum Direction {
     TD, // Top-Down (same as TB)
     TB, // Top-Bottom
@@ -27,7 +53,7 @@ impl Direction {
 }
 
 /// Shape of a node in the diagram
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum NodeShape {
     Rectangle,    // [text]
     Rounded,      // (text)
@@ -46,34 +72,116 @@ pub enum NodeShape {
 }
 
 /// Style of an edge/connection
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub enum EdgeStyle {
     Solid,
     Dotted,
     Thick,
 }
 
+/// Marker drawn at one end of an edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum EdgeMarker {
+    None,   // ---
+    Arrow,  // -->  (or <-- at the start)
+    Circle, // --o  (or o-- at the start)
+    Cross,  // --x  (or x-- at the start)
+}
+
+/// Side of a node an edge attaches to (`A:e --> B:w`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum Port {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Port {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "n" => Some(Port::North),
+            "s" => Some(Port::South),
+            "e" => Some(Port::East),
+            "w" => Some(Port::West),
+            _ => None,
+        }
+    }
+}
+
 /// A node in the Mermaid graph
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MermaidNode {
     pub id: String,
     pub label: String,
     pub shape: NodeShape,
 }
 
+/// A hyperlink attached to a node with `click`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct NodeLink {
+    pub url: String,
+    pub tooltip: Option<String>,
+    /// Link target such as `_blank`
+    pub target: Option<String>,
+}
+
 /// An edge between two nodes
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MermaidEdge {
     pub source: String,
     pub target: String,
     pub label: Option<String>,
+    /// Label drawn next to the source end (`-->||start|label|end||`)
+    pub start_label: Option<String>,
+    /// Label drawn next to the target end
+    pub end_label: Option<String>,
     pub style: EdgeStyle,
-    pub has_arrow_start: bool,
-    pub has_arrow_end: bool,
+    pub start_marker: EdgeMarker,
+    pub end_marker: EdgeMarker,
+    /// Minimum number of ranks the edge spans (`-->` is 1, `---->` is 3)
+    pub min_length: usize,
+    /// Sides of the source and target the edge is pinned to, if any
+    pub start_port: Option<Port>,
+    pub end_port: Option<Port>,
+    /// Class from a `:::name` suffix on the arrow, styling the edge by `classDef`
+    #[serde(default)]
+    pub class: Option<String>,
+}
+
+impl MermaidEdge {
+    /// A plain `source --> target` edge
+    pub fn new(source: &str, target: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            target: target.to_string(),
+            label: None,
+            start_label: None,
+            end_label: None,
+            style: EdgeStyle::Solid,
+            start_marker: EdgeMarker::None,
+            end_marker: EdgeMarker::Arrow,
+            min_length: 1,
+            start_port: None,
+            end_port: None,
+            class: None,
+        }
+    }
+
+    /// Whether the edge has an arrowhead at its source end (`<-->`)
+    pub fn has_arrow_start(&self) -> bool {
+        self.start_marker == EdgeMarker::Arrow
+    }
+
+    /// Whether the edge has an arrowhead at its target end (`-->`)
+    pub fn has_arrow_end(&self) -> bool {
+        self.end_marker == EdgeMarker::Arrow
+    }
 }
 
 /// A subgraph container
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MermaidSubgraph {
     pub id: String,
     pub label: String,
@@ -83,16 +191,25 @@ pub struct MermaidSubgraph {
 }
 
 /// The complete parsed Mermaid graph
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MermaidGraph {
     pub direction: Direction,
+    #[serde(serialize_with = "sorted")]
     pub nodes: HashMap<String, MermaidNode>,
     pub node_order: Vec<String>, // Track insertion order
     pub edges: Vec<MermaidEdge>,
     pub subgraphs: Vec<MermaidSubgraph>,
+    #[serde(serialize_with = "sorted_styles")]
     pub class_defs: HashMap<String, HashMap<String, String>>,
+    #[serde(serialize_with = "sorted")]
     pub class_assignments: HashMap<String, String>,
+    #[serde(serialize_with = "sorted_styles")]
     pub node_styles: HashMap<String, HashMap<String, String>>,
+    #[serde(serialize_with = "sorted")]
+    pub node_links: HashMap<String, NodeLink>,
+    /// SVG spacing from the frontmatter config (`nodeSpacing:` and the like)
+    #[serde(default)]
+    pub config: FlowchartConfig,
 }
 
 impl MermaidGraph {
@@ -106,7 +223,59 @@ impl MermaidGraph {
             class_defs: HashMap::new(),
             class_assignments: HashMap::new(),
             node_styles: HashMap::new(),
+            node_links: HashMap::new(),
+            config: FlowchartConfig::default(),
+        }
+    }
+
+    /// The style properties of node `id`: the `default` class, then its
+    /// assigned class, then its own `style` statement, each overriding the last
+    pub fn node_style(&self, id: &str) -> HashMap<String, String> {
+        let mut style = HashMap::new();
+        let class = self.class_assignments.get(id).map(String::as_str);
+        for name in [Some("default"), class].into_iter().flatten() {
+            if let Some(props) = self.class_defs.get(name) {
+                style.extend(props.clone());
+            }
+        }
+        if let Some(props) = self.node_styles.get(id) {
+            style.extend(props.clone());
         }
+        style
+    }
+}
+
+/// Spacing of SVG flowcharts, in px, and how their edges are drawn
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct FlowchartConfig {
+    /// Space between neighboring nodes of a rank. Default: 40
+    pub node_spacing: Option<f64>,
+    /// Space between ranks. Default: 50
+    pub rank_spacing: Option<f64>,
+    /// Draw the edges leaving (or entering) the same side of a node as one
+    /// trunk with a branch per edge, in text and SVG output. Default: false
+    pub bundle_edges: Option<bool>,
+    /// Lay the top-level subgraphs out as lanes side by side, with ranks
+    /// lined up across them, in text and grid SVG output. Default: false
+    pub swimlanes: Option<bool>,
+}
+
+impl FlowchartConfig {
+    pub fn node_spacing(&self) -> f64 {
+        self.node_spacing.unwrap_or(40.0)
+    }
+
+    pub fn rank_spacing(&self) -> f64 {
+        self.rank_spacing.unwrap_or(50.0)
+    }
+
+    pub fn bundle_edges(&self) -> bool {
+        self.bundle_edges.unwrap_or(false)
+    }
+
+    pub fn swimlanes(&self) -> bool {
+        self.swimlanes.unwrap_or(false)
     }
 }
 
@@ -114,32 +283,32 @@ impl MermaidGraph {
 // Sequence diagram types
 // ============================================================================
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum ActorType {
     Participant,
     Actor,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Actor {
     pub id: String,
     pub label: String,
     pub actor_type: ActorType,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum LineStyle {
     Solid,
     Dashed,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum ArrowHead {
     Filled,
     Open,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Message {
     pub from: String,
     pub to: String,
@@ -150,7 +319,7 @@ pub struct Message {
     pub deactivate: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum BlockType {
     Loop,
     Alt,
@@ -161,13 +330,13 @@ pub enum BlockType {
     Rect,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct BlockDivider {
     pub index: usize,
     pub label: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Block {
     pub block_type: BlockType,
     pub label: String,
@@ -176,14 +345,14 @@ pub struct Block {
     pub dividers: Vec<BlockDivider>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum NotePosition {
     Left,
     Right,
     Over,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Note {
     pub actor_ids: Vec<String>,
     pub text: String,
@@ -191,12 +360,70 @@ pub struct Note {
     pub after_index: i32,
 }
 
-#[derive(Debug, Clone)]
+/// An `activate X` or `deactivate X` statement, after the message at
+/// `after_index` (-1 before the first)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Activation {
+    pub actor_id: String,
+    pub active: bool,
+    pub after_index: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SequenceDiagram {
     pub actors: Vec<Actor>,
     pub messages: Vec<Message>,
     pub blocks: Vec<Block>,
     pub notes: Vec<Note>,
+    /// Standalone activation statements, in order
+    #[serde(default)]
+    pub activations: Vec<Activation>,
+    /// SVG spacing from the frontmatter config (`actorMargin:` and the like)
+    #[serde(default)]
+    pub config: SequenceConfig,
+}
+
+/// Spacing of SVG sequence diagrams, in px. Values left unset come from
+/// `SvgRenderOptions::sequence`, then from the defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct SequenceConfig {
+    /// Space between neighboring participant boxes. Default: 20
+    pub actor_margin: Option<f64>,
+    /// Space from one message to the next. Default: 50
+    pub message_margin: Option<f64>,
+    /// Space above each note box. Default: 15
+    pub box_margin: Option<f64>,
+    /// Whether the participant boxes are repeated at the bottom. Default: true
+    pub mirror_actors: Option<bool>,
+}
+
+impl SequenceConfig {
+    /// `self`, with the values it leaves unset taken from `fallback`
+    pub fn or(self, fallback: SequenceConfig) -> SequenceConfig {
+        SequenceConfig {
+            actor_margin: self.actor_margin.or(fallback.actor_margin),
+            message_margin: self.message_margin.or(fallback.message_margin),
+            box_margin: self.box_margin.or(fallback.box_margin),
+            mirror_actors: self.mirror_actors.or(fallback.mirror_actors),
+        }
+    }
+
+    pub fn actor_margin(&self) -> f64 {
+        self.actor_margin.unwrap_or(20.0)
+    }
+
+    pub fn message_margin(&self) -> f64 {
+        self.message_margin.unwrap_or(50.0)
+    }
+
+    pub fn box_margin(&self) -> f64 {
+        self.box_margin.unwrap_or(15.0)
+    }
+
+    pub fn mirror_actors(&self) -> bool {
+        self.mirror_actors.unwrap_or(true)
+    }
 }
 
 impl Default for SequenceDiagram {
@@ -212,6 +439,8 @@ impl SequenceDiagram {
             messages: Vec::new(),
             blocks: Vec::new(),
             notes: Vec::new(),
+            activations: Vec::new(),
+            config: SequenceConfig::default(),
         }
     }
 }
@@ -220,7 +449,7 @@ impl SequenceDiagram {
 // Class diagram types
 // ============================================================================
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum Visibility {
     Public,    // +
     Private,   // -
@@ -251,7 +480,7 @@ impl Visibility {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ClassMember {
     pub visibility: Visibility,
     pub name: String,
@@ -264,7 +493,7 @@ pub struct ClassMember {
     pub params: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ClassNode {
     pub id: String,
     pub label: String,
@@ -275,7 +504,7 @@ pub struct ClassNode {
     pub is_lollipop: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum RelationshipType {
     Inheritance, // <|--
     Composition, // *--
@@ -285,7 +514,7 @@ pub enum RelationshipType {
     Realization, // ..|>
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ClassRelationship {
     pub from: String,
     pub to: String,
@@ -296,20 +525,20 @@ pub struct ClassRelationship {
     pub marker_at_from: bool, // true = marker at 'from' end, false = marker at 'to' end
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ClassNamespace {
     pub name: String,
     pub class_ids: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ClassNote {
     pub text: String,
     /// If Some, this note is attached to a specific class
     pub for_class: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ClassDiagram {
     pub classes: Vec<ClassNode>,
     pub relationships: Vec<ClassRelationship>,
@@ -317,6 +546,12 @@ pub struct ClassDiagram {
     pub notes: Vec<ClassNote>,
     /// Layout direction: "TB" (default), "BT", "LR", "RL"
     pub direction: String,
+    /// Style properties per stereotype (annotation name without `<< >>`),
+    /// from the frontmatter's `stereotypeStyles:`. SVG boxes use `fill`,
+    /// `stroke`, `stroke-width` and `stroke-dasharray`; text output shows
+    /// `marker` (default: the stereotype's initial) before the class name.
+    #[serde(default, serialize_with = "sorted_styles")]
+    pub stereotype_styles: HashMap<String, HashMap<String, String>>,
 }
 
 impl Default for ClassDiagram {
@@ -333,6 +568,7 @@ impl ClassDiagram {
             namespaces: Vec::new(),
             notes: Vec::new(),
             direction: "TB".to_string(),
+            stereotype_styles: HashMap::new(),
         }
     }
 }
@@ -341,14 +577,14 @@ impl ClassDiagram {
 // ER diagram types
 // ============================================================================
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum ErKey {
     PK, // Primary Key
     FK, // Foreign Key
     UK, // Unique Key
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ErAttribute {
     pub attr_type: String,
     pub name: String,
@@ -356,14 +592,14 @@ pub struct ErAttribute {
     pub comment: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ErEntity {
     pub id: String,
     pub label: String,
     pub attributes: Vec<ErAttribute>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum Cardinality {
     One,      // ||   exactly one
     ZeroOne,  // o|   zero or one
@@ -382,7 +618,7 @@ impl Cardinality {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ErRelationship {
     pub entity1: String,
     pub entity2: String,
@@ -392,10 +628,27 @@ pub struct ErRelationship {
     pub identifying: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ErDiagram {
     pub entities: Vec<ErEntity>,
     pub relationships: Vec<ErRelationship>,
+    /// SVG layout from the frontmatter config (`layoutDirection:`)
+    #[serde(default)]
+    pub config: ErConfig,
+}
+
+/// Layout of SVG ER diagrams
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ErConfig {
+    /// Which way the entities are laid out. Default: LR, one row
+    pub layout_direction: Option<Direction>,
+}
+
+impl ErConfig {
+    pub fn layout_direction(&self) -> Direction {
+        self.layout_direction.unwrap_or(Direction::LR)
+    }
 }
 
 impl Default for ErDiagram {
@@ -409,6 +662,7 @@ impl ErDiagram {
         Self {
             entities: Vec::new(),
             relationships: Vec::new(),
+            config: ErConfig::default(),
         }
     }
 }
@@ -418,7 +672,7 @@ impl ErDiagram {
 // ============================================================================
 
 /// Direction of the git graph
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum GitGraphDirection {
     LR, // Left to Right (default, horizontal)
     TB, // Top to Bottom (vertical)
@@ -437,8 +691,29 @@ impl GitGraphDirection {
     }
 }
 
+/// How text output draws fork and merge lines between branch rows
+/// (`mergeStyle:` in the frontmatter config)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum GitMergeStyle {
+    /// `\` and `/` diagonals
+    #[default]
+    Diagonal,
+    /// Vertical lines with square elbows (`┐`, `┘`, `├`), Unicode only
+    Orthogonal,
+}
+
+impl GitMergeStyle {
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "diagonal" => Some(GitMergeStyle::Diagonal),
+            "orthogonal" => Some(GitMergeStyle::Orthogonal),
+            _ => None,
+        }
+    }
+}
+
 /// Type of commit (affects visual styling)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum CommitType {
     Normal,
     Reverse,
@@ -446,21 +721,28 @@ pub enum CommitType {
 }
 
 /// A commit in the git graph
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GitCommit {
     pub id: String, // Auto-generated (A, B, C...) or custom
     pub commit_type: CommitType,
     pub tag: Option<String>,
+    pub message: Option<String>, // From `msg:`, shown when showCommitMessage is set
     pub branch: String,          // Which branch this commit is on
     pub parent_ids: Vec<String>, // Parent commit IDs (1 for normal, 2 for merge)
     pub is_merge: bool,
     pub is_cherry_pick: bool,
     pub cherry_pick_source: Option<String>,
     pub cherry_pick_parent: Option<String>,
+    /// From `author:`, shown in the SVG tooltip (and label with showCommitMetadata)
+    #[serde(default)]
+    pub author: Option<String>,
+    /// From `date:`, shown like the author
+    #[serde(default)]
+    pub date: Option<String>,
 }
 
 /// A branch in the git graph
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GitBranch {
     pub name: String,
     pub order: Option<i32>,            // Custom ordering
@@ -469,7 +751,8 @@ pub struct GitBranch {
 }
 
 /// Configuration options parsed from YAML frontmatter
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct GitGraphConfig {
     /// Whether to show branch name labels. Default: true
     pub show_branches: bool,
@@ -479,8 +762,20 @@ pub struct GitGraphConfig {
     pub main_branch_name: String,
     /// Position of the main branch in the list of branches. Default: 0
     pub main_branch_order: Option<i32>,
+    /// Whether commit messages (`msg:`) are shown next to commits. Default: false
+    pub show_commit_message: bool,
+    /// Whether commit authors and dates (`author:`, `date:`) are shown under
+    /// the commit labels (SVG only). Default: false
+    pub show_commit_metadata: bool,
+    /// Whether commits with an author or date get a tooltip listing them
+    /// (SVG only). Default: true
+    pub show_commit_tooltip: bool,
+    /// Whether commits made at the same depth on different branches align. Default: false
+    pub parallel_commits: bool,
     /// Whether commit labels are rotated 45° (SVG only). Default: true
     pub rotate_commit_label: bool,
+    /// Fork and merge lines of horizontal text output. Default: diagonal
+    pub merge_style: GitMergeStyle,
     /// Theme name (base, forest, dark, default, neutral). Default: "default"
     pub theme: String,
     /// Branch colors (git0..git7)
@@ -510,9 +805,14 @@ impl Default for GitGraphConfig {
         Self {
             show_branches: true,
             show_commit_label: true,
+            show_commit_message: false,
+            show_commit_metadata: false,
+            show_commit_tooltip: true,
+            parallel_commits: false,
             main_branch_name: "main".to_string(),
             main_branch_order: None,
             rotate_commit_label: true,
+            merge_style: GitMergeStyle::Diagonal,
             theme: "default".to_string(),
             branch_colors: vec![None; 8],
             branch_label_colors: vec![None; 8],
@@ -529,7 +829,7 @@ impl Default for GitGraphConfig {
 }
 
 /// The complete parsed GitGraph
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GitGraph {
     pub direction: GitGraphDirection,
     pub commits: Vec<GitCommit>,
@@ -559,6 +859,74 @@ impl GitGraph {
             config,
         }
     }
+
+    /// Branches in display order: branches with an `order` attribute sorted
+    /// by it, then the rest in creation order. The main branch comes first
+    /// unless it was given an order itself.
+    pub fn branches_by_order(&self) -> Vec<&GitBranch> {
+        let main = self.config.main_branch_name.as_str();
+        let mut sorted: Vec<_> = self.branches.iter().enumerate().collect();
+        sorted.sort_by(|(ia, a), (ib, b)| match (a.order, b.order) {
+            (Some(ao), Some(bo)) => ao.cmp(&bo),
+            (Some(_), None) if b.name == main => std::cmp::Ordering::Greater,
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) if a.name == main => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => ia.cmp(ib),
+        });
+        sorted.into_iter().map(|(_, b)| b).collect()
+    }
+
+    /// Depth of each commit, in commit order: roots are 0, every other commit
+    /// sits one step after its deepest parent. Used by `parallelCommits` to
+    /// line up commits made "at the same time" on different branches.
+    pub fn commit_depths(&self) -> Vec<usize> {
+        let mut by_id: HashMap<&str, usize> = HashMap::new();
+        let mut depths = Vec::with_capacity(self.commits.len());
+        for commit in &self.commits {
+            let depth = commit
+                .parent_ids
+                .iter()
+                .filter_map(|p| by_id.get(p.as_str()))
+                .map(|d| d + 1)
+                .max()
+                .unwrap_or(0);
+            by_id.insert(&commit.id, depth);
+            depths.push(depth);
+        }
+        depths
+    }
+
+    /// Look up commits by id without searching the commit list each time
+    pub fn commit_index(&self) -> CommitIndex<'_> {
+        let mut positions = HashMap::with_capacity(self.commits.len());
+        for (i, commit) in self.commits.iter().enumerate() {
+            positions.entry(commit.id.as_str()).or_insert(i);
+        }
+        CommitIndex {
+            commits: &self.commits,
+            positions,
+        }
+    }
+}
+
+/// The commits of a [`GitGraph`] by id, built once per render
+#[derive(Debug, Clone)]
+pub struct CommitIndex<'a> {
+    commits: &'a [GitCommit],
+    positions: HashMap<&'a str, usize>,
+}
+
+impl<'a> CommitIndex<'a> {
+    /// The commit with `id`, the first one if several share it
+    pub fn get(&self, id: &str) -> Option<&'a GitCommit> {
+        self.position(id).map(|i| &self.commits[i])
+    }
+
+    /// Where the commit with `id` is in the commit list
+    pub fn position(&self, id: &str) -> Option<usize> {
+        self.positions.get(id).copied()
+    }
 }
 
 // ============================================================================
@@ -566,7 +934,7 @@ impl GitGraph {
 // ============================================================================
 
 /// Mermaid built-in theme names
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum MermaidTheme {
     /// Light theme (white background, dark text)
     Default,
@@ -593,17 +961,175 @@ impl std::fmt::Display for MermaidTheme {
     }
 }
 
+/// Layout engine used for SVG flowcharts (`layout:` in the frontmatter config)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum FlowchartLayout {
+    /// Character grid shared with the ASCII renderer
+    #[default]
+    Grid,
+    /// Layered (Sugiyama-style) layout computed in floating point
+    Layered,
+}
+
+impl FlowchartLayout {
+    /// `dagre` and `elk` both select the layered engine, as it is the closest
+    /// match to either
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "grid" => Some(FlowchartLayout::Grid),
+            "layered" | "dagre" | "elk" => Some(FlowchartLayout::Layered),
+            _ => None,
+        }
+    }
+}
+
+/// Shape of SVG flowchart edges (`curve:` in the frontmatter config)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum CurveStyle {
+    /// Straight segments between route points
+    #[default]
+    Linear,
+    /// Smooth B-spline through the route points
+    Basis,
+    /// Horizontal, then vertical steps between route points
+    StepAfter,
+}
+
+impl CurveStyle {
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "linear" => Some(CurveStyle::Linear),
+            "basis" => Some(CurveStyle::Basis),
+            "stepafter" => Some(CurveStyle::StepAfter),
+            _ => None,
+        }
+    }
+}
+
+/// How layered SVG flowcharts route their edges (`edgeRouting:` in the
+/// frontmatter config)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum EdgeRouting {
+    /// Straight segments through the ranks an edge crosses
+    #[default]
+    Polyline,
+    /// Right-angle segments along horizontal channels between ranks
+    Orthogonal,
+}
+
+impl EdgeRouting {
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "polyline" => Some(EdgeRouting::Polyline),
+            "orthogonal" => Some(EdgeRouting::Orthogonal),
+            _ => None,
+        }
+    }
+}
+
+/// Alignment of the lines of text inside flowchart nodes and class boxes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum LabelAlign {
+    Left,
+    Center,
+    Right,
+}
+
+impl LabelAlign {
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "left" => Some(LabelAlign::Left),
+            "center" | "centre" => Some(LabelAlign::Center),
+            "right" => Some(LabelAlign::Right),
+            _ => None,
+        }
+    }
+
+    /// Columns before text `width` wide on a line with `room` columns
+    pub fn offset(self, room: usize, width: usize) -> usize {
+        let spare = room.saturating_sub(width);
+        match self {
+            LabelAlign::Left => 0,
+            LabelAlign::Center => spare / 2,
+            LabelAlign::Right => spare,
+        }
+    }
+
+    /// The x and SVG `text-anchor` of text between `left` and `right`
+    pub fn anchor(self, left: f64, right: f64) -> (f64, &'static str) {
+        match self {
+            LabelAlign::Left => (left, "start"),
+            LabelAlign::Center => ((left + right) / 2.0, "middle"),
+            LabelAlign::Right => (right, "end"),
+        }
+    }
+}
+
+/// Characters text output is drawn with, for terminals and fonts with more or
+/// fewer glyphs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum Charset {
+    /// Box-drawing lines and geometric arrowheads (`┌─►`, `△`, `╲`)
+    Unicode,
+    /// Box-drawing lines with ASCII arrowheads, markers and diagonals
+    /// (`┌─>`, `^`, `\`), for fonts that only cover box drawing
+    SafeUnicode,
+    /// Plain ASCII (`+->`)
+    Ascii,
+}
+
+impl Charset {
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "unicode" => Some(Charset::Unicode),
+            "safe" | "safe-unicode" | "safeunicode" => Some(Charset::SafeUnicode),
+            "ascii" => Some(Charset::Ascii),
+            _ => None,
+        }
+    }
+
+    /// The ASCII stand-in for `c` if this charset lacks it
+    pub fn glyph(self, c: char) -> char {
+        if self != Charset::SafeUnicode {
+            return c;
+        }
+        match c {
+            '►' | '▶' | '▷' => '>',
+            '◄' | '◀' | '◁' => '<',
+            '▲' | '△' => '^',
+            '▼' | '▽' => 'v',
+            '◇' | '○' => 'o',
+            '◆' | '●' | '◢' | '◣' | '◤' | '◥' => '*',
+            '╱' => '/',
+            '╲' => '\\',
+            '╳' => 'X',
+            c => c,
+        }
+    }
+}
+
 // ============================================================================
 // Frontmatter configuration (common across all diagram types)
 // ============================================================================
 
 /// Common configuration extracted from YAML frontmatter
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct FrontmatterConfig {
     /// The theme to use for SVG rendering
     pub theme: MermaidTheme,
     /// Optional diagram title from frontmatter
     pub title: Option<String>,
+    /// Layout engine for SVG flowcharts
+    pub layout: FlowchartLayout,
+    /// Edge curve for SVG flowcharts, if set
+    pub curve: Option<CurveStyle>,
+    /// Edge routing for layered SVG flowcharts, if set
+    pub edge_routing: Option<EdgeRouting>,
+    /// Accessible title from an `accTitle:` statement
+    pub acc_title: Option<String>,
+    /// Accessible description from an `accDescr:` statement or `accDescr { ... }` block
+    pub acc_descr: Option<String>,
     /// Raw frontmatter lines (for diagram-specific parsers to inspect)
     pub raw_lines: Vec<String>,
 }
@@ -613,6 +1139,11 @@ impl Default for FrontmatterConfig {
         Self {
             theme: MermaidTheme::Default,
             title: None,
+            layout: FlowchartLayout::Grid,
+            curve: None,
+            edge_routing: None,
+            acc_title: None,
+            acc_descr: None,
             raw_lines: Vec::new(),
         }
     }
@@ -622,7 +1153,7 @@ impl Default for FrontmatterConfig {
 // Diagram type enum for dispatch
 // ============================================================================
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum DiagramType {
     Flowchart(MermaidGraph),
     Sequence(SequenceDiagram),
@@ -631,9 +1162,234 @@ pub enum DiagramType {
     GitGraph(GitGraph),
 }
 
+/// Kind of a Mermaid diagram, named by the first word of its header line
+/// (`graph TD`, `sequenceDiagram`, `pie title Pets`, ...). Only the
+/// [`SUPPORTED`](DiagramKind::SUPPORTED) kinds render; wrappers can hand the
+/// others to Mermaid itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagramKind {
+    Flowchart,
+    State,
+    Sequence,
+    Class,
+    Er,
+    GitGraph,
+    Pie,
+    Gantt,
+    Journey,
+    Mindmap,
+    Timeline,
+    QuadrantChart,
+    Requirement,
+    C4,
+    Sankey,
+    XyChart,
+    Block,
+    Packet,
+    Kanban,
+    Architecture,
+    Radar,
+    Treemap,
+    ZenUml,
+    /// A header no Mermaid diagram starts with
+    Unknown,
+}
+
+impl DiagramKind {
+    /// The kinds m2svg renders
+    pub const SUPPORTED: &'static [DiagramKind] = &[
+        DiagramKind::Flowchart,
+        DiagramKind::State,
+        DiagramKind::Sequence,
+        DiagramKind::Class,
+        DiagramKind::Er,
+        DiagramKind::GitGraph,
+    ];
+
+    /// The kind of diagram a header line starts, ignoring case, a trailing
+    /// `:` and the `-beta`/`-v2` suffixes
+    pub fn from_header(line: &str) -> Self {
+        let word = line
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(':')
+            .to_lowercase();
+        let word = word
+            .strip_suffix("-beta")
+            .or_else(|| word.strip_suffix("-v2"))
+            .unwrap_or(&word);
+        match word {
+            "graph" | "flowchart" | "flowchart-elk" => DiagramKind::Flowchart,
+            "statediagram" => DiagramKind::State,
+            "sequencediagram" => DiagramKind::Sequence,
+            "classdiagram" => DiagramKind::Class,
+            "erdiagram" => DiagramKind::Er,
+            "gitgraph" => DiagramKind::GitGraph,
+            "pie" => DiagramKind::Pie,
+            "gantt" => DiagramKind::Gantt,
+            "journey" => DiagramKind::Journey,
+            "mindmap" => DiagramKind::Mindmap,
+            "timeline" => DiagramKind::Timeline,
+            "quadrantchart" => DiagramKind::QuadrantChart,
+            "requirementdiagram" => DiagramKind::Requirement,
+            "c4context" | "c4container" | "c4component" | "c4dynamic" | "c4deployment" => {
+                DiagramKind::C4
+            }
+            "sankey" => DiagramKind::Sankey,
+            "xychart" => DiagramKind::XyChart,
+            "block" => DiagramKind::Block,
+            "packet" => DiagramKind::Packet,
+            "kanban" => DiagramKind::Kanban,
+            "architecture" => DiagramKind::Architecture,
+            "radar" => DiagramKind::Radar,
+            "treemap" => DiagramKind::Treemap,
+            "zenuml" => DiagramKind::ZenUml,
+            _ => DiagramKind::Unknown,
+        }
+    }
+
+    /// The header keyword of the kind, as Mermaid spells it
+    pub fn name(self) -> &'static str {
+        match self {
+            DiagramKind::Flowchart => "flowchart",
+            DiagramKind::State => "stateDiagram",
+            DiagramKind::Sequence => "sequenceDiagram",
+            DiagramKind::Class => "classDiagram",
+            DiagramKind::Er => "erDiagram",
+            DiagramKind::GitGraph => "gitGraph",
+            DiagramKind::Pie => "pie",
+            DiagramKind::Gantt => "gantt",
+            DiagramKind::Journey => "journey",
+            DiagramKind::Mindmap => "mindmap",
+            DiagramKind::Timeline => "timeline",
+            DiagramKind::QuadrantChart => "quadrantChart",
+            DiagramKind::Requirement => "requirementDiagram",
+            DiagramKind::C4 => "C4Context",
+            DiagramKind::Sankey => "sankey",
+            DiagramKind::XyChart => "xychart",
+            DiagramKind::Block => "block",
+            DiagramKind::Packet => "packet",
+            DiagramKind::Kanban => "kanban",
+            DiagramKind::Architecture => "architecture",
+            DiagramKind::Radar => "radar",
+            DiagramKind::Treemap => "treemap",
+            DiagramKind::ZenUml => "zenuml",
+            DiagramKind::Unknown => "unknown",
+        }
+    }
+
+    /// Whether m2svg renders diagrams of this kind
+    pub fn is_supported(self) -> bool {
+        Self::SUPPORTED.contains(&self)
+    }
+}
+
 /// Result of parsing a Mermaid diagram: the diagram itself plus frontmatter config
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ParsedDiagram {
     pub diagram: DiagramType,
+    #[serde(default)]
     pub frontmatter: FrontmatterConfig,
 }
+
+/// Version of the serialized diagram model, following semantic versioning.
+///
+/// Minor releases only add fields that have defaults, so any document with the
+/// same major version reads as-is. A new major version comes with an entry in
+/// `IR_UPGRADES` that rewrites documents from the previous one.
+pub const IR_VERSION: &str = "1.9.0";
+
+/// Upgrades between major IR versions; entry `n` turns a `n.x` document into
+/// `n+1.0`, so the current major version is the length of this list.
+const IR_UPGRADES: &[fn(&mut serde_json::Value)] = &[
+    // 0.x: `--emit-json` output from before the version field existed. The
+    // layout is the same as 1.0.
+    |_| {},
+];
+
+/// A serialized diagram: the model plus the IR version it was written with
+#[derive(Serialize, JsonSchema)]
+#[schemars(title = "m2svg diagram")]
+struct VersionedDiagram<'a> {
+    /// IR version (semver) the document was written with
+    version: &'a str,
+    #[serde(flatten)]
+    diagram: &'a ParsedDiagram,
+}
+
+impl ParsedDiagram {
+    /// Serialize the diagram model to pretty-printed JSON, tagged with [`IR_VERSION`].
+    pub fn to_json(&self) -> Result<String, String> {
+        let versioned = VersionedDiagram {
+            version: IR_VERSION,
+            diagram: self,
+        };
+        serde_json::to_string_pretty(&versioned)
+            .map_err(|e| format!("Failed to serialize diagram: {}", e))
+    }
+
+    /// Read a diagram model from JSON, as written by [`ParsedDiagram::to_json`].
+    ///
+    /// Documents from an older major IR version (including unversioned ones)
+    /// are upgraded first; documents from a newer major version are rejected.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let mut value: serde_json::Value =
+            serde_json::from_str(json).map_err(|e| format!("Invalid diagram JSON: {}", e))?;
+
+        let version = match value.get("version") {
+            None => "0.0.0".to_string(),
+            Some(serde_json::Value::String(v)) => v.clone(),
+            Some(v) => return Err(format!("Invalid diagram JSON: bad version {}", v)),
+        };
+        let major = version
+            .split('.')
+            .next()
+            .and_then(|m| m.parse::<usize>().ok())
+            .ok_or_else(|| format!("Invalid diagram JSON: bad version \"{}\"", version))?;
+        if major > IR_UPGRADES.len() {
+            return Err(format!(
+                "Diagram JSON has IR version {}, but this version of m2svg reads up to {}",
+                version, IR_VERSION
+            ));
+        }
+        for upgrade in &IR_UPGRADES[major..] {
+            upgrade(&mut value);
+        }
+
+        serde_json::from_value(value).map_err(|e| format!("Invalid diagram JSON: {}", e))
+    }
+
+    /// JSON Schema of the documents written by [`ParsedDiagram::to_json`].
+    pub fn json_schema() -> String {
+        let schema = schemars::schema_for!(VersionedDiagram);
+        serde_json::to_string_pretty(&schema).expect("schema serializes to JSON")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_mermaid;
+
+    #[test]
+    fn test_json_reads_older_and_rejects_newer_versions() {
+        let parsed = parse_mermaid("graph LR\n  A --> B").unwrap();
+        let mut value: serde_json::Value =
+            serde_json::from_str(&parsed.to_json().unwrap()).unwrap();
+        assert_eq!(value["version"], IR_VERSION);
+
+        // Unversioned documents predate the version field
+        value.as_object_mut().unwrap().remove("version");
+        let unversioned = ParsedDiagram::from_json(&value.to_string()).unwrap();
+        let reread: serde_json::Value =
+            serde_json::from_str(&unversioned.to_json().unwrap()).unwrap();
+        value["version"] = IR_VERSION.into();
+        assert_eq!(reread, value);
+
+        value["version"] = "99.0.0".into();
+        assert!(ParsedDiagram::from_json(&value.to_string())
+            .unwrap_err()
+            .contains("99.0.0"));
+    }
+}
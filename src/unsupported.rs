@@ -0,0 +1,179 @@
+//! Mermaid syntax that m2svg parses past without rendering
+//!
+//! The parsers skip statements they don't understand, which is right for
+//! rendering what they can, but leaves users guessing why a `linkStyle` had no
+//! effect. [`FEATURES`] lists the syntax that is known to be skipped or only
+//! partly drawn, and [`unsupported_features`] reports each use of it, so the
+//! gap is named instead of silent. Supporting a feature means taking it off
+//! the list.
+
+use crate::validate::{Diagnostic, Severity};
+
+/// A piece of Mermaid syntax that m2svg accepts but doesn't fully render
+pub struct UnsupportedFeature {
+    /// Name of the syntax, as users would search for it
+    pub name: &'static str,
+    /// What m2svg does with it instead
+    pub effect: &'static str,
+    /// Diagram headers (lowercase prefixes) it applies to; empty for all
+    diagrams: &'static [&'static str],
+    /// Whether a trimmed line of the diagram body uses it
+    matches: fn(&str) -> bool,
+}
+
+const FLOWCHART: &[&str] = &["graph", "flowchart"];
+const SEQUENCE: &[&str] = &["sequencediagram"];
+const STATE: &[&str] = &["statediagram"];
+const CLASS: &[&str] = &["classdiagram"];
+
+/// The known gaps, one entry per feature
+pub const FEATURES: &[UnsupportedFeature] = &[
+    UnsupportedFeature {
+        name: "init directive",
+        effect: "ignored; set options in the frontmatter instead",
+        diagrams: &[],
+        matches: |line| line.starts_with("%%{"),
+    },
+    UnsupportedFeature {
+        name: "linkStyle",
+        effect: "edge styles are ignored",
+        diagrams: FLOWCHART,
+        matches: |line| first_word(line) == "linkStyle",
+    },
+    UnsupportedFeature {
+        name: "click callback",
+        effect: "ignored; only `click` links to URLs are kept",
+        diagrams: FLOWCHART,
+        matches: |line| {
+            let mut words = line.split_whitespace();
+            words.next() == Some("click")
+                && words.nth(1).is_some_and(|action| {
+                    action != "href" && !action.starts_with('"') && !action.starts_with('\'')
+                })
+        },
+    },
+    UnsupportedFeature {
+        name: "node metadata (@{ ... })",
+        effect: "the shape and label in it are ignored",
+        diagrams: FLOWCHART,
+        matches: |line| line.contains("@{"),
+    },
+    UnsupportedFeature {
+        name: "autonumber",
+        effect: "messages are not numbered",
+        diagrams: SEQUENCE,
+        matches: |line| first_word(line) == "autonumber",
+    },
+    UnsupportedFeature {
+        name: "activation",
+        effect: "activation bars are not drawn",
+        diagrams: SEQUENCE,
+        matches: |line| matches!(first_word(line), "activate" | "deactivate"),
+    },
+    UnsupportedFeature {
+        name: "box",
+        effect: "participants are not grouped",
+        diagrams: SEQUENCE,
+        matches: |line| first_word(line) == "box",
+    },
+    UnsupportedFeature {
+        name: "create/destroy",
+        effect: "the participant is drawn for the whole diagram",
+        diagrams: SEQUENCE,
+        matches: |line| matches!(first_word(line), "create" | "destroy"),
+    },
+    UnsupportedFeature {
+        name: "participant links",
+        effect: "ignored",
+        diagrams: SEQUENCE,
+        matches: |line| matches!(first_word(line), "link" | "links"),
+    },
+    UnsupportedFeature {
+        name: "sequence blocks",
+        effect: "the messages are drawn without the block's frame and label",
+        diagrams: SEQUENCE,
+        matches: |line| {
+            matches!(
+                first_word(line),
+                "loop" | "alt" | "opt" | "par" | "critical" | "break" | "rect"
+            )
+        },
+    },
+    UnsupportedFeature {
+        name: "state notes",
+        effect: "ignored",
+        diagrams: STATE,
+        matches: |line| first_word(line) == "note",
+    },
+    UnsupportedFeature {
+        name: "fork, join and choice states",
+        effect: "drawn as ordinary states",
+        diagrams: STATE,
+        matches: |line| {
+            first_word(line) == "state"
+                && ["<<fork>>", "<<join>>", "<<choice>>"]
+                    .iter()
+                    .any(|kind| line.contains(kind))
+        },
+    },
+    UnsupportedFeature {
+        name: "concurrent states (--)",
+        effect: "the regions are merged into one",
+        diagrams: STATE,
+        matches: |line| line == "--",
+    },
+    UnsupportedFeature {
+        name: "class interaction",
+        effect: "`click`, `callback` and `link` statements are ignored",
+        diagrams: CLASS,
+        matches: |line| matches!(first_word(line), "click" | "callback" | "link"),
+    },
+];
+
+fn first_word(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
+/// Report each line of `input` that uses an entry of [`FEATURES`], as a
+/// warning naming the feature and what happens to it.
+///
+/// # Example
+/// ```rust
+/// use m2svg::unsupported::unsupported_features;
+///
+/// let warnings = unsupported_features("graph LR\n  A --> B\n  linkStyle 0 stroke:#f00");
+/// assert_eq!(warnings.len(), 1);
+/// assert_eq!(warnings[0].line, Some(3));
+/// assert_eq!(
+///     warnings[0].message,
+///     "Unsupported feature linkStyle: edge styles are ignored"
+/// );
+/// ```
+pub fn unsupported_features(input: &str) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = input.lines().map(str::trim).collect();
+    let (_, body) = crate::parser::parse_frontmatter(input);
+    let body_start = lines.len() - body.lines().count();
+    let header = lines[body_start..]
+        .iter()
+        .find(|line| !line.is_empty() && !line.starts_with("%%"))
+        .map(|line| line.to_lowercase())
+        .unwrap_or_default();
+
+    let mut diagnostics = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        // Only directives can come before the header
+        let in_body = i >= body_start;
+        for feature in FEATURES {
+            let applies = feature.diagrams.is_empty()
+                || (in_body && feature.diagrams.iter().any(|d| header.starts_with(d)));
+            if applies && (feature.matches)(line) {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    line: Some(i + 1),
+                    message: format!("Unsupported feature {}: {}", feature.name, feature.effect),
+                });
+            }
+        }
+    }
+    diagnostics
+}
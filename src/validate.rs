@@ -0,0 +1,399 @@
+//! Checking diagrams without rendering them (`m2svg check`)
+//!
+//! Mermaid accepts a lot that is probably a mistake: styling a node that no
+//! edge mentions, declaring a participant twice, a config key that nothing
+//! reads. [`validate`] parses a diagram and reports such problems, with the
+//! line they are on, so editors and CI can flag them before anyone looks at
+//! the output.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::parser::{self, parse_frontmatter, ParseMode};
+use crate::types::{ClassDiagram, DiagramType, MermaidGraph, NodeShape, ParsedDiagram};
+use crate::unsupported::unsupported_features;
+
+lazy_static! {
+    static ref RE_CLASS: Regex = Regex::new(r"^class\s+([\w,-]+)\s+(\w+)$").unwrap();
+    static ref RE_STYLE: Regex = Regex::new(r"^style\s+([\w,-]+)\s").unwrap();
+    static ref RE_CLICK: Regex = Regex::new(r"^click\s+(\w[\w-]*)").unwrap();
+    static ref RE_PARTICIPANT: Regex = Regex::new(r"^(?:participant|actor)\s+(\S+)").unwrap();
+    static ref RE_NOTE_FOR: Regex = Regex::new(r"^note\s+for\s+(\w+)").unwrap();
+    static ref RE_CONFIG_LINE: Regex = Regex::new(r"^([A-Za-z]\w*)\s*=").unwrap();
+    static ref RE_YAML_KEY: Regex = Regex::new(r#"^(?:-\s+)?['"]?([A-Za-z]\w*)['"]?\s*:"#).unwrap();
+}
+
+/// Frontmatter keys that something reads, lowercased; `gitN`, `gitBranchLabelN`
+/// and `gitInvN` are checked separately
+const FRONTMATTER_KEYS: &[&str] = &[
+    "config",
+    "class",
+    "flowchart",
+    "er",
+    "gitgraph",
+    "sequence",
+    "themevariables",
+    "stereotypestyles",
+    "title",
+    "theme",
+    "layout",
+    "curve",
+    "edgerouting",
+    "showbranches",
+    "showcommitlabel",
+    "showcommitmessage",
+    "showcommitmetadata",
+    "showcommittooltip",
+    "mainbranchname",
+    "mainbranchorder",
+    "rotatecommitlabel",
+    "parallelcommits",
+    "mergestyle",
+    "commitlabelcolor",
+    "commitlabelbackground",
+    "commitlabelfontsize",
+    "taglabelcolor",
+    "taglabelbackground",
+    "taglabelborder",
+    "taglabelfontsize",
+    "actormargin",
+    "messagemargin",
+    "boxmargin",
+    "mirroractors",
+    "nodespacing",
+    "rankspacing",
+    "bundleedges",
+    "swimlanes",
+    "layoutdirection",
+];
+
+/// `key=value` lines read by the text renderer, lowercased
+const CONFIG_LINE_KEYS: &[&str] = &[
+    "paddingx",
+    "paddingy",
+    "headerevery",
+    "maxgap",
+    "maxlabelwidth",
+    "wrapwidth",
+    "minnodewidth",
+    "maxnodewidth",
+    "uniformnodewidth",
+    "labelalign",
+    "maxwidth",
+    "charset",
+    "roundedcorners",
+];
+
+/// How serious a [`Diagnostic`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The diagram can't be rendered
+    Error,
+    /// The diagram renders, but probably not as intended
+    Warning,
+}
+
+/// A problem found by [`validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// 1-based line of the input, when the problem has one
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "{}: ", line)?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+/// Parse `input` and report what looks wrong with it, in line order.
+///
+/// A diagram that doesn't parse gets a single error. Otherwise the checks are:
+/// `class`, `style` and `click` statements naming nodes the flowchart doesn't
+/// have, or classes no `classDef` defines; participants declared twice; states
+/// that no transition from a start state `[*]` reaches; class relationships and
+/// notes naming classes the diagram never declares (when it declares any);
+/// frontmatter or `key=value` config lines that m2svg doesn't read; syntax
+/// that m2svg doesn't render (see [`crate::unsupported`]); and any other
+/// statement the parser skipped.
+///
+/// # Example
+/// ```rust
+/// use m2svg::validate::{validate, Severity};
+///
+/// let diagnostics = validate("graph LR\n  A --> B\n  style C fill:#f00");
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(diagnostics[0].severity, Severity::Warning);
+/// assert_eq!(diagnostics[0].line, Some(3));
+/// assert_eq!(diagnostics[0].to_string(), "3: warning: Undefined node C in style statement");
+/// ```
+pub fn validate(input: &str) -> Vec<Diagnostic> {
+    match parser::parse_mermaid_with_mode(input, ParseMode::Lenient) {
+        Ok((parsed, skipped)) => check_parsed(input, &parsed, skipped),
+        Err(message) => vec![Diagnostic {
+            severity: Severity::Error,
+            line: None,
+            message,
+        }],
+    }
+}
+
+/// The checks of [`validate`] on `input` as already parsed, with the warnings
+/// for the statements its parser skipped
+pub(crate) fn check_parsed(
+    input: &str,
+    parsed: &ParsedDiagram,
+    skipped: Vec<Diagnostic>,
+) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = input.lines().map(str::trim).collect();
+    let mut diagnostics = config_keys(input);
+
+    // Line numbers are those of the whole input, frontmatter included
+    let (_, body) = parse_frontmatter(input);
+    let offset = input.lines().count() - body.lines().count();
+    let body: Vec<(usize, &str)> = lines
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .map(|(i, line)| (i + 1, *line))
+        .collect();
+    let header = body
+        .iter()
+        .map(|(_, line)| line.to_lowercase())
+        .find(|line| !line.is_empty() && !line.starts_with("%%"))
+        .unwrap_or_default();
+
+    match &parsed.diagram {
+        DiagramType::Flowchart(graph) if header.starts_with("statediagram") => {
+            diagnostics.extend(unreachable_states(graph, &body));
+        }
+        DiagramType::Flowchart(graph) => diagnostics.extend(flowchart_references(graph, &body)),
+        DiagramType::Sequence(_) => diagnostics.extend(duplicate_participants(&body)),
+        DiagramType::Class(diagram) => diagnostics.extend(class_notes(diagram, &body)),
+        DiagramType::Er(_) | DiagramType::GitGraph(_) => {}
+    }
+    // A skipped statement that is a known gap is reported as that
+    let unsupported = unsupported_features(input);
+    diagnostics.extend(
+        skipped
+            .into_iter()
+            .filter(|d| !unsupported.iter().any(|u| u.line == d.line)),
+    );
+    diagnostics.extend(unsupported);
+
+    diagnostics.sort_by_key(|d| d.line);
+    diagnostics
+}
+
+fn warning(line: usize, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Warning,
+        line: Some(line),
+        message,
+    }
+}
+
+/// First line of `body` where `word` appears as a whole word
+fn find_word(body: &[(usize, &str)], word: &str) -> Option<usize> {
+    let re = Regex::new(&format!(r"(^|[^\w]){}([^\w]|$)", regex::escape(word))).ok()?;
+    body.iter()
+        .find(|(_, line)| re.is_match(line))
+        .map(|(n, _)| *n)
+}
+
+/// Frontmatter keys and `key=value` lines that nothing reads
+fn config_keys(input: &str) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = input.lines().collect();
+    let mut diagnostics = Vec::new();
+    let is_git_color = |key: &str| {
+        ["git", "gitbranchlabel", "gitinv"].iter().any(|prefix| {
+            key.strip_prefix(prefix)
+                .is_some_and(|n| n.len() == 1 && n.as_bytes()[0].is_ascii_digit())
+        })
+    };
+
+    // Frontmatter, between the opening `---` and the closing one
+    let mut body_start = 0;
+    let first = lines.iter().position(|l| !l.trim().is_empty());
+    if let Some(start) = first.filter(|&i| lines[i].trim() == "---") {
+        if let Some(len) = lines[start + 1..].iter().position(|l| l.trim() == "---") {
+            body_start = start + len + 2;
+            // Stereotype names under `stereotypeStyles:` are the user's own
+            let mut user_keys_deeper_than: Option<usize> = None;
+            for (i, line) in lines
+                .iter()
+                .enumerate()
+                .take(start + len + 1)
+                .skip(start + 1)
+            {
+                let Some(caps) = RE_YAML_KEY.captures(line.trim()) else {
+                    continue;
+                };
+                let indent = line.len() - line.trim_start().len();
+                match user_keys_deeper_than {
+                    Some(depth) if indent > depth => continue,
+                    _ => user_keys_deeper_than = None,
+                }
+                let key = caps[1].to_lowercase();
+                if key == "stereotypestyles" {
+                    user_keys_deeper_than = Some(indent);
+                }
+                if !FRONTMATTER_KEYS.contains(&key.as_str()) && !is_git_color(&key) {
+                    diagnostics.push(warning(
+                        i + 1,
+                        format!("Unknown config key {} (ignored)", &caps[1]),
+                    ));
+                }
+            }
+        }
+    }
+
+    for (i, line) in lines.iter().enumerate().skip(body_start) {
+        let line = line.trim();
+        if line.contains("--")
+            || line.contains("->")
+            || line.contains("==")
+            || line.starts_with("click ")
+        {
+            continue;
+        }
+        if let Some(caps) = RE_CONFIG_LINE.captures(line) {
+            if !CONFIG_LINE_KEYS.contains(&caps[1].to_lowercase().as_str()) {
+                diagnostics.push(warning(
+                    i + 1,
+                    format!("Unknown config key {} (ignored)", &caps[1]),
+                ));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// `class`, `style` and `click` statements naming nodes (or subgraphs) that
+/// don't exist, and classes that no `classDef` defines
+fn flowchart_references(graph: &MermaidGraph, body: &[(usize, &str)]) -> Vec<Diagnostic> {
+    let mut subgraph_ids = HashSet::new();
+    let mut stack: Vec<_> = graph.subgraphs.iter().collect();
+    while let Some(sg) = stack.pop() {
+        subgraph_ids.insert(sg.id.as_str());
+        stack.extend(&sg.children);
+    }
+    let exists = |id: &str| graph.nodes.contains_key(id) || subgraph_ids.contains(id);
+
+    let mut diagnostics = Vec::new();
+    for &(n, line) in body {
+        let (statement, ids) = if let Some(caps) = RE_CLASS.captures(line) {
+            if !graph.class_defs.contains_key(&caps[2]) && &caps[2] != "default" {
+                diagnostics.push(warning(n, format!("Class {} has no classDef", &caps[2])));
+            }
+            ("class", caps[1].to_string())
+        } else if let Some(caps) = RE_STYLE.captures(line) {
+            ("style", caps[1].to_string())
+        } else if let Some(caps) = RE_CLICK.captures(line) {
+            ("click", caps[1].to_string())
+        } else {
+            continue;
+        };
+        for id in ids.split(',').map(str::trim).filter(|id| !exists(id)) {
+            diagnostics.push(warning(
+                n,
+                format!("Undefined node {} in {} statement", id, statement),
+            ));
+        }
+    }
+    diagnostics
+}
+
+/// Participants declared more than once
+fn duplicate_participants(body: &[(usize, &str)]) -> Vec<Diagnostic> {
+    let mut declared: HashMap<&str, usize> = HashMap::new();
+    let mut diagnostics = Vec::new();
+    for &(n, line) in body {
+        let Some(id) = RE_PARTICIPANT.captures(line).and_then(|c| c.get(1)) else {
+            continue;
+        };
+        match declared.get(id.as_str()) {
+            Some(first) => diagnostics.push(warning(
+                n,
+                format!(
+                    "Participant {} is already declared on line {}",
+                    id.as_str(),
+                    first
+                ),
+            )),
+            None => {
+                declared.insert(id.as_str(), n);
+            }
+        }
+    }
+    diagnostics
+}
+
+/// States that no transition from a start state reaches
+fn unreachable_states(graph: &MermaidGraph, body: &[(usize, &str)]) -> Vec<Diagnostic> {
+    let mut reached: HashSet<&str> = graph
+        .nodes
+        .values()
+        .filter(|node| node.shape == NodeShape::StateStart)
+        .map(|node| node.id.as_str())
+        .collect();
+    if reached.is_empty() {
+        return Vec::new();
+    }
+    let mut queue: VecDeque<&str> = reached.iter().copied().collect();
+    while let Some(id) = queue.pop_front() {
+        for edge in graph.edges.iter().filter(|e| e.source == id) {
+            if reached.insert(edge.target.as_str()) {
+                queue.push_back(&edge.target);
+            }
+        }
+    }
+
+    // Composite states are entered through their own start states
+    let mut composites = HashSet::new();
+    let mut stack: Vec<_> = graph.subgraphs.iter().collect();
+    while let Some(sg) = stack.pop() {
+        composites.insert(sg.id.as_str());
+        stack.extend(&sg.children);
+    }
+
+    graph
+        .node_order
+        .iter()
+        .filter(|id| !reached.contains(id.as_str()) && !composites.contains(id.as_str()))
+        .filter(|id| graph.nodes[*id].shape != NodeShape::StateEnd)
+        .filter_map(|id| {
+            let n = find_word(body, id)?;
+            Some(warning(
+                n,
+                format!("State {} can't be reached from a start state", id),
+            ))
+        })
+        .collect()
+}
+
+/// Notes for classes that the diagram doesn't have. Relationships declare
+/// the classes they name, as in Mermaid, so only notes are checked.
+fn class_notes(diagram: &ClassDiagram, body: &[(usize, &str)]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for &(n, line) in body {
+        if let Some(caps) = RE_NOTE_FOR.captures(line) {
+            if !diagram.classes.iter().any(|c| c.id == caps[1]) {
+                diagnostics.push(warning(n, format!("Undefined class {} in note", &caps[1])));
+            }
+        }
+    }
+    diagnostics
+}
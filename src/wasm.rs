@@ -0,0 +1,53 @@
+//! WebAssembly bindings (the `wasm` feature)
+//!
+//! Exports `render_ascii`, `render_svg` and `layout_json` to JavaScript through wasm-bindgen,
+//! so browsers and Node can render diagrams without mermaid.js. Options come
+//! as a JSON object with the camelCase names of the render option fields; an
+//! empty string or `{}` means the defaults. Errors are thrown as `Error`s.
+//!
+//! ```js
+//! import init, { render_svg } from "./pkg/m2svg.js";
+//!
+//! await init();
+//! const svg = render_svg("graph LR\n  A --> B", '{"curve": "basis"}');
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use crate::json_options::{ascii_options, svg_options};
+
+/// Render a Mermaid diagram to ASCII/Unicode text.
+///
+/// `opts_json` may set `useAscii`, `charset` (`unicode`, `safe` or `ascii`),
+/// `roundedCorners`, `paddingX`, `paddingY`, `boxBorderPadding`,
+/// `sequenceHeaderEvery`, `sequenceMaxGap`, `maxLabelWidth` and `wrapWidth`.
+#[wasm_bindgen]
+pub fn render_ascii(input: &str, opts_json: &str) -> Result<String, JsError> {
+    let opts = ascii_options(opts_json).map_err(|e| JsError::new(&e))?;
+    crate::render_mermaid_ascii(input, Some(opts)).map_err(|e| JsError::new(&e))
+}
+
+/// Render a Mermaid diagram to SVG.
+///
+/// `opts_json` may set `curve` (`linear`, `basis` or `stepAfter`), `routing`
+/// (`polyline` or `orthogonal`), `maxLabelWidth`, `wrapWidth`, `fonts` (an
+/// array of font families), `systemFonts` (to fall back to fonts every system
+/// has instead of importing one), `sequence` (sequence diagram spacing:
+/// `actorMargin`, `messageMargin`, `boxMargin` and `mirrorActors`),
+/// `fragmentNamespace` (to embed the SVG in a page next to others) and the
+/// size: `responsive`, `width`/`height` or `maxWidth`.
+#[wasm_bindgen]
+pub fn render_svg(input: &str, opts_json: &str) -> Result<String, JsError> {
+    let opts = svg_options(opts_json).map_err(|e| JsError::new(&e))?;
+    crate::render_mermaid_svg(input, Some(opts)).map_err(|e| JsError::new(&e))
+}
+
+/// Lay out a Mermaid flowchart and return its node positions and edge routes
+/// as JSON, as `layout_to_json` does.
+///
+/// `opts_json` takes the same options as `render_svg`.
+#[wasm_bindgen]
+pub fn layout_json(input: &str, opts_json: &str) -> Result<String, JsError> {
+    let opts = svg_options(opts_json).map_err(|e| JsError::new(&e))?;
+    crate::layout_to_json(input, Some(opts)).map_err(|e| JsError::new(&e))
+}
@@ -0,0 +1,103 @@
+//! Word-wrapping of long node labels
+//!
+//! Used by the `wrap_width` render options and the maximum of their
+//! `node_width`: a flowchart node label wider than the limit is broken onto
+//! several lines at spaces, so the box grows taller instead of wider. Lines are joined with `\n`, which the renderers draw as
+//! separate rows of the label.
+
+use std::borrow::Cow;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::ascii::canvas::text_width;
+use crate::types::{DiagramType, ParsedDiagram};
+
+/// Wrap the node labels of `parsed` that are wider than `max_width` columns.
+///
+/// A `max_width` of 0 means no limit. Other diagram types are returned as-is.
+pub fn wrap_diagram(parsed: &ParsedDiagram, max_width: usize) -> Cow<'_, ParsedDiagram> {
+    if max_width == 0 {
+        return Cow::Borrowed(parsed);
+    }
+    wrap_diagram_with(parsed, max_width as f64, |text| text_width(text) as f64)
+}
+
+/// Wrap the node labels of `parsed` that are wider than `max_width`, as
+/// measured by `width`
+pub(crate) fn wrap_diagram_with(
+    parsed: &ParsedDiagram,
+    max_width: f64,
+    width: impl Fn(&str) -> f64,
+) -> Cow<'_, ParsedDiagram> {
+    let DiagramType::Flowchart(graph) = &parsed.diagram else {
+        return Cow::Borrowed(parsed);
+    };
+    let too_wide = |label: &String| width(label) > max_width;
+    if !graph.nodes.values().any(|n| too_wide(&n.label)) {
+        return Cow::Borrowed(parsed);
+    }
+
+    let mut graph = graph.clone();
+    for node in graph.nodes.values_mut() {
+        if too_wide(&node.label) {
+            node.label = wrap_text_with(&node.label, max_width, &width).join("\n");
+        }
+    }
+    Cow::Owned(ParsedDiagram {
+        diagram: DiagramType::Flowchart(graph),
+        frontmatter: parsed.frontmatter.clone(),
+    })
+}
+
+/// Break `text` into lines at most `max_width` columns wide, at spaces where
+/// possible. A word wider than the limit is split between grapheme clusters.
+///
+/// # Example
+/// ```rust
+/// use m2svg::wrap::wrap_text;
+///
+/// assert_eq!(wrap_text("Validate the incoming order", 12), ["Validate the", "incoming", "order"]);
+/// assert_eq!(wrap_text("Supercalifragilistic", 8), ["Supercal", "ifragili", "stic"]);
+/// ```
+pub fn wrap_text(text: &str, max_width: usize) -> Vec<String> {
+    wrap_text_with(text, max_width.max(1) as f64, |text| {
+        text_width(text) as f64
+    })
+}
+
+/// Break `text` into lines at most `max_width` wide, as measured by `width`
+fn wrap_text_with(text: &str, max_width: f64, width: impl Fn(&str) -> f64) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word = word;
+        // Start a new line when the word doesn't fit after a space
+        if !line.is_empty() && width(&format!("{} {}", line, word)) > max_width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        // Split words wider than a whole line
+        while width(&format!("{}{}", line, word)) > max_width {
+            let fits = word
+                .grapheme_indices(true)
+                .map(|(i, cluster)| i + cluster.len())
+                .take_while(|&end| width(&format!("{}{}", line, &word[..end])) <= max_width)
+                .last()
+                .unwrap_or(0);
+            let head = match &word[..fits] {
+                "" if line.is_empty() => word.graphemes(true).next().unwrap_or_default(),
+                head => head,
+            };
+            line.push_str(head);
+            lines.push(std::mem::take(&mut line));
+            word = &word[head.len()..];
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
@@ -138,20 +138,38 @@ ascii_test!(flowchart, back_reference_from_child);
 ascii_test!(flowchart, backlink_from_bottom);
 ascii_test!(flowchart, backlink_from_top);
 ascii_test!(flowchart, backlink_with_short_y_padding);
+ascii_test!(flowchart, bidirectional_back_edges);
+ascii_test!(flowchart, bundled_edges);
 ascii_test!(flowchart, comments);
+ascii_test!(flowchart, cycle_entered_later);
+ascii_test!(flowchart, crossing_reduction);
 ascii_test!(flowchart, custom_padding);
 ascii_test!(flowchart, duplicate_labels);
+ascii_test!(flowchart, edge_end_labels);
+ascii_test!(flowchart, edge_end_labels_td);
+ascii_test!(flowchart, edge_markers);
+ascii_test!(flowchart, edge_markers_td);
 ascii_test!(flowchart, flowchart_tb_simple);
 ascii_test!(flowchart, graph_bt_direction);
 ascii_test!(flowchart, graph_tb_direction);
+ascii_test!(flowchart, link_weights);
+ascii_test!(flowchart, long_edge);
+ascii_test!(flowchart, long_edge_td);
 ascii_test!(flowchart, nested_subgraphs_with_labels);
+ascii_test!(flowchart, node_ports);
+ascii_test!(flowchart, node_ports_lr);
+ascii_test!(flowchart, node_shapes);
+ascii_test!(flowchart, options_directive);
 ascii_test!(flowchart, preserve_order_of_definition);
 ascii_test!(flowchart, self_reference);
 ascii_test!(flowchart, self_reference_with_edge);
+ascii_test!(flowchart, shared_corridor);
 ascii_test!(flowchart, single_node);
 ascii_test!(flowchart, single_node_longer_name);
 ascii_test!(flowchart, subgraph_complex_mixed);
 ascii_test!(flowchart, subgraph_complex_nested);
+ascii_test!(flowchart, subgraph_direction_lr);
+ascii_test!(flowchart, subgraph_direction_td);
 ascii_test!(flowchart, subgraph_empty);
 ascii_test!(flowchart, subgraph_mixed_nodes);
 ascii_test!(flowchart, subgraph_mixed_nodes_td);
@@ -173,8 +191,15 @@ ascii_test!(flowchart, three_nodes_single_line);
 ascii_test!(flowchart, two_layer_single_graph);
 ascii_test!(flowchart, two_layer_single_graph_longer_names);
 ascii_test!(flowchart, title_frontmatter);
+ascii_test!(flowchart, truncated_labels);
+ascii_test!(flowchart, wrapped_labels);
+ascii_test!(flowchart, uniform_node_width);
+ascii_test!(flowchart, label_align);
+ascii_test!(flowchart, max_width);
+ascii_test!(flowchart, swimlanes);
 ascii_test!(flowchart, two_nodes_linked);
 ascii_test!(flowchart, two_nodes_longer_names);
+ascii_test!(flowchart, wide_labels);
 ascii_test!(flowchart, two_root_nodes);
 ascii_test!(flowchart, two_root_nodes_longer_names);
 ascii_test!(flowchart, two_single_root_nodes);
@@ -198,6 +223,9 @@ ascii_test!(class, cls_lollipop);
 ascii_test!(class, cls_methods);
 ascii_test!(class, cls_notes);
 ascii_test!(class, cls_separate_annotation);
+ascii_test!(class, cls_self_reference);
+ascii_test!(class, cls_node_width);
+ascii_test!(class, cls_label_align);
 
 // =============================================================================
 // ASCII ER diagram tests
@@ -207,14 +235,17 @@ ascii_test!(er, er_attributes);
 ascii_test!(er, er_basic);
 ascii_test!(er, er_identifying);
 ascii_test!(er, er_multi_relationship);
+ascii_test!(er, er_self_reference);
 
 // =============================================================================
 // ASCII Sequence diagram tests
 // =============================================================================
 
 ascii_test!(sequence, seq_basic);
+ascii_test!(sequence, seq_header_every);
 ascii_test!(sequence, seq_multiple_messages);
 ascii_test!(sequence, seq_self_message);
+ascii_test!(sequence, seq_wrapped_labels);
 
 // =============================================================================
 // ASCII GitGraph tests
@@ -224,16 +255,23 @@ ascii_test!(gitgraph, gitgraph_basic);
 ascii_test!(gitgraph, gitgraph_bottom_to_top);
 ascii_test!(gitgraph, gitgraph_branch_order);
 ascii_test!(gitgraph, gitgraph_branches);
+ascii_test!(gitgraph, gitgraph_interleaved_branches);
 ascii_test!(gitgraph, gitgraph_cherry_pick);
+ascii_test!(gitgraph, gitgraph_cherry_pick_multi);
 ascii_test!(gitgraph, gitgraph_commit_types);
 ascii_test!(gitgraph, gitgraph_complex);
 ascii_test!(gitgraph, gitgraph_custom_ids);
 ascii_test!(gitgraph, gitgraph_merge_complex);
 ascii_test!(gitgraph, gitgraph_tags);
 ascii_test!(gitgraph, gitgraph_vertical);
+ascii_test!(gitgraph, gitgraph_vertical_complex);
 ascii_test!(gitgraph, gitgraph_no_branches);
 ascii_test!(gitgraph, gitgraph_no_commit_labels);
 ascii_test!(gitgraph, gitgraph_custom_main_name);
+ascii_test!(gitgraph, gitgraph_commit_messages);
+ascii_test!(gitgraph, gitgraph_commit_messages_vertical);
+ascii_test!(gitgraph, gitgraph_parallel_commits);
+ascii_test!(gitgraph, gitgraph_parallel_commits_vertical);
 
 // =============================================================================
 // Unicode Flowchart tests
@@ -246,14 +284,35 @@ unicode_test!(flowchart, ampersand_without_edge);
 unicode_test!(flowchart, back_reference_from_child);
 unicode_test!(flowchart, backlink_from_bottom);
 unicode_test!(flowchart, backlink_from_top);
+unicode_test!(flowchart, bidirectional_back_edges);
+unicode_test!(flowchart, bundled_edges);
 unicode_test!(flowchart, comments);
+unicode_test!(flowchart, cycle_entered_later);
+unicode_test!(flowchart, crossing_reduction);
 unicode_test!(flowchart, duplicate_labels);
+unicode_test!(flowchart, edge_end_labels);
+unicode_test!(flowchart, edge_end_labels_td);
+unicode_test!(flowchart, chained_edge_labels);
+unicode_test!(flowchart, edge_markers);
+unicode_test!(flowchart, edge_markers_td);
+unicode_test!(flowchart, emphasized_nodes);
 unicode_test!(flowchart, graph_bt_direction);
+unicode_test!(flowchart, link_weights);
+unicode_test!(flowchart, long_edge);
+unicode_test!(flowchart, long_edge_td);
+unicode_test!(flowchart, node_ports);
+unicode_test!(flowchart, node_ports_lr);
+unicode_test!(flowchart, node_shapes);
 unicode_test!(flowchart, preserve_order_of_definition);
+unicode_test!(flowchart, safe_charset);
 unicode_test!(flowchart, self_reference);
 unicode_test!(flowchart, self_reference_with_edge);
+unicode_test!(flowchart, shared_corridor);
 unicode_test!(flowchart, single_node);
 unicode_test!(flowchart, single_node_longer_name);
+unicode_test!(flowchart, square_corners);
+unicode_test!(flowchart, subgraph_direction_lr);
+unicode_test!(flowchart, subgraph_direction_td);
 unicode_test!(flowchart, three_nodes);
 unicode_test!(flowchart, three_nodes_single_line);
 unicode_test!(flowchart, two_layer_single_graph);
@@ -263,7 +322,16 @@ unicode_test!(flowchart, two_nodes_longer_names);
 unicode_test!(flowchart, two_root_nodes);
 unicode_test!(flowchart, two_root_nodes_longer_names);
 unicode_test!(flowchart, title_frontmatter);
+unicode_test!(flowchart, truncated_labels);
+unicode_test!(flowchart, wrapped_labels);
+unicode_test!(flowchart, uniform_node_width);
+unicode_test!(flowchart, label_align);
+unicode_test!(flowchart, max_width);
+unicode_test!(flowchart, swimlanes);
+unicode_test!(flowchart, subgraph_interleaved);
+unicode_test!(flowchart, route_bend_penalty);
 unicode_test!(flowchart, two_single_root_nodes);
+unicode_test!(flowchart, wide_labels);
 
 // =============================================================================
 // Unicode Class diagram tests
@@ -284,6 +352,11 @@ unicode_test!(class, cls_lollipop);
 unicode_test!(class, cls_methods);
 unicode_test!(class, cls_notes);
 unicode_test!(class, cls_separate_annotation);
+unicode_test!(class, cls_self_reference);
+unicode_test!(class, cls_node_width);
+unicode_test!(class, cls_label_align);
+unicode_test!(class, cls_stereotype_styles);
+unicode_test!(class, cls_safe_charset);
 
 // =============================================================================
 // Unicode ER diagram tests
@@ -293,14 +366,20 @@ unicode_test!(er, er_attributes);
 unicode_test!(er, er_basic);
 unicode_test!(er, er_identifying);
 unicode_test!(er, er_multi_relationship);
+unicode_test!(er, er_self_reference);
 
 // =============================================================================
 // Unicode Sequence diagram tests
 // =============================================================================
 
 unicode_test!(sequence, seq_basic);
+unicode_test!(sequence, seq_header_every);
 unicode_test!(sequence, seq_multiple_messages);
 unicode_test!(sequence, seq_self_message);
+unicode_test!(sequence, seq_wide_labels);
+unicode_test!(sequence, seq_notes);
+unicode_test!(sequence, seq_participant_order);
+unicode_test!(sequence, seq_wrapped_labels);
 
 // =============================================================================
 // Unicode GitGraph tests
@@ -310,16 +389,24 @@ unicode_test!(gitgraph, gitgraph_basic);
 unicode_test!(gitgraph, gitgraph_bottom_to_top);
 unicode_test!(gitgraph, gitgraph_branch_order);
 unicode_test!(gitgraph, gitgraph_branches);
+unicode_test!(gitgraph, gitgraph_interleaved_branches);
 unicode_test!(gitgraph, gitgraph_cherry_pick);
+unicode_test!(gitgraph, gitgraph_cherry_pick_multi);
 unicode_test!(gitgraph, gitgraph_commit_types);
 unicode_test!(gitgraph, gitgraph_complex);
 unicode_test!(gitgraph, gitgraph_custom_ids);
 unicode_test!(gitgraph, gitgraph_merge_complex);
 unicode_test!(gitgraph, gitgraph_tags);
 unicode_test!(gitgraph, gitgraph_vertical);
+unicode_test!(gitgraph, gitgraph_vertical_complex);
 unicode_test!(gitgraph, gitgraph_no_branches);
 unicode_test!(gitgraph, gitgraph_no_commit_labels);
 unicode_test!(gitgraph, gitgraph_custom_main_name);
+unicode_test!(gitgraph, gitgraph_commit_messages);
+unicode_test!(gitgraph, gitgraph_commit_messages_vertical);
+unicode_test!(gitgraph, gitgraph_parallel_commits);
+unicode_test!(gitgraph, gitgraph_parallel_commits_vertical);
+unicode_test!(gitgraph, gitgraph_orthogonal_merges);
 
 // =============================================================================
 // SVG tests
@@ -557,25 +644,45 @@ svg_test!(class, class_generics);
 svg_test!(class, class_inheritance);
 svg_test!(class, class_namespace);
 svg_test!(class, class_relationships);
+svg_test!(class, class_self_reference);
+svg_test!(class, class_stereotype_styles);
 svg_test!(er, er_attributes);
 svg_test!(er, er_basic);
+svg_test!(er, er_layout_direction);
 svg_test!(er, er_order_system);
+svg_test!(er, er_self_reference);
 svg_test!(er, er_zero_or_one);
 svg_test!(flowchart, flowchart_arrow_link);
 svg_test!(flowchart, flowchart_basic_node);
+svg_test!(flowchart, flowchart_bundled_edges);
 svg_test!(flowchart, flowchart_chaining);
 svg_test!(flowchart, flowchart_circle);
+svg_test!(flowchart, flowchart_class_def);
+svg_test!(flowchart, flowchart_click);
 svg_test!(flowchart, flowchart_comprehensive);
+svg_test!(flowchart, flowchart_curve_basis);
+svg_test!(flowchart, flowchart_curve_step_after);
 svg_test!(flowchart, flowchart_cylinder);
 svg_test!(flowchart, flowchart_decision_tree);
 svg_test!(flowchart, flowchart_diamond);
 svg_test!(flowchart, flowchart_dotted_link);
 svg_test!(flowchart, flowchart_double_circle);
+svg_test!(flowchart, flowchart_edge_end_labels);
+svg_test!(flowchart, flowchart_edge_class);
 svg_test!(flowchart, flowchart_flag);
 svg_test!(flowchart, flowchart_hexagon);
+svg_test!(flowchart, flowchart_layered);
+svg_test!(flowchart, flowchart_layered_bundled_edges);
+svg_test!(flowchart, flowchart_layered_cycle);
+svg_test!(flowchart, flowchart_layered_edge_class);
+svg_test!(flowchart, flowchart_layered_shapes);
+svg_test!(flowchart, flowchart_layered_spacing);
+svg_test!(flowchart, flowchart_layered_subgraphs);
+svg_test!(flowchart, flowchart_orthogonal_routing);
 svg_test!(flowchart, flowchart_link_with_text);
 svg_test!(flowchart, flowchart_loop_back);
 svg_test!(flowchart, flowchart_lr_direction);
+svg_test!(flowchart, flowchart_node_ports);
 svg_test!(flowchart, flowchart_node_with_text);
 svg_test!(flowchart, flowchart_parallel_links);
 svg_test!(flowchart, flowchart_round_edges);
@@ -583,9 +690,12 @@ svg_test!(flowchart, flowchart_stadium);
 svg_test!(flowchart, flowchart_styling);
 svg_test!(flowchart, flowchart_subgraphs);
 svg_test!(flowchart, flowchart_subroutine);
+svg_test!(flowchart, flowchart_swimlanes);
 svg_test!(flowchart, flowchart_td_direction);
+svg_test!(flowchart, flowchart_thick_bidirectional_link);
 svg_test!(flowchart, flowchart_thick_link);
 svg_test!(flowchart, flowchart_title);
+svg_test!(flowchart, flowchart_accessibility);
 svg_test!(flowchart, flowchart_trapezoid);
 svg_test!(sequence, sequence_activation);
 svg_test!(sequence, sequence_actors);
@@ -598,6 +708,7 @@ svg_test!(sequence, sequence_loop);
 svg_test!(sequence, sequence_notes);
 svg_test!(sequence, sequence_parallel);
 svg_test!(sequence, sequence_participants);
+svg_test!(sequence, sequence_participant_order);
 svg_test!(sequence, sequence_rect);
 svg_test!(sequence, sequence_stacked_activation);
 
@@ -611,9 +722,777 @@ svg_test!(gitgraph, gitgraph_complex);
 svg_test!(gitgraph, gitgraph_custom_ids);
 svg_test!(gitgraph, gitgraph_merge_complex);
 svg_test!(gitgraph, gitgraph_tags);
+svg_test!(gitgraph, gitgraph_theme_variables);
+svg_test!(gitgraph, gitgraph_title);
 svg_test!(gitgraph, gitgraph_vertical);
 svg_test!(gitgraph, gitgraph_no_branches);
 svg_test!(gitgraph, gitgraph_no_commit_labels);
 svg_test!(gitgraph, gitgraph_custom_main_name);
+svg_test!(gitgraph, gitgraph_commit_messages);
+svg_test!(gitgraph, gitgraph_commit_messages_vertical);
+svg_test!(gitgraph, gitgraph_commit_metadata);
+svg_test!(gitgraph, gitgraph_parallel_commits);
+svg_test!(gitgraph, gitgraph_parallel_commits_vertical);
 
 // Legacy SVG tests (kept for backwards compatibility)
+
+// =============================================================================
+// Determinism
+// =============================================================================
+
+/// The input of every fixture, text and SVG alike
+fn fixture_inputs() -> Vec<(PathBuf, String)> {
+    fn walk(dir: &std::path::Path, found: &mut Vec<PathBuf>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                walk(&path, found);
+            } else if path.extension().is_some_and(|e| e == "txt" || e == "mmd") {
+                found.push(path);
+            }
+        }
+    }
+    let mut paths = Vec::new();
+    walk(
+        &PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata"),
+        &mut paths,
+    );
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let content = fs::read_to_string(&path).unwrap();
+            let input = if path.extension().is_some_and(|e| e == "txt") {
+                parse_test_file(&content).map_or(content.clone(), |(input, _)| input)
+            } else {
+                let lines = content.lines().filter(|l| !l.trim_start().starts_with('#'));
+                lines.collect::<Vec<_>>().join("\n")
+            };
+            (path, input)
+        })
+        .collect()
+}
+
+/// Hash maps iterate in a different order on each run (and for each map), so
+/// rendering every fixture twice in one process catches output that depends
+/// on it
+#[test]
+fn fixtures_render_identically_twice() {
+    for (path, input) in fixture_inputs() {
+        let render = || {
+            let text = |use_ascii| {
+                let options = m2svg::AsciiRenderOptions {
+                    use_ascii,
+                    ..Default::default()
+                };
+                m2svg::render_mermaid_ascii(&input, Some(options))
+            };
+            let json = m2svg::parse_mermaid(&input).and_then(|p| p.to_json());
+            (text(true), text(false), m2svg::render_to_svg(&input), json)
+        };
+        assert_eq!(render(), render(), "{:?} renders differently twice", path);
+    }
+}
+
+/// A label long enough to need a canvas over the cell limit fails to render,
+/// and renders again once the limit is lifted
+#[test]
+fn canvas_over_cell_limit_is_too_large() {
+    let input = format!("graph TD\n  A[{}] --> B\n  B --> C", "x".repeat(2000));
+    let options = |max_canvas_cells| m2svg::AsciiRenderOptions {
+        limits: m2svg::RenderLimits {
+            max_canvas_cells,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let error = m2svg::render_mermaid_ascii(&input, Some(options(10_000))).unwrap_err();
+    assert!(
+        error.starts_with("Diagram too large: ") && error.ends_with("canvas cells (limit 10000)"),
+        "{}",
+        error
+    );
+    assert!(m2svg::render_mermaid_ascii(&input, Some(options(0))).is_ok());
+    assert!(m2svg::render_mermaid_ascii("graph TD\n  A --> B", Some(options(10_000))).is_ok());
+}
+
+/// The parser returns an error rather than panicking, on inputs that once
+/// made it panic and on each fixture cut off in the middle and at the end of
+/// every line. `cargo fuzz run parse_mermaid` (see fuzz/) searches further.
+#[test]
+fn parser_never_panics() {
+    let inputs = [
+        // `İ` takes a byte more lowercased, which put the `id:` value
+        // mid-character
+        "gitGraph\n  commit İ id:é".to_string(),
+        // More commits than there are single-byte ids
+        format!("gitGraph\n{}", "  commit\n".repeat(300)),
+    ];
+    for input in &inputs {
+        let _ = m2svg::parse_mermaid(input);
+    }
+    for (_, input) in fixture_inputs() {
+        let mut end = 0;
+        for line in input.split_inclusive('\n') {
+            let middle = (0..=line.len() / 2)
+                .rev()
+                .find(|&i| line.is_char_boundary(i))
+                .unwrap_or(0);
+            let _ = m2svg::parse_mermaid(&input[..end + middle]);
+            end += line.len();
+            let _ = m2svg::parse_mermaid(&input[..end]);
+        }
+    }
+}
+
+/// Classes that only a relationship names are declared by it, as in Mermaid;
+/// a note for a class the diagram doesn't have is still reported
+#[test]
+fn class_relationships_declare_their_classes() {
+    let input = "classDiagram\n  class Animal {\n    +name\n  }\n  Animal <|-- Dog\n  Dog --> Dog";
+    assert_eq!(m2svg::validate(input), Vec::new());
+
+    let diagnostics = m2svg::validate(&format!("{}\n  note for Ghost \"boo\"", input));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "7: warning: Undefined class Ghost in note"
+    );
+}
+
+/// Rendering with warnings draws the same diagram, and warns about what
+/// `m2svg check` reports
+#[test]
+fn render_with_warnings_matches_render_and_check() {
+    for (path, input) in fixture_inputs() {
+        let Ok(output) = m2svg::render_mermaid_ascii_with_warnings(&input, None) else {
+            assert!(m2svg::render_mermaid_ascii(&input, None).is_err());
+            continue;
+        };
+        assert_eq!(
+            Ok(output.content),
+            m2svg::render_mermaid_ascii(&input, None),
+            "{:?}",
+            path
+        );
+        assert_eq!(output.warnings, m2svg::validate(&input), "{:?}", path);
+    }
+}
+
+/// Each fixture written back out as Mermaid text parses to the same diagram,
+/// and writing that again gives the same text. State diagrams may list their
+/// states in another order, so only their text is compared.
+#[test]
+fn fixtures_round_trip_through_mermaid_text() {
+    let json = |parsed: &m2svg::ParsedDiagram| {
+        let mut frontmatter = parsed.frontmatter.clone();
+        frontmatter.raw_lines.clear();
+        (
+            serde_json::to_value(&parsed.diagram).unwrap(),
+            serde_json::to_value(&frontmatter).unwrap(),
+        )
+    };
+    for (path, input) in fixture_inputs() {
+        let Ok(parsed) = m2svg::parse_mermaid(&input) else {
+            continue;
+        };
+        let text = m2svg::to_mermaid(&parsed);
+        let reparsed = m2svg::parse_mermaid(&text)
+            .unwrap_or_else(|e| panic!("{:?} doesn't parse again: {}\n{}", path, e, text));
+        assert_eq!(m2svg::to_mermaid(&reparsed), text, "{:?}", path);
+        if m2svg::detect_diagram_type(&input) != m2svg::DiagramKind::State {
+            assert_eq!(json(&reparsed), json(&parsed), "{:?}\n{}", path, text);
+        }
+    }
+}
+
+/// Statements the fixtures don't use round trip as well
+#[test]
+fn written_mermaid_text_parses_to_the_same_diagram() {
+    let inputs = [
+        "---\ntitle: Checkout: flow\nconfig:\n  theme: dark\n  layout: elk\n  flowchart:\n    curve: basis\n    nodeSpacing: 80\n---\n\
+         graph LR\n  accTitle: Checkout\n  accDescr {\n    Two lines\n    of text\n  }\n\
+         A([Start]):e ==> B{{Check}} & C[(DB)]\n  C -.->|read| D>Flag]\n\
+         D <-->|a|b|c| E[/Up\\]:::warn\n  E x--x F[\\Down/]\n  F ----o G(((Done)))\n  H\n\
+         subgraph outer [Outer box]\n    direction TB\n    B\n    subgraph inner\n      C\n    end\n  end\n\
+         classDef warn fill:#f96,stroke:#333\n  class inner warn\n  style A fill:#fff\n\
+         click A \"https://example.com\" \"Home\" _blank",
+        "stateDiagram-v2\n  direction LR\n  [*] --> Idle\n  Idle --> Busy : start\n\
+         state \"Working hard\" as Busy {\n    [*] --> Step\n    Step --> [*]\n  }\n\
+         Busy --> [*]\n  Lonely\n  Done : All done",
+        "sequenceDiagram\n  participant A as Alice\n  actor B\n  Note over A,B: start\n\
+         loop every minute\n    A->>+B: ping\n    alt ok\n      B-->>-A: pong\n    else failed\n\
+         B--xA: error\n      Note right of B: retry\n    end\n  end\n  par one\n    A-)B: x\n\
+         and two\n    A->B: y\n  end\n  rect rgb(0, 0, 255)\n    B-->A: z\n  end",
+        "classDiagram\n  direction LR\n  class Shape~T~ {\n    <<interface>>\n    +area()$ double\n\
+         -List~int~ points\n    #name : String name$\n    +draw(int x, int y)*\n  }\n\
+         Shape \"1\" <|-- \"many\" Circle : is\n  Circle ..> Point\n  Circle --() Drawable\n\
+         Printable ()-- Shape\n  namespace Geometry {\n    class Point\n  }\n\
+         note for Circle \"round\"\n  note \"general\"",
+        "erDiagram\n  CUSTOMER }o--|| STORE : \"shops at\"\n  STORE {\n    int id PK, FK \"key\"\n\
+         string name UK\n  }\n  CUSTOMER |o..|{ ADDRESS : has\n  LONE {\n  }",
+        "---\nconfig:\n  theme: forest\n  gitGraph:\n    mainBranchName: trunk\n\
+         rotateCommitLabel: false\n  themeVariables:\n    git0: \"#ff0000\"\n---\n\
+         gitGraph TB:\n  commit id: \"root\" tag: \"v1\"\n  branch feature order: 1\n\
+         branch empty\n  checkout feature\n  commit type: HIGHLIGHT msg: \"fix: parser\"\n\
+         checkout trunk\n  commit type: REVERSE\n  merge feature tag: \"v2\"\n\
+         checkout empty\n  cherry-pick id: \"C\"",
+    ];
+    let json = |parsed: &m2svg::ParsedDiagram| {
+        let mut frontmatter = parsed.frontmatter.clone();
+        frontmatter.raw_lines.clear();
+        (
+            serde_json::to_value(&parsed.diagram).unwrap(),
+            serde_json::to_value(&frontmatter).unwrap(),
+        )
+    };
+    for input in inputs {
+        let parsed = m2svg::parse_mermaid(input).unwrap();
+        let text = m2svg::to_mermaid(&parsed);
+        let (reparsed, warnings) =
+            m2svg::parse_mermaid_with_mode(&text, m2svg::ParseMode::Lenient).unwrap();
+        assert!(warnings.is_empty(), "{:?}\n{}", warnings, text);
+        assert_eq!(m2svg::to_mermaid(&reparsed), text);
+        if m2svg::detect_diagram_type(input) != m2svg::DiagramKind::State {
+            assert_eq!(json(&reparsed), json(&parsed), "{}", text);
+        }
+    }
+}
+
+/// Node widths size the boxes of both flowchart layouts and of class diagrams
+#[test]
+fn node_width_sizes_svg_boxes() {
+    let widths = |input: &str, node_width| {
+        let options = m2svg::SvgRenderOptions {
+            node_width,
+            ..Default::default()
+        };
+        let svg = m2svg::render_mermaid_svg(input, Some(options)).unwrap();
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+        doc.descendants()
+            .filter(|n| n.has_tag_name("rect"))
+            .filter_map(|n| n.attribute("width")?.parse::<f64>().ok())
+            .collect::<Vec<_>>()
+    };
+    let uniform = m2svg::NodeWidth {
+        uniform: true,
+        ..Default::default()
+    };
+    let min = m2svg::NodeWidth {
+        min: 150,
+        ..Default::default()
+    };
+    for input in [
+        "graph TD\n  A[Start] --> B[Check the order]\n  A --> C[Ship]",
+        "---\nlayout: layered\n---\ngraph TD\n  A[Start] --> B[Check the order]\n  A --> C[Ship]",
+        "classDiagram\n  class Order {\n    +String id\n    +submit() bool\n  }\n  Order --> Item",
+    ] {
+        let natural = widths(input, Default::default());
+        let widest = natural.iter().copied().fold(0.0, f64::max);
+        assert!(natural.iter().any(|&w| w < widest), "{}", input);
+        assert!(
+            widths(input, uniform).iter().all(|&w| w == widest),
+            "{}",
+            input
+        );
+        let at_least = widths(input, min);
+        assert!(
+            at_least.iter().all(|&w| w >= 150.0),
+            "{}: {:?}",
+            input,
+            at_least
+        );
+    }
+}
+
+/// Collapsed member lists and hidden private members make class boxes shorter
+#[test]
+fn class_members_compact_svg_boxes() {
+    let input = "classDiagram\n  class Order {\n    -secret\n    +id\n    +total\n    +status\n    +pay()\n  }";
+    let render = |class_members| {
+        let options = m2svg::SvgRenderOptions {
+            class_members,
+            ..Default::default()
+        };
+        m2svg::render_mermaid_svg(input, Some(options)).unwrap()
+    };
+    let height = |svg: &str| {
+        let doc = roxmltree::Document::parse(svg).unwrap();
+        let height = doc.root_element().attribute("height").unwrap().to_string();
+        height.parse::<f64>().unwrap()
+    };
+
+    let full = render(m2svg::ClassMembers::default());
+    assert!(full.contains("secret") && full.contains("status"));
+
+    let compact = render(m2svg::ClassMembers {
+        max: 1,
+        hide_private: true,
+    });
+    assert!(!compact.contains("secret") && !compact.contains("status"));
+    assert!(compact.contains(">+id<") && compact.contains(">… (2 more)<"));
+    assert!(compact.contains("pay"));
+    assert!(height(&compact) < height(&full));
+}
+
+/// The label alignment anchors node label and class text at the side it names
+#[test]
+fn label_align_anchors_svg_text() {
+    let anchors = |input: &str, label_align| {
+        let options = m2svg::SvgRenderOptions {
+            label_align,
+            ..Default::default()
+        };
+        let svg = m2svg::render_mermaid_svg(input, Some(options)).unwrap();
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+        doc.descendants()
+            .filter(|n| n.has_tag_name("text"))
+            .map(|n| n.attribute("text-anchor").unwrap_or("start").to_string())
+            .collect::<Vec<_>>()
+    };
+    let flowcharts = [
+        "graph TD\n  A[Start] --> B[Ship]",
+        "---\nlayout: layered\n---\ngraph TD\n  A[Start] --> B{Ship?}",
+    ];
+    for input in flowcharts {
+        assert!(anchors(input, None).iter().all(|a| a == "middle"));
+        let right = anchors(input, Some(m2svg::LabelAlign::Right));
+        assert!(right.iter().all(|a| a == "end"), "{}: {:?}", input, right);
+    }
+
+    let class = "classDiagram\n  class Order {\n    +String id\n    +submit() bool\n  }";
+    assert_eq!(anchors(class, None), ["middle", "start", "start"]);
+    assert_eq!(
+        anchors(class, Some(m2svg::LabelAlign::Center)),
+        ["middle", "middle", "middle"]
+    );
+}
+
+/// The exported layout places nodes where the SVG draws them, with each edge
+/// running from its source's box to its target's
+#[test]
+fn layout_json_matches_svg_boxes() {
+    for input in [
+        "graph TD\n  subgraph s [Shop]\n    A[Start] --> B[Check the order]\n  end\n  B -->|ok| C[Ship]",
+        "---\nlayout: layered\n---\ngraph LR\n  subgraph s [Shop]\n    A[Start] --> B[Check the order]\n  end\n  B -->|ok| C[Ship]",
+    ] {
+        let json = m2svg::layout_to_json(input, None).unwrap();
+        let layout: m2svg::svg::PositionedGraph = serde_json::from_str(&json).unwrap();
+        let svg = m2svg::render_mermaid_svg(input, None).unwrap();
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+        let rects: Vec<[f64; 4]> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("rect"))
+            .filter_map(|n| {
+                let attr = |name| n.attribute(name)?.parse::<f64>().ok();
+                Some([attr("x")?, attr("y")?, attr("width")?, attr("height")?])
+            })
+            .collect();
+
+        assert_eq!(layout.nodes.len(), 3, "{}", input);
+        for node in &layout.nodes {
+            let rect = [node.x, node.y, node.width, node.height];
+            assert!(rects.contains(&rect), "{}: {} at {:?}", input, node.id, rect);
+        }
+        assert_eq!(layout.groups.len(), 1);
+        assert!(layout.groups[0].width.is_some());
+
+        let node = |id: &str| layout.nodes.iter().find(|n| n.id == id).unwrap();
+        let on_box = |p: &m2svg::svg::Point, n: &m2svg::svg::PositionedNode| {
+            (n.x - 1.0..=n.x + n.width + 1.0).contains(&p.x)
+                && (n.y - 1.0..=n.y + n.height + 1.0).contains(&p.y)
+        };
+        assert_eq!(layout.edges.len(), 2);
+        for edge in &layout.edges {
+            let (first, last) = (&edge.points[0], edge.points.last().unwrap());
+            assert!(on_box(first, node(&edge.source)), "{}: {:?}", input, edge);
+            assert!(on_box(last, node(&edge.target)), "{}: {:?}", input, edge);
+        }
+        assert!(layout.edges[1].label_position.is_some());
+    }
+
+    let error = m2svg::layout_to_json("sequenceDiagram\n  A->>B: hi", None).unwrap_err();
+    assert_eq!(error, "Only flowcharts can be laid out to JSON");
+}
+
+/// Node links with a script scheme are dropped from full SVG documents too,
+/// in both flowchart layouts, while web and relative links are kept
+#[test]
+fn svg_links_only_allow_safe_schemes() {
+    for layout in ["", "---\nlayout: layered\n---\n"] {
+        let input = format!(
+            "{}graph LR\n  A --> B --> C\n  click A \"javascript:alert(document.cookie)\"\n  \
+             click B \" JavaScript:alert(1)\"\n  click C \"docs/c.html\"",
+            layout
+        );
+        let svg = m2svg::render_mermaid_svg(&input, None).unwrap();
+        assert!(!svg.to_lowercase().contains("javascript:"), "{}", svg);
+        assert!(svg.contains(r#"<a href="docs/c.html">"#), "{}", svg);
+        assert_eq!(svg.matches("<a ").count(), 1, "{}", svg);
+        assert!(
+            svg.contains(">A</text>") && svg.contains(">B</text>"),
+            "{}",
+            svg
+        );
+    }
+}
+
+/// Fragments have no prolog or stylesheet, and only ids of their namespace,
+/// which their references all point to
+#[test]
+fn svg_fragments_are_namespaced_and_self_contained() {
+    let options = m2svg::SvgRenderOptions {
+        fragment_namespace: Some("doc 1".to_string()),
+        ..Default::default()
+    };
+    for input in [
+        "---\ntitle: Orders\n---\ngraph LR\n  A --> B\n  A <--> C\n  click A \"javascript:alert(1)\"",
+        "---\nlayout: layered\n---\ngraph TD\n  A --o B",
+        "sequenceDiagram\n  accDescr: Greeting\n  A->>B: hi",
+        "erDiagram\n  CUSTOMER ||--o{ ORDER : places",
+        "gitGraph\n  commit\n  branch dev\n  commit",
+    ] {
+        let svg = m2svg::render_mermaid_svg(input, Some(options.clone())).unwrap();
+        assert!(svg.starts_with("<svg"), "{}", input);
+        assert!(!svg.contains("var(--"), "{}", input);
+        assert!(!svg.contains("javascript:"), "{}", input);
+
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+        assert!(!doc.descendants().any(|n| n.has_tag_name("style")));
+        let ids: Vec<&str> = doc
+            .descendants()
+            .filter_map(|n| n.attribute("id"))
+            .collect();
+        assert!(ids.iter().all(|id| id.starts_with("doc-1-")), "{:?}", ids);
+        for node in doc.descendants() {
+            for attr in node.attributes() {
+                let mut rest = attr.value();
+                while let Some(start) = rest.find("url(#") {
+                    let id = &rest[start + 5..];
+                    let id = &id[..id.find(')').unwrap()];
+                    assert!(ids.contains(&id), "{}: {} not found", input, id);
+                    rest = &rest[start + 5..];
+                }
+            }
+        }
+    }
+}
+
+/// The size options set the root's width and height, keeping the viewBox
+#[test]
+fn svg_size_sets_root_dimensions() {
+    use m2svg::svg::SvgSize;
+
+    let root = |size| {
+        let options = m2svg::SvgRenderOptions {
+            size,
+            ..Default::default()
+        };
+        let svg = m2svg::render_mermaid_svg("graph LR\n  A --> B", Some(options)).unwrap();
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+        let root = doc.root_element();
+        let attr = |name| root.attribute(name).map(str::to_string);
+        (attr("viewBox").unwrap(), attr("width"), attr("height"))
+    };
+    let (view_box, width, height) = root(SvgSize::Natural);
+    assert_eq!(view_box, "0 0 136 120");
+    assert_eq!(
+        (width.as_deref(), height.as_deref()),
+        (Some("136"), Some("120"))
+    );
+
+    let fixed = |width, height| SvgSize::Fixed { width, height };
+    let cases = [
+        (SvgSize::Responsive, Some("100%"), None),
+        (fixed(Some(272.0), None), Some("272"), Some("240")),
+        (fixed(None, Some(60.0)), Some("68"), Some("60")),
+        (fixed(Some(100.0), Some(100.0)), Some("100"), Some("100")),
+        (SvgSize::MaxWidth(68.0), Some("68"), Some("60")),
+        (SvgSize::MaxWidth(500.0), Some("136"), Some("120")),
+    ];
+    for (size, width, height) in cases {
+        let (box_, w, h) = root(size);
+        assert_eq!(box_, view_box);
+        assert_eq!((w.as_deref(), h.as_deref()), (width, height), "{:?}", size);
+    }
+}
+
+#[test]
+fn svg_fonts_can_be_system_or_embedded() {
+    use m2svg::svg::{FontSource, SYSTEM_FONT_STACK};
+
+    let diagrams = [
+        "graph LR\n  A --> B",
+        "sequenceDiagram\n  A->>B: hi",
+        "classDiagram\n  class A",
+        "erDiagram\n  A ||--o{ B : has",
+        "gitGraph\n  commit",
+    ];
+    let renderer = m2svg::Renderer::new();
+    for input in diagrams {
+        let parsed = m2svg::parse_mermaid(input).unwrap();
+        let render = |font_source| {
+            let options = m2svg::SvgRenderOptions {
+                font_source,
+                ..Default::default()
+            };
+            renderer.render_svg(&parsed, Some(options))
+        };
+        let web = render(FontSource::Web);
+        assert!(!web.contains(SYSTEM_FONT_STACK), "{}", input);
+
+        let system = render(FontSource::System);
+        assert!(!system.contains("@import"), "{}", input);
+        assert!(system.contains(&format!("'Inter', {}", SYSTEM_FONT_STACK)));
+
+        // A WOFF2 file is embedded as it is
+        let font = FontSource::embed(b"wOF2 font".to_vec()).unwrap();
+        let embedded = render(font);
+        assert!(!embedded.contains("@import"), "{}", input);
+        assert!(embedded.contains(
+            "@font-face { font-family: 'Inter'; src: url(data:font/woff2;base64,d09GMiBmb250) format('woff2'); }"
+        ));
+        roxmltree::Document::parse(&embedded).unwrap();
+    }
+    assert!(FontSource::embed(b"not a font".to_vec()).is_err());
+}
+
+#[test]
+fn svg_prefers_dark_adds_the_dark_palette() {
+    let render = |input: &str, prefers_dark, fragment_namespace| {
+        let options = m2svg::SvgRenderOptions {
+            prefers_dark,
+            fragment_namespace,
+            ..Default::default()
+        };
+        m2svg::render_mermaid_svg(input, Some(options)).unwrap()
+    };
+    let media = "@media (prefers-color-scheme: dark)";
+    let flowchart = "graph LR\n  A --> B";
+
+    assert!(!render(flowchart, false, None).contains(media));
+    let svg = render(flowchart, true, None);
+    assert!(svg.contains(media));
+    assert!(svg.contains("--bg:#333333 !important;"));
+    assert!(svg.contains("--bg:#FFFFFF;"));
+    roxmltree::Document::parse(&svg).unwrap();
+
+    // Every diagram colors its elements through the variables the block sets
+    let diagrams = [
+        "sequenceDiagram\n  A->>B: hi",
+        "classDiagram\n  class A",
+        "erDiagram\n  A ||--o{ B : has",
+        "gitGraph\n  commit",
+    ];
+    for input in diagrams {
+        let svg = render(input, true, None);
+        assert!(svg.contains(media), "{}", input);
+        assert!(svg.contains("--bg:#FFFFFF;"), "{}", input);
+        assert!(!svg.contains("fill=\"#333333\""), "{}", input);
+    }
+
+    // Nothing to switch to in dark diagrams and fragments
+    let dark = "---\ntheme: dark\n---\ngraph LR\n  A --> B";
+    assert!(!render(dark, true, None).contains(media));
+    assert!(!render(flowchart, true, Some("a".to_string())).contains(media));
+}
+
+/// `activate` / `deactivate` statements count towards the activation depth
+/// of the messages after them, as `+` / `-` marks on messages do, and are
+/// written back as statements
+#[test]
+fn sequence_activation_statements() {
+    let input = "sequenceDiagram
+    Alice->>Bob: Hi
+    activate Bob
+    Bob->>+Carol: Ask
+    activate Carol
+    Carol->>Bob: Answer
+    deactivate Carol
+    Carol-->>-Bob: Done
+    Bob->>Alice: Reply
+    deactivate Bob
+    Bob->>Alice: Bye";
+    let svg = m2svg::render_mermaid_svg(input, None).unwrap();
+    let depths: Vec<&str> = svg
+        .split("data-activation-depth=\"")
+        .skip(1)
+        .map(|rest| &rest[..rest.find('"').unwrap()])
+        .collect();
+    assert_eq!(depths, ["0", "1", "2", "1", "1", "0"]);
+
+    let emitted = m2svg::to_mermaid(&m2svg::parse_mermaid(input).unwrap());
+    assert!(emitted.contains("    activate Bob\n    Bob->>+Carol: Ask\n    activate Carol\n"));
+    assert!(emitted.contains("    deactivate Bob\n    Bob->>Alice: Bye"));
+}
+
+#[test]
+fn sequence_spacing_from_frontmatter_and_options() {
+    let measure = |input: &str, sequence| {
+        let options = m2svg::SvgRenderOptions {
+            sequence,
+            ..Default::default()
+        };
+        let svg = m2svg::render_mermaid_svg(input, Some(options)).unwrap();
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+        let boxes = doc
+            .descendants()
+            .filter(|n| n.attribute("class") == Some("node"))
+            .count();
+        let height: f64 = doc
+            .root_element()
+            .attribute("height")
+            .unwrap()
+            .parse()
+            .unwrap();
+        (height, boxes)
+    };
+    let diagram = "sequenceDiagram\n  A->>B: one\n  B->>A: two";
+    let (height, boxes) = measure(diagram, m2svg::SequenceConfig::default());
+    assert_eq!((height, boxes), (270.0, 4));
+
+    let compact = m2svg::SequenceConfig {
+        message_margin: Some(30.0),
+        mirror_actors: Some(false),
+        ..Default::default()
+    };
+    assert_eq!(measure(diagram, compact), (170.0, 2));
+
+    // The frontmatter wins over the options
+    let configured =
+        "---\nconfig:\n  sequence:\n    messageMargin: 40\n    mirrorActors: true\n---\n";
+    let input = format!("{}{}", configured, diagram);
+    assert_eq!(measure(&input, compact), (240.0, 4));
+    assert!(m2svg::validate(&input).is_empty());
+}
+
+/// `has_arrow_start()` and `has_arrow_end()` are true for arrowheads only,
+/// not for circle and cross ends
+#[test]
+fn edge_arrow_accessors() {
+    let parsed = m2svg::parse_mermaid("graph LR\n  A <--> B\n  B o--x C\n  C --- D").unwrap();
+    let m2svg::DiagramType::Flowchart(graph) = parsed.diagram else {
+        panic!("expected a flowchart");
+    };
+    let arrows: Vec<(bool, bool)> = graph
+        .edges
+        .iter()
+        .map(|e| (e.has_arrow_start(), e.has_arrow_end()))
+        .collect();
+    assert_eq!(arrows, [(true, true), (false, false), (false, false)]);
+}
+
+/// Node ids are read before the arrow, so an id ending in `o` or `x` keeps
+/// its last letter instead of lending it to the arrow as a start marker
+#[test]
+fn edge_markers_next_to_ids_ending_in_o_or_x() {
+    use m2svg::EdgeMarker::{Arrow, Circle, Cross, None};
+    let cases = [
+        ("helloo--oB", ("helloo", "B"), (None, Circle)),
+        ("box--xB", ("box", "B"), (None, Cross)),
+        ("xo-->ox", ("xo", "ox"), (None, Arrow)),
+        ("hello o--o B", ("hello", "B"), (Circle, Circle)),
+        ("helloo o--o B", ("helloo", "B"), (Circle, Circle)),
+        ("io x--x xi", ("io", "xi"), (Cross, Cross)),
+        ("A[Go]o--oB", ("A", "B"), (Circle, Circle)),
+    ];
+    for (line, ends, markers) in cases {
+        let parsed = m2svg::parse_mermaid(&format!("graph LR\n  {}", line)).unwrap();
+        let m2svg::DiagramType::Flowchart(graph) = parsed.diagram else {
+            panic!("expected a flowchart");
+        };
+        assert_eq!(graph.edges.len(), 1, "{}", line);
+        let edge = &graph.edges[0];
+        assert_eq!(
+            (edge.source.as_str(), edge.target.as_str()),
+            ends,
+            "{}",
+            line
+        );
+        assert_eq!((edge.start_marker, edge.end_marker), markers, "{}", line);
+    }
+}
+
+/// The render server skips a message body over its size limit, answers it
+/// with an error and keeps serving the next message
+#[test]
+fn server_rejects_oversized_messages() {
+    let big = format!("{{\"id\": 1, \"input\": \"{}\"}}", "x".repeat(2 << 20));
+    let body = r#"{"id": 2, "input": "graph LR\n  A --> B", "format": "ascii"}"#;
+    let input = format!(
+        "Content-Length: {}\r\n\r\n{}Content-Length: {}\r\n\r\n{}",
+        big.len(),
+        big,
+        body.len(),
+        body
+    );
+    let mut output = Vec::new();
+    m2svg::server::serve(input.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains(r#"{"error":"Message body is too large"#));
+    assert!(output.contains(r#""id":2"#));
+    assert!(output.contains("+---+"));
+}
+
+/// Requests naming a document render from its parse session: edits to
+/// comments give the last result back, other edits render again
+#[test]
+fn server_renders_documents_from_sessions() {
+    let request = |id: u32, input: &str| {
+        let body = serde_json::json!({ "id": id, "input": input, "document": "a.mmd" }).to_string();
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    };
+    let input = [
+        request(1, "graph LR\n  A --> B"),
+        request(2, "graph LR\n  %% a comment\n  A --> B"),
+        request(3, "graph LR\n  %% a comment\n  A --> B[Billing]"),
+    ]
+    .concat();
+    let mut output = Vec::new();
+    m2svg::server::serve(input.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let outputs: Vec<String> = output
+        .split("Content-Length: ")
+        .filter_map(|frame| frame.split_once("\r\n\r\n"))
+        .map(|(_, body)| {
+            let response: serde_json::Value = serde_json::from_str(body).unwrap();
+            response["output"].as_str().unwrap().to_string()
+        })
+        .collect();
+    assert_eq!(outputs.len(), 3);
+    assert_eq!(outputs[0], outputs[1]);
+    assert!(outputs[2].contains("Billing"));
+}
+
+/// JSON options take SVG sizes only as positive numbers, and the node limit
+/// the same way for both output kinds
+#[test]
+fn json_options_check_sizes_and_limits() {
+    let respond = |format: &str, options: &str| {
+        let body = format!(
+            r#"{{"input": "graph LR\n  A --> B", "format": "{}", "options": {}}}"#,
+            format, options
+        );
+        let input = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        let mut output = Vec::new();
+        m2svg::server::serve(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+    for (options, name) in [
+        (r#"{"width": 0}"#, "width"),
+        (r#"{"height": -20}"#, "height"),
+        (r#"{"maxWidth": -1.5}"#, "maxWidth"),
+    ] {
+        let response = respond("svg", options);
+        let error = format!("Invalid options: {} must be a positive number", name);
+        assert!(response.contains(&error), "{}", response);
+    }
+    assert!(respond("svg", r#"{"width": 640}"#).contains(r#"width=\"640\""#));
+
+    for format in ["svg", "ascii"] {
+        let response = respond(format, r#"{"maxNodes": 1}"#);
+        assert!(response.contains(r#""error":"#), "{}", response);
+        assert!(respond(format, r#"{"maxNodes": 2}"#).contains(r#""output":"#));
+    }
+}